@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use tokio::process::Command;
+
+use super::{Agent, AgentProcess, Capabilities};
+
+/// Aider CLI agent backend.
+///
+/// Invokes: `aider --message-file <file> --yes-always --no-pretty`
+/// The prompt goes through a file rather than `--message` to avoid hitting the
+/// OS ARG_MAX limit. `--yes-always` answers every confirmation (non-interactive).
+///
+/// Aider commits its own edits, so `manages_own_commits` tells the orchestrator
+/// not to commit on top of them.
+pub struct AiderAgent {
+    model: Option<String>,
+    message_file: PathBuf,
+    /// The `--flags` of the last run's command line.
+    flags: Mutex<Vec<String>>,
+}
+
+/// Distinguishes the message files of agents in one process (`ralph watch`).
+static NEXT_MESSAGE_FILE: AtomicU32 = AtomicU32::new(0);
+
+impl AiderAgent {
+    pub fn new(model: Option<String>) -> Self {
+        let message_file = std::env::temp_dir().join(format!(
+            "ralph-aider-message-{}-{}.md",
+            std::process::id(),
+            NEXT_MESSAGE_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            model,
+            message_file,
+            flags: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Drop for AiderAgent {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.message_file);
+    }
+}
+
+impl Agent for AiderAgent {
+    fn name(&self) -> &str {
+        "aider"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            manages_own_commits: true,
+            supports_model_override: true,
+            ..Capabilities::default()
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        super::check_binary_available("aider")
+    }
+
+    fn passed_flags(&self) -> Vec<String> {
+        self.flags.lock().unwrap().clone()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        std::fs::write(&self.message_file, prompt).context("Failed to write aider message file")?;
+
+        let mut cmd = Command::new("aider");
+        cmd.arg("--message-file")
+            .arg(&self.message_file)
+            .arg("--yes-always")
+            .arg("--no-pretty");
+
+        if let Some(ref model) = self.model {
+            cmd.arg("--model").arg(model);
+        }
+
+        *self.flags.lock().unwrap() = super::flags_of(&cmd);
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
+        cmd.current_dir(workdir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let child = cmd
+            .spawn()
+            .context("Failed to spawn aider — is it installed and on PATH?")?;
+
+        Ok(child.into())
+    }
+}
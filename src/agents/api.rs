@@ -1,27 +1,124 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
 use tokio::process::Command;
 
-use super::{Agent, AgentProcess};
+use super::{Agent, AgentProcess, Capabilities, Sampling, TokenUsage, UsageReport};
 
-/// API-based agent that calls the Anthropic Messages API directly via curl.
+/// API-based agent that calls a model API directly over HTTP.
 ///
-/// Works with:
+/// With `--api-protocol anthropic` (the default) it speaks the Anthropic
+/// Messages API and works with:
 /// - The real Anthropic API (`https://api.anthropic.com`)
 /// - opencode-claude-max-proxy (`http://localhost:3456`)
 /// - Any Anthropic-compatible endpoint
 ///
-/// Uses streaming SSE so Ralph can still detect stalls and completion tokens
-/// from the curl stdout, just like CLI agents.
+/// With `--api-protocol openai` it speaks OpenAI Chat Completions instead, for
+/// vLLM, LiteLLM, OpenRouter, Groq, and other OpenAI-compatible endpoints. The
+/// base URL includes the version (`https://api.openai.com/v1`).
+///
+/// The model works through tools — `read_file`, `write_file`, and
+/// `run_command`, confined to the working directory — in a loop that sends
+/// the tool results back until it answers without calling one. The loop runs
+/// on a tokio task that streams SSE and writes the text deltas, a `tool: …`
+/// line per call, and command output to the agent's stdout (errors to
+/// stderr), so Ralph detects stalls and completion tokens just like with CLI
+/// agents. The API key only ever travels in request headers, never in
+/// process arguments.
+///
+/// `--temperature` is sent as-is; `--reasoning-effort` enables extended
+/// thinking with a matching token budget. The API rejects the two together.
+///
+/// The usage-bearing events of every turn (`message_start` / `message_delta`,
+/// or the final OpenAI chunk with `usage`) are kept so `take_usage` can report
+/// exact token counts for the run.
 pub struct ApiAgent {
+    protocol: ApiProtocol,
     base_url: String,
     api_key: String,
     model: String,
+    sampling: Sampling,
+    client: reqwest::Client,
+    /// Usage-bearing events of the last run, one JSON object per line.
+    usage_events: Arc<Mutex<String>>,
+}
+
+/// Wire protocols accepted by `--api-protocol`.
+pub const API_PROTOCOLS: &[&str] = &["anthropic", "openai"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ApiProtocol {
+    Anthropic,
+    OpenAi,
 }
 
+impl ApiProtocol {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "anthropic" => Ok(ApiProtocol::Anthropic),
+            "openai" => Ok(ApiProtocol::OpenAi),
+            other => anyhow::bail!(
+                "Unknown API protocol '{other}'. Supported: {}",
+                API_PROTOCOLS.join(", ")
+            ),
+        }
+    }
+}
+
+/// Output token budget on top of any thinking budget.
+const MAX_OUTPUT_TOKENS: u32 = 16384;
+
+/// Tool-use round trips allowed in one run.
+const MAX_TURNS: usize = 100;
+
+/// Bytes of a file or command output returned to the model in one tool result.
+const MAX_TOOL_OUTPUT: usize = 50_000;
+
+const SYSTEM_PROMPT: &str = "You are a software engineer working in a code repository. \
+Use the tools to read files, write files, and run shell commands; paths are relative to the \
+repository root. Make the changes by writing files — describing them is not enough — and \
+keep going until the task is done, then reply with a short summary.";
+
+/// USD per million (input, output) tokens, matched by model-name prefix (most
+/// specific first). Cache writes bill at 1.25× input, cache reads at 0.1×.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+];
+
 impl ApiAgent {
-    pub fn new(base_url: Option<String>, api_key: Option<String>, model: Option<String>) -> Result<Self> {
+    pub fn new(
+        base_url: Option<String>,
+        api_key: Option<String>,
+        model: Option<String>,
+        protocol: &str,
+        sampling: Sampling,
+    ) -> Result<Self> {
+        let protocol = ApiProtocol::parse(protocol)?;
+        if protocol == ApiProtocol::OpenAi {
+            return Self::new_openai(base_url, api_key, model, sampling);
+        }
+
+        if let Some(t) = sampling.temperature {
+            if !(0.0..=1.0).contains(&t) {
+                anyhow::bail!("--temperature must be between 0 and 1 for the API agent (got {t})");
+            }
+            if sampling.reasoning_effort.is_some() {
+                anyhow::bail!(
+                    "The API agent cannot combine --temperature with --reasoning-effort \
+                     (extended thinking requires the default temperature)"
+                );
+            }
+        }
+
         let api_key = api_key
             .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
             .context(
@@ -36,96 +133,807 @@ impl ApiAgent {
         let model = model.unwrap_or_else(|| "claude-sonnet-4-20250514".to_string());
 
         Ok(Self {
+            protocol,
             base_url,
             api_key,
             model,
+            sampling,
+            client: http_client()?,
+            usage_events: Arc::default(),
         })
     }
+
+    fn new_openai(
+        base_url: Option<String>,
+        api_key: Option<String>,
+        model: Option<String>,
+        sampling: Sampling,
+    ) -> Result<Self> {
+        if let Some(t) = sampling.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                anyhow::bail!(
+                    "--temperature must be between 0 and 2 for the OpenAI protocol (got {t})"
+                );
+            }
+        }
+
+        // Self-hosted servers (vLLM, LiteLLM) often need no key at all.
+        let api_key = api_key
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+            .unwrap_or_default();
+        let base_url = base_url
+            .or_else(|| std::env::var("OPENAI_BASE_URL").ok())
+            .unwrap_or_else(|| "https://api.openai.com/v1".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let model = model.unwrap_or_else(|| "gpt-4o".to_string());
+
+        Ok(Self {
+            protocol: ApiProtocol::OpenAi,
+            base_url,
+            api_key,
+            model,
+            sampling,
+            client: http_client()?,
+            usage_events: Arc::default(),
+        })
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .build()
+        .context("Failed to create HTTP client for API agent")
+}
+
+/// Token counts from the usage-bearing SSE events of a run, summed over its
+/// responses (one per tool-use turn). `message_start` opens a response and
+/// carries its input side; `message_delta` the running output total (and, on
+/// newer API versions, final input counts).
+fn parse_usage(events: &str) -> Option<TokenUsage> {
+    let mut responses: Vec<TokenUsage> = Vec::new();
+    for line in events.lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let fields = match event["type"].as_str() {
+            Some("message_start") => {
+                responses.push(TokenUsage::default());
+                &event["message"]["usage"]
+            }
+            Some("message_delta") => &event["usage"],
+            _ => continue,
+        };
+        if responses.is_empty() {
+            responses.push(TokenUsage::default());
+        }
+        let totals = responses.last_mut().expect("a response was opened");
+        let set = |slot: &mut u64, key: &str| {
+            if let Some(n) = fields[key].as_u64() {
+                *slot = n;
+            }
+        };
+        set(&mut totals.input_tokens, "input_tokens");
+        set(&mut totals.output_tokens, "output_tokens");
+        set(
+            &mut totals.cache_creation_input_tokens,
+            "cache_creation_input_tokens",
+        );
+        set(
+            &mut totals.cache_read_input_tokens,
+            "cache_read_input_tokens",
+        );
+    }
+    if responses.is_empty() {
+        return None;
+    }
+    let mut total = TokenUsage::default();
+    for usage in &responses {
+        total.add(usage);
+    }
+    Some(total)
+}
+
+/// Token counts from the final chunk of each OpenAI stream in a run, sent
+/// when the request asks for `stream_options.include_usage`. Cached prompt
+/// tokens are split out of `prompt_tokens`.
+fn parse_openai_usage(events: &str) -> Option<TokenUsage> {
+    let mut total: Option<TokenUsage> = None;
+    for line in events.lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let usage = &event["usage"];
+        let Some(prompt) = usage["prompt_tokens"].as_u64() else {
+            continue;
+        };
+        let cached = usage["prompt_tokens_details"]["cached_tokens"]
+            .as_u64()
+            .unwrap_or(0)
+            .min(prompt);
+        total
+            .get_or_insert_with(TokenUsage::default)
+            .add(&TokenUsage {
+                input_tokens: prompt - cached,
+                output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: cached,
+                unsplit_tokens: 0,
+            });
+    }
+    total
+}
+
+/// Cost of `usage` on `model`, if its pricing is known.
+fn cost_usd(model: &str, usage: &TokenUsage) -> Option<f64> {
+    let &(_, input, output) = PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))?;
+    let input_equiv = usage.input_tokens as f64
+        + usage.cache_creation_input_tokens as f64 * 1.25
+        + usage.cache_read_input_tokens as f64 * 0.1;
+    Some((input_equiv * input + usage.output_tokens as f64 * output) / 1_000_000.0)
+}
+
+impl ApiAgent {
+    fn apply_sampling(&self, body: &mut serde_json::Value) {
+        if let Some(t) = self.sampling.temperature {
+            body["temperature"] = serde_json::json!(t);
+        }
+        if self.protocol == ApiProtocol::OpenAi {
+            // Reasoning models take the level as-is; others reject the field,
+            // so it is only sent when asked for.
+            if let Some(ref effort) = self.sampling.reasoning_effort {
+                body["reasoning_effort"] = serde_json::json!(effort);
+            }
+            return;
+        }
+        if let Some(budget) = self.sampling.thinking_budget() {
+            body["max_tokens"] = serde_json::json!(MAX_OUTPUT_TOKENS + budget);
+            body["thinking"] = serde_json::json!({ "type": "enabled", "budget_tokens": budget });
+        }
+    }
+
+    /// Request body shared by every turn of a run: model, sampling, system
+    /// prompt, and tools. The conversation is added per turn.
+    fn base_body(&self) -> Value {
+        let mut body = match self.protocol {
+            ApiProtocol::Anthropic => json!({
+                "model": self.model,
+                "max_tokens": MAX_OUTPUT_TOKENS,
+                "stream": true,
+                "system": SYSTEM_PROMPT,
+                "tools": tool_specs(self.protocol),
+            }),
+            ApiProtocol::OpenAi => json!({
+                "model": self.model,
+                "stream": true,
+                "stream_options": { "include_usage": true },
+                "tools": tool_specs(self.protocol),
+            }),
+        };
+        self.apply_sampling(&mut body);
+        body
+    }
+}
+
+/// Tools the model can call: name, description, and input schema.
+fn tools() -> [(&'static str, &'static str, Value); 3] {
+    let path = json!({ "type": "string", "description": "Path relative to the repository root" });
+    [
+        (
+            "read_file",
+            "Read a text file.",
+            json!({ "type": "object", "properties": { "path": path }, "required": ["path"] }),
+        ),
+        (
+            "write_file",
+            "Create or overwrite a file with the given content. Parent directories are created.",
+            json!({
+                "type": "object",
+                "properties": { "path": path, "content": { "type": "string" } },
+                "required": ["path", "content"],
+            }),
+        ),
+        (
+            "run_command",
+            "Run a shell command in the repository root and return its exit code and combined output.",
+            json!({
+                "type": "object",
+                "properties": { "command": { "type": "string" } },
+                "required": ["command"],
+            }),
+        ),
+    ]
+}
+
+/// `tools()` in the request format of `protocol`.
+fn tool_specs(protocol: ApiProtocol) -> Value {
+    tools()
+        .into_iter()
+        .map(|(name, description, schema)| match protocol {
+            ApiProtocol::Anthropic => json!({
+                "name": name,
+                "description": description,
+                "input_schema": schema,
+            }),
+            ApiProtocol::OpenAi => json!({
+                "type": "function",
+                "function": { "name": name, "description": description, "parameters": schema },
+            }),
+        })
+        .collect()
+}
+
+/// What one `data:` payload of the stream carries.
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+    Text(String),
+    Error(String),
+    Usage,
+    Other,
+}
+
+/// A tool call made by the model.
+#[derive(Debug, Default, Clone)]
+struct ToolCall {
+    id: String,
+    name: String,
+    /// Input as JSON text, as it was streamed.
+    arguments: String,
+}
+
+impl ToolCall {
+    /// One-line description for the agent output, e.g. `write_file src/app.rs`.
+    fn summary(&self) -> String {
+        let input: Value = serde_json::from_str(&self.arguments).unwrap_or_default();
+        let target = ["path", "command"]
+            .iter()
+            .find_map(|key| input[key].as_str())
+            .and_then(|s| s.lines().next())
+            .unwrap_or_default();
+        let target: String = target.chars().take(120).collect();
+        format!("{} {target}", self.name).trim_end().to_string()
+    }
+}
+
+/// One assistant reply, assembled from its stream events.
+#[derive(Debug, Default)]
+struct Reply {
+    text: String,
+    /// Anthropic content blocks by index: text, thinking (sent back with its
+    /// signature, as the API requires), and tool_use with its input still in
+    /// pieces under `partial_json`.
+    blocks: Vec<Value>,
+    /// OpenAI tool calls by index.
+    calls: Vec<ToolCall>,
+}
+
+impl Reply {
+    /// Fold in one SSE `data:` payload. Anthropic sends
+    ///   {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}
+    /// and OpenAI
+    ///   {"id":"…","choices":[{"index":0,"delta":{"content":"Hello"}}]}
+    /// ending with a chunk carrying `usage`, then `[DONE]`. Tool calls arrive
+    /// the same way, their input JSON split over several deltas.
+    fn apply(&mut self, protocol: ApiProtocol, data: &str) -> StreamEvent {
+        let Ok(event) = serde_json::from_str::<Value>(data) else {
+            return StreamEvent::Other;
+        };
+        if let Some(message) = event["error"]["message"].as_str() {
+            return StreamEvent::Error(message.to_string());
+        }
+        match protocol {
+            ApiProtocol::Anthropic => self.apply_anthropic(&event),
+            ApiProtocol::OpenAi => self.apply_openai(&event),
+        }
+    }
+
+    fn apply_anthropic(&mut self, event: &Value) -> StreamEvent {
+        let index = event["index"].as_u64().unwrap_or(0) as usize;
+        if self.blocks.len() <= index {
+            self.blocks.resize(index + 1, Value::Null);
+        }
+        let delta = &event["delta"];
+        let (field, piece) = match (event["type"].as_str(), delta["type"].as_str()) {
+            (Some("message_start" | "message_delta"), _) => return StreamEvent::Usage,
+            (Some("content_block_start"), _) => {
+                self.blocks[index] = event["content_block"].clone();
+                return StreamEvent::Other;
+            }
+            (Some("content_block_delta"), Some("text_delta")) => ("text", &delta["text"]),
+            (Some("content_block_delta"), Some("thinking_delta")) => {
+                ("thinking", &delta["thinking"])
+            }
+            (Some("content_block_delta"), Some("input_json_delta")) => {
+                ("partial_json", &delta["partial_json"])
+            }
+            (Some("content_block_delta"), Some("signature_delta")) => {
+                self.blocks[index]["signature"] = delta["signature"].clone();
+                return StreamEvent::Other;
+            }
+            _ => return StreamEvent::Other,
+        };
+        let piece = piece.as_str().unwrap_or_default();
+        let block = &mut self.blocks[index];
+        if block.is_null() {
+            // Some proxies skip `content_block_start` for plain text.
+            *block = json!({ "type": "text" });
+        }
+        match &mut block[field] {
+            Value::String(text) => text.push_str(piece),
+            slot => *slot = Value::String(piece.to_string()),
+        }
+        if field != "text" || piece.is_empty() {
+            return StreamEvent::Other;
+        }
+        self.text.push_str(piece);
+        StreamEvent::Text(piece.to_string())
+    }
+
+    fn apply_openai(&mut self, event: &Value) -> StreamEvent {
+        if event["usage"].is_object() {
+            return StreamEvent::Usage;
+        }
+        let delta = &event["choices"][0]["delta"];
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0) as usize;
+            if self.calls.len() <= index {
+                self.calls.resize_with(index + 1, ToolCall::default);
+            }
+            let slot = &mut self.calls[index];
+            if let Some(id) = call["id"].as_str() {
+                slot.id = id.to_string();
+            }
+            let function = &call["function"];
+            slot.name
+                .push_str(function["name"].as_str().unwrap_or_default());
+            slot.arguments
+                .push_str(function["arguments"].as_str().unwrap_or_default());
+        }
+        match delta["content"].as_str() {
+            Some(text) if !text.is_empty() => {
+                self.text.push_str(text);
+                StreamEvent::Text(text.to_string())
+            }
+            _ => StreamEvent::Other,
+        }
+    }
+
+    /// The assistant message to add to the conversation, and the tools it calls.
+    fn finish(self, protocol: ApiProtocol) -> (Value, Vec<ToolCall>) {
+        match protocol {
+            ApiProtocol::Anthropic => {
+                let mut calls = Vec::new();
+                let mut content = Vec::new();
+                for mut block in self.blocks.into_iter().filter(|b| !b.is_null()) {
+                    if block["type"] == "tool_use" {
+                        let arguments = block
+                            .as_object_mut()
+                            .and_then(|b| b.remove("partial_json"))
+                            .and_then(|v| v.as_str().map(str::to_string))
+                            .unwrap_or_default();
+                        block["input"] = serde_json::from_str(&arguments).unwrap_or(json!({}));
+                        calls.push(ToolCall {
+                            id: block["id"].as_str().unwrap_or_default().to_string(),
+                            name: block["name"].as_str().unwrap_or_default().to_string(),
+                            arguments,
+                        });
+                    }
+                    content.push(block);
+                }
+                (json!({ "role": "assistant", "content": content }), calls)
+            }
+            ApiProtocol::OpenAi => {
+                let calls: Vec<ToolCall> = self
+                    .calls
+                    .into_iter()
+                    .filter(|c| !c.name.is_empty())
+                    .collect();
+                let mut message = json!({ "role": "assistant", "content": self.text });
+                if !calls.is_empty() {
+                    message["tool_calls"] = calls
+                        .iter()
+                        .map(|c| {
+                            json!({
+                                "id": c.id,
+                                "type": "function",
+                                "function": { "name": c.name, "arguments": c.arguments },
+                            })
+                        })
+                        .collect();
+                }
+                (message, calls)
+            }
+        }
+    }
+}
+
+impl ApiProtocol {
+    /// Messages carrying the results of one turn's tool calls back to the model.
+    fn tool_results(self, results: &[(ToolCall, Result<String, String>)]) -> Vec<Value> {
+        let text = |result: &Result<String, String>| match result {
+            Ok(output) | Err(output) => output.clone(),
+        };
+        match self {
+            ApiProtocol::Anthropic => {
+                let content: Vec<Value> = results
+                    .iter()
+                    .map(|(call, result)| {
+                        json!({
+                            "type": "tool_result",
+                            "tool_use_id": call.id,
+                            "content": text(result),
+                            "is_error": result.is_err(),
+                        })
+                    })
+                    .collect();
+                vec![json!({ "role": "user", "content": content })]
+            }
+            ApiProtocol::OpenAi => results
+                .iter()
+                .map(|(call, result)| {
+                    json!({ "role": "tool", "tool_call_id": call.id, "content": text(result) })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One run of the agent: the conversation so far and what each turn needs to
+/// send it. Owned by the run's tokio task.
+struct Session {
+    protocol: ApiProtocol,
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+    body: Value,
+    messages: Vec<Value>,
+    workdir: PathBuf,
+    env: Vec<(String, String)>,
+    usage_events: Arc<Mutex<String>>,
+}
+
+impl Session {
+    fn request(&self) -> reqwest::RequestBuilder {
+        let mut body = self.body.clone();
+        body["messages"] = json!(self.messages);
+        let request = self.client.post(&self.url).json(&body);
+        match self.protocol {
+            ApiProtocol::Anthropic => request
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01"),
+            ApiProtocol::OpenAi if self.api_key.is_empty() => request,
+            ApiProtocol::OpenAi => request.bearer_auth(&self.api_key),
+        }
+    }
+
+    /// Run turns until the model answers without calling a tool. Returns the
+    /// exit code.
+    async fn run(mut self, stdout: &mut DuplexStream, stderr: &mut DuplexStream) -> i32 {
+        for _ in 0..MAX_TURNS {
+            let Some(reply) = self.turn(stdout, stderr).await else {
+                return 1;
+            };
+            if !reply.text.is_empty() && !reply.text.ends_with('\n') {
+                let _ = stdout.write_all(b"\n").await;
+            }
+            let (message, calls) = reply.finish(self.protocol);
+            if calls.is_empty() {
+                return 0;
+            }
+            self.messages.push(message);
+            let mut results = Vec::with_capacity(calls.len());
+            for call in calls {
+                let _ = stdout
+                    .write_all(format!("tool: {}\n", call.summary()).as_bytes())
+                    .await;
+                let result = run_tool(&call, &self.workdir, &self.env, stdout).await;
+                results.push((call, result));
+            }
+            self.messages.extend(self.protocol.tool_results(&results));
+        }
+        let _ = stderr
+            .write_all(format!("API agent stopped after {MAX_TURNS} tool-use turns\n").as_bytes())
+            .await;
+        1
+    }
+
+    /// Send the conversation and stream one reply, echoing its text to
+    /// `stdout`. `None` once an error has been written to `stderr`.
+    async fn turn(&self, stdout: &mut DuplexStream, stderr: &mut DuplexStream) -> Option<Reply> {
+        let mut response = match self.request().send().await {
+            Ok(response) => response,
+            Err(e) => {
+                let _ = stderr
+                    .write_all(format!("API request failed: {e}\n").as_bytes())
+                    .await;
+                return None;
+            }
+        };
+        let status = response.status();
+        if !status.is_success() {
+            // Errors before streaming starts come back as a plain JSON body.
+            let body = response.text().await.unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            let _ = stderr
+                .write_all(format!("API error ({status}): {message}\n").as_bytes())
+                .await;
+            return None;
+        }
+
+        let mut reply = Reply::default();
+        let mut failed = false;
+        let mut pending = Vec::new();
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = stderr
+                        .write_all(format!("API stream failed: {e}\n").as_bytes())
+                        .await;
+                    return None;
+                }
+            };
+            pending.extend_from_slice(&chunk);
+            while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim_start();
+                match reply.apply(self.protocol, data) {
+                    StreamEvent::Text(text) => {
+                        let _ = stdout.write_all(text.as_bytes()).await;
+                    }
+                    StreamEvent::Error(message) => {
+                        let _ = stderr.write_all(format!("{message}\n").as_bytes()).await;
+                        failed = true;
+                    }
+                    StreamEvent::Usage => {
+                        if let Ok(mut events) = self.usage_events.lock() {
+                            events.push_str(data);
+                            events.push('\n');
+                        }
+                    }
+                    StreamEvent::Other => {}
+                }
+            }
+        }
+        (!failed).then_some(reply)
+    }
+}
+
+/// Run one tool call in `workdir`. An `Err` goes back to the model as a
+/// failed call for it to correct.
+async fn run_tool(
+    call: &ToolCall,
+    workdir: &Path,
+    env: &[(String, String)],
+    stdout: &mut DuplexStream,
+) -> Result<String, String> {
+    let input: Value = match call.arguments.trim() {
+        "" => json!({}),
+        arguments => {
+            serde_json::from_str(arguments).map_err(|e| format!("invalid tool input: {e}"))?
+        }
+    };
+    let arg = |key: &str| {
+        input[key]
+            .as_str()
+            .ok_or_else(|| format!("missing \"{key}\" argument"))
+    };
+    match call.name.as_str() {
+        "read_file" => {
+            let path = arg("path")?;
+            let mut text = String::new();
+            no_follow()
+                .read(true)
+                .open(resolve(workdir, path)?)
+                .await
+                .map_err(|e| format!("could not read {path}: {e}"))?
+                .read_to_string(&mut text)
+                .await
+                .map_err(|e| format!("could not read {path}: {e}"))?;
+            Ok(head(&text, MAX_TOOL_OUTPUT))
+        }
+        "write_file" => {
+            let (path, content) = (arg("path")?, arg("content")?);
+            let target = resolve(workdir, path)?;
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| format!("could not create the directory for {path}: {e}"))?;
+            }
+            no_follow()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&target)
+                .await
+                .map_err(|e| format!("could not write {path}: {e}"))?
+                .write_all(content.as_bytes())
+                .await
+                .map_err(|e| format!("could not write {path}: {e}"))?;
+            Ok(format!("wrote {} bytes to {path}", content.len()))
+        }
+        "run_command" => run_command(arg("command")?, workdir, env, stdout).await,
+        other => Err(format!("unknown tool '{other}'")),
+    }
+}
+
+/// `path` inside `workdir`. Absolute paths, `..` and symlinks are refused so
+/// file tools stay within the project: a link the agent made with
+/// `run_command` could otherwise point anywhere.
+fn resolve(workdir: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    if relative.is_absolute() || relative.components().any(|c| c == Component::ParentDir) {
+        return Err(format!(
+            "{path} is outside the working directory; use a path relative to it"
+        ));
+    }
+    let mut target = workdir
+        .canonicalize()
+        .map_err(|e| format!("could not resolve the working directory: {e}"))?;
+    for component in relative.components() {
+        target.push(component);
+        // Components that don't exist yet are created as plain directories.
+        if std::fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(format!(
+                "{path} goes through a symlink; file tools don't follow links"
+            ));
+        }
+    }
+    Ok(target)
+}
+
+/// Open options that fail on a symlink in the last component, in case one
+/// appears between `resolve` and the open.
+fn no_follow() -> tokio::fs::OpenOptions {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.custom_flags(nix::fcntl::OFlag::O_NOFOLLOW.bits());
+    options
+}
+
+/// Run `command` with `sh` in `workdir`, echoing its output to `stdout` as it
+/// arrives so a long build doesn't look like a stall.
+async fn run_command(
+    command: &str,
+    workdir: &Path,
+    env: &[(String, String)],
+    stdout: &mut DuplexStream,
+) -> Result<String, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{command}"))
+        .current_dir(workdir)
+        .envs(env.iter().cloned())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("could not run the command: {e}"))?;
+    let mut reader = BufReader::new(child.stdout.take().expect("stdout was piped"));
+    let mut output = Vec::new();
+    let mut line = Vec::new();
+    while matches!(reader.read_until(b'\n', &mut line).await, Ok(n) if n > 0) {
+        let _ = stdout.write_all(&line).await;
+        if !line.ends_with(b"\n") {
+            let _ = stdout.write_all(b"\n").await;
+        }
+        output.append(&mut line);
+    }
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("could not wait for the command: {e}"))?;
+    let code = status
+        .code()
+        .map_or_else(|| "none (killed)".to_string(), |c| c.to_string());
+    Ok(format!(
+        "exit code: {code}\n{}",
+        tail(&String::from_utf8_lossy(&output), MAX_TOOL_OUTPUT)
+    ))
+}
+
+/// The first `max` bytes of `text`, noting what was cut.
+fn head(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[… {} more bytes]", &text[..end], text.len() - end)
+}
+
+/// The last `max` bytes of `text`, noting what was cut.
+fn tail(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut start = text.len() - max;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[… {start} earlier bytes]\n{}", &text[start..])
 }
 
 impl Agent for ApiAgent {
+    fn name(&self) -> &str {
+        "api"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn is_available(&self) -> bool {
-        // curl is available on basically every system
-        super::check_binary_available("curl")
-    }
-
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess> {
-        // Build the Anthropic Messages API request body.
-        // We use streaming so Ralph can read incremental output and detect stalls.
-        let body = serde_json::json!({
-            "model": self.model,
-            "max_tokens": 16384,
-            "stream": true,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ]
-        });
+        // The HTTP client is built in; reachability shows on the first request.
+        true
+    }
 
-        let body_str = serde_json::to_string(&body)
-            .context("Failed to serialize API request body")?;
-
-        // Use a shell script that:
-        // 1. Calls curl with streaming SSE
-        // 2. Pipes through a simple awk/sed to extract text deltas from SSE events
-        // 3. Outputs plain text that Ralph can read like any other agent
-        //
-        // The SSE events look like:
-        //   event: content_block_delta
-        //   data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}
-        //
-        // We extract the "text" field from text_delta events and print it.
-        let url = format!("{}/v1/messages", self.base_url);
-
-        let script = format!(
-            r#"curl -sN \
-  -H "Content-Type: application/json" \
-  -H "x-api-key: {api_key}" \
-  -H "anthropic-version: 2023-06-01" \
-  -d '{body}' \
-  "{url}" | while IFS= read -r line; do
-    case "$line" in
-      data:*)
-        json="${{line#data: }}"
-        # Extract text from text_delta events using grep+sed (no jq dependency)
-        text=$(printf '%s' "$json" | grep -o '"text":"[^"]*"' | head -1 | sed 's/"text":"//;s/"$//')
-        if [ -n "$text" ]; then
-          # Unescape basic JSON escapes
-          printf '%b' "$text"
-        fi
-        # Check for error
-        if printf '%s' "$json" | grep -q '"type":"error"'; then
-          printf '%s' "$json" | grep -o '"message":"[^"]*"' | sed 's/"message":"//;s/"$//' >&2
-        fi
-        ;;
-    esac
-  done
-  echo"#,
-            api_key = self.api_key,
-            body = body_str.replace('\'', "'\\''"),
-            url = url,
-        );
-
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c")
-            .arg(&script)
-            .current_dir(workdir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        let child = cmd
-            .spawn()
-            .context("Failed to spawn curl for API agent")?;
-
-        Ok(AgentProcess { child })
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        // Start each run with no usage recorded.
+        if let Ok(mut events) = self.usage_events.lock() {
+            events.clear();
+        }
+        let (url, mut messages) = match self.protocol {
+            ApiProtocol::Anthropic => (format!("{}/v1/messages", self.base_url), vec![]),
+            ApiProtocol::OpenAi => (
+                format!("{}/chat/completions", self.base_url),
+                vec![json!({ "role": "system", "content": SYSTEM_PROMPT })],
+            ),
+        };
+        messages.push(json!({ "role": "user", "content": prompt }));
+        let session = Session {
+            protocol: self.protocol,
+            client: self.client.clone(),
+            url,
+            api_key: self.api_key.clone(),
+            body: self.base_body(),
+            messages,
+            workdir: workdir.to_path_buf(),
+            env: env.to_vec(),
+            usage_events: self.usage_events.clone(),
+        };
+        Ok(AgentProcess::task(|mut stdout, mut stderr| async move {
+            session.run(&mut stdout, &mut stderr).await
+        }))
+    }
+
+    fn take_usage(&self) -> Option<UsageReport> {
+        let events = std::mem::take(&mut *self.usage_events.lock().ok()?);
+        let tokens = match self.protocol {
+            ApiProtocol::Anthropic => parse_usage(&events)?,
+            ApiProtocol::OpenAi => parse_openai_usage(&events)?,
+        };
+        Some(UsageReport {
+            model: self.model.clone(),
+            cost_usd: cost_usd(&self.model, &tokens),
+            tokens,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{Read, Write};
 
     #[test]
     fn api_agent_requires_key() {
@@ -133,7 +941,7 @@ mod tests {
         let old = std::env::var("ANTHROPIC_API_KEY").ok();
         std::env::remove_var("ANTHROPIC_API_KEY");
 
-        let result = ApiAgent::new(None, None, None);
+        let result = ApiAgent::new(None, None, None, "anthropic", Sampling::default());
         assert!(result.is_err());
 
         // Restore
@@ -148,6 +956,8 @@ mod tests {
             Some("http://localhost:3456".to_string()),
             Some("test-key".to_string()),
             Some("claude-sonnet-4-20250514".to_string()),
+            "anthropic",
+            Sampling::default(),
         );
         assert!(agent.is_ok());
         assert!(agent.unwrap().is_available());
@@ -155,8 +965,444 @@ mod tests {
 
     #[test]
     fn api_agent_defaults() {
-        let agent = ApiAgent::new(None, Some("key".to_string()), None).unwrap();
+        let agent = ApiAgent::new(
+            None,
+            Some("key".to_string()),
+            None,
+            "anthropic",
+            Sampling::default(),
+        )
+        .unwrap();
         assert_eq!(agent.base_url, "https://api.anthropic.com");
         assert_eq!(agent.model, "claude-sonnet-4-20250514");
     }
+
+    #[test]
+    fn usage_comes_from_message_start_and_final_delta() {
+        let events = concat!(
+            r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":1200,"cache_creation_input_tokens":0,"cache_read_input_tokens":800,"output_tokens":1}}}"#,
+            "\n",
+            r#"{"type":"message_delta","delta":{"stop_reason":null},"usage":{"output_tokens":90}}"#,
+            "\n",
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":450}}"#,
+            "\n",
+        );
+        let usage = parse_usage(events).expect("usage");
+        assert_eq!(
+            usage,
+            TokenUsage {
+                input_tokens: 1200,
+                output_tokens: 450,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 800,
+                unsplit_tokens: 0,
+            }
+        );
+        assert!(parse_usage("not json\n").is_none());
+
+        // 1200 + 80 input-equivalent at $3/M, 450 output at $15/M.
+        let cost = cost_usd("claude-sonnet-4-20250514", &usage).expect("known model");
+        assert!((cost - 0.01059).abs() < 1e-9);
+        assert!(cost_usd("gpt-4o", &usage).is_none());
+    }
+
+    #[test]
+    fn api_agent_maps_sampling_into_request_body() {
+        let key = || Some("key".to_string());
+        let body = || serde_json::json!({ "max_tokens": MAX_OUTPUT_TOKENS });
+
+        let agent = ApiAgent::new(None, key(), None, "anthropic", Sampling::new(None, Some(0.2))).unwrap();
+        let mut cool = body();
+        agent.apply_sampling(&mut cool);
+        assert_eq!(cool["temperature"], serde_json::json!(0.2f32));
+        assert!(cool.get("thinking").is_none());
+
+        let agent = ApiAgent::new(None, key(), None, "anthropic", Sampling::new(Some("low"), None)).unwrap();
+        let mut thinking = body();
+        agent.apply_sampling(&mut thinking);
+        assert_eq!(thinking["thinking"]["budget_tokens"], 4_000);
+        assert_eq!(thinking["max_tokens"], MAX_OUTPUT_TOKENS + 4_000);
+
+        assert!(ApiAgent::new(None, key(), None, "anthropic", Sampling::new(None, Some(1.5))).is_err());
+        assert!(ApiAgent::new(None, key(), None, "anthropic", Sampling::new(Some("high"), Some(0.0))).is_err());
+    }
+
+    #[test]
+    fn openai_protocol_defaults_and_sampling() {
+        let agent = ApiAgent::new(
+            Some("http://localhost:8000/v1/".to_string()),
+            None,
+            None,
+            "openai",
+            Sampling::new(Some("high"), Some(1.5)),
+        )
+        .unwrap();
+        assert_eq!(agent.base_url, "http://localhost:8000/v1");
+        assert_eq!(agent.model, "gpt-4o");
+
+        let mut body = serde_json::json!({});
+        agent.apply_sampling(&mut body);
+        assert_eq!(body["temperature"], serde_json::json!(1.5f32));
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(body.get("thinking").is_none());
+
+        let too_hot = Sampling::new(None, Some(2.5));
+        assert!(ApiAgent::new(None, None, None, "openai", too_hot).is_err());
+        assert!(ApiAgent::new(None, None, None, "cohere", Sampling::default()).is_err());
+    }
+
+    #[test]
+    fn openai_usage_splits_out_cached_prompt_tokens() {
+        let events = concat!(
+            r#"{"id":"c1","choices":[],"usage":{"prompt_tokens":1000,"completion_tokens":120,"prompt_tokens_details":{"cached_tokens":600}}}"#,
+            "\n",
+        );
+        assert_eq!(
+            parse_openai_usage(events),
+            Some(TokenUsage {
+                input_tokens: 400,
+                output_tokens: 120,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 600,
+                unsplit_tokens: 0,
+            })
+        );
+        assert!(parse_openai_usage("{\"usage\":{}}\n").is_none());
+    }
+
+    /// Answer one HTTP request per `(status, body)` in turn; the thread
+    /// returns the raw requests.
+    fn serve(
+        responses: Vec<(&'static str, String)>,
+    ) -> (std::net::SocketAddr, std::thread::JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                // Read until the JSON body's closing brace has arrived.
+                while !request.ends_with(b"}") {
+                    let n = stream.read(&mut buf).expect("read request");
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .expect("write response");
+                requests.push(String::from_utf8_lossy(&request).to_string());
+            }
+            requests
+        });
+        (addr, server)
+    }
+
+    /// An SSE stream of `data:` events.
+    fn sse(events: &[&str]) -> String {
+        events.iter().map(|e| format!("data: {e}\n\n")).collect()
+    }
+
+    #[tokio::test]
+    async fn anthropic_protocol_streams_text_deltas() {
+        let body = concat!(
+            "event: message_start\n",
+            r#"data: {"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":40,"output_tokens":1}}}"#,
+            "\n\n",
+            "event: content_block_delta\n",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Done: \"login\" page\n"}}"#,
+            "\n\n",
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"<promise>COMPLETE</promise>"}}"#,
+            "\n\n",
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":12}}"#,
+            "\n\n",
+            "event: message_stop\n",
+            r#"data: {"type":"message_stop"}"#,
+            "\n\n",
+        );
+        let (addr, server) = serve(vec![("200 OK", body.to_string())]);
+
+        let agent = ApiAgent::new(
+            Some(format!("http://{addr}")),
+            Some("sk-secret".to_string()),
+            None,
+            "anthropic",
+            Sampling::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let proc = agent
+            .spawn("Build the login page", dir.path(), &[])
+            .expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+
+        let request = server.join().expect("server thread")[0].to_lowercase();
+        assert!(request.starts_with("post /v1/messages "), "{request}");
+        assert!(request.contains("x-api-key: sk-secret"), "{request}");
+        assert!(request.contains("anthropic-version: 2023-06-01"));
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "Done: \"login\" page\n<promise>COMPLETE</promise>\n"
+        );
+        let usage = agent.take_usage().expect("usage");
+        assert_eq!(
+            (usage.tokens.input_tokens, usage.tokens.output_tokens),
+            (40, 12)
+        );
+        assert!(agent.take_usage().is_none());
+    }
+
+    #[tokio::test]
+    async fn api_errors_go_to_stderr_and_fail_the_run() {
+        let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        let (addr, server) = serve(vec![("401 Unauthorized", body.to_string())]);
+
+        let agent = ApiAgent::new(
+            Some(format!("http://{addr}")),
+            Some("wrong".to_string()),
+            None,
+            "anthropic",
+            Sampling::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let proc = agent.spawn("prompt", dir.path(), &[]).expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+        server.join().expect("server thread");
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("401"), "{stderr}");
+        assert!(stderr.contains("invalid x-api-key"), "{stderr}");
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn stream_events_are_classified_per_protocol() {
+        let anthropic = ApiProtocol::Anthropic;
+        let mut reply = Reply::default();
+        assert_eq!(
+            reply.apply(
+                anthropic,
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#
+            ),
+            StreamEvent::Error("Overloaded".to_string())
+        );
+        assert_eq!(
+            reply.apply(anthropic, r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#),
+            StreamEvent::Other
+        );
+        assert_eq!(
+            reply.apply(anthropic, r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"hm"}}"#),
+            StreamEvent::Other
+        );
+        reply.apply(anthropic, r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#);
+        let (message, calls) = reply.finish(anthropic);
+        assert_eq!(
+            message["content"][0],
+            json!({ "type": "thinking", "thinking": "hm", "signature": "sig" })
+        );
+        assert!(calls.is_empty());
+
+        let openai = ApiProtocol::OpenAi;
+        let mut reply = Reply::default();
+        assert_eq!(reply.apply(openai, "[DONE]"), StreamEvent::Other);
+        assert_eq!(
+            reply.apply(openai, r#"{"choices":[],"usage":{"prompt_tokens":1}}"#),
+            StreamEvent::Usage
+        );
+    }
+
+    #[test]
+    fn file_tools_stay_inside_the_workdir() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let workdir = dir.path();
+        let root = workdir.canonicalize().expect("canonical workdir");
+        assert_eq!(
+            resolve(workdir, "src/main.rs"),
+            Ok(root.join("src/main.rs"))
+        );
+        assert!(resolve(workdir, "../secrets.env").is_err());
+        assert!(resolve(workdir, "src/../../x").is_err());
+        assert!(resolve(workdir, "/etc/passwd").is_err());
+
+        // Links out of the project, made with `run_command`, are not followed.
+        std::os::unix::fs::symlink("/etc", workdir.join("etc")).expect("link dir");
+        std::os::unix::fs::symlink("/etc/passwd", workdir.join("passwd")).expect("link file");
+        assert!(resolve(workdir, "etc/passwd").is_err());
+        assert!(resolve(workdir, "passwd").is_err());
+        assert!(resolve(workdir, "./etc").is_err());
+
+        assert_eq!(head("abcdef", 4), "abcd\n[… 2 more bytes]");
+        assert_eq!(tail("abcdef", 4), "[… 2 earlier bytes]\ncdef");
+        assert_eq!(tail("ééé", 3), "[… 4 earlier bytes]\né");
+    }
+
+    #[tokio::test]
+    async fn anthropic_tool_calls_run_in_the_workdir_until_the_model_is_done() {
+        let first = sse(&[
+            r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":100,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Writing the file."}}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"write_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\": \"src/hello.txt\", "}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"content\": \"hi\\n\"}"}}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_2","name":"run_command","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"{\"command\": \"cat src/hello.txt; echo \\\"$RALPH_TMPDIR\\\"\"}"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":30}}"#,
+        ]);
+        let second = sse(&[
+            r#"{"type":"message_start","message":{"id":"msg_2","usage":{"input_tokens":150,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"<promise>COMPLETE</promise>"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":5}}"#,
+        ]);
+        let (addr, server) = serve(vec![("200 OK", first), ("200 OK", second)]);
+
+        let agent = ApiAgent::new(
+            Some(format!("http://{addr}")),
+            Some("key".to_string()),
+            None,
+            "anthropic",
+            Sampling::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let env = [("RALPH_TMPDIR".to_string(), "/tmp/scratch".to_string())];
+        let proc = agent
+            .spawn("Write hello.txt", dir.path(), &env)
+            .expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/hello.txt")).expect("written file"),
+            "hi\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "Writing the file.\ntool: write_file src/hello.txt\n\
+             tool: run_command cat src/hello.txt; echo \"$RALPH_TMPDIR\"\nhi\n/tmp/scratch\n\
+             <promise>COMPLETE</promise>\n"
+        );
+
+        let requests = server.join().expect("server thread");
+        assert!(requests[0].contains(r#""tools":[{"#), "{}", requests[0]);
+        let resent = &requests[1];
+        assert!(resent.contains(r#""id":"toolu_1""#), "{resent}");
+        assert!(!resent.contains("partial_json"), "{resent}");
+        assert!(resent.contains(r#""tool_use_id":"toolu_2""#), "{resent}");
+        assert!(
+            resent.contains(r#""content":"exit code: 0\nhi\n/tmp/scratch\n""#),
+            "{resent}"
+        );
+
+        let usage = agent.take_usage().expect("usage");
+        assert_eq!(
+            (usage.tokens.input_tokens, usage.tokens.output_tokens),
+            (250, 35)
+        );
+    }
+
+    #[tokio::test]
+    async fn openai_tool_errors_go_back_to_the_model() {
+        let first = sse(&[
+            r#"{"choices":[{"index":0,"delta":{"role":"assistant","tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"read_file","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"path\":\"../secret\"}"}}]}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+            "[DONE]",
+        ]);
+        let second = sse(&[
+            r#"{"choices":[{"index":0,"delta":{"content":"<promise>COMPLETE</promise>"}}]}"#,
+            "[DONE]",
+        ]);
+        let (addr, server) = serve(vec![("200 OK", first), ("200 OK", second)]);
+
+        let agent = ApiAgent::new(
+            Some(format!("http://{addr}/v1")),
+            None,
+            None,
+            "openai",
+            Sampling::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let proc = agent.spawn("Read it", dir.path(), &[]).expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "tool: read_file ../secret\n<promise>COMPLETE</promise>\n"
+        );
+        let requests = server.join().expect("server thread");
+        assert!(
+            requests[0].contains(r#""role":"system""#),
+            "{}",
+            requests[0]
+        );
+        let resent = &requests[1];
+        assert!(resent.contains(r#""tool_calls":[{"#), "{resent}");
+        assert!(resent.contains(r#""role":"tool""#), "{resent}");
+        assert!(resent.contains(r#""tool_call_id":"call_1""#), "{resent}");
+        assert!(resent.contains("outside the working directory"), "{resent}");
+    }
+
+    #[tokio::test]
+    async fn openai_protocol_streams_chat_completion_deltas() {
+        let body = concat!(
+            r#"data: {"id":"c1","choices":[{"index":0,"delta":{"role":"assistant","content":""}}],"usage":null}"#,
+            "\n\n",
+            r#"data: {"id":"c1","choices":[{"index":0,"delta":{"content":"Use <div> & say \"hi\"\n"}}],"usage":null}"#,
+            "\n\n",
+            r#"data: {"id":"c1","choices":[{"index":0,"delta":{"content":"<promise>COMPLETE</promise>"}}],"usage":null}"#,
+            "\n\n",
+            r#"data: {"id":"c1","choices":[],"usage":{"prompt_tokens":30,"completion_tokens":9}}"#,
+            "\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (addr, server) = serve(vec![("200 OK", body.to_string())]);
+
+        let agent = ApiAgent::new(
+            Some(format!("http://{addr}/v1")),
+            Some("sk-test".to_string()),
+            Some("qwen2.5-coder".to_string()),
+            "openai",
+            Sampling::default(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let proc = agent
+            .spawn("Build the login page", dir.path(), &[])
+            .expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+
+        let request = server.join().expect("server thread")[0].to_lowercase();
+        assert!(
+            request.starts_with("post /v1/chat/completions "),
+            "{request}"
+        );
+        assert!(
+            request.contains("authorization: bearer sk-test"),
+            "{request}"
+        );
+        assert!(request.contains(r#""include_usage":true"#));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "Use <div> & say \"hi\"\n<promise>COMPLETE</promise>\n"
+        );
+        let usage = agent.take_usage().expect("usage");
+        assert_eq!(
+            (usage.tokens.input_tokens, usage.tokens.output_tokens),
+            (30, 9)
+        );
+        assert!(usage.cost_usd.is_none());
+    }
 }
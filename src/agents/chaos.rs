@@ -0,0 +1,317 @@
+//! Chaos mode (`--chaos`, `[chaos]`): some iterations get a fault instead of
+//! a clean agent run, so hooks, notifications, fallback, and the circuit
+//! breaker can be rehearsed before an overnight run depends on them.
+//!
+//! Faults are injected at the `AgentProcess` layer the HTTP backends use, so
+//! the orchestrator sees them exactly as it would see a misbehaving agent.
+
+use anyhow::{bail, Result};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWriteExt, ReadBuf};
+
+use super::process::AgentOutput;
+use super::{Agent, AgentProcess, AgentProfile, Capabilities, EventFormat, UsageReport};
+use crate::config::ChaosConfig;
+
+/// Faults `[chaos] faults` may list.
+pub const CHAOS_FAULTS: &[&str] = &["delay", "kill", "rate_limit"];
+
+const DEFAULT_DELAY_SECS: u64 = 30;
+
+/// What `rate_limit` prints, in the shape providers' CLIs report a 429.
+const RATE_LIMIT_ERROR: &[u8] =
+    b"[chaos] Error: 429 Too Many Requests: rate limit exceeded, retry after 60s\n";
+
+/// Decides which iterations get which fault. Clones share one random
+/// sequence, so a fallback agent continues it.
+#[derive(Clone)]
+pub struct Chaos {
+    rate: f64,
+    faults: Vec<String>,
+    delay: Duration,
+    state: Arc<Mutex<u64>>,
+}
+
+impl Chaos {
+    pub fn new(rate: f64, config: &ChaosConfig) -> Result<Self> {
+        if !(0.0..=1.0).contains(&rate) {
+            bail!("--chaos must be a rate in [0, 1], got {rate}");
+        }
+        let faults = match &config.faults {
+            Some(faults) => faults.clone(),
+            None => CHAOS_FAULTS.iter().map(|f| f.to_string()).collect(),
+        };
+        if let Some(unknown) = faults.iter().find(|f| !CHAOS_FAULTS.contains(&f.as_str())) {
+            bail!(
+                "Unknown chaos fault `{unknown}` (expected {})",
+                CHAOS_FAULTS.join(", ")
+            );
+        }
+        if faults.is_empty() {
+            bail!("[chaos] faults is empty");
+        }
+        let seed = config.seed.unwrap_or_else(random_seed);
+        Ok(Self {
+            rate,
+            faults,
+            delay: Duration::from_secs(config.delay_secs.unwrap_or(DEFAULT_DELAY_SECS)),
+            // xorshift gets stuck at zero.
+            state: Arc::new(Mutex::new(seed.max(1))),
+        })
+    }
+
+    /// E.g. `25% of iterations (delay, kill, rate_limit)`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{:.0}% of iterations ({})",
+            self.rate * 100.0,
+            self.faults.join(", ")
+        )
+    }
+
+    /// Run `agent` with faults injected.
+    pub fn wrap(&self, agent: Box<dyn Agent>) -> Box<dyn Agent> {
+        Box::new(ChaosAgent {
+            inner: agent,
+            chaos: self.clone(),
+        })
+    }
+
+    /// The fault for the next iteration, if it gets one.
+    fn roll(&self) -> Option<&str> {
+        let hit = self.next() as f64 / u64::MAX as f64;
+        if hit >= self.rate {
+            return None;
+        }
+        let pick = self.next() as usize % self.faults.len();
+        Some(&self.faults[pick])
+    }
+
+    /// xorshift64*: plenty for picking faults.
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// An agent whose runs sometimes fail on purpose.
+pub struct ChaosAgent {
+    inner: Box<dyn Agent>,
+    chaos: Chaos,
+}
+
+impl Agent for ChaosAgent {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn profile(&self) -> AgentProfile {
+        self.inner.profile()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        let Some(fault) = self.chaos.roll() else {
+            return self.inner.spawn(prompt, workdir, env);
+        };
+        eprintln!("    🐒  Chaos: injecting `{fault}` into this iteration");
+        match fault {
+            "delay" => {
+                let mut process = self.inner.spawn(prompt, workdir, env)?;
+                let delay = self.chaos.delay;
+                process.stdout = process.stdout.take().map(|out| Delayed::boxed(out, delay));
+                process.stderr = process.stderr.take().map(|out| Delayed::boxed(out, delay));
+                Ok(process)
+            }
+            "kill" => Ok(AgentProcess::task(|mut stdout, mut stderr| async move {
+                let _ = stdout.write_all(b"Reading the task...\n").await;
+                let _ = stderr
+                    .write_all(b"[chaos] agent killed mid-iteration\n")
+                    .await;
+                137
+            })),
+            _ => Ok(AgentProcess::task(|_stdout, mut stderr| async move {
+                let _ = stderr.write_all(RATE_LIMIT_ERROR).await;
+                1
+            })),
+        }
+    }
+
+    fn take_usage(&self) -> Option<UsageReport> {
+        self.inner.take_usage()
+    }
+
+    fn usage_from_output(&self, output: &str) -> Option<UsageReport> {
+        self.inner.usage_from_output(output)
+    }
+
+    fn resume_session(&self, session: Option<&str>) {
+        self.inner.resume_session(session)
+    }
+
+    fn session_id(&self, output: &str) -> Option<String> {
+        self.inner.session_id(output)
+    }
+
+    fn structured_output(&self, enabled: bool) {
+        self.inner.structured_output(enabled)
+    }
+
+    fn read_only(&self, enabled: bool) {
+        self.inner.read_only(enabled)
+    }
+
+    fn passed_flags(&self) -> Vec<String> {
+        self.inner.passed_flags()
+    }
+
+    fn event_format(&self) -> Option<EventFormat> {
+        self.inner.event_format()
+    }
+}
+
+/// An output stream that yields nothing until `delay` has passed.
+struct Delayed {
+    sleep: Pin<Box<tokio::time::Sleep>>,
+    inner: AgentOutput,
+}
+
+impl Delayed {
+    fn boxed(inner: AgentOutput, delay: Duration) -> AgentOutput {
+        Box::new(Self {
+            sleep: Box::pin(tokio::time::sleep(delay)),
+            inner,
+        })
+    }
+}
+
+impl AsyncRead for Delayed {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        ready!(self.sleep.as_mut().poll(cx));
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    struct Echo;
+
+    impl Agent for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn spawn(
+            &self,
+            _prompt: &str,
+            _workdir: &Path,
+            _env: &[(String, String)],
+        ) -> Result<AgentProcess> {
+            Ok(AgentProcess::task(|mut stdout, _stderr| async move {
+                let _ = stdout.write_all(b"done\n").await;
+                0
+            }))
+        }
+    }
+
+    fn chaos(rate: f64, faults: &[&str]) -> Chaos {
+        let config = ChaosConfig {
+            faults: Some(faults.iter().map(|f| f.to_string()).collect()),
+            delay_secs: Some(1),
+            seed: Some(7),
+            ..ChaosConfig::default()
+        };
+        Chaos::new(rate, &config).expect("valid chaos config")
+    }
+
+    #[test]
+    fn rate_and_fault_list_are_checked() {
+        assert!(Chaos::new(1.5, &ChaosConfig::default()).is_err());
+        let config = ChaosConfig {
+            faults: Some(vec!["meteor".to_string()]),
+            ..ChaosConfig::default()
+        };
+        assert!(Chaos::new(0.5, &config).is_err());
+
+        assert!((0..50).all(|_| chaos(0.0, &["kill"]).roll().is_none()));
+        let always = chaos(1.0, &["kill", "rate_limit"]);
+        let picks: Vec<&str> = (0..50).filter_map(|_| always.roll()).collect();
+        assert_eq!(picks.len(), 50);
+        assert!(picks.contains(&"kill") && picks.contains(&"rate_limit"));
+    }
+
+    #[tokio::test]
+    async fn faults_look_like_a_failing_agent() {
+        let workdir = Path::new(".");
+        let killed = chaos(1.0, &["kill"]).wrap(Box::new(Echo));
+        let output = killed
+            .spawn("prompt", workdir, &[])
+            .expect("spawn")
+            .wait_with_output()
+            .await
+            .expect("wait");
+        assert_eq!(output.status.code(), Some(137));
+
+        let limited = chaos(1.0, &["rate_limit"]).wrap(Box::new(Echo));
+        let output = limited
+            .spawn("prompt", workdir, &[])
+            .expect("spawn")
+            .wait_with_output()
+            .await
+            .expect("wait");
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("429"));
+    }
+
+    #[tokio::test]
+    async fn delay_holds_back_the_real_output() {
+        let delayed = chaos(1.0, &["delay"]).wrap(Box::new(Echo));
+        let mut process = delayed.spawn("prompt", Path::new("."), &[]).expect("spawn");
+        let mut stdout = process.stdout.take().expect("stdout");
+        let mut line = Vec::new();
+
+        let early =
+            tokio::time::timeout(Duration::from_millis(300), stdout.read_to_end(&mut line)).await;
+        assert!(early.is_err(), "output arrived before the delay");
+        stdout.read_to_end(&mut line).await.expect("read");
+        assert_eq!(line, b"done\n");
+    }
+}
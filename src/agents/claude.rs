@@ -1,42 +1,144 @@
 use anyhow::{Context, Result};
+use std::hash::{BuildHasher, Hasher};
 use std::path::Path;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
-use super::{Agent, AgentProcess};
+use crate::config::McpServer;
+
+use super::events::json_lines;
+use super::mcp;
+use super::{Agent, AgentProcess, Capabilities, EventFormat, Sampling, TokenUsage, UsageReport};
 
 /// Claude Code agent backend.
 ///
 /// Pipes the prompt via stdin (`-p -`) to avoid hitting the OS ARG_MAX limit
 /// on large prompts. Equivalent to: `echo "<prompt>" | claude --print -p -`
+///
+/// `--reasoning-effort` is passed as a `MAX_THINKING_TOKENS` budget; Claude Code
+/// has no temperature setting.
+///
+/// Read-only runs (`--plan`) use `--permission-mode plan` instead of skipping
+/// permissions, so Claude Code can read the project but not edit it.
+///
+/// Under `--keep-session`, every run gets a session id of Ralph's choosing
+/// (`--session-id`), so a later run can continue it with `--resume` without
+/// parsing the output. Otherwise Claude Code picks its own.
+///
+/// With structured output on, `--output-format stream-json` prints one JSON
+/// event per line; its closing `result` event carries the run's usage and
+/// cost, which plain `--print` output lacks.
 pub struct ClaudeAgent {
     model: Option<String>,
+    sampling: Sampling,
+    /// Session the next run continues.
+    resume: Mutex<Option<String>>,
+    /// Whether sessions are kept (`resume_session` has been called).
+    keep: AtomicBool,
+    /// Session of the last run.
+    session: Mutex<Option<String>>,
+    structured: AtomicBool,
+    read_only: AtomicBool,
+    /// MCP servers each run starts with.
+    mcp: Mutex<Vec<McpServer>>,
+    /// The `--flags` of the last run's command line.
+    flags: Mutex<Vec<String>>,
 }
 
 impl ClaudeAgent {
-    pub fn new(model: Option<String>) -> Self {
-        Self { model }
+    pub fn new(model: Option<String>, sampling: Sampling) -> Self {
+        Self {
+            model,
+            sampling,
+            resume: Mutex::new(None),
+            keep: AtomicBool::new(false),
+            session: Mutex::new(None),
+            structured: AtomicBool::new(false),
+            read_only: AtomicBool::new(false),
+            mcp: Mutex::new(Vec::new()),
+            flags: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl ClaudeAgent {
+    /// The session flag for the next run and its id: `--resume` to continue
+    /// one, `--session-id` to start one Ralph can come back to, or nothing
+    /// when sessions aren't kept.
+    fn next_session(&self) -> Option<(&'static str, String)> {
+        match self.resume.lock().unwrap().clone() {
+            Some(id) => Some(("--resume", id)),
+            None if self.keep.load(Ordering::Relaxed) => Some(("--session-id", new_session_id())),
+            None => None,
+        }
     }
 }
 
 impl Agent for ClaudeAgent {
+    fn name(&self) -> &str {
+        "claude"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            supports_mcp: true,
+            supports_read_only: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn is_available(&self) -> bool {
         super::check_binary_available("claude")
     }
 
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess> {
+    fn passed_flags(&self) -> Vec<String> {
+        self.flags.lock().unwrap().clone()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
         let mut cmd = Command::new("claude");
 
-        cmd.arg("--dangerously-skip-permissions")
-            .arg("--print")
-            .arg("-p")
-            .arg("-"); // read prompt from stdin
+        if self.read_only.load(Ordering::Relaxed) {
+            cmd.arg("--permission-mode").arg("plan");
+        } else {
+            cmd.arg("--dangerously-skip-permissions");
+        }
+        cmd.arg("--print").arg("-p").arg("-"); // read prompt from stdin
+        if self.structured.load(Ordering::Relaxed) {
+            // `--print` only streams events with `--verbose`.
+            cmd.arg("--output-format")
+                .arg("stream-json")
+                .arg("--verbose");
+        }
 
+        let servers = self.mcp.lock().unwrap().clone();
+        if !servers.is_empty() {
+            cmd.arg("--mcp-config").arg(mcp::claude_config(&servers));
+        }
         if let Some(ref model) = self.model {
             cmd.arg("--model").arg(model);
         }
+        if let Some(budget) = self.sampling.thinking_budget() {
+            cmd.env("MAX_THINKING_TOKENS", budget.to_string());
+        }
+        let session = self.next_session();
+        if let Some((flag, ref id)) = session {
+            cmd.arg(flag).arg(id);
+        }
+        *self.session.lock().unwrap() = session.map(|(_, id)| id);
 
+        *self.flags.lock().unwrap() = super::flags_of(&cmd);
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
         cmd.current_dir(workdir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -54,6 +156,120 @@ impl Agent for ClaudeAgent {
             let _ = stdin.shutdown().await;
         });
 
-        Ok(AgentProcess { child })
+        Ok(child.into())
+    }
+
+    fn resume_session(&self, session: Option<&str>) {
+        self.keep.store(true, Ordering::Relaxed);
+        *self.resume.lock().unwrap() = session.map(str::to_string);
+    }
+
+    fn session_id(&self, _output: &str) -> Option<String> {
+        self.session.lock().unwrap().clone()
+    }
+
+    fn structured_output(&self, enabled: bool) {
+        self.structured.store(enabled, Ordering::Relaxed);
+    }
+
+    fn read_only(&self, enabled: bool) {
+        self.read_only.store(enabled, Ordering::Relaxed);
+    }
+
+    fn mcp_servers(&self, servers: &[McpServer]) {
+        *self.mcp.lock().unwrap() = servers.to_vec();
+    }
+
+    fn event_format(&self) -> Option<EventFormat> {
+        self.structured
+            .load(Ordering::Relaxed)
+            .then_some(EventFormat::ClaudeStream)
+    }
+
+    fn usage_from_output(&self, output: &str) -> Option<UsageReport> {
+        let result = json_lines(output)
+            .filter(|e| e["type"] == "result")
+            .last()?;
+        let tokens: TokenUsage = serde_json::from_value(result["usage"].clone()).ok()?;
+        Some(UsageReport {
+            model: self.model.clone().unwrap_or_else(|| "default".to_string()),
+            tokens,
+            cost_usd: result["total_cost_usd"].as_f64(),
+        })
+    }
+}
+
+/// A random (version 4) UUID, the form `--session-id` requires.
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    for half in bytes.chunks_mut(8) {
+        // Each `RandomState` is keyed differently, from OS randomness.
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ids_are_distinct_v4_uuids() {
+        let a = new_session_id();
+        let b = new_session_id();
+        assert_ne!(a, b);
+        let groups: Vec<&str> = a.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'), "{a}");
+        assert!("89ab".contains(&groups[3][..1]), "{a}");
+    }
+
+    #[test]
+    fn session_flags_only_under_keep_session() {
+        let agent = ClaudeAgent::new(None, Sampling::default());
+        assert!(agent.next_session().is_none());
+
+        agent.resume_session(None);
+        let (flag, _) = agent.next_session().expect("new session");
+        assert_eq!(flag, "--session-id");
+
+        agent.resume_session(Some("abc"));
+        assert_eq!(agent.next_session(), Some(("--resume", "abc".to_string())));
+    }
+
+    #[test]
+    fn usage_comes_from_the_stream_json_result() {
+        let agent = ClaudeAgent::new(Some("sonnet".to_string()), Sampling::default());
+        assert!(agent.usage_from_output("plain --print output\n").is_none());
+
+        let log = r#"=== STDOUT ===
+{"type":"assistant","message":{"content":[{"type":"text","text":"done"}]}}
+{"type":"result","subtype":"success","is_error":false,"total_cost_usd":0.0421,"usage":{"input_tokens":12,"cache_creation_input_tokens":3000,"cache_read_input_tokens":9000,"output_tokens":450}}
+"#;
+        let usage = agent.usage_from_output(log).expect("usage");
+        assert_eq!(usage.model, "sonnet");
+        assert_eq!(usage.tokens.output_tokens, 450);
+        assert_eq!(usage.tokens.cache_read_input_tokens, 9000);
+        assert_eq!(usage.cost_usd, Some(0.0421));
     }
 }
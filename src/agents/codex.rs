@@ -1,39 +1,127 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
-use super::{Agent, AgentProcess};
+use crate::config::McpServer;
+
+use super::events::json_lines;
+use super::mcp;
+use super::{
+    Agent, AgentProcess, AgentProfile, Capabilities, EventFormat, Sampling, TokenUsage, UsageReport,
+};
 
 /// Codex (OpenAI) CLI agent backend.
 ///
 /// Pipes the prompt via stdin to avoid hitting the OS ARG_MAX limit.
-/// Codex appends stdin to the `-p` flag content.
+/// Codex appends stdin to the `-p` flag content. Codex has no temperature
+/// setting; `--reasoning-effort` maps to its `model_reasoning_effort` config.
+///
+/// `codex exec` ends its stderr with the run's token total, which is the only
+/// usage it reports; there is no input/output split to price. Its header
+/// names the run's session, which `codex exec resume <id>` continues.
+///
+/// Read-only runs (`--plan`) use `--sandbox read-only` instead of
+/// `--full-auto`, so commands can read the project but not change it.
+///
+/// With structured output on, `--json` prints one JSON event per line
+/// instead: the session is the `thread.started` thread id, and
+/// `turn.completed` splits the usage into input, cached, and output tokens.
 pub struct CodexAgent {
     model: Option<String>,
+    sampling: Sampling,
+    /// Session the next run continues.
+    resume: Mutex<Option<String>>,
+    structured: AtomicBool,
+    read_only: AtomicBool,
+    /// MCP servers each run starts with.
+    mcp: Mutex<Vec<McpServer>>,
+    /// The `--flags` of the last run's command line.
+    flags: Mutex<Vec<String>>,
 }
 
 impl CodexAgent {
-    pub fn new(model: Option<String>) -> Self {
-        Self { model }
+    pub fn new(model: Option<String>, sampling: Sampling) -> Self {
+        Self {
+            model,
+            sampling,
+            resume: Mutex::new(None),
+            structured: AtomicBool::new(false),
+            read_only: AtomicBool::new(false),
+            mcp: Mutex::new(Vec::new()),
+            flags: Mutex::new(Vec::new()),
+        }
     }
 }
 
 impl Agent for CodexAgent {
+    fn name(&self) -> &str {
+        "codex"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            supports_mcp: true,
+            supports_read_only: true,
+            ..Capabilities::default()
+        }
+    }
+
+    /// `codex exec` prints little until a step finishes, and a long
+    /// command or edit can keep it silent for minutes.
+    fn profile(&self) -> AgentProfile {
+        AgentProfile {
+            timeout_secs: 900,
+            stall_timeout_secs: 300,
+        }
+    }
+
     fn is_available(&self) -> bool {
         super::check_binary_available("codex")
     }
 
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess> {
+    fn passed_flags(&self) -> Vec<String> {
+        self.flags.lock().unwrap().clone()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
         let mut cmd = Command::new("codex");
 
-        cmd.arg("exec").arg("--full-auto");
+        cmd.arg("exec");
+        if self.read_only.load(Ordering::Relaxed) {
+            cmd.arg("--sandbox").arg("read-only");
+        } else {
+            cmd.arg("--full-auto");
+        }
+        if self.structured.load(Ordering::Relaxed) {
+            cmd.arg("--json");
+        }
 
         if let Some(ref model) = self.model {
             cmd.arg("--model").arg(model);
         }
+        if let Some(config) = self.sampling.codex_config() {
+            cmd.arg("-c").arg(config);
+        }
+        for config in mcp::codex_overrides(&self.mcp.lock().unwrap()) {
+            cmd.arg("-c").arg(config);
+        }
+        if let Some(session) = self.resume.lock().unwrap().as_deref() {
+            cmd.arg("resume").arg(session).arg("-");
+        }
 
+        *self.flags.lock().unwrap() = super::flags_of(&cmd);
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
         cmd.current_dir(workdir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -50,6 +138,142 @@ impl Agent for CodexAgent {
             let _ = stdin.shutdown().await;
         });
 
-        Ok(AgentProcess { child })
+        Ok(child.into())
+    }
+
+    fn usage_from_output(&self, output: &str) -> Option<UsageReport> {
+        let tokens = match turn_usage(output) {
+            Some(tokens) => tokens,
+            None => TokenUsage {
+                unsplit_tokens: tokens_used(output)?,
+                ..TokenUsage::default()
+            },
+        };
+        Some(UsageReport {
+            model: self.model.clone().unwrap_or_else(|| "default".to_string()),
+            tokens,
+            cost_usd: None,
+        })
+    }
+
+    fn resume_session(&self, session: Option<&str>) {
+        *self.resume.lock().unwrap() = session.map(str::to_string);
+    }
+
+    fn session_id(&self, output: &str) -> Option<String> {
+        let thread = json_lines(output)
+            .filter(|e| e["type"] == "thread.started")
+            .find_map(|e| e["thread_id"].as_str().map(str::to_string));
+        thread.or_else(|| {
+            output.lines().rev().find_map(|line| {
+                let id = line.trim().strip_prefix("session id:")?.trim();
+                (!id.is_empty()).then(|| id.to_string())
+            })
+        })
+    }
+
+    fn structured_output(&self, enabled: bool) {
+        self.structured.store(enabled, Ordering::Relaxed);
+    }
+
+    fn read_only(&self, enabled: bool) {
+        self.read_only.store(enabled, Ordering::Relaxed);
+    }
+
+    fn mcp_servers(&self, servers: &[McpServer]) {
+        *self.mcp.lock().unwrap() = servers.to_vec();
+    }
+
+    fn event_format(&self) -> Option<EventFormat> {
+        self.structured
+            .load(Ordering::Relaxed)
+            .then_some(EventFormat::CodexJson)
+    }
+}
+
+/// Usage summed over the `turn.completed` events of `--json` output. Codex
+/// counts cached input inside `input_tokens`.
+fn turn_usage(output: &str) -> Option<TokenUsage> {
+    let mut total: Option<TokenUsage> = None;
+    for event in json_lines(output).filter(|e| e["type"] == "turn.completed") {
+        let usage = &event["usage"];
+        let count = |key: &str| usage[key].as_u64().unwrap_or(0);
+        let cached = count("cached_input_tokens");
+        total
+            .get_or_insert_with(TokenUsage::default)
+            .add(&TokenUsage {
+                input_tokens: count("input_tokens").saturating_sub(cached),
+                cache_read_input_tokens: cached,
+                output_tokens: count("output_tokens"),
+                ..TokenUsage::default()
+            });
+    }
+    total
+}
+
+/// The last `tokens used` total in `output`: `tokens used: 12,345` on one
+/// line, or the label and the number on consecutive lines.
+fn tokens_used(output: &str) -> Option<u64> {
+    let lines: Vec<&str> = output.lines().map(str::trim).collect();
+    lines.iter().enumerate().rev().find_map(|(i, line)| {
+        let rest = line.strip_prefix("tokens used")?;
+        let number = match rest.trim_start_matches(':').trim() {
+            "" => lines.get(i + 1)?,
+            same_line => same_line,
+        };
+        number.replace(',', "").parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_total_comes_from_the_last_tokens_used_line() {
+        assert_eq!(tokens_used("done\ntokens used: 1,234\n"), Some(1234));
+        assert_eq!(
+            tokens_used("tokens used\n900\nmore work\ntokens used\n12,345\n"),
+            Some(12345)
+        );
+        assert_eq!(tokens_used("no usage here\n"), None);
+
+        let agent = CodexAgent::new(Some("gpt-5".to_string()), Sampling::default());
+        let usage = agent
+            .usage_from_output("=== STDERR ===\ntokens used\n4,200\n")
+            .expect("usage");
+        assert_eq!(usage.model, "gpt-5");
+        assert_eq!(usage.tokens.total(), 4200);
+        assert!(usage.cost_usd.is_none());
+    }
+
+    #[test]
+    fn session_id_comes_from_the_header() {
+        let agent = CodexAgent::new(None, Sampling::default());
+        let log = "=== STDERR ===\nworkdir: /repo\nmodel: gpt-5\nsession id: 0199a2b4-7c3e-7d10-9f2a-1b2c3d4e5f60\n--------\n";
+        assert_eq!(
+            agent.session_id(log).as_deref(),
+            Some("0199a2b4-7c3e-7d10-9f2a-1b2c3d4e5f60")
+        );
+        assert!(agent.session_id("no header\n").is_none());
+    }
+
+    #[test]
+    fn json_events_give_the_session_and_split_usage() {
+        let agent = CodexAgent::new(None, Sampling::default());
+        let log = r#"=== STDOUT ===
+{"type":"thread.started","thread_id":"0199a2b4-aaaa-7d10-9f2a-1b2c3d4e5f60"}
+{"type":"item.completed","item":{"id":"item_0","type":"agent_message","text":"done"}}
+{"type":"turn.completed","usage":{"input_tokens":24763,"cached_input_tokens":24448,"output_tokens":122}}
+"#;
+        assert_eq!(
+            agent.session_id(log).as_deref(),
+            Some("0199a2b4-aaaa-7d10-9f2a-1b2c3d4e5f60")
+        );
+        let usage = agent.usage_from_output(log).expect("usage");
+        assert_eq!(usage.tokens.input_tokens, 315);
+        assert_eq!(usage.tokens.cache_read_input_tokens, 24448);
+        assert_eq!(usage.tokens.output_tokens, 122);
+        assert_eq!(usage.tokens.unsplit_tokens, 0);
     }
 }
@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::process::Command;
+
+use super::{Agent, AgentProcess, Capabilities};
+use crate::shell::quote;
+
+/// Agent defined in ralph.toml as a shell command template:
+///
+/// ```toml
+/// [agents.mytool]
+/// command = "mytool run --model {model} --prompt {prompt}"
+/// ```
+///
+/// Placeholders are replaced with shell-quoted values:
+/// - `{prompt}` — the prompt text
+/// - `{prompt_file}` — a file holding the prompt (for prompts over ARG_MAX)
+/// - `{model}` — `--model`, or empty
+/// - `{workdir}` — the working directory the command runs in
+///
+/// When the template uses neither `{prompt}` nor `{prompt_file}`, the prompt
+/// is sent on stdin. The command runs under `sh -c` and its output is read
+/// like any CLI agent's.
+pub struct CommandAgent {
+    name: String,
+    template: String,
+    model: Option<String>,
+    prompt_file: PathBuf,
+}
+
+/// Distinguishes the prompt files of agents in one process (`ralph watch`).
+static NEXT_PROMPT_FILE: AtomicU32 = AtomicU32::new(0);
+
+impl CommandAgent {
+    pub fn new(name: String, template: String, model: Option<String>) -> Self {
+        let prompt_file = std::env::temp_dir().join(format!(
+            "ralph-command-prompt-{}-{}.md",
+            std::process::id(),
+            NEXT_PROMPT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            name,
+            template,
+            model,
+            prompt_file,
+        }
+    }
+
+    fn takes_prompt_as_arg(&self) -> bool {
+        self.template.contains("{prompt}") || self.template.contains("{prompt_file}")
+    }
+
+    /// The template with every placeholder filled in, in one pass so text
+    /// inside the prompt is never substituted. Unknown `{...}` stays as is.
+    fn render(&self, prompt: &str, workdir: &Path) -> String {
+        let mut out = String::with_capacity(self.template.len() + prompt.len());
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            let value = tail.find('}').and_then(|end| {
+                let value = match &tail[1..end] {
+                    "prompt" => prompt.to_string(),
+                    "prompt_file" => self.prompt_file.display().to_string(),
+                    "model" => self.model.clone().unwrap_or_default(),
+                    "workdir" => workdir.display().to_string(),
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            match value {
+                Some((value, end)) => {
+                    out.push_str(&quote(&value));
+                    rest = &tail[end + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &tail[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+impl Drop for CommandAgent {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.prompt_file);
+    }
+}
+
+/// `true` if `program` names an executable, directly or on PATH.
+fn on_path(program: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    let executable = |p: &Path| {
+        p.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+    if program.contains('/') {
+        return executable(Path::new(program));
+    }
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| executable(&dir.join(program))))
+        .unwrap_or(false)
+}
+
+impl Agent for CommandAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            manages_own_commits: false,
+            supports_model_override: self.template.contains("{model}"),
+            supports_mcp: false,
+            supports_read_only: false,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        // Tools differ on `--version`, so only check the program exists.
+        self.template.split_whitespace().next().is_some_and(on_path)
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        std::fs::write(&self.prompt_file, prompt).context("Failed to write agent prompt file")?;
+
+        let stdin = if self.takes_prompt_as_arg() {
+            Stdio::null()
+        } else {
+            std::fs::File::open(&self.prompt_file)
+                .context("Failed to open agent prompt file")?
+                .into()
+        };
+
+        let mut cmd = Command::new("sh");
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
+        cmd.arg("-c")
+            .arg(self.render(prompt, workdir))
+            .current_dir(workdir)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let child = cmd
+            .spawn()
+            .context("Failed to spawn custom agent command")?;
+
+        Ok(child.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(template: &str) -> CommandAgent {
+        CommandAgent::new("mytool".to_string(), template.to_string(), None)
+    }
+
+    #[test]
+    fn placeholders_are_filled_with_quoted_values() {
+        let agent = CommandAgent::new(
+            "mytool".to_string(),
+            "mytool run --model {model} --prompt {prompt} --in {workdir}".to_string(),
+            Some("big".to_string()),
+        );
+        assert_eq!(
+            agent.render("it's done", Path::new("/srv/app")),
+            "mytool run --model big --prompt 'it'\\''s done' --in /srv/app"
+        );
+        assert!(agent.takes_prompt_as_arg());
+        assert_eq!(
+            agent.render("use {model}", Path::new("/a")),
+            "mytool run --model big --prompt 'use {model}' --in /a"
+        );
+        let awk = command("awk '{print}' {prompt_file}");
+        assert!(awk
+            .render("x", Path::new("/a"))
+            .starts_with("awk '{print}' /"));
+
+        assert!(agent.capabilities().supports_model_override);
+        let piped = command("mytool --stdin");
+        assert!(!piped.takes_prompt_as_arg());
+        assert!(!piped.capabilities().supports_model_override);
+        assert!(!command("no-such-tool-xyz run").is_available());
+        assert!(command("sh -c true").is_available());
+    }
+
+    #[tokio::test]
+    async fn prompt_reaches_the_command_as_arg_file_or_stdin() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        for template in [
+            "printf '%s\\n' {prompt}",
+            "cat {prompt_file}; echo",
+            "cat; echo",
+        ] {
+            let agent = CommandAgent::new("mytool".to_string(), template.to_string(), None);
+            let proc = agent
+                .spawn("Build the login page", dir.path(), &[])
+                .expect("spawn");
+            let output = proc.wait_with_output().await.expect("wait");
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                "Build the login page\n",
+                "template: {template}"
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Flag drift: agent CLIs rename and drop flags between releases (codex
+//! replacing `--full-auto`, say), and most warn on stderr for a while before
+//! the old flag stops working or quietly means something else. Ralph passes
+//! those flags itself, so a run collects such warnings and sums them up when
+//! it ends, and `ralph doctor` looks for them in each agent's probe.
+//!
+//! Only lines that name one of the `--flags` Ralph passed count: runtime
+//! deprecation notices (Node's `punycode`, say) and the warnings of the tools
+//! the agent runs are noise here.
+
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+
+/// Longest warning kept, in characters.
+const MAX_LINE: usize = 200;
+
+fn warning_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)\b(unknown|unrecognized|unexpected|invalid|unsupported)\s+(option|flag|argument)",
+            r"|\b(deprecated|renamed|no longer supported|will be removed|has been removed)\b",
+        ))
+        .expect("valid flag warning pattern")
+    })
+}
+
+fn flag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?:^|[\s'"`(])(--[A-Za-z][\w-]*)"#).expect("valid flag pattern")
+    })
+}
+
+/// The lines of `stderr` that warn about one of `flags`, each once, in
+/// order.
+pub fn scan(stderr: &str, flags: &[String]) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    for line in stderr.lines() {
+        let line = crate::output::strip_ansi(line);
+        let line = line.trim();
+        let names_ours = flag_re()
+            .captures_iter(line)
+            .any(|c| flags.iter().any(|f| f == &c[1]));
+        if !names_ours || !warning_re().is_match(line) {
+            continue;
+        }
+        let line: String = match line.char_indices().nth(MAX_LINE) {
+            Some((cut, _)) => format!("{}…", &line[..cut]),
+            None => line.to_string(),
+        };
+        if !found.contains(&line) {
+            found.push(line);
+        }
+    }
+    found
+}
+
+/// The flag warnings a run's agents printed, by agent.
+#[derive(Debug, Default)]
+pub struct FlagWarnings {
+    by_agent: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl FlagWarnings {
+    /// Keep the warnings in `stderr`, printed by `agent`, about the `flags`
+    /// Ralph passed it.
+    pub fn record(&mut self, agent: &str, stderr: &str, flags: &[String]) {
+        let found = scan(stderr, flags);
+        if !found.is_empty() {
+            self.by_agent
+                .entry(agent.to_string())
+                .or_default()
+                .extend(found);
+        }
+    }
+
+    /// The agents that printed any.
+    pub fn agents(&self) -> Vec<&str> {
+        self.by_agent.keys().map(String::as_str).collect()
+    }
+
+    /// One warning covering the whole run, or `None` when no agent printed
+    /// any.
+    pub fn summary(&self) -> Option<String> {
+        if self.by_agent.is_empty() {
+            return None;
+        }
+        let mut out = format!(
+            "{} warned about command-line flags; the CLI may have changed under Ralph \
+             (check `ralph doctor` and upgrade Ralph, or pin the agent's version):",
+            self.agents().join(", ")
+        );
+        for (agent, lines) in &self.by_agent {
+            for line in lines {
+                out.push_str(&format!("\n  {agent}: {line}"));
+            }
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_warnings_that_name_a_flag_ralph_passed() {
+        let flags = ["--full-auto".to_string(), "--yolo".to_string()];
+        let stderr = "\
+Reading prompt from stdin...
+\x1b[33mwarning:\x1b[0m `--full-auto` is deprecated; use `--sandbox workspace-write` instead
+error: unexpected argument '--yolo' found
+(node:4242) [DEP0040] DeprecationWarning: The `punycode` module is deprecated.
+warning: unused variable `x` in src/lib.rs
+warning: `--frozen` is deprecated; use `--locked`
+warning: `--full-auto` is deprecated; use `--sandbox workspace-write` instead
+";
+        assert_eq!(
+            scan(stderr, &flags),
+            [
+                "warning: `--full-auto` is deprecated; use `--sandbox workspace-write` instead",
+                "error: unexpected argument '--yolo' found",
+            ]
+        );
+
+        let mut warnings = FlagWarnings::default();
+        assert_eq!(warnings.summary(), None);
+        warnings.record("codex", stderr, &flags);
+        warnings.record("claude", "all good\n", &flags);
+        warnings.record("codex", "Unknown option --full-auto\n", &flags);
+        assert_eq!(warnings.agents(), ["codex"]);
+        let summary = warnings.summary().expect("summary");
+        assert!(summary.starts_with("codex warned about command-line flags"));
+        assert_eq!(summary.lines().count(), 4, "{summary}");
+        assert!(summary.contains("\n  codex: Unknown option --full-auto"));
+    }
+}
@@ -0,0 +1,232 @@
+//! `--structured-output`: the JSON event streams Claude Code
+//! (`--output-format stream-json`) and Codex (`exec --json`) print instead of
+//! plain text.
+//!
+//! Each line is one event. Only what the agent said counts toward completion
+//! detection, so a completion token that merely shows up in a tool call or a
+//! file the agent read is never taken as a claim.
+
+use serde_json::Value;
+
+use crate::output::TOOL_TAG;
+
+/// Longest tool input shown on a `[tool]` line.
+const INPUT_PREVIEW_CHARS: usize = 120;
+
+/// The event stream a backend prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    /// Claude Code's `--output-format stream-json`.
+    ClaudeStream,
+    /// Codex's `exec --json`.
+    CodexJson,
+}
+
+/// What one event says about the run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    /// Text the agent wrote as its reply.
+    Text(String),
+    /// A tool the agent called, with a preview of its input.
+    ToolCall { name: String, input: String },
+    /// The agent reported that the run failed.
+    Error(String),
+}
+
+impl EventFormat {
+    /// The events in one line of output, or `None` when the line isn't an
+    /// event (a banner or warning printed around the stream).
+    pub fn parse(self, line: &str) -> Option<Vec<AgentEvent>> {
+        let event: Value = serde_json::from_str(line.trim()).ok()?;
+        event.get("type")?.as_str()?;
+        Some(match self {
+            EventFormat::ClaudeStream => claude_events(&event),
+            EventFormat::CodexJson => codex_events(&event),
+        })
+    }
+}
+
+impl AgentEvent {
+    /// The event as lines for the terminal, the TUI, and progress output.
+    pub fn display(&self) -> Vec<String> {
+        match self {
+            AgentEvent::Text(text) => text.lines().map(str::to_string).collect(),
+            AgentEvent::ToolCall { name, input } if input.is_empty() => {
+                vec![format!("{TOOL_TAG}{name}")]
+            }
+            AgentEvent::ToolCall { name, input } => vec![format!("{TOOL_TAG}{name}: {input}")],
+            AgentEvent::Error(message) => vec![format!("error: {message}")],
+        }
+    }
+}
+
+/// `assistant` messages carry text and tool calls; `user` messages carry
+/// tool results, which are not the agent's words; `result` closes the run.
+fn claude_events(event: &Value) -> Vec<AgentEvent> {
+    match event["type"].as_str() {
+        Some("assistant") => event["message"]["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|block| match block["type"].as_str()? {
+                "text" => Some(AgentEvent::Text(block["text"].as_str()?.to_string())),
+                "tool_use" => Some(AgentEvent::ToolCall {
+                    name: block["name"].as_str()?.to_string(),
+                    input: preview(&block["input"]),
+                }),
+                _ => None,
+            })
+            .collect(),
+        Some("result") if event["is_error"].as_bool() == Some(true) => {
+            let message = event["result"]
+                .as_str()
+                .or_else(|| event["subtype"].as_str())
+                .unwrap_or("unknown error");
+            vec![AgentEvent::Error(message.to_string())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Items are announced with `item.started` and finished with
+/// `item.completed`; a failed turn or stream ends with an error.
+fn codex_events(event: &Value) -> Vec<AgentEvent> {
+    let item = &event["item"];
+    let event = match (event["type"].as_str(), item["type"].as_str()) {
+        (Some("item.completed"), Some("agent_message")) => item["text"]
+            .as_str()
+            .map(|t| AgentEvent::Text(t.to_string())),
+        (Some("item.started"), Some("command_execution")) => Some(AgentEvent::ToolCall {
+            name: "exec".to_string(),
+            input: preview(&item["command"]),
+        }),
+        (Some("item.completed"), Some("file_change")) => Some(AgentEvent::ToolCall {
+            name: "edit".to_string(),
+            input: item["changes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c["path"].as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }),
+        (Some("item.started"), Some("mcp_tool_call")) => Some(AgentEvent::ToolCall {
+            name: format!(
+                "{}.{}",
+                item["server"].as_str().unwrap_or("mcp"),
+                item["tool"].as_str().unwrap_or("?")
+            ),
+            input: preview(&item["arguments"]),
+        }),
+        (Some("item.started"), Some("web_search")) => Some(AgentEvent::ToolCall {
+            name: "web_search".to_string(),
+            input: preview(&item["query"]),
+        }),
+        (Some("turn.failed"), _) => Some(AgentEvent::Error(
+            event["error"]["message"]
+                .as_str()
+                .unwrap_or("turn failed")
+                .to_string(),
+        )),
+        (Some("error"), _) => Some(AgentEvent::Error(
+            event["message"].as_str().unwrap_or("error").to_string(),
+        )),
+        _ => None,
+    };
+    event.into_iter().collect()
+}
+
+/// A one-line preview of a tool's input: the command or path when there is
+/// one, the compact JSON otherwise.
+fn preview(input: &Value) -> String {
+    const KEYS: &[&str] = &["command", "file_path", "path", "pattern", "url", "query"];
+    let text = match input {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Object(fields) => KEYS
+            .iter()
+            .find_map(|key| fields.get(*key)?.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| input.to_string()),
+        other => other.to_string(),
+    };
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(INPUT_PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None if line.len() < text.len() => format!("{line} …"),
+        None => line.to_string(),
+    }
+}
+
+/// Every event line in `output`, parsed as JSON.
+pub fn json_lines(output: &str) -> impl Iterator<Item = Value> + '_ {
+    output
+        .lines()
+        .filter(|line| line.trim_start().starts_with('{'))
+        .filter_map(|line| serde_json::from_str(line.trim()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claude_stream_separates_text_tools_and_errors() {
+        let format = EventFormat::ClaudeStream;
+        assert_eq!(
+            format.parse(r#"{"type":"system","subtype":"init","session_id":"s"}"#),
+            Some(vec![])
+        );
+        let assistant = r#"{"type":"assistant","message":{"content":[
+            {"type":"text","text":"Done.\n<promise>COMPLETE</promise>"},
+            {"type":"tool_use","name":"Bash","input":{"command":"cargo test","description":"run tests"}}]}}"#
+            .replace('\n', "");
+        assert_eq!(
+            format.parse(&assistant),
+            Some(vec![
+                AgentEvent::Text("Done.\n<promise>COMPLETE</promise>".to_string()),
+                AgentEvent::ToolCall {
+                    name: "Bash".to_string(),
+                    input: "cargo test".to_string()
+                },
+            ])
+        );
+        // A tool result quoting the token is not the agent's claim.
+        let result = r#"{"type":"user","message":{"content":[{"type":"tool_result","content":"<promise>COMPLETE</promise>"}]}}"#;
+        assert_eq!(format.parse(result), Some(vec![]));
+        let failed = r#"{"type":"result","subtype":"error_max_turns","is_error":true}"#;
+        assert_eq!(
+            format.parse(failed),
+            Some(vec![AgentEvent::Error("error_max_turns".to_string())])
+        );
+        assert_eq!(format.parse("Warning: not JSON"), None);
+    }
+
+    #[test]
+    fn codex_json_separates_text_tools_and_errors() {
+        let format = EventFormat::CodexJson;
+        let exec = r#"{"type":"item.started","item":{"id":"item_1","type":"command_execution","command":"bash -lc ls","status":"in_progress"}}"#;
+        let events = format.parse(exec).unwrap();
+        assert_eq!(events[0].display(), ["[tool] exec: bash -lc ls"]);
+        let message = r#"{"type":"item.completed","item":{"id":"item_2","type":"agent_message","text":"All done"}}"#;
+        assert_eq!(
+            format.parse(message),
+            Some(vec![AgentEvent::Text("All done".to_string())])
+        );
+        let failed = r#"{"type":"turn.failed","error":{"message":"stream disconnected"}}"#;
+        assert_eq!(
+            format.parse(failed),
+            Some(vec![AgentEvent::Error("stream disconnected".to_string())])
+        );
+        assert_eq!(format.parse(r#"{"type":"turn.started"}"#), Some(vec![]));
+    }
+
+    #[test]
+    fn long_tool_input_is_cut_to_one_line() {
+        let input = Value::String(format!("{}\nsecond line", "x".repeat(200)));
+        let shown = preview(&input);
+        assert_eq!(shown.chars().count(), INPUT_PREVIEW_CHARS + 1);
+        assert!(shown.ends_with('…'));
+        assert_eq!(preview(&Value::String("a\nb".to_string())), "a …");
+    }
+}
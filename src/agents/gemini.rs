@@ -1,10 +1,11 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Stdio;
+use std::sync::Mutex;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
-use super::{Agent, AgentProcess};
+use super::{Agent, AgentProcess, Capabilities};
 
 /// Gemini CLI agent backend.
 ///
@@ -13,20 +14,45 @@ use super::{Agent, AgentProcess};
 /// An optional `--model MODEL` override is supported.
 pub struct GeminiAgent {
     model: Option<String>,
+    /// The `--flags` of the last run's command line.
+    flags: Mutex<Vec<String>>,
 }
 
 impl GeminiAgent {
     pub fn new(model: Option<String>) -> Self {
-        Self { model }
+        Self {
+            model,
+            flags: Mutex::new(Vec::new()),
+        }
     }
 }
 
 impl Agent for GeminiAgent {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn is_available(&self) -> bool {
         super::check_binary_available("gemini")
     }
 
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess> {
+    fn passed_flags(&self) -> Vec<String> {
+        self.flags.lock().unwrap().clone()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
         let mut cmd = Command::new("gemini");
 
         // Use `-p ""` so the actual prompt comes via stdin (avoids E2BIG)
@@ -36,6 +62,9 @@ impl Agent for GeminiAgent {
             cmd.arg("--model").arg(model);
         }
 
+        *self.flags.lock().unwrap() = super::flags_of(&cmd);
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
         cmd.current_dir(workdir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -52,6 +81,6 @@ impl Agent for GeminiAgent {
             let _ = stdin.shutdown().await;
         });
 
-        Ok(AgentProcess { child })
+        Ok(child.into())
     }
 }
@@ -0,0 +1,153 @@
+//! MCP servers passed through to the agent CLIs that can start them
+//! (`[agents.<name>.mcp]`), so a loop can use project tools such as a
+//! database or a browser. Claude Code takes them as `--mcp-config` JSON,
+//! Codex as `-c mcp_servers.<name>.…` overrides.
+
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::config::McpServer;
+
+/// Check every configured server before the run starts, rather than have
+/// the agent fail on it each iteration.
+pub fn check(servers_by_agent: &HashMap<String, Vec<McpServer>>) -> Result<()> {
+    for (agent, servers) in servers_by_agent {
+        let mut seen = Vec::new();
+        for server in servers {
+            let name = &server.name;
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                bail!("[agents.{agent}.mcp] server name `{name}` must be letters, digits, `_`, or `-`");
+            }
+            if seen.contains(&name) {
+                bail!("[agents.{agent}.mcp] lists server `{name}` twice");
+            }
+            seen.push(name);
+            match (&server.command, &server.url) {
+                (Some(_), None) => {}
+                (None, Some(_)) if server.args.is_empty() && server.env.is_empty() => {}
+                (None, Some(_)) => {
+                    bail!("[agents.{agent}.mcp] server `{name}` has a url, so args and env don't apply")
+                }
+                _ => bail!("[agents.{agent}.mcp] server `{name}` needs either a command or a url"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `--mcp-config` JSON for Claude Code.
+pub fn claude_config(servers: &[McpServer]) -> String {
+    let servers: Map<String, Value> = servers
+        .iter()
+        .map(|server| {
+            let entry = match (&server.command, &server.url) {
+                (Some(command), _) => json!({
+                    "command": command,
+                    "args": server.args,
+                    "env": server.env,
+                }),
+                (None, url) => json!({ "type": "http", "url": url }),
+            };
+            (server.name.clone(), entry)
+        })
+        .collect();
+    json!({ "mcpServers": servers }).to_string()
+}
+
+/// The `-c key=value` overrides for Codex. Values are TOML, which accepts
+/// JSON strings and string arrays as they are.
+pub fn codex_overrides(servers: &[McpServer]) -> Vec<String> {
+    let mut overrides = Vec::new();
+    for server in servers {
+        let key = format!("mcp_servers.{}", server.name);
+        if let Some(command) = &server.command {
+            overrides.push(format!("{key}.command={}", json!(command)));
+            if !server.args.is_empty() {
+                overrides.push(format!("{key}.args={}", json!(server.args)));
+            }
+            if !server.env.is_empty() {
+                let env: Vec<String> = server
+                    .env
+                    .iter()
+                    .map(|(name, value)| format!("{} = {}", json!(name), json!(value)))
+                    .collect();
+                overrides.push(format!("{key}.env={{ {} }}", env.join(", ")));
+            }
+        } else if let Some(url) = &server.url {
+            overrides.push(format!("{key}.url={}", json!(url)));
+        }
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn servers() -> Vec<McpServer> {
+        vec![
+            McpServer {
+                name: "db".to_string(),
+                command: Some("npx".to_string()),
+                args: vec!["-y".to_string(), "pg-mcp".to_string()],
+                env: BTreeMap::from([("PGHOST".to_string(), "localhost".to_string())]),
+                url: None,
+            },
+            McpServer {
+                name: "docs".to_string(),
+                url: Some("https://mcp.example.com/mcp".to_string()),
+                ..McpServer::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn servers_render_for_each_cli() {
+        let config: Value = serde_json::from_str(&claude_config(&servers())).expect("json");
+        assert_eq!(config["mcpServers"]["db"]["args"], json!(["-y", "pg-mcp"]));
+        assert_eq!(config["mcpServers"]["db"]["env"]["PGHOST"], "localhost");
+        assert_eq!(
+            config["mcpServers"]["docs"],
+            json!({ "type": "http", "url": "https://mcp.example.com/mcp" })
+        );
+
+        assert_eq!(
+            codex_overrides(&servers()),
+            [
+                r#"mcp_servers.db.command="npx""#,
+                r#"mcp_servers.db.args=["-y","pg-mcp"]"#,
+                r#"mcp_servers.db.env={ "PGHOST" = "localhost" }"#,
+                r#"mcp_servers.docs.url="https://mcp.example.com/mcp""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_servers_are_rejected_up_front() {
+        let mut by_agent = HashMap::from([("claude".to_string(), servers())]);
+        assert!(check(&by_agent).is_ok());
+
+        by_agent.get_mut("claude").unwrap()[1].name = "db".to_string();
+        let err = check(&by_agent).expect_err("duplicate name");
+        assert!(err.to_string().contains("lists server `db` twice"), "{err}");
+
+        by_agent.get_mut("claude").unwrap()[1].name = "my docs".to_string();
+        assert!(check(&by_agent).is_err());
+
+        by_agent.get_mut("claude").unwrap()[1] = McpServer {
+            name: "empty".to_string(),
+            ..McpServer::default()
+        };
+        let err = check(&by_agent).expect_err("no command or url");
+        assert!(
+            err.to_string().contains("needs either a command or a url"),
+            "{err}"
+        );
+    }
+}
@@ -1,34 +1,213 @@
+mod aider;
 mod api;
+mod chaos;
 mod claude;
 mod codex;
+mod command;
+pub mod drift;
+mod events;
 mod gemini;
+pub mod mcp;
+mod ollama;
 mod opencode;
+mod process;
+mod sandbox;
+mod slots;
 
-pub use api::ApiAgent;
+pub use aider::AiderAgent;
+pub use api::{ApiAgent, API_PROTOCOLS};
+pub use chaos::Chaos;
 pub use claude::ClaudeAgent;
 pub use codex::CodexAgent;
+pub use command::CommandAgent;
+pub use events::{AgentEvent, EventFormat};
 pub use gemini::GeminiAgent;
+pub use ollama::OllamaAgent;
 pub use opencode::OpenCodeAgent;
+pub use process::AgentProcess;
+pub use sandbox::Sandbox;
+pub use slots::AgentSlots;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
-use tokio::process::Child;
+use tokio::process::Command;
 
-/// A spawned agent process with attached stdio handles.
-pub struct AgentProcess {
-    pub child: Child,
-}
+use crate::config::McpServer;
 
 /// Trait implemented by every agent backend (Claude Code, Gemini CLI, Codex, …).
 ///
 /// `spawn` is intentionally synchronous — tokio's `Command::spawn()` doesn't need
-/// to be awaited. Only the *waiting* for the child and reading its output are async.
+/// to be awaited, and HTTP backends start their request on a tokio task. Only
+/// the *waiting* for the agent and reading its output are async.
 pub trait Agent: Send + Sync {
+    /// The name the agent is selected by (`--agent`, `[agents.<name>]`).
+    fn name(&self) -> &str;
+
+    /// What this backend supports, for callers that would otherwise match
+    /// on its name.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Timeouts that suit how this backend reports progress, for runs
+    /// without `--timeout` / `--stall-timeout`.
+    fn profile(&self) -> AgentProfile {
+        AgentProfile::default()
+    }
+
     /// Return `true` if the agent binary is on PATH and appears runnable.
     fn is_available(&self) -> bool;
 
+    /// The `--flags` the last spawn passed the CLI, so that flag warnings
+    /// from the tools the agent runs aren't pinned on it. Empty for backends
+    /// without a command line of Ralph's.
+    fn passed_flags(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     /// Spawn the agent with the given prompt, returning the live process handle.
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess>;
+    /// `env` holds extra variables for this run (`RALPH_TMPDIR`).
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess>;
+
+    /// Exact token usage reported for the last spawned run, for backends whose
+    /// output includes it. Call once the process has exited.
+    fn take_usage(&self) -> Option<UsageReport> {
+        None
+    }
+
+    /// Usage the backend printed in its own output (Codex's `tokens used`
+    /// line), for backends with no other way to report it. `output` is the
+    /// iteration's full log.
+    fn usage_from_output(&self, _output: &str) -> Option<UsageReport> {
+        None
+    }
+
+    /// Make the next `spawn` continue `session` (an id from `session_id`)
+    /// instead of starting cold; `None` starts a new session. Only called
+    /// under `--keep-session`. Backends without sessions ignore it.
+    fn resume_session(&self, _session: Option<&str>) {}
+
+    /// The session the last spawned run can be resumed from. `output` is
+    /// the iteration's full log.
+    fn session_id(&self, _output: &str) -> Option<String> {
+        None
+    }
+
+    /// Make later spawns print the backend's structured event stream
+    /// (`--structured-output`). Backends without one ignore it.
+    fn structured_output(&self, _enabled: bool) {}
+
+    /// Make later spawns unable to change files (`--plan`). Backends with no
+    /// read-only mode (`supports_read_only` unset) ignore it.
+    fn read_only(&self, _enabled: bool) {}
+
+    /// Start later spawns with these MCP servers (`[agents.<name>.mcp]`).
+    /// Backends without MCP support ignore them.
+    fn mcp_servers(&self, _servers: &[McpServer]) {}
+
+    /// The event stream the next spawn prints, or `None` for plain text.
+    fn event_format(&self) -> Option<EventFormat> {
+        None
+    }
+}
+
+/// Features that differ between agent backends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+    /// The backend commits its own edits (aider); Ralph then skips its
+    /// per-task commit instead of committing on top of them.
+    pub manages_own_commits: bool,
+    /// `--model` selects the model the backend runs.
+    pub supports_model_override: bool,
+    /// The backend starts the MCP servers under `[agents.<name>.mcp]`.
+    pub supports_mcp: bool,
+    /// `read_only` keeps the backend from writing files.
+    pub supports_read_only: bool,
+}
+
+/// Default iteration limits for a backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgentProfile {
+    /// Hard limit on one iteration, in seconds.
+    pub timeout_secs: u64,
+    /// How long the agent may go without output before it counts as stalled.
+    pub stall_timeout_secs: u64,
+}
+
+impl Default for AgentProfile {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 600,
+            stall_timeout_secs: 120,
+        }
+    }
+}
+
+/// Usage for one agent run: exact token counts and, for models with known
+/// pricing, the resulting cost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageReport {
+    pub model: String,
+    pub tokens: TokenUsage,
+    pub cost_usd: Option<f64>,
+}
+
+/// Token counts for one agent run, as reported by the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+    /// Tokens the backend reports only as a total (Codex CLI), not split
+    /// into input and output.
+    #[serde(default)]
+    pub unsplit_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.unsplit_tokens += other.unsplit_tokens;
+    }
+
+    /// Every token counted, whichever way the backend split them.
+    pub fn total(&self) -> u64 {
+        self.input_tokens
+            + self.output_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+            + self.unsplit_tokens
+    }
+}
+
+/// Start the agent as the leader of a new process group, so a timeout or
+/// cancel can signal everything it launched (shells, test runners), not just
+/// the agent binary itself.
+pub fn own_process_group(cmd: &mut Command) {
+    cmd.process_group(0);
+}
+
+/// The `--flags` on `cmd`'s command line, without their `=value`.
+pub fn flags_of(cmd: &Command) -> Vec<String> {
+    cmd.as_std()
+        .get_args()
+        .filter_map(|arg| arg.to_str())
+        .filter(|arg| arg.starts_with("--"))
+        .map(|arg| arg.split('=').next().unwrap_or(arg).to_string())
+        .collect()
 }
 
 /// Check if an agent binary is reachable by trying to run it directly.
@@ -44,22 +223,171 @@ pub fn check_binary_available(name: &str) -> bool {
         .unwrap_or(false)
 }
 
-/// Build the concrete agent implementation for the given name.
+/// Reasoning-effort levels accepted by `--reasoning-effort`.
+pub const REASONING_EFFORTS: &[&str] = &["low", "medium", "high"];
+
+/// Sampling controls for one agent invocation. Each backend maps what it can
+/// onto its own flags and ignores the rest (see `Sampling::unsupported_by`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sampling {
+    /// One of `REASONING_EFFORTS`.
+    pub reasoning_effort: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+impl Sampling {
+    pub fn new(reasoning_effort: Option<&str>, temperature: Option<f32>) -> Self {
+        Self {
+            reasoning_effort: reasoning_effort.map(str::to_string),
+            temperature,
+        }
+    }
+
+    /// `-c` override for Codex's `model_reasoning_effort` setting.
+    pub fn codex_config(&self) -> Option<String> {
+        let effort = self.reasoning_effort.as_deref()?;
+        Some(format!("model_reasoning_effort=\"{effort}\""))
+    }
+
+    /// Extended-thinking token budget for backends that size reasoning in
+    /// tokens rather than levels (Claude Code, the Anthropic API).
+    pub fn thinking_budget(&self) -> Option<u32> {
+        match self.reasoning_effort.as_deref()? {
+            "low" => Some(4_000),
+            "medium" => Some(10_000),
+            _ => Some(24_000),
+        }
+    }
+
+    /// Flags set here that `agent` has no way to honour.
+    pub fn unsupported_by(&self, agent: &str) -> Vec<&'static str> {
+        let (effort, temperature) = match agent {
+            "codex" | "claude" => (true, false),
+            "api" => (true, true),
+            "ollama" => (false, true),
+            _ => (false, false),
+        };
+        let mut flags = Vec::new();
+        if self.reasoning_effort.is_some() && !effort {
+            flags.push("--reasoning-effort");
+        }
+        if self.temperature.is_some() && !temperature {
+            flags.push("--temperature");
+        }
+        flags
+    }
+
+    /// Print a warning for each flag `agent` will ignore.
+    pub fn warn_unsupported(&self, agent: &str) {
+        for flag in self.unsupported_by(agent) {
+            eprintln!("⚠️   {agent} has no equivalent of {flag}; ignoring it");
+        }
+    }
+}
+
+/// Build the concrete agent implementation for the given name. Names other
+/// than the built-in backends resolve to `commands`, the custom agents from
+/// `[agents.<name>] command` in ralph.toml.
 pub fn create_agent(
     name: &str,
     model: Option<String>,
     api_url: Option<String>,
     api_key: Option<String>,
+    api_protocol: &str,
+    sampling: &Sampling,
+    commands: &HashMap<String, String>,
 ) -> Result<Box<dyn Agent>> {
     match name {
-        "claude" => Ok(Box::new(ClaudeAgent::new(model))),
+        "claude" => Ok(Box::new(ClaudeAgent::new(model, sampling.clone()))),
         "gemini" => Ok(Box::new(GeminiAgent::new(model))),
-        "codex" => Ok(Box::new(CodexAgent::new(model))),
+        "codex" => Ok(Box::new(CodexAgent::new(model, sampling.clone()))),
         "opencode" => Ok(Box::new(OpenCodeAgent::new(model))),
-        "api" => Ok(Box::new(ApiAgent::new(api_url, api_key, model)?)),
-        other => anyhow::bail!(
-            "Unknown agent '{}'. Supported agents: claude, gemini, codex, opencode, api",
-            other
-        ),
+        "aider" => Ok(Box::new(AiderAgent::new(model))),
+        "api" => Ok(Box::new(
+            ApiAgent::new(api_url, api_key, model, api_protocol, sampling.clone())
+                .map_err(crate::error::RalphError::Config)?,
+        )),
+        "ollama" => Ok(Box::new(
+            OllamaAgent::new(api_url, model, sampling.clone())
+                .map_err(crate::error::RalphError::Config)?,
+        )),
+        other => match commands.get(other) {
+            Some(template) => Ok(Box::new(CommandAgent::new(
+                other.to_string(),
+                template.clone(),
+                model,
+            ))),
+            None => Err(crate::error::RalphError::Config(anyhow::anyhow!(
+                "Unknown agent '{}'. Supported agents: claude, gemini, codex, opencode, aider, api, ollama, \
+                 or a custom [agents.{}] command in ralph.toml",
+                other,
+                other
+            ))
+            .into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_reports_flags_each_backend_ignores() {
+        let sampling = Sampling::new(Some("high"), Some(0.0));
+        assert_eq!(sampling.unsupported_by("codex"), vec!["--temperature"]);
+        assert_eq!(sampling.unsupported_by("claude"), vec!["--temperature"]);
+        assert!(sampling.unsupported_by("api").is_empty());
+        assert_eq!(sampling.unsupported_by("ollama"), vec!["--reasoning-effort"]);
+        assert_eq!(
+            sampling.unsupported_by("gemini"),
+            vec!["--reasoning-effort", "--temperature"]
+        );
+        assert!(Sampling::default().unsupported_by("gemini").is_empty());
+
+        assert_eq!(
+            sampling.codex_config().as_deref(),
+            Some("model_reasoning_effort=\"high\"")
+        );
+        assert_eq!(
+            Sampling::new(Some("low"), None).thinking_budget(),
+            Some(4_000)
+        );
+        assert_eq!(Sampling::default().thinking_budget(), None);
+    }
+
+    fn create(name: &str, commands: &HashMap<String, String>) -> Result<Box<dyn Agent>> {
+        create_agent(
+            name,
+            None,
+            None,
+            None,
+            "anthropic",
+            &Sampling::default(),
+            commands,
+        )
+    }
+
+    #[test]
+    fn agents_report_their_name_and_capabilities() {
+        let agent = |name| create(name, &HashMap::new()).unwrap();
+        // `api` is left out: creating it needs an API key.
+        for name in ["claude", "gemini", "codex", "opencode", "aider", "ollama"] {
+            assert_eq!(agent(name).name(), name);
+            assert!(agent(name).capabilities().supports_model_override, "{name}");
+        }
+        assert!(agent("aider").capabilities().manages_own_commits);
+        assert!(!agent("codex").capabilities().manages_own_commits);
+    }
+
+    #[test]
+    fn custom_agent_names_resolve_to_config_commands() {
+        let commands = HashMap::from([("mytool".to_string(), "sh -c true".to_string())]);
+        let agent = create("mytool", &commands).unwrap();
+        assert!(agent.is_available());
+        assert_eq!(agent.name(), "mytool");
+
+        let err = create("othertool", &commands).err().expect("unknown agent");
+        assert!(err.to_string().contains("[agents.othertool] command"), "{err}");
     }
 }
@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWriteExt, DuplexStream};
+
+use super::{Agent, AgentProcess, AgentProfile, Capabilities, Sampling, TokenUsage, UsageReport};
+
+/// Agent backed by a local Ollama server's `/api/chat` endpoint.
+///
+/// The server is `--api-url`, else `$OLLAMA_HOST`, else
+/// `http://localhost:11434`. The model comes from `--model` (default `llama3`)
+/// and must already be pulled.
+///
+/// Ollama streams one JSON object per line; the message text is printed as it
+/// arrives so stall and timeout detection work as for CLI agents. The final
+/// `"done":true` object carries token counts and is kept for `take_usage`.
+///
+/// `--temperature` maps to the `temperature` option; there is no equivalent of
+/// `--reasoning-effort`.
+pub struct OllamaAgent {
+    base_url: String,
+    model: String,
+    sampling: Sampling,
+    client: reqwest::Client,
+    /// The `"done":true` object of the last run.
+    done: Arc<Mutex<Option<String>>>,
+}
+
+const DEFAULT_HOST: &str = "http://localhost:11434";
+
+impl OllamaAgent {
+    pub fn new(
+        base_url: Option<String>,
+        model: Option<String>,
+        sampling: Sampling,
+    ) -> Result<Self> {
+        if let Some(t) = sampling.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                anyhow::bail!(
+                    "--temperature must be between 0 and 2 for the ollama agent (got {t})"
+                );
+            }
+        }
+
+        let base_url = base_url
+            .or_else(|| std::env::var("OLLAMA_HOST").ok())
+            .map(|host| normalize_host(&host))
+            .unwrap_or_else(|| DEFAULT_HOST.to_string());
+        let model = model.unwrap_or_else(|| "llama3".to_string());
+
+        Ok(Self {
+            base_url,
+            model,
+            sampling,
+            client: reqwest::Client::builder()
+                .build()
+                .context("Failed to create HTTP client for ollama agent")?,
+            done: Arc::default(),
+        })
+    }
+
+    /// The `/api/chat` request for `prompt`.
+    fn request_body(&self, prompt: &str) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "stream": true,
+            "messages": [{ "role": "user", "content": prompt }],
+        });
+        if let Some(t) = self.sampling.temperature {
+            body["options"] = serde_json::json!({ "temperature": t });
+        }
+        body
+    }
+}
+
+/// `OLLAMA_HOST` is often a bare `host:port`; the URL needs a scheme.
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("http://{host}")
+    }
+}
+
+/// Token counts from the final `"done":true` object of a response.
+fn parse_usage(done: &str) -> Option<TokenUsage> {
+    let event: serde_json::Value = serde_json::from_str(done.trim()).ok()?;
+    Some(TokenUsage {
+        input_tokens: event["prompt_eval_count"].as_u64()?,
+        output_tokens: event["eval_count"].as_u64().unwrap_or(0),
+        ..TokenUsage::default()
+    })
+}
+
+/// Send `request` and echo the streamed message text to `stdout`. Returns the
+/// exit code; errors are written to `stderr`.
+async fn stream_chat(
+    request: reqwest::RequestBuilder,
+    done: &Mutex<Option<String>>,
+    stdout: &mut DuplexStream,
+    stderr: &mut DuplexStream,
+) -> i32 {
+    let mut response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let _ = stderr
+                .write_all(format!("Ollama request failed: {e}\n").as_bytes())
+                .await;
+            return 1;
+        }
+    };
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v["error"].as_str().map(str::to_string))
+            .unwrap_or(body);
+        let _ = stderr
+            .write_all(format!("Ollama error ({status}): {message}\n").as_bytes())
+            .await;
+        return 1;
+    }
+
+    let mut failed = false;
+    let mut pending = Vec::new();
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = stderr
+                    .write_all(format!("Ollama stream failed: {e}\n").as_bytes())
+                    .await;
+                return 1;
+            }
+        };
+        pending.extend_from_slice(&chunk);
+        // Each streamed line looks like
+        //   {"model":"llama3","message":{"role":"assistant","content":"Hi"},"done":false}
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let Ok(event) = serde_json::from_slice::<serde_json::Value>(&line) else {
+                continue;
+            };
+            if let Some(error) = event["error"].as_str() {
+                let _ = stderr.write_all(format!("{error}\n").as_bytes()).await;
+                failed = true;
+                continue;
+            }
+            if let Some(text) = event["message"]["content"].as_str() {
+                let _ = stdout.write_all(text.as_bytes()).await;
+            }
+            if event["done"] == true {
+                if let Ok(mut done) = done.lock() {
+                    *done = Some(String::from_utf8_lossy(&line).into_owned());
+                }
+            }
+        }
+    }
+    let _ = stdout.write_all(b"\n").await;
+    i32::from(failed)
+}
+
+impl Agent for OllamaAgent {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            ..Capabilities::default()
+        }
+    }
+
+    /// A local model can take minutes to load before its first token, and
+    /// generates slower than a hosted one.
+    fn profile(&self) -> AgentProfile {
+        AgentProfile {
+            timeout_secs: 900,
+            stall_timeout_secs: 300,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        // The HTTP client is built in; reachability shows on the first request.
+        true
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        _workdir: &Path,
+        _env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        // Start each run with no usage recorded.
+        if let Ok(mut done) = self.done.lock() {
+            *done = None;
+        }
+        let request = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&self.request_body(prompt));
+        let done = self.done.clone();
+        Ok(AgentProcess::task(|mut stdout, mut stderr| async move {
+            stream_chat(request, &done, &mut stdout, &mut stderr).await
+        }))
+    }
+
+    fn take_usage(&self) -> Option<UsageReport> {
+        let done = self.done.lock().ok()?.take()?;
+        Some(UsageReport {
+            model: self.model.clone(),
+            tokens: parse_usage(&done)?,
+            // Local models have no per-token price.
+            cost_usd: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn ollama_agent_resolves_host_and_model() {
+        let agent = OllamaAgent::new(
+            Some("127.0.0.1:11500/".to_string()),
+            None,
+            Sampling::default(),
+        )
+        .unwrap();
+        assert_eq!(agent.base_url, "http://127.0.0.1:11500");
+        assert_eq!(agent.model, "llama3");
+        assert_eq!(
+            normalize_host("https://gpu-box:11434"),
+            "https://gpu-box:11434"
+        );
+
+        let body = agent.request_body("hi");
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert!(body.get("options").is_none());
+
+        let warm = OllamaAgent::new(
+            None,
+            Some("qwen2.5-coder".to_string()),
+            Sampling::new(None, Some(0.7)),
+        )
+        .unwrap();
+        assert_eq!(
+            warm.request_body("hi")["options"]["temperature"],
+            serde_json::json!(0.7f32)
+        );
+        assert!(OllamaAgent::new(None, None, Sampling::new(None, Some(3.0))).is_err());
+    }
+
+    #[test]
+    fn usage_comes_from_the_done_object() {
+        let done = r#"{"model":"llama3","done":true,"prompt_eval_count":812,"eval_count":95}"#;
+        assert_eq!(
+            parse_usage(done),
+            Some(TokenUsage {
+                input_tokens: 812,
+                output_tokens: 95,
+                ..TokenUsage::default()
+            })
+        );
+        assert!(parse_usage("{\"done\":true}").is_none());
+    }
+
+    #[tokio::test]
+    async fn streams_decoded_message_text_from_the_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the JSON body's closing brace has arrived.
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).expect("read request");
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = concat!(
+                r#"{"model":"llama3","message":{"role":"assistant","content":"Use \u003cdiv\u003e \u0026 say \"hi\"\n"},"done":false}"#,
+                "\n",
+                r#"{"model":"llama3","message":{"role":"assistant","content":"<promise>COMPLETE</promise>"},"done":false}"#,
+                "\n",
+                r#"{"model":"llama3","message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":12,"eval_count":7}"#,
+                "\n",
+            );
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .expect("write response");
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let agent =
+            OllamaAgent::new(Some(format!("http://{addr}")), None, Sampling::default()).unwrap();
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let proc = agent
+            .spawn("Build the login page", dir.path(), &[])
+            .expect("spawn");
+        let output = proc.wait_with_output().await.expect("wait");
+
+        let request = server.join().expect("server thread");
+        assert!(request.starts_with("POST /api/chat "), "{request}");
+        assert!(request.contains(r#""content":"Build the login page""#));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "Use <div> & say \"hi\"\n<promise>COMPLETE</promise>\n"
+        );
+        let usage = agent.take_usage().expect("usage");
+        assert_eq!(
+            (usage.tokens.input_tokens, usage.tokens.output_tokens),
+            (12, 7)
+        );
+    }
+}
@@ -1,30 +1,56 @@
 use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Stdio;
+use std::sync::Mutex;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
-use super::{Agent, AgentProcess};
+use super::{Agent, AgentProcess, Capabilities};
 
 /// OpenCode CLI agent backend.
 ///
 /// Pipes the prompt via stdin to avoid hitting the OS ARG_MAX limit.
 pub struct OpenCodeAgent {
     model: Option<String>,
+    /// The `--flags` of the last run's command line.
+    flags: Mutex<Vec<String>>,
 }
 
 impl OpenCodeAgent {
     pub fn new(model: Option<String>) -> Self {
-        Self { model }
+        Self {
+            model,
+            flags: Mutex::new(Vec::new()),
+        }
     }
 }
 
 impl Agent for OpenCodeAgent {
+    fn name(&self) -> &str {
+        "opencode"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_model_override: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn is_available(&self) -> bool {
         super::check_binary_available("opencode")
     }
 
-    fn spawn(&self, prompt: &str, workdir: &Path) -> Result<AgentProcess> {
+    fn passed_flags(&self) -> Vec<String> {
+        self.flags.lock().unwrap().clone()
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
         let mut cmd = Command::new("opencode");
 
         cmd.arg("run");
@@ -33,6 +59,9 @@ impl Agent for OpenCodeAgent {
             cmd.arg("--model").arg(model);
         }
 
+        *self.flags.lock().unwrap() = super::flags_of(&cmd);
+        super::own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
         cmd.current_dir(workdir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -49,6 +78,6 @@ impl Agent for OpenCodeAgent {
             let _ = stdin.shutdown().await;
         });
 
-        Ok(AgentProcess { child })
+        Ok(child.into())
     }
 }
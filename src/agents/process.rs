@@ -0,0 +1,177 @@
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+use std::future::Future;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Output};
+use tokio::io::{AsyncRead, AsyncReadExt, DuplexStream};
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+/// One output stream of a running agent.
+pub type AgentOutput = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Buffer of the in-memory pipes behind a task agent's output.
+const PIPE_BUFFER: usize = 64 * 1024;
+
+/// A running agent: a child process (CLI backends), or a tokio task writing
+/// to in-memory pipes (HTTP backends). Either way the orchestrator reads
+/// `stdout` and `stderr` line by line and waits for an exit status, so stall
+/// detection and timeouts work the same for both.
+pub struct AgentProcess {
+    pub stdout: Option<AgentOutput>,
+    pub stderr: Option<AgentOutput>,
+    runner: Runner,
+}
+
+enum Runner {
+    Child(Child),
+    Task {
+        handle: JoinHandle<i32>,
+        /// Kept once the task is joined; a `JoinHandle` can't be awaited twice.
+        status: Option<ExitStatus>,
+    },
+}
+
+impl From<Child> for AgentProcess {
+    fn from(mut child: Child) -> Self {
+        Self {
+            stdout: child.stdout.take().map(|s| Box::new(s) as AgentOutput),
+            stderr: child.stderr.take().map(|s| Box::new(s) as AgentOutput),
+            runner: Runner::Child(child),
+        }
+    }
+}
+
+impl AgentProcess {
+    /// Run `task` in the background as the agent. It gets writers for stdout
+    /// and stderr and returns the exit code; output ends when it returns.
+    pub fn task<F, Fut>(task: F) -> Self
+    where
+        F: FnOnce(DuplexStream, DuplexStream) -> Fut,
+        Fut: Future<Output = i32> + Send + 'static,
+    {
+        let (stdout, stdout_writer) = tokio::io::duplex(PIPE_BUFFER);
+        let (stderr, stderr_writer) = tokio::io::duplex(PIPE_BUFFER);
+        Self {
+            stdout: Some(Box::new(stdout)),
+            stderr: Some(Box::new(stderr)),
+            runner: Runner::Task {
+                handle: tokio::spawn(task(stdout_writer, stderr_writer)),
+                status: None,
+            },
+        }
+    }
+
+    /// The agent's process group (its PID, as agents lead their own group);
+    /// `None` for a task, or once the child has been reaped.
+    pub fn process_group(&self) -> Option<u32> {
+        match &self.runner {
+            Runner::Child(child) => child.id(),
+            Runner::Task { .. } => None,
+        }
+    }
+
+    /// Wait for the agent to finish.
+    pub async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        match &mut self.runner {
+            Runner::Child(child) => child.wait().await,
+            Runner::Task { handle, status } => {
+                if let Some(status) = status {
+                    return Ok(*status);
+                }
+                let joined = match handle.await {
+                    Ok(code) => ExitStatus::from_raw((code & 0xff) << 8),
+                    // Aborted or panicked: report it like a killed process.
+                    Err(_) => ExitStatus::from_raw(Signal::SIGKILL as i32),
+                };
+                *status = Some(joined);
+                Ok(joined)
+            }
+        }
+    }
+
+    /// Stop the agent. A child's whole process group is signalled: with a
+    /// grace period `first` goes first so the agent can flush partial output,
+    /// and whatever is left is SIGKILLed. A task is aborted at once.
+    pub async fn terminate(&mut self, first: Signal, grace_secs: u64) {
+        let child = match &mut self.runner {
+            Runner::Child(child) => child,
+            Runner::Task { handle, .. } => {
+                handle.abort();
+                let _ = self.wait().await;
+                return;
+            }
+        };
+        let Some(pid) = child.id() else {
+            return; // already exited and reaped
+        };
+        let group = Pid::from_raw(pid as i32);
+        if grace_secs > 0 && killpg(group, first).is_ok() {
+            let _ = tokio::time::timeout(Duration::from_secs(grace_secs), child.wait()).await;
+        }
+        // Also reaches children that outlived the agent itself.
+        if killpg(group, Signal::SIGKILL).is_err() {
+            let _ = child.kill().await;
+        }
+        let _ = child.wait().await;
+    }
+
+    /// Wait for the agent while collecting all of its output.
+    pub async fn wait_with_output(mut self) -> std::io::Result<Output> {
+        async fn read_all(output: Option<AgentOutput>) -> std::io::Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            if let Some(mut output) = output {
+                output.read_to_end(&mut buf).await?;
+            }
+            Ok(buf)
+        }
+        let (stdout, stderr) =
+            tokio::try_join!(read_all(self.stdout.take()), read_all(self.stderr.take()))?;
+        let status = self.wait().await?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn task_output_and_exit_code_look_like_a_process() {
+        let proc = AgentProcess::task(|mut stdout, mut stderr| async move {
+            let _ = stdout.write_all(b"hello\n").await;
+            let _ = stderr.write_all(b"warning\n").await;
+            3
+        });
+        let output = proc.wait_with_output().await.expect("wait");
+        assert_eq!(output.stdout, b"hello\n");
+        assert_eq!(output.stderr, b"warning\n");
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn terminating_a_task_ends_its_output() {
+        let mut proc = AgentProcess::task(|mut stdout, _stderr| async move {
+            let _ = stdout.write_all(b"started\n").await;
+            std::future::pending::<()>().await;
+            0
+        });
+        let mut stdout = proc.stdout.take().expect("stdout");
+        let mut started = [0u8; 8];
+        stdout.read_exact(&mut started).await.expect("read");
+        assert_eq!(&started, b"started\n");
+        proc.terminate(Signal::SIGTERM, 30).await;
+
+        let mut rest = Vec::new();
+        stdout.read_to_end(&mut rest).await.expect("read to end");
+        assert!(rest.is_empty());
+        let status = proc.wait().await.expect("wait again");
+        assert_eq!(status.code(), None);
+    }
+}
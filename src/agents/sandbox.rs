@@ -0,0 +1,296 @@
+//! `--sandbox docker[:image]`: every command an agent backend launches runs
+//! in a throwaway container that sees only the workdir, with no network
+//! unless `--sandbox-allow` names hosts. Those hosts are the only names the
+//! container can resolve; it is a DNS allowlist, not an egress filter.
+//!
+//! The API and Ollama agents make their HTTP requests from Ralph itself, so
+//! they reach their server whatever the container's network.
+//!
+//! Backends aren't changed: the wrapped agent's `spawn` gets a `PATH` whose
+//! first entry holds shims named after the CLIs (and `sh`, for the command
+//! and API agents' tools) that hand their arguments to `docker run`.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{Agent, AgentProcess, AgentProfile, Capabilities, EventFormat, UsageReport};
+use crate::shell::quote;
+
+/// Image used by a bare `--sandbox docker`.
+pub const DEFAULT_IMAGE: &str = "ralph-sandbox";
+
+/// Programs run through the container.
+const SHIMMED: &[&str] = &["claude", "codex", "gemini", "opencode", "aider", "sh"];
+
+/// Host variables passed into the container when set, besides the ones Ralph
+/// gives the agent for the run.
+const PASSED_ENV: &[&str] = &[
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "GEMINI_API_KEY",
+    "GOOGLE_API_KEY",
+    "OPENROUTER_API_KEY",
+    "MAX_THINKING_TOKENS",
+];
+
+/// Names the run's extra variables for the shim, which can't know them
+/// in advance.
+const ENV_LIST_VAR: &str = "RALPH_SANDBOX_ENV";
+
+/// A prepared sandbox: the image and the shim directory. Cheap to clone;
+/// the shims are deleted when the last clone is dropped.
+#[derive(Clone)]
+pub struct Sandbox {
+    image: String,
+    shims: Arc<tempfile::TempDir>,
+}
+
+impl Sandbox {
+    /// Parse `--sandbox` (`docker` or `docker:<image>`), resolve the
+    /// `--sandbox-allow` hosts, and write the shims.
+    pub async fn new(spec: &str, workdir: &Path, allow: &[String]) -> Result<Self> {
+        let image = parse_spec(spec)?;
+        if !super::check_binary_available("docker") {
+            bail!("--sandbox {spec} needs `docker` on PATH");
+        }
+        let mut hosts = Vec::new();
+        for host in allow {
+            let addr = tokio::net::lookup_host((host.as_str(), 443))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.find(|a| a.is_ipv4()))
+                .with_context(|| format!("--sandbox-allow {host}: could not resolve it"))?;
+            hosts.push((host.clone(), addr.ip().to_string()));
+        }
+        let shims = tempfile::Builder::new()
+            .prefix("ralph-sandbox-")
+            .tempdir()
+            .context("Failed to create the sandbox shim directory")?;
+        for program in SHIMMED {
+            write_shim(shims.path(), program, &image, workdir, &hosts)?;
+        }
+        Ok(Self {
+            image,
+            shims: Arc::new(shims),
+        })
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// Run `agent` inside this sandbox.
+    pub fn wrap(&self, agent: Box<dyn Agent>) -> Box<dyn Agent> {
+        Box::new(SandboxedAgent {
+            inner: agent,
+            sandbox: self.clone(),
+        })
+    }
+
+    fn shim_dir(&self) -> &Path {
+        self.shims.path()
+    }
+}
+
+/// The image named by a `--sandbox` value.
+pub fn parse_spec(spec: &str) -> Result<String> {
+    match spec.split_once(':') {
+        None if spec == "docker" => Ok(DEFAULT_IMAGE.to_string()),
+        Some(("docker", image)) if !image.is_empty() => Ok(image.to_string()),
+        _ => bail!("--sandbox must be `docker` or `docker:<image>`, got `{spec}`"),
+    }
+}
+
+/// An agent whose processes run through the sandbox's shims.
+pub struct SandboxedAgent {
+    inner: Box<dyn Agent>,
+    sandbox: Sandbox,
+}
+
+impl Agent for SandboxedAgent {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn profile(&self) -> AgentProfile {
+        self.inner.profile()
+    }
+
+    /// The agent's CLI lives in the image, so only docker has to be here.
+    fn is_available(&self) -> bool {
+        super::check_binary_available("docker")
+    }
+
+    fn spawn(
+        &self,
+        prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        let host_path = std::env::var_os("PATH").unwrap_or_default();
+        let mut dirs = vec![self.sandbox.shim_dir().to_path_buf()];
+        dirs.extend(std::env::split_paths(&host_path));
+        let path: PathBuf = std::env::join_paths(dirs)
+            .context("PATH holds a directory that can't be joined")?
+            .into();
+        let names: Vec<&str> = env.iter().map(|(name, _)| name.as_str()).collect();
+
+        let mut env = env.to_vec();
+        env.push(("PATH".to_string(), path.display().to_string()));
+        env.push((ENV_LIST_VAR.to_string(), names.join(" ")));
+        self.inner.spawn(prompt, workdir, &env)
+    }
+
+    fn take_usage(&self) -> Option<UsageReport> {
+        self.inner.take_usage()
+    }
+
+    fn usage_from_output(&self, output: &str) -> Option<UsageReport> {
+        self.inner.usage_from_output(output)
+    }
+
+    fn resume_session(&self, session: Option<&str>) {
+        self.inner.resume_session(session)
+    }
+
+    fn session_id(&self, output: &str) -> Option<String> {
+        self.inner.session_id(output)
+    }
+
+    fn structured_output(&self, enabled: bool) {
+        self.inner.structured_output(enabled)
+    }
+
+    fn read_only(&self, enabled: bool) {
+        self.inner.read_only(enabled)
+    }
+
+    fn passed_flags(&self) -> Vec<String> {
+        self.inner.passed_flags()
+    }
+
+    fn event_format(&self) -> Option<EventFormat> {
+        self.inner.event_format()
+    }
+}
+
+fn write_shim(
+    dir: &Path,
+    program: &str,
+    image: &str,
+    workdir: &Path,
+    hosts: &[(String, String)],
+) -> Result<()> {
+    let workdir = quote(&workdir.display().to_string());
+    let network = if hosts.is_empty() {
+        "--network none".to_string()
+    } else {
+        // Listed hosts resolve through /etc/hosts; nothing else resolves.
+        let mut args = "--dns 127.0.0.1".to_string();
+        for (host, ip) in hosts {
+            args.push_str(&format!(" --add-host {}", quote(&format!("{host}:{ip}"))));
+        }
+        args
+    };
+    let script = format!(
+        r#"#!/bin/sh
+# Written by ralph --sandbox: runs `{program}` in {image}.
+env_args=
+for name in {passed} ${list}; do
+  env_args="$env_args -e $name"
+done
+exec docker run --rm -i --init --label ralph.sandbox=1 \
+  --user "$(id -u):$(id -g)" {network} \
+  -e HOME=/tmp $env_args \
+  -v {workdir}:{workdir} -w "$(pwd)" \
+  ${{RALPH_TMPDIR:+-v "$RALPH_TMPDIR:$RALPH_TMPDIR"}} \
+  {quoted_image} {program} "$@"
+"#,
+        passed = PASSED_ENV.join(" "),
+        list = ENV_LIST_VAR,
+        quoted_image = quote(image),
+    );
+    let path = dir.join(program);
+    std::fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::CommandAgent;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn sandbox_spec_names_the_image() {
+        assert_eq!(parse_spec("docker").unwrap(), DEFAULT_IMAGE);
+        assert_eq!(
+            parse_spec("docker:ghcr.io/acme/agents:1.2").unwrap(),
+            "ghcr.io/acme/agents:1.2"
+        );
+        assert!(parse_spec("podman").is_err());
+        assert!(parse_spec("docker:").is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::await_holding_lock)] // PATH stays swapped across the spawn
+    async fn agent_commands_run_through_docker() {
+        let _guard = crate::global_env_lock().lock().expect("lock env mutation");
+        let bin = tempdir().expect("create bin dir");
+        let docker = bin.path().join("docker");
+        fs::write(&docker, "#!/bin/sh\nprintf '%s\\n' \"$@\"\n").expect("write fake docker");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).expect("chmod");
+        }
+        let original_path = std::env::var_os("PATH").unwrap_or_default();
+        let mut dirs = vec![bin.path().to_path_buf()];
+        dirs.extend(std::env::split_paths(&original_path));
+        std::env::set_var("PATH", std::env::join_paths(dirs).expect("join PATH"));
+
+        let work = tempdir().expect("create workdir");
+        let sandbox = Sandbox::new("docker:agents:dev", work.path(), &[]).await;
+        let result = match sandbox {
+            Ok(sandbox) => {
+                let agent = sandbox.wrap(Box::new(CommandAgent::new(
+                    "mytool".to_string(),
+                    "echo hi".to_string(),
+                    None,
+                )));
+                let env = vec![("RALPH_TMPDIR".to_string(), "/tmp/iter".to_string())];
+                match agent.spawn("prompt", work.path(), &env) {
+                    Ok(process) => process.wait_with_output().await.map_err(Into::into),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        std::env::set_var("PATH", original_path);
+
+        let output = result.expect("fake docker runs");
+        let output = String::from_utf8_lossy(&output.stdout);
+        let args: Vec<&str> = output.lines().collect();
+        let workdir = work.path().display().to_string();
+        assert_eq!(args[..2], ["run", "--rm"]);
+        assert!(output.contains("--network\nnone\n"), "{output}");
+        assert!(output.contains("-e\nRALPH_TMPDIR\n"), "{output}");
+        assert!(output.contains("/tmp/iter:/tmp/iter"), "{output}");
+        assert!(
+            output.contains(&format!("-v\n{workdir}:{workdir}\n")),
+            "{output}"
+        );
+        let image = args.iter().position(|a| *a == "agents:dev").expect("image");
+        assert_eq!(args[image + 1..image + 3], ["sh", "-c"]);
+    }
+}
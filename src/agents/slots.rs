@@ -0,0 +1,64 @@
+//! Per-agent session limits shared by every loop in a `ralph watch` session
+//! (`[agents.<name>] max_concurrent` in ralph.toml).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// One semaphore per limited agent. Cloning shares the limits; agents without
+/// a limit are never gated.
+#[derive(Debug, Clone, Default)]
+pub struct AgentSlots {
+    limits: Arc<HashMap<String, (usize, Arc<Semaphore>)>>,
+}
+
+impl AgentSlots {
+    pub fn new(limits: &HashMap<String, usize>) -> Self {
+        let limits = limits
+            .iter()
+            .map(|(agent, &max)| (agent.clone(), (max, Arc::new(Semaphore::new(max.max(1))))))
+            .collect();
+        Self {
+            limits: Arc::new(limits),
+        }
+    }
+
+    /// The configured limit for `agent`, if any.
+    pub fn limit(&self, agent: &str) -> Option<usize> {
+        self.limits.get(agent).map(|(max, _)| *max)
+    }
+
+    /// Whether every session slot for `agent` is taken right now.
+    pub fn is_full(&self, agent: &str) -> bool {
+        self.limits
+            .get(agent)
+            .is_some_and(|(_, sem)| sem.available_permits() == 0)
+    }
+
+    /// Wait for a session slot for `agent`; `None` if it is unlimited.
+    pub async fn acquire(&self, agent: &str) -> Option<OwnedSemaphorePermit> {
+        let (_, sem) = self.limits.get(agent)?;
+        sem.clone().acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn limited_agents_share_their_slots_across_clones() {
+        let slots = AgentSlots::new(&HashMap::from([("claude".to_string(), 1)]));
+        let other_loop = slots.clone();
+
+        let held = slots.acquire("claude").await.expect("limited agent");
+        assert!(other_loop.is_full("claude"));
+        assert!(!other_loop.is_full("codex"));
+        assert!(other_loop.acquire("codex").await.is_none());
+
+        drop(held);
+        assert!(!other_loop.is_full("claude"));
+        assert_eq!(slots.limit("claude"), Some(1));
+        assert_eq!(slots.limit("codex"), None);
+    }
+}
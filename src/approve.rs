@@ -0,0 +1,128 @@
+//! `ralph approve <TASK_ID>… [--reject]` — resolve tasks queued for human review.
+//!
+//! A task lands in `needs_review` when the agent claims completion below
+//! `--min-confidence` and no `--verify-cmd` is configured.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::Path;
+
+use crate::cli::ApproveArgs;
+use crate::state::{StateManager, TaskStatus};
+
+pub fn approve_tasks(args: ApproveArgs) -> Result<()> {
+    let workdir = args
+        .workdir
+        .as_deref()
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+
+    let state = match args.name.as_deref() {
+        Some(name) => StateManager::new_named(&workdir, name)?,
+        None => StateManager::new(&workdir)?,
+    };
+
+    let mut task_list = state
+        .load_tasks()?
+        .context("No tasks.json found — nothing to approve")?;
+
+    for id in &args.task_ids {
+        let task = task_list
+            .tasks
+            .iter_mut()
+            .find(|t| &t.id == id)
+            .with_context(|| format!("Unknown task: {id}"))?;
+        if task.status != TaskStatus::NeedsReview {
+            anyhow::bail!("Task {id} is {}, not awaiting review", task.status);
+        }
+
+        if args.reject {
+            task.status = TaskStatus::Pending;
+            println!("↩️   {} — {} sent back to pending", task.id, task.title);
+        } else {
+            task.status = TaskStatus::Complete;
+            task.completed_at = Some(Utc::now());
+            println!("✅  {} — {} approved", task.id, task.title);
+        }
+    }
+
+    task_list.updated_at = Utc::now();
+    state.save_tasks(&task_list)?;
+
+    let verb = if args.reject { "rejected" } else { "approved" };
+    state.append_progress(&format!(
+        "**Review** — {} {verb} by a human",
+        args.task_ids.join(", ")
+    ))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Task, TaskList};
+    use tempfile::tempdir;
+
+    fn seed(workdir: &Path, status: TaskStatus) {
+        let state = StateManager::new(workdir).expect("create state manager");
+        let now = Utc::now();
+        state
+            .save_tasks(&TaskList {
+                version: 1,
+                prd_path: "prd.md".to_string(),
+                created_at: now,
+                updated_at: now,
+                tasks: vec![Task {
+                    id: "T1".to_string(),
+                    title: "Review me".to_string(),
+                    description: "body".to_string(),
+                    priority: 1,
+                    status,
+                    ..Default::default()
+                }],
+            })
+            .expect("save tasks");
+    }
+
+    fn args(workdir: &Path, reject: bool) -> ApproveArgs {
+        ApproveArgs {
+            task_ids: vec!["T1".to_string()],
+            name: None,
+            reject,
+            workdir: Some(workdir.to_path_buf()),
+        }
+    }
+
+    fn status_of(workdir: &Path) -> TaskStatus {
+        StateManager::new(workdir)
+            .expect("create state manager")
+            .load_tasks()
+            .expect("load tasks")
+            .expect("tasks exist")
+            .tasks[0]
+            .status
+            .clone()
+    }
+
+    #[test]
+    fn approve_and_reject_resolve_needs_review() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path(), TaskStatus::NeedsReview);
+        approve_tasks(args(dir.path(), false)).expect("approve");
+        assert_eq!(status_of(dir.path()), TaskStatus::Complete);
+
+        seed(dir.path(), TaskStatus::NeedsReview);
+        approve_tasks(args(dir.path(), true)).expect("reject");
+        assert_eq!(status_of(dir.path()), TaskStatus::Pending);
+    }
+
+    #[test]
+    fn approve_rejects_tasks_not_awaiting_review() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path(), TaskStatus::Pending);
+        let err = approve_tasks(args(dir.path(), false)).expect_err("should refuse");
+        assert!(err.to_string().contains("not awaiting review"));
+    }
+}
@@ -0,0 +1,214 @@
+//! `ralph-fake-agent` — a scripted stand-in for codex, claude, gemini, or
+//! opencode, for testing ralph configurations, hooks, and the TUI without real
+//! agent credentials.
+//!
+//! Put it on PATH under an agent's name and point `RALPH_FAKE_SCRIPT` at a
+//! TOML script of `[[step]]` tables. Each invocation plays the next step; once
+//! the script runs out, the last step repeats. The step counter lives in
+//! `RALPH_FAKE_STATE` (default: `<script>.step`), so delete that file to start
+//! over. Set `RALPH_FAKE_PROMPT_LOG` to append every prompt received to a file.
+//!
+//! Built only with `--features fake-agent`.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SCRIPT_ENV: &str = "RALPH_FAKE_SCRIPT";
+const STATE_ENV: &str = "RALPH_FAKE_STATE";
+const PROMPT_LOG_ENV: &str = "RALPH_FAKE_PROMPT_LOG";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    #[serde(rename = "step")]
+    steps: Vec<Step>,
+}
+
+/// One scripted agent invocation.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Step {
+    /// Written to stdout one line at a time.
+    stdout: String,
+    stderr: String,
+    /// Pause before any output (a long one exercises `--timeout`).
+    delay_ms: u64,
+    /// Pause between stdout lines (exercises streaming and `--stall-timeout`).
+    line_delay_ms: u64,
+    exit_code: i32,
+    /// Files written relative to the working directory, like an agent's edits.
+    files: BTreeMap<String, String>,
+}
+
+impl Script {
+    fn parse(raw: &str) -> Result<Self> {
+        let script: Script = toml::from_str(raw)?;
+        anyhow::ensure!(!script.steps.is_empty(), "script has no [[step]] tables");
+        Ok(script)
+    }
+
+    fn step(&self, index: usize) -> &Step {
+        &self.steps[index.min(self.steps.len() - 1)]
+    }
+}
+
+fn main() {
+    match run() {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("ralph-fake-agent: {e:#}");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn run() -> Result<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // ralph probes agents with `--version` to see whether they are installed.
+    if args.first().map(String::as_str) == Some("--version") {
+        println!("ralph-fake-agent {}", env!("CARGO_PKG_VERSION"));
+        return Ok(0);
+    }
+
+    let script_path = std::env::var_os(SCRIPT_ENV)
+        .map(PathBuf::from)
+        .with_context(|| format!("{SCRIPT_ENV} is not set"))?;
+    let raw = std::fs::read_to_string(&script_path)
+        .with_context(|| format!("Failed to read {}", script_path.display()))?;
+    let script =
+        Script::parse(&raw).with_context(|| format!("Invalid {}", script_path.display()))?;
+
+    let state_path = std::env::var_os(STATE_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let mut name = script_path.as_os_str().to_owned();
+            name.push(".step");
+            PathBuf::from(name)
+        });
+    let index = next_step(&state_path)?;
+
+    let prompt = read_prompt(&args);
+    if let Some(log) = std::env::var_os(PROMPT_LOG_ENV) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log)
+            .context("Failed to open prompt log")?;
+        writeln!(file, "=== step {} ===\n{prompt}", index + 1)?;
+    }
+
+    let cwd = std::env::current_dir().context("Cannot resolve working directory")?;
+    play(
+        script.step(index),
+        &cwd,
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    )
+}
+
+/// Read and bump the step counter; returns the zero-based step to play.
+fn next_step(state_path: &Path) -> Result<usize> {
+    let index = match std::fs::read_to_string(state_path) {
+        Ok(raw) => raw.trim().parse().unwrap_or(0),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e).context("Failed to read step counter"),
+    };
+    std::fs::write(state_path, (index + 1).to_string())
+        .with_context(|| format!("Failed to write {}", state_path.display()))?;
+    Ok(index)
+}
+
+/// The prompt arrives on stdin (every built-in agent pipes it) or, failing
+/// that, as the last argument.
+fn read_prompt(args: &[String]) -> String {
+    let mut prompt = String::new();
+    if !std::io::stdin().is_terminal() {
+        let _ = std::io::stdin().read_to_string(&mut prompt);
+    }
+    if prompt.is_empty() {
+        prompt = args.last().cloned().unwrap_or_default();
+    }
+    prompt
+}
+
+fn play(step: &Step, cwd: &Path, out: &mut impl Write, err: &mut impl Write) -> Result<i32> {
+    std::thread::sleep(Duration::from_millis(step.delay_ms));
+
+    for (path, content) in &step.files {
+        let path = cwd.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    for (i, line) in step.stdout.lines().enumerate() {
+        if i > 0 {
+            std::thread::sleep(Duration::from_millis(step.line_delay_ms));
+        }
+        writeln!(out, "{line}")?;
+        out.flush()?;
+    }
+    err.write_all(step.stderr.as_bytes())?;
+    Ok(step.exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn last_step_repeats_once_the_script_runs_out() {
+        let script = Script::parse(
+            "[[step]]\nstdout = \"first\"\n\n[[step]]\nstdout = \"second\"\nexit_code = 1\n",
+        )
+        .expect("parse");
+        assert_eq!(script.step(0).stdout, "first");
+        assert_eq!(script.step(1).exit_code, 1);
+        assert_eq!(script.step(7).stdout, "second");
+
+        assert!(Script::parse("").is_err());
+        assert!(Script::parse("[[step]]\nstdot = \"typo\"\n").is_err());
+    }
+
+    #[test]
+    fn step_counter_advances_per_invocation() {
+        let dir = tempdir().expect("create tempdir");
+        let state = dir.path().join("script.toml.step");
+        assert_eq!(next_step(&state).expect("first"), 0);
+        assert_eq!(next_step(&state).expect("second"), 1);
+        assert_eq!(std::fs::read_to_string(&state).expect("read"), "2");
+    }
+
+    #[test]
+    fn play_writes_files_and_output() {
+        let dir = tempdir().expect("create tempdir");
+        let step = Step {
+            stdout: "editing\n<promise>COMPLETE</promise>\n".to_string(),
+            stderr: "warning: fake\n".to_string(),
+            exit_code: 3,
+            files: BTreeMap::from([("src/lib.rs".to_string(), "pub fn f() {}\n".to_string())]),
+            ..Step::default()
+        };
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = play(&step, dir.path(), &mut out, &mut err).expect("play");
+
+        assert_eq!(code, 3);
+        assert_eq!(
+            String::from_utf8(out).expect("utf8"),
+            "editing\n<promise>COMPLETE</promise>\n"
+        );
+        assert_eq!(String::from_utf8(err).expect("utf8"), "warning: fake\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/lib.rs")).expect("read"),
+            "pub fn f() {}\n"
+        );
+    }
+}
@@ -1,18 +1,34 @@
 use clap::{Args, Parser, Subcommand};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
-use crate::state::SharedLoopStatus;
+use crate::agents::{AgentSlots, API_PROTOCOLS, REASONING_EFFORTS};
+use crate::config::{
+    ChaosConfig, ContextConfig, FallbackConfig, FormatConfig, McpServer, OutputConfig,
+    ReportConfig, SchedulerConfig,
+};
+use crate::export::EXPORT_FORMATS;
+use crate::orchestrator::DIRTY_CHECKOUT_ACTIONS;
+use crate::state::{SharedLoopStatus, FORMAT_NAMES};
+use crate::timefmt::TIMESTAMP_ZONES;
+use crate::watch::EXISTING_RUN_ACTIONS;
 
 /// Ralph — Orchestrates AI coding agents in isolated loops to implement PRD features
 #[derive(Parser)]
 #[command(name = "ralph", version, about, long_about = None)]
 pub struct Cli {
+    /// Print errors as a JSON object (`{"error": {"kind", "exit_code", "message"}}`) on stderr
+    #[arg(long, global = true)]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+// Parsed once at startup, so the size of `RunArgs` doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Create a starter PRD template in the current directory
@@ -31,8 +47,173 @@ pub enum Commands {
     Logs(LogsArgs),
     /// Gracefully stop a running loop (or all loops)
     Stop(StopArgs),
+    /// Approve (or reject) tasks queued for human review
+    Approve(ApproveArgs),
+    /// Open a task's latest log, diff, and changed files in your editor
+    Open(OpenArgs),
+    /// Re-run the verify command and re-open completed tasks that no longer pass
+    Verify(VerifyArgs),
+    /// Convert tasks.json for an issue tracker or a human-run checklist
+    Export(ExportArgs),
     /// Manage reusable PRD templates
     Template(TemplateArgs),
+    /// Inspect and validate webhook configuration
+    Hooks(HooksArgs),
+    /// Register a recurring run (executed by `ralph daemon`)
+    Schedule(ScheduleArgs),
+    /// Run registered schedules in the foreground
+    Daemon(DaemonArgs),
+    /// Run ralph on another machine over SSH
+    Remote(RemoteArgs),
+    /// Store tokens and API keys in the OS keyring for `*_ref` keys in ralph.toml
+    Secret(SecretArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RemoteArgs {
+    #[command(subcommand)]
+    pub command: RemoteCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RemoteCommands {
+    /// Copy a PRD to a host, run it there, and stream the output back
+    Run(RemoteRunArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RemoteRunArgs {
+    /// SSH destination, e.g. user@host or a Host alias from ~/.ssh/config
+    pub host: String,
+
+    /// PRD file to copy to the host and run
+    #[arg(long)]
+    pub prd: PathBuf,
+
+    /// Project directory on the host, created if missing [default: ~/ plus
+    /// the local workdir's name]
+    #[arg(long, value_name = "DIR")]
+    pub remote_dir: Option<String>,
+
+    /// ralph binary on the host [default: found on its PATH, in
+    /// ~/.cargo/bin, or in ~/.local/bin; installed there when missing]
+    #[arg(long, value_name = "PATH")]
+    pub ralph_bin: Option<String>,
+
+    /// Option passed to ssh as `-o OPTION` (repeatable), e.g. Port=2222
+    #[arg(short = 'o', long = "ssh-option", value_name = "OPTION")]
+    pub ssh_options: Vec<String>,
+
+    /// Local project directory (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
+
+    /// Arguments for `ralph run` on the host, after `--`
+    #[arg(last = true, value_name = "RUN_ARGS")]
+    pub run_args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ScheduleArgs {
+    /// PRD to run on each firing (or use --template)
+    #[arg(conflicts_with = "template")]
+    pub prd: Option<PathBuf>,
+
+    /// Five-field cron expression in local time, e.g. "0 2 * * *"
+    #[arg(long, required_unless_present_any = ["list", "remove"])]
+    pub cron: Option<String>,
+
+    /// Saved template to run on each firing
+    #[arg(long)]
+    pub template: Option<String>,
+
+    /// Schedule name (defaults to the template name or PRD file stem)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Agent to use (defaults to `ralph run`'s default)
+    #[arg(long)]
+    pub agent: Option<String>,
+
+    /// Project directory the run happens in (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
+
+    /// List registered schedules with their next run time
+    #[arg(long, conflicts_with_all = ["cron", "remove"])]
+    pub list: bool,
+
+    /// Remove the named schedule
+    #[arg(long, value_name = "NAME", conflicts_with = "cron")]
+    pub remove: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    /// Start anything due this minute, wait for it, and exit (for system cron)
+    #[arg(long)]
+    pub once: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    pub command: HooksCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HooksCommands {
+    /// Send a synthetic `ping` event and report HTTP status and latency
+    Test {
+        /// Webhook URL (defaults to `[hooks] url` from ralph.toml)
+        #[arg(long)]
+        url: Option<String>,
+        /// Bearer token (defaults to `[hooks] token` from ralph.toml)
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Run a local receiver that prints the events sent to it, for
+    /// developing and debugging hook integrations
+    Serve {
+        /// Port to listen on (localhost only)
+        #[arg(long, default_value = "9999")]
+        port: u16,
+        /// Reject requests without this bearer token (defaults to `[hooks]
+        /// token` from ralph.toml)
+        #[arg(long)]
+        token: Option<String>,
+        /// Append each event to this file as a line of JSON
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Also show each event as a desktop notification
+        #[arg(long)]
+        desktop: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct SecretArgs {
+    #[command(subcommand)]
+    pub command: SecretCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretCommands {
+    /// Store a secret, typed at a prompt or piped on stdin
+    Set {
+        /// Keyring entry as service/name, e.g. ralph/hook
+        reference: String,
+    },
+    /// Remove a stored secret
+    Delete {
+        /// Keyring entry as service/name, e.g. ralph/hook
+        reference: String,
+    },
+    /// Check that a secret is stored and readable, without printing it
+    Check {
+        /// Keyring entry as service/name, e.g. ralph/hook
+        reference: String,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -45,13 +226,15 @@ pub struct TemplateArgs {
 pub enum TemplateCommands {
     /// Save a PRD file as a reusable template
     Save {
-        /// Template name (e.g. "code-review")
+        /// Template name (e.g. "code-review", or "team/code-review" in a namespace)
         name: String,
         /// Path to the PRD markdown file to save
         prd: PathBuf,
     },
     /// List all saved templates
     List {
+        /// Only list templates in this namespace (e.g. "team")
+        namespace: Option<String>,
         /// Show full descriptions (not just names)
         #[arg(short, long)]
         verbose: bool,
@@ -66,18 +249,41 @@ pub enum TemplateCommands {
         /// Template name
         name: String,
     },
+    /// Pack templates into a .tar.gz to share
+    Export {
+        /// Archive to write (e.g. templates.tar.gz)
+        out: PathBuf,
+        /// Templates or namespaces to include (default: all)
+        names: Vec<String>,
+    },
+    /// Add templates from a tarball, a directory, or a git URL
+    Import {
+        /// Where to import from (default: [templates] source in ralph.toml)
+        source: Option<String>,
+        /// Namespace to import into (default: [templates] namespace)
+        #[arg(long)]
+        namespace: Option<String>,
+        /// Replace templates that already exist
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 #[derive(Args, Debug)]
 pub struct RunArgs {
     /// Path to the PRD markdown file (or use --template)
-    #[arg(required_unless_present = "template")]
+    #[arg(required_unless_present_any = ["template", "tasks"])]
     pub prd: Option<PathBuf>,
 
     /// Use a saved template instead of a PRD file (see: ralph template list)
     #[arg(long, conflicts_with = "prd")]
     pub template: Option<String>,
 
+    /// Build tasks.json from a Markdown `- [ ]` checklist instead of having an
+    /// agent parse the PRD. The checklist doubles as the PRD when none is given.
+    #[arg(long, value_name = "CHECKLIST")]
+    pub tasks: Option<PathBuf>,
+
     /// Agent to use (claude, gemini, codex)
     #[arg(long, default_value = "codex")]
     pub agent: String,
@@ -86,30 +292,236 @@ pub struct RunArgs {
     #[arg(long)]
     pub model: Option<String>,
 
+    /// Reasoning effort for implementation iterations (codex, claude, api)
+    #[arg(long, value_parser = REASONING_EFFORTS.to_vec())]
+    pub reasoning_effort: Option<String>,
+
+    /// Sampling temperature for implementation iterations (api only)
+    #[arg(long)]
+    pub temperature: Option<f32>,
+
+    /// Reasoning effort used when parsing the PRD into tasks
+    #[arg(long, value_parser = REASONING_EFFORTS.to_vec())]
+    pub parse_reasoning_effort: Option<String>,
+
+    /// Sampling temperature used when parsing the PRD (e.g. 0 for repeatable
+    /// task lists; api and ollama)
+    #[arg(long)]
+    pub parse_temperature: Option<f32>,
+
     /// Maximum number of iterations before stopping
     #[arg(long, default_value = "20")]
     pub max_iterations: u32,
 
-    /// Per-iteration timeout in seconds (hard kill)
-    #[arg(long, default_value = "600")]
-    pub timeout: u64,
+    /// When the tasks left outnumber the iterations left and tasks are being
+    /// finished steadily, raise --max-iterations once by this factor (e.g. 1.5x)
+    #[arg(long, value_name = "FACTOR")]
+    pub auto_extend: Option<String>,
 
-    /// Kill agent if it produces no output for this many seconds (default: 120 = 2 min)
-    #[arg(long, default_value = "120")]
-    pub stall_timeout: u64,
+    /// Per-iteration timeout in seconds (hard kill) [default: the agent's
+    /// profile, 600 for most agents]
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Kill agent if it produces no output for this many seconds [default:
+    /// the agent's profile, 120 for most agents]
+    #[arg(long, value_name = "SECS")]
+    pub stall_timeout: Option<u64>,
+
+    /// Warn (terminal, TUI, hooks) once an iteration has used this fraction of
+    /// --timeout; 0 disables the warning
+    #[arg(long, default_value = "0.8", value_name = "FRACTION")]
+    pub timeout_warn: f64,
+
+    /// On timeout or stall, send SIGINT to the agent's process group and
+    /// wait this many seconds before SIGKILL (0 = SIGKILL immediately)
+    #[arg(long, default_value = "0", value_name = "SECS")]
+    pub kill_grace: u64,
+
+    /// On stop (Ctrl+C, `ralph stop`, SIGTERM), send SIGTERM to the agent's process
+    /// group and wait this many seconds before SIGKILL (0 = SIGKILL immediately)
+    #[arg(long, default_value = "30", value_name = "SECS")]
+    pub stop_grace: u64,
 
     /// Timeout in seconds for PRD parsing (falls back to next available agent)
     #[arg(long, default_value = "120")]
     pub parse_timeout: u64,
 
+    /// Corrective re-prompts when the parse agent returns invalid tasks, before
+    /// falling back to the next agent
+    #[arg(long, default_value = "2", value_name = "N")]
+    pub parse_retries: u32,
+
     /// Maximum consecutive failures before circuit-breaking
     #[arg(long, default_value = "3")]
     pub max_failures: u32,
 
+    /// Failed attempts at one task before it is marked failed and the run
+    /// moves on without it and the tasks that depend on it
+    #[arg(long, default_value = "3", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: u32,
+
+    /// Run only these tasks (comma-separated IDs); each one's dependencies
+    /// must be complete or selected too
+    #[arg(
+        long,
+        value_name = "IDS",
+        value_delimiter = ',',
+        conflicts_with = "from"
+    )]
+    pub only: Vec<String>,
+
+    /// Run this task and every task after it in tasks.json
+    #[arg(long, value_name = "ID")]
+    pub from: Option<String>,
+
+    /// Leave these tasks out of the run (comma-separated IDs)
+    #[arg(long, value_name = "IDS", value_delimiter = ',')]
+    pub skip: Vec<String>,
+
+    /// After each iteration that finishes its task, show the diff and wait
+    /// for y (commit), n (revert and retry), e (edit), or q (stop)
+    #[arg(long)]
+    pub interactive: bool,
+
+    /// Ask the agent for a unified diff instead of letting it write files;
+    /// Ralph shows it for y (apply), n (reject), e (edit), or q (stop), then
+    /// applies and commits it
+    #[arg(long, conflicts_with = "interactive")]
+    pub plan: bool,
+
+    /// With --plan, apply every plan that applies cleanly without asking
+    #[arg(long, requires = "plan")]
+    pub auto_approve: bool,
+
+    /// Run up to N agents at once on tasks whose dependencies are complete,
+    /// sharing the workdir; each batch is committed once all of them finish
+    #[arg(long, default_value = "1", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub task_parallel: u32,
+
+    /// Stop before the next iteration once the run has used this many tokens
+    /// (counted from agents that report usage)
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<u64>,
+
+    /// Stop before the next iteration once the run's estimated cost reaches
+    /// this many dollars (counted from models with known pricing)
+    #[arg(long, value_name = "USD")]
+    pub max_cost_usd: Option<f64>,
+
+    /// Keep each iteration prompt under about this many tokens by cutting the
+    /// PRD and progress log; the task itself is always sent in full
+    #[arg(long, value_name = "N")]
+    pub max_prompt_tokens: Option<usize>,
+
+    /// Queue a finished task for review instead of committing it when its
+    /// diff touches more than this many files
+    #[arg(long, value_name = "N")]
+    pub max_files_changed: Option<usize>,
+
+    /// Queue a finished task for review instead of committing it when its
+    /// diff adds and removes more than this many lines (git only)
+    #[arg(long, value_name = "N")]
+    pub max_lines_changed: Option<usize>,
+
     /// Project directory (defaults to current directory)
     #[arg(long)]
     pub workdir: Option<PathBuf>,
 
+    /// Monorepo subproject (relative to --workdir) to confine the agent to.
+    /// Scopes the prompt instructions, --verify-cmd, and auto-commits.
+    #[arg(long, value_name = "DIR")]
+    pub project: Option<PathBuf>,
+
+    /// Path outside --project the agent may still change (repeatable; root
+    /// lockfiles are always allowed). Other out-of-scope edits are reverted.
+    #[arg(long, value_name = "PATH")]
+    pub allow_path: Vec<PathBuf>,
+
+    /// Discard any existing tasks.json (backed up first) and re-parse the PRD
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Re-parse the PRD but keep the progress of existing tasks.json tasks
+    /// the new ones match (by title or description); the old list is backed up
+    #[arg(long, conflicts_with_all = ["fresh", "tasks", "replay"])]
+    pub reparse: bool,
+
+    /// On a failed iteration, save git status, the diff, recently changed
+    /// files, an environment fingerprint, and the prompt to
+    /// .ralph/failures/iteration-N/
+    #[arg(long)]
+    pub failure_snapshots: bool,
+
+    /// Outside a git repository, mirror the workdir into .ralph/snapshots/
+    /// before each iteration and restore it when the iteration errors.
+    /// Ignored in git repositories.
+    #[arg(long)]
+    pub snapshots: bool,
+
+    /// In a git repository, leave the working tree as it is when an iteration
+    /// errors, times out, or ends without finishing its task instead of
+    /// rolling it back to a checkpoint taken before the iteration
+    #[arg(long)]
+    pub no_rollback: bool,
+
+    /// Keep a "## Ralph progress" checklist at the end of the PRD file, updated
+    /// as tasks finish. Modifies the PRD; not applied to templates.
+    #[arg(long)]
+    pub annotate_prd: bool,
+
+    /// Time zone of progress.md timestamps: utc, or local (with its UTC offset)
+    #[arg(long, default_value = "utc", value_parser = TIMESTAMP_ZONES.to_vec())]
+    pub timestamps: String,
+
+    /// Task list format: json, or toml / json5 (tasks.toml, tasks.json5) for
+    /// hand-editing. An existing list in another format is converted; without
+    /// it the existing list keeps its format, and a new one is json
+    #[arg(long, value_parser = FORMAT_NAMES.to_vec())]
+    pub tasks_format: Option<String>,
+
+    /// Once all tasks are complete, run the agent once more to remove dead code,
+    /// debug prints, and scratch files from the run. Checked with --verify-cmd
+    /// before it is committed; reverted if the check fails.
+    #[arg(long)]
+    pub cleanup_pass: bool,
+
+    /// Continue the agent's session (claude, codex) across iterations on the
+    /// same task instead of starting each one cold
+    #[arg(long)]
+    pub keep_session: bool,
+
+    /// Read the agent's structured event stream (claude, codex) instead of
+    /// its plain text, so tool calls, replies, and errors are told apart
+    #[arg(long)]
+    pub structured_output: bool,
+
+    /// Inject faults (delayed output, killed iterations, rate limits) into
+    /// this share of iterations, to rehearse hooks and failure handling
+    #[arg(
+        long,
+        hide = true,
+        value_name = "RATE",
+        num_args = 0..=1,
+        default_missing_value = "0.25"
+    )]
+    pub chaos: Option<f64>,
+
+    /// Formatter or linter to run on each completed task's changed files
+    /// before its commit (repeatable); `{files}` expands to those files
+    #[arg(long, value_name = "CMD")]
+    pub format_cmd: Vec<String>,
+
+    /// Save the starting task list and every iteration's prompt, output, and
+    /// exit status to DIR as a replayable fixture
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Re-run a `--record` fixture: start from its task list and play back
+    /// its agent output instead of running an agent
+    #[arg(long, value_name = "DIR", conflicts_with = "fresh")]
+    pub replay: Option<PathBuf>,
+
     /// Git branch name for this loop (auto-generated from PRD name if omitted)
     #[arg(long)]
     pub branch: Option<String>,
@@ -118,6 +530,33 @@ pub struct RunArgs {
     #[arg(long)]
     pub no_branch: bool,
 
+    /// Branch to create the loop's branch from (default: the current HEAD).
+    /// Fetched from origin first, with a warning if the local copy is behind
+    #[arg(long, conflicts_with = "no_branch")]
+    pub base: Option<String>,
+
+    /// Between tasks, rebase the loop's branch onto the latest --base
+    #[arg(long, requires = "base")]
+    pub rebase: bool,
+
+    /// What to do when tracked files have uncommitted changes and Ralph has
+    /// to switch branches: ask (abort when not a terminal), stash them,
+    /// commit them to the current branch, or abort
+    #[arg(long, default_value = "ask", value_parser = DIRTY_CHECKOUT_ACTIONS.to_vec())]
+    pub on_dirty: String,
+
+    /// Run the agent in a container: `docker` (image `ralph-sandbox`) or
+    /// `docker:<image>`. Only the workdir is mounted, with no network unless
+    /// `--sandbox-allow` names hosts
+    #[arg(long, value_name = "docker[:IMAGE]")]
+    pub sandbox: Option<String>,
+
+    /// Host the sandboxed agent can resolve, e.g. api.anthropic.com
+    /// (repeatable). Turns on the network and pins the host in /etc/hosts;
+    /// other names don't resolve, but connections by IP are not blocked
+    #[arg(long, value_name = "HOST", requires = "sandbox")]
+    pub sandbox_allow: Vec<String>,
+
     /// Stream agent output to the terminal in real time
     #[arg(long, short)]
     pub verbose: bool,
@@ -134,19 +573,64 @@ pub struct RunArgs {
     #[arg(long)]
     pub hook_token: Option<String>,
 
+    /// Attach this many lines of the iteration log (redacted, at most 4 KB)
+    /// to task_failed and circuit_breaker webhook events
+    #[arg(long, value_name = "N")]
+    pub hook_log_lines: Option<usize>,
+
     /// Send progress notifications to OpenClaw channel (e.g. discord:CHANNEL_ID)
-    /// Requires OPENCLAW_HOOKS_TOKEN env var.
+    /// Requires OPENCLAW_HOOKS_TOKEN env var, or `[notify] token_ref` in ralph.toml.
     #[arg(long)]
     pub notify: Option<String>,
 
-    /// Base URL for API agent (default: https://api.anthropic.com, or http://localhost:3456 for Max proxy)
+    /// OpenClaw gateway token (from `[notify] token_ref` in ralph.toml).
+    #[arg(skip)]
+    pub notify_token: Option<String>,
+
+    /// Base URL for API agent (default: https://api.anthropic.com, or http://localhost:3456 for Max proxy;
+    /// $OPENAI_BASE_URL, else https://api.openai.com/v1 with --api-protocol openai)
+    /// or ollama agent (default: $OLLAMA_HOST, else http://localhost:11434)
     #[arg(long)]
     pub api_url: Option<String>,
 
-    /// API key for API agent (default: reads ANTHROPIC_API_KEY env var)
+    /// API key for API agent (default: reads ANTHROPIC_API_KEY, or OPENAI_API_KEY with --api-protocol openai)
     #[arg(long)]
     pub api_key: Option<String>,
 
+    /// OS keyring entry holding the API agent's key (from `[agents.api]
+    /// api_key_ref` in ralph.toml), used when --api-key isn't given.
+    #[arg(skip)]
+    pub api_key_ref: Option<String>,
+
+    /// Wire protocol for API agent: anthropic (Messages API) or openai (Chat Completions,
+    /// for vLLM, LiteLLM, OpenRouter, Groq, ...)
+    #[arg(long, default_value = "anthropic", value_parser = API_PROTOCOLS.to_vec())]
+    pub api_protocol: String,
+
+    /// Completion detectors to stack, in order (token, json, exit_code, confidence,
+    /// tasks_edit, verify). Defaults to `token,confidence,tasks_edit` plus `verify`
+    /// when --verify-cmd is set.
+    #[arg(long, value_delimiter = ',', value_name = "LIST")]
+    pub completion: Vec<String>,
+
+    /// Word the agent prints between `<promise>` tags to claim completion
+    #[arg(long, default_value = "COMPLETE", value_name = "WORD")]
+    pub completion_token: String,
+
+    /// Only auto-accept a completion claim whose self-reported confidence
+    /// (`<promise confidence=0.9>`) is at least this value (0 disables the gate)
+    #[arg(long, default_value = "0", value_name = "0.0-1.0", value_parser = parse_confidence)]
+    pub min_confidence: f64,
+
+    /// Shell command run when a claim falls below --min-confidence; exit 0 accepts it.
+    /// Without it, low-confidence tasks are queued for `ralph approve`.
+    #[arg(long, value_name = "CMD")]
+    pub verify_cmd: Option<String>,
+
+    /// Serve this run's live status as JSON on http://127.0.0.1:<PORT>/status
+    #[arg(long, value_name = "PORT")]
+    pub status_port: Option<u16>,
+
     // ── Internal fields set programmatically by `ralph watch` ─────────────────
     /// Name override for the state directory.
     /// If set, state lives in `.ralph-<state_name>/` instead of `.ralph/`.
@@ -160,6 +644,52 @@ pub struct RunArgs {
     /// Cancellation flag — set to `true` to request a graceful stop.
     #[arg(skip)]
     pub cancel_flag: Option<Arc<AtomicBool>>,
+
+    /// Seconds the TUI operator has to answer before the circuit breaker
+    /// trips; `None` when no one is watching.
+    #[arg(skip)]
+    pub breaker_timeout: Option<u64>,
+
+    /// Per-agent session limits (`[agents.<name>] max_concurrent`), shared
+    /// with the other loops in a watch session.
+    #[arg(skip)]
+    pub agent_slots: Option<AgentSlots>,
+
+    /// Custom agents by name (from `[agents.<name>] command` in ralph.toml).
+    #[arg(skip)]
+    pub agent_commands: HashMap<String, String>,
+
+    /// MCP servers by agent name (from `[agents.<name>.mcp]` in ralph.toml).
+    #[arg(skip)]
+    pub agent_mcp: HashMap<String, Vec<McpServer>>,
+
+    /// Agent output post-processing (from `[output]` in ralph.toml).
+    #[arg(skip)]
+    pub output: OutputConfig,
+
+    /// End-of-run reports such as the changelog (from `[report]` in ralph.toml).
+    #[arg(skip)]
+    pub report: ReportConfig,
+
+    /// Formatters from `[[format]]` in ralph.toml, used when no --format-cmd is given.
+    #[arg(skip)]
+    pub formatters: Vec<FormatConfig>,
+
+    /// `[chaos]` settings from ralph.toml.
+    #[arg(skip)]
+    pub chaos_config: ChaosConfig,
+
+    /// Iterations reserved for high-priority tasks (from `[scheduler]` in ralph.toml).
+    #[arg(skip)]
+    pub scheduler: SchedulerConfig,
+
+    /// Agent fallback hysteresis and cooldown (from `[fallback]` in ralph.toml).
+    #[arg(skip)]
+    pub fallback: FallbackConfig,
+
+    /// The prompt's repository overview (from `[context]` in ralph.toml).
+    #[arg(skip)]
+    pub repo_context: ContextConfig,
 }
 
 #[derive(Args, Debug)]
@@ -180,20 +710,48 @@ pub struct ParseArgs {
     #[arg(long)]
     pub model: Option<String>,
 
+    /// Reasoning effort passed to the parsing agent (codex, claude)
+    #[arg(long, value_parser = REASONING_EFFORTS.to_vec())]
+    pub reasoning_effort: Option<String>,
+
+    /// Sampling temperature for parsing, where the agent supports one
+    #[arg(long)]
+    pub temperature: Option<f32>,
+
     /// Timeout in seconds for PRD parsing (falls back to next available agent)
     #[arg(long, default_value = "120")]
     pub parse_timeout: u64,
 
+    /// Corrective re-prompts when the parse agent returns invalid tasks, before
+    /// falling back to the next agent
+    #[arg(long, default_value = "2", value_name = "N")]
+    pub parse_retries: u32,
+
     /// Write tasks.json to this path instead of printing
     #[arg(long, short)]
     pub output: Option<PathBuf>,
+
+    /// Parse with this many agents (--agent first, then other installed ones)
+    /// and merge the task lists, prompting for tasks they disagree on
+    #[arg(long, default_value = "1", value_name = "N")]
+    pub consensus: usize,
 }
 
 #[derive(Args, Debug)]
 pub struct StatusArgs {
     /// Path to the project directory to check (defaults to current directory)
-    #[arg(long)]
+    #[arg(long, conflicts_with = "all")]
     pub workdir: Option<PathBuf>,
+
+    /// Also scan project roots (`--root` and `[status] roots` in ralph.toml)
+    /// for loops missing from the global registry
+    #[arg(long)]
+    pub all: bool,
+
+    /// Directory whose projects `--all` scans: the directory itself and each
+    /// subdirectory (repeatable)
+    #[arg(long, value_name = "DIR", requires = "all")]
+    pub root: Vec<PathBuf>,
 }
 
 #[derive(Args, Debug)]
@@ -214,30 +772,138 @@ pub struct WatchArgs {
     #[arg(long)]
     pub model: Option<String>,
 
+    /// Reasoning effort for implementation iterations (codex, claude, api)
+    #[arg(long, value_parser = REASONING_EFFORTS.to_vec())]
+    pub reasoning_effort: Option<String>,
+
+    /// Sampling temperature for implementation iterations (api only)
+    #[arg(long)]
+    pub temperature: Option<f32>,
+
+    /// Reasoning effort used when parsing the PRD into tasks
+    #[arg(long, value_parser = REASONING_EFFORTS.to_vec())]
+    pub parse_reasoning_effort: Option<String>,
+
+    /// Sampling temperature used when parsing the PRD (e.g. 0 for repeatable
+    /// task lists; api and ollama)
+    #[arg(long)]
+    pub parse_temperature: Option<f32>,
+
     /// Maximum iterations per loop
     #[arg(long, default_value = "20")]
     pub max_iterations: u32,
 
-    /// Per-iteration timeout in seconds
-    #[arg(long, default_value = "600")]
-    pub timeout: u64,
-
-    /// Stall timeout in seconds (no output → kill)
-    #[arg(long, default_value = "120")]
-    pub stall_timeout: u64,
+    /// Raise a loop's --max-iterations once by this factor (e.g. 1.5x) when
+    /// its tasks outnumber the iterations left and progress is steady
+    #[arg(long, value_name = "FACTOR")]
+    pub auto_extend: Option<String>,
+
+    /// Keep each iteration prompt under about this many tokens by cutting the
+    /// PRD and progress log
+    #[arg(long, value_name = "N")]
+    pub max_prompt_tokens: Option<usize>,
+
+    /// Stop a loop before its next iteration once it has used this many tokens
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<u64>,
+
+    /// Stop a loop before its next iteration once its estimated cost reaches
+    /// this many dollars
+    #[arg(long, value_name = "USD")]
+    pub max_cost_usd: Option<f64>,
+
+    /// Queue a finished task for review instead of committing it when its
+    /// diff touches more than this many files
+    #[arg(long, value_name = "N")]
+    pub max_files_changed: Option<usize>,
+
+    /// Queue a finished task for review instead of committing it when its
+    /// diff adds and removes more than this many lines (git only)
+    #[arg(long, value_name = "N")]
+    pub max_lines_changed: Option<usize>,
+
+    /// Per-iteration timeout in seconds [default: the agent's profile]
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Stall timeout in seconds (no output → kill) [default: the agent's
+    /// profile]
+    #[arg(long, value_name = "SECS")]
+    pub stall_timeout: Option<u64>,
+
+    /// Warn (terminal, TUI, hooks) once an iteration has used this fraction of
+    /// --timeout; 0 disables the warning
+    #[arg(long, default_value = "0.8", value_name = "FRACTION")]
+    pub timeout_warn: f64,
+
+    /// On timeout or stall, send SIGINT to the agent's process group and
+    /// wait this many seconds before SIGKILL (0 = SIGKILL immediately)
+    #[arg(long, default_value = "0", value_name = "SECS")]
+    pub kill_grace: u64,
+
+    /// On stop (Ctrl+C, `ralph stop`, SIGTERM), send SIGTERM to the agent's process
+    /// group and wait this many seconds before SIGKILL (0 = SIGKILL immediately)
+    #[arg(long, default_value = "30", value_name = "SECS")]
+    pub stop_grace: u64,
 
     /// Maximum consecutive failures per loop before circuit-breaking
     #[arg(long, default_value = "3")]
     pub max_failures: u32,
 
+    /// Failed attempts at one task before it is marked failed and the loop
+    /// moves on without it and the tasks that depend on it
+    #[arg(long, default_value = "3", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: u32,
+
+    /// Re-parse each PRD that already has a task list, keeping the progress
+    /// of the tasks the new parse matches
+    #[arg(long)]
+    pub reparse: bool,
+
     /// Shared working directory for all loops (defaults to current directory)
     #[arg(long)]
     pub workdir: Option<PathBuf>,
 
+    /// Monorepo subproject (relative to --workdir) every loop is confined to
+    #[arg(long, value_name = "DIR")]
+    pub project: Option<PathBuf>,
+
+    /// Path outside --project the agents may still change (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub allow_path: Vec<PathBuf>,
+
+    /// Run the agent in a container: `docker` (image `ralph-sandbox`) or
+    /// `docker:<image>`. Only the workdir is mounted, with no network unless
+    /// `--sandbox-allow` names hosts
+    #[arg(long, value_name = "docker[:IMAGE]")]
+    pub sandbox: Option<String>,
+
+    /// Host the sandboxed agent can resolve, e.g. api.anthropic.com
+    /// (repeatable). Turns on the network and pins the host in /etc/hosts;
+    /// other names don't resolve, but connections by IP are not blocked
+    #[arg(long, value_name = "HOST", requires = "sandbox")]
+    pub sandbox_allow: Vec<String>,
+
     /// Disable the TUI dashboard (plain progress output)
     #[arg(long)]
     pub no_tui: bool,
 
+    /// Before a loop trips the circuit breaker, ask in the TUI whether to
+    /// continue, switch agent, or stop; stop if unanswered after SECS (0 never asks)
+    #[arg(long, default_value = "60", value_name = "SECS")]
+    pub breaker_timeout: u64,
+
+    /// Run inside a new tmux session instead of the TUI: a control window
+    /// plus one window following each loop's logs
+    #[arg(long)]
+    pub tmux: bool,
+
+    /// What to do when a loop's .ralph-<slug>/ holds an unfinished run from an
+    /// earlier session: ask (resume when not a terminal), resume, archive it
+    /// to .ralph-archive/ and start over, or abort that loop
+    #[arg(long, default_value = "ask", value_parser = EXISTING_RUN_ACTIONS.to_vec())]
+    pub existing: String,
+
     /// Webhook URL to POST events to
     #[arg(long)]
     pub hook_url: Option<String>,
@@ -246,13 +912,112 @@ pub struct WatchArgs {
     #[arg(long)]
     pub hook_token: Option<String>,
 
+    /// Attach this many lines of the iteration log (redacted, at most 4 KB)
+    /// to task_failed and circuit_breaker webhook events
+    #[arg(long, value_name = "N")]
+    pub hook_log_lines: Option<usize>,
+
     /// Send progress notifications to OpenClaw channel (e.g. discord:CHANNEL_ID)
     #[arg(long)]
     pub notify: Option<String>,
 
+    /// OpenClaw gateway token (from `[notify] token_ref` in ralph.toml).
+    #[arg(skip)]
+    pub notify_token: Option<String>,
+
+    /// OS keyring entry holding the API agent's key (from `[agents.api]
+    /// api_key_ref` in ralph.toml).
+    #[arg(skip)]
+    pub api_key_ref: Option<String>,
+
+    /// Base URL for the api or ollama agent (see `ralph run --help`)
+    #[arg(long)]
+    pub api_url: Option<String>,
+
+    /// Wire protocol for the api agent: anthropic or openai
+    #[arg(long, default_value = "anthropic", value_parser = API_PROTOCOLS.to_vec())]
+    pub api_protocol: String,
+
+    /// Completion detectors to stack, in order (see `ralph run --help`)
+    #[arg(long, value_delimiter = ',', value_name = "LIST")]
+    pub completion: Vec<String>,
+
+    /// Word the agent prints between `<promise>` tags to claim completion
+    #[arg(long, default_value = "COMPLETE", value_name = "WORD")]
+    pub completion_token: String,
+
+    /// Only auto-accept completion claims at or above this confidence (0 disables)
+    #[arg(long, default_value = "0", value_name = "0.0-1.0", value_parser = parse_confidence)]
+    pub min_confidence: f64,
+
+    /// Shell command that verifies low-confidence claims (exit 0 accepts)
+    #[arg(long, value_name = "CMD")]
+    pub verify_cmd: Option<String>,
+
     /// Stream agent output to terminal (only useful with --no-tui)
     #[arg(long, short)]
     pub verbose: bool,
+
+    /// On a failed iteration, save debug context to each loop's
+    /// failures/iteration-N/ directory
+    #[arg(long)]
+    pub failure_snapshots: bool,
+
+    /// Keep a "## Ralph progress" checklist at the end of each PRD file
+    #[arg(long)]
+    pub annotate_prd: bool,
+
+    /// Continue the agent's session (claude, codex) across iterations on the
+    /// same task instead of starting each one cold
+    #[arg(long)]
+    pub keep_session: bool,
+
+    /// Read the agent's structured event stream (claude, codex) instead of
+    /// its plain text, so tool calls, replies, and errors are told apart
+    #[arg(long)]
+    pub structured_output: bool,
+
+    /// Time zone of progress.md timestamps: utc, or local (with its UTC offset)
+    #[arg(long, default_value = "utc", value_parser = TIMESTAMP_ZONES.to_vec())]
+    pub timestamps: String,
+
+    /// Task list format: json, or toml / json5 (tasks.toml, tasks.json5) for
+    /// hand-editing. An existing list in another format is converted; without
+    /// it the existing list keeps its format, and a new one is json
+    #[arg(long, value_parser = FORMAT_NAMES.to_vec())]
+    pub tasks_format: Option<String>,
+
+    /// Serve every loop's live status as JSON on http://127.0.0.1:<PORT>/status
+    #[arg(long, value_name = "PORT")]
+    pub status_port: Option<u16>,
+
+    /// Extra per-agent regexes hidden by the TUI chatter filter (from `[tui.chatter]`).
+    #[arg(skip)]
+    pub chatter_patterns: HashMap<String, Vec<String>>,
+
+    /// Sessions allowed per agent across all loops (from `[agents.<name>] max_concurrent`).
+    #[arg(skip)]
+    pub agent_limits: HashMap<String, usize>,
+
+    /// Custom agents by name (from `[agents.<name>] command`).
+    #[arg(skip)]
+    pub agent_commands: HashMap<String, String>,
+
+    /// MCP servers by agent name (from `[agents.<name>.mcp]`).
+    #[arg(skip)]
+    pub agent_mcp: HashMap<String, Vec<McpServer>>,
+
+    /// Agent output post-processing (from `[output]`).
+    #[arg(skip)]
+    pub output: OutputConfig,
+
+    /// Agent fallback hysteresis and cooldown (from `[fallback]` in ralph.toml).
+    #[arg(skip)]
+    pub fallback: FallbackConfig,
+
+    /// The prompt's repository overview (from `[context]` in ralph.toml).
+    #[arg(skip)]
+    pub repo_context: ContextConfig,
 }
 
 #[derive(Args, Debug)]
@@ -283,10 +1048,139 @@ pub struct StopArgs {
     /// Project directory to search for lock files (defaults to current directory)
     #[arg(long)]
     pub workdir: Option<PathBuf>,
+
+    /// Seconds to wait for each loop to exit after SIGTERM
+    #[arg(long, default_value = "30", value_name = "SECS")]
+    pub timeout: u64,
+
+    /// Send SIGKILL to loops still running after --timeout, and to their
+    /// agents' process groups
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ApproveArgs {
+    /// Task IDs to approve (e.g. T3)
+    #[arg(required = true)]
+    pub task_ids: Vec<String>,
+
+    /// Loop name (PRD filename stem) for `ralph watch` state.
+    /// Omit to use the default .ralph/ directory.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Send the tasks back to pending instead of marking them complete
+    #[arg(long)]
+    pub reject: bool,
+
+    /// Project directory (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct OpenArgs {
+    /// Task ID to open (e.g. T3)
+    pub task_id: String,
+
+    /// Loop name (PRD filename stem) for `ralph watch` state.
+    /// Omit to use the default .ralph/ directory.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Editor command (default: $VISUAL, then $EDITOR, then VS Code's `code`)
+    #[arg(long, value_name = "CMD")]
+    pub editor: Option<String>,
+
+    /// Print the paths instead of opening them
+    #[arg(long)]
+    pub print: bool,
+
+    /// Project directory (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Command to check the tree with; `{task_id}` runs it once per completed
+    /// task, and a task's own `verify` command takes its place (defaults to
+    /// verify_cmd under [completion] in ralph.toml)
+    #[arg(long)]
+    pub verify_cmd: Option<String>,
+
+    /// Loop name (PRD filename stem) for `ralph watch` state.
+    /// Omit to use the default .ralph/ directory.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Project directory (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
+
+    /// Seconds each run of the command may take before it counts as failed
+    #[arg(long, default_value = "600")]
+    pub timeout: u64,
+
+    /// Report the tasks that fail without re-opening them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Output format
+    #[arg(long, default_value = "md-checklist", value_parser = EXPORT_FORMATS.to_vec())]
+    pub format: String,
+
+    /// Loop name (PRD filename stem) for `ralph watch` state.
+    /// Omit to use the default .ralph/ directory.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Write to this file instead of stdout
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Project directory (defaults to current directory)
+    #[arg(long)]
+    pub workdir: Option<PathBuf>,
 }
 
 #[derive(Args, Debug)]
-pub struct InitArgs {}
+pub struct InitArgs {
+    /// Write the PRD here instead of prd.md
+    #[arg(long, short = 'o', value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Replace files that already exist instead of keeping them
+    #[arg(long)]
+    pub force: bool,
+
+    /// Also scaffold ralph.toml, .ralphignore, .gitignore entries for Ralph's
+    /// per-run files, and a ralph-watch.sh script
+    #[arg(long)]
+    pub full: bool,
+
+    /// Draft the PRD from a git revision range (e.g. `main..feature`) instead of
+    /// writing the blank template. An agent summarizes the diff and TODOs into
+    /// the remaining work.
+    #[arg(long, value_name = "RANGE")]
+    pub from_diff: Option<String>,
+
+    /// Agent used to draft the PRD (only with --from-diff)
+    #[arg(long, default_value = "codex", requires = "from_diff")]
+    pub agent: String,
+
+    /// Model override passed to the agent binary
+    #[arg(long, requires = "from_diff")]
+    pub model: Option<String>,
+
+    /// Timeout in seconds for the drafting agent (falls back to next available agent)
+    #[arg(long, default_value = "180", requires = "from_diff")]
+    pub draft_timeout: u64,
+}
 
 #[derive(Args, Debug)]
 pub struct DoctorArgs {
@@ -295,9 +1189,21 @@ pub struct DoctorArgs {
     pub workdir: Option<PathBuf>,
 }
 
+/// `--min-confidence`: a number from 0 to 1.
+fn parse_confidence(value: &str) -> Result<f64, String> {
+    let confidence: f64 = value
+        .parse()
+        .map_err(|_| format!("`{value}` is not a number"))?;
+    if (0.0..=1.0).contains(&confidence) {
+        Ok(confidence)
+    } else {
+        Err(format!("must be between 0.0 and 1.0, got {value}"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Cli, Commands};
+    use super::{Cli, Commands, HooksCommands, RemoteArgs, RemoteCommands, SecretCommands};
     use clap::Parser;
     use std::path::PathBuf;
 
@@ -318,7 +1224,24 @@ mod tests {
         let cli = Cli::try_parse_from(["ralph", "init"]).expect("parse should succeed");
 
         match cli.command {
-            Commands::Init(_args) => {}
+            Commands::Init(args) => {
+                assert!(!args.full && !args.force);
+                assert!(args.output.is_none());
+            }
+            _ => panic!("expected init command"),
+        }
+    }
+
+    #[test]
+    fn init_subcommand_parses_from_diff_range() {
+        let cli = Cli::try_parse_from(["ralph", "init", "--from-diff", "main..feature"])
+            .expect("parse should succeed");
+
+        match cli.command {
+            Commands::Init(args) => {
+                assert_eq!(args.from_diff.as_deref(), Some("main..feature"));
+                assert_eq!(args.agent, "codex");
+            }
             _ => panic!("expected init command"),
         }
     }
@@ -335,6 +1258,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn open_subcommand_parses_task_and_editor() {
+        let cli = Cli::try_parse_from(["ralph", "open", "T3", "--editor", "code -n"])
+            .expect("parse should succeed");
+
+        match cli.command {
+            Commands::Open(args) => {
+                assert_eq!(args.task_id, "T3");
+                assert_eq!(args.editor.as_deref(), Some("code -n"));
+                assert!(!args.print);
+            }
+            _ => panic!("expected open command"),
+        }
+    }
+
+    #[test]
+    fn remote_run_passes_trailing_args_to_ralph_run() {
+        let cli = Cli::try_parse_from([
+            "ralph",
+            "remote",
+            "run",
+            "dev@box",
+            "--prd",
+            "prd.md",
+            "-o",
+            "Port=2222",
+            "--",
+            "--agent",
+            "claude",
+        ])
+        .expect("parse should succeed");
+
+        match cli.command {
+            Commands::Remote(RemoteArgs {
+                command: RemoteCommands::Run(args),
+            }) => {
+                assert_eq!(args.host, "dev@box");
+                assert_eq!(args.prd, PathBuf::from("prd.md"));
+                assert_eq!(args.ssh_options, ["Port=2222"]);
+                assert_eq!(args.run_args, ["--agent", "claude"]);
+            }
+            _ => panic!("expected remote run command"),
+        }
+    }
+
+    #[test]
+    fn secret_set_takes_a_keyring_reference() {
+        let cli = Cli::try_parse_from(["ralph", "secret", "set", "ralph/hook"])
+            .expect("parse should succeed");
+
+        match cli.command {
+            Commands::Secret(args) => match args.command {
+                SecretCommands::Set { reference } => assert_eq!(reference, "ralph/hook"),
+                _ => panic!("expected secret set"),
+            },
+            _ => panic!("expected secret command"),
+        }
+    }
+
     #[test]
     fn run_subcommand_parses_agent_iterations_and_timeout_flags() {
         let cli = Cli::try_parse_from([
@@ -357,7 +1339,7 @@ mod tests {
                 assert_eq!(args.prd, Some(PathBuf::from("prd.md")));
                 assert_eq!(args.agent, "gemini");
                 assert_eq!(args.max_iterations, 5);
-                assert_eq!(args.timeout, 300);
+                assert_eq!(args.timeout, Some(300));
                 assert_eq!(args.parse_timeout, 45);
             }
             _ => panic!("expected run command"),
@@ -385,11 +1367,28 @@ mod tests {
         match cli.command {
             Commands::Status(args) => {
                 assert!(args.workdir.is_none());
+                assert!(!args.all);
             }
             _ => panic!("expected status command"),
         }
     }
 
+    #[test]
+    fn status_all_takes_roots_but_not_workdir() {
+        let cli = Cli::try_parse_from(["ralph", "status", "--all", "--root", "~/code"])
+            .expect("parse should succeed");
+        match cli.command {
+            Commands::Status(args) => {
+                assert!(args.all);
+                assert_eq!(args.root, vec![PathBuf::from("~/code")]);
+            }
+            _ => panic!("expected status command"),
+        }
+
+        assert!(Cli::try_parse_from(["ralph", "status", "--root", "/src"]).is_err());
+        assert!(Cli::try_parse_from(["ralph", "status", "--all", "--workdir", "."]).is_err());
+    }
+
     #[test]
     fn stop_subcommand_parses_all_flag() {
         let cli = Cli::try_parse_from(["ralph", "stop", "--all"]).expect("parse should succeed");
@@ -420,6 +1419,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn min_confidence_must_be_between_zero_and_one() {
+        let cli = Cli::try_parse_from(["ralph", "run", "prd.md", "--min-confidence", "0.8"])
+            .expect("parse should succeed");
+        match cli.command {
+            Commands::Run(args) => assert_eq!(args.min_confidence, 0.8),
+            _ => panic!("expected run command"),
+        }
+        for bad in ["1.5", "-0.1", "high"] {
+            assert!(Cli::try_parse_from(["ralph", "run", "--min-confidence", bad]).is_err());
+            assert!(
+                Cli::try_parse_from(["ralph", "watch", "a.md", "--min-confidence", bad]).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn hooks_test_subcommand_parses_url_and_token() {
+        let cli = Cli::try_parse_from([
+            "ralph",
+            "hooks",
+            "test",
+            "--url",
+            "https://hooks.example/ralph",
+            "--token",
+            "secret",
+        ])
+        .expect("parse should succeed");
+
+        match cli.command {
+            Commands::Hooks(args) => match args.command {
+                HooksCommands::Test { url, token } => {
+                    assert_eq!(url.as_deref(), Some("https://hooks.example/ralph"));
+                    assert_eq!(token.as_deref(), Some("secret"));
+                }
+                _ => panic!("expected hooks test"),
+            },
+            _ => panic!("expected hooks command"),
+        }
+
+        let cli = Cli::try_parse_from(["ralph", "hooks", "serve", "--desktop"])
+            .expect("parse should succeed");
+        match cli.command {
+            Commands::Hooks(args) => match args.command {
+                HooksCommands::Serve {
+                    port,
+                    token,
+                    out,
+                    desktop,
+                } => {
+                    assert_eq!(port, 9999);
+                    assert!(token.is_none() && out.is_none() && desktop);
+                }
+                _ => panic!("expected hooks serve"),
+            },
+            _ => panic!("expected hooks command"),
+        }
+    }
+
     #[test]
     fn unknown_flags_produce_helpful_errors() {
         let err = match Cli::try_parse_from(["ralph", "run", "prd.md", "--bogus"]) {
@@ -1,5 +1,6 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 /// Top-level config file schema for `ralph.toml`.
@@ -7,21 +8,255 @@ use std::path::{Path, PathBuf};
 pub struct RalphConfig {
     pub defaults: Option<DefaultsConfig>,
     pub hooks: Option<HooksConfig>,
+    pub completion: Option<CompletionConfig>,
+    pub tui: Option<TuiConfig>,
+    pub status: Option<StatusConfig>,
+    /// Per-agent settings, keyed by agent name (`[agents.claude]`).
+    pub agents: Option<HashMap<String, AgentConfig>>,
+    pub output: Option<OutputConfig>,
+    pub report: Option<ReportConfig>,
+    /// Formatters run on each completed task's files (`[[format]]`).
+    pub format: Option<Vec<FormatConfig>>,
+    pub chaos: Option<ChaosConfig>,
+    pub scheduler: Option<SchedulerConfig>,
+    pub state: Option<StateConfig>,
+    pub fallback: Option<FallbackConfig>,
+    pub templates: Option<TemplatesConfig>,
+    pub notify: Option<NotifyConfig>,
+    pub context: Option<ContextConfig>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct DefaultsConfig {
     pub agent: Option<String>,
     pub max_iterations: Option<u32>,
+    /// `--auto-extend` factor, e.g. `"1.5x"`.
+    pub auto_extend: Option<String>,
     pub timeout: Option<u64>,
     pub stall_timeout: Option<u64>,
     pub max_failures: Option<u32>,
+    /// Failed attempts per task before it is given up on (`--max-attempts`).
+    pub max_attempts: Option<u32>,
+    /// `ralph run` token budget (`--max-tokens`).
+    pub max_tokens: Option<u64>,
+    /// `ralph run` cost budget in dollars (`--max-cost-usd`).
+    pub max_cost_usd: Option<f64>,
+    /// Iteration prompt budget in tokens (`--max-prompt-tokens`).
+    pub max_prompt_tokens: Option<usize>,
+    /// Files a task's diff may touch before it is queued for review
+    /// (`--max-files-changed`).
+    pub max_files_changed: Option<usize>,
+    /// Lines a task's diff may change before it is queued for review
+    /// (`--max-lines-changed`).
+    pub max_lines_changed: Option<usize>,
+    /// `utc` (default) or `local` timestamps in progress.md.
+    pub timestamps: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct HooksConfig {
     pub url: Option<String>,
     pub token: Option<String>,
+    /// OS keyring entry holding the token, e.g. `ralph/hook`; read when a
+    /// webhook is sent.
+    pub token_ref: Option<String>,
+    /// `--hook-log-lines`: iteration log lines attached to failure events.
+    pub log_lines: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CompletionConfig {
+    /// Ordered detector stack, e.g. `["token", "confidence", "verify"]`.
+    pub detectors: Option<Vec<String>>,
+    pub min_confidence: Option<f64>,
+    pub verify_cmd: Option<String>,
+    /// `--completion-token`: the word between `<promise>` tags.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StateConfig {
+    /// `--tasks-format`: json, json5, or toml.
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TuiConfig {
+    /// Extra regexes per agent hidden by the TUI's `t` (chatter) filter.
+    pub chatter: Option<HashMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StatusConfig {
+    /// Project roots `ralph status --all` scans (`~/` is expanded).
+    pub roots: Option<Vec<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AgentConfig {
+    /// Sessions of this agent `ralph watch` runs at once across all loops;
+    /// loops beyond it wait for a free session.
+    pub max_concurrent: Option<usize>,
+    /// Shell command template that defines a custom agent under this name,
+    /// with `{prompt}`, `{prompt_file}`, `{model}`, and `{workdir}` placeholders.
+    pub command: Option<String>,
+    /// Wire protocol of the `api` agent (`anthropic` or `openai`);
+    /// `--api-protocol` overrides it.
+    pub protocol: Option<String>,
+    /// MCP servers the agent starts with (`[agents.claude.mcp]`).
+    pub mcp: Option<McpConfig>,
+    /// OS keyring entry holding the `api` agent's key, e.g. `ralph/anthropic`;
+    /// read when the agent is created.
+    pub api_key_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct McpConfig {
+    pub servers: Vec<McpServer>,
+}
+
+/// One MCP server: a local `command`, or a remote one at `url`.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct McpServer {
+    pub name: String,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment for a local server.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OutputConfig {
+    /// Post-processors applied to each agent output line, in order
+    /// (default: all of `strip-ansi`, `collapse-progress`, `redact`, `tag-tools`).
+    pub processors: Option<Vec<String>>,
+    /// Extra regexes masked by `redact`.
+    pub redact: Option<Vec<String>>,
+    /// Extra regexes marking tool-call lines for `tag-tools`.
+    pub tool_patterns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct FormatConfig {
+    /// Shell command; `{files}` is replaced by the task's changed files.
+    pub cmd: String,
+    /// Only run when a changed file has one of these extensions, and pass
+    /// only those files to `{files}` (default: any file).
+    pub extensions: Option<Vec<String>>,
+}
+
+/// Injected failures for rehearsing hooks, notifications, and the circuit
+/// breaker before a real run (`[chaos]`, or the hidden `--chaos` flag).
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ChaosConfig {
+    /// Chance (0.0–1.0) that an iteration gets a fault.
+    pub rate: Option<f64>,
+    /// Faults to pick from: `delay`, `kill`, `rate_limit` (default: all).
+    pub faults: Option<Vec<String>>,
+    /// How long a `delay` holds back the agent's output (default 30).
+    pub delay_secs: Option<u64>,
+    /// Seed for a repeatable sequence of faults.
+    pub seed: Option<u64>,
+}
+
+/// Iterations held back at the end of a `ralph run` for its most important
+/// tasks (`[scheduler]`).
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct SchedulerConfig {
+    /// Tasks at this priority or higher (1 is highest) get the reserve.
+    pub reserve_for_priority: Option<u32>,
+    /// How many of the last iterations are reserved (default: one per
+    /// unfinished reserved task).
+    pub reserve_iterations: Option<u32>,
+}
+
+/// When the loop switches to a fallback agent and back (`[fallback]`).
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct FallbackConfig {
+    /// Failures in a row on one agent before switching (default 1).
+    pub switch_after: Option<u32>,
+    /// Tasks a fallback agent completes before the loop returns to the
+    /// tasks' own agent (default 0: the next task after a success).
+    pub stay_for: Option<u32>,
+    /// Seconds an agent is skipped after an auth or rate-limit error; the
+    /// loop moves to a fallback agent instead of stopping or waiting
+    /// (default 0: off).
+    pub cooldown: Option<u64>,
+}
+
+/// A team's shared PRD templates (`[templates]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TemplatesConfig {
+    /// What `ralph template import` reads without an argument: a git URL,
+    /// a tarball, or a directory.
+    pub source: Option<String>,
+    /// Namespace the shared templates are imported into (e.g. `team`).
+    pub namespace: Option<String>,
+}
+
+/// The repository overview in each iteration prompt (`[context]`).
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ContextConfig {
+    /// Include it at all (default true; git repositories only).
+    pub enabled: Option<bool>,
+    /// Directory levels the file tree opens before listing a directory as
+    /// a file count (default 2).
+    pub tree_depth: Option<usize>,
+    /// Lines the file tree may take (default 200).
+    pub max_tree_entries: Option<usize>,
+    /// Latest commits whose patches are included (default 3; 0 for none).
+    pub commits: Option<usize>,
+    /// Lines those patches may take (default 300).
+    pub max_diff_lines: Option<usize>,
+}
+
+/// `--notify` settings (`[notify]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotifyConfig {
+    /// OS keyring entry holding the OpenClaw gateway token, used before the
+    /// `OPENCLAW_*_TOKEN` environment variables.
+    pub token_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReportConfig {
+    /// Update a changelog from the completed tasks and the run's commits
+    /// once every task is complete.
+    pub changelog: Option<bool>,
+    /// Changelog path, relative to the project (default `CHANGELOG.md`).
+    pub changelog_file: Option<PathBuf>,
+    /// `template` (default) writes a fixed-format entry; `agent` has the
+    /// agent summarize the work into release notes.
+    pub changelog_style: Option<String>,
+}
+
+impl HooksConfig {
+    /// `token`, else the one `token_ref` names in the OS keyring. Only called
+    /// once a webhook will be sent, so other commands never touch the keyring.
+    pub fn resolve_token(&self) -> Result<Option<String>> {
+        match &self.token_ref {
+            Some(_) if self.token.is_some() => {
+                bail!("[hooks] sets both token and token_ref; keep only token_ref")
+            }
+            Some(reference) => crate::secrets::get(reference)
+                .context("[hooks] token_ref")
+                .map(Some),
+            None => Ok(self.token.clone()),
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// The gateway token `token_ref` names in the OS keyring, if any.
+    pub fn resolve_token(&self) -> Result<Option<String>> {
+        self.token_ref
+            .as_deref()
+            .map(|reference| crate::secrets::get(reference).context("[notify] token_ref"))
+            .transpose()
+    }
 }
 
 pub fn load_config() -> Result<Option<RalphConfig>> {
@@ -113,6 +348,7 @@ max_iterations = 20
 timeout = 600
 stall_timeout = 120
 max_failures = 3
+max_cost_usd = 5.0
 
 [hooks]
 url = "https://example.com/webhook"
@@ -132,7 +368,180 @@ token = "secret"
         assert_eq!(defaults.timeout, Some(600));
         assert_eq!(defaults.stall_timeout, Some(120));
         assert_eq!(defaults.max_failures, Some(3));
+        assert_eq!(defaults.max_cost_usd, Some(5.0));
+        assert_eq!(defaults.max_tokens, None);
         assert_eq!(hooks.url.as_deref(), Some("https://example.com/webhook"));
         assert_eq!(hooks.token.as_deref(), Some("secret"));
     }
+
+    #[test]
+    fn a_plaintext_token_and_a_keyring_ref_conflict() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            r#"
+[hooks]
+url = "https://example.com/webhook"
+token = "secret"
+token_ref = "ralph/hook"
+
+[agents.api]
+api_key_ref = "ralph/anthropic"
+
+[notify]
+token_ref = "ralph/openclaw"
+"#,
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let api = &config.agents.as_ref().expect("agents")["api"];
+        assert_eq!(api.api_key_ref.as_deref(), Some("ralph/anthropic"));
+        let err = config.hooks.expect("hooks").resolve_token().unwrap_err();
+        assert!(
+            err.to_string().contains("both token and token_ref"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn parses_completion_detector_stack() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            r#"
+[completion]
+detectors = ["token", "confidence", "verify"]
+min_confidence = 0.75
+verify_cmd = "cargo test"
+token = "DONE"
+"#,
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let completion = config.completion.expect("completion should exist");
+
+        assert_eq!(
+            completion.detectors,
+            Some(vec![
+                "token".to_string(),
+                "confidence".to_string(),
+                "verify".to_string()
+            ])
+        );
+        assert_eq!(completion.min_confidence, Some(0.75));
+        assert_eq!(completion.verify_cmd.as_deref(), Some("cargo test"));
+        assert_eq!(completion.token.as_deref(), Some("DONE"));
+    }
+
+    #[test]
+    fn parses_format_commands() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            r#"
+[[format]]
+cmd = "cargo fmt"
+
+[[format]]
+cmd = "ruff check --fix {files}"
+extensions = ["py"]
+"#,
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let format = config.format.expect("format should exist");
+
+        assert_eq!(format.len(), 2);
+        assert_eq!(format[0].cmd, "cargo fmt");
+        assert!(format[0].extensions.is_none());
+        assert_eq!(format[1].extensions, Some(vec!["py".to_string()]));
+    }
+
+    #[test]
+    fn parses_chaos_settings() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            r#"
+[chaos]
+rate = 0.5
+faults = ["kill", "rate_limit"]
+seed = 42
+"#,
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let chaos = config.chaos.expect("chaos should exist");
+
+        assert_eq!(chaos.rate, Some(0.5));
+        assert_eq!(
+            chaos.faults,
+            Some(vec!["kill".to_string(), "rate_limit".to_string()])
+        );
+        assert_eq!(chaos.seed, Some(42));
+        assert!(chaos.delay_secs.is_none());
+    }
+
+    #[test]
+    fn parses_per_agent_session_limits() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            "[agents.claude]\nmax_concurrent = 1\n\n[agents.codex]\n\n[agents.api]\nprotocol = \"openai\"\n\n\
+             [agents.mytool]\ncommand = \"mytool run --prompt {prompt}\"\n\n\
+             [[agents.claude.mcp.servers]]\nname = \"db\"\ncommand = \"npx\"\nargs = [\"-y\", \"pg-mcp\"]\n",
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let agents = config.agents.expect("agents should exist");
+
+        assert_eq!(agents["claude"].max_concurrent, Some(1));
+        assert_eq!(agents["codex"].max_concurrent, None);
+        assert_eq!(agents["api"].protocol.as_deref(), Some("openai"));
+        assert_eq!(
+            agents["mytool"].command.as_deref(),
+            Some("mytool run --prompt {prompt}")
+        );
+        let servers = &agents["claude"].mcp.as_ref().expect("mcp").servers;
+        assert_eq!(servers[0].name, "db");
+        assert_eq!(servers[0].args, ["-y", "pg-mcp"]);
+        assert!(agents["codex"].mcp.is_none());
+    }
+
+    #[test]
+    fn parses_report_changelog_settings() {
+        let cwd = tempdir().expect("temp cwd");
+        std::fs::write(
+            cwd.path().join("ralph.toml"),
+            "[report]\nchangelog = true\nchangelog_file = \"docs/RELEASE_NOTES.md\"\nchangelog_style = \"agent\"\n",
+        )
+        .expect("write config");
+
+        let config = load_config_from(cwd.path(), None)
+            .expect("load should succeed")
+            .expect("config should exist");
+        let report = config.report.expect("report should exist");
+
+        assert_eq!(report.changelog, Some(true));
+        assert_eq!(
+            report.changelog_file.as_deref(),
+            Some(std::path::Path::new("docs/RELEASE_NOTES.md"))
+        );
+        assert_eq!(report.changelog_style.as_deref(), Some("agent"));
+    }
 }
@@ -0,0 +1,444 @@
+//! `ralph doctor`: checks that the agents are installed and logged in and
+//! don't warn about the flags of a one-word probe run (the core of what a
+//! real run passes, not every flag), that git works, and that the disk has
+//! room. A run that trips its circuit breaker keeps a report of the checks
+//! that cost nothing with the failure: which agents are installed, git, and
+//! disk. It does not probe logins, as that means running the agents.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+use crate::agents::drift;
+use crate::cli::DoctorArgs;
+
+/// One line of the report.
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorRow {
+    pub check: String,
+    /// `OK`, `WARN`, `FAIL`, `MISSING`, or `N/A`.
+    pub status: String,
+    pub details: String,
+}
+
+impl DoctorRow {
+    pub fn is_ok(&self) -> bool {
+        self.status == "OK"
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AgentAuthStatus {
+    Authenticated,
+    NotAuthenticated(String),
+    TimedOut,
+    ProbeFailed(String),
+}
+
+/// `ralph doctor`.
+pub async fn run(args: DoctorArgs) -> Result<()> {
+    let workdir = args
+        .workdir
+        .unwrap_or_else(|| PathBuf::from("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+
+    let rows = checks(&workdir).await;
+    println!("Ralph doctor report for {}", workdir.display());
+    println!();
+    print!("{}", table(&rows));
+
+    Ok(())
+}
+
+const AGENTS: [&str; 4] = ["claude", "codex", "gemini", "opencode"];
+
+/// Every check, run against `workdir`.
+pub async fn checks(workdir: &Path) -> Vec<DoctorRow> {
+    let mut rows = Vec::new();
+
+    for agent in AGENTS {
+        if !command_on_path(agent).await {
+            rows.push(DoctorRow {
+                check: format!("agent:{agent}"),
+                status: "MISSING".to_string(),
+                details: "not found on PATH".to_string(),
+            });
+            continue;
+        }
+
+        let (auth, flag_warnings) = probe_agent_auth(agent, workdir).await;
+        let (status, details) = match auth {
+            AgentAuthStatus::Authenticated => ("OK", "installed + authenticated".to_string()),
+            AgentAuthStatus::NotAuthenticated(msg) => ("WARN", msg),
+            AgentAuthStatus::TimedOut => ("WARN", "probe timed out after 10s".to_string()),
+            AgentAuthStatus::ProbeFailed(msg) => ("WARN", msg),
+        };
+
+        rows.push(DoctorRow {
+            check: format!("agent:{agent}"),
+            status: status.to_string(),
+            details,
+        });
+        rows.push(flags_row(agent, flag_warnings));
+    }
+
+    rows.extend(system_rows(workdir).await);
+    rows
+}
+
+/// The checks that run no agent: whether each is installed, git, and disk.
+pub async fn quick_checks(workdir: &Path) -> Vec<DoctorRow> {
+    let mut rows = Vec::new();
+    for agent in AGENTS {
+        let (status, details) = if command_on_path(agent).await {
+            ("OK", "installed (login not checked)")
+        } else {
+            ("MISSING", "not found on PATH")
+        };
+        rows.push(DoctorRow {
+            check: format!("agent:{agent}"),
+            status: status.to_string(),
+            details: details.to_string(),
+        });
+    }
+    rows.extend(system_rows(workdir).await);
+    rows
+}
+
+/// git and disk.
+async fn system_rows(workdir: &Path) -> Vec<DoctorRow> {
+    let mut rows = Vec::new();
+    if !command_on_path("git").await {
+        rows.push(DoctorRow {
+            check: "git".to_string(),
+            status: "FAIL".to_string(),
+            details: "git not found on PATH".to_string(),
+        });
+        rows.push(DoctorRow {
+            check: "git-repo".to_string(),
+            status: "N/A".to_string(),
+            details: "git is not installed".to_string(),
+        });
+    } else {
+        let git_version = detect_git_version().await;
+        rows.push(DoctorRow {
+            check: "git".to_string(),
+            status: "OK".to_string(),
+            details: git_version
+                .unwrap_or_else(|e| format!("installed (version probe failed: {e})")),
+        });
+
+        let (status, details) = match is_git_repo(workdir).await {
+            Ok(true) => ("OK", format!("{} is a git repository", workdir.display())),
+            Ok(false) => (
+                "WARN",
+                format!("{} is not a git repository", workdir.display()),
+            ),
+            Err(e) => ("WARN", format!("failed to check repo status: {e}")),
+        };
+        rows.push(DoctorRow {
+            check: "git-repo".to_string(),
+            status: status.to_string(),
+            details,
+        });
+    }
+
+    let disk_row = match check_disk_space(workdir).await {
+        Ok((total_kib, avail_kib, used_percent)) => DoctorRow {
+            check: "disk".to_string(),
+            status: if used_percent >= 95 { "WARN" } else { "OK" }.to_string(),
+            details: format!(
+                "{} free / {} total ({}% used)",
+                kib_to_human(avail_kib),
+                kib_to_human(total_kib),
+                used_percent
+            ),
+        },
+        Err(e) => DoctorRow {
+            check: "disk".to_string(),
+            status: "WARN".to_string(),
+            details: format!("failed to check disk space: {e}"),
+        },
+    };
+    rows.push(disk_row);
+    rows
+}
+
+async fn command_on_path(bin: &str) -> bool {
+    let status = Command::new(bin)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status();
+    matches!(
+        timeout(Duration::from_secs(10), status).await,
+        Ok(Ok(s)) if s.success()
+    )
+}
+
+async fn detect_git_version() -> Result<String> {
+    let output = Command::new("git")
+        .arg("--version")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .context("failed to execute `git --version`")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", first_non_empty_line(&stderr));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn is_git_repo(workdir: &Path) -> Result<bool> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(workdir)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .context("failed to execute git repo probe")?;
+
+    if !output.status.success() {
+        return Ok(false);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+/// The probe's verdict on the login, and the flag warnings it printed
+/// (`None` when it didn't finish).
+async fn probe_agent_auth(agent: &str, workdir: &Path) -> (AgentAuthStatus, Option<Vec<String>>) {
+    let mut cmd = build_auth_probe(agent);
+    let flags = crate::agents::flags_of(&cmd);
+    cmd.current_dir(workdir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let result = match timeout(Duration::from_secs(10), cmd.output()).await {
+        Ok(res) => res,
+        Err(_) => return (AgentAuthStatus::TimedOut, None),
+    };
+
+    let output = match result {
+        Ok(output) => output,
+        Err(e) => return (AgentAuthStatus::ProbeFailed(e.to_string()), None),
+    };
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let flag_warnings = Some(drift::scan(&stderr, &flags));
+    if output.status.success() {
+        return (AgentAuthStatus::Authenticated, flag_warnings);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let combined = if stderr.trim().is_empty() {
+        stdout.as_ref()
+    } else {
+        stderr.as_ref()
+    };
+    let message = first_non_empty_line(combined);
+
+    if agent == "claude" && is_claude_api_key_error(combined) {
+        return (
+            AgentAuthStatus::NotAuthenticated(
+                "API key required for `claude --print` (set ANTHROPIC_API_KEY)".to_string(),
+            ),
+            flag_warnings,
+        );
+    }
+
+    let status = AgentAuthStatus::NotAuthenticated(if message.is_empty() {
+        "probe failed with non-zero exit".to_string()
+    } else {
+        message
+    });
+    (status, flag_warnings)
+}
+
+/// Whether the agent's probe complained about the flags it was passed.
+fn flags_row(agent: &str, warnings: Option<Vec<String>>) -> DoctorRow {
+    let (status, details) = match warnings.as_deref() {
+        None => ("N/A", "the probe did not finish".to_string()),
+        Some([]) => ("OK", "no deprecated or unknown flags".to_string()),
+        Some([first]) => ("WARN", first.clone()),
+        Some([first, rest @ ..]) => ("WARN", format!("{first} (+{} more)", rest.len())),
+    };
+    DoctorRow {
+        check: format!("flags:{agent}"),
+        status: status.to_string(),
+        details,
+    }
+}
+
+fn build_auth_probe(agent: &str) -> Command {
+    match agent {
+        "claude" => {
+            let mut c = Command::new("claude");
+            c.arg("--dangerously-skip-permissions")
+                .arg("--print")
+                .arg("-p")
+                .arg("hi");
+            c
+        }
+        "codex" => {
+            let mut c = Command::new("codex");
+            c.arg("exec").arg("--full-auto").arg("hi");
+            c
+        }
+        "gemini" => {
+            let mut c = Command::new("gemini");
+            c.arg("-p").arg("hi").arg("--yolo");
+            c
+        }
+        "opencode" => {
+            let mut c = Command::new("opencode");
+            c.arg("run").arg("hi");
+            c
+        }
+        _ => Command::new(agent),
+    }
+}
+
+fn is_claude_api_key_error(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    lower.contains("invalid api key") || lower.contains("api key")
+}
+
+async fn check_disk_space(workdir: &Path) -> Result<(u64, u64, u8)> {
+    let output = Command::new("df")
+        .arg("-k")
+        .arg(workdir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .context("failed to execute `df -k`")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", first_non_empty_line(&stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_df_k_output(&stdout).ok_or_else(|| anyhow::anyhow!("unexpected `df -k` output format"))
+}
+
+fn parse_df_k_output(output: &str) -> Option<(u64, u64, u8)> {
+    let line = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .nth(1)?;
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        return None;
+    }
+
+    let total_kib = fields.get(fields.len() - 5)?.parse::<u64>().ok()?;
+    let avail_kib = fields.get(fields.len() - 3)?.parse::<u64>().ok()?;
+    let used_percent = fields
+        .get(fields.len() - 2)?
+        .trim_end_matches('%')
+        .parse::<u8>()
+        .ok()?;
+
+    Some((total_kib, avail_kib, used_percent))
+}
+
+fn kib_to_human(kib: u64) -> String {
+    let gib = kib as f64 / (1024.0 * 1024.0);
+    if gib >= 1.0 {
+        return format!("{gib:.1} GiB");
+    }
+
+    let mib = kib as f64 / 1024.0;
+    if mib >= 1.0 {
+        return format!("{mib:.1} MiB");
+    }
+
+    format!("{kib} KiB")
+}
+
+fn first_non_empty_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The report as an aligned table.
+pub fn table(rows: &[DoctorRow]) -> String {
+    let mut check_w = "CHECK".len();
+    let mut status_w = "STATUS".len();
+    for row in rows {
+        check_w = check_w.max(row.check.len());
+        status_w = status_w.max(row.status.len());
+    }
+
+    let mut out = format!("{:<check_w$}  {:<status_w$}  DETAILS\n", "CHECK", "STATUS");
+    out.push_str(&format!(
+        "{}  {}  {}\n",
+        "-".repeat(check_w),
+        "-".repeat(status_w),
+        "-".repeat(48)
+    ));
+    for row in rows {
+        out.push_str(&format!(
+            "{:<check_w$}  {:<status_w$}  {}\n",
+            row.check, row.status, row.details
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_df_k_output_parses_expected_columns() {
+        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\n/dev/disk3s1s1 488245288 110354576 361727632 24% /\n";
+        let parsed = parse_df_k_output(output).expect("parse should succeed");
+
+        assert_eq!(parsed.0, 488245288);
+        assert_eq!(parsed.1, 361727632);
+        assert_eq!(parsed.2, 24);
+    }
+
+    #[test]
+    fn flags_row_reports_what_the_probe_warned_about() {
+        assert_eq!(flags_row("codex", Some(vec![])).status, "OK");
+        assert_eq!(flags_row("codex", None).status, "N/A");
+        let warnings = drift::scan(
+            "warning: `--full-auto` is deprecated\nerror: unexpected argument '--yolo' found\n",
+            &["--full-auto".to_string(), "--yolo".to_string()],
+        );
+        let row = flags_row("codex", Some(warnings));
+        assert_eq!(row.check, "flags:codex");
+        assert_eq!(row.status, "WARN");
+        assert_eq!(
+            row.details,
+            "warning: `--full-auto` is deprecated (+1 more)"
+        );
+    }
+
+    #[test]
+    fn parse_df_k_output_rejects_invalid_shape() {
+        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\ninvalid\n";
+        assert!(parse_df_k_output(output).is_none());
+    }
+}
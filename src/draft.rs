@@ -0,0 +1,199 @@
+//! `ralph init --from-diff <range>` — draft a PRD for a half-finished branch.
+//!
+//! Collects the commit log, diffstat, patch, and newly added TODO/FIXME comments
+//! for the range and asks an agent to describe the *remaining* work as a PRD
+//! that `ralph run` can pick up.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::agents::Sampling;
+use crate::git::GitManager;
+use crate::ignore::RalphIgnore;
+use crate::parser::run_agent;
+
+/// Upper bound on how much of the raw patch is embedded in the prompt.
+const MAX_DIFF_CHARS: usize = 60_000;
+
+const DRAFT_PROMPT: &str = r#"You are a senior engineer writing a Product Requirements Document (PRD) for work that is already partially done on a git branch.
+
+Below is the commit log, a diffstat, the TODO/FIXME comments added on the branch, and the (possibly truncated) diff. Infer what the branch is trying to achieve and describe the work that still REMAINS.
+
+Output ONLY the PRD as markdown — no preamble, no code fences around the whole document. Use exactly these top-level sections:
+
+# Overview
+# Requirements
+# Acceptance Criteria
+
+Under Requirements, list the remaining tasks as `## T1: <title>`, `## T2: <title>`, … with one or two sentences each. Do not list work that the diff shows is already finished.
+
+## Commits
+
+{log}
+
+## Diffstat
+
+{stat}
+
+## TODO comments added
+
+{todos}
+
+## Diff
+
+{diff}
+"#;
+
+/// Draft a PRD from the git range in `workdir` and write it to `prd_path`.
+pub async fn draft_prd_from_diff(
+    workdir: &Path,
+    prd_path: &Path,
+    range: &str,
+    agent: &str,
+    model: Option<&str>,
+    timeout_secs: u64,
+) -> Result<()> {
+    // Protected paths stay out of the diff the agent summarizes.
+    let git = GitManager::new(workdir).ignoring(&RalphIgnore::load(workdir)?);
+    if !git.is_git_repo().await {
+        anyhow::bail!("{} is not a git repository", workdir.display());
+    }
+
+    let log = git
+        .log_range(range)
+        .await
+        .with_context(|| format!("Cannot read commits for range '{range}'"))?;
+    let stat = git.diff_stat_range(range).await?;
+    let diff = git.diff_range(range).await?;
+
+    if log.is_empty() && diff.is_empty() {
+        anyhow::bail!("Range '{range}' contains no commits or changes");
+    }
+
+    let todos = extract_added_todos(&diff);
+    let prompt = build_prompt(&log, &stat, &todos, &diff);
+
+    eprintln!("📝  Drafting PRD from {range} with {agent} (this may take a moment)…");
+    let raw = run_agent(agent, model, &Sampling::default(), &prompt, timeout_secs).await?;
+    let prd = clean_agent_markdown(&raw);
+    if prd.trim().is_empty() {
+        anyhow::bail!("Agent returned an empty PRD draft");
+    }
+
+    std::fs::write(prd_path, prd)
+        .with_context(|| format!("Failed to write {}", prd_path.display()))?;
+    Ok(())
+}
+
+fn build_prompt(log: &str, stat: &str, todos: &[String], diff: &str) -> String {
+    let todos = if todos.is_empty() {
+        "(none)".to_string()
+    } else {
+        todos
+            .iter()
+            .map(|t| format!("- {t}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    DRAFT_PROMPT
+        .replace("{log}", or_none(log))
+        .replace("{stat}", or_none(stat))
+        .replace("{todos}", &todos)
+        .replace("{diff}", &truncate_diff(diff, MAX_DIFF_CHARS))
+}
+
+fn or_none(s: &str) -> &str {
+    if s.trim().is_empty() {
+        "(none)"
+    } else {
+        s
+    }
+}
+
+/// Return TODO/FIXME comments from lines *added* in a unified diff, tagged with
+/// the file they appear in.
+fn extract_added_todos(diff: &str) -> Vec<String> {
+    let mut current_file = String::new();
+    let mut todos = Vec::new();
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current_file = path.trim_start_matches("b/").to_string();
+            continue;
+        }
+        let Some(added) = line.strip_prefix('+') else {
+            continue;
+        };
+        if added.contains("TODO") || added.contains("FIXME") {
+            todos.push(format!("{}: {}", current_file, added.trim()));
+        }
+    }
+
+    todos
+}
+
+/// Cut the diff at a line boundary so the prompt stays within budget.
+fn truncate_diff(diff: &str, max_chars: usize) -> String {
+    if diff.len() <= max_chars {
+        return diff.to_string();
+    }
+    let mut end = max_chars;
+    while end > 0 && !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = diff[..end].rfind('\n').unwrap_or(end);
+    format!(
+        "{}\n\n[diff truncated — {} of {} bytes shown]",
+        &diff[..cut],
+        cut,
+        diff.len()
+    )
+}
+
+/// Strip a wrapping ```markdown fence if the agent added one anyway.
+fn clean_agent_markdown(raw: &str) -> String {
+    let trimmed = raw.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return format!("{trimmed}\n");
+    };
+    let body = rest.split_once('\n').map(|(_, b)| b).unwrap_or_default();
+    let body = body.trim_end().strip_suffix("```").unwrap_or(body);
+    format!("{}\n", body.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_added_todos_only_reports_added_lines() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,3 @@
+-// TODO: old note that was removed
++// TODO: wire up the retry policy
++fn retry() {}
+ // FIXME: untouched context line
+";
+        let todos = extract_added_todos(diff);
+        assert_eq!(todos, vec!["src/lib.rs: // TODO: wire up the retry policy"]);
+    }
+
+    #[test]
+    fn truncate_diff_cuts_on_line_boundary() {
+        let diff = "line one\nline two\nline three\n";
+        let out = truncate_diff(diff, 12);
+        assert!(out.starts_with("line one\n\n[diff truncated"));
+        assert_eq!(truncate_diff(diff, 1000), diff);
+    }
+
+    #[test]
+    fn clean_agent_markdown_strips_wrapping_fence() {
+        let raw = "```markdown\n# Overview\n\nStuff\n```\n";
+        assert_eq!(clean_agent_markdown(raw), "# Overview\n\nStuff\n");
+        assert_eq!(clean_agent_markdown("# Overview\n"), "# Overview\n");
+    }
+}
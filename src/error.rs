@@ -0,0 +1,166 @@
+//! Error kinds that wrappers can tell apart, mapped to stable exit codes.
+//!
+//! Internals keep using `anyhow`. Where a failure has a meaningful kind, the
+//! error is tagged with a `RalphError` at its origin; `report` finds that tag
+//! anywhere in the context chain when the error reaches `main`.
+
+use std::fmt;
+use std::process::ExitCode;
+
+/// Exit code for failures without a more specific kind.
+pub const EXIT_RUN_FAILED: u8 = 1;
+
+#[derive(Debug)]
+pub enum RalphError {
+    /// Invalid ralph.toml, flags, or agent name.
+    Config(anyhow::Error),
+    /// The selected agent CLI is not installed.
+    AgentUnavailable(String),
+    /// The PRD could not be turned into a task list.
+    Parse(anyhow::Error),
+    Git(anyhow::Error),
+    /// `.ralph/` state could not be created or read.
+    State(anyhow::Error),
+    /// Stopped by a signal, the TUI, or `ralph stop` before finishing.
+    Cancelled,
+    CircuitBreaker {
+        consecutive_failures: u32,
+    },
+    /// Stopped by `--max-tokens` or `--max-cost-usd`.
+    BudgetExceeded(String),
+    /// Stopped because the provider rejected the agent's credentials.
+    AuthFailed(String),
+}
+
+impl RalphError {
+    /// Machine-readable kind used in `--json` error objects.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RalphError::Config(_) => "config",
+            RalphError::AgentUnavailable(_) => "agent_unavailable",
+            RalphError::Parse(_) => "parse",
+            RalphError::Git(_) => "git",
+            RalphError::State(_) => "state",
+            RalphError::Cancelled => "cancelled",
+            RalphError::CircuitBreaker { .. } => "circuit_breaker",
+            RalphError::BudgetExceeded(_) => "budget_exceeded",
+            RalphError::AuthFailed(_) => "auth_failed",
+        }
+    }
+
+    /// Process exit code. 2 is left to clap for usage errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RalphError::Config(_) => 3,
+            RalphError::AgentUnavailable(_) => 4,
+            RalphError::Parse(_) => 5,
+            RalphError::Git(_) => 6,
+            RalphError::State(_) => 7,
+            RalphError::CircuitBreaker { .. } => 8,
+            RalphError::BudgetExceeded(_) => 9,
+            RalphError::AuthFailed(_) => 10,
+            RalphError::Cancelled => 130,
+        }
+    }
+
+    fn inner(&self) -> Option<&anyhow::Error> {
+        match self {
+            RalphError::Config(e)
+            | RalphError::Parse(e)
+            | RalphError::Git(e)
+            | RalphError::State(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RalphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(e) = self.inner() {
+            // The rest of the wrapped chain is exposed through `source()`.
+            return write!(f, "{e}");
+        }
+        match self {
+            RalphError::AgentUnavailable(agent) => write!(
+                f,
+                "Agent '{agent}' not found on PATH. Install it and try again."
+            ),
+            RalphError::Cancelled => write!(f, "Cancelled before all tasks completed"),
+            RalphError::CircuitBreaker {
+                consecutive_failures,
+            } => write!(
+                f,
+                "Circuit breaker tripped after {consecutive_failures} consecutive failures"
+            ),
+            RalphError::BudgetExceeded(reason) => write!(f, "Budget exceeded: {reason}"),
+            RalphError::AuthFailed(reason) => write!(f, "Stopped: {reason}"),
+            _ => unreachable!("wrapped variants are handled above"),
+        }
+    }
+}
+
+impl std::error::Error for RalphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner().and_then(|e| e.source())
+    }
+}
+
+/// The `RalphError` tag anywhere in `err`'s context chain, if any.
+pub fn tag_of(err: &anyhow::Error) -> Option<&RalphError> {
+    err.chain().find_map(|e| e.downcast_ref::<RalphError>())
+}
+
+/// Print an error that reached `main` (plain or as a JSON object on stderr)
+/// and return the matching exit code.
+pub fn report(err: &anyhow::Error, json: bool) -> ExitCode {
+    let tag = tag_of(err);
+    let kind = tag.map_or("run_failed", RalphError::kind);
+    let code = tag.map_or(EXIT_RUN_FAILED, RalphError::exit_code);
+
+    if json {
+        let object = serde_json::json!({
+            "error": {
+                "kind": kind,
+                "exit_code": code,
+                "message": format!("{err:#}"),
+            }
+        });
+        eprintln!("{object}");
+    } else {
+        eprintln!("Error: {err:?}");
+    }
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn tags_survive_context_and_keep_the_wrapped_chain() {
+        let io = std::io::Error::other("disk full");
+        let state = RalphError::State(anyhow::Error::new(io).context("Failed to write tasks.json"));
+        let err = Err::<(), _>(state)
+            .context("Cannot start loop")
+            .unwrap_err();
+
+        let tag = tag_of(&err).expect("tagged");
+        assert_eq!(tag.kind(), "state");
+        assert_eq!(tag.exit_code(), 7);
+        assert_eq!(
+            format!("{err:#}"),
+            "Cannot start loop: Failed to write tasks.json: disk full"
+        );
+    }
+
+    #[test]
+    fn untagged_errors_are_run_failures() {
+        let err = anyhow::anyhow!("agent exited with status 1");
+        assert!(tag_of(&err).is_none());
+
+        let err: anyhow::Error = RalphError::AgentUnavailable("codex".to_string()).into();
+        assert_eq!(tag_of(&err).map(RalphError::exit_code), Some(4));
+        assert!(err.to_string().contains("'codex' not found on PATH"));
+    }
+}
@@ -0,0 +1,303 @@
+//! `ralph export --format <FORMAT>` — hand a parsed plan to people or trackers
+//! instead of running it.
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::path::Path;
+
+use crate::cli::ExportArgs;
+use crate::state::{StateManager, TaskList, TaskStatus};
+
+/// `--format` choices.
+pub const EXPORT_FORMATS: &[&str] = &["md-checklist", "github-issues", "jira-csv", "taskwarrior"];
+
+pub fn export_tasks(args: ExportArgs) -> Result<()> {
+    let workdir = args
+        .workdir
+        .as_deref()
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+
+    let state = match args.name.as_deref() {
+        Some(name) => StateManager::new_named(&workdir, name)?,
+        None => StateManager::new(&workdir)?,
+    };
+    let task_list = state
+        .load_tasks()?
+        .context("No tasks.json found — run `ralph parse <prd> --output` or `ralph run` first")?;
+
+    let rendered = render(&task_list, &args.format)?;
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, rendered)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "✅  Exported {} tasks to {}",
+                task_list.tasks.len(),
+                path.display()
+            );
+        }
+        None => print!("{rendered}"),
+    }
+    Ok(())
+}
+
+fn render(task_list: &TaskList, format: &str) -> Result<String> {
+    match format {
+        "md-checklist" => Ok(md_checklist(task_list)),
+        "github-issues" => github_issues(task_list),
+        "jira-csv" => Ok(jira_csv(task_list)),
+        "taskwarrior" => taskwarrior(task_list),
+        other => anyhow::bail!("Unknown export format: {other}"),
+    }
+}
+
+/// A checklist to paste into a PR description or planning doc.
+fn md_checklist(task_list: &TaskList) -> String {
+    let mut out = format!("# Tasks — {}\n\n", task_list.prd_path);
+    for task in &task_list.tasks {
+        let check = if task.status == TaskStatus::Complete {
+            "x"
+        } else {
+            " "
+        };
+        out.push_str(&format!("- [{check}] **{}** {}", task.id, task.title));
+        if !matches!(task.status, TaskStatus::Pending | TaskStatus::Complete) {
+            out.push_str(&format!(" _({})_", task.status));
+        }
+        out.push('\n');
+        if !task.description.is_empty() {
+            out.push_str(&format!("  {}\n", task.description));
+        }
+        if !task.depends_on.is_empty() {
+            out.push_str(&format!("  Depends on: {}\n", task.depends_on.join(", ")));
+        }
+        for criterion in &task.acceptance {
+            out.push_str(&format!("  Acceptance: {criterion}\n"));
+        }
+    }
+    out
+}
+
+/// One `{title, body}` object per task. Bodies are Markdown, ready for
+/// `gh issue create --title … --body …`.
+fn github_issues(task_list: &TaskList) -> Result<String> {
+    let issues: Vec<_> = task_list
+        .tasks
+        .iter()
+        .map(|task| {
+            let mut body = format!("{}\n\n", task.description);
+            if !task.depends_on.is_empty() {
+                body.push_str("**Depends on:**\n");
+                for dep in &task.depends_on {
+                    let title = title_of(task_list, dep).unwrap_or("unknown task");
+                    body.push_str(&format!("- {dep}: {title}\n"));
+                }
+                body.push('\n');
+            }
+            if !task.acceptance.is_empty() {
+                body.push_str("**Acceptance criteria:**\n");
+                for criterion in &task.acceptance {
+                    body.push_str(&format!("- [ ] {criterion}\n"));
+                }
+                body.push('\n');
+            }
+            body.push_str(&format!(
+                "_Task {} (priority {}) from `{}`, exported by ralph._\n",
+                task.id, task.priority, task_list.prd_path
+            ));
+            json!({
+                "title": format!("{}: {}", task.id, task.title),
+                "body": body,
+                "state": if task.status == TaskStatus::Complete { "closed" } else { "open" },
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&issues)? + "\n")
+}
+
+/// CSV for Jira's external system import; map the columns in the import wizard.
+fn jira_csv(task_list: &TaskList) -> String {
+    let mut out = String::from("Issue ID,Summary,Description,Priority,Status,Depends On\n");
+    for task in &task_list.tasks {
+        let row = [
+            task.id.clone(),
+            task.title.clone(),
+            task.description.clone(),
+            task.priority.to_string(),
+            task.status.to_string(),
+            task.depends_on.join(" "),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A JSON array for `task import`. UUIDs are derived from the PRD path and task
+/// id, so dependencies resolve and re-importing updates tasks in place.
+fn taskwarrior(task_list: &TaskList) -> Result<String> {
+    let project = Path::new(&task_list.prd_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "ralph".to_string());
+    let entry = task_list.created_at.format("%Y%m%dT%H%M%SZ").to_string();
+
+    let tasks: Vec<_> = task_list
+        .tasks
+        .iter()
+        .map(|task| {
+            let mut item = json!({
+                "uuid": task_uuid(&task_list.prd_path, &task.id),
+                "description": format!("{}: {}", task.id, task.title),
+                "project": project,
+                "entry": entry,
+                "status": if task.status == TaskStatus::Complete { "completed" } else { "pending" },
+                "tags": ["ralph"],
+            });
+            if let Some(done) = task.completed_at {
+                item["end"] = json!(done.format("%Y%m%dT%H%M%SZ").to_string());
+            }
+            if !task.description.is_empty() {
+                item["annotations"] = json!([{ "entry": entry, "description": task.description }]);
+            }
+            if !task.depends_on.is_empty() {
+                let depends: Vec<String> = task
+                    .depends_on
+                    .iter()
+                    .map(|dep| task_uuid(&task_list.prd_path, dep))
+                    .collect();
+                item["depends"] = json!(depends.join(","));
+            }
+            item
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&tasks)? + "\n")
+}
+
+fn title_of<'a>(task_list: &'a TaskList, id: &str) -> Option<&'a str> {
+    task_list
+        .tasks
+        .iter()
+        .find(|t| t.id == id)
+        .map(|t| t.title.as_str())
+}
+
+/// A stable, UUID-shaped id from two FNV-1a hashes of `prd_path` + `id`.
+fn task_uuid(prd_path: &str, id: &str) -> String {
+    let fnv = |seed: u64| {
+        let mut hash = seed;
+        for byte in prd_path.bytes().chain([0]).chain(id.bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    };
+    let (hi, lo) = (fnv(0xcbf2_9ce4_8422_2325), fnv(0x8422_2325_cbf2_9ce4));
+    // Version 4 / RFC 4122 variant bits, so Taskwarrior accepts it.
+    let hi = (hi & !0xf000) | 0x4000;
+    let lo = (lo & !(0b11 << 62)) | (0b10 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xffff,
+        lo >> 48,
+        lo & 0xffff_ffff_ffff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Task;
+    use chrono::Utc;
+
+    fn task(id: &str, status: TaskStatus, depends_on: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            title: format!("Title {id}"),
+            description: format!("Do {id}, carefully"),
+            priority: 1,
+            status,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn sample() -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "auth.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: vec![
+                task("T1", TaskStatus::Complete, &[]),
+                Task {
+                    acceptance: vec!["Logs in".to_string()],
+                    ..task("T2", TaskStatus::Failed, &["T1"])
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn checklist_ticks_complete_tasks_and_flags_failures() {
+        let out = md_checklist(&sample());
+        assert!(out.contains("- [x] **T1** Title T1\n  Do T1, carefully\n"));
+        assert!(out.contains("- [ ] **T2** Title T2 _(failed)_\n"));
+        assert!(out.contains("  Depends on: T1\n  Acceptance: Logs in\n"));
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_commas() {
+        let out = jira_csv(&sample());
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            rows[0],
+            "Issue ID,Summary,Description,Priority,Status,Depends On"
+        );
+        assert_eq!(rows[2], "T2,Title T2,\"Do T2, carefully\",1,failed,T1");
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn taskwarrior_dependencies_point_at_stable_uuids() {
+        let list = sample();
+        let out: serde_json::Value =
+            serde_json::from_str(&taskwarrior(&list).expect("render")).expect("json");
+        assert_eq!(out[0]["status"], "completed");
+        assert_eq!(out[1]["project"], "auth");
+        assert_eq!(out[1]["depends"], out[0]["uuid"]);
+        assert_eq!(task_uuid("auth.md", "T1"), task_uuid("auth.md", "T1"));
+        assert_ne!(task_uuid("auth.md", "T1"), task_uuid("api.md", "T1"));
+        let uuid = task_uuid("auth.md", "T1");
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn github_issues_link_dependencies_by_title() {
+        let out: serde_json::Value =
+            serde_json::from_str(&github_issues(&sample()).expect("render")).expect("json");
+        assert_eq!(out[0]["state"], "closed");
+        assert_eq!(out[1]["title"], "T2: Title T2");
+        let body = out[1]["body"].as_str().expect("body");
+        assert!(body.contains("- T1: Title T1\n"), "{body}");
+        assert!(
+            body.contains("**Acceptance criteria:**\n- [ ] Logs in\n"),
+            "{body}"
+        );
+    }
+}
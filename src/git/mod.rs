@@ -2,34 +2,83 @@ use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
+use crate::error::RalphError;
+use crate::ignore::RalphIgnore;
+
 /// Thin async wrapper around the `git` binary for branch and commit management.
 pub struct GitManager {
     workdir: PathBuf,
+    /// Pathspec (relative to `workdir`) that status checks and commits are limited to.
+    scope: Option<PathBuf>,
+    /// `:(exclude)` pathspecs for `.ralphignore`d paths.
+    excludes: Vec<String>,
 }
 
 impl GitManager {
     pub fn new(workdir: &Path) -> Self {
         Self {
             workdir: workdir.to_path_buf(),
+            scope: None,
+            excludes: Vec::new(),
         }
     }
 
+    /// Limit `has_changes` and `commit_all` to `subdir` (relative to the workdir),
+    /// so edits outside a monorepo subproject are never picked up.
+    pub fn scoped_to(mut self, subdir: &Path) -> Self {
+        self.scope = Some(subdir.to_path_buf());
+        self
+    }
+
+    /// Keep `.ralphignore`d paths out of `has_changes`, `commit_all`, and diffs.
+    pub fn ignoring(mut self, ignore: &RalphIgnore) -> Self {
+        self.excludes = ignore.exclude_pathspecs();
+        self
+    }
+
     // ── Internal helpers ──────────────────────────────────────────────────────
 
+    /// Append `-- <scope> <excludes…>` to `args` when a scope or excludes are set.
+    async fn run_scoped(&self, args: &[&str]) -> Result<String> {
+        if self.scope.is_none() && self.excludes.is_empty() {
+            return self.run(args).await;
+        }
+        let scope = self
+            .scope
+            .as_deref()
+            .unwrap_or(Path::new("."))
+            .to_string_lossy();
+        let mut scoped = args.to_vec();
+        scoped.extend(["--", scope.as_ref()]);
+        scoped.extend(self.excludes.iter().map(String::as_str));
+        self.run(&scoped).await
+    }
+
     async fn run(&self, args: &[&str]) -> Result<String> {
+        Ok(self.run_raw(args).await?.trim().to_string())
+    }
+
+    /// Like `run`, but keeps leading whitespace (significant in porcelain output).
+    async fn run_raw(&self, args: &[&str]) -> Result<String> {
         let output = Command::new("git")
             .args(args)
             .current_dir(&self.workdir)
             .output()
             .await
-            .with_context(|| format!("Failed to run: git {}", args.join(" ")))?;
+            .with_context(|| format!("Failed to run: git {}", args.join(" ")))
+            .map_err(RalphError::Git)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("git {} failed: {}", args[0], stderr.trim());
+            return Err(RalphError::Git(anyhow::anyhow!(
+                "git {} failed: {}",
+                args[0],
+                stderr.trim()
+            ))
+            .into());
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 
     // ── Public API ────────────────────────────────────────────────────────────
@@ -46,8 +95,9 @@ impl GitManager {
         self.run(&["rev-parse", "--abbrev-ref", "HEAD"]).await
     }
 
-    /// Create `branch` (if it doesn't exist) and check it out.
-    pub async fn create_or_checkout_branch(&self, branch: &str) -> Result<()> {
+    /// Create `branch` (if it doesn't exist) and check it out. A new branch
+    /// starts at `from` when given, else at HEAD.
+    pub async fn create_or_checkout_branch(&self, branch: &str, from: Option<&str>) -> Result<()> {
         // Check if the branch already exists locally.
         let list = self
             .run(&["branch", "--list", branch])
@@ -55,7 +105,9 @@ impl GitManager {
             .unwrap_or_default();
 
         if list.trim().is_empty() {
-            self.run(&["checkout", "-b", branch]).await?;
+            let mut args = vec!["checkout", "-b", branch];
+            args.extend(from);
+            self.run(&args).await?;
         } else {
             self.run(&["checkout", branch]).await?;
         }
@@ -63,19 +115,300 @@ impl GitManager {
         Ok(())
     }
 
-    /// Return `true` if the working tree has any uncommitted changes.
+    /// Return `true` if `rev` names a commit.
+    pub async fn rev_exists(&self, rev: &str) -> bool {
+        let commit = format!("{rev}^{{commit}}");
+        self.run(&["rev-parse", "--verify", "--quiet", &commit])
+            .await
+            .is_ok()
+    }
+
+    /// Return `true` if a remote called `name` is configured.
+    pub async fn has_remote(&self, name: &str) -> bool {
+        self.run(&["remote", "get-url", name]).await.is_ok()
+    }
+
+    /// Fetch `branch` from `remote`, updating its remote-tracking ref.
+    pub async fn fetch(&self, remote: &str, branch: &str) -> Result<()> {
+        self.run(&["fetch", "--quiet", remote, branch]).await?;
+        Ok(())
+    }
+
+    /// Number of commits reachable from `to` but not from `from`.
+    pub async fn commits_between(&self, from: &str, to: &str) -> Result<u64> {
+        let count = self
+            .run(&["rev-list", "--count", &format!("{from}..{to}")])
+            .await?;
+        count
+            .parse()
+            .with_context(|| format!("Unexpected rev-list output: {count}"))
+    }
+
+    /// Rebase the current branch onto `upstream`, stashing uncommitted changes
+    /// around it. A rebase that stops on conflicts is aborted, leaving the
+    /// branch as it was.
+    pub async fn rebase_onto(&self, upstream: &str) -> Result<()> {
+        if let Err(e) = self.run(&["rebase", "--autostash", upstream]).await {
+            let _ = self.run(&["rebase", "--abort"]).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Return `true` if tracked files anywhere in the workdir have uncommitted
+    /// changes — the ones a branch switch carries along or refuses to overwrite.
+    pub async fn has_tracked_changes(&self) -> Result<bool> {
+        let status = self
+            .run(&["status", "--porcelain", "--untracked-files=no"])
+            .await?;
+        Ok(!status.is_empty())
+    }
+
+    /// Stash uncommitted changes to tracked files under `message`.
+    pub async fn stash(&self, message: &str) -> Result<()> {
+        self.run(&["stash", "push", "-m", message]).await?;
+        Ok(())
+    }
+
+    /// Stash the uncommitted changes to workdir-relative `paths`, untracked
+    /// files included, under `message`.
+    pub async fn stash_paths(&self, message: &str, paths: &[&str]) -> Result<()> {
+        let mut args = vec!["stash", "push", "--include-untracked", "-m", message, "--"];
+        args.extend(paths);
+        self.run(&args).await?;
+        Ok(())
+    }
+
+    /// Commit every change to tracked files, ignoring any scope.
+    pub async fn commit_tracked(&self, message: &str) -> Result<String> {
+        self.run(&["commit", "-a", "-m", message]).await
+    }
+
+    /// Return `true` if the working tree (or the scoped subdirectory) has any
+    /// uncommitted changes.
     pub async fn has_changes(&self) -> Result<bool> {
-        let status = self.run(&["status", "--porcelain"]).await?;
+        let status = self.run_scoped(&["status", "--porcelain"]).await?;
         Ok(!status.trim().is_empty())
     }
 
-    /// Stage all changes and create a commit with `message`.
+    /// List every uncommitted path under the workdir, ignoring any scope.
+    /// Paths are relative to the workdir; untracked files are listed individually.
+    pub async fn changed_paths(&self) -> Result<Vec<ChangedPath>> {
+        let prefix = self.run(&["rev-parse", "--show-prefix"]).await?;
+        let status = self
+            .run_raw(&[
+                "status",
+                "--porcelain",
+                "-z",
+                "--untracked-files=all",
+                "--",
+                ".",
+            ])
+            .await?;
+
+        let mut paths = Vec::new();
+        let mut entries = status.split('\0').filter(|e| e.len() > 3);
+        while let Some(entry) = entries.next() {
+            let (code, path) = entry.split_at(3);
+            // Renames and copies are followed by their source path.
+            if code.starts_with(['R', 'C']) {
+                entries.next();
+            }
+            paths.push(ChangedPath {
+                path: path
+                    .strip_prefix(prefix.as_str())
+                    .unwrap_or(path)
+                    .to_string(),
+                untracked: code.starts_with("??"),
+            });
+        }
+        Ok(paths)
+    }
+
+    /// Throw away uncommitted changes to a single workdir-relative path.
+    pub async fn revert_path(&self, change: &ChangedPath) -> Result<()> {
+        if change.untracked {
+            let full = self.workdir.join(&change.path);
+            return std::fs::remove_file(&full)
+                .with_context(|| format!("Cannot remove {}", full.display()));
+        }
+        if self
+            .run(&["checkout", "HEAD", "--", &change.path])
+            .await
+            .is_err()
+        {
+            // Not in HEAD: a newly added file.
+            self.run(&["rm", "-f", "-q", "--", &change.path]).await?;
+        }
+        Ok(())
+    }
+
+    /// Record the tracked files' uncommitted state as a dangling commit,
+    /// leaving the working tree alone. `None` when there is nothing to record.
+    pub async fn stash_create(&self) -> Result<Option<String>> {
+        let hash = self.run(&["stash", "create"]).await?;
+        Ok((!hash.is_empty()).then_some(hash))
+    }
+
+    /// Workdir-relative paths whose working-tree content differs from `rev`.
+    pub async fn paths_changed_since(&self, rev: &str) -> Result<Vec<String>> {
+        let names = self
+            .run_raw(&["diff", "--name-only", "--relative", "-z", rev])
+            .await?;
+        Ok(names
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Like [`paths_changed_since`](Self::paths_changed_since), paired with
+    /// each path's status letter (`A`, `M`, `D`, ...). Renames show as a
+    /// delete and an add.
+    pub async fn name_status_since(&self, rev: &str) -> Result<Vec<(char, String)>> {
+        let out = self
+            .run_raw(&["diff", "--name-status", "--no-renames", "--relative", "-z", rev])
+            .await?;
+        let mut fields = out.split('\0').filter(|f| !f.is_empty());
+        let mut changes = Vec::new();
+        while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+            let letter = status.chars().next().unwrap_or('M');
+            changes.push((letter, path.to_string()));
+        }
+        Ok(changes)
+    }
+
+    /// Put a workdir-relative path back the way it is in `rev`, or remove it
+    /// when `rev` doesn't have it. Returns whether the file was removed.
+    pub async fn restore_path_from(&self, rev: &str, path: &str) -> Result<bool> {
+        let object = format!("{rev}:./{path}");
+        if self.run(&["cat-file", "-e", &object]).await.is_ok() {
+            // Only the working tree: `checkout` would also stage the file.
+            self.run(&["restore", &format!("--source={rev}"), "--worktree", "--", path])
+                .await?;
+            return Ok(false);
+        }
+        self.run(&["rm", "--cached", "-q", "--ignore-unmatch", "--", path])
+            .await?;
+        let full = self.workdir.join(path);
+        if full.exists() {
+            std::fs::remove_file(&full)
+                .with_context(|| format!("Cannot remove {}", full.display()))?;
+        }
+        Ok(true)
+    }
+
+    /// Return the full patch for a revision range (e.g. `main..feature`).
+    pub async fn diff_range(&self, range: &str) -> Result<String> {
+        self.run_scoped(&["diff", range]).await
+    }
+
+    /// Return `--stat` output for a revision range.
+    pub async fn diff_stat_range(&self, range: &str) -> Result<String> {
+        self.run_scoped(&["diff", "--stat", range]).await
+    }
+
+    /// Human-readable `git status --short --branch` for the whole workdir.
+    pub async fn status_summary(&self) -> Result<String> {
+        self.run(&["status", "--short", "--branch"]).await
+    }
+
+    /// Uncommitted changes to tracked files, as a patch against HEAD.
+    pub async fn diff_head(&self) -> Result<String> {
+        self.run_scoped(&["diff", "HEAD"]).await
+    }
+
+    /// Lines added plus removed for each tracked path whose working-tree
+    /// content differs from `rev`, relative to the workdir. Binary files
+    /// count as 0.
+    pub async fn numstat_since(&self, rev: &str) -> Result<Vec<(String, usize)>> {
+        let numstat = self.run(&["diff", "--numstat", "--relative", rev]).await?;
+        Ok(numstat
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let added: usize = fields.next()?.parse().unwrap_or(0);
+                let removed: usize = fields.next()?.parse().unwrap_or(0);
+                Some((fields.next()?.to_string(), added + removed))
+            })
+            .collect())
+    }
+
+    /// Full hash of the commit HEAD points at.
+    pub async fn head_commit(&self) -> Result<String> {
+        self.run(&["rev-parse", "HEAD"]).await
+    }
+
+    /// Return one-line commit subjects for a revision range.
+    pub async fn log_range(&self, range: &str) -> Result<String> {
+        self.run(&["log", "--oneline", range]).await
+    }
+
+    /// Tracked files (within the scope, if set), relative to the workdir.
+    pub async fn tracked_files(&self) -> Result<Vec<String>> {
+        let files = self.run_scoped(&["ls-files", "-z"]).await?;
+        Ok(files
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Subject and patch of the last `count` commits (within the scope, if
+    /// set), newest first. Ralph's state directories are left out.
+    pub async fn recent_patches(&self, count: usize) -> Result<String> {
+        let count = format!("-{count}");
+        let scope = self
+            .scope
+            .as_deref()
+            .unwrap_or(Path::new("."))
+            .to_string_lossy();
+        let mut args = vec![
+            "log",
+            &count,
+            "--patch",
+            "--no-color",
+            "--format=commit %h %s",
+            "--",
+            scope.as_ref(),
+            ":(exclude).ralph*",
+        ];
+        args.extend(self.excludes.iter().map(String::as_str));
+        self.run(&args).await
+    }
+
+    /// Hash and subject of each commit whose message contains `text`, newest first.
+    pub async fn commits_mentioning(&self, text: &str) -> Result<Vec<(String, String)>> {
+        let grep = format!("--grep={text}");
+        let log = self
+            .run(&["log", "--fixed-strings", &grep, "--format=%H %s"])
+            .await?;
+        Ok(log
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(hash, subject)| (hash.to_string(), subject.to_string()))
+            .collect())
+    }
+
+    /// A commit's message, stat, and patch.
+    pub async fn show_commit(&self, rev: &str) -> Result<String> {
+        self.run(&["show", "--stat", "--patch", rev]).await
+    }
+
+    /// Stage all changes (within the scope, if set) and create a commit with `message`.
     pub async fn commit_all(&self, message: &str) -> Result<String> {
-        self.run(&["add", "-A"]).await?;
+        self.run_scoped(&["add", "-A"]).await?;
         self.run(&["commit", "-m", message]).await
     }
 }
 
+/// One entry from `git status`, relative to the workdir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedPath {
+    pub path: String,
+    pub untracked: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,7 +457,7 @@ mod tests {
         let before_head = run_git(dir.path(), &["rev-parse", "HEAD"]);
 
         manager
-            .create_or_checkout_branch("feature/test-branch")
+            .create_or_checkout_branch("feature/test-branch", None)
             .await
             .expect("create branch");
 
@@ -145,7 +478,7 @@ mod tests {
 
         let manager = GitManager::new(dir.path());
         manager
-            .create_or_checkout_branch("existing-branch")
+            .create_or_checkout_branch("existing-branch", None)
             .await
             .expect("checkout existing branch");
 
@@ -193,13 +526,122 @@ mod tests {
         assert_eq!(subject, message);
     }
 
+    #[tokio::test]
+    async fn scoped_manager_only_sees_and_commits_its_subdirectory() {
+        let dir = init_repo();
+        create_initial_commit(dir.path());
+        fs::create_dir_all(dir.path().join("packages/api")).expect("create subproject");
+        fs::write(dir.path().join("README.md"), "outside\n").expect("edit outside scope");
+
+        let manager = GitManager::new(dir.path()).scoped_to(Path::new("packages/api"));
+        assert!(!manager.has_changes().await.expect("status outside scope"));
+
+        fs::write(dir.path().join("packages/api/lib.rs"), "// api\n").expect("write api file");
+        assert!(manager.has_changes().await.expect("status inside scope"));
+        manager
+            .commit_all("feat: api")
+            .await
+            .expect("scoped commit");
+
+        let changed_files = run_git(
+            dir.path(),
+            &["show", "--pretty=format:", "--name-only", "HEAD"],
+        );
+        assert_eq!(changed_files.trim(), "packages/api/lib.rs");
+        // The out-of-scope edit is left uncommitted.
+        assert!(GitManager::new(dir.path())
+            .has_changes()
+            .await
+            .expect("status"));
+    }
+
+    #[tokio::test]
+    async fn ignoring_manager_never_commits_ralphignored_paths() {
+        let dir = init_repo();
+        create_initial_commit(dir.path());
+        fs::create_dir_all(dir.path().join("vendor/lib")).expect("create vendor");
+        fs::write(dir.path().join("vendor/lib/a.go"), "package lib\n").expect("write vendor");
+        fs::write(dir.path().join("schema.pb.go"), "generated\n").expect("write generated");
+
+        let ignore = RalphIgnore::parse("vendor/\n*.pb.go\n").expect("parse ignore");
+        let manager = GitManager::new(dir.path()).ignoring(&ignore);
+        assert!(!manager.has_changes().await.expect("status ignoring"));
+
+        fs::write(dir.path().join("main.go"), "package main\n").expect("write main");
+        manager.commit_all("feat: main").await.expect("commit");
+
+        let changed_files = run_git(
+            dir.path(),
+            &["show", "--pretty=format:", "--name-only", "HEAD"],
+        );
+        assert_eq!(changed_files.trim(), "main.go");
+        let stat = manager.diff_stat_range("HEAD~1..HEAD").await.expect("stat");
+        assert!(stat.contains("main.go"));
+    }
+
+    #[tokio::test]
+    async fn changed_paths_lists_edits_and_revert_path_discards_them() {
+        let dir = init_repo();
+        create_initial_commit(dir.path());
+        fs::write(dir.path().join("README.md"), "edited\n").expect("edit tracked file");
+        fs::create_dir_all(dir.path().join("docs")).expect("create docs");
+        fs::write(dir.path().join("docs/new.md"), "new\n").expect("write untracked file");
+
+        let manager = GitManager::new(dir.path());
+        let mut changed = manager.changed_paths().await.expect("changed paths");
+        changed.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            changed,
+            vec![
+                ChangedPath {
+                    path: "README.md".to_string(),
+                    untracked: false,
+                },
+                ChangedPath {
+                    path: "docs/new.md".to_string(),
+                    untracked: true,
+                },
+            ]
+        );
+
+        for change in &changed {
+            manager.revert_path(change).await.expect("revert");
+        }
+        assert!(!manager.has_changes().await.expect("status after revert"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("README.md")).expect("read"),
+            "initial\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn range_helpers_report_commits_and_patch() {
+        let dir = init_repo();
+        create_initial_commit(dir.path());
+        let base = run_git(dir.path(), &["rev-parse", "HEAD"]);
+        fs::write(dir.path().join("feature.rs"), "// TODO: finish\n").expect("write file");
+        run_git(dir.path(), &["add", "feature.rs"]);
+        run_git(dir.path(), &["commit", "-m", "wip: start feature"]);
+
+        let manager = GitManager::new(dir.path());
+        let range = format!("{base}..HEAD");
+
+        let log = manager.log_range(&range).await.expect("log range");
+        let diff = manager.diff_range(&range).await.expect("diff range");
+        let stat = manager.diff_stat_range(&range).await.expect("diff stat");
+
+        assert!(log.contains("wip: start feature"));
+        assert!(diff.contains("+// TODO: finish"));
+        assert!(stat.contains("feature.rs"));
+    }
+
     #[tokio::test]
     async fn works_in_fresh_git_repo_with_no_prior_commits() {
         let dir = init_repo();
         let manager = GitManager::new(dir.path());
 
         manager
-            .create_or_checkout_branch("fresh-start")
+            .create_or_checkout_branch("fresh-start", None)
             .await
             .expect("create branch in fresh repo");
         fs::write(dir.path().join("first.txt"), "first commit\n").expect("write first file");
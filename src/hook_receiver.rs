@@ -0,0 +1,356 @@
+//! `ralph hooks serve`: a local webhook receiver for developing hook
+//! integrations. It prints each event Ralph POSTs to it, and can append them
+//! to a JSON Lines file and show them as desktop notifications.
+//!
+//! Like `--status-port`, it binds to localhost only and speaks just enough
+//! HTTP for `curl`.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde_json::{json, Value};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request body accepted; hook payloads are a few KB at most.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// What to do with each event received.
+pub struct Receiver {
+    /// Bearer token requests must carry, as `[hooks] token` sends it.
+    pub token: Option<String>,
+    /// JSON Lines file every event is appended to.
+    pub out: Option<PathBuf>,
+    /// Show each event as a desktop notification.
+    pub desktop: bool,
+    /// Events received so far.
+    count: Mutex<u64>,
+}
+
+impl Receiver {
+    pub fn new(token: Option<String>, out: Option<PathBuf>, desktop: bool) -> Self {
+        Self {
+            token,
+            out,
+            desktop,
+            count: Mutex::new(0),
+        }
+    }
+}
+
+/// Listen on `127.0.0.1:<port>` until Ctrl+C.
+pub async fn serve(port: u16, receiver: Receiver) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Cannot listen on port {port}"))?;
+    let url = format!("http://127.0.0.1:{port}/");
+    println!("🔔  Listening for hook events on {url}");
+    println!("    Send them here with --hook-url {url} (Ctrl+C to stop)");
+    if receiver.token.is_some() {
+        println!("    Requests without the [hooks] token are rejected");
+    }
+    if let Some(out) = &receiver.out {
+        println!("    Appending events to {}", out.display());
+    }
+
+    let receiver = Arc::new(receiver);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((stream, _)) = accepted else { continue };
+                let receiver = receiver.clone();
+                tokio::spawn(async move {
+                    let _ = respond(stream, &receiver).await;
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    let count = *receiver.count.lock().unwrap_or_else(|e| e.into_inner());
+    println!("\n{count} event(s) received.");
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, receiver: &Receiver) -> std::io::Result<()> {
+    let (code, reply) = match read_request(&mut stream).await? {
+        Request::Invalid(reason) => ("400 Bad Request", json!({ "error": reason })),
+        Request::Post { authorization, .. } if !authorized(receiver, authorization.as_deref()) => {
+            println!(
+                "{}  ⛔  rejected a request with a missing or wrong token",
+                now()
+            );
+            ("401 Unauthorized", json!({ "error": "bad bearer token" }))
+        }
+        Request::Post { body, .. } => match serde_json::from_slice::<Value>(&body) {
+            Ok(event) => {
+                receive(receiver, &event);
+                ("200 OK", json!({ "ok": true }))
+            }
+            Err(e) => (
+                "400 Bad Request",
+                json!({ "error": format!("body is not JSON: {e}") }),
+            ),
+        },
+        Request::Other => (
+            "405 Method Not Allowed",
+            json!({ "error": "send events with POST" }),
+        ),
+    };
+    let reply = reply.to_string();
+    let response = format!(
+        "HTTP/1.1 {code}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{reply}",
+        reply.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+enum Request {
+    Post {
+        authorization: Option<String>,
+        body: Vec<u8>,
+    },
+    Other,
+    Invalid(String),
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(end) = find(&buf, b"\r\n\r\n") {
+            break end;
+        }
+        let n = read_with_timeout(stream, &mut chunk).await?;
+        if n == 0 || buf.len() > 64 * 1024 {
+            return Ok(Request::Invalid("incomplete request headers".to_string()));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.lines();
+    if !lines.next().unwrap_or("").starts_with("POST ") {
+        return Ok(Request::Other);
+    }
+    let mut length = 0;
+    let mut authorization = None;
+    let mut expects_continue = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().unwrap_or(0),
+            "authorization" => authorization = Some(value.to_string()),
+            "expect" => expects_continue = value.eq_ignore_ascii_case("100-continue"),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY_BYTES {
+        return Ok(Request::Invalid(format!(
+            "body over {MAX_BODY_BYTES} bytes"
+        )));
+    }
+    // curl holds back bodies over 1 KB until the server agrees to take them.
+    if expects_continue {
+        stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+    }
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < length {
+        let n = read_with_timeout(stream, &mut chunk).await?;
+        if n == 0 {
+            return Ok(Request::Invalid(
+                "body shorter than Content-Length".to_string(),
+            ));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    Ok(Request::Post {
+        authorization,
+        body,
+    })
+}
+
+async fn read_with_timeout(stream: &mut TcpStream, buf: &mut [u8]) -> std::io::Result<usize> {
+    tokio::time::timeout(Duration::from_secs(5), stream.read(buf))
+        .await
+        .unwrap_or(Ok(0))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn authorized(receiver: &Receiver, authorization: Option<&str>) -> bool {
+    match &receiver.token {
+        Some(token) => authorization.and_then(|a| a.strip_prefix("Bearer ")) == Some(token),
+        None => true,
+    }
+}
+
+/// Print, store, and show one event.
+fn receive(receiver: &Receiver, event: &Value) {
+    *receiver.count.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+    let (name, detail) = describe(event);
+    println!("{}  {name:<18} {detail}", now());
+
+    if let Some(out) = &receiver.out {
+        if let Err(e) = append_line(out, event) {
+            eprintln!("⚠️  Cannot write {}: {e:#}", out.display());
+        }
+    }
+    if receiver.desktop {
+        desktop_notification(&format!("Ralph: {name}"), &detail);
+    }
+}
+
+/// The event's name, and a one-line summary of what it is about.
+fn describe(event: &Value) -> (String, String) {
+    let field = |key: &str| event.get(key).and_then(Value::as_str);
+    let name = field("event").unwrap_or("unknown").to_string();
+
+    let mut detail = match (field("task_id"), field("task_title")) {
+        (Some(id), Some(title)) => format!("{id} — {title}"),
+        (Some(id), None) => id.to_string(),
+        _ => String::new(),
+    };
+    let note = ["error", "last_error", "reason", "summary", "message"]
+        .into_iter()
+        .find_map(field)
+        .and_then(|text| text.lines().next());
+    if let Some(note) = note {
+        if !detail.is_empty() {
+            detail.push_str(": ");
+        }
+        detail.push_str(note);
+    }
+    if let Some(progress) = event.get("progress") {
+        let count = |key: &str| progress.get(key).and_then(Value::as_u64).unwrap_or(0);
+        detail.push_str(&format!(
+            " ({}/{} done)",
+            count("completed"),
+            count("total")
+        ));
+    }
+    (name, detail.trim_start().to_string())
+}
+
+fn append_line(path: &Path, event: &Value) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{event}")?;
+    Ok(())
+}
+
+fn now() -> String {
+    Local::now().format("%H:%M:%S").to_string()
+}
+
+/// Best effort: `notify-send` on Linux, `osascript` on macOS.
+fn desktop_notification(title: &str, body: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut cmd = std::process::Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"{}\"",
+            quote(body),
+            quote(title)
+        ));
+        cmd
+    } else {
+        let mut cmd = std::process::Command::new("notify-send");
+        cmd.arg(title).arg(body);
+        cmd
+    };
+    let _ = cmd
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `raw` to a one-shot receiver and return the response.
+    async fn exchange(receiver: &Receiver, raw: &[u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let mut client = TcpStream::connect(addr).await.expect("connect");
+        let (stream, _) = listener.accept().await.expect("accept");
+        client.write_all(raw).await.expect("write");
+        respond(stream, receiver).await.expect("respond");
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.expect("read");
+        response
+    }
+
+    fn post(body: &str, extra_headers: &str) -> Vec<u8> {
+        format!(
+            "POST / HTTP/1.1\r\nHost: x\r\nContent-Type: application/json\r\n{extra_headers}Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes()
+    }
+
+    #[tokio::test]
+    async fn events_are_stored_and_bad_tokens_rejected() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let out = dir.path().join("events.jsonl");
+        let receiver = Receiver::new(Some("secret".to_string()), Some(out.clone()), false);
+        let event = r#"{"event":"task_failed","task_id":"T2","task_title":"Login","error":"timed out\nafter 600s","progress":{"completed":1,"failed":1,"remaining":1,"total":3}}"#;
+
+        let denied = exchange(&receiver, &post(event, "Authorization: Bearer wrong\r\n")).await;
+        assert!(denied.starts_with("HTTP/1.1 401"), "{denied}");
+        let accepted = exchange(
+            &receiver,
+            &post(
+                event,
+                "Authorization: Bearer secret\r\nExpect: 100-continue\r\n",
+            ),
+        )
+        .await;
+        assert!(
+            accepted.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK"),
+            "{accepted}"
+        );
+
+        let stored = std::fs::read_to_string(&out).expect("read events");
+        let lines: Vec<Value> = stored
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json line"))
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["task_id"], "T2");
+
+        let (name, detail) = describe(&lines[0]);
+        assert_eq!(name, "task_failed");
+        assert_eq!(detail, "T2 — Login: timed out (1/3 done)");
+    }
+
+    #[tokio::test]
+    async fn only_json_posts_are_accepted() {
+        let receiver = Receiver::new(None, None, false);
+        let get = exchange(&receiver, b"GET / HTTP/1.1\r\n\r\n").await;
+        assert!(get.starts_with("HTTP/1.1 405"), "{get}");
+        let garbage = exchange(&receiver, &post("not json", "")).await;
+        assert!(garbage.starts_with("HTTP/1.1 400"), "{garbage}");
+        let ping = exchange(&receiver, &post(r#"{"event":"ping","message":"hi"}"#, "")).await;
+        assert!(ping.starts_with("HTTP/1.1 200"), "{ping}");
+        assert_eq!(*receiver.count.lock().unwrap(), 1);
+    }
+}
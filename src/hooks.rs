@@ -1,7 +1,17 @@
 //! Callback hooks — notify external systems (e.g. OpenClaw) when events occur.
 
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::agents::TokenUsage;
+use crate::doctor::DoctorRow;
+use crate::output::{LineProcessor, Redact};
+
+/// Most bytes of iteration log a failure event carries.
+const MAX_LOG_TAIL_BYTES: usize = 4096;
 
 /// Events that can be sent to the callback hook.
 #[derive(Debug, Clone, Serialize)]
@@ -24,9 +34,86 @@ pub enum HookEvent {
         iteration: u32,
         duration_secs: u64,
         error: String,
+        /// `auth`, `rate_limit`, `network`, `sandbox_denied`, `agent_crash`,
+        /// or `task`.
+        failure_kind: String,
         consecutive_failures: u32,
         progress: Progress,
     },
+    /// The agent claimed completion below `--min-confidence`, or the task's
+    /// diff broke `--max-files-changed`/`--max-lines-changed`, and the task is
+    /// waiting on `ralph approve`.
+    TaskNeedsReview {
+        task_id: String,
+        task_title: String,
+        iteration: u32,
+        confidence: Option<f64>,
+        threshold: f64,
+        /// The task's acceptance criteria, for the reviewer to check.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        acceptance: Vec<String>,
+        /// What held the task back when it wasn't low confidence, e.g.
+        /// `62 files changed (cap 50)`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        progress: Progress,
+    },
+    /// The agent changed files outside `--project`; they were reverted.
+    ScopeViolation {
+        task_id: String,
+        task_title: String,
+        iteration: u32,
+        project: String,
+        paths: Vec<String>,
+        /// Subset of `paths` protected by `.ralphignore`.
+        ignored: Vec<String>,
+        /// Subset of `paths` whose revert failed.
+        unreverted: Vec<String>,
+        progress: Progress,
+    },
+    /// The running iteration has used `--timeout-warn` of its `--timeout`.
+    TimeoutWarning {
+        task_id: String,
+        task_title: String,
+        iteration: u32,
+        elapsed_secs: u64,
+        timeout_secs: u64,
+        progress: Progress,
+    },
+    /// The agent hit a provider rate limit; Ralph waits `retry_after_secs`
+    /// and retries the task without counting a failure.
+    RateLimited {
+        task_id: String,
+        task_title: String,
+        iteration: u32,
+        retry_after_secs: u64,
+        progress: Progress,
+    },
+    /// The loop moved to another agent (`[fallback]`), or back to the task's
+    /// own one.
+    AgentSwitched {
+        /// The task the new agent takes over; `None` when the operator
+        /// switched at the circuit breaker.
+        task_id: Option<String>,
+        from: String,
+        to: String,
+        /// `failures`, `rate_limited`, `auth`, `operator`, or `returned`.
+        reason: String,
+        /// How long `from` is benched (`[fallback] cooldown`), if it is.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        benched_secs: Option<u64>,
+        progress: Progress,
+    },
+    /// Sent as each iteration starts, with a time-remaining estimate.
+    Heartbeat {
+        task_id: String,
+        task_title: String,
+        iteration: u32,
+        elapsed_secs: u64,
+        avg_iteration_secs: Option<u64>,
+        eta_secs: Option<u64>,
+        progress: Progress,
+    },
     /// All tasks finished — the full PRD is implemented.
     AllComplete {
         total_tasks: u32,
@@ -34,18 +121,84 @@ pub enum HookEvent {
         total_duration_secs: u64,
         summary: String,
         progress: Progress,
+        /// Token totals from usage.json, when the agent reports usage.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tokens: Option<TokenUsage>,
+        /// Estimated cost, for models with known pricing.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cost_usd: Option<f64>,
+    },
+    /// Ralph stopped before an iteration because `--max-tokens` or
+    /// `--max-cost-usd` was reached.
+    BudgetExceeded {
+        reason: String,
+        tokens: TokenUsage,
+        cost_usd: Option<f64>,
+        max_tokens: Option<u64>,
+        max_cost_usd: Option<f64>,
+        progress: Progress,
     },
     /// Ralph stopped due to circuit breaker (too many consecutive failures).
     CircuitBreaker {
         consecutive_failures: u32,
         last_error: String,
         progress: Progress,
+        /// `ralph doctor` checks, run when the breaker tripped.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        doctor: Vec<DoctorRow>,
+    },
+    /// More tasks are left than iterations: warned once per run, and sent
+    /// again if `--auto-extend` raises the limit.
+    IterationsShort {
+        remaining_tasks: u32,
+        iterations_left: u32,
+        max_iterations: u32,
+        /// The new `--max-iterations`, when `--auto-extend` raised it.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        extended_to: Option<u32>,
+        progress: Progress,
     },
     /// Ralph stopped because it hit max iterations without finishing.
     MaxIterations {
         max_iterations: u32,
         progress: Progress,
     },
+    /// `ralph watch` started its loops.
+    WatchStarted {
+        loops: Vec<WatchLoop>,
+        parallel: usize,
+    },
+    /// Every `ralph watch` loop has finished, failed, or stopped.
+    WatchComplete {
+        loops: Vec<WatchLoopSummary>,
+        total_duration_secs: u64,
+    },
+    /// Synthetic event sent by `ralph hooks test` to validate a webhook setup.
+    Ping { message: String, sent_at: DateTime<Utc> },
+}
+
+impl HookEvent {
+    /// Stable snake_case name of the event (matches the serialized `event` tag).
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::TaskComplete { .. } => "task_complete",
+            HookEvent::TaskFailed { .. } => "task_failed",
+            HookEvent::TaskNeedsReview { .. } => "task_needs_review",
+            HookEvent::ScopeViolation { .. } => "scope_violation",
+            HookEvent::TimeoutWarning { .. } => "timeout_warning",
+            HookEvent::RateLimited { .. } => "rate_limited",
+            HookEvent::AgentSwitched { .. } => "agent_switched",
+            HookEvent::Heartbeat { .. } => "heartbeat",
+            HookEvent::AllComplete { .. } => "all_complete",
+            HookEvent::BudgetExceeded { .. } => "budget_exceeded",
+            HookEvent::CircuitBreaker { .. } => "circuit_breaker",
+            HookEvent::IterationsShort { .. } => "iterations_short",
+            HookEvent::MaxIterations { .. } => "max_iterations",
+            HookEvent::WatchStarted { .. } => "watch_started",
+            HookEvent::WatchComplete { .. } => "watch_complete",
+            HookEvent::Ping { .. } => "ping",
+        }
+    }
 }
 
 /// Progress snapshot included in every event.
@@ -57,6 +210,26 @@ pub struct Progress {
     pub total: u32,
 }
 
+/// One loop of a `ralph watch` session.
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchLoop {
+    pub name: String,
+    pub prd: String,
+}
+
+/// How one `ralph watch` loop ended.
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchLoopSummary {
+    pub name: String,
+    pub prd: String,
+    /// `complete`, `failed`, `stopped`, or `incomplete` (e.g. out of iterations).
+    pub outcome: String,
+    pub error: Option<String>,
+    pub tasks_done: u32,
+    pub tasks_total: u32,
+    pub duration_secs: u64,
+}
+
 /// Configuration for the callback hook.
 #[derive(Debug, Clone)]
 pub struct HookConfig {
@@ -66,6 +239,8 @@ pub struct HookConfig {
     pub token: Option<String>,
     /// Timeout for HTTP requests.
     pub timeout: Duration,
+    /// Lines of the iteration log attached to failure events (0 = none).
+    pub log_lines: usize,
 }
 
 impl HookConfig {
@@ -74,21 +249,17 @@ impl HookConfig {
             url,
             token,
             timeout: Duration::from_secs(10),
+            log_lines: 0,
         }
     }
 }
 
 /// Send a hook event. Fires and forgets — errors are logged but don't stop Ralph.
-pub async fn send_hook(config: &HookConfig, event: &HookEvent) {
-    let event_name = match event {
-        HookEvent::TaskComplete { .. } => "task_complete",
-        HookEvent::TaskFailed { .. } => "task_failed",
-        HookEvent::AllComplete { .. } => "all_complete",
-        HookEvent::CircuitBreaker { .. } => "circuit_breaker",
-        HookEvent::MaxIterations { .. } => "max_iterations",
-    };
+/// `log_path` is the log of the iteration the event is about, if any.
+pub async fn send_hook(config: &HookConfig, event: &HookEvent, log_path: Option<&Path>) {
+    let event_name = event.name();
 
-    let body = match serde_json::to_string(event) {
+    let body = match payload(config, event, log_path).and_then(|b| serde_json::to_string(&b)) {
         Ok(b) => b,
         Err(e) => {
             eprintln!("⚠️  Hook: failed to serialize event: {e}");
@@ -134,3 +305,238 @@ pub async fn send_hook(config: &HookConfig, event: &HookEvent) {
         }
     }
 }
+
+/// The JSON body for `event`. `task_failed` and `circuit_breaker` get a
+/// `log_tail` of the iteration log when `config.log_lines` asks for one.
+fn payload(
+    config: &HookConfig,
+    event: &HookEvent,
+    log_path: Option<&Path>,
+) -> serde_json::Result<serde_json::Value> {
+    let mut body = serde_json::to_value(event)?;
+    let is_failure = matches!(
+        event,
+        HookEvent::TaskFailed { .. } | HookEvent::CircuitBreaker { .. }
+    );
+    if is_failure && config.log_lines > 0 {
+        if let Some(tail) = log_path.and_then(|p| read_log_tail(p, config.log_lines)) {
+            body["log_tail"] = redacted(&tail).into();
+        }
+    }
+    Ok(body)
+}
+
+/// Read the last N lines from a log file.
+pub fn read_log_tail(log_path: &Path, lines: usize) -> Option<String> {
+    let content = std::fs::read_to_string(log_path).ok()?;
+    let all_lines: Vec<&str> = content.lines().collect();
+    let start = all_lines.len().saturating_sub(lines);
+    Some(all_lines[start..].join("\n"))
+}
+
+/// `tail` with secrets masked, cut to its last `MAX_LOG_TAIL_BYTES`. The log
+/// was already redacted if `[output]` kept `redact`; this covers the rest.
+pub fn redacted(tail: &str) -> String {
+    let redact = Redact::new(&[]).expect("built-in secret patterns");
+    let lines: Vec<String> = tail
+        .lines()
+        .filter_map(|line| redact.process(line.to_string()))
+        .collect();
+    let text = lines.join("\n");
+    if text.len() <= MAX_LOG_TAIL_BYTES {
+        return text;
+    }
+    let mut start = text.len() - MAX_LOG_TAIL_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &text[start..])
+}
+
+/// Result of delivering a test event to a webhook.
+#[derive(Debug)]
+pub struct PingReport {
+    /// HTTP status code returned by the receiver (0 if no response was received).
+    pub status: u16,
+    /// Round-trip time of the request.
+    pub latency: Duration,
+}
+
+impl PingReport {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// POST a synthetic `Ping` event to the hook URL and report status + latency.
+///
+/// Unlike `send_hook`, this surfaces the HTTP status so callers can tell a
+/// misconfigured receiver apart from one that accepted the event.
+pub async fn send_ping(config: &HookConfig) -> Result<PingReport> {
+    let event = HookEvent::Ping {
+        message: "Test event from `ralph hooks test`".to_string(),
+        sent_at: Utc::now(),
+    };
+    let body = serde_json::to_string(&event).context("Failed to serialize ping event")?;
+
+    let mut cmd = tokio::process::Command::new("curl");
+    cmd.arg("-s")
+        .arg("-o")
+        .arg("/dev/null")
+        .arg("-w")
+        .arg("%{http_code}")
+        .arg("-X")
+        .arg("POST")
+        .arg("-H")
+        .arg("Content-Type: application/json")
+        .arg("--max-time")
+        .arg(config.timeout.as_secs().to_string());
+
+    if let Some(ref token) = config.token {
+        cmd.arg("-H").arg(format!("Authorization: Bearer {token}"));
+    }
+
+    cmd.arg("-d").arg(&body).arg(&config.url);
+    cmd.stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+
+    let started = Instant::now();
+    let output = cmd
+        .output()
+        .await
+        .context("Failed to run curl — is it installed?")?;
+    let latency = started.elapsed();
+
+    let status = parse_http_code(&String::from_utf8_lossy(&output.stdout));
+    if status == 0 && !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "No response from {} (curl {}): {}",
+            config.url,
+            output.status,
+            stderr.trim()
+        );
+    }
+
+    Ok(PingReport { status, latency })
+}
+
+/// Parse the `%{http_code}` curl write-out; `000` (no response) maps to 0.
+fn parse_http_code(raw: &str) -> u16 {
+    raw.trim().parse().unwrap_or(0)
+}
+
+/// `ralph hooks test` entry point — send a ping and print the outcome.
+pub async fn test_hook(config: &HookConfig) -> Result<()> {
+    println!("🔔  Sending test event to {}…", config.url);
+    let report = send_ping(config).await?;
+    let latency_ms = report.latency.as_millis();
+
+    if report.is_success() {
+        println!("    ✅  HTTP {} in {}ms", report.status, latency_ms);
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "Webhook responded with HTTP {} in {}ms",
+            report.status,
+            latency_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_event_serializes_with_event_tag() {
+        let event = HookEvent::Ping {
+            message: "hello".to_string(),
+            sent_at: Utc::now(),
+        };
+        let json = serde_json::to_value(&event).expect("serialize ping");
+
+        assert_eq!(json["event"], "ping");
+        assert_eq!(json["message"], "hello");
+        assert_eq!(event.name(), "ping");
+    }
+
+    #[test]
+    fn agent_switches_name_the_agents_and_the_reason() {
+        let event = HookEvent::AgentSwitched {
+            task_id: Some("T6".to_string()),
+            from: "codex".to_string(),
+            to: "gemini".to_string(),
+            reason: "rate_limited".to_string(),
+            benched_secs: Some(600),
+            progress: Progress {
+                completed: 0,
+                failed: 0,
+                remaining: 1,
+                total: 1,
+            },
+        };
+        let json = serde_json::to_value(&event).expect("serialize switch");
+
+        assert_eq!(json["event"], "agent_switched");
+        assert_eq!(event.name(), "agent_switched");
+        assert_eq!(json["from"], "codex");
+        assert_eq!(json["to"], "gemini");
+        assert_eq!(json["benched_secs"], 600);
+    }
+
+    #[test]
+    fn failure_events_carry_a_redacted_log_tail() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let log = dir.path().join("iter.log");
+        std::fs::write(
+            &log,
+            "starting\ncompiling\nerror: token=abcdef1234567890 rejected\nexit 1\n",
+        )
+        .expect("write log");
+        let mut config = HookConfig::new("http://localhost".to_string(), None);
+        let breaker = HookEvent::CircuitBreaker {
+            consecutive_failures: 3,
+            last_error: "Too many consecutive failures".to_string(),
+            progress: Progress {
+                completed: 0,
+                failed: 1,
+                remaining: 0,
+                total: 1,
+            },
+            doctor: vec![],
+        };
+
+        let body = payload(&config, &breaker, Some(&log)).expect("payload");
+        assert!(body.get("log_tail").is_none(), "off by default");
+
+        config.log_lines = 2;
+        let body = payload(&config, &breaker, Some(&log)).expect("payload");
+        assert_eq!(body["log_tail"], "error: [redacted] rejected\nexit 1");
+        assert_eq!(body["event"], "circuit_breaker");
+
+        let long = "x".repeat(MAX_LOG_TAIL_BYTES * 2);
+        assert_eq!(redacted(&long).len(), MAX_LOG_TAIL_BYTES + "…".len());
+    }
+
+    #[test]
+    fn parse_http_code_handles_missing_response() {
+        assert_eq!(parse_http_code("204"), 204);
+        assert_eq!(parse_http_code("000"), 0);
+        assert_eq!(parse_http_code(""), 0);
+    }
+
+    #[test]
+    fn ping_report_success_is_2xx_only() {
+        let ok = PingReport {
+            status: 202,
+            latency: Duration::from_millis(5),
+        };
+        let redirect = PingReport {
+            status: 301,
+            latency: Duration::from_millis(5),
+        };
+        assert!(ok.is_success());
+        assert!(!redirect.is_success());
+    }
+}
@@ -0,0 +1,184 @@
+//! `.ralphignore`: workspace paths the agent must not modify.
+//!
+//! One gitignore-style pattern per line, relative to the workdir. `#` starts a
+//! comment, a leading `/` or an inner `/` anchors the pattern to the workdir,
+//! a trailing `/` matches directories only, and `*`, `?`, `**` glob as in git.
+//! Negation (`!`) is not supported.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::Path;
+
+pub const IGNORE_FILE: &str = ".ralphignore";
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    /// The line as written, for the prompt.
+    source: String,
+    regex: Regex,
+    /// Git pathspecs excluding the same paths from staging and diffs.
+    pathspecs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RalphIgnore {
+    patterns: Vec<Pattern>,
+}
+
+impl RalphIgnore {
+    /// Load `<workdir>/.ralphignore`; a missing file means nothing is ignored.
+    pub fn load(workdir: &Path) -> Result<Self> {
+        let path = workdir.join(IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('!') {
+                eprintln!(
+                    "⚠️   {IGNORE_FILE}: negated pattern '{line}' is not supported; skipping"
+                );
+                continue;
+            }
+            patterns.push(Pattern::new(line)?);
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the workdir-relative `path` is protected. `.ralphignore` itself
+    /// always is, so the agent cannot lift its own restrictions.
+    pub fn matches(&self, path: &str) -> bool {
+        !self.is_empty()
+            && (path == IGNORE_FILE || self.patterns.iter().any(|p| p.regex.is_match(path)))
+    }
+
+    /// The patterns as written, for the prompt.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|p| p.source.as_str())
+    }
+
+    /// `:(exclude)` pathspecs that keep protected paths out of `git add` and diffs.
+    pub fn exclude_pathspecs(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .flat_map(|p| p.pathspecs.iter().cloned())
+            .collect()
+    }
+}
+
+impl Pattern {
+    fn new(line: &str) -> Result<Self> {
+        let dir_only = line.ends_with('/');
+        let body = line.trim_end_matches('/');
+        let anchored = body.starts_with('/') || body.contains('/');
+        let body = body.trim_start_matches('/');
+        anyhow::ensure!(!body.is_empty(), "pattern '{line}' matches nothing");
+
+        let glob = glob_to_regex(body);
+        let prefix = if anchored { "" } else { "(?:.*/)?" };
+        // A matched directory covers everything beneath it.
+        let suffix = if dir_only { "/.*" } else { "(?:/.*)?" };
+        let regex = Regex::new(&format!("^{prefix}{glob}{suffix}$"))
+            .with_context(|| format!("pattern '{line}'"))?;
+
+        let base = if anchored {
+            body.to_string()
+        } else {
+            format!("**/{body}")
+        };
+        let mut pathspecs = vec![format!(":(exclude,glob){base}/**")];
+        if !dir_only {
+            pathspecs.push(format!(":(exclude,glob){base}"));
+        }
+
+        Ok(Self {
+            source: line.to_string(),
+            regex,
+            pathspecs,
+        })
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    out.push_str("(?:.*/)?");
+                } else {
+                    out.push_str(".*");
+                }
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_follow_gitignore_anchoring_rules() {
+        let ignore = RalphIgnore::parse(
+            "# generated code\n\
+             *.pb.go\n\
+             /migrations/\n\
+             vendor\n\
+             docs/**/*.pdf\n",
+        )
+        .expect("parse");
+
+        assert!(ignore.matches("api/user.pb.go"));
+        assert!(ignore.matches("migrations/0001.sql"));
+        assert!(!ignore.matches("db/migrations/0001.sql"));
+        assert!(!ignore.matches("migrations"));
+        assert!(ignore.matches("vendor/lib/a.go"));
+        assert!(ignore.matches("tools/vendor"));
+        assert!(ignore.matches("docs/spec.pdf"));
+        assert!(ignore.matches("docs/v2/spec.pdf"));
+        assert!(!ignore.matches("docs/spec.md"));
+        assert!(!ignore.matches("vendored.txt"));
+        assert!(ignore.matches(IGNORE_FILE));
+
+        assert_eq!(
+            ignore.patterns().collect::<Vec<_>>(),
+            vec!["*.pb.go", "/migrations/", "vendor", "docs/**/*.pdf"]
+        );
+        assert!(!RalphIgnore::default().matches(IGNORE_FILE));
+    }
+
+    #[test]
+    fn pathspecs_mirror_the_patterns() {
+        let ignore = RalphIgnore::parse("vendor\n/migrations/\n!keep.txt\n").expect("parse");
+        assert_eq!(
+            ignore.exclude_pathspecs(),
+            vec![
+                ":(exclude,glob)**/vendor/**",
+                ":(exclude,glob)**/vendor",
+                ":(exclude,glob)migrations/**",
+            ]
+        );
+    }
+}
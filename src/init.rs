@@ -0,0 +1,236 @@
+//! `ralph init` — start a project: a PRD template (or a draft from a branch
+//! with `--from-diff`), and with `--full` the rest of Ralph's files.
+//!
+//! Running it again is safe: files that already exist are kept unless
+//! `--force` is given, and the `.gitignore` entries are added only once.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::InitArgs;
+use crate::draft;
+
+const PRD_TEMPLATE: &str = r#"# Overview
+
+TODO: Describe the problem, target users, and desired outcomes.
+
+# Requirements
+
+TODO: List functional and non-functional requirements.
+
+# Acceptance Criteria
+
+TODO: Define testable completion criteria.
+"#;
+
+const CONFIG_TEMPLATE: &str = r#"# Settings for `ralph run` and `ralph watch` in this directory.
+# Command-line flags override them; see "Configuration" in Ralph's README.
+
+[defaults]
+agent = "codex"
+max_iterations = 20
+# timeout = 600          # seconds per iteration (default depends on the agent)
+# stall_timeout = 120    # seconds without output before the agent is killed
+
+# [completion]
+# verify_cmd = "cargo test"
+
+# [hooks]
+# url = "https://example.com/ralph-webhook"
+# token = "secret"
+"#;
+
+const IGNORE_TEMPLATE: &str = r#"# Paths the agent must not create, modify, or delete (gitignore-style
+# patterns). Changes to them are reverted and never committed.
+# .env
+# migrations/
+# vendor/
+"#;
+
+/// Marks Ralph's block in `.gitignore`, so it is added once.
+const GITIGNORE_MARKER: &str = "# ralph: per-run files (tasks.json and progress.md stay tracked)";
+
+const GITIGNORE_ENTRIES: &[&str] = &[
+    ".ralph*/logs/",
+    ".ralph*/lock",
+    ".ralph*/current*.json",
+    ".ralph*/session.json",
+    ".ralph*/status.json",
+    ".ralph*/snapshots/",
+    ".ralph*/failures/",
+    ".ralph-archive/",
+];
+
+/// Script that starts the project's `ralph watch` session.
+const WATCH_SCRIPT: &str = "ralph-watch.sh";
+
+pub async fn init_project(args: InitArgs) -> Result<()> {
+    let workdir = std::env::current_dir().context("Cannot resolve current directory")?;
+    let prd_path = workdir.join(args.output.as_deref().unwrap_or(Path::new("prd.md")));
+    if let Some(parent) = prd_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create {}", parent.display()))?;
+    }
+
+    match args.from_diff.as_deref() {
+        // Drafting costs an agent call, so an existing PRD stops it up front.
+        Some(_) if prd_path.exists() && !args.force => anyhow::bail!(
+            "{} already exists. Pass --force to replace it with a draft, or --output to write the draft elsewhere.",
+            prd_path.display()
+        ),
+        Some(range) => {
+            draft::draft_prd_from_diff(
+                &workdir,
+                &prd_path,
+                range,
+                &args.agent,
+                args.model.as_deref(),
+                args.draft_timeout,
+            )
+            .await?;
+            println!("Created {}", prd_path.display());
+        }
+        None => scaffold(&prd_path, PRD_TEMPLATE, args.force)?,
+    }
+
+    if args.full {
+        scaffold(&workdir.join("ralph.toml"), CONFIG_TEMPLATE, args.force)?;
+        scaffold(&workdir.join(".ralphignore"), IGNORE_TEMPLATE, args.force)?;
+        let script = workdir.join(WATCH_SCRIPT);
+        scaffold(&script, &watch_script(&workdir, &prd_path), args.force)?;
+        make_executable(&script)?;
+        add_gitignore_entries(&workdir.join(".gitignore"))?;
+    }
+    Ok(())
+}
+
+/// Write `content` to `path` unless it exists and `force` is off.
+fn scaffold(path: &Path, content: &str, force: bool) -> Result<()> {
+    let existed = path.exists();
+    if existed && !force {
+        println!(
+            "Kept {} (already exists; --force replaces it)",
+            path.display()
+        );
+        return Ok(());
+    }
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    let verb = if existed { "Replaced" } else { "Created" };
+    println!("{verb} {}", path.display());
+    Ok(())
+}
+
+/// A `ralph watch` command for the project's PRD, to add more PRDs to.
+fn watch_script(workdir: &Path, prd_path: &Path) -> String {
+    let prd: PathBuf = prd_path
+        .strip_prefix(workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| prd_path.to_path_buf());
+    format!(
+        "#!/bin/sh\n\
+         # One `ralph watch` loop per PRD. Split large work into a PRD per area\n\
+         # (auth.md, api.md, …) and list them here; arguments are passed through.\n\
+         exec ralph watch '{}' --parallel 2 \"$@\"\n",
+        prd.display().to_string().replace('\'', r"'\''")
+    )
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    std::fs::set_permissions(path, permissions)
+        .with_context(|| format!("Cannot make {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Append Ralph's per-run files to `.gitignore`, creating it if needed.
+fn add_gitignore_entries(path: &Path) -> Result<()> {
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    if existing.lines().any(|line| line.trim() == GITIGNORE_MARKER) {
+        println!(
+            "Kept {} (Ralph's entries are already there)",
+            path.display()
+        );
+        return Ok(());
+    }
+    let mut content = existing.clone();
+    if !content.is_empty() {
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push('\n');
+    }
+    content.push_str(GITIGNORE_MARKER);
+    content.push('\n');
+    for entry in GITIGNORE_ENTRIES {
+        content.push_str(entry);
+        content.push('\n');
+    }
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    let verb = if existing.is_empty() {
+        "Created"
+    } else {
+        "Updated"
+    };
+    println!("{verb} {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RalphConfig;
+    use tempfile::tempdir;
+
+    #[test]
+    fn prd_template_has_expected_sections() {
+        let dir = tempdir().expect("create tempdir");
+        let path = dir.path().join("prd.md");
+
+        scaffold(&path, PRD_TEMPLATE, false).expect("create template");
+
+        let content = std::fs::read_to_string(&path).expect("read generated template");
+        assert!(content.contains("# Overview"));
+        assert!(content.contains("# Requirements"));
+        assert!(content.contains("# Acceptance Criteria"));
+        assert!(content.contains("TODO:"));
+    }
+
+    #[test]
+    fn scaffolding_keeps_existing_files_unless_forced() {
+        let dir = tempdir().expect("create tempdir");
+        let path = dir.path().join("prd.md");
+        std::fs::write(&path, "# Mine\n").expect("write prd");
+
+        scaffold(&path, PRD_TEMPLATE, false).expect("scaffold");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Mine\n");
+        scaffold(&path, PRD_TEMPLATE, true).expect("scaffold");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), PRD_TEMPLATE);
+
+        let config: RalphConfig = toml::from_str(CONFIG_TEMPLATE).expect("template parses");
+        assert_eq!(config.defaults.unwrap().max_iterations, Some(20));
+    }
+
+    #[test]
+    fn gitignore_entries_are_added_once() {
+        let dir = tempdir().expect("create tempdir");
+        let path = dir.path().join(".gitignore");
+        std::fs::write(&path, "target/").expect("write .gitignore");
+
+        add_gitignore_entries(&path).expect("add entries");
+        add_gitignore_entries(&path).expect("add entries again");
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("target/\n\n# ralph:"), "{content}");
+        assert_eq!(content.matches(GITIGNORE_MARKER).count(), 1);
+        assert!(content.contains("\n.ralph*/logs/\n"));
+
+        let script = watch_script(dir.path(), &dir.path().join("docs/prd.md"));
+        assert!(script.contains("exec ralph watch 'docs/prd.md' --parallel 2 \"$@\""));
+    }
+}
@@ -1,20 +1,40 @@
 mod agents;
+mod approve;
 mod cli;
 mod config;
+mod doctor;
+mod draft;
+mod error;
+mod export;
 mod git;
+mod hook_receiver;
 mod hooks;
+mod ignore;
+mod init;
 mod notify;
 mod logs;
+mod open;
 mod orchestrator;
+mod output;
 mod parser;
+mod rate_limit;
+mod remote;
+mod schedule;
+mod secrets;
+mod shell;
 mod state;
+mod status_server;
 mod stop;
+mod systemd;
 mod tui;
 mod templates;
+mod timefmt;
+mod verify;
 mod watch;
 
-use std::path::{Path, PathBuf};
-use std::process::{Command as StdCommand, Stdio};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
 mod watcher;
 
 use anyhow::{Context, Result};
@@ -22,27 +42,41 @@ use chrono::Utc;
 use clap::parser::ValueSource;
 use clap::{CommandFactory, Parser};
 use cli::{Cli, Commands};
-use tokio::process::Command;
-use tokio::time::{timeout, Duration};
+use error::RalphError;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let argv: Vec<std::ffi::OsString> = std::env::args_os().collect();
     let cli = Cli::parse_from(argv.clone());
     let matches = Cli::command().get_matches_from(argv);
-    let config = config::load_config()?;
+    let json = cli.json;
+
+    match run_cli(cli, &matches).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error::report(&e, json),
+    }
+}
+
+async fn run_cli(cli: Cli, matches: &clap::ArgMatches) -> Result<()> {
+    // Keyring secrets (`*_ref`) are read where they're used, so `ralph
+    // secret` and the commands that need none never touch the keyring.
+    let config = config::load_config().map_err(RalphError::Config)?;
 
     match cli.command {
         Commands::Init(args) => {
-            init_prd(args).await?;
+            init::init_project(args).await?;
         }
         Commands::Doctor(args) => {
-            run_doctor(args).await?;
+            doctor::run(args).await?;
         }
         Commands::Run(mut args) => {
+            if args.prd.is_none() && args.template.is_none() {
+                args.prd = args.tasks.clone();
+            }
             resolve_prd_or_template(&mut args.prd, &args.template)?;
             if let Some(run_matches) = matches.subcommand_matches("run") {
-                apply_run_config(&mut args, config.as_ref(), run_matches);
+                apply_run_config(&mut args, config.as_ref(), run_matches)
+                    .map_err(RalphError::Config)?;
             }
             orchestrator::run(args).await?;
         }
@@ -51,14 +85,17 @@ async fn main() -> Result<()> {
             if let Some(parse_matches) = matches.subcommand_matches("parse") {
                 apply_parse_config(&mut args, config.as_ref(), parse_matches);
             }
-            parser::parse_and_print(args).await?;
+            parser::parse_and_print(args)
+                .await
+                .map_err(RalphError::Parse)?;
         }
         Commands::Status(args) => {
-            show_status(args).await?;
+            show_status(args, config.as_ref()).await?;
         }
         Commands::Watch(mut args) => {
             if let Some(watch_matches) = matches.subcommand_matches("watch") {
-                apply_watch_config(&mut args, config.as_ref(), watch_matches);
+                apply_watch_config(&mut args, config.as_ref(), watch_matches)
+                    .map_err(RalphError::Config)?;
             }
             watch::watch(args).await?;
         }
@@ -68,14 +105,75 @@ async fn main() -> Result<()> {
         Commands::Stop(args) => {
             stop::stop_loops(args).await?;
         }
+        Commands::Approve(args) => {
+            approve::approve_tasks(args)?;
+        }
+        Commands::Open(args) => {
+            open::open_task(args).await?;
+        }
+        Commands::Verify(mut args) => {
+            if args.verify_cmd.is_none() {
+                args.verify_cmd = config
+                    .as_ref()
+                    .and_then(|c| c.completion.as_ref())
+                    .and_then(|c| c.verify_cmd.clone());
+            }
+            verify::verify_tasks(args).await?;
+        }
+        Commands::Export(args) => {
+            export::export_tasks(args)?;
+        }
         Commands::Template(args) => {
             match args.command {
                 cli::TemplateCommands::Save { name, prd } => templates::save(&name, &prd)?,
-                cli::TemplateCommands::List { verbose } => templates::list(verbose)?,
+                cli::TemplateCommands::List { namespace, verbose } => {
+                    templates::list(namespace.as_deref(), verbose)?
+                }
                 cli::TemplateCommands::Show { name } => templates::show(&name)?,
                 cli::TemplateCommands::Remove { name } => templates::remove(&name)?,
+                cli::TemplateCommands::Export { out, names } => templates::export(&out, &names)?,
+                cli::TemplateCommands::Import {
+                    source,
+                    namespace,
+                    force,
+                } => templates::import(
+                    source.as_deref(),
+                    namespace.as_deref(),
+                    force,
+                    config.as_ref().and_then(|c| c.templates.as_ref()),
+                )?,
             }
         }
+        Commands::Schedule(args) => {
+            schedule::command(args)?;
+        }
+        Commands::Daemon(args) => {
+            schedule::daemon(args.once).await?;
+        }
+        Commands::Remote(args) => match args.command {
+            cli::RemoteCommands::Run(args) => remote::run(args)?,
+        },
+        Commands::Secret(args) => secrets::command(args)?,
+        Commands::Hooks(args) => match args.command {
+            cli::HooksCommands::Test { url, token } => {
+                let hook = resolve_hook_config(url, token, config.as_ref())?;
+                hooks::test_hook(&hook).await?;
+            }
+            cli::HooksCommands::Serve {
+                port,
+                token,
+                out,
+                desktop,
+            } => {
+                let token = match (token, config.as_ref().and_then(|c| c.hooks.as_ref())) {
+                    (Some(token), _) => Some(token),
+                    (None, Some(hooks)) => hooks.resolve_token()?,
+                    (None, None) => None,
+                };
+                let receiver = hook_receiver::Receiver::new(token, out, desktop);
+                hook_receiver::serve(port, receiver).await?;
+            }
+        },
     }
 
     Ok(())
@@ -96,13 +194,31 @@ fn resolve_prd_or_template(
     Ok(())
 }
 
+/// Build a hook config from CLI flags, falling back to `[hooks]` in ralph.toml.
+fn resolve_hook_config(
+    url: Option<String>,
+    token: Option<String>,
+    config: Option<&config::RalphConfig>,
+) -> Result<hooks::HookConfig> {
+    let hooks_config = config.and_then(|c| c.hooks.as_ref());
+    let url = url
+        .or_else(|| hooks_config.and_then(|h| h.url.clone()))
+        .context("No webhook URL: pass --url or set [hooks] url in ralph.toml")?;
+    let token = match (token, hooks_config) {
+        (Some(token), _) => Some(token),
+        (None, Some(hooks)) => hooks.resolve_token()?,
+        (None, None) => None,
+    };
+    Ok(hooks::HookConfig::new(url, token))
+}
+
 fn apply_run_config(
     args: &mut cli::RunArgs,
     config: Option<&config::RalphConfig>,
     matches: &clap::ArgMatches,
-) {
+) -> Result<()> {
     let Some(config) = config else {
-        return;
+        return Ok(());
     };
 
     if let Some(defaults) = &config.defaults {
@@ -116,14 +232,34 @@ fn apply_run_config(
                 args.max_iterations = value;
             }
         }
+        if !was_provided_by_cli(matches, "auto_extend") {
+            if let Some(factor) = &defaults.auto_extend {
+                args.auto_extend = Some(factor.clone());
+            }
+        }
+        if !was_provided_by_cli(matches, "max_prompt_tokens") {
+            if let Some(value) = defaults.max_prompt_tokens {
+                args.max_prompt_tokens = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_files_changed") {
+            if let Some(value) = defaults.max_files_changed {
+                args.max_files_changed = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_lines_changed") {
+            if let Some(value) = defaults.max_lines_changed {
+                args.max_lines_changed = Some(value);
+            }
+        }
         if !was_provided_by_cli(matches, "timeout") {
             if let Some(value) = defaults.timeout {
-                args.timeout = value;
+                args.timeout = Some(value);
             }
         }
         if !was_provided_by_cli(matches, "stall_timeout") {
             if let Some(value) = defaults.stall_timeout {
-                args.stall_timeout = value;
+                args.stall_timeout = Some(value);
             }
         }
         if !was_provided_by_cli(matches, "max_failures") {
@@ -131,6 +267,26 @@ fn apply_run_config(
                 args.max_failures = value;
             }
         }
+        if !was_provided_by_cli(matches, "max_attempts") {
+            if let Some(value) = defaults.max_attempts {
+                args.max_attempts = value;
+            }
+        }
+        if !was_provided_by_cli(matches, "max_tokens") {
+            if let Some(value) = defaults.max_tokens {
+                args.max_tokens = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_cost_usd") {
+            if let Some(value) = defaults.max_cost_usd {
+                args.max_cost_usd = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "timestamps") {
+            if let Some(zone) = &defaults.timestamps {
+                args.timestamps = zone.clone();
+            }
+        }
     }
 
     if let Some(hooks) = &config.hooks {
@@ -139,12 +295,134 @@ fn apply_run_config(
                 args.hook_url = Some(url.clone());
             }
         }
-        if !was_provided_by_cli(matches, "hook_token") {
-            if let Some(token) = &hooks.token {
-                args.hook_token = Some(token.clone());
+        // The keyring is only asked when a webhook will actually be sent.
+        if !was_provided_by_cli(matches, "hook_token") && args.hook_url.is_some() {
+            if let Some(token) = hooks.resolve_token()? {
+                args.hook_token = Some(token);
+            }
+        }
+        if !was_provided_by_cli(matches, "hook_log_lines") {
+            if let Some(lines) = hooks.log_lines {
+                args.hook_log_lines = Some(lines);
+            }
+        }
+    }
+
+    if let Some(completion) = &config.completion {
+        if !was_provided_by_cli(matches, "completion") {
+            if let Some(detectors) = &completion.detectors {
+                args.completion = detectors.clone();
             }
         }
+        if !was_provided_by_cli(matches, "min_confidence") {
+            if let Some(value) = completion.min_confidence {
+                if !(0.0..=1.0).contains(&value) {
+                    anyhow::bail!("[completion] min_confidence must be between 0.0 and 1.0, got {value}");
+                }
+                args.min_confidence = value;
+            }
+        }
+        if !was_provided_by_cli(matches, "verify_cmd") {
+            if let Some(cmd) = &completion.verify_cmd {
+                args.verify_cmd = Some(cmd.clone());
+            }
+        }
+        if !was_provided_by_cli(matches, "completion_token") {
+            if let Some(token) = &completion.token {
+                args.completion_token = token.clone();
+            }
+        }
+    }
+
+    if let Some(agents) = &config.agents {
+        args.agent_slots = Some(crate::agents::AgentSlots::new(&agent_limits(agents)));
+        args.agent_commands = agent_commands(agents);
+        args.agent_mcp = agent_mcp(agents);
+    }
+    if !was_provided_by_cli(matches, "api_protocol") {
+        if let Some(protocol) = api_protocol(config) {
+            args.api_protocol = protocol;
+        }
     }
+    if !was_provided_by_cli(matches, "api_key") {
+        args.api_key_ref = api_key_ref(config);
+    }
+    if let (Some(notify), Some(_)) = (&config.notify, &args.notify) {
+        args.notify_token = notify.resolve_token()?;
+    }
+
+    if let Some(output) = &config.output {
+        args.output = output.clone();
+    }
+    if let Some(report) = &config.report {
+        args.report = report.clone();
+    }
+    if let Some(format) = &config.format {
+        args.formatters = format.clone();
+    }
+    if let Some(chaos) = &config.chaos {
+        if !was_provided_by_cli(matches, "chaos") {
+            args.chaos = chaos.rate;
+        }
+        args.chaos_config = chaos.clone();
+    }
+    if let Some(scheduler) = &config.scheduler {
+        args.scheduler = scheduler.clone();
+    }
+    if let Some(fallback) = &config.fallback {
+        args.fallback = fallback.clone();
+    }
+    if let Some(context) = &config.context {
+        args.repo_context = context.clone();
+    }
+    if let Some(format) = config.state.as_ref().and_then(|s| s.format.as_ref()) {
+        if !was_provided_by_cli(matches, "tasks_format") {
+            args.tasks_format = Some(format.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Session limits set by `[agents.<name>] max_concurrent`.
+fn agent_limits(agents: &HashMap<String, config::AgentConfig>) -> HashMap<String, usize> {
+    agents
+        .iter()
+        .filter_map(|(name, agent)| Some((name.clone(), agent.max_concurrent?)))
+        .collect()
+}
+
+/// Custom agents defined by `[agents.<name>] command`.
+fn agent_commands(agents: &HashMap<String, config::AgentConfig>) -> HashMap<String, String> {
+    agents
+        .iter()
+        .filter_map(|(name, agent)| Some((name.clone(), agent.command.clone()?)))
+        .collect()
+}
+
+/// MCP servers defined by `[agents.<name>.mcp]`.
+fn agent_mcp(
+    agents: &HashMap<String, config::AgentConfig>,
+) -> HashMap<String, Vec<config::McpServer>> {
+    agents
+        .iter()
+        .filter_map(|(name, agent)| Some((name.clone(), agent.mcp.as_ref()?.servers.clone())))
+        .collect()
+}
+
+/// `[agents.api] api_key_ref`, read from the keyring once the `api` agent is
+/// created.
+fn api_key_ref(config: &config::RalphConfig) -> Option<String> {
+    config
+        .agents
+        .as_ref()?
+        .get("api")?
+        .api_key_ref
+        .clone()
+}
+
+/// `[agents.api] protocol`.
+fn api_protocol(config: &config::RalphConfig) -> Option<String> {
+    config.agents.as_ref()?.get("api")?.protocol.clone()
 }
 
 fn apply_parse_config(
@@ -170,11 +448,38 @@ fn apply_watch_config(
     args: &mut cli::WatchArgs,
     config: Option<&config::RalphConfig>,
     matches: &clap::ArgMatches,
-) {
+) -> Result<()> {
     let Some(config) = config else {
-        return;
+        return Ok(());
     };
 
+    if let Some(chatter) = config.tui.as_ref().and_then(|t| t.chatter.as_ref()) {
+        args.chatter_patterns = chatter.clone();
+    }
+    if let Some(agents) = &config.agents {
+        args.agent_limits = agent_limits(agents);
+        args.agent_commands = agent_commands(agents);
+        args.agent_mcp = agent_mcp(agents);
+    }
+    if let Some(output) = &config.output {
+        args.output = output.clone();
+    }
+    if let Some(fallback) = &config.fallback {
+        args.fallback = fallback.clone();
+    }
+    if let Some(context) = &config.context {
+        args.repo_context = context.clone();
+    }
+    args.api_key_ref = api_key_ref(config);
+    if !was_provided_by_cli(matches, "api_protocol") {
+        if let Some(protocol) = api_protocol(config) {
+            args.api_protocol = protocol;
+        }
+    }
+    if let (Some(notify), Some(_)) = (&config.notify, &args.notify) {
+        args.notify_token = notify.resolve_token()?;
+    }
+
     if let Some(defaults) = &config.defaults {
         if !was_provided_by_cli(matches, "agent") {
             if let Some(agent) = &defaults.agent {
@@ -186,14 +491,34 @@ fn apply_watch_config(
                 args.max_iterations = value;
             }
         }
+        if !was_provided_by_cli(matches, "auto_extend") {
+            if let Some(factor) = &defaults.auto_extend {
+                args.auto_extend = Some(factor.clone());
+            }
+        }
+        if !was_provided_by_cli(matches, "max_prompt_tokens") {
+            if let Some(value) = defaults.max_prompt_tokens {
+                args.max_prompt_tokens = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_files_changed") {
+            if let Some(value) = defaults.max_files_changed {
+                args.max_files_changed = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_lines_changed") {
+            if let Some(value) = defaults.max_lines_changed {
+                args.max_lines_changed = Some(value);
+            }
+        }
         if !was_provided_by_cli(matches, "timeout") {
             if let Some(value) = defaults.timeout {
-                args.timeout = value;
+                args.timeout = Some(value);
             }
         }
         if !was_provided_by_cli(matches, "stall_timeout") {
             if let Some(value) = defaults.stall_timeout {
-                args.stall_timeout = value;
+                args.stall_timeout = Some(value);
             }
         }
         if !was_provided_by_cli(matches, "max_failures") {
@@ -201,6 +526,26 @@ fn apply_watch_config(
                 args.max_failures = value;
             }
         }
+        if !was_provided_by_cli(matches, "max_attempts") {
+            if let Some(value) = defaults.max_attempts {
+                args.max_attempts = value;
+            }
+        }
+        if !was_provided_by_cli(matches, "max_tokens") {
+            if let Some(value) = defaults.max_tokens {
+                args.max_tokens = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "max_cost_usd") {
+            if let Some(value) = defaults.max_cost_usd {
+                args.max_cost_usd = Some(value);
+            }
+        }
+        if !was_provided_by_cli(matches, "timestamps") {
+            if let Some(zone) = &defaults.timestamps {
+                args.timestamps = zone.clone();
+            }
+        }
     }
 
     if let Some(hooks) = &config.hooks {
@@ -209,385 +554,62 @@ fn apply_watch_config(
                 args.hook_url = Some(url.clone());
             }
         }
-        if !was_provided_by_cli(matches, "hook_token") {
-            if let Some(token) = &hooks.token {
-                args.hook_token = Some(token.clone());
+        // The keyring is only asked when a webhook will actually be sent.
+        if !was_provided_by_cli(matches, "hook_token") && args.hook_url.is_some() {
+            if let Some(token) = hooks.resolve_token()? {
+                args.hook_token = Some(token);
             }
         }
-    }
-}
-
-fn was_provided_by_cli(matches: &clap::ArgMatches, arg_id: &str) -> bool {
-    matches.value_source(arg_id) == Some(ValueSource::CommandLine)
-}
-
-#[derive(Debug)]
-struct DoctorRow {
-    check: String,
-    status: String,
-    details: String,
-}
-
-#[derive(Debug, PartialEq, Eq)]
-enum AgentAuthStatus {
-    Authenticated,
-    NotAuthenticated(String),
-    TimedOut,
-    ProbeFailed(String),
-}
-
-async fn init_prd(_args: cli::InitArgs) -> Result<()> {
-    let workdir = std::env::current_dir().context("Cannot resolve current directory")?;
-    create_prd_template(&workdir)?;
-    println!("Created {}", workdir.join("prd.md").display());
-    Ok(())
-}
-
-fn create_prd_template(workdir: &std::path::Path) -> Result<()> {
-    let prd_path = workdir.join("prd.md");
-    if prd_path.exists() {
-        anyhow::bail!(
-            "{} already exists. Aborting to avoid overwriting it.",
-            prd_path.display()
-        );
-    }
-
-    let template = r#"# Overview
-
-TODO: Describe the problem, target users, and desired outcomes.
-
-# Requirements
-
-TODO: List functional and non-functional requirements.
-
-# Acceptance Criteria
-
-TODO: Define testable completion criteria.
-"#;
-
-    std::fs::write(&prd_path, template)
-        .with_context(|| format!("Failed to write {}", prd_path.display()))?;
-
-    Ok(())
-}
-
-async fn run_doctor(args: cli::DoctorArgs) -> Result<()> {
-    let workdir = args
-        .workdir
-        .unwrap_or_else(|| PathBuf::from("."))
-        .canonicalize()
-        .context("Cannot resolve workdir — does it exist?")?;
-
-    let mut rows = Vec::new();
-
-    for agent in ["claude", "codex", "gemini", "opencode"] {
-        if !command_on_path(agent) {
-            rows.push(DoctorRow {
-                check: format!("agent:{agent}"),
-                status: "MISSING".to_string(),
-                details: "not found on PATH".to_string(),
-            });
-            continue;
+        if !was_provided_by_cli(matches, "hook_log_lines") {
+            if let Some(lines) = hooks.log_lines {
+                args.hook_log_lines = Some(lines);
+            }
         }
-
-        let (status, details) = match probe_agent_auth(agent).await {
-            AgentAuthStatus::Authenticated => ("OK", "installed + authenticated".to_string()),
-            AgentAuthStatus::NotAuthenticated(msg) => ("WARN", msg),
-            AgentAuthStatus::TimedOut => ("WARN", "probe timed out after 10s".to_string()),
-            AgentAuthStatus::ProbeFailed(msg) => ("WARN", msg),
-        };
-
-        rows.push(DoctorRow {
-            check: format!("agent:{agent}"),
-            status: status.to_string(),
-            details,
-        });
-    }
-
-    if !command_on_path("git") {
-        rows.push(DoctorRow {
-            check: "git".to_string(),
-            status: "FAIL".to_string(),
-            details: "git not found on PATH".to_string(),
-        });
-        rows.push(DoctorRow {
-            check: "git-repo".to_string(),
-            status: "N/A".to_string(),
-            details: "git is not installed".to_string(),
-        });
-    } else {
-        let git_version = detect_git_version().await;
-        rows.push(DoctorRow {
-            check: "git".to_string(),
-            status: "OK".to_string(),
-            details: git_version
-                .unwrap_or_else(|e| format!("installed (version probe failed: {e})")),
-        });
-
-        let (status, details) = match is_git_repo(&workdir).await {
-            Ok(true) => ("OK", format!("{} is a git repository", workdir.display())),
-            Ok(false) => (
-                "WARN",
-                format!("{} is not a git repository", workdir.display()),
-            ),
-            Err(e) => ("WARN", format!("failed to check repo status: {e}")),
-        };
-        rows.push(DoctorRow {
-            check: "git-repo".to_string(),
-            status: status.to_string(),
-            details,
-        });
-    }
-
-    let disk_row = match check_disk_space(&workdir).await {
-        Ok((total_kib, avail_kib, used_percent)) => DoctorRow {
-            check: "disk".to_string(),
-            status: if used_percent >= 95 { "WARN" } else { "OK" }.to_string(),
-            details: format!(
-                "{} free / {} total ({}% used)",
-                kib_to_human(avail_kib),
-                kib_to_human(total_kib),
-                used_percent
-            ),
-        },
-        Err(e) => DoctorRow {
-            check: "disk".to_string(),
-            status: "WARN".to_string(),
-            details: format!("failed to check disk space: {e}"),
-        },
-    };
-    rows.push(disk_row);
-
-    println!("Ralph doctor report for {}", workdir.display());
-    println!();
-    print_doctor_table(&rows);
-
-    Ok(())
-}
-
-fn command_on_path(bin: &str) -> bool {
-    StdCommand::new(bin)
-        .arg("--version")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-}
-
-async fn detect_git_version() -> Result<String> {
-    let output = Command::new("git")
-        .arg("--version")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .context("failed to execute `git --version`")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("{}", first_non_empty_line(&stderr));
-    }
-
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-}
-
-async fn is_git_repo(workdir: &Path) -> Result<bool> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(workdir)
-        .args(["rev-parse", "--is-inside-work-tree"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .context("failed to execute git repo probe")?;
-
-    if !output.status.success() {
-        return Ok(false);
-    }
-
-    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
-}
-
-async fn probe_agent_auth(agent: &str) -> AgentAuthStatus {
-    let mut cmd = build_auth_probe(agent);
-    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
-
-    let result = match timeout(Duration::from_secs(10), cmd.output()).await {
-        Ok(res) => res,
-        Err(_) => return AgentAuthStatus::TimedOut,
-    };
-
-    let output = match result {
-        Ok(output) => output,
-        Err(e) => return AgentAuthStatus::ProbeFailed(e.to_string()),
-    };
-
-    if output.status.success() {
-        return AgentAuthStatus::Authenticated;
-    }
-
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let combined = if stderr.trim().is_empty() {
-        stdout.as_ref()
-    } else {
-        stderr.as_ref()
-    };
-    let message = first_non_empty_line(combined);
-
-    if agent == "claude" && is_claude_api_key_error(combined) {
-        return AgentAuthStatus::NotAuthenticated(
-            "API key required for `claude --print` (set ANTHROPIC_API_KEY)".to_string(),
-        );
     }
 
-    AgentAuthStatus::NotAuthenticated(if message.is_empty() {
-        "probe failed with non-zero exit".to_string()
-    } else {
-        message
-    })
-}
-
-fn build_auth_probe(agent: &str) -> Command {
-    match agent {
-        "claude" => {
-            let mut c = Command::new("claude");
-            c.arg("--dangerously-skip-permissions")
-                .arg("--print")
-                .arg("-p")
-                .arg("hi");
-            c
-        }
-        "codex" => {
-            let mut c = Command::new("codex");
-            c.arg("exec").arg("--full-auto").arg("hi");
-            c
-        }
-        "gemini" => {
-            let mut c = Command::new("gemini");
-            c.arg("-p").arg("hi").arg("--yolo");
-            c
-        }
-        "opencode" => {
-            let mut c = Command::new("opencode");
-            c.arg("run").arg("hi");
-            c
-        }
-        _ => Command::new(agent),
-    }
-}
-
-fn is_claude_api_key_error(text: &str) -> bool {
-    let lower = text.to_ascii_lowercase();
-    lower.contains("invalid api key") || lower.contains("api key")
-}
-
-async fn check_disk_space(workdir: &Path) -> Result<(u64, u64, u8)> {
-    let output = Command::new("df")
-        .arg("-k")
-        .arg(workdir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .context("failed to execute `df -k`")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("{}", first_non_empty_line(&stderr));
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_df_k_output(&stdout).ok_or_else(|| anyhow::anyhow!("unexpected `df -k` output format"))
-}
-
-fn parse_df_k_output(output: &str) -> Option<(u64, u64, u8)> {
-    let line = output
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-        .nth(1)?;
-
-    let fields: Vec<&str> = line.split_whitespace().collect();
-    if fields.len() < 6 {
-        return None;
-    }
-
-    let total_kib = fields.get(fields.len() - 5)?.parse::<u64>().ok()?;
-    let avail_kib = fields.get(fields.len() - 3)?.parse::<u64>().ok()?;
-    let used_percent = fields
-        .get(fields.len() - 2)?
-        .trim_end_matches('%')
-        .parse::<u8>()
-        .ok()?;
-
-    Some((total_kib, avail_kib, used_percent))
-}
-
-fn kib_to_human(kib: u64) -> String {
-    let gib = kib as f64 / (1024.0 * 1024.0);
-    if gib >= 1.0 {
-        return format!("{gib:.1} GiB");
+    if let Some(completion) = &config.completion {
+        if !was_provided_by_cli(matches, "completion") {
+            if let Some(detectors) = &completion.detectors {
+                args.completion = detectors.clone();
+            }
+        }
+        if !was_provided_by_cli(matches, "min_confidence") {
+            if let Some(value) = completion.min_confidence {
+                if !(0.0..=1.0).contains(&value) {
+                    anyhow::bail!("[completion] min_confidence must be between 0.0 and 1.0, got {value}");
+                }
+                args.min_confidence = value;
+            }
+        }
+        if !was_provided_by_cli(matches, "verify_cmd") {
+            if let Some(cmd) = &completion.verify_cmd {
+                args.verify_cmd = Some(cmd.clone());
+            }
+        }
+        if !was_provided_by_cli(matches, "completion_token") {
+            if let Some(token) = &completion.token {
+                args.completion_token = token.clone();
+            }
+        }
     }
-
-    let mib = kib as f64 / 1024.0;
-    if mib >= 1.0 {
-        return format!("{mib:.1} MiB");
+    if let Some(format) = config.state.as_ref().and_then(|s| s.format.as_ref()) {
+        if !was_provided_by_cli(matches, "tasks_format") {
+            args.tasks_format = Some(format.clone());
+        }
     }
-
-    format!("{kib} KiB")
-}
-
-fn first_non_empty_line(text: &str) -> String {
-    text.lines()
-        .map(str::trim)
-        .find(|line| !line.is_empty())
-        .unwrap_or_default()
-        .to_string()
+    Ok(())
 }
 
-fn print_doctor_table(rows: &[DoctorRow]) {
-    let mut check_w = "CHECK".len();
-    let mut status_w = "STATUS".len();
-    for row in rows {
-        check_w = check_w.max(row.check.len());
-        status_w = status_w.max(row.status.len());
-    }
-
-    println!(
-        "{:<check_w$}  {:<status_w$}  DETAILS",
-        "CHECK",
-        "STATUS",
-        check_w = check_w,
-        status_w = status_w
-    );
-    println!(
-        "{}  {}  {}",
-        "-".repeat(check_w),
-        "-".repeat(status_w),
-        "-".repeat(48)
-    );
-
-    for row in rows {
-        println!(
-            "{:<check_w$}  {:<status_w$}  {}",
-            row.check,
-            row.status,
-            row.details,
-            check_w = check_w,
-            status_w = status_w
-        );
-    }
+fn was_provided_by_cli(matches: &clap::ArgMatches, arg_id: &str) -> bool {
+    matches.value_source(arg_id) == Some(ValueSource::CommandLine)
 }
 
-async fn show_status(args: cli::StatusArgs) -> Result<()> {
+async fn show_status(args: cli::StatusArgs, config: Option<&config::RalphConfig>) -> Result<()> {
     use std::path::PathBuf;
 
     // If --workdir is given, scan that directory only (old behavior).
     // Otherwise, scan the global registry for loops running anywhere.
-    let (locks, scope_label) = if let Some(ref wd) = args.workdir {
+    let (mut locks, scope_label) = if let Some(ref wd) = args.workdir {
         let workdir = wd.canonicalize().context("Cannot resolve workdir")?;
         let local_locks = find_active_locks(&workdir).await?;
         (local_locks, format!("in {}", workdir.display()))
@@ -606,6 +628,29 @@ async fn show_status(args: cli::StatusArgs) -> Result<()> {
         (locks, "system-wide".to_string())
     };
 
+    // --all: loops started before the registry existed, or whose registry
+    // link was lost, are still found under the configured project roots.
+    if args.all {
+        let mut roots = args.root.clone();
+        roots.extend(
+            config
+                .and_then(|c| c.status.as_ref())
+                .and_then(|s| s.roots.clone())
+                .unwrap_or_default(),
+        );
+        let home = dirs::home_dir();
+        for root in roots {
+            let root = expand_home(&root, home.as_deref());
+            for (path, lock) in scan_project_root(&root).await {
+                if !locks.iter().any(|(p, _)| p == &path) {
+                    locks.push((path, lock));
+                }
+            }
+        }
+        // Group loops by repository
+        locks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
     if locks.is_empty() {
         println!("💤  No ralph loops running {scope_label}");
         return Ok(());
@@ -623,7 +668,7 @@ async fn show_status(args: cli::StatusArgs) -> Result<()> {
             .to_std()
             .unwrap_or_default();
 
-        let elapsed_str = format_duration(elapsed);
+        let elapsed_str = timefmt::verbose_duration(elapsed.as_secs());
         let name = path
             .parent()
             .and_then(|p| p.file_name())
@@ -646,13 +691,46 @@ async fn show_status(args: cli::StatusArgs) -> Result<()> {
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| "?".to_string());
 
+        // status.json follows the loop between lock updates; one left over
+        // from an earlier run is ignored.
+        let live = path
+            .parent()
+            .map(|dir| dir.join("status.json"))
+            .filter(|file| {
+                std::fs::metadata(file)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|t| chrono::DateTime::<Utc>::from(t) >= lock.started_at)
+            })
+            .and_then(|file| state::StatusSnapshot::load(&file));
+        let (task, progress) = match &live {
+            Some(live) if !live.current_task.is_empty() => (
+                live.current_task.clone(),
+                format!("{}/{} done", live.tasks_done, live.tasks_total),
+            ),
+            _ => (lock.current_task.clone(), lock.progress.clone()),
+        };
+
         println!("    {status_icon} [{}] PID {}", loop_name, lock.pid);
         println!("       Dir:      {}", project_dir);
         println!("       PRD:      {}", lock.prd_path);
-        println!("       Agent:    {}", lock.agent);
-        println!("       Task:     {}", lock.current_task);
-        println!("       Progress: {}", lock.progress);
+        println!("       Agent:    {}", live.as_ref().map_or(&lock.agent, |l| &l.agent));
+        if let Some(ref live) = live {
+            println!("       State:    {}", live.state);
+            println!("       Iter:     {}", live.iteration);
+        }
+        println!("       Task:     {}", task);
+        println!("       Progress: {}", progress);
         println!("       Time:     {}", elapsed_str);
+        if let Some(eta) = lock.eta_secs {
+            println!("       ETA:      ~{}", timefmt::verbose_duration(eta));
+        }
+        let usage = path
+            .parent()
+            .and_then(|dir| state::UsageLog::load(&dir.join("usage.json")).ok())
+            .filter(|u| !u.iterations.is_empty());
+        if let Some(usage) = usage {
+            println!("       Usage:    {}", usage.summary());
+        }
         if !alive {
             println!("       (process appears dead — stale lock)");
         }
@@ -683,22 +761,34 @@ fn is_pid_alive(pid: u32) -> bool {
     }
 }
 
-/// Format a duration as h:m:s
-fn format_duration(d: std::time::Duration) -> String {
-    let total_secs = d.as_secs();
-    let h = total_secs / 3600;
-    let m = (total_secs % 3600) / 60;
-    let s = total_secs % 60;
-
-    if h > 0 {
-        format!("{}h {}m {}s", h, m, s)
-    } else if m > 0 {
-        format!("{}m {}s", m, s)
-    } else {
-        format!("{}s", s)
+/// Expand a leading `~/` in a configured path.
+fn expand_home(path: &std::path::Path, home: Option<&std::path::Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
     }
 }
 
+/// Lock files in `root` itself and in each of its (non-hidden) subdirectories.
+/// Unreadable directories are skipped.
+async fn scan_project_root(root: &std::path::Path) -> Vec<(PathBuf, state::LockFile)> {
+    let Ok(root) = root.canonicalize() else {
+        return Vec::new();
+    };
+    let mut results = find_active_locks(&root).await.unwrap_or_default();
+    let Ok(mut read_dir) = tokio::fs::read_dir(&root).await else {
+        return results;
+    };
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() && !hidden {
+            results.extend(find_active_locks(&path).await.unwrap_or_default());
+        }
+    }
+    results
+}
+
 /// Find all lock files in .ralph/ and .ralph-*/ directories.
 async fn find_active_locks(workdir: &std::path::Path) -> Result<Vec<(PathBuf, state::LockFile)>> {
     let mut results = Vec::new();
@@ -731,6 +821,14 @@ async fn find_active_locks(workdir: &std::path::Path) -> Result<Vec<(PathBuf, st
     Ok(results)
 }
 
+/// Shared test lock for tests that mutate process-global state (PATH, env vars).
+/// Import from both `orchestrator::tests` and `parser::tests` to serialize them.
+#[cfg(test)]
+pub(crate) fn global_env_lock() -> &'static std::sync::Mutex<()> {
+    static LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
+    LOCK.get_or_init(|| std::sync::Mutex::new(()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -748,6 +846,8 @@ mod tests {
             started_at: Utc::now(),
             prd_path: "tests/PRD.md".to_string(),
             agent: "codex".to_string(),
+            eta_secs: None,
+            agent_pgids: Vec::new(),
         }
     }
 
@@ -781,46 +881,94 @@ mod tests {
         assert!(lock_paths.contains(&watch_b_state.lock_file));
     }
 
-    #[test]
-    fn create_prd_template_writes_expected_sections() {
-        let dir = tempdir().expect("create tempdir");
-
-        create_prd_template(dir.path()).expect("create template");
+    #[tokio::test]
+    async fn scan_project_root_finds_loops_in_root_and_child_projects() {
+        let root = tempdir().expect("create tempdir");
+        let top = state::StateManager::new(root.path()).expect("create root state");
+        let api = root.path().join("api");
+        let named = state::StateManager::new_named(&api, "watch-a").expect("create api state");
+        let hidden = root.path().join(".cache");
+        let ignored = state::StateManager::new(&hidden).expect("create hidden state");
+        let nested = root.path().join("web").join("app");
+        let too_deep = state::StateManager::new(&nested).expect("create nested state");
+
+        for (state, pid) in [
+            (&top, 2001),
+            (&named, 2002),
+            (&ignored, 2003),
+            (&too_deep, 2004),
+        ] {
+            std::fs::write(
+                &state.lock_file,
+                serde_json::to_string(&sample_lock(pid)).expect("serialise lock"),
+            )
+            .expect("write lock");
+        }
 
-        let content =
-            std::fs::read_to_string(dir.path().join("prd.md")).expect("read generated template");
-        assert!(content.contains("# Overview"));
-        assert!(content.contains("# Requirements"));
-        assert!(content.contains("# Acceptance Criteria"));
-        assert!(content.contains("TODO:"));
+        let mut pids: Vec<u32> = scan_project_root(root.path())
+            .await
+            .into_iter()
+            .map(|(_, lock)| lock.pid)
+            .collect();
+        pids.sort();
+        assert_eq!(pids, vec![2001, 2002]);
+        let missing = root.path().join("missing");
+        assert!(scan_project_root(&missing).await.is_empty());
     }
 
     #[test]
-    fn create_prd_template_fails_when_prd_exists() {
-        let dir = tempdir().expect("create tempdir");
-        let prd_path = dir.path().join("prd.md");
-        std::fs::write(&prd_path, "existing content").expect("seed existing prd");
-
-        let err = create_prd_template(dir.path()).expect_err("should reject existing prd");
-        let rendered = err.to_string();
-        assert!(rendered.contains("already exists"));
-        assert!(rendered.contains("prd.md"));
+    fn expand_home_only_rewrites_tilde_prefix() {
+        let home = std::path::Path::new("/home/dev");
+        assert_eq!(
+            expand_home(std::path::Path::new("~/code"), Some(home)),
+            PathBuf::from("/home/dev/code")
+        );
+        assert_eq!(
+            expand_home(std::path::Path::new("/srv/~x"), Some(home)),
+            PathBuf::from("/srv/~x")
+        );
     }
 
     #[test]
-    fn parse_df_k_output_parses_expected_columns() {
-        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\n/dev/disk3s1s1 488245288 110354576 361727632 24% /\n";
-        let parsed = parse_df_k_output(output).expect("parse should succeed");
+    fn resolve_hook_config_falls_back_to_config_file() {
+        let config = RalphConfig {
+            defaults: None,
+            hooks: Some(HooksConfig {
+                url: Some("https://config.example/hook".to_string()),
+                token: Some("config-token".to_string()),
+                token_ref: None,
+                log_lines: None,
+            }),
+            completion: None,
+            tui: None,
+            status: None,
+            agents: None,
+            output: None,
+            report: None,
+            format: None,
+            chaos: None,
+            scheduler: None,
+            state: None,
+            fallback: None,
+            templates: None,
+            notify: None,
+            context: None,
+        };
 
-        assert_eq!(parsed.0, 488245288);
-        assert_eq!(parsed.1, 361727632);
-        assert_eq!(parsed.2, 24);
-    }
+        let from_config = resolve_hook_config(None, None, Some(&config)).expect("resolve");
+        assert_eq!(from_config.url, "https://config.example/hook");
+        assert_eq!(from_config.token.as_deref(), Some("config-token"));
 
-    #[test]
-    fn parse_df_k_output_rejects_invalid_shape() {
-        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\ninvalid\n";
-        assert!(parse_df_k_output(output).is_none());
+        let from_cli = resolve_hook_config(
+            Some("https://cli.example/hook".to_string()),
+            None,
+            Some(&config),
+        )
+        .expect("resolve");
+        assert_eq!(from_cli.url, "https://cli.example/hook");
+        assert_eq!(from_cli.token.as_deref(), Some("config-token"));
+
+        assert!(resolve_hook_config(None, None, None).is_err());
     }
 
     #[test]
@@ -846,25 +994,52 @@ mod tests {
                 timeout: Some(700),
                 stall_timeout: Some(99),
                 max_failures: Some(4),
+                max_attempts: None,
+                max_tokens: Some(2_000_000),
+                max_cost_usd: None,
+                auto_extend: None,
+                max_prompt_tokens: None,
+                max_files_changed: None,
+                max_lines_changed: None,
+                timestamps: Some("local".to_string()),
             }),
             hooks: Some(HooksConfig {
                 url: Some("https://hooks.example/ralph".to_string()),
                 token: Some("token-abc".to_string()),
+                token_ref: None,
+                log_lines: Some(20),
             }),
+            completion: None,
+            tui: None,
+            status: None,
+            agents: None,
+            output: None,
+            report: None,
+            format: None,
+            chaos: None,
+            scheduler: None,
+            state: None,
+            fallback: None,
+            templates: None,
+            notify: None,
+            context: None,
         };
 
-        apply_run_config(&mut args, Some(&config), run_matches);
+        apply_run_config(&mut args, Some(&config), run_matches).expect("apply config");
 
         assert_eq!(args.agent, "codex");
         assert_eq!(args.max_iterations, 33);
-        assert_eq!(args.timeout, 700);
-        assert_eq!(args.stall_timeout, 99);
+        assert_eq!(args.timeout, Some(700));
+        assert_eq!(args.stall_timeout, Some(99));
         assert_eq!(args.max_failures, 4);
+        assert_eq!(args.timestamps, "local");
+        assert_eq!(args.max_tokens, Some(2_000_000));
         assert_eq!(
             args.hook_url.as_deref(),
             Some("https://hooks.example/ralph")
         );
         assert_eq!(args.hook_token.as_deref(), Some("token-abc"));
+        assert_eq!(args.hook_log_lines, Some(20));
     }
 
     #[test]
@@ -900,28 +1075,82 @@ mod tests {
                 timeout: Some(700),
                 stall_timeout: Some(99),
                 max_failures: Some(4),
+                max_attempts: None,
+                max_tokens: None,
+                max_cost_usd: None,
+                auto_extend: None,
+                max_prompt_tokens: None,
+                max_files_changed: None,
+                max_lines_changed: None,
+                timestamps: None,
             }),
             hooks: Some(HooksConfig {
                 url: Some("https://config.example/hook".to_string()),
                 token: Some("token-from-config".to_string()),
+                token_ref: None,
+                log_lines: None,
             }),
+            completion: None,
+            tui: None,
+            status: None,
+            agents: None,
+            output: None,
+            report: None,
+            format: None,
+            chaos: None,
+            scheduler: None,
+            state: None,
+            fallback: None,
+            templates: None,
+            notify: None,
+            context: None,
         };
 
-        apply_run_config(&mut args, Some(&config), run_matches);
+        apply_run_config(&mut args, Some(&config), run_matches).expect("apply config");
 
         assert_eq!(args.agent, "gemini");
         assert_eq!(args.max_iterations, 5);
         // Not specified on CLI, so config still applies.
-        assert_eq!(args.timeout, 700);
+        assert_eq!(args.timeout, Some(700));
         assert_eq!(args.hook_url.as_deref(), Some("https://cli.example/hook"));
         assert_eq!(args.hook_token.as_deref(), Some("token-from-config"));
     }
-}
 
-/// Shared test lock for tests that mutate process-global state (PATH, env vars).
-/// Import from both `orchestrator::tests` and `parser::tests` to serialize them.
-#[cfg(test)]
-pub(crate) fn global_env_lock() -> &'static std::sync::Mutex<()> {
-    static LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
-    LOCK.get_or_init(|| std::sync::Mutex::new(()))
+    #[test]
+    fn watch_applies_the_api_and_budget_settings() {
+        let argv = ["ralph", "watch", "a.md", "--api-url", "http://localhost:8000/v1"];
+        let cli = cli::Cli::parse_from(argv);
+        let matches = cli::Cli::command()
+            .try_get_matches_from(argv)
+            .expect("matches should parse");
+        let watch_matches = matches
+            .subcommand_matches("watch")
+            .expect("watch subcommand matches");
+
+        let mut args = match cli.command {
+            cli::Commands::Watch(args) => args,
+            _ => panic!("expected watch command"),
+        };
+
+        let api = config::AgentConfig {
+            protocol: Some("openai".to_string()),
+            ..Default::default()
+        };
+        let config = RalphConfig {
+            defaults: Some(DefaultsConfig {
+                max_tokens: Some(500_000),
+                max_cost_usd: Some(2.5),
+                ..Default::default()
+            }),
+            agents: Some(HashMap::from([("api".to_string(), api)])),
+            ..Default::default()
+        };
+
+        apply_watch_config(&mut args, Some(&config), watch_matches).expect("apply config");
+
+        assert_eq!(args.api_url.as_deref(), Some("http://localhost:8000/v1"));
+        assert_eq!(args.api_protocol, "openai");
+        assert_eq!(args.max_tokens, Some(500_000));
+        assert_eq!(args.max_cost_usd, Some(2.5));
+    }
 }
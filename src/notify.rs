@@ -5,7 +5,8 @@
 //! calling the `message` tool. No AI middleman — messages are delivered exactly
 //! as formatted.
 
-use crate::hooks::HookEvent;
+use crate::hooks::{self, HookEvent};
+use crate::timefmt::compact_duration;
 use std::path::Path;
 
 /// Parsed notify target (e.g. `discord:1234567890`).
@@ -48,14 +49,18 @@ pub struct NotifyConfig {
 
 impl NotifyConfig {
     /// Build from env vars + CLI flag.
-    /// Tries OPENCLAW_GATEWAY_TOKEN, then OPENCLAW_TOKEN, then OPENCLAW_HOOKS_TOKEN.
-    pub fn from_env(notify_flag: &str, prd_name: &str) -> Option<Self> {
+    /// Uses `token` (from `[notify] token_ref`) if given, else tries
+    /// OPENCLAW_GATEWAY_TOKEN, then OPENCLAW_TOKEN, then OPENCLAW_HOOKS_TOKEN.
+    pub fn from_env(notify_flag: &str, prd_name: &str, token: Option<&str>) -> Option<Self> {
         let target = NotifyTarget::parse(notify_flag)?;
 
-        let gateway_token = std::env::var("OPENCLAW_GATEWAY_TOKEN")
-            .or_else(|_| std::env::var("OPENCLAW_TOKEN"))
-            .or_else(|_| std::env::var("OPENCLAW_HOOKS_TOKEN"))
-            .ok()?;
+        let gateway_token = match token {
+            Some(token) => token.to_string(),
+            None => std::env::var("OPENCLAW_GATEWAY_TOKEN")
+                .or_else(|_| std::env::var("OPENCLAW_TOKEN"))
+                .or_else(|_| std::env::var("OPENCLAW_HOOKS_TOKEN"))
+                .ok()?,
+        };
 
         let gateway_url = std::env::var("OPENCLAW_URL")
             .unwrap_or_else(|_| "http://127.0.0.1:18789".to_string());
@@ -78,25 +83,35 @@ fn format_event(config: &NotifyConfig, event: &HookEvent, log_tail: Option<&str>
             task_title,
             iteration,
             duration_secs,
+            files_changed,
             progress,
             ..
         } => {
-            format!(
+            let mut msg = format!(
                 "✅ **{task_id}** — {task_title} (iter {iteration}, {duration_secs}s)\n📊 `[{prd}]` {}/{} tasks done",
                 progress.completed, progress.total
-            )
+            );
+            if !files_changed.is_empty() {
+                msg.push_str(&format!(
+                    "\n📝 {} file(s): {}",
+                    files_changed.len(),
+                    truncate(&files_changed.join(", "), 200)
+                ));
+            }
+            msg
         }
         HookEvent::TaskFailed {
             task_id,
             task_title,
             iteration,
             error,
+            failure_kind,
             consecutive_failures,
             progress,
             ..
         } => {
             let mut msg = format!(
-                "❌ **{task_id}** — {task_title} failed (iter {iteration}, {consecutive_failures} consecutive)\n📊 `[{prd}]` {}/{} done\nError: {}",
+                "❌ **{task_id}** — {task_title} failed (iter {iteration}, {consecutive_failures} consecutive)\n📊 `[{prd}]` {}/{} done\nError ({failure_kind}): {}",
                 progress.completed, progress.total, truncate(error, 200)
             );
             if let Some(tail) = log_tail {
@@ -104,32 +119,164 @@ fn format_event(config: &NotifyConfig, event: &HookEvent, log_tail: Option<&str>
             }
             msg
         }
+        HookEvent::TaskNeedsReview {
+            task_id,
+            task_title,
+            iteration,
+            confidence,
+            threshold,
+            acceptance,
+            reason,
+            progress,
+        } => {
+            let why = reason.clone().unwrap_or_else(|| {
+                let confidence = confidence
+                    .map(|c| format!("{c:.2}"))
+                    .unwrap_or_else(|| "unscored".to_string());
+                format!("confidence {confidence} < {threshold:.2}")
+            });
+            let mut msg = format!(
+                "👀 **{task_id}** — {task_title} needs review (iter {iteration}, {why})\n📊 `[{prd}]` {}/{} done — run `ralph approve {task_id}`",
+                progress.completed, progress.total
+            );
+            for criterion in acceptance {
+                msg.push_str(&format!("\n☐ {}", truncate(criterion, 200)));
+            }
+            msg
+        }
+        HookEvent::ScopeViolation {
+            task_id,
+            project,
+            paths,
+            ignored,
+            unreverted,
+            ..
+        } => {
+            let place = if ignored.len() == paths.len() {
+                "listed in `.ralphignore`".to_string()
+            } else {
+                format!("outside `{project}`")
+            };
+            let mut msg = format!(
+                "🚧 **{task_id}** touched {} file(s) {place} — reverted: {}",
+                paths.len(),
+                truncate(&paths.join(", "), 200)
+            );
+            if !unreverted.is_empty() {
+                msg.push_str(&format!("\n⚠️ Could not revert: {}", unreverted.join(", ")));
+            }
+            msg
+        }
+        HookEvent::TimeoutWarning {
+            task_id,
+            iteration,
+            elapsed_secs,
+            timeout_secs,
+            ..
+        } => format!(
+            "⏳ `[{prd}]` **{task_id}** (iter {iteration}) has run {elapsed_secs}s of its {timeout_secs}s timeout"
+        ),
+        HookEvent::RateLimited {
+            task_id,
+            iteration,
+            retry_after_secs,
+            ..
+        } => format!(
+            "⏳ `[{prd}]` **{task_id}** (iter {iteration}) hit a rate limit — retrying in {}",
+            compact_duration(*retry_after_secs)
+        ),
+        HookEvent::AgentSwitched {
+            task_id,
+            from,
+            to,
+            reason,
+            benched_secs,
+            ..
+        } => {
+            let task = task_id
+                .as_deref()
+                .map(|id| format!("**{id}** "))
+                .unwrap_or_default();
+            let benched = benched_secs
+                .map(|secs| format!(", {from} benched for {}", compact_duration(secs)))
+                .unwrap_or_default();
+            format!("🔄 `[{prd}]` {task}{from} → {to} ({reason}{benched})")
+        }
+        HookEvent::Heartbeat {
+            task_id,
+            iteration,
+            eta_secs,
+            progress,
+            ..
+        } => {
+            let eta = eta_secs
+                .map(|secs| format!("~{}m left", secs.div_ceil(60)))
+                .unwrap_or_else(|| "ETA unknown".to_string());
+            format!(
+                "💓 `[{prd}]` iter {iteration} on **{task_id}** — {}/{} done, {eta}",
+                progress.completed, progress.total
+            )
+        }
         HookEvent::AllComplete {
             total_tasks,
             total_iterations,
             total_duration_secs,
+            tokens,
+            cost_usd,
             ..
         } => {
-            let mins = total_duration_secs / 60;
-            let secs = total_duration_secs % 60;
-            format!(
-                "🎉 **All {total_tasks} tasks complete!**\n📊 `[{prd}]` {total_iterations} iterations, {mins}m{secs}s total"
-            )
+            let total = compact_duration(*total_duration_secs);
+            let mut msg = format!(
+                "🎉 **All {total_tasks} tasks complete!**\n📊 `[{prd}]` {total_iterations} iterations, {total} total"
+            );
+            if let Some(tokens) = tokens {
+                msg.push_str(&format!(", {} tokens", tokens.total()));
+            }
+            if let Some(cost) = cost_usd {
+                msg.push_str(&format!(", ${cost:.2}"));
+            }
+            msg
         }
+        HookEvent::BudgetExceeded { reason, progress, .. } => format!(
+            "💸 **Budget exceeded** — {reason}\n📊 `[{prd}]` {}/{} done",
+            progress.completed, progress.total
+        ),
         HookEvent::CircuitBreaker {
             consecutive_failures,
             last_error,
             progress,
+            doctor,
         } => {
             let mut msg = format!(
                 "⚠️ **Circuit breaker triggered** — {consecutive_failures} consecutive failures\n📊 `[{prd}]` {}/{} done\nLast error: {}",
                 progress.completed, progress.total, truncate(last_error, 200)
             );
+            for row in doctor.iter().filter(|row| !row.is_ok()) {
+                msg.push_str(&format!(
+                    "\n🩺 {} {}: {}",
+                    row.check,
+                    row.status,
+                    truncate(&row.details, 100)
+                ));
+            }
             if let Some(tail) = log_tail {
                 msg.push_str(&format!("\n```\n{}\n```", truncate(tail, 500)));
             }
             msg
         }
+        HookEvent::IterationsShort {
+            remaining_tasks,
+            iterations_left,
+            extended_to,
+            ..
+        } => match extended_to {
+            Some(to) => format!(
+                "⏫ `[{prd}]` {remaining_tasks} tasks left for {iterations_left} iterations — progress is steady, extended to {to}"
+            ),
+            None => format!(
+                "⚠️ `[{prd}]` {remaining_tasks} tasks left but only {iterations_left} iterations — some won't be attempted"
+            ),
+        },
         HookEvent::MaxIterations {
             max_iterations,
             progress,
@@ -139,6 +286,37 @@ fn format_event(config: &NotifyConfig, event: &HookEvent, log_tail: Option<&str>
                 progress.completed, progress.total
             )
         }
+        HookEvent::WatchStarted { loops, parallel } => {
+            let names: Vec<&str> = loops.iter().map(|l| l.name.as_str()).collect();
+            format!(
+                "🚀 **Watch started** — {} loops, {parallel} at a time: {}",
+                loops.len(),
+                names.join(", ")
+            )
+        }
+        HookEvent::WatchComplete {
+            loops,
+            total_duration_secs,
+        } => {
+            let complete = loops.iter().filter(|l| l.outcome == "complete").count();
+            let mut msg = format!(
+                "🏁 **Watch complete** — {complete}/{} loops finished in {}",
+                loops.len(),
+                compact_duration(*total_duration_secs)
+            );
+            for l in loops {
+                msg.push_str(&format!(
+                    "\n• `{}` {} — {}/{} tasks, {}",
+                    l.name,
+                    l.outcome,
+                    l.tasks_done,
+                    l.tasks_total,
+                    compact_duration(l.duration_secs)
+                ));
+            }
+            msg
+        }
+        HookEvent::Ping { message, .. } => format!("🔔 `[{prd}]` {message}"),
     }
 }
 
@@ -156,14 +334,6 @@ fn truncate(s: &str, max_len: usize) -> &str {
     }
 }
 
-/// Read the last N lines from a log file.
-fn read_log_tail(log_path: &Path, lines: usize) -> Option<String> {
-    let content = std::fs::read_to_string(log_path).ok()?;
-    let all_lines: Vec<&str> = content.lines().collect();
-    let start = all_lines.len().saturating_sub(lines);
-    Some(all_lines[start..].join("\n"))
-}
-
 /// Send a notification to OpenClaw via /tools/invoke → message tool.
 /// Direct delivery — no AI middleman, message arrives exactly as formatted.
 pub async fn send_notify(
@@ -171,10 +341,15 @@ pub async fn send_notify(
     event: &HookEvent,
     log_path: Option<&Path>,
 ) {
+    // Heartbeats are for machine consumers; one chat message per iteration is noise.
+    if matches!(event, HookEvent::Heartbeat { .. }) {
+        return;
+    }
+
     // For failure events, grab log tail
     let log_tail = match event {
         HookEvent::TaskFailed { .. } | HookEvent::CircuitBreaker { .. } => {
-            log_path.and_then(|p| read_log_tail(p, 15))
+            log_path.and_then(|p| hooks::read_log_tail(p, 15))
         }
         _ => None,
     };
@@ -0,0 +1,398 @@
+//! `ralph open <TASK_ID>` — open what a task left behind in an editor: its
+//! latest iteration log, its latest failure snapshot, the diff of its
+//! commits, and the files that diff touches, each at its first changed line.
+//!
+//! Tasks are found in commits by the subjects Ralph writes
+//! (`feat: T3 — … (ralph)`, `wip: T3 — … (ralph, needs review)`, or
+//! `feat: T1, T3 (ralph)` for a parallel batch).
+//! A task with no commit yet gets the working-tree diff its failure snapshot
+//! saved instead.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command as StdCommand;
+
+use crate::cli::OpenArgs;
+use crate::git::GitManager;
+use crate::state::StateManager;
+
+/// Editors that take `--goto path:line`.
+const GOTO_EDITORS: &[&str] = &["code", "code-insiders", "codium", "cursor"];
+
+const NO_EDITOR: &str =
+    "No editor found — set $EDITOR, pass --editor, or install VS Code's `code` command";
+
+pub async fn open_task(args: OpenArgs) -> Result<()> {
+    let workdir = args
+        .workdir
+        .as_deref()
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+
+    let state = match args.name.as_deref() {
+        Some(name) => StateManager::new_named(&workdir, name)?,
+        None => StateManager::new(&workdir)?,
+    };
+    let task_list = state
+        .load_tasks()?
+        .context("No tasks.json found — nothing to open")?;
+    let task = task_list
+        .tasks
+        .iter()
+        .find(|t| t.id == args.task_id)
+        .with_context(|| format!("Unknown task: {}", args.task_id))?;
+
+    let targets = collect(&state, &GitManager::new(&workdir), &workdir, &task.id).await?;
+    if targets.is_empty() {
+        anyhow::bail!(
+            "Nothing to open for {}: no iteration logs, commits, or failure snapshots",
+            task.id
+        );
+    }
+
+    if args.print {
+        for target in &targets {
+            println!("{target}");
+        }
+        return Ok(());
+    }
+
+    let editor = resolve_editor(args.editor.as_deref()).context(NO_EDITOR)?;
+    println!("📂  {} — {} ({} files)", task.id, task.title, targets.len());
+    launch(&editor, &targets, false)
+}
+
+/// Open `files` in the user's editor and wait until it is closed.
+pub fn edit(files: &[PathBuf]) -> Result<()> {
+    let editor = resolve_editor(None).context(NO_EDITOR)?;
+    let targets: Vec<Target> = files.iter().cloned().map(Target::file).collect();
+    launch(&editor, &targets, true)
+}
+
+/// A file to open, at a line when one is known.
+#[derive(Debug, PartialEq)]
+struct Target {
+    path: PathBuf,
+    line: Option<u32>,
+}
+
+impl Target {
+    fn file(path: PathBuf) -> Self {
+        Self { path, line: None }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.path.display()),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+/// Everything there is to open for `task_id`, most useful first.
+async fn collect(
+    state: &StateManager,
+    git: &GitManager,
+    workdir: &Path,
+    task_id: &str,
+) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    if let Some(log) = latest_log(&state.logs_dir, task_id) {
+        targets.push(Target::file(log));
+    }
+    let snapshot = latest_failure(&state.ralph_dir.join("failures"), task_id);
+    if let Some(dir) = &snapshot {
+        targets.push(Target::file(dir.join("error.txt")));
+    }
+
+    let mut patch = String::new();
+    for hash in task_commits(git, task_id).await {
+        patch.push_str(&git.show_commit(&hash).await?);
+        patch.push('\n');
+    }
+    if !patch.is_empty() {
+        let path = state.ralph_dir.join("open").join(format!("{task_id}.diff"));
+        std::fs::create_dir_all(path.parent().expect("has a parent"))?;
+        std::fs::write(&path, &patch)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        targets.push(Target::file(path));
+    } else if let Some(dir) = &snapshot {
+        let path = dir.join("git-diff.patch");
+        patch = std::fs::read_to_string(&path).unwrap_or_default();
+        if !patch.trim().is_empty() {
+            targets.push(Target::file(path));
+        }
+    }
+
+    for (file, line) in changed_files(&patch) {
+        let path = workdir.join(file);
+        if path.is_file() {
+            targets.push(Target {
+                path,
+                line: Some(line),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// The task's commits, oldest first. Outside a git repository there are none.
+async fn task_commits(git: &GitManager, task_id: &str) -> Vec<String> {
+    let mut hashes: Vec<String> = git
+        .commits_mentioning(task_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, subject)| commit_tasks(subject).contains(&task_id))
+        .map(|(hash, _)| hash)
+        .collect();
+    hashes.reverse();
+    hashes
+}
+
+/// The task IDs in a Ralph commit subject; empty for other commits.
+fn commit_tasks(subject: &str) -> Vec<&str> {
+    let Some(rest) = subject
+        .strip_prefix("feat: ")
+        .or_else(|| subject.strip_prefix("wip: "))
+    else {
+        return vec![];
+    };
+    let Some(end) = rest
+        .find(" — ")
+        .or_else(|| rest.find(" (ralph)"))
+        .or_else(|| rest.find(" (ralph, needs review)"))
+    else {
+        return vec![];
+    };
+    rest[..end].split(", ").collect()
+}
+
+/// The highest-numbered `iteration-N-<task_id>.log`.
+fn latest_log(logs_dir: &Path, task_id: &str) -> Option<PathBuf> {
+    let suffix = format!("-{task_id}.log");
+    std::fs::read_dir(logs_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let n: u32 = name
+                .strip_prefix("iteration-")?
+                .strip_suffix(&suffix)?
+                .parse()
+                .ok()?;
+            Some((n, entry.path()))
+        })
+        .max_by_key(|(n, _)| *n)
+        .map(|(_, path)| path)
+}
+
+/// The latest `--failure-snapshots` bundle whose `error.txt` names the task.
+fn latest_failure(failures_dir: &Path, task_id: &str) -> Option<PathBuf> {
+    let needle = format!("task {task_id} (");
+    std::fs::read_dir(failures_dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let n: u32 = name.strip_prefix("iteration-")?.parse().ok()?;
+            let error = std::fs::read_to_string(entry.path().join("error.txt")).ok()?;
+            let first = error.lines().next()?;
+            first.contains(&needle).then(|| (n, entry.path()))
+        })
+        .max_by_key(|(n, _)| *n)
+        .map(|(_, path)| path)
+}
+
+/// Each file a patch changes, with the first line of its first hunk, in
+/// patch order.
+fn changed_files(patch: &str) -> Vec<(String, u32)> {
+    let mut files: Vec<(String, u32)> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in patch.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current = path
+                .strip_prefix("b/")
+                .filter(|p| !files.iter().any(|(f, _)| f == p))
+                .map(str::to_string);
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            let Some(file) = current.take() else {
+                continue;
+            };
+            let start = hunk
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|n| n.parse::<u32>().ok())
+                .unwrap_or(1);
+            files.push((file, start.max(1)));
+        }
+    }
+    files
+}
+
+/// `--editor`, `$VISUAL`, `$EDITOR`, or `code` when it is on PATH, split into
+/// program and arguments.
+fn resolve_editor(flag: Option<&str>) -> Option<Vec<String>> {
+    let chosen = flag
+        .map(str::to_string)
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty());
+    match chosen {
+        Some(editor) => Some(editor.split_whitespace().map(str::to_string).collect()),
+        None => on_path("code").then(|| vec!["code".to_string()]),
+    }
+}
+
+fn on_path(bin: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(bin).is_file()))
+}
+
+/// Run the editor on `targets`. GUI editors return at once unless told to
+/// `wait`.
+fn launch(editor: &[String], targets: &[Target], wait: bool) -> Result<()> {
+    let (program, args) = editor.split_first().context("Empty editor command")?;
+    let goto = Path::new(program)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| GOTO_EDITORS.contains(&n));
+    let mut command = StdCommand::new(program);
+    command.args(args);
+    if goto {
+        if wait {
+            command.arg("--wait");
+        }
+        command.arg("--goto");
+        command.args(targets.iter().map(Target::to_string));
+    } else {
+        command.args(targets.iter().map(|t| &t.path));
+    }
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to start editor: {program}"))?;
+    if !status.success() {
+        anyhow::bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = StdCommand::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[test]
+    fn ralph_commit_subjects_name_their_tasks() {
+        assert_eq!(commit_tasks("feat: T3 — Add login (ralph)"), ["T3"]);
+        assert_eq!(commit_tasks("feat: T1, T3 (ralph)"), ["T1", "T3"]);
+        assert_eq!(
+            commit_tasks("wip: T4 — Add logout (ralph, needs review)"),
+            ["T4"]
+        );
+        assert!(commit_tasks("fix: T3 typo").is_empty());
+        assert!(commit_tasks("feat: T30").is_empty());
+
+        let patch = "\
+diff --git a/src/login.rs b/src/login.rs
+--- a/src/login.rs
++++ b/src/login.rs
+@@ -10,3 +12,5 @@ fn main() {
++fn login() {}
+@@ -40,1 +44,1 @@
+diff --git a/notes.md b/notes.md
+--- /dev/null
++++ b/notes.md
+@@ -0,0 +1 @@
++hi
+diff --git a/old.rs b/old.rs
+--- a/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+        assert_eq!(
+            changed_files(patch),
+            [
+                ("src/login.rs".to_string(), 12),
+                ("notes.md".to_string(), 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn collects_the_latest_log_snapshot_commit_and_files() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::create_dir_all(root.join("src")).expect("create src");
+        fs::write(root.join("src/login.rs"), "fn login() {}\n").expect("write login");
+        git(root, &["add", "-A"]);
+        git(
+            root,
+            &["commit", "-q", "-m", "feat: T3 — Add login (ralph)"],
+        );
+        fs::write(root.join("src/other.rs"), "\n").expect("write other");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "feat: T30 — Other (ralph)"]);
+
+        let state = StateManager::new(root).expect("create state manager");
+        for log in [
+            "iteration-2-T3.log",
+            "iteration-5-T3.log",
+            "iteration-7-T30.log",
+        ] {
+            fs::write(state.logs_dir.join(log), "log\n").expect("write log");
+        }
+        let failure = state.failure_dir(4);
+        fs::create_dir_all(&failure).expect("create snapshot");
+        fs::write(
+            failure.join("error.txt"),
+            "Iteration 4 — task T3 (codex)\n\nboom\n",
+        )
+        .expect("write error");
+
+        let targets = collect(&state, &GitManager::new(root), root, "T3")
+            .await
+            .expect("collect");
+        let shown: Vec<String> = targets
+            .iter()
+            .map(|t| {
+                let rel = t.path.strip_prefix(root).expect("inside workdir");
+                match t.line {
+                    Some(line) => format!("{}:{line}", rel.display()),
+                    None => rel.display().to_string(),
+                }
+            })
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                ".ralph/logs/iteration-5-T3.log",
+                ".ralph/failures/iteration-4/error.txt",
+                ".ralph/open/T3.diff",
+                "src/login.rs:1",
+            ]
+        );
+        let diff = fs::read_to_string(root.join(".ralph/open/T3.diff")).expect("read diff");
+        assert!(diff.contains("Add login"), "{diff}");
+        assert!(!diff.contains("other.rs"), "{diff}");
+    }
+}
@@ -0,0 +1,127 @@
+//! Per-task retry budget (`--max-attempts`, or a task's own `max_attempts`).
+//! A task that keeps failing is given up on: it is marked failed, the tasks
+//! waiting on it are skipped, and the run goes on with unrelated work rather
+//! than spending its failures on one task until the circuit breaker trips.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::state::{Task, TaskList, TaskStatus};
+
+/// Failed attempts per task in this run.
+pub struct Attempts {
+    max_attempts: u32,
+    failed: HashMap<String, u32>,
+}
+
+impl Attempts {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            failed: HashMap::new(),
+        }
+    }
+
+    /// Count a failed attempt at `task`. Returns how many it has used once
+    /// that is its whole budget, and `None` while it may be retried.
+    pub fn fail(&mut self, task: &Task) -> Option<u32> {
+        let failed = self.failed.entry(task.id.clone()).or_insert(0);
+        *failed += 1;
+        let budget = task.max_attempts.unwrap_or(self.max_attempts);
+        (*failed >= budget).then_some(*failed)
+    }
+}
+
+/// The pending tasks that wait on `id`, directly or through other tasks, in
+/// tasks.json order.
+pub fn blocked_by(task_list: &TaskList, id: &str) -> Vec<String> {
+    let mut blocked: HashSet<&str> = HashSet::from([id]);
+    loop {
+        let before = blocked.len();
+        for task in &task_list.tasks {
+            if task
+                .depends_on
+                .iter()
+                .any(|dep| blocked.contains(dep.as_str()))
+            {
+                blocked.insert(&task.id);
+            }
+        }
+        if blocked.len() == before {
+            break;
+        }
+    }
+    task_list
+        .tasks
+        .iter()
+        .filter(|t| {
+            t.id != id && t.status == TaskStatus::Pending && blocked.contains(t.id.as_str())
+        })
+        .map(|t| t.id.clone())
+        .collect()
+}
+
+/// E.g. `Task T2 given up after 3 failed attempts; skipping T3, T4`.
+pub fn describe(task: &Task, attempts: u32, blocked: &[String]) -> String {
+    let mut msg = format!(
+        "Task {} given up after {attempts} failed attempt{}",
+        task.id,
+        if attempts == 1 { "" } else { "s" }
+    );
+    if !blocked.is_empty() {
+        msg.push_str(&format!("; skipping {}", blocked.join(", ")));
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn task(id: &str, depends_on: &[&str], status: TaskStatus) -> Task {
+        Task {
+            id: id.to_string(),
+            title: format!("Task {id}"),
+            priority: 1,
+            status,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_task_is_given_up_once_its_budget_is_spent() {
+        let mut attempts = Attempts::new(3);
+        let plain = task("T1", &[], TaskStatus::Pending);
+        let mut flaky = task("T2", &[], TaskStatus::Pending);
+        flaky.max_attempts = Some(1);
+
+        assert_eq!(attempts.fail(&plain), None);
+        assert_eq!(attempts.fail(&flaky), Some(1));
+        assert_eq!(attempts.fail(&plain), None);
+        assert_eq!(attempts.fail(&plain), Some(3));
+        assert_eq!(
+            describe(&plain, 3, &["T3".to_string()]),
+            "Task T1 given up after 3 failed attempts; skipping T3"
+        );
+    }
+
+    #[test]
+    fn dependents_are_blocked_transitively() {
+        let list = TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: vec![
+                task("T4", &["T3"], TaskStatus::Pending),
+                task("T1", &[], TaskStatus::Failed),
+                task("T2", &[], TaskStatus::Pending),
+                task("T3", &["T1", "T2"], TaskStatus::Pending),
+                task("T5", &["T1"], TaskStatus::Complete),
+            ],
+        };
+        assert_eq!(blocked_by(&list, "T1"), ["T4", "T3"]);
+        assert!(blocked_by(&list, "T4").is_empty());
+    }
+}
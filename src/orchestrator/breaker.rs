@@ -0,0 +1,98 @@
+//! Asking before the circuit breaker trips. Under the `ralph watch` TUI, a
+//! loop that reaches `--max-failures` puts the question to the operator —
+//! continue anyway, switch agent, or stop — and waits up to
+//! `--breaker-timeout` seconds for `c`, `a`, or `s`. Whoever is watching
+//! often knows whether the failures were a network blip or a dead agent.
+//!
+//! Unanswered, or when the session is cancelled, the loop stops as it would
+//! have without the prompt.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::state::{BreakerChoice, BreakerPrompt, SharedLoopStatus};
+
+/// How often the prompt is checked for an answer.
+const POLL: Duration = Duration::from_millis(200);
+
+/// Show the prompt on `loop_status` and wait for the operator's answer.
+/// `switch_to` names the agent `SwitchAgent` would move to; without one
+/// that answer is not offered.
+pub async fn ask(
+    loop_status: &SharedLoopStatus,
+    failures: u32,
+    timeout_secs: u64,
+    switch_to: Option<String>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+) -> BreakerChoice {
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    if let Ok(mut s) = loop_status.lock() {
+        s.breaker_prompt = Some(BreakerPrompt {
+            failures,
+            deadline,
+            switch_to,
+            answer: None,
+        });
+    }
+    let answer = loop {
+        let answer = loop_status
+            .lock()
+            .ok()
+            .and_then(|s| s.breaker_prompt.as_ref().and_then(|p| p.answer));
+        let cancelled = cancel_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed));
+        if answer.is_some() || cancelled || Instant::now() >= deadline {
+            break answer;
+        }
+        tokio::time::sleep(POLL).await;
+    };
+    if let Ok(mut s) = loop_status.lock() {
+        s.breaker_prompt = None;
+    }
+    answer.unwrap_or(BreakerChoice::Stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::LoopStatus;
+    use std::sync::Mutex;
+
+    fn status() -> SharedLoopStatus {
+        Arc::new(Mutex::new(LoopStatus::new(
+            "auth".to_string(),
+            "auth.md".to_string(),
+            "codex".to_string(),
+        )))
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_operator_and_stops_when_no_answer_comes() {
+        let ls = status();
+        let operator = ls.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let mut s = operator.lock().unwrap();
+                if let Some(prompt) = s.breaker_prompt.as_mut() {
+                    assert_eq!(prompt.failures, 3);
+                    assert_eq!(prompt.switch_to.as_deref(), Some("claude"));
+                    prompt.answer = Some(BreakerChoice::SwitchAgent);
+                    return;
+                }
+            }
+        });
+        let choice = ask(&ls, 3, 30, Some("claude".to_string()), None).await;
+        assert_eq!(choice, BreakerChoice::SwitchAgent);
+        assert!(ls.lock().unwrap().breaker_prompt.is_none());
+
+        let unanswered = ask(&ls, 3, 0, None, None).await;
+        assert_eq!(unanswered, BreakerChoice::Stop);
+
+        let cancel = Arc::new(AtomicBool::new(true));
+        let cancelled = ask(&ls, 3, 30, None, Some(cancel)).await;
+        assert_eq!(cancelled, BreakerChoice::Stop);
+    }
+}
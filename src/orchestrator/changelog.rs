@@ -0,0 +1,266 @@
+//! `[report] changelog = true`: once every task is complete, add an entry for
+//! the run to the project's changelog — the completed tasks plus the commits
+//! made since the run started. The `template` style writes that entry as is;
+//! the `agent` style hands it to the agent as a draft to turn into release
+//! notes, falling back to the template when the agent leaves the file alone.
+
+use anyhow::Result;
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::agents::Agent;
+use crate::cli::RunArgs;
+use crate::config::ReportConfig;
+use crate::git::GitManager;
+use crate::output::Pipeline;
+use crate::state::{StateManager, TaskList, TaskStatus};
+
+/// Styles accepted by `[report] changelog_style`.
+const STYLES: &[&str] = &["template", "agent"];
+
+const CHANGELOG_PROMPT: &str = r#"You are an expert software engineer. Every task of a PRD has just been implemented in this repository. Your only job now is to update the changelog at `{file}` — do not change any other file.
+
+## Draft entry
+
+{entry}
+## Instructions
+
+1. Add one new entry for this work at the top of `{file}`, below its title if it has one. Create the file if it does not exist.
+2. Write for users of the project: group changes under "Added", "Changed", and "Fixed" headings, one short line per change.
+3. Leave out task IDs, commit hashes, and internal refactors nobody outside the project would notice.
+4. Keep every existing entry exactly as it is.
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    Template,
+    Agent,
+}
+
+/// Where and how to write the changelog.
+#[derive(Debug, Clone)]
+pub struct Changelog {
+    file: PathBuf,
+    style: Style,
+}
+
+impl Changelog {
+    /// The changelog described by `[report]`, or `None` when it is off.
+    pub fn from_config(config: &ReportConfig) -> Result<Option<Self>> {
+        if config.changelog != Some(true) {
+            return Ok(None);
+        }
+        let style = match config.changelog_style.as_deref() {
+            None | Some("template") => Style::Template,
+            Some("agent") => Style::Agent,
+            Some(other) => anyhow::bail!(
+                "Unknown changelog_style '{other}' (expected one of: {})",
+                STYLES.join(", ")
+            ),
+        };
+        let file = config
+            .changelog_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("CHANGELOG.md"));
+        Ok(Some(Self { file, style }))
+    }
+}
+
+/// Write the changelog entry for the run and commit it.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    changelog: &Changelog,
+    agent: &dyn Agent,
+    args: &RunArgs,
+    git: &GitManager,
+    state: &StateManager,
+    project_dir: &Path,
+    output: Arc<Pipeline>,
+    iteration: u32,
+    run_base: Option<&str>,
+    task_list: &TaskList,
+) -> Result<()> {
+    let path = project_dir.join(&changelog.file);
+    let commits = match run_base {
+        Some(base) => git
+            .log_range(&format!("{base}..HEAD"))
+            .await
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+    let entry = render_entry(
+        task_list,
+        &commits,
+        &Utc::now().format("%Y-%m-%d").to_string(),
+    );
+    let before = std::fs::read_to_string(&path).unwrap_or_default();
+
+    let mut written_by_agent = false;
+    if changelog.style == Style::Agent {
+        println!("\n━━━ Changelog ━━━");
+        let log_path = state.log_path(iteration, "changelog");
+        println!("    Log: {}", log_path.display());
+        let prompt = CHANGELOG_PROMPT
+            .replace("{file}", &changelog.file.display().to_string())
+            .replace("{entry}", &entry);
+        let (timeout, stall_timeout) = super::limits_for(args, agent);
+        let result = super::run_iteration(
+            agent,
+            &prompt,
+            project_dir,
+            &log_path,
+            timeout,
+            stall_timeout,
+            args.kill_grace,
+            args.stop_grace,
+            &[],
+            args.verbose,
+            args.loop_status.clone(),
+            args.cancel_flag.clone(),
+            output,
+            Some(&state.lock_file),
+        )
+        .await;
+        let after = std::fs::read_to_string(&path).unwrap_or_default();
+        written_by_agent = result.is_ok() && after != before;
+        if !written_by_agent {
+            println!(
+                "    ⚠️   The agent did not update the changelog — writing the template entry"
+            );
+        }
+    }
+    if !written_by_agent {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, insert_entry(&before, &entry))?;
+    }
+
+    println!("    📝  Changelog updated: {}", changelog.file.display());
+    state.append_progress(&format!(
+        "**Iteration {iteration}** — changelog updated ({}).",
+        changelog.file.display()
+    ))?;
+    // An agent that commits its own edits has already committed the changelog.
+    let committed_by_agent = written_by_agent && agent.capabilities().manages_own_commits;
+    if !args.no_branch && !committed_by_agent {
+        super::auto_commit(git, "docs: update changelog (ralph)", false).await;
+    }
+    Ok(())
+}
+
+/// A dated entry listing the completed tasks and the run's commits
+/// (`git log --oneline` output).
+fn render_entry(task_list: &TaskList, commits: &str, date: &str) -> String {
+    let prd = Path::new(&task_list.prd_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| task_list.prd_path.clone());
+    let mut entry = format!("## {date} — {prd}\n\n### Completed tasks\n\n");
+    for task in task_list
+        .tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Complete)
+    {
+        entry.push_str(&format!("- **{}** — {}\n", task.id, task.title));
+    }
+    let commits: Vec<&str> = commits.lines().filter(|l| !l.trim().is_empty()).collect();
+    if !commits.is_empty() {
+        entry.push_str("\n### Commits\n\n");
+        for commit in commits {
+            entry.push_str(&format!("- {}\n", commit.trim()));
+        }
+    }
+    entry.push('\n');
+    entry
+}
+
+/// `existing` with `entry` added at the top, below a leading `# ` title.
+/// A new changelog gets a `# Changelog` title.
+fn insert_entry(existing: &str, entry: &str) -> String {
+    if existing.trim().is_empty() {
+        return format!("# Changelog\n\n{entry}");
+    }
+    match existing.strip_prefix("# ") {
+        Some(_) => {
+            let (title, rest) = existing.split_once('\n').unwrap_or((existing, ""));
+            format!("{title}\n\n{entry}{}", rest.trim_start_matches('\n'))
+        }
+        None => format!("{entry}{existing}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Task;
+
+    fn task(id: &str, title: &str, status: TaskStatus) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            priority: 1,
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn entry_lists_completed_tasks_and_commits() {
+        let now = Utc::now();
+        let task_list = TaskList {
+            version: 1,
+            prd_path: "/srv/app/prd.md".to_string(),
+            created_at: now,
+            updated_at: now,
+            tasks: vec![
+                task("T1", "Login page", TaskStatus::Complete),
+                task("T2", "Dropped idea", TaskStatus::Failed),
+            ],
+        };
+        let entry = render_entry(
+            &task_list,
+            "abc1234 feat: T1 — Login page (ralph)\n",
+            "2026-10-15",
+        );
+        assert_eq!(
+            entry,
+            "## 2026-10-15 — prd.md\n\n### Completed tasks\n\n- **T1** — Login page\n\n\
+             ### Commits\n\n- abc1234 feat: T1 — Login page (ralph)\n\n"
+        );
+        assert!(!render_entry(&task_list, "", "2026-10-15").contains("### Commits"));
+    }
+
+    #[test]
+    fn entries_go_below_the_title_newest_first() {
+        let first = insert_entry("", "## B\n\n");
+        assert_eq!(first, "# Changelog\n\n## B\n\n");
+        assert_eq!(
+            insert_entry(&first, "## C\n\n"),
+            "# Changelog\n\n## C\n\n## B\n\n"
+        );
+        assert_eq!(insert_entry("## A\n", "## B\n\n"), "## B\n\n## A\n");
+    }
+
+    #[test]
+    fn config_selects_style_and_file() {
+        assert!(Changelog::from_config(&ReportConfig::default())
+            .unwrap()
+            .is_none());
+        let config = ReportConfig {
+            changelog: Some(true),
+            changelog_file: None,
+            changelog_style: Some("agent".to_string()),
+        };
+        let changelog = Changelog::from_config(&config).unwrap().expect("enabled");
+        assert_eq!(changelog.style, Style::Agent);
+        assert_eq!(changelog.file, PathBuf::from("CHANGELOG.md"));
+
+        let config = ReportConfig {
+            changelog_style: Some("haiku".to_string()),
+            ..config
+        };
+        assert!(Changelog::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Switching to the loop's branch without dragging the user's uncommitted
+//! work along: tracked changes are stashed, committed where they are, or the
+//! run stops before anything touches the tree. With `--base`, the branch is
+//! also rooted on, and kept current with, the base branch.
+
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::git::GitManager;
+
+/// `--on-dirty` choices.
+pub const DIRTY_CHECKOUT_ACTIONS: &[&str] = &["ask", "stash", "commit", "abort"];
+
+/// Check out `branch` (created from `base` if new), first clearing
+/// uncommitted changes to tracked files as `action` says. Returns what was
+/// done with them, for the progress log.
+pub async fn checkout_branch(
+    git: &GitManager,
+    branch: &str,
+    base: Option<&str>,
+    action: &str,
+) -> Result<Option<String>> {
+    let current = git.current_branch().await.unwrap_or_default();
+    let mut note = None;
+    if current != branch && git.has_tracked_changes().await? {
+        let choice = match action {
+            "ask" if std::io::stdin().is_terminal() => ask(&current, branch)?,
+            "ask" => "abort",
+            other => other,
+        };
+        match choice {
+            "stash" => {
+                git.stash(&format!("ralph: before switching to {branch}"))
+                    .await?;
+                note = Some(format!(
+                    "Stashed uncommitted changes on {current} — `git stash pop` there to restore them"
+                ));
+            }
+            "commit" => {
+                git.commit_tracked(&format!("WIP before ralph run on {branch}"))
+                    .await?;
+                note = Some(format!("Committed uncommitted changes to {current} as WIP"));
+            }
+            _ => bail!(
+                "{current} has uncommitted changes that would follow Ralph onto {branch}. \
+                 Commit or stash them, or pass --on-dirty stash|commit"
+            ),
+        }
+    }
+    git.create_or_checkout_branch(branch, base)
+        .await
+        .with_context(|| format!("Cannot check out {branch}"))?;
+    Ok(note)
+}
+
+/// Where `base` stands against origin after fetching it.
+pub struct BaseStatus {
+    /// The ref to rebase onto: `origin/<base>` when it exists, else `base`.
+    pub upstream: String,
+    /// Commits on `origin/<base>` that the local `base` lacks.
+    pub behind: u64,
+    /// Why the fetch failed, if it did; `upstream` may then be stale.
+    pub fetch_error: Option<String>,
+}
+
+/// Fetch `base` from origin (when there is one) and see how far the local
+/// branch has fallen behind.
+pub async fn check_base(git: &GitManager, base: &str) -> Result<BaseStatus> {
+    let mut fetch_error = None;
+    if git.has_remote("origin").await {
+        if let Err(e) = git.fetch("origin", base).await {
+            fetch_error = Some(e.to_string());
+        }
+    }
+    let remote = format!("origin/{base}");
+    let has_local = git.rev_exists(base).await;
+    if !git.rev_exists(&remote).await {
+        if !has_local {
+            bail!("Base branch {base} does not exist");
+        }
+        return Ok(BaseStatus {
+            upstream: base.to_string(),
+            behind: 0,
+            fetch_error,
+        });
+    }
+    let behind = if has_local {
+        git.commits_between(base, &remote).await?
+    } else {
+        0
+    };
+    Ok(BaseStatus {
+        upstream: remote,
+        behind,
+        fetch_error,
+    })
+}
+
+/// Rebase the current branch onto the latest `base`. Returns a note when the
+/// branch moved, `None` when it was already current.
+pub async fn rebase_onto_base(git: &GitManager, base: &str) -> Result<Option<String>> {
+    let status = check_base(git, base).await?;
+    let behind = git.commits_between("HEAD", &status.upstream).await?;
+    if behind == 0 {
+        return Ok(None);
+    }
+    git.rebase_onto(&status.upstream).await.with_context(|| {
+        format!(
+            "Rebase onto {} hit conflicts and was aborted",
+            status.upstream
+        )
+    })?;
+    Ok(Some(format!(
+        "Rebased onto {} ({behind} new commit(s))",
+        status.upstream
+    )))
+}
+
+fn ask(current: &str, branch: &str) -> Result<&'static str> {
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!(
+            "❓  {current} has uncommitted changes. Before switching to {branch}: \
+             [s]tash, [c]ommit, or [A]bort? "
+        );
+        std::io::stdout().flush()?;
+        let answer = lines.next().transpose()?.unwrap_or_default();
+        match answer.trim().to_lowercase().as_str() {
+            "s" | "stash" => return Ok("stash"),
+            "c" | "commit" => return Ok("commit"),
+            "" | "a" | "abort" => return Ok("abort"),
+            _ => continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .output()
+            .expect("git should run");
+        assert!(output.status.success(), "git {} failed", args.join(" "));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn dirty_repo() -> tempfile::TempDir {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q", "-b", "main"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::write(root.join("app.txt"), "v1\n").expect("seed app");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+        fs::write(root.join("app.txt"), "user edit\n").expect("user edit");
+        dir
+    }
+
+    #[tokio::test]
+    async fn dirty_tree_is_stashed_committed_or_aborts() {
+        let dir = dirty_repo();
+        let manager = GitManager::new(dir.path());
+        let err = checkout_branch(&manager, "ralph/prd", None, "abort")
+            .await
+            .expect_err("abort leaves the tree alone");
+        assert!(err.to_string().contains("--on-dirty"), "{err}");
+        assert_eq!(
+            git(dir.path(), &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "main"
+        );
+
+        let note = checkout_branch(&manager, "ralph/prd", None, "stash")
+            .await
+            .expect("stash and switch");
+        assert!(note.expect("note").contains("Stashed"));
+        assert_eq!(
+            git(dir.path(), &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "ralph/prd"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("app.txt")).expect("read app"),
+            "v1\n"
+        );
+        assert!(git(dir.path(), &["stash", "list"]).contains("ralph/prd"));
+
+        let dir = dirty_repo();
+        let manager = GitManager::new(dir.path());
+        checkout_branch(&manager, "ralph/prd", None, "commit")
+            .await
+            .expect("commit and switch");
+        assert_eq!(
+            git(dir.path(), &["log", "-1", "--format=%s", "main"]),
+            "WIP before ralph run on ralph/prd"
+        );
+        assert!(!manager.has_tracked_changes().await.expect("status"));
+    }
+
+    #[tokio::test]
+    async fn staying_on_the_branch_keeps_changes() {
+        let dir = dirty_repo();
+        let manager = GitManager::new(dir.path());
+        let note = checkout_branch(&manager, "main", None, "abort")
+            .await
+            .expect("no switch needed");
+        assert!(note.is_none());
+
+        // Untracked files don't conflict with the switch and are left alone.
+        git(dir.path(), &["checkout", "-q", "--", "app.txt"]);
+        fs::write(dir.path().join("notes.md"), "scratch\n").expect("untracked");
+        checkout_branch(&manager, "ralph/prd", None, "abort")
+            .await
+            .expect("untracked files don't block");
+        assert!(dir.path().join("notes.md").exists());
+    }
+
+    #[tokio::test]
+    async fn branch_starts_at_base_and_rebases_onto_origin() {
+        let origin = dirty_repo();
+        git(origin.path(), &["commit", "-q", "-am", "v2"]);
+        let clone = tempdir().expect("create tempdir");
+        let root = clone.path();
+        git(
+            root,
+            &["clone", "-q", &origin.path().to_string_lossy(), "."],
+        );
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        git(root, &["checkout", "-q", "-b", "elsewhere", "HEAD~1"]);
+        let manager = GitManager::new(root);
+
+        checkout_branch(&manager, "ralph/prd", Some("main"), "abort")
+            .await
+            .expect("branch from base");
+        assert_eq!(
+            git(root, &["rev-parse", "HEAD"]),
+            git(root, &["rev-parse", "main"])
+        );
+        fs::write(root.join("new.txt"), "task\n").expect("task output");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "T1"]);
+
+        // Someone pushes to main overnight.
+        fs::write(origin.path().join("other.txt"), "upstream\n").expect("upstream edit");
+        git(origin.path(), &["add", "-A"]);
+        git(origin.path(), &["commit", "-q", "-m", "v3"]);
+
+        let status = check_base(&manager, "main").await.expect("check base");
+        assert_eq!(status.upstream, "origin/main");
+        assert_eq!(status.behind, 1);
+        assert!(status.fetch_error.is_none());
+
+        let note = rebase_onto_base(&manager, "main")
+            .await
+            .expect("rebase")
+            .expect("branch moved");
+        assert!(note.contains("origin/main"), "{note}");
+        assert!(root.join("other.txt").exists());
+        assert_eq!(git(root, &["log", "-1", "--format=%s"]), "T1");
+        assert!(rebase_onto_base(&manager, "main")
+            .await
+            .expect("second rebase")
+            .is_none());
+    }
+}
@@ -0,0 +1,217 @@
+//! In git, a checkpoint of the working tree taken before each iteration, so
+//! an iteration that errors, times out, or doesn't finish its task can be
+//! rolled back and the next attempt starts clean (`--no-rollback` turns this off), and a completed
+//! one can report the files it changed.
+//!
+//! The checkpoint is `git stash create`: a dangling commit of the tracked
+//! files' uncommitted state, made without touching the tree. Untracked files
+//! aren't in it, so only their names are kept; rolling back removes the ones
+//! the iteration added and leaves the rest alone. Ralph's own state is never
+//! rolled back.
+
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+use super::inventory::{ChangeKind, FileChange};
+use crate::git::GitManager;
+
+pub struct Checkpoint {
+    head: String,
+    /// The stash commit, or `None` when the tree matched HEAD.
+    stash: Option<String>,
+    untracked: HashSet<String>,
+}
+
+/// What a rollback changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Rollback {
+    pub restored: usize,
+    pub removed: usize,
+}
+
+impl Checkpoint {
+    pub async fn take(git: &GitManager) -> Result<Self> {
+        let head = git.head_commit().await?;
+        let stash = git.stash_create().await?;
+        let untracked = untracked(git).await?;
+        Ok(Self {
+            head,
+            stash,
+            untracked,
+        })
+    }
+
+    /// The commit the tree is compared against: the stash, or HEAD when the
+    /// tree was clean.
+    pub fn base(&self) -> &str {
+        self.stash.as_deref().unwrap_or(&self.head)
+    }
+
+    /// Put the working tree back the way it was when the checkpoint was
+    /// taken. Refused when the agent committed in between, since undoing a
+    /// commit is not Ralph's call.
+    pub async fn rollback(&self, git: &GitManager) -> Result<Rollback> {
+        if git.head_commit().await? != self.head {
+            bail!("the agent made commits since the checkpoint");
+        }
+        let base = self.base();
+        let mut paths = git.paths_changed_since(base).await?;
+        paths.extend(
+            untracked(git)
+                .await?
+                .into_iter()
+                .filter(|path| !self.untracked.contains(path)),
+        );
+
+        let mut rollback = Rollback::default();
+        for path in paths.iter().filter(|p| !p.starts_with(".ralph")) {
+            if git.restore_path_from(base, path).await? {
+                rollback.removed += 1;
+            } else {
+                rollback.restored += 1;
+            }
+        }
+        Ok(rollback)
+    }
+
+    /// The files the iteration changed: the tree against the checkpoint,
+    /// plus untracked files that weren't there before. Commits the agent
+    /// made count too. Ralph's own state is left out.
+    pub async fn changes(&self, git: &GitManager) -> Result<Vec<FileChange>> {
+        let mut changes: Vec<FileChange> = git
+            .name_status_since(self.base())
+            .await?
+            .into_iter()
+            .map(|(status, path)| FileChange {
+                path,
+                kind: match status {
+                    'A' => ChangeKind::Added,
+                    'D' => ChangeKind::Deleted,
+                    _ => ChangeKind::Modified,
+                },
+            })
+            .collect();
+        changes.extend(
+            untracked(git)
+                .await?
+                .into_iter()
+                .filter(|path| !self.untracked.contains(path))
+                .map(|path| FileChange {
+                    path,
+                    kind: ChangeKind::Added,
+                }),
+        );
+        changes.retain(|c| !c.path.starts_with(".ralph"));
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes.dedup_by(|a, b| a.path == b.path);
+        Ok(changes)
+    }
+}
+
+async fn untracked(git: &GitManager) -> Result<HashSet<String>> {
+    Ok(git
+        .changed_paths()
+        .await?
+        .into_iter()
+        .filter(|c| c.untracked)
+        .map(|c| c.path)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[tokio::test]
+    async fn rollback_restores_the_tree_as_it_was_before_the_iteration() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::write(root.join("lib.rs"), "v1\n").expect("seed lib");
+        fs::write(root.join("old.rs"), "old\n").expect("seed old");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+
+        // Work in progress from an earlier attempt survives the rollback.
+        fs::write(root.join("lib.rs"), "v2\n").expect("earlier attempt");
+        fs::write(root.join("notes.txt"), "mine\n").expect("user file");
+        let manager = GitManager::new(root);
+        let checkpoint = Checkpoint::take(&manager).await.expect("checkpoint");
+
+        fs::write(root.join("lib.rs"), "broken\n").expect("agent edit");
+        fs::remove_file(root.join("old.rs")).expect("agent delete");
+        fs::write(root.join("half.rs"), "half\n").expect("agent file");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state file");
+
+        let rollback = checkpoint.rollback(&manager).await.expect("rollback");
+        assert_eq!(
+            rollback,
+            Rollback {
+                restored: 2,
+                removed: 1
+            }
+        );
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "v2\n");
+        assert_eq!(fs::read_to_string(root.join("old.rs")).unwrap(), "old\n");
+        assert!(!root.join("half.rs").exists());
+        assert!(root.join("notes.txt").exists());
+        assert!(root.join(".ralph/progress.md").exists());
+
+        fs::write(root.join("lib.rs"), "v3\n").expect("agent edit");
+        git(root, &["commit", "-q", "-am", "agent commit"]);
+        assert!(checkpoint.rollback(&manager).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn changes_list_what_the_iteration_touched() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::write(root.join("lib.rs"), "v1\n").expect("seed lib");
+        fs::write(root.join("old.rs"), "old\n").expect("seed old");
+        fs::write(root.join("same.rs"), "same\n").expect("seed same");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+
+        // Left over from before the iteration: not the iteration's changes.
+        fs::write(root.join("same.rs"), "earlier\n").expect("earlier edit");
+        fs::write(root.join("notes.txt"), "mine\n").expect("user file");
+        let manager = GitManager::new(root);
+        let checkpoint = Checkpoint::take(&manager).await.expect("checkpoint");
+
+        fs::write(root.join("lib.rs"), "v2\n").expect("agent edit");
+        fs::remove_file(root.join("old.rs")).expect("agent delete");
+        fs::write(root.join("new.rs"), "new\n").expect("agent file");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state file");
+        git(root, &["add", "lib.rs"]);
+        git(root, &["commit", "-q", "-m", "agent commit"]);
+
+        let changes: Vec<String> = checkpoint
+            .changes(&manager)
+            .await
+            .expect("changes")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(changes, ["M lib.rs", "A new.rs", "D old.rs"]);
+    }
+}
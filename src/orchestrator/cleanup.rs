@@ -0,0 +1,156 @@
+//! `--cleanup-pass`: once every task is complete, one more agent run removes
+//! dead code, leftover debug prints, and scratch files the run introduced.
+//! The result is checked with `--verify-cmd` (when set) before it is
+//! committed; a cleanup that fails either way is undone from a workdir
+//! snapshot, which also covers uncommitted work and projects without git.
+
+use anyhow::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::tree_snapshot::TreeSnapshot;
+use crate::agents::Agent;
+use crate::cli::RunArgs;
+use crate::git::GitManager;
+use crate::output::Pipeline;
+use crate::state::StateManager;
+
+const CLEANUP_PROMPT: &str = r#"You are an expert software engineer. Every task of a PRD has just been implemented in this repository. Your job now is a final cleanup of that work — not new features.
+
+## Files changed during the run
+
+{changed_files}
+
+{scope}{scratch}## Instructions
+
+1. Remove dead code, unused imports, and commented-out code introduced during the run.
+2. Remove leftover debug output (print/console.log/dbg! statements added for debugging).
+3. Delete scratch files the run left in the repository: throwaway test scripts, notes, temporary data.
+4. Do not change behavior, public interfaces, or tests that pass. When unsure whether something is used, leave it.
+5. If a test suite exists, run it and make sure it still passes.
+6. Finish with a short summary of what you removed.
+"#;
+
+/// Run the cleanup pass and commit or revert its result.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    agent: &dyn Agent,
+    args: &RunArgs,
+    git: &GitManager,
+    state: &StateManager,
+    workdir: &Path,
+    output: Arc<Pipeline>,
+    iteration: u32,
+    run_base: Option<&str>,
+    scope: &str,
+) -> Result<()> {
+    println!("\n━━━ Cleanup pass ━━━");
+    let snapshot = TreeSnapshot::new(workdir, state.snapshot_dir());
+    if let Err(e) = snapshot.capture() {
+        println!("    ⚠️   Skipping the cleanup pass — could not snapshot the workdir: {e:#}");
+        state.append_progress(&format!("Cleanup pass skipped — snapshot failed: {e}"))?;
+        return Ok(());
+    }
+
+    let changed_files = match run_base {
+        Some(base) => git
+            .diff_stat_range(&format!("{base}..HEAD"))
+            .await
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let scratch_dir = tempfile::Builder::new()
+        .prefix("ralph-cleanup-")
+        .tempdir()
+        .ok();
+    let env: Vec<(String, String)> = scratch_dir
+        .iter()
+        .map(|dir| ("RALPH_TMPDIR".to_string(), dir.path().display().to_string()))
+        .collect();
+    let prompt = build_prompt(
+        &changed_files,
+        scope,
+        &super::scratch_section(scratch_dir.as_ref().map(|d| d.path())),
+    );
+
+    let log_path = state.log_path(iteration, "cleanup");
+    println!("    Log: {}", log_path.display());
+    let (timeout, stall_timeout) = super::limits_for(args, agent);
+    let result = super::run_iteration(
+        agent,
+        &prompt,
+        workdir,
+        &log_path,
+        timeout,
+        stall_timeout,
+        args.kill_grace,
+        args.stop_grace,
+        &env,
+        args.verbose,
+        args.loop_status.clone(),
+        args.cancel_flag.clone(),
+        output,
+        Some(&state.lock_file),
+    )
+    .await;
+    drop(scratch_dir);
+
+    let failure = match result {
+        Err(e) => Some(format!("agent failed: {e}")),
+        Ok(_) => match &args.verify_cmd {
+            Some(cmd) => {
+                match super::completion::run_verify_cmd(cmd, workdir, timeout).await {
+                    Ok(true) => None,
+                    Ok(false) => Some(format!("`{cmd}` failed afterwards")),
+                    Err(e) => Some(format!("{e}")),
+                }
+            }
+            None => None,
+        },
+    };
+
+    match failure {
+        None => {
+            println!("    🧹  Cleanup pass done");
+            state.append_progress(&format!("**Iteration {iteration}** — cleanup pass done."))?;
+            if !args.no_branch && !agent.capabilities().manages_own_commits {
+                super::auto_commit(git, "chore: cleanup pass (ralph)", false).await;
+            }
+        }
+        Some(reason) => {
+            println!("    ⚠️   Cleanup pass discarded — {reason}");
+            if let Err(e) = snapshot.restore() {
+                eprintln!("    ⚠️   Could not undo the cleanup: {e:#}");
+            }
+            state.append_progress(&format!(
+                "**Iteration {iteration}** — cleanup pass discarded: {reason}"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn build_prompt(changed_files: &str, scope: &str, scratch: &str) -> String {
+    let changed_files = match changed_files.trim() {
+        "" => "(not available — check the files you know the run touched)",
+        stat => stat,
+    };
+    CLEANUP_PROMPT
+        .replace("{changed_files}", &format!("```\n{changed_files}\n```"))
+        .replace("{scope}", scope)
+        .replace("{scratch}", scratch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_lists_the_run_changes() {
+        let prompt = build_prompt(" src/a.rs | 4 ++--\n", "", "");
+        assert!(prompt.contains("```\nsrc/a.rs | 4 ++--\n```"), "{prompt}");
+        assert!(!prompt.contains("{scope}"));
+        assert!(build_prompt(" ", "", "").contains("(not available"));
+    }
+}
@@ -0,0 +1,766 @@
+//! Completion detection: deciding whether an iteration finished its task.
+//!
+//! Each [`CompletionDetector`] looks at the iteration's output and refines an
+//! [`Assessment`] handed to it by the previous detector in the stack. The stack
+//! is selected with `--completion` / `[completion] detectors` and order is
+//! significant — e.g. `confidence` only gates claims made by detectors before it.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+/// Detector names accepted by `--completion`.
+pub const DETECTOR_NAMES: &[&str] = &[
+    "token",
+    "json",
+    "exit_code",
+    "confidence",
+    "tasks_edit",
+    "verify",
+];
+
+/// The stack used without `--completion`, in order.
+const DEFAULT_STACK: &[&str] = &["token", "confidence", "tasks_edit", "verify"];
+
+// ── Detector trait ────────────────────────────────────────────────────────────
+
+pub type DetectFuture<'a> = Pin<Box<dyn Future<Output = Assessment> + Send + 'a>>;
+
+/// Everything a detector may inspect about one finished iteration.
+pub struct DetectionContext<'a> {
+    pub stdout: &'a str,
+    /// The agent's exit code; `None` if a signal ended it.
+    pub exit_code: Option<i32>,
+    /// Serialized tasks before and after the agent ran.
+    pub tasks_before: &'a str,
+    pub tasks_after: &'a str,
+    pub workdir: &'a Path,
+    pub timeout_secs: u64,
+    /// The task's own `verify` command, which every claim on it must pass.
+    pub task_verify: Option<&'a str>,
+}
+
+/// Running conclusion threaded through the detector stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assessment {
+    /// Nothing so far suggests the task is done.
+    NoClaim,
+    /// The agent claims the task is done.
+    Claimed { confidence: Option<f64> },
+    /// A claim was made but is not trusted on its own.
+    Doubtful { confidence: Option<f64> },
+    /// A claim was independently confirmed; later detectors leave it alone.
+    Verified,
+    /// The claim was checked and refuted.
+    Rejected(String),
+    /// The claim failed its verification command.
+    Unverified(FailedCheck),
+}
+
+/// A verification command that failed, with what it printed, so the next
+/// attempt at the task can see what to fix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedCheck {
+    pub reason: String,
+    /// The last [`CHECK_OUTPUT_LINES`] lines of its stdout and stderr.
+    pub output: String,
+}
+
+pub trait CompletionDetector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Refine `current` using this detector's evidence.
+    fn assess<'a>(&'a self, ctx: &'a DetectionContext<'a>, current: Assessment)
+        -> DetectFuture<'a>;
+}
+
+/// How an iteration that ran to completion is judged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Complete,
+    /// Claimed complete but doubtful, with nothing left to verify it.
+    NeedsReview(Option<f64>),
+    Incomplete(String),
+    /// Claimed complete, but the verification command failed.
+    Unverified(FailedCheck),
+}
+
+/// Run every detector in order and map the final assessment to a verdict.
+pub async fn evaluate(
+    detectors: &[Box<dyn CompletionDetector>],
+    ctx: &DetectionContext<'_>,
+) -> Verdict {
+    let mut assessment = Assessment::NoClaim;
+    for detector in detectors {
+        assessment = detector.assess(ctx, assessment).await;
+    }
+    // A task's own command gates every claim on it, whatever the stack.
+    if let (Some(cmd), Assessment::Claimed { .. } | Assessment::Doubtful { .. }) =
+        (ctx.task_verify, &assessment)
+    {
+        assessment = check_claim("Claim", cmd, ctx).await;
+    }
+
+    match assessment {
+        Assessment::NoClaim => Verdict::Incomplete("Task not completed this iteration".to_string()),
+        Assessment::Claimed { .. } | Assessment::Verified => Verdict::Complete,
+        Assessment::Doubtful { confidence } => Verdict::NeedsReview(confidence),
+        Assessment::Rejected(reason) => Verdict::Incomplete(reason),
+        Assessment::Unverified(failed) => Verdict::Unverified(failed),
+    }
+}
+
+/// Build the detector stack from names (empty = default stack). `token` is
+/// the word the `token` detector looks for between `<promise>` tags.
+pub fn build_detectors(
+    names: &[String],
+    min_confidence: f64,
+    verify_cmd: Option<&str>,
+    token: &str,
+) -> Result<Vec<Box<dyn CompletionDetector>>> {
+    check_token(token)?;
+    let names: Vec<&str> = if names.is_empty() {
+        DEFAULT_STACK
+            .iter()
+            .copied()
+            .filter(|n| *n != "verify" || verify_cmd.is_some())
+            .collect()
+    } else {
+        names.iter().map(|n| n.trim()).collect()
+    };
+
+    let mut detectors: Vec<Box<dyn CompletionDetector>> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let detector: Box<dyn CompletionDetector> = match *name {
+            "token" => Box::new(TokenDetector {
+                word: token.to_string(),
+            }),
+            "json" => Box::new(JsonDetector),
+            "exit_code" => Box::new(ExitCodeDetector),
+            "tasks_edit" => Box::new(TasksEditDetector),
+            "confidence" => Box::new(ConfidenceDetector {
+                threshold: min_confidence,
+            }),
+            "verify" => {
+                let cmd =
+                    verify_cmd.context("The `verify` completion detector requires --verify-cmd")?;
+                Box::new(VerifyDetector {
+                    cmd: cmd.to_string(),
+                    // Behind a confidence gate, only doubtful claims are checked.
+                    check_all_claims: !names[..i].contains(&"confidence"),
+                })
+            }
+            other => anyhow::bail!(
+                "Unknown completion detector '{other}' (expected one of: {})",
+                DETECTOR_NAMES.join(", ")
+            ),
+        };
+        detectors.push(detector);
+    }
+
+    if detectors.is_empty() {
+        anyhow::bail!("At least one completion detector is required");
+    }
+    Ok(detectors)
+}
+
+/// `--completion-token` must fit between `<promise>` tags.
+fn check_token(token: &str) -> Result<()> {
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        anyhow::bail!("--completion-token must be letters, digits, or `_`, got '{token}'");
+    }
+    Ok(())
+}
+
+/// What the prompt tells the agent to end its output with once the task is
+/// done: the `<promise>` token, or the JSON status line when the stack reads
+/// that instead.
+pub fn completion_signal(names: &[String], token: &str) -> String {
+    let reads = |name: &str| {
+        names.is_empty() && DEFAULT_STACK.contains(&name) || names.iter().any(|n| n.trim() == name)
+    };
+    if reads("json") && !reads("token") {
+        r#"{"status": "complete", "confidence": 0.9}"#.to_string()
+    } else {
+        format!(r#"<promise confidence="0.9">{token}</promise>"#)
+    }
+}
+
+// ── Detectors ─────────────────────────────────────────────────────────────────
+
+/// Claims completion when the final lines of stdout contain
+/// `<promise …>COMPLETE</promise>` (or the `--completion-token` word).
+struct TokenDetector {
+    word: String,
+}
+
+impl CompletionDetector for TokenDetector {
+    fn name(&self) -> &'static str {
+        "token"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        let next = match (
+            current,
+            parse_promise(final_portion(ctx.stdout), &self.word),
+        ) {
+            (Assessment::NoClaim, Some(p)) => Assessment::Claimed {
+                confidence: p.confidence,
+            },
+            (current, _) => current,
+        };
+        Box::pin(std::future::ready(next))
+    }
+}
+
+/// Claims completion when one of the final lines of stdout is a JSON object
+/// with `"status": "complete"`, e.g. `{"status": "complete", "confidence": 0.9}`.
+struct JsonDetector;
+
+impl CompletionDetector for JsonDetector {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        let next = match (current, parse_json_status(final_portion(ctx.stdout))) {
+            (Assessment::NoClaim, Some(p)) => Assessment::Claimed {
+                confidence: p.confidence,
+            },
+            (current, _) => current,
+        };
+        Box::pin(std::future::ready(next))
+    }
+}
+
+/// Claims completion when the agent exited 0, for agents (usually
+/// `[agents.<name>] command` scripts) that report the outcome that way.
+struct ExitCodeDetector;
+
+impl CompletionDetector for ExitCodeDetector {
+    fn name(&self) -> &'static str {
+        "exit_code"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        let next = match current {
+            Assessment::NoClaim if ctx.exit_code == Some(0) => {
+                Assessment::Claimed { confidence: None }
+            }
+            current => current,
+        };
+        Box::pin(std::future::ready(next))
+    }
+}
+
+/// Claims completion when the agent edited tasks.json itself.
+struct TasksEditDetector;
+
+impl CompletionDetector for TasksEditDetector {
+    fn name(&self) -> &'static str {
+        "tasks_edit"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        let next = match current {
+            Assessment::NoClaim if ctx.tasks_before != ctx.tasks_after => {
+                Assessment::Claimed { confidence: None }
+            }
+            current => current,
+        };
+        Box::pin(std::future::ready(next))
+    }
+}
+
+/// Marks claims below the threshold as doubtful.
+struct ConfidenceDetector {
+    threshold: f64,
+}
+
+impl CompletionDetector for ConfidenceDetector {
+    fn name(&self) -> &'static str {
+        "confidence"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        _ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        let next = match current {
+            Assessment::Claimed { confidence } if !meets_threshold(confidence, self.threshold) => {
+                Assessment::Doubtful { confidence }
+            }
+            current => current,
+        };
+        Box::pin(std::future::ready(next))
+    }
+}
+
+/// Settles doubtful (or, without a confidence gate, all) claims by running a
+/// shell command. Tasks with a `verify` command of their own are left to it.
+struct VerifyDetector {
+    cmd: String,
+    check_all_claims: bool,
+}
+
+impl CompletionDetector for VerifyDetector {
+    fn name(&self) -> &'static str {
+        "verify"
+    }
+
+    fn assess<'a>(
+        &'a self,
+        ctx: &'a DetectionContext<'a>,
+        current: Assessment,
+    ) -> DetectFuture<'a> {
+        Box::pin(async move {
+            let label = match &current {
+                Assessment::Doubtful { confidence } => {
+                    format!("Low-confidence claim ({})", format_confidence(*confidence))
+                }
+                Assessment::Claimed { .. } if self.check_all_claims => "Claim".to_string(),
+                _ => return current,
+            };
+            if ctx.task_verify.is_some() {
+                return current;
+            }
+            check_claim(&label, &self.cmd, ctx).await
+        })
+    }
+}
+
+async fn check_claim(label: &str, cmd: &str, ctx: &DetectionContext<'_>) -> Assessment {
+    match run_check(cmd, ctx.workdir, ctx.timeout_secs).await {
+        Ok(check) if check.passed => Assessment::Verified,
+        Ok(check) => Assessment::Unverified(FailedCheck {
+            reason: format!("{label} failed verification: {cmd}"),
+            output: check.output,
+        }),
+        Err(e) => Assessment::Rejected(format!("{e:#}")),
+    }
+}
+
+// ── Helpers ───────────────────────────────────────────────────────────────────
+
+/// A completion claim found in agent stdout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Promise {
+    /// Self-reported confidence in `0.0..=1.0`, if the agent supplied one.
+    pub confidence: Option<f64>,
+}
+
+/// Only this many trailing non-blank lines of output may carry the token, so a
+/// token the agent merely quotes (from a file or an echoed prompt) earlier in
+/// its output is not taken as a claim.
+const TOKEN_TAIL_LINES: usize = 20;
+
+/// The last [`TOKEN_TAIL_LINES`] non-blank lines of `stdout`.
+pub fn final_portion(stdout: &str) -> &str {
+    let mut remaining = TOKEN_TAIL_LINES;
+    let mut end = stdout.len();
+    while let Some(newline) = stdout[..end].rfind('\n') {
+        if !stdout[newline + 1..end].trim().is_empty() {
+            remaining -= 1;
+            if remaining == 0 {
+                return &stdout[newline + 1..];
+            }
+        }
+        end = newline;
+    }
+    stdout
+}
+
+/// Find the last `<promise …>{word}</promise>` token in `stdout`.
+///
+/// Accepts the bare form as well as `<promise confidence=0.9>` and
+/// `<promise confidence="0.9">`. Percentages (`confidence=85`) are scaled down.
+pub fn parse_promise(stdout: &str, word: &str) -> Option<Promise> {
+    let closing = format!("{word}</promise>");
+    let mut found = None;
+    let mut rest = stdout;
+
+    while let Some(start) = rest.find("<promise") {
+        let after = &rest[start + "<promise".len()..];
+        rest = after;
+
+        let Some(close) = after.find('>') else {
+            break;
+        };
+        let attrs = &after[..close];
+        // `<promises>` or similar is not our token.
+        if !attrs.is_empty() && !attrs.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if !after[close + 1..].starts_with(&closing) {
+            continue;
+        }
+
+        found = Some(Promise {
+            confidence: parse_confidence(attrs),
+        });
+    }
+
+    found
+}
+
+/// Find the last line of `stdout` that is a JSON object with
+/// `"status": "complete"`, taking its `confidence` if it has one.
+pub fn parse_json_status(stdout: &str) -> Option<Promise> {
+    stdout.lines().rev().find_map(|line| {
+        let value: serde_json::Value = serde_json::from_str(line.trim()).ok()?;
+        let status = value.get("status")?.as_str()?;
+        status.eq_ignore_ascii_case("complete").then(|| Promise {
+            confidence: value
+                .get("confidence")
+                .and_then(serde_json::Value::as_f64)
+                .and_then(scale_confidence),
+        })
+    })
+}
+
+fn parse_confidence(attrs: &str) -> Option<f64> {
+    let value = attrs
+        .split_whitespace()
+        .find_map(|a| a.strip_prefix("confidence="))?;
+    scale_confidence(value.trim_matches(|c| c == '"' || c == '\'').parse().ok()?)
+}
+
+fn scale_confidence(value: f64) -> Option<f64> {
+    let value = if value > 1.0 && value <= 100.0 {
+        value / 100.0
+    } else {
+        value
+    };
+    (0.0..=1.0).contains(&value).then_some(value)
+}
+
+/// Whether a claim with `confidence` may be auto-accepted at `threshold`.
+///
+/// A threshold of `0` disables the gate. Once a threshold is set, a claim
+/// without a score is treated as unscored and does not pass.
+pub fn meets_threshold(confidence: Option<f64>, threshold: f64) -> bool {
+    if threshold <= 0.0 {
+        return true;
+    }
+    confidence.is_some_and(|c| c >= threshold)
+}
+
+/// Render a confidence for logs, e.g. `0.62` or `unscored`.
+pub fn format_confidence(confidence: Option<f64>) -> String {
+    confidence
+        .map(|c| format!("{c:.2}"))
+        .unwrap_or_else(|| "unscored".to_string())
+}
+
+/// Run the verification command through `sh -c` in `workdir`.
+/// Returns `Ok(true)` when it exits 0 within `timeout_secs`.
+pub async fn run_verify_cmd(cmd: &str, workdir: &Path, timeout_secs: u64) -> Result<bool> {
+    Ok(run_check(cmd, workdir, timeout_secs).await?.passed)
+}
+
+/// How much of a failed check's output the next attempt gets to see.
+pub const CHECK_OUTPUT_LINES: usize = 40;
+
+/// A finished verification command.
+pub struct Check {
+    pub passed: bool,
+    /// The tail of its stdout followed by stderr.
+    pub output: String,
+}
+
+/// [`run_verify_cmd`], keeping the end of what the command printed.
+pub async fn run_check(cmd: &str, workdir: &Path, timeout_secs: u64) -> Result<Check> {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .current_dir(workdir)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+
+    match timeout(Duration::from_secs(timeout_secs), child).await {
+        Ok(output) => {
+            let output = output.with_context(|| format!("Failed to run verify command: {cmd}"))?;
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let lines: Vec<&str> = text.trim_end().lines().collect();
+            Ok(Check {
+                passed: output.status.success(),
+                output: lines[lines.len().saturating_sub(CHECK_OUTPUT_LINES)..].join("\n"),
+            })
+        }
+        Err(_) => Ok(Check {
+            passed: false,
+            output: format!("(timed out after {timeout_secs}s)"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const DEFAULT_TOKEN: &str = "COMPLETE";
+
+    fn ctx<'a>(stdout: &'a str, tasks_after: &'a str, workdir: &'a Path) -> DetectionContext<'a> {
+        DetectionContext {
+            stdout,
+            tasks_before: "[]",
+            tasks_after,
+            exit_code: Some(0),
+            workdir,
+            timeout_secs: 5,
+            task_verify: None,
+        }
+    }
+
+    #[test]
+    fn parses_bare_and_scored_promises() {
+        assert_eq!(
+            parse_promise("done\n<promise>COMPLETE</promise>\n", DEFAULT_TOKEN),
+            Some(Promise { confidence: None })
+        );
+        assert_eq!(
+            parse_promise("<promise confidence=0.9>COMPLETE</promise>", DEFAULT_TOKEN),
+            Some(Promise {
+                confidence: Some(0.9)
+            })
+        );
+        assert_eq!(
+            parse_promise(
+                r#"<promise confidence="85">COMPLETE</promise>"#,
+                DEFAULT_TOKEN
+            ),
+            Some(Promise {
+                confidence: Some(0.85)
+            })
+        );
+        assert_eq!(parse_promise("no token here", DEFAULT_TOKEN), None);
+        assert_eq!(
+            parse_promise("<promise>INCOMPLETE</promise>", DEFAULT_TOKEN),
+            None
+        );
+    }
+
+    #[test]
+    fn last_promise_wins_and_bad_scores_are_dropped() {
+        let out = "<promise confidence=0.2>COMPLETE</promise>\n\
+                   <promise confidence=oops>COMPLETE</promise>";
+        assert_eq!(
+            parse_promise(out, DEFAULT_TOKEN),
+            Some(Promise { confidence: None })
+        );
+    }
+
+    #[tokio::test]
+    async fn tokens_before_the_final_lines_are_not_claims() {
+        let dir = tempdir().expect("create tempdir");
+        let quoted = format!(
+            "Reading README.md:\n<promise>COMPLETE</promise>\n{}",
+            "still working\n\n".repeat(TOKEN_TAIL_LINES)
+        );
+        let detectors =
+            build_detectors(&["token".to_string()], 0.0, None, DEFAULT_TOKEN).expect("build");
+        assert!(matches!(
+            evaluate(&detectors, &ctx(&quoted, "[]", dir.path())).await,
+            Verdict::Incomplete(_)
+        ));
+
+        let claimed = format!("{quoted}<promise>COMPLETE</promise>\ntokens used: 120\n");
+        assert_eq!(
+            evaluate(&detectors, &ctx(&claimed, "[]", dir.path())).await,
+            Verdict::Complete
+        );
+        assert_eq!(final_portion("a\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn threshold_gate() {
+        assert!(meets_threshold(None, 0.0));
+        assert!(meets_threshold(Some(0.9), 0.8));
+        assert!(!meets_threshold(Some(0.5), 0.8));
+        assert!(!meets_threshold(None, 0.8));
+    }
+
+    #[tokio::test]
+    async fn verify_cmd_reports_exit_status() {
+        let dir = tempdir().expect("create tempdir");
+        assert!(run_verify_cmd("true", dir.path(), 5).await.expect("run"));
+        assert!(!run_verify_cmd("exit 3", dir.path(), 5).await.expect("run"));
+        assert!(!run_verify_cmd("sleep 5", dir.path(), 1).await.expect("run"));
+    }
+
+    #[tokio::test]
+    async fn default_stack_accepts_token_or_tasks_edit() {
+        let dir = tempdir().expect("create tempdir");
+        let stack = build_detectors(&[], 0.0, None, DEFAULT_TOKEN).expect("build stack");
+        let names: Vec<_> = stack.iter().map(|d| d.name()).collect();
+        assert_eq!(names, ["token", "confidence", "tasks_edit"]);
+
+        let token = ctx("<promise>COMPLETE</promise>", "[]", dir.path());
+        assert_eq!(evaluate(&stack, &token).await, Verdict::Complete);
+
+        let edited = ctx("done", "[{}]", dir.path());
+        assert_eq!(evaluate(&stack, &edited).await, Verdict::Complete);
+
+        let nothing = ctx("still working", "[]", dir.path());
+        assert!(matches!(
+            evaluate(&stack, &nothing).await,
+            Verdict::Incomplete(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn confidence_gate_defers_to_verify_or_review() {
+        let dir = tempdir().expect("create tempdir");
+        let low = ctx(
+            "<promise confidence=0.3>COMPLETE</promise>",
+            "[]",
+            dir.path(),
+        );
+
+        let review = build_detectors(&[], 0.8, None, DEFAULT_TOKEN).expect("build stack");
+        assert_eq!(
+            evaluate(&review, &low).await,
+            Verdict::NeedsReview(Some(0.3))
+        );
+
+        let verified = build_detectors(&[], 0.8, Some("true"), DEFAULT_TOKEN).expect("build stack");
+        assert_eq!(evaluate(&verified, &low).await, Verdict::Complete);
+
+        let refuted = build_detectors(&[], 0.8, Some("false"), DEFAULT_TOKEN).expect("build stack");
+        assert!(matches!(
+            evaluate(&refuted, &low).await,
+            Verdict::Unverified(failed) if failed.reason.contains("failed verification")
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_without_confidence_gate_checks_every_claim() {
+        let dir = tempdir().expect("create tempdir");
+        let names = vec!["token".to_string(), "verify".to_string()];
+        let stack =
+            build_detectors(&names, 0.0, Some("false"), DEFAULT_TOKEN).expect("build stack");
+        let claim = ctx(
+            "<promise confidence=1.0>COMPLETE</promise>",
+            "[]",
+            dir.path(),
+        );
+        assert!(matches!(
+            evaluate(&stack, &claim).await,
+            Verdict::Unverified(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn task_verify_gates_every_claim_and_keeps_its_output() {
+        let dir = tempdir().expect("create tempdir");
+        // Without a confidence gate, --verify-cmd would refute this claim.
+        let names = vec!["token".to_string(), "verify".to_string()];
+        let stack =
+            build_detectors(&names, 0.0, Some("false"), DEFAULT_TOKEN).expect("build stack");
+        let mut claim = ctx(
+            "<promise confidence=1.0>COMPLETE</promise>",
+            "[]",
+            dir.path(),
+        );
+        claim.task_verify = Some("true");
+        assert_eq!(evaluate(&stack, &claim).await, Verdict::Complete);
+
+        let stack =
+            build_detectors(&["token".to_string()], 0.0, None, DEFAULT_TOKEN).expect("build stack");
+        claim.task_verify = Some("seq 1 100; echo 'test login ... FAILED' >&2; exit 1");
+        let Verdict::Unverified(failed) = evaluate(&stack, &claim).await else {
+            panic!("expected a failed check");
+        };
+        assert!(failed.reason.starts_with("Claim failed verification: seq"));
+        let lines: Vec<&str> = failed.output.lines().collect();
+        assert_eq!(lines.len(), CHECK_OUTPUT_LINES);
+        assert_eq!(lines[0], "62");
+        assert_eq!(lines[CHECK_OUTPUT_LINES - 1], "test login ... FAILED");
+    }
+
+    #[tokio::test]
+    async fn alternative_signals_claim_completion() {
+        let dir = tempdir().expect("create tempdir");
+        let names = |list: &str| list.split(',').map(str::to_string).collect::<Vec<_>>();
+
+        let custom = build_detectors(&names("token"), 0.0, None, "TASK_DONE").expect("build");
+        let done = ctx("<promise>TASK_DONE</promise>", "[]", dir.path());
+        assert_eq!(evaluate(&custom, &done).await, Verdict::Complete);
+        let default = ctx("<promise>COMPLETE</promise>", "[]", dir.path());
+        assert!(matches!(
+            evaluate(&custom, &default).await,
+            Verdict::Incomplete(_)
+        ));
+        assert!(build_detectors(&names("token"), 0.0, None, "</promise>").is_err());
+
+        let json =
+            build_detectors(&names("json,confidence"), 0.8, None, DEFAULT_TOKEN).expect("build");
+        let line = ctx(
+            "All tests pass.\n{\"status\": \"complete\", \"confidence\": 0.5}\n",
+            "[]",
+            dir.path(),
+        );
+        assert_eq!(
+            evaluate(&json, &line).await,
+            Verdict::NeedsReview(Some(0.5))
+        );
+        let other = ctx("{\"status\": \"blocked\"}", "[]", dir.path());
+        assert!(matches!(
+            evaluate(&json, &other).await,
+            Verdict::Incomplete(_)
+        ));
+        assert_eq!(
+            completion_signal(&names("json,confidence"), DEFAULT_TOKEN),
+            r#"{"status": "complete", "confidence": 0.9}"#
+        );
+        assert_eq!(
+            completion_signal(&[], "TASK_DONE"),
+            r#"<promise confidence="0.9">TASK_DONE</promise>"#
+        );
+
+        let exit = build_detectors(&names("exit_code"), 0.0, None, DEFAULT_TOKEN).expect("build");
+        let mut failed = ctx("tests failed", "[]", dir.path());
+        assert_eq!(evaluate(&exit, &failed).await, Verdict::Complete);
+        failed.exit_code = Some(1);
+        assert!(matches!(
+            evaluate(&exit, &failed).await,
+            Verdict::Incomplete(_)
+        ));
+    }
+
+    #[test]
+    fn build_detectors_rejects_bad_stacks() {
+        let unknown = build_detectors(&["magic".to_string()], 0.0, None, DEFAULT_TOKEN);
+        assert!(unknown.is_err());
+        let verify = build_detectors(&["verify".to_string()], 0.0, None, DEFAULT_TOKEN);
+        assert!(verify.is_err());
+    }
+}
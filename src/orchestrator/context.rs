@@ -0,0 +1,185 @@
+//! `--max-prompt-tokens`: keeps the iteration prompt within a budget by
+//! cutting the two parts that grow without bound, the PRD and the progress
+//! log. The task itself, the task table, and Ralph's instructions are always
+//! sent in full.
+
+/// Rough characters per token for English text and code.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Share of the room left that the progress log may claim when both it and
+/// the PRD are too long.
+const PROGRESS_SHARE: usize = 4;
+
+/// Share of the PRD's allowance its list of cut sections may take.
+const OUTLINE_SHARE: usize = 5;
+
+/// A cheap token estimate: about four characters to a token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The PRD and progress log as they go into the prompt.
+pub struct Fitted {
+    pub prd: String,
+    pub progress: String,
+    /// Estimated tokens before and after, when anything was cut.
+    pub trimmed: Option<(usize, usize)>,
+}
+
+/// Fit `prd` and `progress` into what `budget` leaves beside a prompt of
+/// `fixed_tokens`. The newest progress entries and the start of the PRD are
+/// kept; the agent is told what was left out and where to read it.
+pub fn fit(
+    prd: &str,
+    progress: &str,
+    fixed_tokens: usize,
+    budget: usize,
+    prd_file: &str,
+    progress_file: &str,
+) -> Fitted {
+    let (prd_tokens, progress_tokens) = (estimate_tokens(prd), estimate_tokens(progress));
+    let room = budget.saturating_sub(fixed_tokens);
+    if prd_tokens + progress_tokens <= room {
+        return Fitted {
+            prd: prd.to_string(),
+            progress: progress.to_string(),
+            trimmed: None,
+        };
+    }
+    // The PRD says what to build; the log only says what happened.
+    let progress_room =
+        progress_tokens.min((room / PROGRESS_SHARE).max(room.saturating_sub(prd_tokens)));
+    let prd = keep_head(prd, room - progress_room, prd_file);
+    let progress = keep_latest_entries(progress, progress_room, progress_file);
+    let after = fixed_tokens + estimate_tokens(&prd) + estimate_tokens(&progress);
+    Fitted {
+        prd,
+        progress,
+        trimmed: Some((fixed_tokens + prd_tokens + progress_tokens, after)),
+    }
+}
+
+/// The opening lines of the PRD that fit in `max_tokens`, followed by the
+/// headings of the sections that didn't.
+fn keep_head(prd: &str, max_tokens: usize, prd_file: &str) -> String {
+    let lines: Vec<&str> = prd.lines().collect();
+    let cut_at = |max: usize| {
+        let mut used = 0;
+        lines
+            .iter()
+            .position(|line| {
+                used += estimate_tokens(line) + 1;
+                used > max
+            })
+            .unwrap_or(lines.len())
+    };
+    let outline_room = max_tokens / OUTLINE_SHARE;
+    let note = |cut: usize| {
+        let sections: Vec<&str> = lines[cut..]
+            .iter()
+            .filter(|line| line.starts_with('#'))
+            .map(|line| line.trim_start_matches('#').trim())
+            .collect();
+        let mut note =
+            format!("_[The rest of {prd_file} is not shown here; read it when you need it.");
+        if !sections.is_empty() {
+            let mut listed = String::new();
+            for section in sections {
+                if estimate_tokens(&listed) + estimate_tokens(section) > outline_room {
+                    listed.push_str(", …");
+                    break;
+                }
+                if !listed.is_empty() {
+                    listed.push_str(", ");
+                }
+                listed.push_str(section);
+            }
+            note.push_str(&format!(" Sections left out: {listed}."));
+        }
+        note.push_str("]_");
+        note
+    };
+    let cut = cut_at(max_tokens);
+    if cut == lines.len() {
+        return prd.to_string();
+    }
+    let cut = cut_at(max_tokens.saturating_sub(estimate_tokens(&note(cut))));
+    let mut kept = lines[..cut].join("\n");
+    if !kept.is_empty() {
+        kept.push_str("\n\n");
+    }
+    kept.push_str(&note(cut));
+    kept
+}
+
+/// The newest `## ` entries of the progress log that fit in `max_tokens`,
+/// after a line saying how many older ones were dropped.
+fn keep_latest_entries(progress: &str, max_tokens: usize, progress_file: &str) -> String {
+    if estimate_tokens(progress) <= max_tokens {
+        return progress.to_string();
+    }
+    let starts: Vec<usize> = progress
+        .match_indices("\n## ")
+        .map(|(i, _)| i + 1)
+        .collect();
+    let note = |dropped: usize| {
+        format!("_[Older entries of {progress_file} are not shown here ({dropped} left out).]_\n")
+    };
+    // Drop whole entries from the top until the rest fits.
+    for (dropped, &start) in starts.iter().enumerate().skip(1) {
+        let rest = &progress[start..];
+        let note = note(dropped);
+        if estimate_tokens(&note) + estimate_tokens(rest) <= max_tokens {
+            return format!("{note}{rest}");
+        }
+    }
+    note(starts.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: usize) -> String {
+        format!(
+            "\n## 2026-01-0{n} 10:00:00 UTC\n\n**Iteration {n}** — {}\n",
+            "x".repeat(200)
+        )
+    }
+
+    #[test]
+    fn short_context_is_left_alone() {
+        let fitted = fit("# PRD\n\nBuild it.", "", 500, 1000, "prd.md", "progress.md");
+        assert_eq!(fitted.prd, "# PRD\n\nBuild it.");
+        assert!(fitted.trimmed.is_none());
+    }
+
+    #[test]
+    fn long_progress_keeps_its_newest_entries() {
+        let progress: String = (1..=6).map(entry).collect();
+        let kept = keep_latest_entries(&progress, 150, ".ralph/progress.md");
+        assert!(kept.starts_with(
+            "_[Older entries of .ralph/progress.md are not shown here (4 left out).]_"
+        ));
+        assert!(kept.contains("**Iteration 5**") && kept.contains("**Iteration 6**"));
+        assert!(!kept.contains("**Iteration 4**"));
+        assert!(estimate_tokens(&kept) <= 150);
+    }
+
+    #[test]
+    fn long_prd_keeps_its_start_and_names_the_rest() {
+        let prd = format!(
+            "# Shop\n\nIntro.\n\n## Cart\n\n{}\n\n## Checkout\n\nPay.\n\n## Admin\n\nManage.\n",
+            "Cart details. ".repeat(40)
+        );
+        let fitted = fit(&prd, &entry(1), 100, 300, "prd.md", "progress.md");
+        assert!(fitted.prd.starts_with("# Shop\n\nIntro."), "{}", fitted.prd);
+        assert!(
+            fitted.prd.contains("Sections left out: Checkout, Admin."),
+            "{}",
+            fitted.prd
+        );
+        let (before, after) = fitted.trimmed.expect("trimmed");
+        assert!(before > 300 && after <= 300, "{before} → {after}");
+    }
+}
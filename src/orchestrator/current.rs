@@ -0,0 +1,116 @@
+//! `current.json` in the state directory: the running iteration's details as
+//! JSON, for agents that can read files. The prompt points at it; it carries
+//! what doesn't fit there (earlier failures, the verify command, limits) in a
+//! form the agent can check its own work against mid-iteration.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::state::Task;
+
+/// What the agent is working on and under which rules.
+#[derive(Debug, Serialize)]
+pub struct Current<'a> {
+    pub iteration: u32,
+    /// 1 on the first try at this task in this run.
+    pub attempt: u32,
+    pub task: &'a Task,
+    /// Why this task's earlier attempts in this run failed, oldest first.
+    pub earlier_failures: &'a [String],
+    /// Run by Ralph to check a low-confidence completion claim.
+    pub verify_cmd: Option<&'a str>,
+    pub constraints: Constraints,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Constraints {
+    pub timeout_secs: u64,
+    /// Subproject (`--project`) the agent must stay inside.
+    pub project: Option<String>,
+    /// Paths outside `project` the agent may still change.
+    pub allow_paths: Vec<String>,
+    /// `.ralphignore` patterns; changes to them are reverted.
+    pub protected_patterns: Vec<String>,
+    pub scratch_dir: Option<String>,
+}
+
+/// Write `current` to `path`, read-only: the agent is meant to read it, and
+/// Ralph doesn't read it back.
+pub fn write(path: &Path, current: &Current) -> Result<()> {
+    // A read-only file can't be overwritten in place.
+    let _ = fs::remove_file(path);
+    let json = serde_json::to_string_pretty(current).context("Failed to serialise current.json")?;
+    fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+/// Remove the file once the iteration is over, so it never outlives it or
+/// ends up in a commit.
+pub fn remove(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TaskStatus;
+    use tempfile::tempdir;
+
+    #[test]
+    fn writes_a_read_only_snapshot_and_replaces_it() {
+        let dir = tempdir().expect("create tempdir");
+        let path = dir.path().join("current.json");
+        let task = Task {
+            id: "T2".to_string(),
+            title: "Add login".to_string(),
+            description: "POST /login".to_string(),
+            priority: 1,
+            status: TaskStatus::InProgress,
+            depends_on: vec!["T1".to_string()],
+            ..Default::default()
+        };
+        let failures = vec!["iteration 3: no completion token".to_string()];
+        let current = Current {
+            iteration: 4,
+            attempt: 2,
+            task: &task,
+            earlier_failures: &failures,
+            verify_cmd: Some("cargo test"),
+            constraints: Constraints {
+                timeout_secs: 600,
+                project: Some("api".to_string()),
+                ..Constraints::default()
+            },
+        };
+
+        write(&path, &current).expect("write current.json");
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["attempt"], 2);
+        assert_eq!(json["task"]["id"], "T2");
+        assert_eq!(
+            json["earlier_failures"][0],
+            "iteration 3: no completion token"
+        );
+        assert_eq!(json["verify_cmd"], "cargo test");
+        assert_eq!(json["constraints"]["project"], "api");
+
+        let next = Current {
+            iteration: 5,
+            attempt: 3,
+            ..current
+        };
+        write(&path, &next).expect("rewrite over the read-only file");
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"iteration\": 5"));
+        remove(&path);
+        assert!(!path.exists());
+    }
+}
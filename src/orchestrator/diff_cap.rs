@@ -0,0 +1,169 @@
+//! `--max-files-changed` and `--max-lines-changed`: a task whose diff
+//! outgrows the cap is queued for review instead of committed, so an agent
+//! that rewrites half the repository under one task is caught before its
+//! work lands in the history.
+//!
+//! In git the diff is measured against the iteration's checkpoint, so files
+//! that were dirty before it (the user's, or an earlier oversized task's)
+//! don't count; failed attempts are rolled back, so that is the task's whole
+//! diff. Without a checkpoint only the files the iteration changed are
+//! known, so only the file cap applies.
+
+use std::path::Path;
+
+use super::checkpoint::Checkpoint;
+use super::inventory::FileChange;
+use crate::git::GitManager;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffCap {
+    files: Option<usize>,
+    lines: Option<usize>,
+}
+
+/// How big a task's diff is. `lines` is `None` outside git.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffSize {
+    pub files: usize,
+    pub lines: Option<usize>,
+}
+
+impl DiffCap {
+    /// The cap the flags set, or `None` when neither was given.
+    pub fn new(files: Option<usize>, lines: Option<usize>) -> Option<Self> {
+        (files.is_some() || lines.is_some()).then_some(Self { files, lines })
+    }
+
+    /// How `size` breaks the cap, e.g. `62 files changed (cap 50)`, or
+    /// `None` when it fits.
+    pub fn exceeded(&self, size: &DiffSize) -> Option<String> {
+        let mut over = Vec::new();
+        if let Some(cap) = self.files.filter(|cap| size.files > *cap) {
+            over.push(format!("{} files changed (cap {cap})", size.files));
+        }
+        if let (Some(cap), Some(lines)) = (self.lines, size.lines) {
+            if lines > cap {
+                over.push(format!("{lines} lines changed (cap {cap})"));
+            }
+        }
+        (!over.is_empty()).then(|| over.join(", "))
+    }
+}
+
+/// The size of the task's diff: `changes`, the files the iteration changed,
+/// with their lines counted against `checkpoint`. Ralph's own state doesn't
+/// count.
+pub async fn measure(
+    git: &GitManager,
+    workdir: &Path,
+    changes: &[FileChange],
+    checkpoint: Option<&Checkpoint>,
+) -> DiffSize {
+    let changes = changes.iter().filter(|c| !is_state(&c.path));
+    let Some(checkpoint) = checkpoint else {
+        return DiffSize {
+            files: changes.count(),
+            lines: None,
+        };
+    };
+    let numstat = git
+        .numstat_since(checkpoint.base())
+        .await
+        .unwrap_or_default();
+    let mut size = DiffSize {
+        files: 0,
+        lines: Some(0),
+    };
+    for change in changes {
+        size.files += 1;
+        let lines = match numstat.iter().find(|(path, _)| *path == change.path) {
+            Some((_, lines)) => *lines,
+            // New since the checkpoint: every line is new.
+            None => std::fs::read_to_string(workdir.join(&change.path))
+                .map(|text| text.lines().count())
+                .unwrap_or(0),
+        };
+        size.lines = size.lines.map(|total| total + lines);
+    }
+    size
+}
+
+fn is_state(path: &str) -> bool {
+    path.starts_with(".ralph")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[test]
+    fn reports_each_limit_broken() {
+        assert!(DiffCap::new(None, None).is_none());
+        let cap = DiffCap::new(Some(50), Some(5000)).unwrap();
+        let within = DiffSize {
+            files: 50,
+            lines: Some(5000),
+        };
+        assert_eq!(cap.exceeded(&within), None);
+        let over = DiffSize {
+            files: 62,
+            lines: Some(7130),
+        };
+        assert_eq!(
+            cap.exceeded(&over).as_deref(),
+            Some("62 files changed (cap 50), 7130 lines changed (cap 5000)")
+        );
+        let no_git = DiffSize {
+            files: 3,
+            lines: None,
+        };
+        assert_eq!(DiffCap::new(None, Some(1)).unwrap().exceeded(&no_git), None);
+    }
+
+    #[tokio::test]
+    async fn measures_tracked_and_untracked_changes() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::write(root.join("lib.rs"), "one\ntwo\nthree\n").expect("seed lib");
+        fs::write(root.join("old.rs"), "old\n").expect("seed old");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+
+        // Dirty before the iteration, so not part of its diff.
+        fs::write(root.join("notes.txt"), "mine\n").expect("user file");
+        fs::write(root.join("old.rs"), "x\ny\nz\n").expect("user edit");
+        let manager = GitManager::new(root);
+        let checkpoint = Checkpoint::take(&manager).await.expect("checkpoint");
+
+        fs::write(root.join("lib.rs"), "one\n2\nthree\nfour\n").expect("edit lib");
+        fs::write(root.join("new.rs"), "a\nb\n").expect("add file");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state file");
+
+        let changes = checkpoint.changes(&manager).await.expect("changes");
+        let size = measure(&manager, root, &changes, Some(&checkpoint)).await;
+        // lib.rs: 2 added + 1 removed; new.rs: 2 new lines.
+        assert_eq!(
+            size,
+            DiffSize {
+                files: 2,
+                lines: Some(5)
+            }
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! What an iteration failed on, so each kind of failure gets its own policy
+//! instead of every one costing a retry: a rejected login stops the run, a
+//! rate limit is waited out, a dropped connection is retried on the same
+//! agent, and a crashed agent hands the task to a fallback.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::rate_limit;
+
+/// Phrases (lowercase) that mark rejected credentials. A bare `401` or
+/// "unauthorized" could be a line number or the project's own test output.
+const AUTH_MARKERS: &[&str] = &[
+    "401 unauthorized",
+    "http 401",
+    "status 401",
+    "status: 401",
+    "status code 401",
+    "invalid api key",
+    "invalid x-api-key",
+    "invalid_api_key",
+    "authentication_error",
+    "authentication failed",
+    "not logged in",
+    "please run /login",
+    "please log in",
+];
+
+/// Phrases (lowercase) that mark a connection problem between the agent and
+/// its provider.
+const NETWORK_MARKERS: &[&str] = &[
+    "connection refused",
+    "connection reset",
+    "connection closed",
+    "stream disconnected",
+    "network error",
+    "network is unreachable",
+    "could not resolve host",
+    "dns error",
+    "error sending request",
+    "econnreset",
+    "econnrefused",
+    "enotfound",
+    "etimedout",
+];
+
+/// Phrases (lowercase) the agents print when their sandbox or permission
+/// mode refuses a command. A plain EPERM or read-only file system could be
+/// the project's own tests failing.
+const SANDBOX_MARKERS: &[&str] = &[
+    "sandbox denied",
+    "denied by sandbox",
+    "rejected by sandbox",
+    "blocked by sandbox",
+    "sandbox(denied",
+    "requested permissions to use",
+    "tool execution denied",
+];
+
+/// Wait before retrying after a network failure, per consecutive failure.
+const NETWORK_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest wait after a network failure.
+const MAX_NETWORK_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The provider rejected the agent's credentials; no retry can pass.
+    Auth,
+    /// The provider's rate or usage limit.
+    RateLimit,
+    /// The agent lost its connection to the provider.
+    Network,
+    /// A sandbox refused something the task needs.
+    SandboxDenied,
+    /// The agent died: killed by a signal.
+    AgentCrash,
+    /// The agent ran, but the task itself didn't work out.
+    Task,
+}
+
+impl FailureKind {
+    /// Name used in hook payloads and `progress.md`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Auth => "auth",
+            FailureKind::RateLimit => "rate_limit",
+            FailureKind::Network => "network",
+            FailureKind::SandboxDenied => "sandbox_denied",
+            FailureKind::AgentCrash => "agent_crash",
+            FailureKind::Task => "task",
+        }
+    }
+
+    /// The kind of failure `err` reports. Errors that weren't classified
+    /// where they arose (timeouts, stalls, agent errors) are the task's.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if rate_limit::retry_after(err).is_some() {
+            return FailureKind::RateLimit;
+        }
+        err.chain()
+            .find_map(|e| e.downcast_ref::<AgentFailure>())
+            .map_or(FailureKind::Task, |failure| failure.kind)
+    }
+
+    /// Whether the task should go back to pending: it never got a fair
+    /// attempt, so the failure says nothing about the task.
+    pub fn retries_task(self) -> bool {
+        matches!(self, FailureKind::Network | FailureKind::AgentCrash)
+    }
+
+    /// Whether a fallback agent might do better. Another agent has the same
+    /// connection and runs in the same sandbox.
+    pub fn tries_another_agent(self) -> bool {
+        !matches!(self, FailureKind::Network | FailureKind::SandboxDenied)
+    }
+
+    /// How long to wait before the next iteration, after the
+    /// `consecutive_failures`th failure in a row.
+    pub fn backoff(self, consecutive_failures: u32) -> Option<Duration> {
+        match self {
+            FailureKind::Network => {
+                Some((NETWORK_BACKOFF * consecutive_failures.max(1)).min(MAX_NETWORK_BACKOFF))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Auth, network, or sandbox failure named in the final lines of a failed
+/// run's output, if any.
+pub fn detect(tail: &str) -> Option<FailureKind> {
+    let lower = tail.to_lowercase();
+    let has = |markers: &[&str]| markers.iter().any(|marker| lower.contains(marker));
+    if has(AUTH_MARKERS) {
+        Some(FailureKind::Auth)
+    } else if has(SANDBOX_MARKERS) {
+        Some(FailureKind::SandboxDenied)
+    } else if has(NETWORK_MARKERS) {
+        Some(FailureKind::Network)
+    } else {
+        None
+    }
+}
+
+/// An iteration failure classified where it arose, from the agent's exit
+/// and output.
+#[derive(Debug)]
+pub struct AgentFailure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl fmt::Display for AgentFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AgentFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_names_the_kind_of_failure() {
+        assert_eq!(
+            detect("Error: 401 Unauthorized: invalid x-api-key"),
+            Some(FailureKind::Auth)
+        );
+        assert_eq!(
+            detect("Invalid API key · Please run /login"),
+            Some(FailureKind::Auth)
+        );
+        assert_eq!(
+            detect("error: stream disconnected before completion"),
+            Some(FailureKind::Network)
+        );
+        assert_eq!(
+            detect("exec error: Sandbox(Denied { output: ... })"),
+            Some(FailureKind::SandboxDenied)
+        );
+        assert_eq!(
+            detect("Claude requested permissions to use Bash, but you haven't granted it yet."),
+            Some(FailureKind::SandboxDenied)
+        );
+        assert_eq!(detect("error[E0308]: mismatched types"), None);
+    }
+
+    #[test]
+    fn ordinary_output_is_not_mistaken_for_auth_or_sandbox() {
+        for output in [
+            "error: expected `;` at src/parser.rs:401:17",
+            "test orders::fetches_order_14012 ... FAILED",
+            "assert_eq!(response.status(), 401) passed: unauthorized users are turned away",
+            "Error: EPERM: operation not permitted, open '/tmp/fixture.lock'",
+            "thread 'main' panicked: Operation not permitted (os error 1)",
+            "bash: /etc/hosts: Read-only file system",
+        ] {
+            assert_eq!(detect(output), None, "{output}");
+        }
+    }
+
+    #[test]
+    fn errors_carry_their_kind_and_policy() {
+        let crash = anyhow::Error::new(AgentFailure {
+            kind: FailureKind::AgentCrash,
+            message: "Agent exited with code Some(137)".to_string(),
+        })
+        .context("Iteration 2");
+        assert_eq!(FailureKind::of(&crash), FailureKind::AgentCrash);
+        let limited = anyhow::Error::new(rate_limit::RateLimited {
+            retry_after: Duration::from_secs(5),
+        });
+        assert_eq!(FailureKind::of(&limited), FailureKind::RateLimit);
+        let stalled = anyhow::anyhow!("Agent stalled — no output for 60s");
+        assert_eq!(FailureKind::of(&stalled), FailureKind::Task);
+
+        assert!(FailureKind::AgentCrash.retries_task());
+        assert!(!FailureKind::Task.retries_task());
+        assert!(!FailureKind::Network.tries_another_agent());
+        assert_eq!(
+            FailureKind::Network.backoff(3),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(FailureKind::Network.backoff(50), Some(MAX_NETWORK_BACKOFF));
+        assert_eq!(FailureKind::AgentCrash.backoff(1), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! `[fallback]`: when the loop moves to a fallback agent and when it goes
+//! back. By default one failure switches and one success returns; raising
+//! `switch_after` and `stay_for` keeps a flaky pair of backends from
+//! trading places every iteration. `cooldown` benches an agent after an auth
+//! or rate-limit error, so it is neither switched to nor returned to until
+//! the time is up.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::FallbackConfig;
+
+pub struct Fallback {
+    /// Failures in a row on the active agent before switching.
+    switch_after: u32,
+    /// Tasks a fallback agent finishes before the loop goes back.
+    stay_for: u32,
+    /// How long an agent is benched after an auth or rate-limit error.
+    cooldown: Duration,
+    /// Failures in a row on the active agent.
+    failures: u32,
+    /// Tasks the fallback agent still has to finish, while one is active.
+    stay_left: Option<u32>,
+    /// When each benched agent may be used again.
+    benched_until: HashMap<String, Instant>,
+}
+
+impl Fallback {
+    pub fn new(config: &FallbackConfig) -> Result<Self> {
+        let switch_after = config.switch_after.unwrap_or(1);
+        if switch_after == 0 {
+            bail!("[fallback] switch_after must be 1 or more");
+        }
+        Ok(Self {
+            switch_after,
+            stay_for: config.stay_for.unwrap_or(0),
+            cooldown: Duration::from_secs(config.cooldown.unwrap_or(0)),
+            failures: 0,
+            stay_left: None,
+            benched_until: HashMap::new(),
+        })
+    }
+
+    /// Count a failure of the active agent; true once it is time to switch.
+    pub fn failed(&mut self) -> bool {
+        self.failures += 1;
+        self.failures >= self.switch_after
+    }
+
+    /// An iteration went through: the failure streak is over.
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    /// The loop moved to a fallback agent.
+    pub fn switched(&mut self) {
+        self.failures = 0;
+        self.stay_left = Some(self.stay_for);
+    }
+
+    /// A task was completed (by whichever agent is active).
+    pub fn task_done(&mut self) {
+        if let Some(left) = self.stay_left.as_mut() {
+            *left = left.saturating_sub(1);
+        }
+    }
+
+    /// Whether the next task may go back to its own agent.
+    pub fn may_return(&self) -> bool {
+        self.stay_left.is_none_or(|left| left == 0)
+    }
+
+    /// The loop is back on the tasks' own agents. True if it had been on a
+    /// fallback agent.
+    pub fn returned(&mut self) -> bool {
+        self.stay_left.take().is_some()
+    }
+
+    /// Bench `agent` for the cooldown, or for `at_least` if longer (a rate
+    /// limit's retry-after). Returns how long, or `None` without a cooldown.
+    pub fn bench(&mut self, agent: &str, at_least: Duration) -> Option<Duration> {
+        if self.cooldown.is_zero() {
+            return None;
+        }
+        let length = self.cooldown.max(at_least);
+        self.benched_until
+            .insert(agent.to_string(), Instant::now() + length);
+        Some(length)
+    }
+
+    /// How much longer `agent` is benched, if it is.
+    pub fn benched(&self, agent: &str) -> Option<Duration> {
+        let until = self.benched_until.get(agent)?;
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fallback(switch_after: u32, stay_for: u32, cooldown: u64) -> Fallback {
+        Fallback::new(&FallbackConfig {
+            switch_after: Some(switch_after),
+            stay_for: Some(stay_for),
+            cooldown: Some(cooldown),
+        })
+        .expect("valid config")
+    }
+
+    #[test]
+    fn defaults_switch_on_the_first_failure_and_return_after_a_success() {
+        let mut f = Fallback::new(&FallbackConfig::default()).expect("defaults");
+        assert!(f.failed());
+        f.switched();
+        assert!(f.may_return());
+        assert!(f.returned());
+        assert!(!f.returned());
+        assert_eq!(f.bench("codex", Duration::from_secs(60)), None);
+        assert_eq!(f.benched("codex"), None);
+    }
+
+    #[test]
+    fn hysteresis_holds_both_ways() {
+        let mut f = fallback(2, 2, 0);
+        assert!(!f.failed());
+        f.succeeded();
+        assert!(!f.failed());
+        assert!(f.failed());
+        f.switched();
+
+        assert!(!f.may_return());
+        f.task_done();
+        assert!(!f.may_return());
+        f.task_done();
+        assert!(f.may_return());
+    }
+
+    #[test]
+    fn benched_agents_wait_out_the_longer_of_cooldown_and_retry_after() {
+        let mut f = fallback(1, 0, 300);
+        assert_eq!(
+            f.bench("codex", Duration::from_secs(30)),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            f.bench("claude", Duration::from_secs(900)),
+            Some(Duration::from_secs(900))
+        );
+        assert!(f
+            .benched("codex")
+            .is_some_and(|left| left > Duration::from_secs(290)));
+        assert!(f.benched("gemini").is_none());
+        assert!(Fallback::new(&FallbackConfig {
+            switch_after: Some(0),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
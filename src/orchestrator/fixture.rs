@@ -0,0 +1,404 @@
+//! `--record DIR` / `--replay DIR`: capture a run's agent invocations as a
+//! fixture, then feed them back through the orchestrator without a real agent.
+//!
+//! A fixture holds the task list the loop started from, and per iteration the
+//! prompt, the agent's stdout and stderr, how it exited, and the tasks.json it
+//! left behind if it edited that file. Replay reproduces all of that, so
+//! scheduling and state handling take the same path they did in the
+//! recording; edits to other files are not reproduced.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::process::Command;
+
+use crate::agents::{own_process_group, Agent, AgentProcess};
+use crate::state::TaskList;
+
+const MANIFEST: &str = "manifest.json";
+const START_TASKS: &str = "tasks.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    prd: String,
+    agent: String,
+    recorded_at: DateTime<Utc>,
+    iterations: Vec<Recorded>,
+}
+
+/// One recorded agent invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recorded {
+    pub iteration: u32,
+    pub task_id: String,
+    pub agent: String,
+    pub outcome: Outcome,
+    /// Whether `<stem>.tasks.json` holds the tasks.json the agent left.
+    pub tasks_edited: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Outcome {
+    /// The agent exited on its own; `None` means it died from a signal.
+    Exited { code: Option<i32> },
+    /// Ralph killed it (timeout, stall, or cancel); replay hangs until the
+    /// same limit kills it again.
+    Killed { reason: String },
+    /// The agent could not be started.
+    SpawnFailed { error: String },
+}
+
+impl Recorded {
+    /// File name prefix for this iteration's files, e.g. `003-T2`.
+    fn stem(&self) -> String {
+        format!("{:03}-{}", self.iteration, self.task_id)
+    }
+}
+
+// ── Recording ─────────────────────────────────────────────────────────────────
+
+pub struct Recorder {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl Recorder {
+    /// Start a fixture in `dir` (created if needed; must not already hold one).
+    pub fn create(dir: &Path, prd: &Path, agent: &str, tasks: &TaskList) -> Result<Self> {
+        anyhow::ensure!(
+            !dir.join(MANIFEST).exists(),
+            "{} already contains a recording",
+            dir.display()
+        );
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let json = serde_json::to_string_pretty(tasks).context("Failed to serialise tasks")?;
+        std::fs::write(dir.join(START_TASKS), json).context("Failed to write fixture tasks")?;
+
+        let recorder = Self {
+            dir: dir.to_path_buf(),
+            manifest: Manifest {
+                version: 1,
+                prd: prd.display().to_string(),
+                agent: agent.to_string(),
+                recorded_at: Utc::now(),
+                iterations: Vec::new(),
+            },
+        };
+        recorder.save()?;
+        Ok(recorder)
+    }
+
+    /// Record one iteration from its iteration log. `tasks_after` is the raw
+    /// tasks.json when the agent changed it.
+    pub fn record(
+        &mut self,
+        iteration: u32,
+        task_id: &str,
+        agent: &str,
+        prompt: &str,
+        log_path: &Path,
+        tasks_after: Option<&str>,
+    ) -> Result<()> {
+        let log = std::fs::read_to_string(log_path);
+        let (outcome, stdout, stderr) = match &log {
+            Ok(log) => split_log(log)
+                .with_context(|| format!("Unrecognised iteration log {}", log_path.display()))?,
+            // run_iteration writes the log once the agent has run, so none
+            // means the spawn itself failed.
+            Err(e) => (
+                Outcome::SpawnFailed {
+                    error: e.to_string(),
+                },
+                "",
+                "",
+            ),
+        };
+        let entry = Recorded {
+            iteration,
+            task_id: task_id.to_string(),
+            agent: agent.to_string(),
+            outcome,
+            tasks_edited: tasks_after.is_some(),
+        };
+
+        let stem = entry.stem();
+        let write = |ext: &str, content: &str| {
+            std::fs::write(self.dir.join(format!("{stem}.{ext}")), content)
+                .with_context(|| format!("Failed to write {stem}.{ext}"))
+        };
+        write("prompt.md", prompt)?;
+        write("stdout", stdout)?;
+        write("stderr", stderr)?;
+        if let Some(tasks) = tasks_after {
+            write("tasks.json", tasks)?;
+        }
+
+        self.manifest.iterations.push(entry);
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(&self.manifest).context("Failed to serialise manifest")?;
+        std::fs::write(self.dir.join(MANIFEST), json).context("Failed to write fixture manifest")
+    }
+}
+
+/// Split an iteration log (see `run_iteration`) into outcome, stdout, stderr.
+fn split_log(log: &str) -> Result<(Outcome, &str, &str)> {
+    let (header, rest) = log
+        .split_once("\n\n=== STDOUT ===\n")
+        .context("missing STDOUT section")?;
+    let (stdout, stderr) = rest
+        .rsplit_once("\n\n=== STDERR ===\n")
+        .context("missing STDERR section")?;
+    let stderr = stderr.strip_suffix('\n').unwrap_or(stderr);
+
+    let header = header
+        .trim()
+        .trim_start_matches("===")
+        .trim_end_matches("===")
+        .trim();
+    let outcome = if let Some(code) = header.strip_prefix("EXIT CODE: ") {
+        let code = code
+            .strip_prefix("Some(")
+            .and_then(|c| c.strip_suffix(')'))
+            .map(str::parse)
+            .transpose()
+            .context("bad exit code")?;
+        Outcome::Exited { code }
+    } else if let Some(reason) = header.strip_prefix("KILLED: ") {
+        Outcome::Killed {
+            reason: reason.to_string(),
+        }
+    } else {
+        anyhow::bail!("unknown header '{header}'");
+    };
+    Ok((outcome, stdout, stderr))
+}
+
+// ── Replay ────────────────────────────────────────────────────────────────────
+
+pub struct Replay {
+    dir: PathBuf,
+    manifest: Manifest,
+    /// Index of the next recorded iteration to play.
+    cursor: AtomicUsize,
+    tasks_file: PathBuf,
+}
+
+impl Replay {
+    /// Open the fixture in `dir`; replayed tasks.json edits go to `tasks_file`.
+    pub fn open(dir: &Path, tasks_file: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("{} is not a recording (no {MANIFEST})", dir.display()))?;
+        let manifest: Manifest =
+            serde_json::from_str(&raw).with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+            cursor: AtomicUsize::new(0),
+            tasks_file: tasks_file.to_path_buf(),
+        })
+    }
+
+    /// Replace `tasks_file` with the task list the recording started from.
+    pub fn seed_tasks(&self) -> Result<()> {
+        std::fs::copy(self.dir.join(START_TASKS), &self.tasks_file)
+            .context("Failed to copy the recorded tasks.json")?;
+        Ok(())
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.manifest.iterations.len()
+    }
+
+    /// Describe how the upcoming iteration departs from the recording, if it does.
+    pub fn divergence(&self, iteration: u32, task_id: &str) -> Option<String> {
+        let index = self.cursor.load(Ordering::SeqCst);
+        match self.manifest.iterations.get(index) {
+            None => Some(format!(
+                "recording ended after {} iteration(s)",
+                self.iterations()
+            )),
+            Some(r) if r.task_id != task_id => Some(format!(
+                "iteration {iteration} picked {task_id}, but recorded iteration {} ran {}",
+                r.iteration, r.task_id
+            )),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Agent whose runs replay a recording's iterations in order.
+pub struct ReplayAgent(pub Arc<Replay>);
+
+impl Agent for ReplayAgent {
+    fn name(&self) -> &str {
+        &self.0.manifest.agent
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn spawn(
+        &self,
+        _prompt: &str,
+        workdir: &Path,
+        env: &[(String, String)],
+    ) -> Result<AgentProcess> {
+        let replay = &self.0;
+        let index = replay.cursor.fetch_add(1, Ordering::SeqCst);
+        let entry = replay
+            .manifest
+            .iterations
+            .get(index)
+            .with_context(|| format!("Recording has no iteration {} to replay", index + 1))?;
+
+        let code = match &entry.outcome {
+            Outcome::Exited { code: Some(code) } => code.to_string(),
+            Outcome::Exited { code: None } => "signal".to_string(),
+            Outcome::Killed { .. } => "hang".to_string(),
+            Outcome::SpawnFailed { error } => anyhow::bail!("Recorded spawn failure: {error}"),
+        };
+        let file = |ext: &str| replay.dir.join(format!("{}.{ext}", entry.stem()));
+        let tasks = if entry.tasks_edited {
+            file("tasks.json")
+        } else {
+            PathBuf::new()
+        };
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(
+                r#"cat "$1"; cat "$2" >&2
+[ -n "$3" ] && cp "$3" "$4"
+case "$5" in
+  signal) kill -KILL $$ ;;
+  hang) exec sleep 2147483647 ;;
+  *) exit "$5" ;;
+esac"#,
+            )
+            .arg("ralph-replay")
+            .arg(file("stdout"))
+            .arg(file("stderr"))
+            .arg(tasks)
+            .arg(&replay.tasks_file)
+            .arg(code);
+        own_process_group(&mut cmd);
+        cmd.envs(env.iter().cloned());
+        cmd.current_dir(workdir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let child = cmd.spawn().context("Failed to spawn replay process")?;
+        Ok(child.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::io::AsyncReadExt;
+
+    fn task_list() -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: vec![],
+        }
+    }
+
+    #[test]
+    fn split_log_reads_exit_codes_and_kills() {
+        let log =
+            "=== EXIT CODE: Some(3) ===\n\n=== STDOUT ===\nout\nmore\n\n=== STDERR ===\nerr\n";
+        let (outcome, stdout, stderr) = split_log(log).expect("split");
+        assert_eq!(outcome, Outcome::Exited { code: Some(3) });
+        assert_eq!((stdout, stderr), ("out\nmore", "err"));
+
+        let log =
+            "=== KILLED: Agent timed out after 5s ===\n\n=== STDOUT ===\n\n\n=== STDERR ===\n\n";
+        let (outcome, stdout, stderr) = split_log(log).expect("split");
+        assert_eq!(
+            outcome,
+            Outcome::Killed {
+                reason: "Agent timed out after 5s".to_string()
+            }
+        );
+        assert_eq!((stdout, stderr), ("", ""));
+
+        let (outcome, _, _) =
+            split_log("=== EXIT CODE: None ===\n\n=== STDOUT ===\n\n\n=== STDERR ===\n\n")
+                .expect("split");
+        assert_eq!(outcome, Outcome::Exited { code: None });
+    }
+
+    #[tokio::test]
+    async fn replay_plays_back_recorded_output_exit_code_and_tasks_edit() {
+        let dir = tempdir().expect("create tempdir");
+        let fixture = dir.path().join("fixture");
+        let tasks_file = dir.path().join("tasks.json");
+
+        let mut recorder =
+            Recorder::create(&fixture, Path::new("prd.md"), "codex", &task_list()).expect("create");
+        let log = dir.path().join("iteration.log");
+        std::fs::write(
+            &log,
+            "=== EXIT CODE: Some(2) ===\n\n=== STDOUT ===\nworking\n\n=== STDERR ===\noops\n",
+        )
+        .expect("write log");
+        recorder
+            .record(1, "T1", "codex", "prompt", &log, Some("{\"edited\": true}"))
+            .expect("record");
+        assert!(Recorder::create(&fixture, Path::new("prd.md"), "codex", &task_list()).is_err());
+
+        let replay = Arc::new(Replay::open(&fixture, &tasks_file).expect("open"));
+        replay.seed_tasks().expect("seed");
+        assert!(std::fs::read_to_string(&tasks_file)
+            .expect("read seeded tasks")
+            .contains("\"prd_path\": \"prd.md\""));
+        assert_eq!(replay.divergence(1, "T1"), None);
+        assert!(replay.divergence(1, "T2").is_some());
+
+        let agent = ReplayAgent(replay.clone());
+        let mut proc = agent.spawn("prompt", dir.path(), &[]).expect("spawn");
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        proc.stdout
+            .take()
+            .expect("stdout")
+            .read_to_string(&mut stdout)
+            .await
+            .expect("read stdout");
+        proc.stderr
+            .take()
+            .expect("stderr")
+            .read_to_string(&mut stderr)
+            .await
+            .expect("read stderr");
+        let status = proc.wait().await.expect("wait");
+
+        assert_eq!(stdout, "working");
+        assert_eq!(stderr, "oops");
+        assert_eq!(status.code(), Some(2));
+        assert_eq!(
+            std::fs::read_to_string(&tasks_file).expect("read tasks"),
+            "{\"edited\": true}"
+        );
+        assert!(replay.divergence(2, "T1").is_some());
+        assert!(agent.spawn("prompt", dir.path(), &[]).is_err());
+    }
+}
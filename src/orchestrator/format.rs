@@ -0,0 +1,154 @@
+//! Formatters and linters (`--format-cmd`, `[[format]]`) run over a completed
+//! task's changed files before its commit, so style fixes land with the task
+//! instead of costing agent iterations.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+use crate::config::FormatConfig;
+use crate::shell::quote;
+
+/// How one formatter run went.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    /// The command as run, with `{files}` expanded.
+    pub cmd: String,
+    /// Why it failed, if it did.
+    pub error: Option<String>,
+}
+
+/// `--format-cmd` entries when there are any, else the `[[format]]` ones.
+pub fn formatters(cli: &[String], config: &[FormatConfig]) -> Vec<FormatConfig> {
+    if cli.is_empty() {
+        return config.to_vec();
+    }
+    cli.iter()
+        .map(|cmd| FormatConfig {
+            cmd: cmd.clone(),
+            extensions: None,
+        })
+        .collect()
+}
+
+/// Run each formatter whose files are among `changed` (workdir-relative).
+/// Failures are reported, not fatal: the task is already complete.
+pub async fn run(
+    formatters: &[FormatConfig],
+    changed: &[String],
+    workdir: &Path,
+    timeout_secs: u64,
+) -> Vec<Outcome> {
+    let mut outcomes = Vec::new();
+    for formatter in formatters {
+        let files: Vec<&str> = changed
+            .iter()
+            .map(String::as_str)
+            .filter(|path| matches_extension(path, formatter.extensions.as_deref()))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        let quoted: Vec<String> = files.iter().map(|f| quote(f)).collect();
+        let cmd = formatter.cmd.replace("{files}", &quoted.join(" "));
+        let error = match run_one(&cmd, workdir, timeout_secs).await {
+            Ok(()) => None,
+            Err(e) => Some(format!("{e:#}")),
+        };
+        outcomes.push(Outcome { cmd, error });
+    }
+    outcomes
+}
+
+fn matches_extension(path: &str, extensions: Option<&[String]>) -> bool {
+    let Some(extensions) = extensions else {
+        return true;
+    };
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| e.trim_start_matches('.') == ext))
+}
+
+async fn run_one(cmd: &str, workdir: &Path, timeout_secs: u64) -> Result<()> {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .current_dir(workdir)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = timeout(Duration::from_secs(timeout_secs), child)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {timeout_secs}s"))?
+        .with_context(|| format!("Failed to run {cmd}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let last = stderr.lines().rev().find(|l| !l.trim().is_empty());
+    anyhow::bail!(
+        "exited with {}{}",
+        output.status,
+        last.map(|l| format!(": {}", l.trim())).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn runs_matching_formatters_on_changed_files() {
+        let dir = tempdir().expect("create tempdir");
+        fs::write(dir.path().join("app.py"), "x=1\n").expect("write py");
+        fs::write(dir.path().join("it's.py"), "y=2\n").expect("write quoted py");
+        let formatters = vec![
+            FormatConfig {
+                cmd: "for f in {files}; do printf '# fmt\\n' >> \"$f\"; done".to_string(),
+                extensions: Some(vec!["py".to_string()]),
+            },
+            FormatConfig {
+                cmd: "touch ran-ts".to_string(),
+                extensions: Some(vec![".ts".to_string()]),
+            },
+            FormatConfig {
+                cmd: "echo 'no config' >&2; exit 2".to_string(),
+                extensions: None,
+            },
+        ];
+        let changed = vec![
+            "app.py".to_string(),
+            "it's.py".to_string(),
+            "README.md".to_string(),
+        ];
+
+        let outcomes = run(&formatters, &changed, dir.path(), 5).await;
+
+        assert_eq!(outcomes.len(), 2, "the .ts formatter has no files");
+        assert!(outcomes[0].error.is_none(), "{:?}", outcomes[0]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("it's.py")).expect("read"),
+            "y=2\n# fmt\n"
+        );
+        assert!(!dir.path().join("ran-ts").exists());
+        let error = outcomes[1].error.as_deref().expect("failure reported");
+        assert!(error.contains("no config"), "{error}");
+    }
+
+    #[test]
+    fn format_cmd_flags_replace_the_config() {
+        let config = vec![FormatConfig {
+            cmd: "ruff check --fix {files}".to_string(),
+            extensions: Some(vec!["py".to_string()]),
+        }];
+        assert_eq!(formatters(&[], &config), config);
+        let cli = formatters(&["cargo fmt".to_string()], &config);
+        assert_eq!(cli.len(), 1);
+        assert_eq!(cli[0].cmd, "cargo fmt");
+        assert!(cli[0].extensions.is_none());
+    }
+}
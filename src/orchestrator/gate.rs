@@ -0,0 +1,223 @@
+//! `--interactive`: after an iteration that finishes its task, show what
+//! changed and wait for a human before committing. `y` commits and moves on,
+//! `n` reverts the iteration's edits and counts as a failed attempt, `e`
+//! opens the changed files in an editor and asks again, and `q` stops the
+//! run with the edits left uncommitted.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::inventory::FileChange;
+use crate::git::{ChangedPath, GitManager};
+use crate::state::Task;
+
+/// Diff lines shown before the rest is cut.
+const DIFF_LINES: usize = 200;
+
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    Accept,
+    Reject,
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Reply {
+    Answer(Answer),
+    Edit,
+}
+
+pub(super) fn parse_reply(input: &str) -> Option<Reply> {
+    match input.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Some(Reply::Answer(Answer::Accept)),
+        "n" | "no" => Some(Reply::Answer(Answer::Reject)),
+        "e" | "edit" => Some(Reply::Edit),
+        "q" | "quit" => Some(Reply::Answer(Answer::Quit)),
+        _ => None,
+    }
+}
+
+/// Show the iteration's changes and ask what to do with them. Ctrl+C or the
+/// end of input counts as quitting.
+pub async fn ask(
+    git: &GitManager,
+    workdir: &Path,
+    task: &Task,
+    changes: &[FileChange],
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<Answer> {
+    let in_repo = git.is_git_repo().await;
+    loop {
+        println!("\n    ── Task {} — {} ──", task.id, task.title);
+        if in_repo {
+            println!("{}", git.status_summary().await.unwrap_or_default());
+            print_diff(&git.diff_head().await.unwrap_or_default(), "git diff HEAD");
+        } else {
+            for change in changes {
+                println!("        {change}");
+            }
+        }
+        let question = format!(
+            "Commit task {}? [y]es, [n]o (revert), [e]dit, [q]uit",
+            task.id
+        );
+        match read_reply(&question, cancel.clone()).await? {
+            Some(Reply::Answer(answer)) => return Ok(answer),
+            Some(Reply::Edit) => {
+                let files = editable_files(git, in_repo, workdir, changes).await;
+                if files.is_empty() {
+                    println!("    Nothing to edit: no changed files.");
+                } else if let Err(e) = crate::open::edit(&files) {
+                    eprintln!("    ⚠️   {e:#}");
+                }
+            }
+            None => println!("    Answer y, n, e, or q."),
+        }
+    }
+}
+
+/// Ask `question` and read one reply, `None` when it isn't one. Ctrl+C or
+/// the end of input counts as quitting.
+pub(super) async fn read_reply(
+    question: &str,
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<Option<Reply>> {
+    print!("\n    {question}: ");
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut stdin = BufReader::new(tokio::io::stdin());
+    let mut line = String::new();
+    let read = tokio::select! {
+        read = stdin.read_line(&mut line) => read?,
+        _ = super::wait_for_cancel(cancel) => 0,
+    };
+    if read == 0 {
+        println!();
+        return Ok(Some(Reply::Answer(Answer::Quit)));
+    }
+    Ok(parse_reply(&line))
+}
+
+pub(super) fn print_diff(diff: &str, source: &str) {
+    let lines: Vec<&str> = diff.lines().collect();
+    for line in lines.iter().take(DIFF_LINES) {
+        println!("{line}");
+    }
+    if lines.len() > DIFF_LINES {
+        println!("… {} more lines ({source})", lines.len() - DIFF_LINES);
+    }
+}
+
+/// The files the iteration left behind that still exist.
+async fn editable_files(
+    git: &GitManager,
+    in_repo: bool,
+    workdir: &Path,
+    changes: &[FileChange],
+) -> Vec<PathBuf> {
+    let paths: Vec<String> = if in_repo {
+        git.changed_paths()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| c.path)
+            .collect()
+    } else {
+        changes.iter().map(|c| c.path.clone()).collect()
+    };
+    paths
+        .into_iter()
+        .filter(|p| !p.starts_with(".ralph"))
+        .map(|p| workdir.join(p))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// What [`revert`] undid.
+#[derive(Debug, Default)]
+pub struct Reverted {
+    pub paths: Vec<String>,
+    /// Paths that could not be reverted.
+    pub failed: Vec<String>,
+}
+
+/// Undo what the iteration changed, leaving paths that were already dirty
+/// before it and Ralph's own state alone.
+pub async fn revert(git: &GitManager, before: &[ChangedPath]) -> Reverted {
+    let mut reverted = Reverted::default();
+    let Ok(after) = git.changed_paths().await else {
+        return reverted;
+    };
+    for change in after {
+        if change.path.starts_with(".ralph") || before.iter().any(|b| b.path == change.path) {
+            continue;
+        }
+        match git.revert_path(&change).await {
+            Ok(_) => reverted.paths.push(change.path),
+            Err(_) => reverted.failed.push(change.path),
+        }
+    }
+    reverted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[test]
+    fn replies_are_single_letters_or_words() {
+        assert_eq!(parse_reply("y\n"), Some(Reply::Answer(Answer::Accept)));
+        assert_eq!(parse_reply(" No "), Some(Reply::Answer(Answer::Reject)));
+        assert_eq!(parse_reply("e"), Some(Reply::Edit));
+        assert_eq!(parse_reply("quit"), Some(Reply::Answer(Answer::Quit)));
+        assert_eq!(parse_reply(""), None);
+        assert_eq!(parse_reply("maybe"), None);
+    }
+
+    #[tokio::test]
+    async fn revert_undoes_only_the_iterations_edits() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::write(root.join("lib.rs"), "v1\n").expect("seed lib");
+        fs::write(root.join("notes.md"), "v1\n").expect("seed notes");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+
+        fs::write(root.join("notes.md"), "user edit\n").expect("user edit");
+        let manager = GitManager::new(root);
+        let before = manager.changed_paths().await.expect("before");
+
+        fs::write(root.join("lib.rs"), "v2\n").expect("agent edit");
+        fs::write(root.join("new.rs"), "new\n").expect("agent file");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state file");
+
+        let reverted = revert(&manager, &before).await;
+        assert!(reverted.failed.is_empty());
+        assert_eq!(reverted.paths.len(), 2, "{:?}", reverted.paths);
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "v1\n");
+        assert!(!root.join("new.rs").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("notes.md")).unwrap(),
+            "user edit\n"
+        );
+        assert!(root.join(".ralph/progress.md").exists());
+    }
+}
@@ -0,0 +1,189 @@
+//! Change tracking for workdirs that aren't git repositories: hash every file
+//! before and after an iteration and compare, so completed tasks still report
+//! which files they touched.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::snapshot::SKIP_DIRS;
+
+/// Bound on the walk, so huge trees don't stall the loop.
+const MAX_SCANNED: usize = 20_000;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+/// Workdir-relative path → content hash of every tracked file.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    files: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = match self.kind {
+            ChangeKind::Added => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+        };
+        write!(f, "{letter} {}", self.path)
+    }
+}
+
+impl Inventory {
+    /// Scan `workdir`. Files whose size and mtime match `previous` keep their
+    /// old hash instead of being read again.
+    pub fn scan(workdir: &Path, previous: &Inventory) -> Self {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![workdir.to_path_buf()];
+        let mut scanned = 0;
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                scanned += 1;
+                if scanned > MAX_SCANNED {
+                    break;
+                }
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                // Ralph's own state changes every iteration.
+                if name.starts_with(".ralph") || SKIP_DIRS.contains(&name.as_ref()) {
+                    continue;
+                }
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    pending.push(entry.path());
+                    continue;
+                }
+                let path = entry.path();
+                let rel = path.strip_prefix(workdir).unwrap_or(&path);
+                let rel = rel.to_string_lossy().to_string();
+                let (len, modified) = (meta.len(), meta.modified().ok());
+                let hash = match previous.files.get(&rel) {
+                    Some(old)
+                        if old.len == len && modified.is_some() && old.modified == modified =>
+                    {
+                        old.hash
+                    }
+                    _ => match hash_file(&path) {
+                        Some(hash) => hash,
+                        None => continue,
+                    },
+                };
+                files.insert(
+                    rel,
+                    Entry {
+                        len,
+                        modified,
+                        hash,
+                    },
+                );
+            }
+        }
+        Self { files }
+    }
+
+    /// What changed between `self` and a later scan, sorted by path.
+    pub fn changes(&self, after: &Inventory) -> Vec<FileChange> {
+        let mut changes: Vec<FileChange> = after
+            .files
+            .iter()
+            .filter_map(|(path, entry)| {
+                let kind = match self.files.get(path) {
+                    None => ChangeKind::Added,
+                    Some(old) if old.hash != entry.hash || old.len != entry.len => {
+                        ChangeKind::Modified
+                    }
+                    Some(_) => return None,
+                };
+                Some(FileChange {
+                    path: path.clone(),
+                    kind,
+                })
+            })
+            .chain(
+                self.files
+                    .keys()
+                    .filter(|path| !after.files.contains_key(*path))
+                    .map(|path| FileChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Deleted,
+                    }),
+            )
+            .collect();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Some(hasher.finish()),
+            Ok(n) => hasher.write(&buf[..n]),
+            Err(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn changes_list_added_modified_and_deleted_files() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).expect("create src");
+        fs::write(root.join("src/app.py"), "v1\n").expect("write");
+        fs::write(root.join("old.txt"), "bye\n").expect("write");
+        fs::write(root.join("same.txt"), "same\n").expect("write");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+
+        let before = Inventory::scan(root, &Inventory::default());
+        fs::write(root.join("src/app.py"), "version 2\n").expect("edit");
+        fs::remove_file(root.join("old.txt")).expect("delete");
+        fs::write(root.join("src/new.py"), "new\n").expect("add");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state");
+        let after = Inventory::scan(root, &before);
+
+        let listed: Vec<String> = before
+            .changes(&after)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(listed, ["D old.txt", "M src/app.py", "A src/new.py"]);
+        assert!(after.changes(&Inventory::scan(root, &after)).is_empty());
+    }
+}
@@ -0,0 +1,873 @@
+//! `--task-parallel N`: when several tasks are ready at once — pending, with
+//! every dependency complete — up to N agents work on them side by side in
+//! the workdir. Each has its own log, scratch dir, and `current-<task>.json`.
+//! Results go into tasks.json and progress.md as each agent finishes; the
+//! commit waits until all of them have, so it never holds another agent's
+//! half-done work. It does hold a failed task's edits when another task of the
+//! batch finished, since the agents' changes can't be told apart; when none
+//! finished, the batch is rolled back to its checkpoint like a failed
+//! iteration. `[agents.<name>] max_concurrent` limits a batch's agents too.
+//!
+//! A task whose agent makes its own commits, or isn't installed, is left to
+//! the one-at-a-time loop, as is a lone ready task.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::task::JoinSet;
+use tokio::time::Duration;
+
+use super::attempts::Attempts;
+use super::checkpoint::Checkpoint;
+use super::completion::{self, CompletionDetector, FailedCheck, Verdict};
+use super::failure::FailureKind;
+use super::scope::ScopeGuard;
+use super::{current, format, snapshot, PromptSource};
+use crate::agents::{drift, Agent, TokenUsage};
+use crate::cli::RunArgs;
+use crate::config::FormatConfig;
+use crate::error::{tag_of, RalphError};
+use crate::git::GitManager;
+use crate::hooks::{HookConfig, HookEvent};
+use crate::ignore::RalphIgnore;
+use crate::notify::NotifyConfig;
+use crate::output::Pipeline;
+use crate::rate_limit;
+use crate::state::{IterationStats, LoopState, StateManager, Task, TaskList, TaskStatus};
+use crate::timefmt;
+
+/// What a batch shares with the main loop.
+pub struct Batch<'a> {
+    pub args: &'a RunArgs,
+    pub state: &'a StateManager,
+    pub git: &'a GitManager,
+    pub hook: &'a Option<HookConfig>,
+    pub notify: &'a Option<NotifyConfig>,
+    pub detectors: &'a [Box<dyn CompletionDetector>],
+    pub prompts: &'a PromptSource<'a>,
+    pub scope_guard: Option<&'a ScopeGuard>,
+    /// `--project`, relative to the workdir.
+    pub project: Option<&'a Path>,
+    pub ignore: &'a RalphIgnore,
+    pub formatters: &'a [FormatConfig],
+    pub workdir: &'a Path,
+    pub project_dir: &'a Path,
+    pub output: &'a Arc<Pipeline>,
+    /// Roll a failed batch back to its git checkpoint.
+    pub rollback: bool,
+    pub is_watch_mode: bool,
+}
+
+/// A ready task and the agent that will work on it.
+pub struct Job {
+    pub task: Task,
+    pub agent: Box<dyn Agent>,
+}
+
+/// How a batch ended, for the main loop.
+#[derive(Default)]
+pub struct Outcome {
+    /// Set when the run must stop: an agent's credentials were rejected.
+    pub stopped_by: Option<RalphError>,
+    /// Wait this long before the next iteration (rate limit or backoff).
+    pub wait: Option<Duration>,
+    /// Log of the last task that failed, for the circuit breaker's event.
+    pub last_failed_log: Option<PathBuf>,
+}
+
+/// Up to `limit` ready tasks, highest priority first, each with the agent
+/// it asks for — or none when fewer than two can share the workdir.
+pub fn select(
+    task_list: &TaskList,
+    only: Option<&HashSet<String>>,
+    limit: u32,
+    args: &RunArgs,
+    make_agent: impl Fn(&str, Option<String>) -> Result<Box<dyn Agent>>,
+) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    for task in super::ready_tasks(task_list, only) {
+        if jobs.len() as u32 >= limit {
+            break;
+        }
+        let name = task.agent.as_deref().unwrap_or(&args.agent);
+        let model = task.model.clone().or_else(|| args.model.clone());
+        match make_agent(name, model) {
+            Ok(agent) if agent.is_available() && !agent.capabilities().manages_own_commits => {
+                jobs.push(Job {
+                    task: task.clone(),
+                    agent,
+                });
+            }
+            _ => {}
+        }
+    }
+    if jobs.len() < 2 {
+        jobs.clear();
+    }
+    jobs
+}
+
+/// One task of the batch while its agent runs.
+struct Running {
+    task: Task,
+    iteration: u32,
+    prompt: String,
+    log_path: PathBuf,
+    current_file: PathBuf,
+    /// The task's tasks.json entry before the agent ran.
+    entry_before: String,
+    started_at: SystemTime,
+    timeout: u64,
+}
+
+/// Run `jobs` side by side as iterations `first_iteration`, … and record
+/// their results.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    batch: &Batch<'_>,
+    jobs: Vec<Job>,
+    first_iteration: u32,
+    task_list: &mut TaskList,
+    consecutive_failures: &mut u32,
+    task_failures: &mut HashMap<String, Vec<String>>,
+    attempts: &mut Attempts,
+    failed_checks: &mut HashMap<String, FailedCheck>,
+    spent: (&mut TokenUsage, &mut Option<f64>),
+    iteration_stats: &mut IterationStats,
+    flag_warnings: &mut drift::FlagWarnings,
+) -> Result<Outcome> {
+    let (args, state, is_watch_mode) = (batch.args, batch.state, batch.is_watch_mode);
+    let (spent, spent_cost) = spent;
+    let ids: Vec<String> = jobs.iter().map(|j| j.task.id.clone()).collect();
+    let last_iteration = first_iteration + jobs.len() as u32 - 1;
+    if !is_watch_mode {
+        println!(
+            "\n━━━ Iterations {first_iteration}–{last_iteration} ━━━  Tasks {} in parallel",
+            ids.join(", ")
+        );
+    }
+    super::log_to_status(
+        &args.loop_status,
+        format!("⏩ Tasks {} in parallel", ids.join(", ")),
+    );
+    if let Some(ref ls) = args.loop_status {
+        if let Ok(mut s) = ls.lock() {
+            s.current_task = format!("{} (in parallel)", ids.join(", "));
+            s.iteration = first_iteration;
+            s.state = LoopState::Running;
+        }
+    }
+
+    if let Err(e) = state.backup_tasks() {
+        eprintln!("⚠️   tasks.json backup failed: {e}");
+    }
+    for id in &ids {
+        super::set_task_status(task_list, id, TaskStatus::InProgress);
+    }
+    task_list.updated_at = Utc::now();
+    state.save_tasks(task_list)?;
+
+    // Out-of-scope paths already dirty before the agents run are the user's.
+    let dirty_before = match batch.scope_guard {
+        Some(_) => batch.git.changed_paths().await.ok(),
+        None => None,
+    };
+
+    // Rolled back to if nothing finishes, and what completed tasks report.
+    let checkpoint = if batch.git.is_git_repo().await {
+        match Checkpoint::take(batch.git).await {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                if !is_watch_mode {
+                    eprintln!("    ⚠️   Git checkpoint failed: {e:#}");
+                }
+                super::log_to_status(&args.loop_status, format!("⚠️  Checkpoint failed: {e}"));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let repo = super::repo_context::gather(batch.git, &args.repo_context).await;
+    let mut running = Vec::new();
+    let mut agents = JoinSet::new();
+    for (index, job) in jobs.into_iter().enumerate() {
+        let iteration = first_iteration + index as u32;
+        let task = job.task;
+        let scratch_dir = match tempfile::Builder::new()
+            .prefix(&format!("ralph-iter{iteration}-"))
+            .tempdir()
+        {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                eprintln!("⚠️   Could not create iteration temp dir: {e}");
+                None
+            }
+        };
+        let env: Vec<(String, String)> = scratch_dir
+            .iter()
+            .map(|dir| ("RALPH_TMPDIR".to_string(), dir.path().display().to_string()))
+            .collect();
+
+        let current_file = state.current_file_for(&task.id);
+        let (prompt, trimmed) = batch.prompts.build(
+            &task,
+            task_list,
+            scratch_dir.as_ref().map(|d| d.path()),
+            &current_file,
+            failed_checks.get(&task.id),
+            repo.as_ref(),
+        );
+        if let Some(msg) = trimmed {
+            if !is_watch_mode {
+                println!("    ✂️   {}: {msg}", task.id);
+            }
+            super::log_to_status(&args.loop_status, format!("✂️ {}: {msg}", task.id));
+        }
+
+        let (timeout, stall_timeout) = super::limits_for(args, job.agent.as_ref());
+        let earlier_failures = task_failures.get(&task.id).cloned().unwrap_or_default();
+        let in_progress = task_list
+            .tasks
+            .iter()
+            .find(|t| t.id == task.id)
+            .unwrap_or(&task);
+        let details = current::Current {
+            iteration,
+            attempt: earlier_failures.len() as u32 + 1,
+            task: in_progress,
+            earlier_failures: &earlier_failures,
+            verify_cmd: in_progress.verify.as_deref().or(args.verify_cmd.as_deref()),
+            constraints: current::Constraints {
+                timeout_secs: timeout,
+                project: batch.project.map(|rel| rel.display().to_string()),
+                allow_paths: args
+                    .allow_path
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect(),
+                protected_patterns: batch.ignore.patterns().map(str::to_string).collect(),
+                scratch_dir: scratch_dir.as_ref().map(|d| d.path().display().to_string()),
+            },
+        };
+        if let Err(e) = current::write(&current_file, &details) {
+            eprintln!("⚠️   {e:#}");
+        }
+
+        let log_path = state.log_path(iteration, &task.id);
+        if !is_watch_mode {
+            println!(
+                "    {} — {} ({})  Log: {}",
+                task.id,
+                task.title,
+                job.agent.name(),
+                log_path.display()
+            );
+        }
+
+        let agent = job.agent;
+        let workdir = batch.workdir.to_path_buf();
+        let (log, loop_status, cancel_flag, output) = (
+            log_path.clone(),
+            args.loop_status.clone(),
+            args.cancel_flag.clone(),
+            batch.output.clone(),
+        );
+        let (kill_grace, stop_grace) = (args.kill_grace, args.stop_grace);
+        let lock_file = state.lock_file.clone();
+        let agent_prompt = prompt.clone();
+        let slots = args.agent_slots.clone();
+        let task_id = task.id.clone();
+        agents.spawn(async move {
+            // Queue behind the batch's other agents, and other loops, past
+            // the agent's `max_concurrent`.
+            let name = agent.name().to_string();
+            let _slot = match &slots {
+                Some(slots) => {
+                    if slots.is_full(&name) {
+                        let msg = format!(
+                            "Task {task_id} waiting for a free {name} session (max_concurrent = {})",
+                            slots.limit(&name).unwrap_or_default()
+                        );
+                        if !is_watch_mode {
+                            println!("    ⏳  {msg}");
+                        }
+                        super::log_to_status(&loop_status, format!("⏳ {msg}"));
+                    }
+                    tokio::select! {
+                        slot = slots.acquire(&name) => slot,
+                        _ = super::wait_for_cancel(cancel_flag.clone()) => {
+                            drop(scratch_dir);
+                            return (index, agent, Err(RalphError::Cancelled.into()), 0);
+                        }
+                    }
+                }
+                None => None,
+            };
+            let started = Instant::now();
+            let result = super::run_iteration(
+                agent.as_ref(),
+                &agent_prompt,
+                &workdir,
+                &log,
+                timeout,
+                stall_timeout,
+                kill_grace,
+                stop_grace,
+                &env,
+                false,
+                loop_status,
+                cancel_flag,
+                output,
+                Some(&lock_file),
+            )
+            .await;
+            drop(scratch_dir);
+            (index, agent, result, started.elapsed().as_secs())
+        });
+        running.push(Running {
+            entry_before: task_entry(task_list, &task.id),
+            task,
+            iteration,
+            prompt,
+            log_path,
+            current_file,
+            started_at: SystemTime::now(),
+            timeout,
+        });
+    }
+
+    let mut outcome = Outcome::default();
+    // Tasks whose work goes into the batch commit.
+    let mut finished: Vec<String> = Vec::new();
+    let mut any_failed = false;
+    while let Some(joined) = agents.join_next().await {
+        let (index, agent, result, duration_secs) = joined.context("A parallel agent panicked")?;
+        let Running {
+            task,
+            iteration,
+            prompt,
+            log_path,
+            current_file,
+            entry_before,
+            started_at,
+            timeout,
+        } = &running[index];
+        let iteration = *iteration;
+        current::remove(current_file);
+
+        let usage = super::record_usage(
+            agent.as_ref(),
+            log_path,
+            iteration,
+            &task.id,
+            state,
+            spent,
+            spent_cost,
+        )?;
+        if let Some(summary) = usage {
+            if !is_watch_mode {
+                println!("    📊  {}: {summary}", task.id);
+            }
+            super::log_to_status(&args.loop_status, format!("📊 {}: {summary}", task.id));
+        }
+        super::record_flag_warnings(flag_warnings, agent.as_ref(), log_path);
+
+        let rate_limited = result.as_ref().err().and_then(rate_limit::retry_after);
+        let failure = match result {
+            Ok(super::IterationOutput { stdout, exit_code }) => {
+                let corrupted = state.restore_tasks_if_invalid(task_list)?;
+                if let Some(problem) = &corrupted {
+                    state.append_progress(&format!(
+                        "**tasks.json restored** — Task {} left it invalid: {problem}",
+                        task.id
+                    ))?;
+                }
+                // Only this task's entry counts: the other agents may be
+                // editing theirs.
+                let entry_after = state
+                    .load_tasks()
+                    .ok()
+                    .flatten()
+                    .map(|tl| task_entry(&tl, &task.id))
+                    .unwrap_or_else(|| entry_before.clone());
+                let detection = completion::DetectionContext {
+                    stdout: &stdout,
+                    exit_code,
+                    tasks_before: entry_before,
+                    tasks_after: &entry_after,
+                    workdir: batch.project_dir,
+                    timeout_secs: *timeout,
+                    task_verify: task.verify.as_deref(),
+                };
+                let verdict = match corrupted {
+                    Some(problem) => {
+                        Verdict::Incomplete(format!("Agent corrupted tasks.json: {problem}"))
+                    }
+                    None => completion::evaluate(batch.detectors, &detection).await,
+                };
+                super::remember_failed_check(&verdict, &task.id, failed_checks);
+                match verdict {
+                    Verdict::Complete => {
+                        if !is_watch_mode {
+                            println!("    ✅  Task {} — complete ({duration_secs}s)", task.id);
+                        }
+                        super::log_to_status(
+                            &args.loop_status,
+                            format!("✅ Task {} complete: {}", task.id, task.title),
+                        );
+                        *consecutive_failures = 0;
+                        super::set_task_status(task_list, &task.id, TaskStatus::Complete);
+                        super::retry_note::clear(task_list, &task.id);
+                        if let Some(t) = task_list.tasks.iter_mut().find(|t| t.id == task.id) {
+                            t.completed_at = Some(Utc::now());
+                        }
+                        task_list.updated_at = Utc::now();
+                        save_task(state, task_list, &task.id)?;
+                        if let Some(ref ls) = args.loop_status {
+                            if let Ok(mut s) = ls.lock() {
+                                s.tasks_done = task_list
+                                    .tasks
+                                    .iter()
+                                    .filter(|t| t.status == TaskStatus::Complete)
+                                    .count() as u32;
+                            }
+                        }
+                        state.append_progress(&format!(
+                            "**Task {} complete** — {}\n\n(iteration {iteration}, in parallel with {})",
+                            task.id,
+                            task.title,
+                            others(&ids, &task.id)
+                        ))?;
+                        super::fire_hook(
+                            batch.hook,
+                            batch.notify,
+                            HookEvent::TaskComplete {
+                                task_id: task.id.clone(),
+                                task_title: task.title.clone(),
+                                iteration,
+                                duration_secs,
+                                // The agents share the workdir, so this is
+                                // the batch's changes so far.
+                                files_changed: match &checkpoint {
+                                    Some(checkpoint) => checkpoint
+                                        .changes(batch.git)
+                                        .await
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|c| c.path)
+                                        .collect(),
+                                    None => vec![],
+                                },
+                                summary: format!(
+                                    "Task {} — {} completed in iteration {iteration}",
+                                    task.id, task.title
+                                ),
+                                progress: super::make_progress(task_list),
+                            },
+                            None,
+                        )
+                        .await;
+                        finished.push(format!("{} — {}", task.id, task.title));
+                        None
+                    }
+                    Verdict::NeedsReview(confidence) => {
+                        if !is_watch_mode {
+                            println!(
+                                "    👀  Task {} — claimed complete below confidence {:.2}; queued for review",
+                                task.id, args.min_confidence
+                            );
+                        }
+                        *consecutive_failures = 0;
+                        super::set_task_status(task_list, &task.id, TaskStatus::NeedsReview);
+                        task_list.updated_at = Utc::now();
+                        save_task(state, task_list, &task.id)?;
+                        state.append_progress(&format!(
+                            "**Task {} needs review** — {}\n\n(iteration {iteration}, confidence {}){}",
+                            task.id,
+                            task.title,
+                            completion::format_confidence(confidence),
+                            super::acceptance_section(&task.acceptance)
+                        ))?;
+                        super::fire_hook(
+                            batch.hook,
+                            batch.notify,
+                            HookEvent::TaskNeedsReview {
+                                task_id: task.id.clone(),
+                                task_title: task.title.clone(),
+                                iteration,
+                                confidence,
+                                threshold: args.min_confidence,
+                                acceptance: task.acceptance.clone(),
+                                reason: None,
+                                progress: super::make_progress(task_list),
+                            },
+                            None,
+                        )
+                        .await;
+                        finished.push(format!("{} — {} (needs review)", task.id, task.title));
+                        None
+                    }
+                    Verdict::Incomplete(reason)
+                    | Verdict::Unverified(FailedCheck { reason, .. }) => {
+                        Some((reason, FailureKind::Task, TaskStatus::Pending))
+                    }
+                }
+            }
+
+            // Interrupted, not failed: the main loop stops at its cancellation check.
+            Err(e) if matches!(tag_of(&e), Some(RalphError::Cancelled)) => {
+                super::set_task_status(task_list, &task.id, TaskStatus::Pending);
+                task_list.updated_at = Utc::now();
+                save_task(state, task_list, &task.id)?;
+                state.append_progress(&format!(
+                    "**Iteration {iteration} interrupted** — Task {} returned to pending",
+                    task.id
+                ))?;
+                None
+            }
+
+            // Not the task's fault: retried after the wait, without a failure.
+            Err(_) if rate_limited.is_some() => {
+                let wait = rate_limited.unwrap_or_default();
+                let msg = format!(
+                    "Rate limited — retrying Task {} in {}",
+                    task.id,
+                    timefmt::compact_duration(wait.as_secs())
+                );
+                if !is_watch_mode {
+                    println!("    ⏳  {msg}");
+                }
+                super::log_to_status(&args.loop_status, format!("⏳ {msg}"));
+                super::set_task_status(task_list, &task.id, TaskStatus::Pending);
+                task_list.updated_at = Utc::now();
+                save_task(state, task_list, &task.id)?;
+                state
+                    .append_progress(&format!("**Iteration {iteration} rate limited** — {msg}"))?;
+                super::fire_hook(
+                    batch.hook,
+                    batch.notify,
+                    HookEvent::RateLimited {
+                        task_id: task.id.clone(),
+                        task_title: task.title.clone(),
+                        iteration,
+                        retry_after_secs: wait.as_secs(),
+                        progress: super::make_progress(task_list),
+                    },
+                    None,
+                )
+                .await;
+                outcome.wait = outcome.wait.max(Some(wait));
+                None
+            }
+
+            // As in the one-at-a-time loop, a crash or dropped connection
+            // says nothing about the task, so it is retried.
+            Err(e) => {
+                let kind = FailureKind::of(&e);
+                let status = if kind.retries_task() || kind == FailureKind::Auth {
+                    TaskStatus::Pending
+                } else {
+                    TaskStatus::Failed
+                };
+                Some((format!("{e}"), kind, status))
+            }
+        };
+
+        if let Some((reason, kind, status)) = failure {
+            any_failed = true;
+            *consecutive_failures += 1;
+            task_failures
+                .entry(task.id.clone())
+                .or_default()
+                .push(format!("iteration {iteration}: {reason}"));
+            if !is_watch_mode {
+                eprintln!("    ❌  Task {} failed ({kind}): {reason}", task.id);
+            }
+            super::log_to_status(
+                &args.loop_status,
+                format!("❌ Task {} failed ({kind}): {reason}", task.id),
+            );
+            // An incomplete task is retried until it has used its attempts.
+            let given_up = (status == TaskStatus::Pending && kind == FailureKind::Task)
+                .then(|| attempts.fail(task))
+                .flatten();
+            let status = if given_up.is_some() {
+                TaskStatus::Failed
+            } else {
+                status
+            };
+            super::set_task_status(task_list, &task.id, status);
+            // Only the agent's own failures are worth showing it again.
+            if !kind.retries_task() && kind != FailureKind::Auth {
+                super::retry_note::record(task_list, &task.id, iteration, &reason, log_path);
+            }
+            task_list.updated_at = Utc::now();
+            save_task(state, task_list, &task.id)?;
+            state.append_progress(&format!(
+                "**Iteration {iteration} FAILED** — Task {} ({kind}): {reason}\n\nConsecutive failures: {}/{}",
+                task.id, consecutive_failures, args.max_failures
+            ))?;
+            if let Some(used) = given_up {
+                super::report_given_up(
+                    state,
+                    task_list,
+                    task,
+                    used,
+                    &args.loop_status,
+                    is_watch_mode,
+                )?;
+            }
+
+            if args.failure_snapshots {
+                let failure = snapshot::Failure {
+                    iteration,
+                    task_id: &task.id,
+                    agent: agent.name(),
+                    model: args.model.as_deref(),
+                    error: &reason,
+                    prompt,
+                    log_path,
+                    started_at: *started_at,
+                };
+                super::save_failure_snapshot(
+                    state,
+                    batch.workdir,
+                    batch.git,
+                    &failure,
+                    is_watch_mode,
+                )
+                .await;
+            }
+
+            super::fire_hook(
+                batch.hook,
+                batch.notify,
+                HookEvent::TaskFailed {
+                    task_id: task.id.clone(),
+                    task_title: task.title.clone(),
+                    iteration,
+                    duration_secs,
+                    error: reason.clone(),
+                    failure_kind: kind.to_string(),
+                    consecutive_failures: *consecutive_failures,
+                    progress: super::make_progress(task_list),
+                },
+                Some(log_path),
+            )
+            .await;
+            outcome.last_failed_log = Some(log_path.clone());
+
+            // No retry gets past rejected credentials.
+            if kind == FailureKind::Auth && outcome.stopped_by.is_none() {
+                let reason = format!("{} authentication failed: {reason}", agent.name());
+                if !is_watch_mode {
+                    eprintln!("\n🔑  {reason} — stopping. Log in again and re-run.");
+                }
+                state
+                    .append_progress(&format!("**STOPPED** — {reason} (iteration {iteration})."))?;
+                super::update_loop_state(
+                    &args.loop_status,
+                    LoopState::Failed("authentication failed".to_string()),
+                );
+                outcome.stopped_by = Some(RalphError::AuthFailed(reason));
+            }
+            outcome.wait = outcome.wait.max(kind.backoff(*consecutive_failures));
+            if given_up.is_some() {
+                *consecutive_failures = 0;
+            }
+        }
+
+        let completed = task_list
+            .tasks
+            .iter()
+            .any(|t| t.id == task.id && t.status == TaskStatus::Complete);
+        iteration_stats.record(duration_secs, completed);
+    }
+
+    // Every agent has stopped. With nothing finished, whatever they changed is
+    // failed work.
+    if let Some(checkpoint) = checkpoint
+        .as_ref()
+        .filter(|_| batch.rollback && any_failed && finished.is_empty())
+    {
+        super::rollback_checkpoint(
+            checkpoint,
+            batch.git,
+            state,
+            &args.loop_status,
+            first_iteration,
+            is_watch_mode,
+        )
+        .await;
+    }
+
+    // Check what they changed together, then commit.
+    if let (Some(guard), Some(before)) = (batch.scope_guard, &dirty_before) {
+        let report = guard.enforce(batch.git, before).await;
+        if !report.violations.is_empty() {
+            let summary = report.summary(guard.project());
+            if !is_watch_mode {
+                println!("    🚧  Reverted changes {summary}");
+                for (path, e) in &report.unreverted {
+                    eprintln!("    ⚠️   Could not revert {path}: {e}");
+                }
+            }
+            super::log_to_status(&args.loop_status, format!("🚧 Reverted changes {summary}"));
+            state.append_progress(&format!(
+                "**Scope violation** — Tasks {} changed files {summary} (reverted)",
+                ids.join(", ")
+            ))?;
+            let unreverted = report.unreverted.into_iter().map(|(p, _)| p).collect();
+            super::fire_hook(
+                batch.hook,
+                batch.notify,
+                HookEvent::ScopeViolation {
+                    task_id: ids.join(", "),
+                    task_title: running
+                        .iter()
+                        .map(|r| r.task.title.as_str())
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                    iteration: first_iteration,
+                    project: guard.project().display().to_string(),
+                    paths: report.violations.clone(),
+                    ignored: report.ignored.clone(),
+                    unreverted,
+                    progress: super::make_progress(task_list),
+                },
+                None,
+            )
+            .await;
+        }
+    }
+
+    if !finished.is_empty() && !batch.formatters.is_empty() {
+        let files = super::task_files(batch.git, &[], batch.workdir).await;
+        let timeout = running.iter().map(|r| r.timeout).max().unwrap_or_default();
+        for outcome in format::run(batch.formatters, &files, batch.workdir, timeout).await {
+            match outcome.error {
+                None => {
+                    if !is_watch_mode {
+                        println!("    🧹  Formatted: {}", outcome.cmd);
+                    }
+                }
+                Some(e) => {
+                    let warning = format!("Formatter `{}` {e}", outcome.cmd);
+                    if !is_watch_mode {
+                        eprintln!("    ⚠️   {warning}");
+                    }
+                    state.append_progress(&warning)?;
+                }
+            }
+        }
+    }
+
+    if !finished.is_empty() && !args.no_branch {
+        super::auto_commit(batch.git, &commit_message(&finished), is_watch_mode).await;
+    }
+    Ok(outcome)
+}
+
+/// Write `task_id`'s entry from `task_list` into tasks.json, leaving the
+/// rest as it is on disk: the other agents of the batch may still be editing
+/// theirs.
+fn save_task(state: &StateManager, task_list: &TaskList, task_id: &str) -> Result<()> {
+    let Some(ours) = task_list.tasks.iter().find(|t| t.id == task_id) else {
+        return Ok(());
+    };
+    let Ok(Some(mut on_disk)) = state.load_tasks() else {
+        return state.save_tasks(task_list);
+    };
+    match on_disk.tasks.iter_mut().find(|t| t.id == task_id) {
+        Some(entry) => *entry = ours.clone(),
+        None => on_disk.tasks.push(ours.clone()),
+    }
+    on_disk.updated_at = task_list.updated_at;
+    state.save_tasks(&on_disk)
+}
+
+/// One task's entry in tasks.json, to spot an agent marking it done.
+fn task_entry(task_list: &TaskList, task_id: &str) -> String {
+    task_list
+        .tasks
+        .iter()
+        .find(|t| t.id == task_id)
+        .and_then(|t| serde_json::to_string(t).ok())
+        .unwrap_or_default()
+}
+
+/// `T2, T3` — the batch's other tasks, for the progress log.
+fn others(ids: &[String], task_id: &str) -> String {
+    let others: Vec<&str> = ids
+        .iter()
+        .map(String::as_str)
+        .filter(|id| *id != task_id)
+        .collect();
+    others.join(", ")
+}
+
+/// One commit for the tasks a batch finished, listing each of them.
+fn commit_message(finished: &[String]) -> String {
+    let ids: Vec<&str> = finished
+        .iter()
+        .map(|line| line.split(" — ").next().unwrap_or(line))
+        .collect();
+    let body: Vec<String> = finished.iter().map(|line| format!("- {line}")).collect();
+    format!("feat: {} (ralph)\n\n{}", ids.join(", "), body.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_commit_lists_every_finished_task() {
+        let message = commit_message(&[
+            "T2 — Add login".to_string(),
+            "T3 — Add logout (needs review)".to_string(),
+        ]);
+        assert_eq!(
+            message,
+            "feat: T2, T3 (ralph)\n\n- T2 — Add login\n- T3 — Add logout (needs review)"
+        );
+        let ids = ["T2".to_string(), "T3".to_string(), "T4".to_string()];
+        assert_eq!(others(&ids, "T3"), "T2, T4");
+    }
+
+    #[test]
+    fn saving_a_task_keeps_the_other_agents_entries() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let task = |id: &str| Task {
+            id: id.to_string(),
+            title: format!("Task {id}"),
+            priority: 1,
+            status: TaskStatus::InProgress,
+            ..Default::default()
+        };
+        let mut task_list = TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: vec![task("T1"), task("T2")],
+        };
+        state.save_tasks(&task_list).expect("save tasks");
+
+        // T2's agent claims its task while T1's is being recorded.
+        let mut on_disk = task_list.clone();
+        on_disk.tasks[1].status = TaskStatus::Complete;
+        state.save_tasks(&on_disk).expect("save tasks");
+        task_list.tasks[0].status = TaskStatus::Failed;
+        save_task(&state, &task_list, "T1").expect("save task");
+
+        let saved = state.load_tasks().expect("load").expect("tasks.json");
+        assert_eq!(saved.tasks[0].status, TaskStatus::Failed);
+        assert_eq!(saved.tasks[1].status, TaskStatus::Complete);
+    }
+}
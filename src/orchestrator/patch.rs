@@ -0,0 +1,461 @@
+//! Unified diffs as agents write them under `--plan`: parsed leniently (hunk
+//! line counts are ignored, since models often get them wrong) and applied
+//! to the workdir. Every file is checked before any is written, so a patch
+//! that doesn't fit leaves the tree as it was.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// The changes a patch makes to one file.
+#[derive(Debug, PartialEq)]
+pub struct FilePatch {
+    /// `None` for a file the patch creates.
+    pub old: Option<String>,
+    /// `None` for a file the patch deletes.
+    pub new: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// The path the patch is about, e.g. for listing it.
+    pub fn path(&self) -> &str {
+        self.new.as_deref().or(self.old.as_deref()).unwrap_or("?")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    /// 1-based line the hunk starts at in the old file, as a hint.
+    old_start: usize,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// The file patches in `text`. Anything before the first `---` header
+/// (`diff --git`, `index` lines, prose) is skipped.
+pub fn parse(text: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let next = lines.get(i + 1).copied().unwrap_or_default();
+        if let (Some(old), Some(new)) = (line.strip_prefix("--- "), next.strip_prefix("+++ ")) {
+            files.push(FilePatch {
+                old: header_path(old),
+                new: header_path(new),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("@@ ") {
+            let file = files
+                .last_mut()
+                .context("Hunk before any ---/+++ file header")?;
+            let old_start = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('-'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1);
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+            while i < lines.len() {
+                let body = lines[i];
+                let starts_file = body.starts_with("--- ")
+                    && lines.get(i + 1).is_some_and(|n| n.starts_with("+++ "));
+                if body.starts_with("@@ ") || body.starts_with("diff ") || starts_file {
+                    break;
+                }
+                match body.chars().next() {
+                    Some('+') => hunk.lines.push(Line::Add(body[1..].to_string())),
+                    Some('-') => hunk.lines.push(Line::Remove(body[1..].to_string())),
+                    Some(' ') => hunk.lines.push(Line::Context(body[1..].to_string())),
+                    // An empty context line whose space was trimmed.
+                    None => hunk.lines.push(Line::Context(String::new())),
+                    // `\ No newline at end of file`, or the end of the diff.
+                    Some('\\') => {}
+                    Some(_) => break,
+                }
+                i += 1;
+            }
+            file.hunks.push(hunk);
+            continue;
+        }
+        i += 1;
+    }
+    if files.is_empty() {
+        bail!("No ---/+++ file headers in the patch");
+    }
+    for file in &files {
+        if file.hunks.is_empty() && file.new.is_some() {
+            bail!("{} has a header but no hunks", file.path());
+        }
+    }
+    Ok(files)
+}
+
+/// `a/src/lib.rs` → `src/lib.rs`; `/dev/null` → `None`. A tab-separated
+/// timestamp is dropped.
+fn header_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Apply `patches` under `workdir`, returning the paths written or removed.
+pub fn apply(workdir: &Path, patches: &[FilePatch]) -> Result<Vec<String>> {
+    let writes = prepare(workdir, patches)?;
+    for (path, content) in &writes {
+        let full = workdir.join(path);
+        match content {
+            Some(content) => {
+                if let Some(parent) = full.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&full, content).with_context(|| format!("Cannot write {path}"))?;
+            }
+            None => {
+                std::fs::remove_file(&full).with_context(|| format!("Cannot remove {path}"))?;
+            }
+        }
+    }
+    Ok(writes.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Whether `patches` would apply under `workdir`, without writing anything.
+pub fn check(workdir: &Path, patches: &[FilePatch]) -> Result<()> {
+    prepare(workdir, patches).map(drop)
+}
+
+/// Every file's new content (`None` to remove it), worked out before any is
+/// written.
+fn prepare(workdir: &Path, patches: &[FilePatch]) -> Result<Vec<(String, Option<String>)>> {
+    let root = workdir
+        .canonicalize()
+        .with_context(|| format!("Cannot resolve {}", workdir.display()))?;
+    let mut writes: Vec<(String, Option<String>)> = Vec::new();
+    for patch in patches {
+        for path in [&patch.old, &patch.new].into_iter().flatten() {
+            check_path(path)?;
+            check_links(&root, path)?;
+        }
+        let original = match &patch.old {
+            Some(old) => {
+                let full = workdir.join(old);
+                std::fs::read_to_string(&full)
+                    .with_context(|| format!("{old}: cannot read it to patch"))?
+            }
+            None => {
+                let new = patch.new.as_deref().unwrap_or_default();
+                if workdir.join(new).exists() {
+                    bail!("{new}: the patch creates it, but it already exists");
+                }
+                String::new()
+            }
+        };
+        let patched = apply_hunks(&original, &patch.hunks)
+            .with_context(|| format!("{} does not match the patch", patch.path()))?;
+        if let (Some(old), Some(new)) = (&patch.old, &patch.new) {
+            if old != new {
+                writes.push((old.clone(), None));
+            }
+        }
+        match &patch.new {
+            Some(new) => writes.push((new.clone(), Some(patched))),
+            None => writes.push((patch.old.clone().unwrap_or_default(), None)),
+        }
+    }
+    Ok(writes)
+}
+
+/// Patches may only touch relative paths inside the workdir, and neither
+/// git's metadata (hooks run on the next commit) nor Ralph's own state.
+fn check_path(path: &str) -> Result<()> {
+    let inside = Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if path.is_empty() || !inside {
+        bail!("{path}: patches may only change files inside the workdir");
+    }
+    let state_dir = Path::new(path)
+        .components()
+        .find_map(|c| match c {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .is_some_and(|top| top == ".ralph" || top.starts_with(".ralph-"));
+    let git_dir = Path::new(path)
+        .components()
+        .any(|c| c.as_os_str().eq_ignore_ascii_case(".git"));
+    if state_dir || git_dir {
+        bail!("{path}: patches may not change .git or Ralph's state");
+    }
+    Ok(())
+}
+
+/// `path` followed through any symlinks on the way must stay inside `root`
+/// (the canonical workdir) and out of what [`check_path`] protects, so a
+/// link the agent left behind can't carry a write elsewhere.
+fn check_links(root: &Path, path: &str) -> Result<()> {
+    let mut resolved = root.to_path_buf();
+    for component in Path::new(path).components() {
+        resolved.push(component);
+        let is_link = std::fs::symlink_metadata(&resolved).is_ok_and(|m| m.is_symlink());
+        if is_link {
+            // A dangling link fails here too: writing through it would
+            // create its target, wherever that is.
+            resolved = resolved
+                .canonicalize()
+                .ok()
+                .filter(|target| target.starts_with(root))
+                .with_context(|| {
+                    format!("{path}: a symlink on the way leads outside the workdir")
+                })?;
+        }
+    }
+    let relative: PathBuf = resolved.strip_prefix(root).unwrap_or(&resolved).into();
+    if relative.as_os_str().is_empty() {
+        bail!("{path}: a symlink on the way leads to the workdir itself");
+    }
+    check_path(&relative.to_string_lossy())
+        .with_context(|| format!("{path} leads to {}", relative.display()))
+}
+
+/// `original` with each hunk applied in order. A hunk is placed where its
+/// context and removed lines match, searching outward from the line its
+/// header names.
+fn apply_hunks(original: &str, hunks: &[Hunk]) -> Result<String> {
+    let trailing_newline = original.is_empty() || original.ends_with('\n');
+    let mut lines: Vec<String> = original.lines().map(str::to_string).collect();
+    // Lines before this index are settled by earlier hunks.
+    let mut floor = 0;
+    for (n, hunk) in hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|l| match l {
+                Line::Context(s) | Line::Remove(s) => Some(s.as_str()),
+                Line::Add(_) => None,
+            })
+            .collect();
+        let new: Vec<String> = hunk
+            .lines
+            .iter()
+            .filter_map(|l| match l {
+                Line::Context(s) | Line::Add(s) => Some(s.clone()),
+                Line::Remove(_) => None,
+            })
+            .collect();
+        let at = find(&lines, &old, hunk.old_start.saturating_sub(1), floor)
+            .with_context(|| format!("hunk {} (near line {}) not found", n + 1, hunk.old_start))?;
+        let added = new.len();
+        lines.splice(at..at + old.len(), new);
+        floor = at + added;
+    }
+    let mut out = lines.join("\n");
+    if trailing_newline && !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Where `needle` occurs in `lines` at or after `floor`, nearest to `hint`.
+/// Trailing whitespace is ignored.
+fn find(lines: &[String], needle: &[&str], hint: usize, floor: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(hint.clamp(floor, lines.len()));
+    }
+    let fits = |at: usize| {
+        at + needle.len() <= lines.len()
+            && needle
+                .iter()
+                .zip(&lines[at..])
+                .all(|(want, have)| want.trim_end() == have.trim_end())
+    };
+    let last = lines.len().checked_sub(needle.len())?;
+    let hint = hint.clamp(floor, last.max(floor));
+    (0..=lines.len()).find_map(|distance| {
+        let after = hint + distance;
+        if after <= last && fits(after) {
+            return Some(after);
+        }
+        let before = hint.checked_sub(distance).filter(|b| *b >= floor)?;
+        fits(before).then_some(before)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const PATCH: &str = "\
+Here is the change:
+
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,5 @@
+ fn one() {}
+
++fn two() {}
+ fn three() {}
+@@ -9,2 +10,2 @@
+-fn old() {}
++fn new() {}
+--- /dev/null
++++ b/src/added.rs
+@@ -0,0 +1,2 @@
++// new file
++pub fn added() {}
+--- a/src/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn gone() {}
+";
+
+    #[test]
+    fn parses_modified_added_and_deleted_files() {
+        let files = parse(PATCH).expect("parses");
+        let paths: Vec<&str> = files.iter().map(FilePatch::path).collect();
+        assert_eq!(paths, ["src/lib.rs", "src/added.rs", "src/gone.rs"]);
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(files[1].old, None);
+        assert_eq!(files[2].new, None);
+        assert!(parse("no diff here").is_err());
+    }
+
+    #[test]
+    fn applies_hunks_even_when_line_numbers_drifted() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).expect("create src");
+        // Two extra lines at the top push every hunk down.
+        fs::write(
+            root.join("src/lib.rs"),
+            "// header\n\nfn one() {}\n\nfn three() {}\n\n\n\n\n\nfn old() {}\n",
+        )
+        .expect("write lib");
+        fs::write(root.join("src/gone.rs"), "fn gone() {}\n").expect("write gone");
+
+        let files = parse(PATCH).expect("parses");
+        let written = apply(root, &files).expect("applies");
+        assert_eq!(written, ["src/lib.rs", "src/added.rs", "src/gone.rs"]);
+        assert_eq!(
+            fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+            "// header\n\nfn one() {}\n\nfn two() {}\nfn three() {}\n\n\n\n\n\nfn new() {}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/added.rs")).unwrap(),
+            "// new file\npub fn added() {}\n"
+        );
+        assert!(!root.join("src/gone.rs").exists());
+    }
+
+    #[test]
+    fn a_patch_that_does_not_fit_changes_nothing() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "one\ntwo\n").expect("write a");
+        fs::write(root.join("b.txt"), "three\n").expect("write b");
+        let patch = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++2
+--- a/b.txt
++++ b/b.txt
+@@ -1 +1 @@
+-four
++4
+";
+        let patches = parse(patch).unwrap();
+        assert!(check(root, &patches).is_err());
+        let err = apply(root, &patches).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("b.txt does not match"),
+            "{err:#}"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let escape = "--- a/../x\n+++ b/../x\n@@ -1 +1 @@\n-a\n+b\n";
+        assert!(apply(root, &parse(escape).unwrap()).is_err());
+    }
+
+    #[test]
+    fn symlinks_out_of_the_workdir_are_not_followed() {
+        let dir = tempdir().expect("create tempdir");
+        let outside = tempdir().expect("create outside dir");
+        let root = dir.path();
+        fs::write(outside.path().join("secret.txt"), "a\n").expect("write outside");
+        fs::create_dir_all(root.join("src")).expect("create src");
+        fs::write(root.join("src/lib.rs"), "a\n").expect("write lib");
+        fs::create_dir_all(root.join(".git/hooks")).expect("create git dir");
+        std::os::unix::fs::symlink(outside.path(), root.join("out")).expect("link out");
+        std::os::unix::fs::symlink(root.join(".git"), root.join("meta")).expect("link git");
+        std::os::unix::fs::symlink(root.join("src"), root.join("code")).expect("link src");
+        std::os::unix::fs::symlink(outside.path().join("gone"), root.join("dangling"))
+            .expect("link dangling");
+
+        let edit = |path: &str| format!("--- a/{path}\n+++ b/{path}\n@@ -1 +1 @@\n-a\n+b\n");
+        let create = |path: &str| format!("--- /dev/null\n+++ b/{path}\n@@ -0,0 +1 @@\n+b\n");
+        assert!(check(root, &parse(&edit("out/secret.txt")).unwrap()).is_err());
+        assert!(check(root, &parse(&create("meta/hooks/pre-commit")).unwrap()).is_err());
+        assert!(check(root, &parse(&create("dangling")).unwrap()).is_err());
+        // Links that stay inside the workdir are fine.
+        assert_eq!(
+            apply(root, &parse(&edit("code/lib.rs")).unwrap()).expect("apply"),
+            ["code/lib.rs"]
+        );
+        assert_eq!(fs::read_to_string(root.join("src/lib.rs")).unwrap(), "b\n");
+        assert_eq!(
+            fs::read_to_string(outside.path().join("secret.txt")).unwrap(),
+            "a\n"
+        );
+    }
+
+    #[test]
+    fn git_metadata_and_ralph_state_are_off_limits() {
+        for path in [
+            ".git/hooks/pre-commit",
+            "./.git/config",
+            "vendor/lib/.GIT/HEAD",
+            ".ralph/tasks.json",
+            ".ralph-auth/state.json",
+        ] {
+            assert!(check_path(path).is_err(), "{path}");
+        }
+        for path in [
+            "src/main.rs",
+            ".github/workflows/ci.yml",
+            "docs/.ralphrc.md",
+            ".gitignore",
+        ] {
+            assert!(check_path(path).is_ok(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! `--plan`: the agent proposes its change as a unified diff instead of
+//! writing files. Ralph undoes anything it wrote anyway, saves the diff to
+//! `.ralph/plans/`, and applies it only once a human accepts it at the prompt
+//! (or `--auto-approve` does). `y` applies, `n` rejects it as a failed
+//! attempt, `e` opens the saved patch in an editor and asks again, and `q`
+//! stops the run.
+
+use anyhow::Result;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use super::gate::{self, Answer, Reply};
+use super::patch;
+use crate::state::Task;
+
+/// The prompt section that replaces writing files with proposing a diff.
+pub const PROMPT_SECTION: &str = "## Plan mode
+
+Do not create, edit, or delete files in this iteration: Ralph discards any change you make to the working tree. Instead, propose the complete change as one unified diff in a single ```diff fenced block — paths relative to the repository root, `--- a/path` / `+++ b/path` headers (`/dev/null` for created or deleted files), and three lines of context around each hunk. A reviewer reads it, and Ralph applies it and runs the verification only once it is approved. You may read files and run read-only commands to work the change out.
+
+In the instructions below, \"implement\" means \"write the diff for\", and the completion token means the diff fully implements the task.
+
+";
+
+/// What became of the agent's proposal.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// Applied; the paths it wrote or removed.
+    Applied(Vec<String>),
+    /// Not applied, and why; the iteration fails.
+    Rejected(String),
+    /// The run stops here.
+    Quit,
+}
+
+/// The patch in the agent's reply: the last ```diff or ```patch block, or,
+/// without one, everything from the first `diff --git` or `--- ` line.
+pub fn extract(reply: &str) -> Option<String> {
+    let lines: Vec<&str> = reply.lines().collect();
+    let fenced = lines
+        .iter()
+        .rposition(|l| matches!(l.trim(), "```diff" | "```patch"))
+        .map(|start| {
+            lines[start + 1..]
+                .iter()
+                .take_while(|l| l.trim() != "```")
+                .copied()
+                .collect::<Vec<_>>()
+        });
+    let body = fenced.or_else(|| {
+        let start = lines
+            .iter()
+            .position(|l| l.starts_with("diff --git ") || l.starts_with("--- "))?;
+        Some(lines[start..].to_vec())
+    })?;
+    let text = body.join("\n") + "\n";
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Save the patch in `reply` to `plan_file`, get it approved, and apply it.
+pub async fn review(
+    workdir: &Path,
+    task: &Task,
+    reply: &str,
+    plan_file: &Path,
+    auto_approve: bool,
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<Outcome> {
+    let Some(proposal) = extract(reply) else {
+        return Ok(Outcome::Rejected(
+            "No patch in the agent's reply (expected a ```diff block)".to_string(),
+        ));
+    };
+    if let Some(dir) = plan_file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(plan_file, &proposal)?;
+
+    loop {
+        // Re-read each time: `e` may have changed it.
+        let text = std::fs::read_to_string(plan_file)?;
+        let checked = patch::parse(&text).and_then(|patches| {
+            patch::check(workdir, &patches)?;
+            Ok(patches)
+        });
+        if auto_approve {
+            return Ok(match checked {
+                Ok(patches) => Outcome::Applied(patch::apply(workdir, &patches)?),
+                Err(e) => Outcome::Rejected(format!("Plan does not apply: {e:#}")),
+            });
+        }
+
+        println!("\n    ── Plan for task {} — {} ──", task.id, task.title);
+        gate::print_diff(&text, &plan_file.display().to_string());
+        match &checked {
+            Ok(patches) => {
+                let paths: Vec<&str> = patches.iter().map(patch::FilePatch::path).collect();
+                println!("\n    Applies cleanly to {}", paths.join(", "));
+            }
+            Err(e) => println!("\n    ⚠️   Does not apply: {e:#}"),
+        }
+        let question = format!(
+            "Apply the plan for task {}? [y]es, [n]o (reject), [e]dit, [q]uit",
+            task.id
+        );
+        match gate::read_reply(&question, cancel.clone()).await? {
+            Some(Reply::Answer(Answer::Accept)) => match checked {
+                Ok(patches) => return Ok(Outcome::Applied(patch::apply(workdir, &patches)?)),
+                Err(_) => println!("    Edit the patch until it applies, or answer n."),
+            },
+            Some(Reply::Answer(Answer::Reject)) => {
+                return Ok(Outcome::Rejected(
+                    "Plan rejected at the --plan prompt".to_string(),
+                ))
+            }
+            Some(Reply::Answer(Answer::Quit)) => return Ok(Outcome::Quit),
+            Some(Reply::Edit) => {
+                if let Err(e) = crate::open::edit(&[plan_file.to_path_buf()]) {
+                    eprintln!("    ⚠️   {e:#}");
+                }
+            }
+            None => println!("    Answer y, n, e, or q."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn extracts_the_last_fenced_diff() {
+        let reply = "\
+First attempt:
+```diff
+--- a/x
++++ b/x
+```
+Actually:
+```diff
+--- a/lib.rs
++++ b/lib.rs
+@@ -1 +1 @@
+-old
++new
+```
+<promise>COMPLETE</promise>
+";
+        let patch = extract(reply).expect("a patch");
+        assert!(patch.starts_with("--- a/lib.rs\n"), "{patch}");
+        assert!(patch.ends_with("+new\n"), "{patch}");
+
+        let bare = "Here:\ndiff --git a/x b/x\n--- a/x\n+++ b/x\n";
+        assert!(extract(bare).unwrap().starts_with("diff --git"));
+        assert_eq!(extract("I changed nothing."), None);
+    }
+
+    #[tokio::test]
+    async fn auto_approve_applies_a_patch_that_fits() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        fs::write(root.join("lib.rs"), "old\n").expect("seed lib");
+        let task = Task {
+            id: "T1".to_string(),
+            title: "Rename".to_string(),
+            priority: 1,
+            status: crate::state::TaskStatus::InProgress,
+            ..Default::default()
+        };
+        let plan_file = root.join(".ralph/plans/iteration-1-T1.patch");
+        let reply = "```diff\n--- a/lib.rs\n+++ b/lib.rs\n@@ -1 +1 @@\n-old\n+new\n```\n";
+
+        let outcome = review(root, &task, reply, &plan_file, true, None)
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::Applied(vec!["lib.rs".to_string()]));
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "new\n");
+        assert!(plan_file.exists());
+
+        let outcome = review(root, &task, reply, &plan_file, true, None)
+            .await
+            .unwrap();
+        assert!(
+            matches!(&outcome, Outcome::Rejected(why) if why.starts_with("Plan does not apply")),
+            "{outcome:?}"
+        );
+        let outcome = review(root, &task, "done", &plan_file, true, None)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Outcome::Rejected(_)));
+    }
+}
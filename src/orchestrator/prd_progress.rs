@@ -0,0 +1,131 @@
+//! `--annotate-prd`: keep a machine-managed "## Ralph progress" checklist at
+//! the end of the PRD, so the file itself shows how far the run has got.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::state::{TaskList, TaskStatus};
+
+const START: &str = "<!-- ralph:progress:start — managed by ralph, edits are overwritten -->";
+const END: &str = "<!-- ralph:progress:end -->";
+
+/// `content` without the managed section, so it never reaches the agent's prompt.
+pub fn strip(content: &str) -> String {
+    let Some(start) = content.find(START) else {
+        return content.to_string();
+    };
+    let end = content[start..]
+        .find(END)
+        .map_or(content.len(), |i| start + i + END.len());
+    let mut out = content[..start].trim_end().to_string();
+    let rest = content[end..].trim_start_matches(['\r', '\n']);
+    if !rest.is_empty() {
+        out.push_str("\n\n");
+        out.push_str(rest);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Rewrite the managed section of `prd_path` to match `task_list`. The file is
+/// only touched when the section changes.
+pub fn annotate(prd_path: &Path, task_list: &TaskList) -> Result<()> {
+    let content = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Cannot read PRD: {}", prd_path.display()))?;
+    let mut updated = strip(&content).trim_end().to_string();
+    updated.push_str("\n\n");
+    updated.push_str(&section(task_list));
+    if updated != content {
+        std::fs::write(prd_path, updated)
+            .with_context(|| format!("Failed to update {}", prd_path.display()))?;
+    }
+    Ok(())
+}
+
+fn section(task_list: &TaskList) -> String {
+    let done = task_list
+        .tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Complete)
+        .count();
+    let mut out = format!(
+        "{START}\n## Ralph progress\n\n{done}/{} tasks complete\n\n",
+        task_list.tasks.len()
+    );
+    for task in &task_list.tasks {
+        let check = if task.status == TaskStatus::Complete {
+            "x"
+        } else {
+            " "
+        };
+        out.push_str(&format!("- [{check}] {} — {}", task.id, task.title));
+        if !matches!(task.status, TaskStatus::Pending | TaskStatus::Complete) {
+            out.push_str(&format!(" ({})", task.status));
+        }
+        out.push('\n');
+    }
+    out.push_str(END);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Task;
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn task_list(statuses: &[TaskStatus]) -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: statuses
+                .iter()
+                .enumerate()
+                .map(|(i, status)| Task {
+                    id: format!("T{}", i + 1),
+                    title: format!("Task {}", i + 1),
+                    priority: i as u32 + 1,
+                    status: status.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn annotate_replaces_its_section_and_strip_removes_it() {
+        let dir = tempdir().expect("create tempdir");
+        let prd = dir.path().join("prd.md");
+        std::fs::write(&prd, "# Auth\n\nBuild login.\n").expect("write prd");
+
+        annotate(
+            &prd,
+            &task_list(&[TaskStatus::Complete, TaskStatus::Pending]),
+        )
+        .expect("first");
+        annotate(
+            &prd,
+            &task_list(&[TaskStatus::Complete, TaskStatus::Failed]),
+        )
+        .expect("second");
+
+        let content = std::fs::read_to_string(&prd).expect("read prd");
+        assert_eq!(content.matches("## Ralph progress").count(), 1);
+        assert!(content.contains("1/2 tasks complete"));
+        assert!(content.contains("- [x] T1 — Task 1\n- [ ] T2 — Task 2 (failed)\n"));
+        assert_eq!(strip(&content), "# Auth\n\nBuild login.\n");
+    }
+
+    #[test]
+    fn strip_keeps_text_after_the_section() {
+        let content = format!("# PRD\n\n{START}\nold\n{END}\n\n## Notes\nKeep me\n");
+        assert_eq!(strip(&content), "# PRD\n\n## Notes\nKeep me\n");
+        assert_eq!(strip("# Untouched\n"), "# Untouched\n");
+    }
+}
@@ -0,0 +1,291 @@
+//! The `## Repository` section of the iteration prompt: the tracked files as
+//! a tree, collapsed below `[context] tree_depth`, and the patches of the
+//! latest commits, so the agent starts out knowing the layout and what just
+//! changed instead of spending its first turns exploring.
+//!
+//! Git repositories only. Both parts are capped by `[context]`, and under
+//! `--max-prompt-tokens` the section also keeps to a share of the budget,
+//! giving up the patches before the tree.
+
+use std::collections::BTreeMap;
+
+use super::context::estimate_tokens;
+use crate::config::ContextConfig;
+use crate::git::GitManager;
+
+const DEFAULT_TREE_DEPTH: usize = 2;
+const DEFAULT_MAX_TREE_ENTRIES: usize = 200;
+const DEFAULT_COMMITS: usize = 3;
+const DEFAULT_MAX_DIFF_LINES: usize = 300;
+
+/// Share of `--max-prompt-tokens` the section may take.
+const BUDGET_SHARE: usize = 5;
+
+/// What the section shows, gathered once per iteration.
+#[derive(Debug, PartialEq)]
+pub struct RepoContext {
+    /// Tracked files, Ralph's state left out.
+    files: usize,
+    depth: usize,
+    tree: Vec<String>,
+    commits: usize,
+    /// The patches, already cut to `max_diff_lines`.
+    diff: Vec<String>,
+}
+
+/// The section's contents, or `None` outside git or with `enabled = false`.
+pub async fn gather(git: &GitManager, config: &ContextConfig) -> Option<RepoContext> {
+    if !config.enabled.unwrap_or(true) || !git.is_git_repo().await {
+        return None;
+    }
+    let depth = config.tree_depth.unwrap_or(DEFAULT_TREE_DEPTH).max(1);
+    let files: Vec<String> = git
+        .tracked_files()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| !path.starts_with(".ralph"))
+        .collect();
+    let tree = tree_lines(
+        &files,
+        depth,
+        config.max_tree_entries.unwrap_or(DEFAULT_MAX_TREE_ENTRIES),
+    );
+
+    let commits = config.commits.unwrap_or(DEFAULT_COMMITS);
+    let max_diff_lines = config.max_diff_lines.unwrap_or(DEFAULT_MAX_DIFF_LINES);
+    let patches = match commits {
+        0 => String::new(),
+        // A repository with no commits yet has no log.
+        n => git.recent_patches(n).await.unwrap_or_default(),
+    };
+    let mut diff: Vec<String> = patches.lines().map(str::to_string).collect();
+    if diff.len() > max_diff_lines {
+        let cut = diff.len() - max_diff_lines;
+        diff.truncate(max_diff_lines);
+        diff.push(format!(
+            "… {cut} more lines; run `git log -p -{commits}` for the rest"
+        ));
+    }
+
+    if files.is_empty() && diff.is_empty() {
+        return None;
+    }
+    Some(RepoContext {
+        files: files.len(),
+        depth,
+        tree,
+        commits,
+        diff,
+    })
+}
+
+impl RepoContext {
+    /// The prompt section, ending in a blank line. With a `budget`, the
+    /// patches and then the tree are cut to fit its share.
+    pub fn render(&self, budget: Option<usize>) -> String {
+        let Some(budget) = budget else {
+            return self.section(self.tree.len(), self.diff.len());
+        };
+        let room = budget / BUDGET_SHARE;
+        let fits = |tree: usize, diff: usize| estimate_tokens(&self.section(tree, diff)) <= room;
+        if fits(self.tree.len(), self.diff.len()) {
+            return self.section(self.tree.len(), self.diff.len());
+        }
+        let diff = (0..self.diff.len())
+            .rev()
+            .find(|&n| fits(self.tree.len(), n))
+            .unwrap_or(0);
+        if diff > 0 || fits(self.tree.len(), 0) {
+            return self.section(self.tree.len(), diff);
+        }
+        let tree = (0..self.tree.len())
+            .rev()
+            .find(|&n| fits(n, 0))
+            .unwrap_or(0);
+        self.section(tree, 0)
+    }
+
+    /// The section with the first `tree` tree lines and `diff` patch lines.
+    fn section(&self, tree: usize, diff: usize) -> String {
+        let mut out = String::from("## Repository\n\n");
+        if tree > 0 {
+            let mut lines = self.tree[..tree].to_vec();
+            if tree < self.tree.len() {
+                lines.push("… (cut to fit the prompt budget; run `git ls-files`)".to_string());
+            }
+            out.push_str(&format!(
+                "Tracked files ({} in total; directories below depth {} show a file count):\n\n{}\n",
+                self.files,
+                self.depth,
+                fenced("text", &lines.join("\n"))
+            ));
+        }
+        if diff > 0 {
+            let mut lines = self.diff[..diff].to_vec();
+            if diff < self.diff.len() {
+                lines.push(format!(
+                    "… (cut to fit the prompt budget; run `git log -p -{}`)",
+                    self.commits
+                ));
+            }
+            out.push_str(&format!(
+                "The latest {} commit(s), newest first:\n\n{}\n",
+                self.commits,
+                fenced("diff", &lines.join("\n"))
+            ));
+        }
+        out
+    }
+}
+
+/// `text` in a code fence long enough that fences inside it can't close it.
+pub(super) fn fenced(lang: &str, text: &str) -> String {
+    let mut fence = "```".to_string();
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+    format!("{fence}{lang}\n{text}\n{fence}\n")
+}
+
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    files: Vec<&'a str>,
+    /// Files anywhere below.
+    count: usize,
+}
+
+/// `files` as an indented tree. Directories `depth` levels down are shown
+/// as `name/ (N files)`; past `max_entries` lines the rest is counted.
+fn tree_lines(files: &[String], depth: usize, max_entries: usize) -> Vec<String> {
+    let mut root = Dir::default();
+    for path in files {
+        let mut parts: Vec<&str> = path.split('/').collect();
+        let name = parts.pop().unwrap_or_default();
+        let mut dir = &mut root;
+        for part in parts {
+            dir = dir.dirs.entry(part).or_default();
+            dir.count += 1;
+        }
+        dir.files.push(name);
+    }
+    let mut lines = Vec::new();
+    push_dir(&root, 0, depth, &mut lines);
+    if lines.len() > max_entries {
+        let more = lines.len() - max_entries;
+        lines.truncate(max_entries);
+        lines.push(format!("… {more} more entries"));
+    }
+    lines
+}
+
+fn push_dir(dir: &Dir, level: usize, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(level);
+    for (name, sub) in &dir.dirs {
+        if level + 1 >= depth {
+            let s = if sub.count == 1 { "" } else { "s" };
+            lines.push(format!("{indent}{name}/ ({} file{s})", sub.count));
+        } else {
+            lines.push(format!("{indent}{name}/"));
+            push_dir(sub, level + 1, depth, lines);
+        }
+    }
+    for file in &dir.files {
+        lines.push(format!("{indent}{file}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[test]
+    fn tree_collapses_deep_directories() {
+        let files = [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/orchestrator/mod.rs",
+            "src/orchestrator/plan.rs",
+            "src/git/mod.rs",
+        ]
+        .map(String::from);
+        assert_eq!(
+            tree_lines(&files, 2, 100),
+            [
+                "src/",
+                "  git/ (1 file)",
+                "  orchestrator/ (2 files)",
+                "  main.rs",
+                "Cargo.toml",
+            ]
+        );
+        assert_eq!(tree_lines(&files, 1, 100), ["src/ (4 files)", "Cargo.toml"]);
+        assert_eq!(
+            tree_lines(&files, 3, 2),
+            ["src/", "  git/", "… 6 more entries"]
+        );
+    }
+
+    #[tokio::test]
+    async fn gathers_files_and_recent_patches() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::create_dir_all(root.join("src")).expect("create src");
+        fs::write(root.join("src/lib.rs"), "fn one() {}\n").expect("write lib");
+        fs::create_dir_all(root.join(".ralph")).expect("create state");
+        fs::write(root.join(".ralph/progress.md"), "log\n").expect("state file");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+        fs::write(root.join("src/lib.rs"), "fn two() {}\n").expect("edit lib");
+        git(root, &["commit", "-q", "-am", "rename one"]);
+
+        let manager = GitManager::new(root);
+        let config = ContextConfig {
+            commits: Some(1),
+            ..Default::default()
+        };
+        let repo = gather(&manager, &config).await.expect("context");
+        let section = repo.render(None);
+        assert!(
+            section.starts_with("## Repository\n\nTracked files (1 in total"),
+            "{section}"
+        );
+        assert!(
+            section.contains("```text\nsrc/\n  lib.rs\n```"),
+            "{section}"
+        );
+        assert!(section.contains("commit "), "{section}");
+        assert!(section.contains(" rename one\n"), "{section}");
+        assert!(section.contains("+fn two() {}"), "{section}");
+        assert!(!section.contains("seed"), "{section}");
+        assert!(!section.contains(".ralph"), "{section}");
+
+        // A tight budget drops the patches before the tree.
+        let small = repo.render(Some(5 * estimate_tokens(&repo.section(repo.tree.len(), 0))));
+        assert!(small.contains("src/"), "{small}");
+        assert!(!small.contains("+fn two"), "{small}");
+
+        let off = ContextConfig {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(gather(&manager, &off).await, None);
+    }
+}
@@ -0,0 +1,169 @@
+//! The end of a failed attempt's log, kept in the task's `notes` so the
+//! retry's prompt shows the agent how the last attempt went wrong instead of
+//! handing it the same prompt to make the same mistake with.
+//!
+//! The note is a block at the end of `notes` starting with `Previous attempt
+//! failed with:`; whatever is written above it is left alone. The next
+//! failure replaces the block, and completing the task removes it.
+
+use std::path::Path;
+
+use super::repo_context::fenced;
+use crate::hooks;
+use crate::state::{Task, TaskList};
+
+const HEADER: &str = "Previous attempt failed with:";
+
+/// Log lines kept in the note (secrets masked, at most 4 KB).
+const TAIL_LINES: usize = 40;
+
+/// Replace the task's failure note with `reason` and the end of `log_path`.
+pub fn record(
+    task_list: &mut TaskList,
+    task_id: &str,
+    iteration: u32,
+    reason: &str,
+    log_path: &Path,
+) {
+    let Some(task) = task_list.tasks.iter_mut().find(|t| t.id == task_id) else {
+        return;
+    };
+    let mut block = format!("{HEADER} {} (iteration {iteration})", first_line(reason));
+    let tail = hooks::read_log_tail(log_path, TAIL_LINES)
+        .map(|tail| hooks::redacted(&tail))
+        .unwrap_or_default();
+    if !tail.trim().is_empty() {
+        block.push_str(&format!(
+            "\n\nThe end of its log:\n\n{}",
+            fenced("text", &tail)
+        ));
+    }
+    let own = own_notes(task.notes.as_deref().unwrap_or_default());
+    task.notes = Some(match own {
+        "" => block.trim_end().to_string(),
+        own => format!("{own}\n\n{}", block.trim_end()),
+    });
+}
+
+/// Remove the task's failure note, keeping anything else in `notes`.
+pub fn clear(task_list: &mut TaskList, task_id: &str) {
+    let Some(task) = task_list.tasks.iter_mut().find(|t| t.id == task_id) else {
+        return;
+    };
+    let own = own_notes(task.notes.as_deref().unwrap_or_default());
+    task.notes = (!own.is_empty()).then(|| own.to_string());
+}
+
+/// The prompt section for the task's failure note, or nothing.
+pub fn prompt_section(task: &Task) -> String {
+    let Some(note) = task
+        .notes
+        .as_deref()
+        .and_then(|notes| notes.find(HEADER).map(|at| &notes[at + HEADER.len()..]))
+    else {
+        return String::new();
+    };
+    format!(
+        "\n**{HEADER}**{note}\n\nWork out what went wrong before trying again; don't repeat it.\n"
+    )
+}
+
+/// `notes` without the failure note.
+fn own_notes(notes: &str) -> &str {
+    let own = match notes.find(HEADER) {
+        Some(at) => &notes[..at],
+        None => notes,
+    };
+    own.trim_end()
+}
+
+fn first_line(reason: &str) -> &str {
+    reason.lines().next().unwrap_or_default().trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn task_list(notes: Option<&str>) -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: vec![Task {
+                id: "T1".to_string(),
+                title: "Parse config".to_string(),
+                priority: 1,
+                notes: notes.map(str::to_string),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn the_log_tail_goes_into_notes_and_the_next_prompt() {
+        let dir = tempdir().expect("create tempdir");
+        let log = dir.path().join("iter-1.log");
+        let lines: Vec<String> = (0..50).map(|i| format!("line {i}")).collect();
+        std::fs::write(
+            &log,
+            lines.join("\n") + "\nerror[E0308]: mismatched types\n",
+        )
+        .expect("write log");
+
+        let mut list = task_list(Some("Keep the old parser around."));
+        record(
+            &mut list,
+            "T1",
+            3,
+            "Agent did not claim completion\nmore",
+            &log,
+        );
+        let notes = list.tasks[0].notes.clone().unwrap();
+        assert!(notes.starts_with(
+            "Keep the old parser around.\n\nPrevious attempt failed with: \
+             Agent did not claim completion (iteration 3)\n\nThe end of its log:\n\n```text\n"
+        ));
+        assert!(
+            notes.contains("error[E0308]: mismatched types\n```"),
+            "{notes}"
+        );
+        assert!(
+            notes.contains("line 11\n") && !notes.contains("line 10\n"),
+            "{notes}"
+        );
+
+        let section = prompt_section(&list.tasks[0]);
+        assert!(section.starts_with(
+            "\n**Previous attempt failed with:** Agent did not claim completion (iteration 3)"
+        ));
+        assert!(!section.contains("old parser"), "{section}");
+
+        // A later failure replaces the note; completing the task removes it.
+        record(
+            &mut list,
+            "T1",
+            4,
+            "timed out",
+            &dir.path().join("missing.log"),
+        );
+        assert_eq!(
+            list.tasks[0].notes.as_deref(),
+            Some("Keep the old parser around.\n\nPrevious attempt failed with: timed out (iteration 4)")
+        );
+        clear(&mut list, "T1");
+        assert_eq!(
+            list.tasks[0].notes.as_deref(),
+            Some("Keep the old parser around.")
+        );
+        assert_eq!(prompt_section(&list.tasks[0]), "");
+
+        let mut bare = task_list(None);
+        record(&mut bare, "T1", 1, "timed out", &log);
+        clear(&mut bare, "T1");
+        assert_eq!(bare.tasks[0].notes, None);
+    }
+}
@@ -0,0 +1,161 @@
+//! `[scheduler]`: the last iterations of a run are kept for its most
+//! important tasks, so low-priority work can't use up the budget while
+//! critical tasks are still unfinished.
+
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+use crate::config::SchedulerConfig;
+use crate::state::{TaskList, TaskStatus};
+
+pub struct Reserve {
+    /// Tasks at this priority or higher (a lower number) are reserved for.
+    max_priority: u32,
+    /// Fixed size of the reserve; one iteration per reserved task if unset.
+    iterations: Option<u32>,
+}
+
+impl Reserve {
+    /// The configured reserve, if `reserve_for_priority` is set.
+    pub fn new(config: &SchedulerConfig) -> Result<Option<Self>> {
+        let Some(max_priority) = config.reserve_for_priority else {
+            if config.reserve_iterations.is_some() {
+                bail!("[scheduler] reserve_iterations needs reserve_for_priority");
+            }
+            return Ok(None);
+        };
+        if max_priority == 0 {
+            bail!("[scheduler] reserve_for_priority must be 1 or more");
+        }
+        Ok(Some(Self {
+            max_priority,
+            iterations: config.reserve_iterations,
+        }))
+    }
+
+    /// E.g. `last 3 iterations for priority 1–2 tasks`.
+    pub fn describe(&self) -> String {
+        let size = match self.iterations {
+            Some(n) => format!("last {n} iterations"),
+            None => "one iteration per unfinished task".to_string(),
+        };
+        match self.max_priority {
+            1 => format!("{size} for priority 1 tasks"),
+            p => format!("{size} for priority 1–{p} tasks"),
+        }
+    }
+
+    /// The tasks the next iteration is limited to, once only the reserve is
+    /// left; `None` while any task may run.
+    ///
+    /// Reserved tasks are the unfinished ones at the reserved priorities and
+    /// everything they wait on, whatever its own priority.
+    pub fn limit(&self, task_list: &TaskList, iterations_left: u32) -> Option<HashSet<String>> {
+        let unfinished =
+            |status: &TaskStatus| matches!(status, TaskStatus::Pending | TaskStatus::InProgress);
+        let mut reserved: HashSet<String> = HashSet::new();
+        let mut queue: Vec<&str> = task_list
+            .tasks
+            .iter()
+            .filter(|t| t.priority <= self.max_priority && unfinished(&t.status))
+            .map(|t| t.id.as_str())
+            .collect();
+        while let Some(id) = queue.pop() {
+            if !reserved.insert(id.to_string()) {
+                continue;
+            }
+            let Some(task) = task_list.tasks.iter().find(|t| t.id == id) else {
+                continue;
+            };
+            queue.extend(
+                task.depends_on
+                    .iter()
+                    .filter(|dep| {
+                        task_list
+                            .tasks
+                            .iter()
+                            .any(|t| &t.id == *dep && unfinished(&t.status))
+                    })
+                    .map(String::as_str),
+            );
+        }
+        if reserved.is_empty() {
+            return None;
+        }
+        let size = self.iterations.unwrap_or(reserved.len() as u32);
+        (iterations_left <= size).then_some(reserved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Task;
+    use chrono::Utc;
+
+    fn task(id: &str, priority: u32, depends_on: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            title: format!("{id} title"),
+            priority,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn list(tasks: Vec<Task>) -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks,
+        }
+    }
+
+    #[test]
+    fn reserve_covers_important_tasks_and_what_they_wait_on() {
+        let reserve = Reserve::new(&SchedulerConfig {
+            reserve_for_priority: Some(1),
+            reserve_iterations: None,
+        })
+        .unwrap()
+        .expect("reserve configured");
+        let mut tasks = list(vec![
+            task("T1", 1, &["T3"]),
+            task("T2", 2, &[]),
+            task("T3", 3, &[]),
+        ]);
+        // T1 and the T3 it waits on: two iterations.
+        assert_eq!(reserve.limit(&tasks, 3), None);
+        let limit = reserve.limit(&tasks, 2).expect("reserve applies");
+        assert_eq!(limit, HashSet::from(["T1".to_string(), "T3".to_string()]));
+
+        // With nothing important left, the last iterations are anyone's.
+        tasks.tasks[0].status = TaskStatus::Complete;
+        assert_eq!(reserve.limit(&tasks, 1), None);
+    }
+
+    #[test]
+    fn fixed_reserve_needs_a_priority() {
+        let config = SchedulerConfig {
+            reserve_for_priority: None,
+            reserve_iterations: Some(3),
+        };
+        assert!(Reserve::new(&config).is_err());
+        assert!(Reserve::new(&SchedulerConfig::default()).unwrap().is_none());
+        let reserve = Reserve::new(&SchedulerConfig {
+            reserve_for_priority: Some(2),
+            reserve_iterations: Some(3),
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            reserve.describe(),
+            "last 3 iterations for priority 1–2 tasks"
+        );
+        let tasks = list(vec![task("T1", 2, &[]), task("T2", 4, &[])]);
+        assert!(reserve.limit(&tasks, 3).is_some());
+        assert!(reserve.limit(&tasks, 4).is_none());
+    }
+}
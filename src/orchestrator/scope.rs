@@ -0,0 +1,223 @@
+//! Post-iteration safety check: edits outside the `--project` subproject
+//! (unless they touch an allowed shared path) and edits to `.ralphignore`d
+//! paths are reverted.
+
+use std::path::{Path, PathBuf};
+
+use crate::git::{ChangedPath, GitManager};
+use crate::ignore::RalphIgnore;
+
+/// Shared files at the workdir root that package managers rewrite from inside
+/// any subproject. Always allowed.
+const SHARED_LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "yarn.lock",
+    "bun.lockb",
+    "go.work.sum",
+    "poetry.lock",
+    "uv.lock",
+];
+
+pub struct ScopeGuard {
+    project: PathBuf,
+    allowed: Vec<PathBuf>,
+    ignore: RalphIgnore,
+}
+
+/// Outcome of one enforcement pass.
+#[derive(Debug, Default)]
+pub struct ScopeReport {
+    /// Out-of-scope paths the agent changed.
+    pub violations: Vec<String>,
+    /// Subset of `violations` protected by `.ralphignore`.
+    pub ignored: Vec<String>,
+    /// Violations that could not be reverted, with the reason.
+    pub unreverted: Vec<(String, String)>,
+}
+
+impl ScopeReport {
+    /// E.g. `outside packages/api/: a.ts; listed in .ralphignore: vendor/x.go`.
+    pub fn summary(&self, project: &Path) -> String {
+        let (ignored, outside): (Vec<&str>, Vec<&str>) = self
+            .violations
+            .iter()
+            .map(String::as_str)
+            .partition(|p| self.ignored.iter().any(|i| i == p));
+        let mut parts = Vec::new();
+        if !outside.is_empty() {
+            parts.push(format!(
+                "outside {}/: {}",
+                project.display(),
+                outside.join(", ")
+            ));
+        }
+        if !ignored.is_empty() {
+            parts.push(format!("listed in .ralphignore: {}", ignored.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+impl ScopeGuard {
+    /// `project` and `allow` are relative to the workdir; "." means no `--project`.
+    pub fn new(project: &Path, allow: &[PathBuf], ignore: RalphIgnore) -> Self {
+        Self {
+            project: project.to_path_buf(),
+            allowed: allow.to_vec(),
+            ignore,
+        }
+    }
+
+    pub fn project(&self) -> &Path {
+        &self.project
+    }
+
+    /// Whether the agent may change the workdir-relative `path`.
+    pub fn permits(&self, path: &str) -> bool {
+        if self.ignore.matches(path) {
+            return false;
+        }
+        let path = Path::new(path);
+        if self.project == Path::new(".") || path.starts_with(&self.project) {
+            return true;
+        }
+        // Ralph's own state directories (`.ralph/`, `.ralph-<name>/`).
+        let in_state_dir = path
+            .components()
+            .next()
+            .is_some_and(|c| c.as_os_str().to_string_lossy().starts_with(".ralph"));
+        in_state_dir
+            || SHARED_LOCKFILES.iter().any(|f| path == Path::new(f))
+            || self.allowed.iter().any(|a| path.starts_with(a))
+    }
+
+    /// Revert out-of-scope changes made since `before` was captured. Paths that
+    /// were already dirty before the iteration belong to the user and are left alone.
+    pub async fn enforce(&self, git: &GitManager, before: &[ChangedPath]) -> ScopeReport {
+        let mut report = ScopeReport::default();
+        let Ok(after) = git.changed_paths().await else {
+            return report;
+        };
+
+        for change in after {
+            if self.permits(&change.path) || before.iter().any(|b| b.path == change.path) {
+                continue;
+            }
+            if let Err(e) = git.revert_path(&change).await {
+                report.unreverted.push((change.path.clone(), e.to_string()));
+            }
+            if self.ignore.matches(&change.path) {
+                report.ignored.push(change.path.clone());
+            }
+            report.violations.push(change.path);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn git(workdir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(workdir)
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {} failed", args.join(" "));
+    }
+
+    #[test]
+    fn permits_project_state_lockfiles_and_allowed_paths() {
+        let guard = ScopeGuard::new(
+            Path::new("packages/api"),
+            &[PathBuf::from("shared/types")],
+            RalphIgnore::default(),
+        );
+
+        assert!(guard.permits("packages/api/src/lib.rs"));
+        assert!(guard.permits(".ralph/tasks.json"));
+        assert!(guard.permits(".ralph-auth/progress.md"));
+        assert!(guard.permits("Cargo.lock"));
+        assert!(guard.permits("shared/types/user.ts"));
+
+        assert!(!guard.permits("packages/web/src/main.ts"));
+        assert!(!guard.permits("packages/api-old/lib.rs"));
+        assert!(!guard.permits("packages/web/Cargo.lock"));
+
+        assert!(
+            ScopeGuard::new(Path::new("."), &[], RalphIgnore::default()).permits("anything.txt")
+        );
+    }
+
+    #[test]
+    fn ralphignore_overrides_project_and_allowed_paths() {
+        let ignore = RalphIgnore::parse("packages/api/generated/\n*.lock\n").expect("parse");
+        let guard = ScopeGuard::new(Path::new("packages/api"), &[], ignore);
+        assert!(guard.permits("packages/api/src/lib.rs"));
+        assert!(!guard.permits("packages/api/generated/client.ts"));
+        assert!(!guard.permits("Cargo.lock"));
+        assert!(!guard.permits(".ralphignore"));
+
+        let report = ScopeReport {
+            violations: vec!["web/app.ts".to_string(), ".ralphignore".to_string()],
+            ignored: vec![".ralphignore".to_string()],
+            unreverted: vec![],
+        };
+        assert_eq!(
+            report.summary(guard.project()),
+            "outside packages/api/: web/app.ts; listed in .ralphignore: .ralphignore"
+        );
+    }
+
+    #[tokio::test]
+    async fn enforce_reverts_new_out_of_scope_changes_only() {
+        let dir = tempdir().expect("create tempdir");
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Ralph Test"]);
+        git(root, &["config", "user.email", "ralph-test@example.com"]);
+        fs::create_dir_all(root.join("packages/api")).expect("create api");
+        fs::create_dir_all(root.join("packages/web")).expect("create web");
+        fs::write(root.join("packages/web/app.ts"), "v1\n").expect("seed web");
+        fs::write(root.join("notes.md"), "v1\n").expect("seed notes");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "seed"]);
+
+        // The user already had notes.md dirty before the iteration.
+        fs::write(root.join("notes.md"), "user edit\n").expect("user edit");
+        let manager = GitManager::new(root);
+        let before = manager.changed_paths().await.expect("before");
+
+        // The agent edits in and out of scope.
+        fs::write(root.join("packages/api/lib.rs"), "api\n").expect("in scope");
+        fs::write(root.join("packages/web/app.ts"), "v2\n").expect("out of scope");
+        fs::write(root.join("packages/web/extra.ts"), "new\n").expect("out of scope new");
+
+        let guard = ScopeGuard::new(Path::new("packages/api"), &[], RalphIgnore::default());
+        let mut report = guard.enforce(&manager, &before).await;
+        report.violations.sort();
+
+        assert_eq!(
+            report.violations,
+            vec!["packages/web/app.ts", "packages/web/extra.ts"]
+        );
+        assert!(report.unreverted.is_empty());
+        assert_eq!(
+            fs::read_to_string(root.join("packages/web/app.ts")).expect("read"),
+            "v1\n"
+        );
+        assert!(!root.join("packages/web/extra.ts").exists());
+        assert!(root.join("packages/api/lib.rs").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("notes.md")).expect("read"),
+            "user edit\n"
+        );
+    }
+}
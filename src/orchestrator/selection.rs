@@ -0,0 +1,183 @@
+//! `--only`, `--from`, and `--skip`: run part of the task list. A selected
+//! task still waits for its dependencies, so each one must be complete
+//! already or selected too; otherwise the run would stall on it.
+
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+use crate::state::{TaskList, TaskStatus};
+
+#[derive(Debug)]
+pub struct Selection {
+    ids: HashSet<String>,
+}
+
+impl Selection {
+    /// The tasks the flags select, or `None` when none was given.
+    pub fn new(
+        task_list: &TaskList,
+        only: &[String],
+        from: Option<&str>,
+        skip: &[String],
+    ) -> Result<Option<Self>> {
+        if only.is_empty() && from.is_none() && skip.is_empty() {
+            return Ok(None);
+        }
+        let known: HashSet<&str> = task_list.tasks.iter().map(|t| t.id.as_str()).collect();
+        for (flag, id) in only
+            .iter()
+            .map(|id| ("--only", id.as_str()))
+            .chain(from.map(|id| ("--from", id)))
+            .chain(skip.iter().map(|id| ("--skip", id.as_str())))
+        {
+            if !known.contains(id) {
+                bail!("{flag}: no task {id} in the task list");
+            }
+        }
+
+        let start = from
+            .and_then(|id| task_list.tasks.iter().position(|t| t.id == id))
+            .unwrap_or(0);
+        let ids: HashSet<String> = task_list.tasks[start..]
+            .iter()
+            .filter(|t| only.is_empty() || only.contains(&t.id))
+            .filter(|t| !skip.contains(&t.id))
+            .map(|t| t.id.clone())
+            .collect();
+        if ids.is_empty() {
+            bail!("--only, --from, and --skip leave no tasks to run");
+        }
+
+        let complete: HashSet<&str> = task_list
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Complete)
+            .map(|t| t.id.as_str())
+            .collect();
+        for task in task_list
+            .tasks
+            .iter()
+            .filter(|t| ids.contains(&t.id) && t.status != TaskStatus::Complete)
+        {
+            if let Some(dep) = task
+                .depends_on
+                .iter()
+                .find(|dep| !complete.contains(dep.as_str()) && !ids.contains(*dep))
+            {
+                bail!(
+                    "{} depends on {dep}, which is neither complete nor selected",
+                    task.id
+                );
+            }
+        }
+        Ok(Some(Self { ids }))
+    }
+
+    /// The tasks the next iteration may pick from: the selection, cut down
+    /// to `reserved` when `[scheduler]` has kept the iteration for those.
+    pub fn narrow(&self, reserved: Option<&HashSet<String>>) -> HashSet<String> {
+        match reserved {
+            Some(reserved) => self.ids.intersection(reserved).cloned().collect(),
+            None => self.ids.clone(),
+        }
+    }
+
+    /// Whether every selected task is complete.
+    pub fn finished(&self, task_list: &TaskList) -> bool {
+        task_list
+            .tasks
+            .iter()
+            .filter(|t| self.ids.contains(&t.id))
+            .all(|t| t.status == TaskStatus::Complete)
+    }
+
+    /// The selected IDs in tasks.json order, e.g. `T3, T5`.
+    pub fn describe(&self, task_list: &TaskList) -> String {
+        task_list
+            .tasks
+            .iter()
+            .filter(|t| self.ids.contains(&t.id))
+            .map(|t| t.id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Task;
+    use chrono::Utc;
+
+    fn list(tasks: &[(&str, &[&str], TaskStatus)]) -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks: tasks
+                .iter()
+                .map(|(id, deps, status)| Task {
+                    id: id.to_string(),
+                    title: format!("Task {id}"),
+                    priority: 1,
+                    status: status.clone(),
+                    depends_on: deps.iter().map(|d| d.to_string()).collect(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_select_tasks_in_list_order() {
+        let tasks = list(&[
+            ("T1", &[], TaskStatus::Complete),
+            ("T2", &[], TaskStatus::Pending),
+            ("T3", &["T1"], TaskStatus::Pending),
+            ("T4", &["T3"], TaskStatus::Pending),
+            ("T5", &[], TaskStatus::Pending),
+        ]);
+        assert!(Selection::new(&tasks, &[], None, &[]).unwrap().is_none());
+
+        let only = Selection::new(&tasks, &ids(&["T5", "T3"]), None, &[])
+            .unwrap()
+            .unwrap();
+        assert_eq!(only.describe(&tasks), "T3, T5");
+
+        let from = Selection::new(&tasks, &[], Some("T3"), &ids(&["T5"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(from.describe(&tasks), "T3, T4");
+        assert!(!from.finished(&tasks));
+        let reserved = HashSet::from(["T4".to_string(), "T2".to_string()]);
+        assert_eq!(
+            from.narrow(Some(&reserved)),
+            HashSet::from(["T4".to_string()])
+        );
+    }
+
+    #[test]
+    fn selections_that_cannot_run_are_rejected() {
+        let tasks = list(&[
+            ("T1", &[], TaskStatus::Complete),
+            ("T2", &[], TaskStatus::Pending),
+            ("T3", &["T1", "T2"], TaskStatus::Pending),
+        ]);
+        let err = Selection::new(&tasks, &ids(&["T3"]), None, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "T3 depends on T2, which is neither complete nor selected"
+        );
+        let err = Selection::new(&tasks, &[], None, &ids(&["T2"])).unwrap_err();
+        assert!(err.to_string().starts_with("T3 depends on T2"), "{err}");
+        let err = Selection::new(&tasks, &[], Some("T9"), &[]).unwrap_err();
+        assert_eq!(err.to_string(), "--from: no task T9 in the task list");
+        assert!(Selection::new(&tasks, &ids(&["T2"]), None, &ids(&["T2"])).is_err());
+        assert!(Selection::new(&tasks, &ids(&["T2", "T3"]), None, &[]).is_ok());
+    }
+}
@@ -0,0 +1,214 @@
+//! `--failure-snapshots`: when an iteration fails, bundle what it takes to
+//! debug it under `.ralph/failures/iteration-N/`, so a failure report can be
+//! acted on without reproducing the run.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+
+use crate::git::GitManager;
+use crate::output::is_credential;
+
+/// Directories never listed in `recent-files.txt`.
+pub(super) const SKIP_DIRS: &[&str] = &[".git", "node_modules", "target", "dist", "build", ".venv"];
+/// Bounds on the recent-files walk, so huge trees don't stall the loop.
+const MAX_SCANNED: usize = 20_000;
+const MAX_LISTED: usize = 50;
+
+/// The failed iteration being captured.
+pub struct Failure<'a> {
+    pub iteration: u32,
+    pub task_id: &'a str,
+    pub agent: &'a str,
+    pub model: Option<&'a str>,
+    pub error: &'a str,
+    pub prompt: &'a str,
+    pub log_path: &'a Path,
+    pub started_at: SystemTime,
+}
+
+/// Write the snapshot to `dir`, replacing one left by an earlier run. Parts that
+/// cannot be collected (e.g. git status outside a repository) record why instead.
+pub async fn capture(
+    dir: &Path,
+    workdir: &Path,
+    git: &GitManager,
+    failure: &Failure<'_>,
+) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).with_context(|| format!("Cannot clear {}", dir.display()))?;
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    let write = |name: &str, content: &str| {
+        std::fs::write(dir.join(name), content).with_context(|| format!("Failed to write {name}"))
+    };
+
+    write(
+        "error.txt",
+        &format!(
+            "Iteration {} — task {} ({})\n\n{}\n",
+            failure.iteration, failure.task_id, failure.agent, failure.error
+        ),
+    )?;
+    write("prompt.md", failure.prompt)?;
+    if failure.log_path.exists() {
+        std::fs::copy(failure.log_path, dir.join("iteration.log"))
+            .context("Failed to copy the iteration log")?;
+    }
+
+    let or_reason =
+        |result: Result<String>| result.unwrap_or_else(|e| format!("(unavailable: {e:#})\n"));
+    write("git-status.txt", &or_reason(git.status_summary().await))?;
+    write("git-diff.patch", &or_reason(git.diff_head().await))?;
+
+    let recent = recent_files(workdir, failure.started_at);
+    let mut listing = format!(
+        "Files modified since the iteration started ({}):\n",
+        recent.len()
+    );
+    for (path, age) in &recent {
+        listing.push_str(&format!("{:>6}s ago  {}\n", age.as_secs(), path.display()));
+    }
+    write("recent-files.txt", &listing)?;
+
+    write("env.txt", &fingerprint(workdir, failure).await)?;
+    Ok(())
+}
+
+/// Workdir-relative files modified at or after `since`, newest first.
+fn recent_files(workdir: &Path, since: SystemTime) -> Vec<(PathBuf, Duration)> {
+    let now = SystemTime::now();
+    let mut found = Vec::new();
+    let mut pending = vec![workdir.to_path_buf()];
+    let mut scanned = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            scanned += 1;
+            if scanned > MAX_SCANNED {
+                break;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                // Ralph's own state changes every iteration.
+                if !SKIP_DIRS.contains(&name.as_ref()) && !name.starts_with(".ralph") {
+                    pending.push(entry.path());
+                }
+                continue;
+            }
+            let Ok(modified) = meta.modified() else {
+                continue;
+            };
+            if modified >= since {
+                let rel = entry.path().strip_prefix(workdir).map(Path::to_path_buf);
+                let age = now.duration_since(modified).unwrap_or_default();
+                found.push((rel.unwrap_or_else(|_| entry.path()), age));
+            }
+        }
+    }
+    found.sort_by_key(|(_, age)| *age);
+    found.truncate(MAX_LISTED);
+    found
+}
+
+/// What the iteration ran with. Credentials are listed by name only.
+async fn fingerprint(workdir: &Path, failure: &Failure<'_>) -> String {
+    let agent_version = Command::new(failure.agent).arg("--version").output();
+    let agent_version = match tokio::time::timeout(Duration::from_secs(5), agent_version).await {
+        Ok(Ok(out)) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        _ => "unknown".to_string(),
+    };
+    let env = |name: &str| std::env::var(name).unwrap_or_default();
+    let mut credentials: Vec<String> = std::env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| is_credential(name))
+        .collect();
+    credentials.sort();
+
+    format!(
+        "ralph:          {}\n\
+         os:             {} ({})\n\
+         agent:          {} (model: {})\n\
+         agent version:  {agent_version}\n\
+         workdir:        {}\n\
+         shell:          {}\n\
+         PATH:           {}\n\
+         credentials:    {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        failure.agent,
+        failure.model.unwrap_or("default"),
+        workdir.display(),
+        env("SHELL"),
+        env("PATH"),
+        if credentials.is_empty() {
+            "none set".to_string()
+        } else {
+            credentials.join(", ")
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn credentials_are_recognised_by_name() {
+        assert!(is_credential("ANTHROPIC_API_KEY"));
+        assert!(is_credential("OPENCLAW_HOOKS_TOKEN"));
+        assert!(is_credential("aws_secret_access_key"));
+        assert!(!is_credential("PATH"));
+    }
+
+    #[tokio::test]
+    async fn capture_bundles_prompt_error_log_and_recent_files() {
+        let dir = tempdir().expect("create tempdir");
+        let workdir = dir.path();
+        let started_at = SystemTime::now() - Duration::from_secs(1);
+        std::fs::create_dir_all(workdir.join("src")).expect("create src");
+        std::fs::write(workdir.join("src/lib.rs"), "fn broken(\n").expect("write file");
+        std::fs::create_dir_all(workdir.join(".ralph/logs")).expect("create state");
+        let log_path = workdir.join(".ralph/logs/iteration-2-T3.log");
+        std::fs::write(&log_path, "=== EXIT CODE: Some(1) ===\n").expect("write log");
+
+        let snapshot = workdir.join(".ralph/failures/iteration-2");
+        let failure = Failure {
+            iteration: 2,
+            task_id: "T3",
+            agent: "ralph-test-missing-agent",
+            model: None,
+            error: "Agent exited with code Some(1)",
+            prompt: "Implement T3",
+            log_path: &log_path,
+            started_at,
+        };
+        capture(&snapshot, workdir, &GitManager::new(workdir), &failure)
+            .await
+            .expect("capture");
+
+        let read = |name: &str| std::fs::read_to_string(snapshot.join(name)).expect(name);
+        assert_eq!(read("prompt.md"), "Implement T3");
+        assert!(read("error.txt").contains("task T3 (ralph-test-missing-agent)"));
+        assert_eq!(read("iteration.log"), "=== EXIT CODE: Some(1) ===\n");
+        assert!(read("git-status.txt").starts_with("(unavailable:"));
+        let recent = read("recent-files.txt");
+        assert!(recent.contains("src/lib.rs"), "{recent}");
+        assert!(!recent.contains(".ralph"), "{recent}");
+        let env = read("env.txt");
+        assert!(env.contains("agent version:  unknown"));
+    }
+}
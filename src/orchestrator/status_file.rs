@@ -0,0 +1,130 @@
+//! `status.json` in the state directory: the loop's `LoopStatus`, rewritten
+//! whenever it changes, for `ralph status` and tools outside the process.
+//! The lock file only records what was true at the start of an iteration.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::state::{SharedLoopStatus, StatusSnapshot};
+
+/// Log lines kept in the file, as `--status-port` serves.
+const RECENT_EVENTS: usize = 50;
+
+/// How often the status is checked for changes.
+const POLL: Duration = Duration::from_millis(500);
+
+/// Keeps status.json current until dropped, and writes it one last time then
+/// so the final state (complete, stopped, failed) is what remains.
+pub struct StatusFile {
+    path: PathBuf,
+    status: SharedLoopStatus,
+    writer: JoinHandle<()>,
+}
+
+impl StatusFile {
+    pub fn spawn(path: PathBuf, status: SharedLoopStatus) -> Self {
+        let writer = tokio::spawn({
+            let (path, status) = (path.clone(), status.clone());
+            async move {
+                let mut last: Option<StatusSnapshot> = None;
+                loop {
+                    if let Some(snapshot) = snapshot(&status) {
+                        if last.as_ref().is_none_or(|l| changed(l, &snapshot)) {
+                            let _ = write(&path, &snapshot);
+                            last = Some(snapshot);
+                        }
+                    }
+                    tokio::time::sleep(POLL).await;
+                }
+            }
+        });
+        Self {
+            path,
+            status,
+            writer,
+        }
+    }
+}
+
+impl Drop for StatusFile {
+    fn drop(&mut self) {
+        self.writer.abort();
+        if let Some(snapshot) = snapshot(&self.status) {
+            let _ = write(&self.path, &snapshot);
+        }
+    }
+}
+
+fn snapshot(status: &SharedLoopStatus) -> Option<StatusSnapshot> {
+    Some(status.lock().ok()?.snapshot(RECENT_EVENTS))
+}
+
+/// The elapsed time ticks on its own; readers can work it out from the rest.
+fn changed(old: &StatusSnapshot, new: &StatusSnapshot) -> bool {
+    let elapsed = old.elapsed_secs;
+    *old != StatusSnapshot {
+        elapsed_secs: elapsed,
+        ..new.clone()
+    }
+}
+
+/// Replace `path` atomically, so readers never see half a file.
+pub fn write(path: &Path, snapshot: &StatusSnapshot) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let json = serde_json::to_string_pretty(snapshot).context("Failed to serialise status")?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .context("Failed to create temp file for status.json")?;
+    tmp.write_all(json.as_bytes())
+        .context("Failed to write temp status.json")?;
+    tmp.persist(path)
+        .map_err(|e| anyhow::anyhow!("Failed to atomically replace status.json: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{LogKind, LoopState, LoopStatus};
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn follows_the_status_and_keeps_the_final_state() {
+        let dir = tempdir().expect("create tempdir");
+        let path = dir.path().join("status.json");
+        let status = Arc::new(Mutex::new(LoopStatus::new(
+            "api".to_string(),
+            "api.md".to_string(),
+            "codex".to_string(),
+        )));
+        let file = StatusFile::spawn(path.clone(), status.clone());
+
+        {
+            let mut s = status.lock().unwrap();
+            s.state = LoopState::Running;
+            s.iteration = 3;
+            s.current_task = "T2: Add login".to_string();
+            s.push_log(LogKind::Status, "started".to_string());
+        }
+        let mut seen = None;
+        for _ in 0..20 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            seen = StatusSnapshot::load(&path).filter(|s| s.iteration == 3);
+            if seen.is_some() {
+                break;
+            }
+        }
+        let seen = seen.expect("status.json follows the live status");
+        assert_eq!(seen.current_task, "T2: Add login");
+        assert_eq!(seen.state, "running");
+        assert_eq!(seen.recent_events[0].text, "started");
+
+        status.lock().unwrap().state = LoopState::Complete;
+        drop(file);
+        let last = StatusSnapshot::load(&path).expect("final status.json");
+        assert_eq!(last.state, "complete");
+    }
+}
@@ -0,0 +1,186 @@
+//! `--snapshots`: a copy of the workdir taken before each iteration, so a
+//! project without git can still be rolled back when an iteration fails.
+//!
+//! Both directions are the same mirror operation: capturing mirrors the
+//! workdir into the snapshot, restoring mirrors the snapshot back. Only files
+//! whose size or mtime changed are copied, and `std::fs::copy` clones rather
+//! than copies where the filesystem supports it (reflinks on Btrfs/XFS,
+//! clonefile on APFS), so repeated snapshots stay cheap.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use super::snapshot::SKIP_DIRS;
+
+/// What one mirror pass changed in its destination.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncStats {
+    pub copied: usize,
+    pub removed: usize,
+}
+
+pub struct TreeSnapshot {
+    workdir: PathBuf,
+    dir: PathBuf,
+}
+
+impl TreeSnapshot {
+    pub fn new(workdir: &Path, dir: PathBuf) -> Self {
+        Self {
+            workdir: workdir.to_path_buf(),
+            dir,
+        }
+    }
+
+    /// Bring the snapshot up to date with the workdir.
+    pub fn capture(&self) -> Result<SyncStats> {
+        let mut stats = SyncStats::default();
+        mirror(&self.workdir, &self.dir, &mut stats)
+            .with_context(|| format!("Failed to snapshot into {}", self.dir.display()))?;
+        Ok(stats)
+    }
+
+    /// Put the workdir back the way it was at the last capture.
+    pub fn restore(&self) -> Result<SyncStats> {
+        anyhow::ensure!(self.dir.is_dir(), "No workdir snapshot to restore");
+        let mut stats = SyncStats::default();
+        mirror(&self.dir, &self.workdir, &mut stats)
+            .context("Failed to restore the workdir snapshot")?;
+        Ok(stats)
+    }
+}
+
+/// Ralph's state and bulky generated directories are neither captured nor
+/// touched on restore.
+fn skipped(name: &OsString) -> bool {
+    let name = name.to_string_lossy();
+    name.starts_with(".ralph") || SKIP_DIRS.contains(&name.as_ref())
+}
+
+/// Make `dst` an exact copy of `src`, apart from skipped entries.
+fn mirror(src: &Path, dst: &Path, stats: &mut SyncStats) -> Result<()> {
+    fs::create_dir_all(dst).with_context(|| format!("Cannot create {}", dst.display()))?;
+    let mut wanted: HashMap<OsString, Metadata> = HashMap::new();
+    for entry in fs::read_dir(src).with_context(|| format!("Cannot read {}", src.display()))? {
+        let entry = entry?;
+        if !skipped(&entry.file_name()) {
+            wanted.insert(entry.file_name(), entry.path().symlink_metadata()?);
+        }
+    }
+
+    for entry in fs::read_dir(dst)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !skipped(&name) && !wanted.contains_key(&name) {
+            remove(&entry.path())?;
+            stats.removed += 1;
+        }
+    }
+
+    for (name, meta) in wanted {
+        let (from, to) = (src.join(&name), dst.join(&name));
+        let existing = to.symlink_metadata().ok();
+        if meta.is_dir() {
+            if existing.as_ref().is_some_and(|m| !m.is_dir()) {
+                remove(&to)?;
+            }
+            mirror(&from, &to, stats)?;
+        } else if meta.is_symlink() {
+            let target = fs::read_link(&from)?;
+            if fs::read_link(&to).ok().as_ref() != Some(&target) {
+                if existing.is_some() {
+                    remove(&to)?;
+                }
+                std::os::unix::fs::symlink(&target, &to)?;
+                stats.copied += 1;
+            }
+        } else if !existing.is_some_and(|m| unchanged(&meta, &m)) {
+            if to.symlink_metadata().is_ok() {
+                remove(&to)?;
+            }
+            fs::copy(&from, &to).with_context(|| format!("Cannot copy {}", from.display()))?;
+            // Keep the mtime so the next pass can tell the file is unchanged;
+            // if this fails the file is just copied again next time.
+            if let (Ok(modified), Ok(file)) =
+                (meta.modified(), fs::File::options().write(true).open(&to))
+            {
+                let _ = file.set_modified(modified);
+            }
+            stats.copied += 1;
+        }
+    }
+    Ok(())
+}
+
+fn unchanged(src: &Metadata, dst: &Metadata) -> bool {
+    dst.is_file()
+        && src.len() == dst.len()
+        && src
+            .modified()
+            .ok()
+            .is_some_and(|m| dst.modified().ok() == Some(m))
+}
+
+fn remove(path: &Path) -> Result<()> {
+    let is_dir = path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    if is_dir {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .with_context(|| format!("Cannot remove {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn restore_undoes_edits_additions_and_deletions() {
+        let dir = tempdir().expect("create tempdir");
+        let workdir = dir.path();
+        fs::create_dir_all(workdir.join("src")).expect("create src");
+        fs::write(workdir.join("src/main.py"), "print('hi')\n").expect("write file");
+        fs::write(workdir.join("notes.txt"), "keep me\n").expect("write file");
+        fs::create_dir_all(workdir.join(".ralph")).expect("create state");
+        fs::write(workdir.join(".ralph/tasks.json"), "[]").expect("write state");
+
+        let snapshot = TreeSnapshot::new(workdir, workdir.join(".ralph/snapshots/workdir"));
+        assert_eq!(snapshot.capture().expect("capture").copied, 2);
+        assert_eq!(
+            snapshot.capture().expect("recapture"),
+            SyncStats::default(),
+            "unchanged files are not copied again"
+        );
+
+        fs::write(workdir.join("src/main.py"), "broken(\n").expect("edit");
+        fs::remove_file(workdir.join("notes.txt")).expect("delete");
+        fs::create_dir_all(workdir.join("scratch")).expect("create dir");
+        fs::write(workdir.join("scratch/tmp.txt"), "x").expect("add");
+        fs::write(workdir.join(".ralph/tasks.json"), "[1]").expect("state changes");
+
+        let stats = snapshot.restore().expect("restore");
+        assert_eq!(
+            stats,
+            SyncStats {
+                copied: 2,
+                removed: 1
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(workdir.join("src/main.py")).expect("read"),
+            "print('hi')\n"
+        );
+        assert!(workdir.join("notes.txt").exists());
+        assert!(!workdir.join("scratch").exists());
+        assert_eq!(
+            fs::read_to_string(workdir.join(".ralph/tasks.json")).expect("read"),
+            "[1]",
+            "Ralph's own state is left alone"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Post-processing for agent output lines, applied before a line reaches the
+//! terminal, the TUI, the iteration log, or completion detection.
+//!
+//! The built-in processors are picked by name with `[output] processors` in
+//! ralph.toml; anything else can implement [`LineProcessor`] and be added to a
+//! [`Pipeline`] with [`Pipeline::with`].
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::config::OutputConfig;
+
+/// Built-in processors, in their default order.
+pub const PROCESSORS: &[&str] = &["strip-ansi", "collapse-progress", "redact", "tag-tools"];
+
+/// Replacement for redacted secrets.
+const REDACTED: &str = "[redacted]";
+/// Prefix for lines that look like tool calls.
+pub const TOOL_TAG: &str = "[tool] ";
+
+/// Secret shapes redacted by default: provider API keys, GitHub and Slack
+/// tokens, AWS access key ids, bearer tokens, and `key=value` style secrets.
+const SECRET_PATTERNS: &[&str] = &[
+    r"\bsk-(?:ant-|proj-)?[A-Za-z0-9_-]{20,}",
+    r"\bgh[pousr]_[A-Za-z0-9]{30,}",
+    r"\bgithub_pat_[A-Za-z0-9_]{30,}",
+    r"\bxox[abprs]-[A-Za-z0-9-]{10,}",
+    r"\bAKIA[0-9A-Z]{16}\b",
+    r"(?i)\bbearer\s+[A-Za-z0-9._~+/-]{16,}=*",
+    r#"(?i)(?:api[_-]?key|secret|token|password)\b["']?\s*[:=]\s*["']?[^\s"',]{8,}"#,
+];
+
+/// Tool-call lines in the plain-text output of the supported agents.
+const TOOL_PATTERNS: &[&str] = &[
+    r"^\s*(?:exec|tool)\b",
+    r"^\s*⏺\s*\w+\(",
+    r"(?i)^\s*\[?tool[ _]?(?:use|call)\]?\b",
+];
+
+/// One step of the pipeline. Returning `None` drops the line.
+pub trait LineProcessor: Send + Sync {
+    fn process(&self, line: String) -> Option<String>;
+}
+
+/// Processors applied in order to every line of agent output.
+#[derive(Default)]
+pub struct Pipeline {
+    processors: Vec<Box<dyn LineProcessor>>,
+}
+
+impl Pipeline {
+    /// Build the pipeline described by `[output]`; all built-ins by default.
+    pub fn from_config(config: &OutputConfig) -> Result<Self> {
+        let names: Vec<&str> = match &config.processors {
+            Some(names) => names.iter().map(String::as_str).collect(),
+            None => PROCESSORS.to_vec(),
+        };
+        let mut pipeline = Self::default();
+        for name in names {
+            pipeline = match name {
+                "strip-ansi" => pipeline.with(StripAnsi),
+                "collapse-progress" => pipeline.with(CollapseProgress),
+                "redact" => {
+                    pipeline.with(Redact::new(config.redact.as_deref().unwrap_or_default())?)
+                }
+                "tag-tools" => pipeline.with(TagToolCalls::new(
+                    config.tool_patterns.as_deref().unwrap_or_default(),
+                )?),
+                other => anyhow::bail!(
+                    "Unknown output processor '{other}' (expected one of: {})",
+                    PROCESSORS.join(", ")
+                ),
+            };
+        }
+        Ok(pipeline)
+    }
+
+    /// Append `processor` to the end of the pipeline.
+    pub fn with(mut self, processor: impl LineProcessor + 'static) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    pub fn process(&self, line: String) -> Option<String> {
+        self.processors
+            .iter()
+            .try_fold(line, |line, processor| processor.process(line))
+    }
+}
+
+/// Removes ANSI colour and cursor escape sequences.
+pub struct StripAnsi;
+
+impl LineProcessor for StripAnsi {
+    fn process(&self, line: String) -> Option<String> {
+        Some(strip_ansi(&line))
+    }
+}
+
+/// Keeps only what a terminal would show of a line redrawn with `\r`, so a
+/// progress bar becomes its final state instead of every intermediate frame.
+pub struct CollapseProgress;
+
+impl LineProcessor for CollapseProgress {
+    fn process(&self, line: String) -> Option<String> {
+        if !line.contains('\r') {
+            return Some(line);
+        }
+        let last = line
+            .split('\r')
+            .rev()
+            .find(|frame| !frame.is_empty())
+            .unwrap_or_default();
+        Some(last.to_string())
+    }
+}
+
+/// Masks secret-shaped strings and the values of credential environment
+/// variables (`*KEY*`, `*TOKEN*`, `*SECRET*`, `*PASSWORD*`).
+pub struct Redact {
+    patterns: Vec<Regex>,
+    values: Vec<String>,
+}
+
+impl Redact {
+    /// The built-in patterns plus `extra` regexes.
+    pub fn new(extra: &[String]) -> Result<Self> {
+        let mut patterns = SECRET_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).expect("built-in secret pattern"))
+            .collect::<Vec<_>>();
+        for pattern in extra {
+            patterns.push(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid [output] redact pattern '{pattern}'"))?,
+            );
+        }
+        // Short values would redact ordinary words.
+        let values = std::env::vars()
+            .filter(|(name, value)| is_credential(name) && value.len() >= 8)
+            .map(|(_, value)| value)
+            .collect();
+        Ok(Self { patterns, values })
+    }
+}
+
+impl LineProcessor for Redact {
+    fn process(&self, mut line: String) -> Option<String> {
+        for value in &self.values {
+            if line.contains(value.as_str()) {
+                line = line.replace(value.as_str(), REDACTED);
+            }
+        }
+        for pattern in &self.patterns {
+            if pattern.is_match(&line) {
+                line = pattern.replace_all(&line, REDACTED).into_owned();
+            }
+        }
+        Some(line)
+    }
+}
+
+/// Prefixes tool-call lines with `[tool] ` so they stand out in logs and can
+/// be filtered in the TUI.
+pub struct TagToolCalls {
+    patterns: Vec<Regex>,
+}
+
+impl TagToolCalls {
+    /// The built-in patterns plus `extra` regexes.
+    pub fn new(extra: &[String]) -> Result<Self> {
+        let mut patterns = TOOL_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).expect("built-in tool pattern"))
+            .collect::<Vec<_>>();
+        for pattern in extra {
+            patterns.push(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid [output] tool pattern '{pattern}'"))?,
+            );
+        }
+        Ok(Self { patterns })
+    }
+}
+
+impl LineProcessor for TagToolCalls {
+    fn process(&self, line: String) -> Option<String> {
+        if !line.starts_with(TOOL_TAG) && self.patterns.iter().any(|p| p.is_match(&line)) {
+            Some(format!("{TOOL_TAG}{line}"))
+        } else {
+            Some(line)
+        }
+    }
+}
+
+/// Whether an environment variable name looks like it holds a credential.
+pub fn is_credential(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ["KEY", "TOKEN", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+/// Strip ANSI escape sequences from a string for clean terminal rendering.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ ... final_byte  (CSI sequence)
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                for c2 in chars.by_ref() {
+                    if c2.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else if chars.peek() == Some(&']') {
+                // OSC sequence: ESC ] ... ST (ESC \ or BEL)
+                chars.next();
+                let mut prev = '\0';
+                for c2 in chars.by_ref() {
+                    if c2 == '\x07' || (prev == '\x1b' && c2 == '\\') {
+                        break;
+                    }
+                    prev = c2;
+                }
+            }
+            // else: skip lone ESC
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(pipeline: &Pipeline, line: &str) -> Option<String> {
+        pipeline.process(line.to_string())
+    }
+
+    #[test]
+    fn default_pipeline_cleans_progress_colour_and_secrets() {
+        let pipeline = Pipeline::from_config(&OutputConfig::default()).expect("default pipeline");
+
+        assert_eq!(
+            run(&pipeline, "\x1b[32mok\x1b[0m  10%\r 50%\r100%").as_deref(),
+            Some("100%")
+        );
+        assert_eq!(
+            run(&pipeline, "using key sk-ant-REDACTED").as_deref(),
+            Some("using key [redacted]")
+        );
+        assert_eq!(
+            run(&pipeline, "API_KEY=hunter2hunter2").as_deref(),
+            Some("[redacted]")
+        );
+        assert_eq!(
+            run(&pipeline, "exec bash -lc 'cargo test'").as_deref(),
+            Some("[tool] exec bash -lc 'cargo test'")
+        );
+        assert_eq!(
+            run(&pipeline, "<promise>COMPLETE</promise>").as_deref(),
+            Some("<promise>COMPLETE</promise>")
+        );
+    }
+
+    #[test]
+    fn processors_are_configurable_and_extensible() {
+        struct DropBlank;
+        impl LineProcessor for DropBlank {
+            fn process(&self, line: String) -> Option<String> {
+                (!line.trim().is_empty()).then_some(line)
+            }
+        }
+
+        let config = OutputConfig {
+            processors: Some(vec!["redact".to_string()]),
+            redact: Some(vec![r"internal-\d+".to_string()]),
+            tool_patterns: None,
+        };
+        let pipeline = Pipeline::from_config(&config)
+            .expect("pipeline")
+            .with(DropBlank);
+        assert_eq!(
+            run(&pipeline, "\x1b[1mhost internal-42\x1b[0m").as_deref(),
+            Some("\x1b[1mhost [redacted]\x1b[0m")
+        );
+        assert_eq!(run(&pipeline, "   "), None);
+
+        let config = OutputConfig {
+            processors: Some(vec!["uppercase".to_string()]),
+            ..OutputConfig::default()
+        };
+        let Err(err) = Pipeline::from_config(&config) else {
+            panic!("unknown processor accepted");
+        };
+        assert!(err
+            .to_string()
+            .contains("Unknown output processor 'uppercase'"));
+    }
+}
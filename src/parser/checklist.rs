@@ -0,0 +1,230 @@
+//! `ralph run --tasks tasks.md` — turn a Markdown checklist straight into a
+//! task list, with no agent call.
+//!
+//! Every `- [ ]` / `- [x]` item becomes a task, numbered T1, T2, … in order;
+//! checked items start out complete. Dependencies go in a `(deps: T1, T2)`
+//! annotation on the item, or on an indented `Depends on: T1` line; each
+//! indented `Acceptance: …` line adds one acceptance criterion. Other
+//! indented lines under an item become its description, and an item may lead
+//! with its own id (`**T3** Title` or `T3: Title`), so the output of
+//! `ralph export --format md-checklist` reads back in.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::state::{Task, TaskList, TaskStatus};
+
+struct Item {
+    line: usize,
+    explicit_id: Option<String>,
+    title: String,
+    done: bool,
+    deps: Vec<String>,
+    acceptance: Vec<String>,
+    description: Vec<String>,
+}
+
+fn item_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*[-*+] \[([ xX])\]\s+(.*)$").expect("valid regex"))
+}
+
+fn id_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:\*\*(T\d+)\*\*\s+|(T\d+):\s+)(.*)$").expect("valid regex"))
+}
+
+fn deps_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\(\s*deps?:\s*([^)]*)\)").expect("valid regex"))
+}
+
+/// Read `path` as a checklist. `prd_path` is recorded in the task list.
+pub fn load(path: &Path, prd_path: &Path) -> Result<TaskList> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read task checklist: {}", path.display()))?;
+    let tasks =
+        tasks_from_checklist(&content).with_context(|| format!("Invalid {}", path.display()))?;
+    let now = Utc::now();
+    Ok(TaskList {
+        version: 1,
+        prd_path: prd_path.to_string_lossy().to_string(),
+        created_at: now,
+        updated_at: now,
+        tasks,
+    })
+}
+
+fn tasks_from_checklist(content: &str) -> Result<Vec<Task>> {
+    let mut items: Vec<Item> = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        if let Some(caps) = item_re().captures(line) {
+            let mut text = caps[2].to_string();
+            let mut deps = Vec::new();
+            if let Some(m) = deps_re().captures(&text) {
+                deps = split_ids(&m[1]);
+                text = deps_re().replace(&text, "").trim().to_string();
+            }
+            let (explicit_id, title) = match id_re().captures(&text) {
+                Some(c) => (
+                    Some(
+                        c.get(1)
+                            .or(c.get(2))
+                            .expect("one id group")
+                            .as_str()
+                            .to_string(),
+                    ),
+                    c[3].trim().to_string(),
+                ),
+                None => (None, text.trim().to_string()),
+            };
+            // Drop the `_(failed)_`-style status note `ralph export` appends.
+            let title = match title.rsplit_once(" _(") {
+                Some((rest, note)) if note.ends_with(")_") => rest.to_string(),
+                _ => title,
+            };
+            items.push(Item {
+                line: n + 1,
+                explicit_id,
+                title,
+                done: &caps[1] != " ",
+                deps,
+                acceptance: Vec::new(),
+                description: Vec::new(),
+            });
+        } else if line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+            // Continuation of the item above; text before the first item is ignored.
+            let Some(item) = items.last_mut() else {
+                continue;
+            };
+            let text = line.trim();
+            if let Some(deps) = text.strip_prefix("Depends on:") {
+                item.deps.extend(split_ids(deps));
+            } else if let Some(criterion) = text.strip_prefix("Acceptance:") {
+                item.acceptance.push(criterion.trim().to_string());
+            } else {
+                item.description.push(text.to_string());
+            }
+        }
+    }
+    anyhow::ensure!(!items.is_empty(), "no `- [ ]` checklist items found");
+
+    // Items are renumbered in order; deps may use an item's own id or its position.
+    let ids: HashMap<String, String> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| Some((item.explicit_id.clone()?, format!("T{}", i + 1))))
+        .collect();
+
+    let now = Utc::now();
+    let tasks = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            anyhow::ensure!(
+                !item.title.is_empty(),
+                "line {}: item has no title",
+                item.line
+            );
+            let depends_on = item
+                .deps
+                .iter()
+                .map(|dep| ids.get(dep).cloned().unwrap_or_else(|| dep.clone()))
+                .collect();
+            Ok(Task {
+                id: format!("T{}", i + 1),
+                title: item.title,
+                description: item.description.join("\n"),
+                priority: i as u32 + 1,
+                status: if item.done {
+                    TaskStatus::Complete
+                } else {
+                    TaskStatus::Pending
+                },
+                depends_on,
+                acceptance: item.acceptance,
+                verify: None,
+                max_attempts: None,
+                completed_at: item.done.then_some(now),
+                notes: None,
+                prd_section: None,
+                agent: None,
+                model: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    crate::state::validate_task_list(&TaskList {
+        version: 1,
+        prd_path: String::new(),
+        created_at: now,
+        updated_at: now,
+        tasks: tasks.clone(),
+    })?;
+    Ok(tasks)
+}
+
+fn split_ids(list: &str) -> Vec<String> {
+    list.split([',', ' '])
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checklist_items_become_numbered_tasks() {
+        let tasks = tasks_from_checklist(
+            "# Plan\n\nSome intro text.\n\n\
+             - [x] Set up the schema\n\
+             - [ ] Add the login endpoint (deps: T1)\n\
+             \x20 Return a session cookie.\n\
+             \x20 Acceptance: a bad password gets a 401\n\
+             * [ ] Write docs (deps: T1, T2)\n",
+        )
+        .expect("parse checklist");
+
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].status, TaskStatus::Complete);
+        assert!(tasks[0].completed_at.is_some());
+        assert_eq!(tasks[1].title, "Add the login endpoint");
+        assert_eq!(tasks[1].description, "Return a session cookie.");
+        assert_eq!(tasks[1].depends_on, ["T1"]);
+        assert_eq!(tasks[1].acceptance, ["a bad password gets a 401"]);
+        assert_eq!((tasks[2].id.as_str(), tasks[2].priority), ("T3", 3));
+        assert_eq!(tasks[2].depends_on, ["T1", "T2"]);
+    }
+
+    #[test]
+    fn exported_checklists_read_back_with_their_ids() {
+        let tasks = tasks_from_checklist(
+            "- [ ] **T4** Second thing _(failed)_\n  Depends on: T9\n- [ ] **T9** First thing\n",
+        )
+        .expect("parse checklist");
+
+        assert_eq!(tasks[0].title, "Second thing");
+        assert_eq!(tasks[0].depends_on, ["T2"]);
+        assert_eq!(tasks[1].id, "T2");
+    }
+
+    #[test]
+    fn bad_checklists_are_rejected() {
+        let err = tasks_from_checklist("just prose\n").expect_err("no items");
+        assert!(err.to_string().contains("no `- [ ]` checklist items"));
+
+        let err = tasks_from_checklist("- [ ] A (deps: T7)\n").expect_err("unknown dep");
+        assert!(err.to_string().contains("unknown task 'T7'"), "{err}");
+
+        let err =
+            tasks_from_checklist("- [ ] A (deps: T2)\n- [ ] B (deps: T1)\n").expect_err("cycle");
+        assert!(err.to_string().contains("Circular"), "{err}");
+    }
+}
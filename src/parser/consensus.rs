@@ -0,0 +1,333 @@
+//! `ralph parse --consensus N` — parse the PRD with several agents, match their
+//! task lists by title, and keep what they agree on.
+//!
+//! Tasks every agent produced are merged automatically. The rest are shown to
+//! the user to keep or drop (or dropped when stdin is not a terminal).
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+use tokio::task::JoinSet;
+
+use super::{agent_on_path, parse_prompt, parse_with_agent, FALLBACK_ORDER};
+use crate::agents::Sampling;
+use crate::state::{Task, TaskStatus};
+
+/// Minimum title word overlap (Jaccard) for two tasks to count as the same.
+const MATCH_THRESHOLD: f64 = 0.5;
+
+/// One task proposed by at least one agent.
+#[derive(Debug)]
+pub struct Candidate {
+    pub task: Task,
+    /// Agents whose parse contained this task, primary first.
+    pub agents: Vec<String>,
+    /// Dependencies as indices into the candidate list.
+    deps: Vec<usize>,
+    /// Index of the parse `task` was taken from.
+    source: usize,
+}
+
+/// Pick `count` agents: `primary` first, then other installed agents in
+/// fallback order.
+pub fn pick_agents(primary: &str, count: usize) -> Result<Vec<String>> {
+    let mut agents = vec![primary.to_string()];
+    for name in FALLBACK_ORDER {
+        if agents.len() == count {
+            break;
+        }
+        if *name != primary && agent_on_path(name) {
+            agents.push(name.to_string());
+        }
+    }
+    if agents.len() < count {
+        anyhow::bail!(
+            "--consensus {count} needs {count} agents, but only found: {}",
+            agents.join(", ")
+        );
+    }
+    Ok(agents)
+}
+
+/// Parse the PRD with every agent concurrently (no fallback — each parse must
+/// come from a distinct agent). `model` only applies to the first agent;
+/// `sampling` applies to every agent that supports it.
+pub async fn parse_with_agents(
+    prd_path: &Path,
+    agents: &[String],
+    model: Option<&str>,
+    sampling: &Sampling,
+    parse_timeout_secs: u64,
+    retries: u32,
+) -> Result<Vec<(String, Vec<Task>)>> {
+    let prompt = parse_prompt(prd_path)?;
+    eprintln!(
+        "🔍  Parsing PRD with {} for consensus (this may take a moment)…",
+        agents.join(" + ")
+    );
+
+    let mut join_set = JoinSet::new();
+    for (i, agent) in agents.iter().enumerate() {
+        let agent = agent.clone();
+        let model = if i == 0 {
+            model.map(str::to_string)
+        } else {
+            None
+        };
+        let prompt = prompt.clone();
+        let sampling = sampling.clone();
+        sampling.warn_unsupported(&agent);
+        join_set.spawn(async move {
+            let tasks = parse_with_agent(
+                &agent,
+                model.as_deref(),
+                &sampling,
+                &prompt,
+                parse_timeout_secs,
+                retries,
+            )
+            .await;
+            (i, agent, tasks)
+        });
+    }
+
+    let mut parses = Vec::new();
+    while let Some(joined) = join_set.join_next().await {
+        let (i, agent, tasks) = joined?;
+        let tasks = tasks.map_err(|e| e.context(format!("{agent} failed to parse the PRD")))?;
+        parses.push((i, agent, tasks));
+    }
+    parses.sort_by_key(|(i, _, _)| *i);
+    Ok(parses.into_iter().map(|(_, a, t)| (a, t)).collect())
+}
+
+/// Match tasks across parses. The first parse is the reference: its tasks come
+/// first, in order, followed by tasks only the other agents proposed.
+pub fn compare(parses: &[(String, Vec<Task>)]) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+    // Per parse: that parse's task index → candidate index.
+    let mut mappings: Vec<Vec<usize>> = Vec::new();
+
+    for (pi, (agent, tasks)) in parses.iter().enumerate() {
+        let mut claimed: HashSet<usize> = HashSet::new();
+        let mut mapping = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let best = candidates
+                .iter()
+                .enumerate()
+                .filter(|(ci, c)| !claimed.contains(ci) && !c.agents.contains(agent))
+                .map(|(ci, c)| (ci, similarity(&c.task.title, &task.title)))
+                .filter(|(_, score)| *score >= MATCH_THRESHOLD)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            let ci = match best {
+                Some((ci, _)) => {
+                    candidates[ci].agents.push(agent.clone());
+                    ci
+                }
+                None => {
+                    candidates.push(Candidate {
+                        task: task.clone(),
+                        agents: vec![agent.clone()],
+                        deps: vec![],
+                        source: pi,
+                    });
+                    candidates.len() - 1
+                }
+            };
+            claimed.insert(ci);
+            mapping.push(ci);
+        }
+        mappings.push(mapping);
+    }
+
+    // Resolve each candidate's dependencies through the parse it came from.
+    for (pi, ((_, tasks), mapping)) in parses.iter().zip(&mappings).enumerate() {
+        for (task, &ci) in tasks.iter().zip(mapping) {
+            if candidates[ci].source != pi {
+                continue;
+            }
+            candidates[ci].deps = task
+                .depends_on
+                .iter()
+                .filter_map(|dep| tasks.iter().position(|t| &t.id == dep))
+                .map(|pos| mapping[pos])
+                .filter(|&dep_ci| dep_ci != ci)
+                .collect();
+        }
+    }
+    candidates
+}
+
+impl Candidate {
+    pub fn is_agreed(&self, agent_count: usize) -> bool {
+        self.agents.len() == agent_count
+    }
+}
+
+/// Decide which candidates to keep. Agreed tasks are always kept; disputed ones
+/// are offered to the user, or dropped when stdin is not a terminal.
+pub fn resolve(candidates: &[Candidate], agent_count: usize) -> Result<Vec<bool>> {
+    let disputed = candidates
+        .iter()
+        .filter(|c| !c.is_agreed(agent_count))
+        .count();
+    let agreed = candidates.len() - disputed;
+    println!("\n🗳️   {agreed} task(s) agreed, {disputed} disputed");
+    if disputed == 0 {
+        return Ok(vec![true; candidates.len()]);
+    }
+
+    let interactive = std::io::stdin().is_terminal();
+    if !interactive {
+        println!("    Not a terminal — keeping agreed tasks only. Disputed:");
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut keep = Vec::with_capacity(candidates.len());
+    for c in candidates {
+        if c.is_agreed(agent_count) {
+            keep.push(true);
+            continue;
+        }
+        let label = format!("\"{}\" (only from {})", c.task.title, c.agents.join(", "));
+        if !interactive {
+            println!("    • {label}");
+            keep.push(false);
+            continue;
+        }
+        print!("    Keep {label}? [y/N] ");
+        std::io::stdout().flush()?;
+        let answer = lines.next().transpose()?.unwrap_or_default();
+        keep.push(matches!(answer.trim(), "y" | "Y" | "yes"));
+    }
+    Ok(keep)
+}
+
+/// Build the final task list from the kept candidates: ids renumbered T1…,
+/// priorities in order, and dependencies on dropped tasks removed.
+pub fn assemble(candidates: &[Candidate], keep: &[bool]) -> Vec<Task> {
+    let mut new_ids: Vec<Option<String>> = vec![None; candidates.len()];
+    let mut next = 1;
+    for (i, kept) in keep.iter().enumerate() {
+        if *kept {
+            new_ids[i] = Some(format!("T{next}"));
+            next += 1;
+        }
+    }
+
+    candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| {
+            let id = new_ids[i].clone()?;
+            let priority = id[1..].parse().unwrap_or(1);
+            Some(Task {
+                id,
+                priority,
+                status: TaskStatus::Pending,
+                depends_on: c.deps.iter().filter_map(|&d| new_ids[d].clone()).collect(),
+                ..c.task.clone()
+            })
+        })
+        .collect()
+}
+
+/// Word-set Jaccard similarity between two titles.
+pub(super) fn similarity(a: &str, b: &str) -> f64 {
+    let words = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, title: &str, deps: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: format!("{title} description"),
+            priority: 1,
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_matches_tasks_by_title_across_agents() {
+        let parses = vec![
+            (
+                "codex".to_string(),
+                vec![
+                    task("T1", "Create user database schema", &[]),
+                    task("T2", "Add login endpoint", &["T1"]),
+                    task("T3", "Write deployment docs", &[]),
+                ],
+            ),
+            (
+                "gemini".to_string(),
+                vec![
+                    task("T1", "Create the user database schema", &[]),
+                    task("T2", "Add password reset flow", &["T3"]),
+                    task("T3", "Add a login endpoint", &["T1"]),
+                ],
+            ),
+        ];
+
+        let candidates = compare(&parses);
+        let agreed: Vec<&str> = candidates
+            .iter()
+            .filter(|c| c.is_agreed(2))
+            .map(|c| c.task.title.as_str())
+            .collect();
+        assert_eq!(
+            agreed,
+            vec!["Create user database schema", "Add login endpoint"]
+        );
+        assert_eq!(candidates.len(), 4);
+        assert_eq!(candidates[2].agents, vec!["codex"]);
+        assert_eq!(candidates[3].agents, vec!["gemini"]);
+        assert_eq!(candidates[3].task.title, "Add password reset flow");
+    }
+
+    #[test]
+    fn assemble_renumbers_and_remaps_dependencies() {
+        let parses = vec![
+            (
+                "codex".to_string(),
+                vec![
+                    task("T1", "Scaffold project", &[]),
+                    task("T2", "Add web dashboard", &["T1"]),
+                ],
+            ),
+            (
+                "claude".to_string(),
+                vec![
+                    task("A", "Scaffold project", &[]),
+                    task("B", "Add metrics exporter", &["A"]),
+                ],
+            ),
+        ];
+        let candidates = compare(&parses);
+
+        // Drop the codex-only dashboard, keep claude's exporter.
+        let tasks = assemble(&candidates, &[true, false, true]);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].id, "T1");
+        assert_eq!(tasks[1].id, "T2");
+        assert_eq!(tasks[1].title, "Add metrics exporter");
+        assert_eq!(tasks[1].depends_on, vec!["T1"]);
+        assert_eq!(tasks[1].priority, 2);
+    }
+}
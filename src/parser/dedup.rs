@@ -0,0 +1,230 @@
+//! `--reparse`: parse the PRD again without losing what an earlier run did.
+//! Each task of the new parse is matched to the old task it duplicates —
+//! by title, and by description when titles were reworded — and takes over
+//! its status, completion time, and notes. The new parse decides ids, order,
+//! and dependencies, so PRD edits still land.
+
+use std::collections::HashSet;
+
+use super::consensus::similarity;
+use crate::state::{Task, TaskList, TaskStatus};
+
+/// Minimum combined score for a new task to count as an old one.
+const MATCH_THRESHOLD: f64 = 0.5;
+
+/// The merged task list and what happened to the old tasks.
+pub struct Merge {
+    pub task_list: TaskList,
+    /// `(new id, old id)` for every new task that kept an old one's state.
+    pub matched: Vec<(String, String)>,
+    /// Old tasks no new task matched.
+    pub dropped: Vec<Task>,
+}
+
+impl Merge {
+    /// E.g. `6 tasks: 4 matched (3 complete), 2 new; 1 old task dropped`.
+    pub fn summary(&self) -> String {
+        let total = self.task_list.tasks.len();
+        let complete = self
+            .task_list
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Complete)
+            .count();
+        let mut summary = format!(
+            "{total} tasks: {} matched ({complete} complete), {} new",
+            self.matched.len(),
+            total - self.matched.len()
+        );
+        match self.dropped.len() {
+            0 => {}
+            1 => summary.push_str("; 1 old task dropped"),
+            n => summary.push_str(&format!("; {n} old tasks dropped")),
+        }
+        summary
+    }
+}
+
+/// Carry the state of `previous` over to the tasks of `parsed` they match.
+pub fn merge(previous: &[Task], mut parsed: TaskList) -> Merge {
+    // Best pairs first, each task used once.
+    let mut pairs: Vec<(f64, usize, usize)> = Vec::new();
+    for (ni, new) in parsed.tasks.iter().enumerate() {
+        for (oi, old) in previous.iter().enumerate() {
+            let score = score(old, new);
+            if score >= MATCH_THRESHOLD {
+                pairs.push((score, ni, oi));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let (mut used_new, mut used_old) = (HashSet::new(), HashSet::new());
+    let mut matched = Vec::new();
+    for (_, ni, oi) in pairs {
+        if used_new.contains(&ni) || used_old.contains(&oi) {
+            continue;
+        }
+        used_new.insert(ni);
+        used_old.insert(oi);
+        let (new, old) = (&mut parsed.tasks[ni], &previous[oi]);
+        // An interrupted task starts over.
+        new.status = match old.status {
+            TaskStatus::InProgress => TaskStatus::Pending,
+            ref status => status.clone(),
+        };
+        new.completed_at = old.completed_at;
+        new.notes = old.notes.clone();
+        // Overrides the user set in tasks.json outlive the parse.
+        new.agent = new.agent.take().or_else(|| old.agent.clone());
+        new.model = new.model.take().or_else(|| old.model.clone());
+        new.verify = new.verify.take().or_else(|| old.verify.clone());
+        new.max_attempts = new.max_attempts.or(old.max_attempts);
+        matched.push((new.id.clone(), old.id.clone()));
+    }
+    matched.sort_by_key(|(id, _)| parsed.tasks.iter().position(|t| &t.id == id));
+
+    let dropped = previous
+        .iter()
+        .enumerate()
+        .filter(|(oi, _)| !used_old.contains(oi))
+        .map(|(_, t)| t.clone())
+        .collect();
+    Merge {
+        task_list: parsed,
+        matched,
+        dropped,
+    }
+}
+
+/// Title similarity, raised by matching descriptions when a title was
+/// reworded but the task wasn't.
+fn score(old: &Task, new: &Task) -> f64 {
+    let title = similarity(&old.title, &new.title);
+    let description = similarity(&old.description, &new.description);
+    title.max((title + description) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn task(id: &str, title: &str, description: &str, status: TaskStatus) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: description.to_string(),
+            priority: 1,
+            status,
+            ..Default::default()
+        }
+    }
+
+    fn list(tasks: Vec<Task>) -> TaskList {
+        TaskList {
+            version: 1,
+            prd_path: "prd.md".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tasks,
+        }
+    }
+
+    #[test]
+    fn reworded_tasks_keep_their_progress() {
+        let mut done = task(
+            "T1",
+            "Create user database schema",
+            "Add a users table with email and password hash",
+            TaskStatus::Complete,
+        );
+        done.completed_at = Some(Utc::now());
+        done.agent = Some("claude".to_string());
+        let previous = vec![
+            done,
+            task(
+                "T2",
+                "Add login endpoint",
+                "POST /login checks the password and returns a session",
+                TaskStatus::InProgress,
+            ),
+            task(
+                "T3",
+                "Write deployment docs",
+                "Document the Docker setup",
+                TaskStatus::Complete,
+            ),
+        ];
+        let parsed = list(vec![
+            task(
+                "T1",
+                "Set up the project",
+                "Cargo workspace and CI",
+                TaskStatus::Pending,
+            ),
+            task(
+                "T2",
+                "Users table",
+                "Add a users table with email and password hash",
+                TaskStatus::Pending,
+            ),
+            task(
+                "T3",
+                "Add the login endpoint",
+                "POST /login returns a session",
+                TaskStatus::Pending,
+            ),
+        ]);
+
+        let merge = merge(&previous, parsed);
+        let tasks = &merge.task_list.tasks;
+        assert_eq!(tasks[0].status, TaskStatus::Pending);
+        assert_eq!(tasks[1].status, TaskStatus::Complete);
+        assert!(tasks[1].completed_at.is_some());
+        assert_eq!(tasks[1].agent.as_deref(), Some("claude"));
+        // Interrupted work is retried.
+        assert_eq!(tasks[2].status, TaskStatus::Pending);
+        assert_eq!(
+            merge.matched,
+            [
+                ("T2".to_string(), "T1".to_string()),
+                ("T3".to_string(), "T2".to_string())
+            ]
+        );
+        assert_eq!(merge.dropped.len(), 1);
+        assert_eq!(merge.dropped[0].title, "Write deployment docs");
+        assert_eq!(
+            merge.summary(),
+            "3 tasks: 2 matched (1 complete), 1 new; 1 old task dropped"
+        );
+    }
+
+    #[test]
+    fn each_old_task_is_matched_once() {
+        let previous = vec![task(
+            "T1",
+            "Add login endpoint",
+            "POST /login",
+            TaskStatus::Complete,
+        )];
+        let parsed = list(vec![
+            task(
+                "T1",
+                "Add login endpoint",
+                "POST /login",
+                TaskStatus::Pending,
+            ),
+            task(
+                "T2",
+                "Add login endpoint tests",
+                "POST /login",
+                TaskStatus::Pending,
+            ),
+        ]);
+        let merge = merge(&previous, parsed);
+        let statuses: Vec<&TaskStatus> = merge.task_list.tasks.iter().map(|t| &t.status).collect();
+        assert_eq!(statuses, [&TaskStatus::Complete, &TaskStatus::Pending]);
+        assert!(merge.dropped.is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+//! How much of a task list could run concurrently, judged from its
+//! dependency graph alone.
+
+use std::collections::HashMap;
+
+use crate::state::Task;
+
+/// A task list's dependency graph scheduled as early as possible: each task
+/// runs in the round after its last dependency.
+#[derive(Debug, PartialEq)]
+pub struct GraphShape {
+    pub tasks: usize,
+    /// Tasks on the longest dependency chain — the rounds a run needs however
+    /// many tasks run at once.
+    pub critical_path: usize,
+    /// The most tasks that are ready in the same round.
+    pub max_width: usize,
+}
+
+impl GraphShape {
+    pub fn of(tasks: &[Task]) -> Self {
+        let by_id: HashMap<&str, &Task> = tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+        let mut rounds: HashMap<&str, usize> = HashMap::new();
+        for task in tasks {
+            round(task, &by_id, &mut rounds);
+        }
+        let mut width: HashMap<usize, usize> = HashMap::new();
+        for r in rounds.values() {
+            *width.entry(*r).or_default() += 1;
+        }
+        Self {
+            tasks: tasks.len(),
+            critical_path: rounds.values().copied().max().unwrap_or(0),
+            max_width: width.values().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// One line of advice on whether running tasks in parallel would pay off.
+    pub fn advice(&self) -> String {
+        if self.tasks < 2 {
+            "nothing to parallelise".to_string()
+        } else if self.max_width <= 1 {
+            "the tasks form a single chain, so running them in parallel would not help".to_string()
+        } else {
+            let speedup = self.tasks as f64 / self.critical_path as f64;
+            let verdict = if speedup < 1.5 {
+                "little to gain from parallelism"
+            } else {
+                "parallelism would help"
+            };
+            format!(
+                "{verdict}: with {} workers the run needs {} rounds instead of {} (up to {speedup:.1}× faster)",
+                self.max_width, self.critical_path, self.tasks
+            )
+        }
+    }
+}
+
+/// 1-based round `task` runs in. Unknown dependencies (and cycles, which
+/// validation rejects anyway) count as already done.
+fn round<'a>(
+    task: &'a Task,
+    by_id: &HashMap<&'a str, &'a Task>,
+    rounds: &mut HashMap<&'a str, usize>,
+) -> usize {
+    if let Some(&r) = rounds.get(task.id.as_str()) {
+        return r;
+    }
+    rounds.insert(task.id.as_str(), 1);
+    let r = 1 + task
+        .depends_on
+        .iter()
+        .filter_map(|dep| by_id.get(dep.as_str()))
+        .map(|dep| round(dep, by_id, rounds))
+        .max()
+        .unwrap_or(0);
+    rounds.insert(task.id.as_str(), r);
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, deps: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            title: id.to_string(),
+            priority: 1,
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shape_measures_longest_chain_and_widest_round() {
+        // T1 → {T2, T3, T4} → T5
+        let tasks = [
+            task("T1", &[]),
+            task("T2", &["T1"]),
+            task("T3", &["T1"]),
+            task("T4", &["T1"]),
+            task("T5", &["T2", "T3", "T4"]),
+        ];
+        let shape = GraphShape::of(&tasks);
+        assert_eq!(
+            shape,
+            GraphShape {
+                tasks: 5,
+                critical_path: 3,
+                max_width: 3
+            }
+        );
+        assert!(shape.advice().starts_with("parallelism would help"));
+        assert!(shape.advice().contains("3 rounds instead of 5"));
+    }
+
+    #[test]
+    fn chains_are_reported_as_sequential() {
+        let tasks = [task("T1", &[]), task("T2", &["T1"]), task("T3", &["T2"])];
+        let shape = GraphShape::of(&tasks);
+        assert_eq!((shape.critical_path, shape.max_width), (3, 1));
+        assert!(shape.advice().contains("single chain"));
+    }
+}
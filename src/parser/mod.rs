@@ -1,13 +1,21 @@
 use anyhow::{Context, Result};
 use chrono::Utc;
+use serde::Deserialize;
 use std::path::Path;
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::Command;
 use tokio::time::timeout;
 
+use crate::agents::{Agent, AgentProcess, ApiAgent, OllamaAgent, Sampling};
 use crate::cli::ParseArgs;
-use crate::state::{Task, TaskList};
+use crate::state::{Task, TaskFormat, TaskList};
+
+pub mod checklist;
+mod consensus;
+pub mod dedup;
+mod graph;
+pub mod sections;
 
 // ── Prompts ───────────────────────────────────────────────────────────────────
 
@@ -22,7 +30,9 @@ Each task object must follow this exact schema:
   "description": "One or two sentences describing what must be implemented.",
   "priority": 1,
   "status": "pending",
-  "depends_on": []
+  "depends_on": [],
+  "acceptance": ["A wrong password returns 401"],
+  "prd_section": "user-login"
 }
 
 Rules:
@@ -30,6 +40,9 @@ Rules:
 - priority: 1 = highest priority; assign in dependency order so prerequisites come first
 - depends_on: list of task ids that must be complete before this one (empty array if none)
 - status: always "pending"
+- acceptance: the PRD's acceptance criteria for this task, one checkable statement per item, copied as written (empty array if the PRD gives none)
+- prd_section: the slug of the PRD heading that describes this task — lowercase, punctuation removed, spaces as hyphens (e.g. a heading `## User Login` → `user-login`). Omit it if no single section covers the task
+- agent, model: include these only when the PRD asks for a specific agent (claude, codex, gemini, opencode, aider, ollama, api) or model for this task; otherwise omit them
 - Do NOT include tasks that are already described as "phase 2" or "future work" unless they are clearly needed for the MVP
 - Output ONLY the JSON array
 
@@ -43,51 +56,109 @@ pub async fn parse_prd(
     prd_path: &Path,
     agent: &str,
     model: Option<&str>,
+    sampling: &Sampling,
     parse_timeout_secs: u64,
+    retries: u32,
 ) -> Result<TaskList> {
-    let prd_content = std::fs::read_to_string(prd_path)
-        .with_context(|| format!("Cannot read PRD file: {}", prd_path.display()))?;
-
-    let prompt = format!("{}{}", PARSE_SYSTEM_PROMPT, prd_content);
+    let prompt = parse_prompt(prd_path)?;
 
     eprintln!("🔍  Parsing PRD with {} (this may take a moment)…", agent);
+    sampling.warn_unsupported(agent);
 
-    let raw = run_agent(agent, model, &prompt, parse_timeout_secs).await?;
-
-    // Extract the JSON array — the agent might wrap it in prose.
-    let json_str = extract_json_array(&raw).with_context(|| {
-        format!(
-            "Agent did not return a JSON array. Raw output:\n---\n{}\n---",
-            raw
+    // Like `run_agent`, but an agent whose tasks fail validation also hands
+    // over to the next one once its corrective retries run out.
+    match parse_with_agent(agent, model, sampling, &prompt, parse_timeout_secs, retries).await {
+        Ok(tasks) => return Ok(new_task_list(prd_path, tasks)),
+        Err(e) => {
+            eprintln!("⚠️  {} failed: {:#}", agent, e);
+            eprintln!("    Trying fallback agents…");
+        }
+    }
+    for fallback in FALLBACK_ORDER {
+        if *fallback == agent || !agent_on_path(fallback) {
+            continue;
+        }
+        eprintln!("🔄  Trying {} as fallback…", fallback);
+        match parse_with_agent(
+            fallback,
+            model,
+            sampling,
+            &prompt,
+            parse_timeout_secs,
+            retries,
         )
-    })?;
+        .await
+        {
+            Ok(tasks) => return Ok(new_task_list(prd_path, tasks)),
+            Err(e) => eprintln!("⚠️  {} also failed: {:#}", fallback, e),
+        }
+    }
 
-    let tasks: Vec<Task> = serde_json::from_str(&json_str).with_context(|| {
-        format!(
-            "JSON array from agent is not valid Task objects. JSON:\n{}\n",
-            json_str
-        )
-    })?;
+    anyhow::bail!(
+        "All agents failed for PRD parsing. Tried: {} + fallbacks.\n\
+         Make sure at least one agent is installed and authenticated.\n\
+         Tip: run your agent standalone first (e.g. `claude --print -p \"hello\"`) to verify it works.",
+        agent
+    )
+}
 
-    Ok(TaskList {
-        version: 1,
-        prd_path: prd_path.to_string_lossy().to_string(),
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-        tasks,
-    })
+/// Parse a PRD with `count` agents and merge their task lists, asking the user
+/// to resolve tasks they disagree on.
+pub async fn parse_prd_consensus(
+    prd_path: &Path,
+    agent: &str,
+    model: Option<&str>,
+    sampling: &Sampling,
+    parse_timeout_secs: u64,
+    retries: u32,
+    count: usize,
+) -> Result<TaskList> {
+    let agents = consensus::pick_agents(agent, count)?;
+    let parses = consensus::parse_with_agents(
+        prd_path,
+        &agents,
+        model,
+        sampling,
+        parse_timeout_secs,
+        retries,
+    )
+    .await?;
+    for (agent, tasks) in &parses {
+        eprintln!("    {agent}: {} tasks", tasks.len());
+    }
+
+    let candidates = consensus::compare(&parses);
+    let keep = consensus::resolve(&candidates, agents.len())?;
+    let tasks = consensus::assemble(&candidates, &keep);
+    Ok(new_task_list(prd_path, tasks))
 }
 
 /// `ralph parse <prd.md>` entry point — parse and print (or write) tasks.
 pub async fn parse_and_print(args: ParseArgs) -> Result<()> {
     let prd_ref = args.prd.as_ref().context("No PRD file specified")?;
-    let task_list = parse_prd(
-        prd_ref,
-        &args.agent,
-        args.model.as_deref(),
-        args.parse_timeout,
-    )
-    .await?;
+    let sampling = Sampling::new(args.reasoning_effort.as_deref(), args.temperature);
+    let task_list = if args.consensus > 1 {
+        parse_prd_consensus(
+            prd_ref,
+            &args.agent,
+            args.model.as_deref(),
+            &sampling,
+            args.parse_timeout,
+            args.parse_retries,
+            args.consensus,
+        )
+        .await?
+    } else {
+        parse_prd(
+            prd_ref,
+            &args.agent,
+            args.model.as_deref(),
+            &sampling,
+            args.parse_timeout,
+            args.parse_retries,
+        )
+        .await?
+    };
 
     println!("\n📋  Tasks extracted from PRD:\n");
     for task in &task_list.tasks {
@@ -103,10 +174,18 @@ pub async fn parse_and_print(args: ParseArgs) -> Result<()> {
         println!();
     }
     println!("Total: {} tasks", task_list.tasks.len());
+    let shape = graph::GraphShape::of(&task_list.tasks);
+    println!(
+        "Dependency graph: critical path {} task(s), up to {} ready at once — {}",
+        shape.critical_path,
+        shape.max_width,
+        shape.advice()
+    );
 
     if let Some(ref output) = args.output {
-        let content =
-            serde_json::to_string_pretty(&task_list).context("Failed to serialise task list")?;
+        let content = TaskFormat::for_path(output)
+            .render(&task_list, None)
+            .context("Failed to serialise task list")?;
         std::fs::write(output, content)
             .with_context(|| format!("Failed to write {}", output.display()))?;
         println!("\n✅  Saved to {}", output.display());
@@ -117,19 +196,179 @@ pub async fn parse_and_print(args: ParseArgs) -> Result<()> {
 
 // ── Private helpers ───────────────────────────────────────────────────────────
 
+fn parse_prompt(prd_path: &Path) -> Result<String> {
+    let prd_content = std::fs::read_to_string(prd_path)
+        .with_context(|| format!("Cannot read PRD file: {}", prd_path.display()))?;
+    // An `--annotate-prd` checklist would read as tasks of its own.
+    let prd_content = crate::orchestrator::strip_prd_progress(&prd_content);
+    Ok(format!("{}{}", PARSE_SYSTEM_PROMPT, prd_content))
+}
+
+/// Turn an agent's raw parse output into tasks.
+fn tasks_from_output(raw: &str) -> Result<Vec<Task>> {
+    validate_tasks(raw).map_err(|error| {
+        anyhow::anyhow!(
+            "Agent output failed validation:\n{}\nRaw output:\n---\n{}\n---",
+            error,
+            raw
+        )
+    })
+}
+
+/// Check parse output against the task schema and the rules the parse prompt
+/// sets. The error is phrased for the agent, so it can go straight into a
+/// corrective prompt.
+fn validate_tasks(raw: &str) -> std::result::Result<Vec<Task>, String> {
+    // Extract the JSON array — the agent might wrap it in prose.
+    let json_str = extract_json_array(raw).ok_or("no JSON array found")?;
+    let items: Vec<serde_json::Value> =
+        serde_json::from_str(&json_str).map_err(|e| format!("invalid JSON: {e}"))?;
+    let tasks = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| Task::deserialize(item).map_err(|e| format!("{e} at index {i}")))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let problems = task_problems(&tasks, &items);
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+    // Catches anything the rules above let through, so a list that parses
+    // cleanly never fails to load later.
+    crate::state::validate_task_list(&new_task_list(Path::new(""), tasks.clone()))
+        .map_err(|e| e.to_string())?;
+    Ok(tasks)
+}
+
+/// Every rule violation in a parsed task list, each with the offending JSON.
+fn task_problems(tasks: &[Task], items: &[serde_json::Value]) -> Vec<String> {
+    if tasks.is_empty() {
+        return vec!["the task array is empty".to_string()];
+    }
+    let mut problems = Vec::new();
+    for (i, (task, item)) in tasks.iter().zip(items).enumerate() {
+        let mut report = |problem: String| {
+            problems.push(format!(
+                "task at index {i}: {problem} — {}",
+                json_excerpt(item)
+            ))
+        };
+        let expected_id = format!("T{}", i + 1);
+        if task.id != expected_id {
+            report(format!(
+                "id `{}` should be `{expected_id}` (ids run T1, T2, T3, …)",
+                task.id
+            ));
+        }
+        if task.priority == 0 {
+            report("priority must be 1 or higher".to_string());
+        }
+        let earlier = &tasks[..i];
+        for dep in &task.depends_on {
+            if !earlier.iter().any(|t| &t.id == dep) {
+                report(format!(
+                    "depends_on `{dep}` is not an earlier task in the list"
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// The offending task as compact JSON, cut short so long descriptions don't
+/// bury the diagnostic.
+fn json_excerpt(item: &serde_json::Value) -> String {
+    const MAX_CHARS: usize = 160;
+    let json = item.to_string();
+    if json.chars().count() <= MAX_CHARS {
+        return json;
+    }
+    let cut: String = json.chars().take(MAX_CHARS).collect();
+    format!("{cut}…")
+}
+
+/// Run the parse prompt through one agent. When its output fails validation,
+/// send the error back and ask for a corrected array, up to `retries` times.
+async fn parse_with_agent(
+    agent: &str,
+    model: Option<&str>,
+    sampling: &Sampling,
+    prompt: &str,
+    parse_timeout_secs: u64,
+    retries: u32,
+) -> Result<Vec<Task>> {
+    let mut attempt_prompt = prompt.to_string();
+    let mut attempt = 0;
+    loop {
+        let raw = try_agent(agent, model, sampling, &attempt_prompt, parse_timeout_secs).await?;
+        let error = match validate_tasks(&raw) {
+            Ok(tasks) => return Ok(tasks),
+            Err(_) if attempt == retries => return tasks_from_output(&raw),
+            Err(error) => error,
+        };
+        attempt += 1;
+        eprintln!(
+            "⚠️  {} returned invalid tasks — asking for a correction ({}/{}):\n{}",
+            agent, attempt, retries, error
+        );
+        attempt_prompt = corrective_prompt(prompt, &raw, &error);
+    }
+}
+
+fn corrective_prompt(prompt: &str, previous: &str, error: &str) -> String {
+    format!(
+        "{prompt}\n\n---\n\nYour previous output was:\n{previous}\n\n\
+         Your output failed: {error} — return only the corrected JSON array."
+    )
+}
+
+fn new_task_list(prd_path: &Path, mut tasks: Vec<Task>) -> TaskList {
+    // A section missing from the PRD would focus the prompt on nothing.
+    if let Ok(content) = std::fs::read_to_string(prd_path) {
+        let known = sections::slugs(&content);
+        for task in &mut tasks {
+            let Some(section) = task.prd_section.take() else {
+                continue;
+            };
+            let section = sections::slug(section.trim_start_matches('#'));
+            if known.contains(&section) {
+                if task.acceptance.is_empty() {
+                    task.acceptance = sections::acceptance(&content, &section);
+                }
+                task.prd_section = Some(section);
+            } else {
+                eprintln!(
+                    "⚠️  {}: prd_section `{section}` matches no PRD heading; it will get the whole PRD",
+                    task.id
+                );
+            }
+        }
+    }
+    TaskList {
+        version: 1,
+        prd_path: prd_path.to_string_lossy().to_string(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        tasks,
+    }
+}
+
 /// Agent ordering for fallback: try the requested agent first, then others.
 /// Agent ordering for fallback. Codex is first because Claude's --print mode
 /// requires ANTHROPIC_API_KEY (OAuth-only installs fail silently).
 const FALLBACK_ORDER: &[&str] = &["codex", "gemini", "api", "claude", "opencode"];
 
-async fn run_agent(
+/// Run a one-shot prompt through `agent`, falling back to other installed agents
+/// on failure or timeout. Returns the agent's raw stdout.
+pub(crate) async fn run_agent(
     agent: &str,
     model: Option<&str>,
+    sampling: &Sampling,
     prompt: &str,
     parse_timeout_secs: u64,
 ) -> Result<String> {
     // Try the requested agent first
-    match try_agent(agent, model, prompt, parse_timeout_secs).await {
+    match try_agent(agent, model, sampling, prompt, parse_timeout_secs).await {
         Ok(output) => return Ok(output),
         Err(e) => {
             eprintln!("⚠️  {} failed: {}", agent, e);
@@ -146,7 +385,7 @@ async fn run_agent(
             continue; // not installed
         }
         eprintln!("🔄  Trying {} as fallback…", fallback);
-        match try_agent(fallback, model, prompt, parse_timeout_secs).await {
+        match try_agent(fallback, model, sampling, prompt, parse_timeout_secs).await {
             Ok(output) => return Ok(output),
             Err(e) => {
                 eprintln!("⚠️  {} also failed: {}", fallback, e);
@@ -181,7 +420,12 @@ fn agent_on_path(name: &str) -> bool {
 
 /// Build agent command. The prompt is written to a temp file and read via
 /// stdin to avoid hitting the OS ARG_MAX (E2BIG) limit on large PRDs.
-fn build_agent_command(agent: &str, model: Option<&str>, prompt: &str) -> Result<(Command, Vec<u8>)> {
+fn build_agent_command(
+    agent: &str,
+    model: Option<&str>,
+    sampling: &Sampling,
+    prompt: &str,
+) -> Result<(Command, Vec<u8>)> {
     let prompt_bytes = prompt.as_bytes().to_vec();
     let mut cmd = match agent {
         "claude" => {
@@ -193,6 +437,9 @@ fn build_agent_command(agent: &str, model: Option<&str>, prompt: &str) -> Result
             if let Some(m) = model {
                 c.arg("--model").arg(m);
             }
+            if let Some(budget) = sampling.thinking_budget() {
+                c.env("MAX_THINKING_TOKENS", budget.to_string());
+            }
             c
         }
         "gemini" => {
@@ -209,6 +456,9 @@ fn build_agent_command(agent: &str, model: Option<&str>, prompt: &str) -> Result
             if let Some(m) = model {
                 c.arg("--model").arg(m);
             }
+            if let Some(config) = sampling.codex_config() {
+                c.arg("-c").arg(config);
+            }
             c
         }
         "opencode" => {
@@ -261,6 +511,7 @@ fn is_claude_api_key_error(stderr: &str) -> bool {
 async fn try_agent(
     agent: &str,
     model: Option<&str>,
+    sampling: &Sampling,
     prompt: &str,
     parse_timeout_secs: u64,
 ) -> Result<String> {
@@ -268,18 +519,43 @@ async fn try_agent(
         probe_claude_print_auth().await?;
     }
 
-    let (mut cmd, prompt_bytes) = build_agent_command(agent, model, prompt)?;
-
-    let mut child = cmd.spawn().with_context(|| format!("Failed to spawn {} — is it installed?", agent))?;
-
-    // Write prompt to stdin
-    if let Some(mut stdin) = child.stdin.take() {
-        use tokio::io::AsyncWriteExt;
-        let _ = stdin.write_all(&prompt_bytes).await;
-        let _ = stdin.shutdown().await;
-    }
+    // Ollama and the API agent have no CLI that reads a prompt on stdin;
+    // their HTTP agents do the same job, and are the ones that take a
+    // temperature (`--parse-temperature`).
+    let model_name = model.map(str::to_string);
+    let http: Option<Box<dyn Agent>> = match agent {
+        "ollama" => Some(Box::new(OllamaAgent::new(
+            None,
+            model_name,
+            sampling.clone(),
+        )?)),
+        "api" => Some(Box::new(ApiAgent::new(
+            None,
+            None,
+            model_name,
+            "anthropic",
+            sampling.clone(),
+        )?)),
+        _ => None,
+    };
+    let proc: AgentProcess = match &http {
+        Some(http) => http.spawn(prompt, Path::new("."), &[])?,
+        None => {
+            let (mut cmd, prompt_bytes) = build_agent_command(agent, model, sampling, prompt)?;
+
+            let mut child = cmd.spawn().with_context(|| format!("Failed to spawn {} — is it installed?", agent))?;
+
+            // Write prompt to stdin
+            if let Some(mut stdin) = child.stdin.take() {
+                use tokio::io::AsyncWriteExt;
+                let _ = stdin.write_all(&prompt_bytes).await;
+                let _ = stdin.shutdown().await;
+            }
+            child.into()
+        }
+    };
 
-    let output = match timeout(Duration::from_secs(parse_timeout_secs), child.wait_with_output()).await {
+    let output = match timeout(Duration::from_secs(parse_timeout_secs), proc.wait_with_output()).await {
         Ok(result) => {
             result.with_context(|| format!("Failed to spawn {} — is it installed?", agent))?
         }
@@ -306,8 +582,11 @@ fn extract_json_array(text: &str) -> Option<String> {
 }
 
 #[cfg(test)]
+#[allow(clippy::await_holding_lock)] // env mutations are serialized across awaits on purpose
 mod tests {
-    use super::{is_claude_api_key_error, parse_prd};
+    use super::{is_claude_api_key_error, parse_prd, parse_prompt, try_agent, validate_tasks};
+    use crate::agents::Sampling;
+    use serde_json::json;
     use std::fs;
     use std::os::unix::fs::PermissionsExt;
     use std::path::{Path, PathBuf};
@@ -365,7 +644,7 @@ mod tests {
 
         let _path_guard = PathGuard::prepend(&bin_dir);
 
-        let task_list = parse_prd(&prd_path, "claude", None, 1)
+        let task_list = parse_prd(&prd_path, "claude", None, &Sampling::default(), 1, 0)
             .await
             .expect("fallback should parse");
 
@@ -402,11 +681,114 @@ mod tests {
 
         let _path_guard = PathGuard::prepend(&bin_dir);
 
-        let task_list = parse_prd(&prd_path, "claude", None, 5)
+        let task_list = parse_prd(&prd_path, "claude", None, &Sampling::default(), 5, 0)
             .await
             .expect("fallback should parse");
 
         assert_eq!(task_list.tasks.len(), 1);
         assert_eq!(task_list.tasks[0].id, "T1");
     }
+
+    #[tokio::test]
+    async fn the_parse_temperature_reaches_the_api_agent() {
+        let sampling = Sampling::new(None, Some(1.5));
+        let err = try_agent("api", None, &sampling, "hi", 5)
+            .await
+            .expect_err("out of range for the API");
+        assert!(err.to_string().contains("between 0 and 1"), "{err}");
+    }
+
+    #[test]
+    fn parse_prompt_leaves_out_the_annotated_progress_checklist() {
+        let dir = tempdir().expect("create tempdir");
+        let prd_path = dir.path().join("prd.md");
+        fs::write(
+            &prd_path,
+            "# PRD\n\n- Add login\n\n\
+             <!-- ralph:progress:start — managed by ralph, edits are overwritten -->\n\
+             ## Ralph progress\n\n- [x] T1 — Add login\n\
+             <!-- ralph:progress:end -->\n",
+        )
+        .expect("write prd");
+
+        let prompt = parse_prompt(&prd_path).expect("build prompt");
+        assert!(prompt.ends_with("# PRD\n\n- Add login\n"), "{prompt}");
+        assert!(!prompt.contains("Ralph progress"), "{prompt}");
+    }
+
+    #[test]
+    fn validation_errors_name_the_offending_task() {
+        let valid = r#"Here you go: [{"id":"T1","title":"Task","description":"Desc","priority":1,"status":"pending","depends_on":[]}]"#;
+        assert_eq!(validate_tasks(valid).expect("valid").len(), 1);
+
+        let missing_title = r#"[{"id":"T1","title":"Task","description":"Desc","priority":1,"status":"pending","depends_on":[]},
+            {"id":"T2","description":"Desc","priority":2,"status":"pending","depends_on":[]}]"#;
+        assert_eq!(
+            validate_tasks(missing_title).expect_err("invalid"),
+            "missing field `title` at index 1"
+        );
+        assert!(validate_tasks("[{\"id\": }]")
+            .expect_err("invalid")
+            .starts_with("invalid JSON"));
+        assert_eq!(
+            validate_tasks("no tasks here").expect_err("invalid"),
+            "no JSON array found"
+        );
+    }
+
+    #[test]
+    fn strict_validation_reports_every_rule_violation_with_its_json() {
+        let task = |id: &str, priority: u32, deps: &[&str]| {
+            json!({"id": id, "title": "Task", "description": "Desc", "priority": priority,
+                   "status": "pending", "depends_on": deps})
+        };
+        let raw = json!([
+            task("T1", 1, &["T2"]),
+            task("T2", 0, &[]),
+            task("T4", 3, &["T1"])
+        ])
+        .to_string();
+
+        let problems: Vec<String> = validate_tasks(&raw)
+            .expect_err("invalid")
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with(
+            "task at index 0: depends_on `T2` is not an earlier task in the list — {\"depends_on\":[\"T2\"]"
+        ));
+        assert!(problems[1].starts_with("task at index 1: priority must be 1 or higher"));
+        assert!(problems[2].starts_with("task at index 2: id `T4` should be `T3`"));
+
+        assert_eq!(
+            validate_tasks("[]").expect_err("empty"),
+            "the task array is empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_prd_sends_validation_errors_back_for_correction() {
+        let _guard = crate::global_env_lock().lock().expect("lock env mutation");
+        let dir = tempdir().expect("create tempdir");
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).expect("create bin dir");
+        let prd_path = dir.path().join("prd.md");
+        fs::write(&prd_path, "# Demo PRD").expect("write prd");
+
+        // Drops the title until the prompt carries the validation error.
+        write_fake_agent(
+            &bin_dir,
+            "codex",
+            "if grep -q 'missing field `title` at index 0'; then\n  echo '[{\"id\":\"T1\",\"title\":\"Task\",\"description\":\"Desc\",\"priority\":1,\"status\":\"pending\",\"depends_on\":[]}]'\nelse\n  echo '[{\"id\":\"T1\",\"description\":\"Desc\",\"priority\":1,\"status\":\"pending\",\"depends_on\":[]}]'\nfi",
+        );
+
+        let _path_guard = PathGuard::prepend(&bin_dir);
+
+        let task_list = parse_prd(&prd_path, "codex", None, &Sampling::default(), 5, 1)
+            .await
+            .expect("corrected parse");
+
+        assert_eq!(task_list.tasks[0].title, "Task");
+    }
 }
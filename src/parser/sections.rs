@@ -0,0 +1,177 @@
+//! PRD sections addressed by heading slug, so an iteration prompt can carry
+//! the part of the PRD a task comes from instead of the whole document.
+
+/// GitHub-style anchor for a heading: lowercase, punctuation dropped, spaces
+/// turned into hyphens (`## User Login (v2)` → `user-login-v2`).
+pub fn slug(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '-' => Some('-'),
+            c if c.is_alphanumeric() || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `(level, text)` of a Markdown ATX heading line. Lines inside code fences
+/// are the caller's concern.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')))
+        .then(|| (level, rest.trim().trim_end_matches('#').trim()))
+}
+
+/// Headings in `content` with the byte offset their line starts at.
+fn headings(content: &str) -> Vec<(usize, usize, String)> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    let mut in_fence = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some((level, text)) = heading(trimmed) {
+                found.push((offset, level, slug(text)));
+            }
+        }
+        offset += line.len();
+    }
+    found
+}
+
+/// Every heading slug in `content`.
+pub fn slugs(content: &str) -> Vec<String> {
+    headings(content).into_iter().map(|(_, _, s)| s).collect()
+}
+
+/// The overview (everything before the first sub-heading) followed by the
+/// section whose heading slugs to `section`, including its sub-sections.
+/// `None` if no heading matches.
+pub fn focus(content: &str, section: &str) -> Option<String> {
+    let headings = headings(content);
+    let (index, &(start, level, _)) = headings
+        .iter()
+        .enumerate()
+        .find(|(_, (_, _, s))| s == section)?;
+    let end = headings[index + 1..]
+        .iter()
+        .find(|(_, l, _)| *l <= level)
+        .map_or(content.len(), |(offset, _, _)| *offset);
+    // The overview stops at the first heading below the document title.
+    let overview_end = headings
+        .iter()
+        .find(|(_, l, _)| *l > 1)
+        .map_or(0, |(offset, _, _)| *offset)
+        .min(start);
+
+    let mut out = content[..overview_end].trim_end().to_string();
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    out.push_str(content[start..end].trim_end());
+    out.push('\n');
+    Some(out)
+}
+
+/// Bullets listed under an "Acceptance criteria" heading or label inside
+/// the section whose heading slugs to `section`. Empty if the section has no
+/// such list.
+pub fn acceptance(content: &str, section: &str) -> Vec<String> {
+    let headings = headings(content);
+    let Some((index, &(start, level, _))) = headings
+        .iter()
+        .enumerate()
+        .find(|(_, (_, _, s))| s == section)
+    else {
+        return Vec::new();
+    };
+    let end = headings[index + 1..]
+        .iter()
+        .find(|(_, l, _)| *l <= level)
+        .map_or(content.len(), |(offset, _, _)| *offset);
+
+    let mut criteria = Vec::new();
+    let mut in_list = false;
+    for line in content[start..end].lines().skip(1) {
+        let text = line.trim();
+        if text.to_lowercase().contains("acceptance criteria") && bullet(text).is_none() {
+            in_list = true;
+        } else if in_list {
+            match bullet(text) {
+                Some(item) => criteria.push(item.to_string()),
+                None if text.is_empty() => {}
+                // The list ends at the next paragraph or heading.
+                None => in_list = false,
+            }
+        }
+    }
+    criteria
+}
+
+/// The text of a `-`, `*`, `+`, or `- [ ]` list item.
+fn bullet(text: &str) -> Option<&str> {
+    let item = text
+        .strip_prefix("- ")
+        .or_else(|| text.strip_prefix("* "))
+        .or_else(|| text.strip_prefix("+ "))?;
+    let item = ["[ ] ", "[x] ", "[X] "]
+        .iter()
+        .find_map(|box_| item.strip_prefix(box_))
+        .unwrap_or(item)
+        .trim();
+    (!item.is_empty()).then_some(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRD: &str = "# Shop\n\nAn online shop.\n\n\
+                       ## User Login (v2)\n\nEmail and password.\n\n### Sessions\n\nCookies.\n\n\
+                       ## Checkout\n\n```md\n## Not a heading\n```\nPay by card.\n";
+
+    #[test]
+    fn slugs_follow_github_anchors() {
+        assert_eq!(slug("User Login (v2)"), "user-login-v2");
+        assert_eq!(slug(" API: rate_limits "), "api-rate_limits");
+        assert_eq!(
+            slugs(PRD),
+            ["shop", "user-login-v2", "sessions", "checkout"]
+        );
+    }
+
+    #[test]
+    fn focus_keeps_the_overview_and_the_whole_section() {
+        let login = focus(PRD, "user-login-v2").expect("section exists");
+        assert_eq!(
+            login,
+            "# Shop\n\nAn online shop.\n\n## User Login (v2)\n\nEmail and password.\n\n### Sessions\n\nCookies.\n"
+        );
+
+        let checkout = focus(PRD, "checkout").expect("section exists");
+        assert!(checkout.starts_with("# Shop\n\nAn online shop.\n\n## Checkout"));
+        assert!(checkout.contains("## Not a heading\n```\nPay by card."));
+        assert!(!checkout.contains("Email and password"));
+
+        assert_eq!(focus(PRD, "missing"), None);
+    }
+
+    #[test]
+    fn acceptance_criteria_come_from_the_tasks_section() {
+        let prd = "# Shop\n\n## Login\n\nEmail and password.\n\n\
+                   **Acceptance criteria:**\n\n- [ ] A bad password gets a 401\n\
+                   * Sessions expire after a day\n\nNotes:\n- not a criterion\n\n\
+                   ## Checkout\n\n### Acceptance Criteria\n- Pay by card\n";
+        assert_eq!(
+            acceptance(prd, "login"),
+            ["A bad password gets a 401", "Sessions expire after a day"]
+        );
+        assert_eq!(acceptance(prd, "checkout"), ["Pay by card"]);
+        assert!(acceptance(prd, "missing").is_empty());
+    }
+}
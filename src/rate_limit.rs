@@ -1,44 +1,130 @@
+use std::fmt;
 use std::time::Duration;
 
+/// Phrases (lowercase) the agents and their providers use for a rate
+/// limit. A bare `429` or "rate limit" could be a line number, a test name,
+/// or the project's own code.
+const MARKERS: &[&str] = &[
+    "429 too many requests",
+    "http 429",
+    "status 429",
+    "status: 429",
+    "status code 429",
+    "rate limit reached",
+    "rate limit exceeded",
+    "rate_limit_error",
+    "rate_limit_exceeded",
+    "usage limit reached",
+    "hit your usage limit",
+    "usage_limit_reached",
+    "resource_exhausted",
+];
+
+/// Rate-limited iterations in a row that are retried without counting a
+/// failure; after that, the limit fails the iteration like any other error.
+pub const MAX_RETRIES: u32 = 5;
+
+/// Labels (lowercase) a retry time in seconds follows: a `Retry-After: 30`
+/// header, Codex's `"resets_in_seconds": 105210`, or `retry after 30s`.
+const RETRY_LABELS: &[&str] = &["retry-after", "resets_in_seconds", "retry after"];
+
+/// Wait used when a rate limit names no retry time.
+const DEFAULT_RETRY: Duration = Duration::from_secs(60);
+
 /// Detects rate limit errors (HTTP 429, usage limit) in agent output.
 ///
 /// If detected, tries to parse a retry duration from the output.
 /// Returns `Some(Duration)` if a rate limit is detected, `None` otherwise.
 pub fn detect_rate_limit(output: &str) -> Option<Duration> {
-    if output.contains("429") || output.contains("usage limit") || output.contains("rate limit") {
-        // Try to find explicit "Retry-After" or "resets_in_seconds"
-        if let Some(idx) = output.find("Retry-After: ") {
-            let remaining = &output[idx + "Retry-After: ".len()..];
-            if let Some(newline_idx) = remaining.find(|c: char| !c.is_numeric()) {
-                let secs_str = &remaining[..newline_idx];
-                if let Ok(secs) = secs_str.parse::<u64>() {
-                    return Some(Duration::from_secs(secs));
-                }
-            }
-        } else if let Some(idx) = output.find("resets_in_seconds") {
-            let remaining = &output[idx + "resets_in_seconds".len()..];
-            // Try to parse a number after "resets_in_seconds"
-            // This is a bit brittle - assumes format like: `resets_in_seconds": 105210}`
-            if let Some(start_num) = remaining.find(|c: char| c.is_numeric()) {
-                 let num_str = &remaining[start_num..];
-                 if let Some(end_num) = num_str.find(|c: char| !c.is_numeric()) {
-                     let secs_str = &num_str[..end_num];
-                     if let Ok(secs) = secs_str.parse::<u64>() {
-                        return Some(Duration::from_secs(secs));
-                     }
-                 } else {
-                     // it's the last part of the string
-                     if let Ok(secs) = num_str.parse::<u64>() {
-                        return Some(Duration::from_secs(secs));
-                     }
-                 }
-
-            }
+    let lower = output.to_lowercase();
+    if !MARKERS.iter().any(|marker| lower.contains(marker)) {
+        return None;
+    }
+    let secs = RETRY_LABELS
+        .iter()
+        .find_map(|label| number_after(&lower, label));
+    Some(secs.map(Duration::from_secs).unwrap_or(DEFAULT_RETRY))
+}
+
+/// The first number following `label` in `text`.
+fn number_after(text: &str, label: &str) -> Option<u64> {
+    let rest = &text[text.find(label)? + label.len()..];
+    let start = rest.find(|c: char| c.is_ascii_digit())?;
+    // Only separators may come between the label and the number.
+    if rest[..start].chars().any(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    let digits = &rest[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end].parse().ok()
+}
+
+/// An iteration that ended on a provider rate limit rather than a failure of
+/// its own; the orchestrator waits `retry_after` and retries the task.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limited; retry in {}s", self.retry_after.as_secs())
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// How long to wait, if `err` is a rate limit.
+pub fn retry_after(err: &anyhow::Error) -> Option<Duration> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<RateLimited>())
+        .map(|limit| limit.retry_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limits_carry_their_retry_time() {
+        assert_eq!(
+            detect_rate_limit("Error: 429 Too Many Requests\nRetry-After: 30"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            detect_rate_limit(
+                r#"{"error":{"type":"usage_limit_reached","resets_in_seconds": 105210}}"#
+            ),
+            Some(Duration::from_secs(105210))
+        );
+        assert_eq!(
+            detect_rate_limit("Rate limit reached for requests"),
+            Some(DEFAULT_RETRY)
+        );
+        assert_eq!(detect_rate_limit("error: file not found"), None);
+    }
+
+    #[test]
+    fn output_that_merely_mentions_a_limit_is_not_one() {
+        for output in [
+            "error: expected `;` at src/api.rs:429:5",
+            "test client::retries_on_429 ... ok",
+            "fn rate_limit(&self) -> Duration {",
+            "- Handle rate_limit responses from the upstream API",
+        ] {
+            assert_eq!(detect_rate_limit(output), None, "{output}");
         }
+    }
 
-        // Default to 1 minute if no specific duration is found
-        Some(Duration::from_secs(60))
-    } else {
-        None
+    #[test]
+    fn rate_limited_errors_are_found_under_context() {
+        let err = anyhow::Error::new(RateLimited {
+            retry_after: Duration::from_secs(5),
+        })
+        .context("Iteration 3");
+        assert_eq!(retry_after(&err), Some(Duration::from_secs(5)));
+        assert_eq!(retry_after(&anyhow::anyhow!("agent crashed")), None);
     }
 }
@@ -0,0 +1,315 @@
+//! `ralph remote run <HOST>` — run a PRD on another machine over SSH, for
+//! when the agents and their credentials live on a dev server.
+//!
+//! Everything goes through the `ssh` binary, so `~/.ssh/config` aliases,
+//! keys, and agents work as they do for the user: the PRD is piped to the
+//! host, ralph is found there (or installed), and `ralph run` is started in
+//! the project directory with its output streamed back. Once it ends, the
+//! host's `progress.md` is copied to `.ralph/remote/<HOST>/`.
+
+use anyhow::{bail, Context, Result};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::cli::RemoteRunArgs;
+use crate::shell::quote;
+use crate::state::StateManager;
+
+/// Where an installed copy of ralph goes on the host.
+const INSTALL_PATH: &str = "~/.local/bin/ralph";
+
+/// Prints the host's `uname -sm`, then the path of its ralph binary if one
+/// is found.
+const PROBE_SCRIPT: &str = r#"uname -sm
+command -v ralph 2>/dev/null && exit 0
+for bin in "$HOME/.cargo/bin/ralph" "$HOME/.local/bin/ralph"; do
+  [ -x "$bin" ] && echo "$bin" && exit 0
+done
+exit 0"#;
+
+pub fn run(args: RemoteRunArgs) -> Result<()> {
+    check_host(&args.host)?;
+    let workdir = args
+        .workdir
+        .as_deref()
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+    let prd = std::fs::read(&args.prd)
+        .with_context(|| format!("Cannot read PRD {}", args.prd.display()))?;
+    let prd_name = args
+        .prd
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("The PRD path has no file name")?;
+    let remote_dir = match &args.remote_dir {
+        Some(dir) => dir.clone(),
+        None => format!(
+            "~/{}",
+            workdir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .context("Cannot name the remote directory after the workdir; pass --remote-dir")?
+        ),
+    };
+    let ssh = Ssh {
+        host: &args.host,
+        options: &args.ssh_options,
+    };
+
+    println!("🔎  Looking for ralph on {}…", args.host);
+    let probe = Probe::parse(&ssh.output(&format!("sh -c {}", quote(PROBE_SCRIPT)))?);
+    let bin = match (&args.ralph_bin, probe.bin) {
+        (Some(bin), _) => bin.clone(),
+        (None, Some(bin)) => bin,
+        (None, None) => install(&ssh, &probe.platform)?,
+    };
+
+    println!("📤  Copying {prd_name} to {}:{remote_dir}", args.host);
+    let dir = remote_path(&remote_dir);
+    ssh.upload(
+        &format!("mkdir -p {dir} && cat > {dir}/{}", quote(prd_name)),
+        &prd,
+    )?;
+
+    println!("🚀  Running ralph on {}\n", args.host);
+    let status = ssh
+        .command(std::io::stdin().is_terminal())
+        .arg(run_command(&bin, &remote_dir, prd_name, &args.run_args))
+        .status()
+        .context("Failed to run ssh — is it installed?")?;
+
+    // Best effort: the run's outcome matters more than the copy.
+    if let Ok(progress) = ssh.output(&format!("cat {dir}/.ralph/progress.md")) {
+        let state = StateManager::new(&workdir)?;
+        let local = state.ralph_dir.join("remote").join(&args.host);
+        std::fs::create_dir_all(&local)?;
+        std::fs::write(local.join("progress.md"), progress)?;
+        println!(
+            "\n📥  Progress log copied to {}",
+            local.join("progress.md").display()
+        );
+    }
+
+    if !status.success() {
+        match status.code() {
+            Some(code) => bail!("ralph on {} exited with code {code}", args.host),
+            None => bail!("ssh to {} was interrupted", args.host),
+        }
+    }
+    Ok(())
+}
+
+/// Refuse a host ssh would read as an option, or that would put the copied
+/// progress log outside `.ralph/remote/`.
+fn check_host(host: &str) -> Result<()> {
+    if host.is_empty() || host.starts_with('-') {
+        bail!("Invalid host {host:?}: it must not be empty or start with '-'");
+    }
+    if host.contains(['/', '\\'])
+        || host
+            .split(['@', ':'])
+            .any(|part| matches!(part, "." | ".."))
+    {
+        bail!("Invalid host {host:?}: it must not contain path separators or '..'");
+    }
+    Ok(())
+}
+
+/// An SSH destination and the `-o` options to reach it.
+struct Ssh<'a> {
+    host: &'a str,
+    options: &'a [String],
+}
+
+impl Ssh<'_> {
+    fn command(&self, tty: bool) -> Command {
+        let mut cmd = Command::new("ssh");
+        for option in self.options {
+            cmd.arg("-o").arg(option);
+        }
+        cmd.arg(if tty { "-t" } else { "-T" })
+            .arg("--")
+            .arg(self.host);
+        cmd
+    }
+
+    /// Run `script` on the host and return its stdout.
+    fn output(&self, script: &str) -> Result<String> {
+        let output = self
+            .command(false)
+            .arg(script)
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run ssh — is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "ssh {}: {}",
+                self.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Run `script` on the host with `data` as its stdin.
+    fn upload(&self, script: &str, data: &[u8]) -> Result<()> {
+        let mut child = self
+            .command(false)
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run ssh — is it installed?")?;
+        std::io::Write::write_all(&mut child.stdin.take().context("ssh stdin")?, data)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "ssh {}: {}",
+                self.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// What the probe found on the host.
+#[derive(Debug, PartialEq)]
+struct Probe {
+    /// `uname -sm`, e.g. `Linux x86_64`.
+    platform: String,
+    bin: Option<String>,
+}
+
+impl Probe {
+    fn parse(output: &str) -> Self {
+        let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+        Self {
+            platform: lines.next().unwrap_or_default().to_string(),
+            bin: lines.next().map(str::to_string),
+        }
+    }
+}
+
+/// Put ralph on a host that has none: this binary when the host runs the
+/// same OS and architecture, otherwise a `cargo install` there.
+fn install(ssh: &Ssh, platform: &str) -> Result<String> {
+    if platform == local_platform() {
+        println!(
+            "📦  Installing this ralph binary at {}:{INSTALL_PATH}",
+            ssh.host
+        );
+        let exe = std::env::current_exe().context("Cannot find the running ralph binary")?;
+        let binary =
+            std::fs::read(&exe).with_context(|| format!("Cannot read {}", exe.display()))?;
+        let path = remote_path(INSTALL_PATH);
+        ssh.upload(
+            &format!("mkdir -p \"$HOME/.local/bin\" && cat > {path} && chmod +x {path}"),
+            &binary,
+        )?;
+        return Ok(INSTALL_PATH.to_string());
+    }
+
+    println!(
+        "📦  {} runs {platform}, not {}: building ralph there with cargo",
+        ssh.host,
+        local_platform()
+    );
+    let install = format!(
+        "command -v cargo >/dev/null || . \"$HOME/.cargo/env\"; cargo install --locked --git {} {}",
+        env!("CARGO_PKG_REPOSITORY"),
+        env!("CARGO_PKG_NAME")
+    );
+    let status = ssh
+        .command(false)
+        .arg(format!("sh -c {}", quote(&install)))
+        .status()
+        .context("Failed to run ssh — is it installed?")?;
+    if !status.success() {
+        bail!(
+            "Could not install ralph on {}; install it there or pass --ralph-bin",
+            ssh.host
+        );
+    }
+    Ok("~/.cargo/bin/ralph".to_string())
+}
+
+/// This machine in `uname -sm` terms.
+fn local_platform() -> String {
+    let os = match std::env::consts::OS {
+        "linux" => "Linux",
+        "macos" => "Darwin",
+        other => other,
+    };
+    let arch = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", "aarch64") => "arm64",
+        (_, arch) => arch,
+    };
+    format!("{os} {arch}")
+}
+
+/// The command that starts the run on the host.
+fn run_command(bin: &str, dir: &str, prd_name: &str, run_args: &[String]) -> String {
+    let mut words = vec![remote_path(bin), "run".to_string(), quote(prd_name)];
+    words.extend(run_args.iter().map(|arg| quote(arg)));
+    format!("cd {} && {}", remote_path(dir), words.join(" "))
+}
+
+/// `path` quoted for the host's shell, keeping a leading `~/` expandable.
+fn remote_path(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("\"$HOME\"/{}", quote(rest)),
+        None => quote(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_output_gives_the_platform_and_binary() {
+        assert_eq!(
+            Probe::parse("Linux x86_64\n/home/dev/.cargo/bin/ralph\n"),
+            Probe {
+                platform: "Linux x86_64".to_string(),
+                bin: Some("/home/dev/.cargo/bin/ralph".to_string()),
+            }
+        );
+        assert_eq!(Probe::parse("Darwin arm64\n").bin, None);
+    }
+
+    #[test]
+    fn hosts_that_look_like_options_or_paths_are_refused() {
+        for host in ["dev", "me@dev.example.com", "dev:2222", "10.0.0.7"] {
+            assert!(check_host(host).is_ok(), "{host}");
+        }
+        for host in [
+            "",
+            "-oProxyCommand=touch /tmp/x",
+            "../../etc",
+            "a/b",
+            "a\\b",
+            "..",
+            "me@..",
+        ] {
+            assert!(check_host(host).is_err(), "{host}");
+        }
+    }
+
+    #[test]
+    fn run_command_quotes_for_the_remote_shell() {
+        let args = ["--agent", "claude", "--verify-cmd", "cargo test --all"].map(String::from);
+        assert_eq!(
+            run_command("~/.local/bin/ralph", "~/my app", "prd.md", &args),
+            "cd \"$HOME\"/'my app' && \"$HOME\"/.local/bin/ralph run prd.md \
+             --agent claude --verify-cmd 'cargo test --all'"
+        );
+        assert_eq!(
+            run_command("ralph", "/srv/app", "it's.md", &[]),
+            "cd /srv/app && ralph run 'it'\\''s.md'"
+        );
+    }
+}
@@ -0,0 +1,601 @@
+//! Recurring runs: `ralph schedule` registers a PRD or template with a cron
+//! expression under `~/.ralph/schedules/`, and `ralph daemon` launches a fresh
+//! `ralph run` for each schedule when it comes due.
+//!
+//! Each schedule is a small JSON file; the output of its runs is appended to a
+//! `<name>.log` file next to it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, Duration};
+
+use crate::cli::ScheduleArgs;
+use crate::systemd;
+
+/// Directory where schedules (and their run logs) are stored.
+fn schedules_dir() -> Result<PathBuf> {
+    let dir = dirs::home_dir()
+        .context("Cannot determine home directory")?
+        .join(".ralph")
+        .join("schedules");
+    fs::create_dir_all(&dir).context("Cannot create ~/.ralph/schedules/")?;
+    Ok(dir)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Schedule {
+    pub name: String,
+    pub cron: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prd: Option<PathBuf>,
+    pub workdir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+impl Schedule {
+    /// Arguments for the `ralph run` this schedule launches.
+    fn run_args(&self) -> Vec<String> {
+        let mut args = vec!["run".to_string()];
+        match (&self.template, &self.prd) {
+            (Some(template), _) => args.extend(["--template".to_string(), template.clone()]),
+            (None, Some(prd)) => args.push(prd.display().to_string()),
+            (None, None) => {}
+        }
+        args.extend(["--workdir".to_string(), self.workdir.display().to_string()]);
+        if let Some(agent) = &self.agent {
+            args.extend(["--agent".to_string(), agent.clone()]);
+        }
+        // Every firing works from the current PRD, not last night's task list.
+        args.push("--fresh".to_string());
+        args
+    }
+
+    /// Whether the schedule should fire in the minute containing `now`.
+    /// Already having run in that minute (e.g. a restarted daemon) counts as done.
+    fn is_due(&self, cron: &Cron, now: DateTime<Local>) -> bool {
+        if !cron.matches(&now) {
+            return false;
+        }
+        match self.last_run {
+            Some(last) => minute_of(last.with_timezone(&Local)) != minute_of(now),
+            None => true,
+        }
+    }
+}
+
+fn minute_of(t: DateTime<Local>) -> i64 {
+    t.timestamp().div_euclid(60)
+}
+
+// ── Cron expressions ─────────────────────────────────────────────────────────
+
+/// A standard five-field cron expression (minute hour day-of-month month
+/// day-of-week), evaluated in local time. Fields accept `*`, numbers, lists,
+/// ranges, and `/step`; day-of-week 0 and 7 are both Sunday.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            anyhow::bail!(
+                "Invalid cron expression '{expr}': expected 5 fields (minute hour day month weekday)"
+            );
+        };
+        let field = |value: &str, name: &str, min: u32, max: u32| {
+            parse_field(value, min, max)
+                .with_context(|| format!("Invalid {name} field '{value}' in cron '{expr}'"))
+        };
+
+        let mut weekdays = field(weekday, "day-of-week", 0, 7)?;
+        for d in &mut weekdays {
+            *d %= 7;
+        }
+        weekdays.sort_unstable();
+        weekdays.dedup();
+
+        Ok(Self {
+            minutes: field(minute, "minute", 0, 59)?,
+            hours: field(hour, "hour", 0, 23)?,
+            days: field(day, "day-of-month", 1, 31)?,
+            months: field(month, "month", 1, 12)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    pub fn matches(&self, t: &DateTime<Local>) -> bool {
+        if !self.minutes.contains(&t.minute())
+            || !self.hours.contains(&t.hour())
+            || !self.months.contains(&t.month())
+        {
+            return false;
+        }
+        let day = self.days.contains(&t.day());
+        let weekday = self.weekdays.contains(&t.weekday().num_days_from_sunday());
+        // As in cron(8): when both day fields are restricted, either may match.
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute strictly after `from`, within a year.
+    pub fn next_after(&self, from: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut t = from.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let limit = from + chrono::Duration::days(366);
+        while t <= limit {
+            if self.matches(&t) {
+                return Some(t);
+            }
+            t += chrono::Duration::minutes(1);
+        }
+        None
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().context("step is not a number")?;
+                anyhow::ensure!(step > 0, "step must be at least 1");
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (a.parse()?, b.parse()?)
+        } else {
+            let value: u32 = range.parse()?;
+            // `5/15` means "from 5 to the end, every 15".
+            (value, if step > 1 { max } else { value })
+        };
+        anyhow::ensure!(
+            min <= start && start <= end && end <= max,
+            "{range} is outside {min}-{max}"
+        );
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+// ── Storage ──────────────────────────────────────────────────────────────────
+
+fn schedule_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.json"))
+}
+
+fn log_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.log"))
+}
+
+pub fn save_to(dir: &Path, schedule: &Schedule) -> Result<()> {
+    let json = serde_json::to_string_pretty(schedule)?;
+    let path = schedule_path(dir, &schedule.name);
+    fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn load_all_from(dir: &Path) -> Result<Vec<Schedule>> {
+    let mut schedules = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        match serde_json::from_str::<Schedule>(&content) {
+            Ok(schedule) => schedules.push(schedule),
+            Err(e) => eprintln!("⚠️   Skipping {}: {e}", path.display()),
+        }
+    }
+    schedules.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(schedules)
+}
+
+pub fn remove_from(dir: &Path, name: &str) -> Result<()> {
+    // The name becomes a path: `../x` must not reach outside `dir`.
+    if !valid_name(name) {
+        anyhow::bail!("Invalid schedule name '{name}'. Use letters, digits, '-' or '_'.");
+    }
+    let path = schedule_path(dir, name);
+    if !path.exists() {
+        anyhow::bail!("Schedule '{name}' not found. Run `ralph schedule --list` to see schedules.");
+    }
+    fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+/// Schedule names become file names.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// ── Commands ─────────────────────────────────────────────────────────────────
+
+/// `ralph schedule`: register, list, or remove schedules.
+pub fn command(args: ScheduleArgs) -> Result<()> {
+    let dir = schedules_dir()?;
+    if args.list {
+        return list(&dir);
+    }
+    if let Some(name) = &args.remove {
+        remove_from(&dir, name)?;
+        println!("✅  Removed schedule '{name}'");
+        return Ok(());
+    }
+
+    let cron_expr = args.cron.context("--cron is required")?;
+    let cron = Cron::parse(&cron_expr)?;
+
+    let prd = match (&args.template, &args.prd) {
+        (Some(template), _) => {
+            // Fail now rather than at 2 a.m.
+            crate::templates::get(template)?;
+            None
+        }
+        (None, Some(prd)) => Some(
+            prd.canonicalize()
+                .with_context(|| format!("PRD file not found: {}", prd.display()))?,
+        ),
+        (None, None) => anyhow::bail!("Pass a PRD file or --template to schedule"),
+    };
+    let workdir = args
+        .workdir
+        .unwrap_or_else(|| PathBuf::from("."))
+        .canonicalize()
+        .context("Invalid --workdir")?;
+
+    let name = match args.name.clone() {
+        Some(name) => name,
+        None => args
+            .template
+            .clone()
+            .or_else(|| {
+                prd.as_ref()
+                    .and_then(|p| p.file_stem())
+                    .map(|s| s.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default(),
+    };
+    if !valid_name(&name) {
+        anyhow::bail!(
+            "Invalid schedule name '{name}'. Use --name with letters, digits, '-' or '_'."
+        );
+    }
+
+    let schedule = Schedule {
+        name: name.clone(),
+        cron: cron_expr,
+        template: args.template,
+        prd,
+        workdir,
+        agent: args.agent,
+        created_at: Utc::now(),
+        last_run: None,
+    };
+    save_to(&dir, &schedule)?;
+
+    println!("✅  Scheduled '{name}' ({})", schedule.cron);
+    if let Some(next) = cron.next_after(Local::now()) {
+        println!("    Next run: {}", next.format("%Y-%m-%d %H:%M"));
+    }
+    println!("    Runs are started by `ralph daemon` (keep it running, or call");
+    println!("    `ralph daemon --once` from system cron every minute).");
+    Ok(())
+}
+
+fn list(dir: &Path) -> Result<()> {
+    let schedules = load_all_from(dir)?;
+    if schedules.is_empty() {
+        println!(
+            "No schedules. Add one with: ralph schedule --cron \"0 2 * * *\" --template <name>"
+        );
+        return Ok(());
+    }
+
+    println!("📅  Schedules ({}):\n", dir.display());
+    for s in &schedules {
+        let target = match (&s.template, &s.prd) {
+            (Some(t), _) => format!("template {t}"),
+            (None, Some(p)) => p.display().to_string(),
+            (None, None) => "-".to_string(),
+        };
+        let next = Cron::parse(&s.cron)
+            .ok()
+            .and_then(|c| c.next_after(Local::now()))
+            .map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M").to_string());
+        let last = s.last_run.map_or("never".to_string(), |t| {
+            t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+        });
+        println!("  {:<20} {:<14} {target}", s.name, s.cron);
+        println!(
+            "  {:<20} in {}  next {next}  last {last}",
+            "",
+            s.workdir.display()
+        );
+    }
+    Ok(())
+}
+
+/// `ralph daemon`: start each schedule's run when it comes due. A schedule
+/// whose previous run is still going is skipped for that firing.
+pub async fn daemon(once: bool) -> Result<()> {
+    let dir = schedules_dir()?;
+    let exe = std::env::current_exe().context("Cannot locate the ralph executable")?;
+    let mut running: HashMap<String, Child> = HashMap::new();
+    let mut shutdown = systemd::Shutdown::listen();
+    let watchdog = systemd::watchdog_interval();
+
+    if !once {
+        println!(
+            "📅  ralph daemon watching {} (Ctrl+C to stop)",
+            dir.display()
+        );
+        systemd::ready();
+    }
+
+    loop {
+        running.retain(|name, child| match child.try_wait() {
+            Ok(Some(status)) => {
+                println!("🏁  {name} finished ({status})");
+                false
+            }
+            _ => true,
+        });
+
+        let now = Local::now();
+        for mut schedule in load_all_from(&dir)? {
+            let cron = match Cron::parse(&schedule.cron) {
+                Ok(cron) => cron,
+                Err(e) => {
+                    eprintln!("⚠️   Skipping {}: {e:#}", schedule.name);
+                    continue;
+                }
+            };
+            if !schedule.is_due(&cron, now) {
+                continue;
+            }
+            if running.contains_key(&schedule.name) {
+                eprintln!(
+                    "⚠️   {} is still running from its last firing; skipping",
+                    schedule.name
+                );
+                continue;
+            }
+
+            match spawn_run(&exe, &dir, &schedule) {
+                Ok(child) => {
+                    println!(
+                        "🚀  Started {} → {}",
+                        schedule.name,
+                        log_path(&dir, &schedule.name).display()
+                    );
+                    running.insert(schedule.name.clone(), child);
+                }
+                Err(e) => eprintln!("⚠️   Failed to start {}: {e:#}", schedule.name),
+            }
+            schedule.last_run = Some(Utc::now());
+            save_to(&dir, &schedule)?;
+        }
+
+        if once {
+            for (name, mut child) in running.drain() {
+                let status = child.wait().await?;
+                println!("🏁  {name} finished ({status})");
+            }
+            return Ok(());
+        }
+
+        systemd::status(&format!("{} run(s) in progress", running.len()));
+
+        // Wake just after the next minute boundary. Watchdog pings come from
+        // this loop, so systemd restarts a daemon that stops cycling.
+        let wait = 60 - u64::from(Local::now().second());
+        let wake = sleep(Duration::from_secs(wait));
+        tokio::pin!(wake);
+        loop {
+            tokio::select! {
+                _ = &mut wake => break,
+                _ = systemd::watchdog_due(watchdog) => systemd::watchdog(),
+                _ = shutdown.requested() => {
+                    systemd::stopping();
+                    if !running.is_empty() {
+                        println!("\n⏹️   Stopping; {} run(s) still in progress keep going", running.len());
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn spawn_run(exe: &Path, dir: &Path, schedule: &Schedule) -> Result<Child> {
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(dir, &schedule.name))?;
+    use std::io::Write;
+    writeln!(
+        &log,
+        "\n===== {} ralph {} =====",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        schedule.run_args().join(" ")
+    )?;
+
+    let child = Command::new(exe)
+        .args(schedule.run_args())
+        .current_dir(&schedule.workdir)
+        // Readiness and watchdog belong to the daemon, not the runs it starts.
+        .env_remove("NOTIFY_SOCKET")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+        .context("Failed to spawn ralph run")?;
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(y, mo, d, h, mi, 0)
+            .single()
+            .expect("unambiguous local time")
+    }
+
+    fn schedule(name: &str) -> Schedule {
+        Schedule {
+            name: name.to_string(),
+            cron: "0 2 * * *".to_string(),
+            template: Some("nightly-maintenance".to_string()),
+            prd: None,
+            workdir: PathBuf::from("/srv/app"),
+            agent: None,
+            created_at: Utc::now(),
+            last_run: None,
+        }
+    }
+
+    #[test]
+    fn cron_fields_support_lists_ranges_and_steps() {
+        let cron = Cron::parse("*/15 9-17 * * 1-5").expect("valid cron");
+        // 2026-03-02 is a Monday.
+        assert!(cron.matches(&local(2026, 3, 2, 9, 0)));
+        assert!(cron.matches(&local(2026, 3, 2, 17, 45)));
+        assert!(!cron.matches(&local(2026, 3, 2, 9, 5)));
+        assert!(!cron.matches(&local(2026, 3, 2, 18, 0)));
+        assert!(!cron.matches(&local(2026, 3, 1, 10, 0)));
+
+        // Both day fields restricted: either one matching is enough.
+        let cron = Cron::parse("0 2 1,15 * 0").expect("valid cron");
+        assert!(cron.matches(&local(2026, 3, 15, 2, 0)));
+        assert!(cron.matches(&local(2026, 3, 8, 2, 0)));
+        assert!(!cron.matches(&local(2026, 3, 9, 2, 0)));
+        assert!(Cron::parse("0 2 * * 7")
+            .expect("sunday as 7")
+            .matches(&local(2026, 3, 8, 2, 0)));
+
+        assert!(Cron::parse("0 2 * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn next_after_finds_the_following_firing() {
+        let cron = Cron::parse("0 2 * * *").expect("valid cron");
+        assert_eq!(
+            cron.next_after(local(2026, 3, 2, 1, 30)),
+            Some(local(2026, 3, 2, 2, 0))
+        );
+        assert_eq!(
+            cron.next_after(local(2026, 3, 2, 2, 0)),
+            Some(local(2026, 3, 3, 2, 0))
+        );
+        assert_eq!(
+            Cron::parse("0 0 31 2 *")
+                .expect("valid")
+                .next_after(local(2026, 1, 1, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn due_once_per_matching_minute() {
+        let cron = Cron::parse("0 2 * * *").expect("valid cron");
+        let mut s = schedule("nightly");
+        let at = local(2026, 3, 2, 2, 0);
+        assert!(s.is_due(&cron, at));
+        assert!(!s.is_due(&cron, local(2026, 3, 2, 2, 1)));
+
+        s.last_run = Some((at + chrono::Duration::seconds(20)).with_timezone(&Utc));
+        assert!(!s.is_due(&cron, at + chrono::Duration::seconds(40)));
+        assert!(s.is_due(&cron, local(2026, 3, 3, 2, 0)));
+    }
+
+    #[test]
+    fn schedules_roundtrip_and_launch_fresh_runs() {
+        let dir = tempdir().expect("create tempdir");
+        let mut nightly = schedule("nightly");
+        nightly.agent = Some("claude".to_string());
+        let mut lint = schedule("lint-sweep");
+        lint.template = None;
+        lint.prd = Some(PathBuf::from("/srv/app/lint.md"));
+        save_to(dir.path(), &nightly).expect("save");
+        save_to(dir.path(), &lint).expect("save");
+        fs::write(dir.path().join("nightly.log"), "old output").expect("write log");
+
+        let loaded = load_all_from(dir.path()).expect("load");
+        assert_eq!(loaded, vec![lint.clone(), nightly.clone()]);
+
+        assert_eq!(
+            nightly.run_args(),
+            vec![
+                "run",
+                "--template",
+                "nightly-maintenance",
+                "--workdir",
+                "/srv/app",
+                "--agent",
+                "claude",
+                "--fresh"
+            ]
+        );
+        assert_eq!(
+            lint.run_args(),
+            vec![
+                "run",
+                "/srv/app/lint.md",
+                "--workdir",
+                "/srv/app",
+                "--fresh"
+            ]
+        );
+
+        remove_from(dir.path(), "lint-sweep").expect("remove");
+        assert_eq!(load_all_from(dir.path()).expect("load").len(), 1);
+        assert!(remove_from(dir.path(), "lint-sweep").is_err());
+        assert!(!valid_name("../escape"));
+        let nested = dir.path().join("schedules");
+        fs::create_dir(&nested).expect("create schedules dir");
+        fs::write(dir.path().join("escape.json"), "{}").expect("write outside");
+        assert!(remove_from(&nested, "../escape").is_err());
+        assert!(dir.path().join("escape.json").exists());
+    }
+}
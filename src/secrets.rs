@@ -0,0 +1,324 @@
+//! Secrets kept in the OS keyring instead of `ralph.toml`: `token_ref =
+//! "ralph/hook"` names the keyring entry whose service is `ralph` and whose
+//! account is `hook`, and `ralph secret set ralph/hook` stores it.
+//!
+//! The keyring is reached through the platform's own tool — `security` (the
+//! macOS Keychain) or `secret-tool` (libsecret, i.e. GNOME Keyring or
+//! KWallet on Linux) — so nothing links against it and an unlocked session
+//! is all it needs.
+
+use anyhow::{bail, Context, Result};
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::cli::{SecretArgs, SecretCommands};
+
+/// Service used for a reference without one, e.g. `hook` → `ralph/hook`.
+const DEFAULT_SERVICE: &str = "ralph";
+
+/// A keyring entry, written `service/account`.
+#[derive(Debug, PartialEq)]
+pub struct SecretRef {
+    pub service: String,
+    pub account: String,
+}
+
+impl SecretRef {
+    pub fn parse(reference: &str) -> Result<Self> {
+        let (service, account) = reference
+            .split_once('/')
+            .unwrap_or((DEFAULT_SERVICE, reference));
+        if service.trim().is_empty() || account.trim().is_empty() {
+            bail!("Bad secret reference {reference:?}: expected service/name, e.g. ralph/hook");
+        }
+        Ok(Self {
+            service: service.to_string(),
+            account: account.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.service, self.account)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    /// macOS `security`.
+    Keychain,
+    /// libsecret's `secret-tool`.
+    SecretService,
+}
+
+impl Backend {
+    fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::Keychain
+        } else {
+            Self::SecretService
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Keychain => "security",
+            Self::SecretService => "secret-tool",
+        }
+    }
+
+    fn missing_hint(self) -> &'static str {
+        match self {
+            Self::Keychain => "`security` not found",
+            Self::SecretService => {
+                "`secret-tool` not found — install libsecret-tools (Debian/Ubuntu) or libsecret (Fedora, Arch)"
+            }
+        }
+    }
+
+    fn lookup_args(self, secret: &SecretRef) -> Vec<String> {
+        let args: Vec<&str> = match self {
+            Self::Keychain => vec![
+                "find-generic-password",
+                "-s",
+                &secret.service,
+                "-a",
+                &secret.account,
+                "-w",
+            ],
+            Self::SecretService => vec![
+                "lookup",
+                "service",
+                &secret.service,
+                "account",
+                &secret.account,
+            ],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+
+    /// Neither tool gets the value as an argument, where other users' `ps`
+    /// would see it: `security` with a trailing bare `-w` prompts for it
+    /// twice, and `secret-tool` reads it once; both from stdin.
+    fn store_args(self, secret: &SecretRef) -> Vec<String> {
+        let label = format!("ralph: {secret}");
+        let args: Vec<&str> = match self {
+            Self::Keychain => vec![
+                "add-generic-password",
+                "-U",
+                "-s",
+                &secret.service,
+                "-a",
+                &secret.account,
+                "-l",
+                &label,
+                "-w",
+            ],
+            Self::SecretService => vec![
+                "store",
+                "--label",
+                &label,
+                "service",
+                &secret.service,
+                "account",
+                &secret.account,
+            ],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+
+    fn delete_args(self, secret: &SecretRef) -> Vec<String> {
+        let args: Vec<&str> = match self {
+            Self::Keychain => vec![
+                "delete-generic-password",
+                "-s",
+                &secret.service,
+                "-a",
+                &secret.account,
+            ],
+            Self::SecretService => vec![
+                "clear",
+                "service",
+                &secret.service,
+                "account",
+                &secret.account,
+            ],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+}
+
+/// The secret stored under `reference`.
+pub fn get(reference: &str) -> Result<String> {
+    let secret = SecretRef::parse(reference)?;
+    let backend = Backend::current();
+    let output = Command::new(backend.program())
+        .args(backend.lookup_args(&secret))
+        .stdin(Stdio::null())
+        .output()
+        .context(backend.missing_hint())?;
+    let value = String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\r', '\n'])
+        .to_string();
+    if !output.status.success() || value.is_empty() {
+        bail!("No secret {secret} in the OS keyring — store it with `ralph secret set {secret}`");
+    }
+    Ok(value)
+}
+
+/// Store `value` under `reference`, replacing any earlier value.
+pub fn set(reference: &str, value: &str) -> Result<()> {
+    let secret = SecretRef::parse(reference)?;
+    let backend = Backend::current();
+    let mut child = Command::new(backend.program())
+        .args(backend.store_args(&secret))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(backend.missing_hint())?;
+    let input = match backend {
+        // The value, then its confirmation.
+        Backend::Keychain => format!("{value}\n{value}\n"),
+        Backend::SecretService => value.to_string(),
+    };
+    child
+        .stdin
+        .take()
+        .with_context(|| format!("{} stdin", backend.program()))?
+        .write_all(input.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "Could not store {secret}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Remove the secret stored under `reference`.
+pub fn delete(reference: &str) -> Result<()> {
+    let secret = SecretRef::parse(reference)?;
+    let backend = Backend::current();
+    let output = Command::new(backend.program())
+        .args(backend.delete_args(&secret))
+        .stdin(Stdio::null())
+        .output()
+        .context(backend.missing_hint())?;
+    if !output.status.success() {
+        bail!(
+            "Could not remove {secret}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// `ralph secret`.
+pub fn command(args: SecretArgs) -> Result<()> {
+    match args.command {
+        SecretCommands::Set { reference } => {
+            let value = read_value(&reference)?;
+            set(&reference, &value)?;
+            println!("🔑  Stored {reference} in the OS keyring");
+        }
+        SecretCommands::Delete { reference } => {
+            delete(&reference)?;
+            println!("🗑️   Removed {reference} from the OS keyring");
+        }
+        SecretCommands::Check { reference } => {
+            get(&reference)?;
+            println!("✅  {reference} is in the OS keyring");
+        }
+    }
+    Ok(())
+}
+
+/// The value to store: typed at a prompt without echo, or piped in.
+fn read_value(reference: &str) -> Result<String> {
+    let stdin = std::io::stdin();
+    let value = if stdin.is_terminal() {
+        print!("Value for {reference}: ");
+        std::io::stdout().flush()?;
+        let echo_off = Command::new("stty").arg("-echo").status().is_ok();
+        let mut line = String::new();
+        let read = stdin.read_line(&mut line);
+        if echo_off {
+            let _ = Command::new("stty").arg("echo").status();
+        }
+        println!();
+        read?;
+        line
+    } else {
+        let mut piped = String::new();
+        stdin.lock().read_to_string(&mut piped)?;
+        piped
+    };
+    let value = value.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        bail!("Nothing to store: the value is empty");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_name_a_service_and_account() {
+        assert_eq!(
+            SecretRef::parse("ralph/hook").unwrap(),
+            SecretRef {
+                service: "ralph".to_string(),
+                account: "hook".to_string(),
+            }
+        );
+        assert_eq!(
+            SecretRef::parse("anthropic").unwrap().to_string(),
+            "ralph/anthropic"
+        );
+        assert_eq!(
+            SecretRef::parse("work/openclaw/gateway").unwrap().account,
+            "openclaw/gateway"
+        );
+        assert!(SecretRef::parse("ralph/").is_err());
+        assert!(SecretRef::parse("").is_err());
+    }
+
+    #[test]
+    fn keyring_tools_get_the_entry_by_service_and_account() {
+        let secret = SecretRef::parse("ralph/hook").unwrap();
+        assert_eq!(
+            Backend::Keychain.lookup_args(&secret),
+            ["find-generic-password", "-s", "ralph", "-a", "hook", "-w"]
+        );
+        assert_eq!(
+            Backend::SecretService.lookup_args(&secret),
+            ["lookup", "service", "ralph", "account", "hook"]
+        );
+        // Both read the value from stdin; `security` prompts for it after a
+        // trailing `-w`.
+        assert_eq!(
+            Backend::Keychain
+                .store_args(&secret)
+                .last()
+                .map(String::as_str),
+            Some("-w")
+        );
+        assert_eq!(
+            Backend::SecretService.store_args(&secret),
+            [
+                "store",
+                "--label",
+                "ralph: ralph/hook",
+                "service",
+                "ralph",
+                "account",
+                "hook"
+            ]
+        );
+    }
+}
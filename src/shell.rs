@@ -0,0 +1,31 @@
+//! Quoting for values spliced into shell command lines: custom agent
+//! commands, formatters, `ralph verify`, the Docker sandbox, tmux windows,
+//! and remote runs.
+
+/// Quote `s` as one shell word. Values made only of characters the shell
+/// leaves alone are returned as-is, so the commands Ralph prints stay
+/// readable.
+pub fn quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@+".contains(c));
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_wraps_only_when_needed() {
+        assert_eq!(quote("prd.md"), "prd.md");
+        assert_eq!(quote("my prd.md"), "'my prd.md'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("T1'; rm -rf ~"), r"'T1'\''; rm -rf ~'");
+    }
+}
@@ -0,0 +1,261 @@
+//! On-disk formats for the task list (`--tasks-format` / `[state] format`).
+//! JSON stays the default since every agent can edit it; TOML and JSON5 are
+//! friendlier to edit by hand and to review. The file is named after its
+//! format (`tasks.toml`, `tasks.json5`), so readers find it without config.
+//!
+//! Ralph rewrites the file as tasks progress. Comments at the top of a TOML or
+//! JSON5 file are kept; comments further down are not.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::TaskList;
+
+/// Names accepted by `--tasks-format`.
+pub const FORMAT_NAMES: &[&str] = &["json", "json5", "toml"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaskFormat {
+    #[default]
+    Json,
+    Json5,
+    Toml,
+}
+
+impl TaskFormat {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "json" => Ok(Self::Json),
+            "json5" => Ok(Self::Json5),
+            "toml" => Ok(Self::Toml),
+            other => anyhow::bail!(
+                "Unknown task list format '{other}' (expected one of: {})",
+                FORMAT_NAMES.join(", ")
+            ),
+        }
+    }
+
+    /// `tasks.json`, `tasks.json5`, or `tasks.toml`.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "tasks.json",
+            Self::Json5 => "tasks.json5",
+            Self::Toml => "tasks.toml",
+        }
+    }
+
+    /// The task list in `dir`, whichever format it was saved in (JSON when
+    /// there is none yet).
+    pub fn detect(dir: &Path) -> (Self, PathBuf) {
+        [Self::Json, Self::Toml, Self::Json5]
+            .into_iter()
+            .map(|format| (format, dir.join(format.file_name())))
+            .find(|(_, path)| path.exists())
+            .unwrap_or_else(|| (Self::Json, dir.join(Self::Json.file_name())))
+    }
+
+    /// The format a file's extension names, JSON for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("json5") => Self::Json5,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn parse(self, content: &str) -> Result<TaskList> {
+        match self {
+            Self::Json => Ok(serde_json::from_str(content)?),
+            Self::Json5 => Ok(serde_json::from_str(&json5_to_json(content))?),
+            Self::Toml => Ok(toml::from_str(content)?),
+        }
+    }
+
+    /// Serialize `list`, keeping the comment block at the top of `previous`
+    /// (the file being replaced).
+    pub fn render(self, list: &TaskList, previous: Option<&str>) -> Result<String> {
+        let body = match self {
+            Self::Json | Self::Json5 => serde_json::to_string_pretty(list)?,
+            Self::Toml => {
+                toml::to_string_pretty(list).context("Failed to serialise tasks as TOML")?
+            }
+        };
+        let header = match self.comment_marker() {
+            Some(marker) => previous.map(|p| header(p, marker)).unwrap_or_default(),
+            None => String::new(),
+        };
+        Ok(header + &body)
+    }
+
+    fn comment_marker(self) -> Option<&'static str> {
+        match self {
+            Self::Json => None,
+            Self::Json5 => Some("//"),
+            Self::Toml => Some("#"),
+        }
+    }
+}
+
+/// The comment lines (and blank lines between them) a file starts with.
+fn header(content: &str, marker: &str) -> String {
+    let lines: Vec<&str> = content
+        .lines()
+        .take_while(|line| line.trim().is_empty() || line.trim_start().starts_with(marker))
+        .collect();
+    let Some(last) = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with(marker))
+    else {
+        return String::new();
+    };
+    let mut header = lines[..=last].join("\n");
+    header.push('\n');
+    header
+}
+
+/// Rewrite JSON5 as JSON: drop comments and trailing commas, quote bare
+/// keys, and turn single-quoted strings into double-quoted ones. Line breaks
+/// are kept, so JSON parse errors point at the right line.
+fn json5_to_json(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' | '\'' => {
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    match chars[i] {
+                        '\\' if chars.get(i + 1) == Some(&'\'') => {
+                            out.push('\'');
+                            i += 1;
+                        }
+                        '\\' => {
+                            out.push('\\');
+                            if let Some(&next) = chars.get(i + 1) {
+                                out.push(next);
+                            }
+                            i += 1;
+                        }
+                        '"' => out.push_str("\\\""),
+                        other => out.push(other),
+                    }
+                    i += 1;
+                }
+                out.push('"');
+                i += 1;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        out.push('\n');
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            '}' | ']' => {
+                let kept = out.trim_end().len();
+                if out[..kept].ends_with(',') {
+                    out.remove(kept - 1);
+                }
+                out.push(c);
+                i += 1;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if next == Some(&':') {
+                    out.push_str(&format!("\"{word}\""));
+                } else {
+                    out.push_str(&word);
+                }
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TaskStatus;
+
+    #[test]
+    fn json5_with_comments_and_bare_keys_parses() {
+        let content = r#"// Sprint 3 queue
+{
+  version: 1,
+  prd_path: 'prd.md',
+  created_at: "2026-01-01T00:00:00Z",
+  updated_at: "2026-01-01T00:00:00Z",
+  /* Keep T1 first. */
+  tasks: [
+    {
+      id: "T1",
+      title: 'Add the "login" page', // from the PRD
+      description: 'It\'s the entry point',
+      priority: 1,
+      status: "pending",
+    },
+  ],
+}
+"#;
+        let list = TaskFormat::Json5.parse(content).expect("parses");
+        assert_eq!(list.tasks[0].title, r#"Add the "login" page"#);
+        assert_eq!(list.tasks[0].description, "It's the entry point");
+
+        let rendered = TaskFormat::Json5
+            .render(&list, Some(content))
+            .expect("renders");
+        assert!(rendered.starts_with("// Sprint 3 queue\n{\n"), "{rendered}");
+        let reparsed = TaskFormat::Json5.parse(&rendered).expect("reparses");
+        assert_eq!(reparsed.tasks.len(), 1);
+    }
+
+    #[test]
+    fn toml_round_trips_and_keeps_its_header() {
+        let mut list = TaskFormat::Json5
+            .parse(
+                r#"{version: 1, prd_path: "prd.md", created_at: "2026-01-01T00:00:00Z",
+                    updated_at: "2026-01-01T00:00:00Z",
+                    tasks: [{id: "T1", title: "Login", description: "POST /login",
+                             priority: 1, status: "complete", depends_on: []}]}"#,
+            )
+            .expect("parses");
+        list.tasks[0].notes = Some("Done.\nSee PR 12.".to_string());
+
+        let previous = "# Review before merging\n\nversion = 1\n";
+        let rendered = TaskFormat::Toml
+            .render(&list, Some(previous))
+            .expect("renders");
+        assert!(
+            rendered.starts_with("# Review before merging\nversion = 1\n"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("[[tasks]]"), "{rendered}");
+
+        let parsed = TaskFormat::Toml.parse(&rendered).expect("reparses");
+        assert_eq!(parsed.tasks[0].status, TaskStatus::Complete);
+        assert_eq!(parsed.tasks[0].notes.as_deref(), Some("Done.\nSee PR 12."));
+        assert_eq!(parsed.created_at, list.created_at);
+    }
+}
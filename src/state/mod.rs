@@ -1,3 +1,5 @@
+mod format;
+
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -7,15 +9,23 @@ use std::io::Write as _;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use crate::agents::TokenUsage;
+use crate::timefmt::{compact_duration, TimestampZone};
+
+pub use format::{TaskFormat, FORMAT_NAMES};
+
 // ── Task model ────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
+    #[default]
     Pending,
     InProgress,
     Complete,
     Failed,
+    /// Claimed complete below the confidence threshold; waiting on `ralph approve`.
+    NeedsReview,
 }
 
 impl std::fmt::Display for TaskStatus {
@@ -25,11 +35,12 @@ impl std::fmt::Display for TaskStatus {
             TaskStatus::InProgress => write!(f, "in_progress"),
             TaskStatus::Complete => write!(f, "complete"),
             TaskStatus::Failed => write!(f, "failed"),
+            TaskStatus::NeedsReview => write!(f, "needs_review"),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
     pub title: String,
@@ -38,10 +49,31 @@ pub struct Task {
     pub status: TaskStatus,
     #[serde(default)]
     pub depends_on: Vec<String>,
+    /// Checkable statements from the PRD that must all hold for the task to
+    /// count as done.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acceptance: Vec<String>,
+    /// Command that must pass before a completion claim on this task counts,
+    /// checked instead of `--verify-cmd`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<String>,
+    /// Failed attempts before the task is given up on, instead of `--max-attempts`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub completed_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
+    /// Slug of the PRD heading this task comes from; the iteration prompt
+    /// carries that section instead of the whole PRD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prd_section: Option<String>,
+    /// Agent to run this task with instead of `--agent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Model for this task instead of `--model`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +85,49 @@ pub struct TaskList {
     pub tasks: Vec<Task>,
 }
 
+/// JSON Schema for tasks.json, published next to it so agents that edit the
+/// file know the expected shape. `load_tasks` additionally rejects duplicate
+/// ids, unknown dependencies, and dependency cycles.
+pub const TASKS_SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "Ralph tasks.json",
+  "description": "Task ids must be unique, and depends_on may only reference existing ids without cycles.",
+  "type": "object",
+  "required": ["version", "prd_path", "created_at", "updated_at", "tasks"],
+  "properties": {
+    "version": { "type": "integer", "minimum": 0 },
+    "prd_path": { "type": "string" },
+    "created_at": { "type": "string", "format": "date-time" },
+    "updated_at": { "type": "string", "format": "date-time" },
+    "tasks": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "required": ["id", "title", "description", "priority", "status"],
+        "properties": {
+          "id": { "type": "string", "minLength": 1 },
+          "title": { "type": "string" },
+          "description": { "type": "string" },
+          "priority": { "type": "integer", "minimum": 0 },
+          "status": {
+            "enum": ["pending", "in_progress", "complete", "failed", "needs_review"]
+          },
+          "depends_on": { "type": "array", "items": { "type": "string" } },
+          "acceptance": { "type": "array", "items": { "type": "string" } },
+          "verify": { "type": "string" },
+          "max_attempts": { "type": "integer", "minimum": 1 },
+          "completed_at": { "type": "string", "format": "date-time" },
+          "notes": { "type": "string" },
+          "prd_section": { "type": "string" },
+          "agent": { "type": "string" },
+          "model": { "type": "string" }
+        }
+      }
+    }
+  }
+}
+"##;
+
 // ── Lock file model ───────────────────────────────────────────────────────────
 
 /// Written to `.ralph/lock` while a `ralph run` is active.
@@ -71,6 +146,32 @@ pub struct LockFile {
     pub prd_path: String,
     /// Agent name in use.
     pub agent: String,
+    /// Estimated seconds until every task is done, once an iteration has finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+    /// Process groups of the agents running now, for `ralph stop --force`:
+    /// killing Ralph alone would leave them running.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agent_pgids: Vec<u32>,
+}
+
+/// Add a running agent's process group to the lock at `lock_file`, or take
+/// it out again once the agent is done. A missing lock stays missing.
+pub fn track_agent_group(lock_file: &Path, pgid: u32, running: bool) -> Result<()> {
+    // The agents of a `--task-parallel` batch share one lock.
+    static UPDATE: Mutex<()> = Mutex::new(());
+    let _guard = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+    let Ok(content) = fs::read_to_string(lock_file) else {
+        return Ok(());
+    };
+    let mut lock: LockFile =
+        serde_json::from_str(&content).context("Failed to parse .ralph/lock")?;
+    lock.agent_pgids.retain(|&p| p != pgid);
+    if running {
+        lock.agent_pgids.push(pgid);
+    }
+    let content = serde_json::to_string_pretty(&lock).context("Failed to serialise lock file")?;
+    fs::write(lock_file, content).context("Failed to write .ralph/lock")
 }
 
 // ── Shared loop status (for TUI and watch command) ────────────────────────────
@@ -78,9 +179,13 @@ pub struct LockFile {
 /// The high-level lifecycle state of a single `ralph watch` loop.
 #[derive(Debug, Clone, PartialEq)]
 pub enum LoopState {
+    /// Waiting for a `--parallel` slot; the value is the 1-based queue position.
+    Queued(usize),
     Starting,
     Parsing,
     Running,
+    /// Waiting out a provider rate limit before retrying the task.
+    RateLimited { until: DateTime<Utc> },
     Complete,
     Failed(String),
     Stopped,
@@ -89,9 +194,13 @@ pub enum LoopState {
 impl std::fmt::Display for LoopState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            LoopState::Queued(position) => write!(f, "queued (#{})", position),
             LoopState::Starting => write!(f, "starting"),
             LoopState::Parsing => write!(f, "parsing"),
             LoopState::Running => write!(f, "running"),
+            LoopState::RateLimited { until } => {
+                write!(f, "rate limited (retry at {} UTC)", until.format("%H:%M:%S"))
+            }
             LoopState::Complete => write!(f, "complete"),
             LoopState::Failed(e) => write!(f, "failed: {}", e),
             LoopState::Stopped => write!(f, "stopped"),
@@ -99,6 +208,56 @@ impl std::fmt::Display for LoopState {
     }
 }
 
+/// The operator's answer to a [`BreakerPrompt`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakerChoice {
+    /// Reset the failure count and keep going with the same agent.
+    Continue,
+    /// Reset the failure count and go on with the next fallback agent.
+    SwitchAgent,
+    /// Trip the circuit breaker.
+    Stop,
+}
+
+/// A loop about to trip the circuit breaker, waiting for the operator to
+/// answer in the TUI. Unanswered by `deadline`, the loop stops.
+#[derive(Debug, Clone)]
+pub struct BreakerPrompt {
+    pub failures: u32,
+    pub deadline: std::time::Instant,
+    /// The agent `SwitchAgent` would move to, if any.
+    pub switch_to: Option<String>,
+    pub answer: Option<BreakerChoice>,
+}
+
+impl BreakerPrompt {
+    /// Whole seconds left to answer.
+    pub fn secs_left(&self) -> u64 {
+        self.deadline
+            .saturating_duration_since(std::time::Instant::now())
+            .as_secs()
+    }
+}
+
+/// Where a buffered log line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogKind {
+    /// Agent stdout.
+    Stdout,
+    /// Agent stderr.
+    Stderr,
+    /// Ralph's own progress messages (task complete, errors, fallbacks).
+    Status,
+}
+
+/// One line in a loop's recent-log buffer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    pub kind: LogKind,
+    pub text: String,
+}
+
 /// Live status of one orchestrator loop, shared between the loop task and the TUI.
 #[derive(Debug)]
 pub struct LoopStatus {
@@ -120,8 +279,14 @@ pub struct LoopStatus {
     pub iteration: u32,
     /// When this loop started (for elapsed time display).
     pub started_at: std::time::Instant,
+    /// When the loop's orchestrator returned.
+    pub finished_at: Option<std::time::Instant>,
     /// Recent log lines for TUI display (capped at 500).
-    pub recent_logs: VecDeque<String>,
+    pub recent_logs: VecDeque<LogLine>,
+    /// Estimated seconds until every task is done (None until measurable).
+    pub eta_secs: Option<u64>,
+    /// Set while the loop waits for the operator before tripping the circuit breaker.
+    pub breaker_prompt: Option<BreakerPrompt>,
 }
 
 impl LoopStatus {
@@ -136,46 +301,217 @@ impl LoopStatus {
             tasks_total: 0,
             iteration: 0,
             started_at: std::time::Instant::now(),
+            finished_at: None,
             recent_logs: VecDeque::with_capacity(500),
+            eta_secs: None,
+            breaker_prompt: None,
         }
     }
 
     /// Append a log line, evicting the oldest if we're at capacity.
-    pub fn push_log(&mut self, line: String) {
+    pub fn push_log(&mut self, kind: LogKind, text: String) {
         if self.recent_logs.len() >= 500 {
             self.recent_logs.pop_front();
         }
-        self.recent_logs.push_back(line);
+        self.recent_logs.push_back(LogLine { kind, text });
+    }
+
+    /// Seconds from `started_at` until the loop finished, or until now.
+    pub fn elapsed_secs(&self) -> u64 {
+        let end = self.finished_at.unwrap_or_else(std::time::Instant::now);
+        end.saturating_duration_since(self.started_at).as_secs()
     }
 
     /// Human-readable elapsed time since `started_at`.
     pub fn elapsed_str(&self) -> String {
-        let secs = self.started_at.elapsed().as_secs();
-        let h = secs / 3600;
-        let m = (secs % 3600) / 60;
-        let s = secs % 60;
-        if h > 0 {
-            format!("{}h{}m", h, m)
-        } else if m > 0 {
-            format!("{}m{}s", m, s)
-        } else {
-            format!("{}s", s)
+        compact_duration(self.elapsed_secs())
+    }
+
+    /// Human-readable time remaining, or "—" while there is no estimate yet.
+    pub fn eta_str(&self) -> String {
+        match self.eta_secs {
+            Some(secs) => format!("~{}", compact_duration(secs)),
+            None => "—".to_string(),
+        }
+    }
+
+    /// A serializable copy, with the last `recent` log lines.
+    pub fn snapshot(&self, recent: usize) -> StatusSnapshot {
+        let skip = self.recent_logs.len().saturating_sub(recent);
+        StatusSnapshot {
+            name: self.name.clone(),
+            prd: self.prd_path.clone(),
+            agent: self.agent.clone(),
+            state: self.state.to_string(),
+            current_task: self.current_task.clone(),
+            tasks_done: self.tasks_done,
+            tasks_total: self.tasks_total,
+            iteration: self.iteration,
+            elapsed_secs: self.elapsed_secs(),
+            eta_secs: self.eta_secs,
+            recent_events: self.recent_logs.iter().skip(skip).cloned().collect(),
         }
     }
 }
 
+/// A `LoopStatus` as served by `--status-port` and saved to status.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    pub name: String,
+    pub prd: String,
+    pub agent: String,
+    pub state: String,
+    pub current_task: String,
+    pub tasks_done: u32,
+    pub tasks_total: u32,
+    pub iteration: u32,
+    pub elapsed_secs: u64,
+    pub eta_secs: Option<u64>,
+    pub recent_events: Vec<LogLine>,
+}
+
+impl StatusSnapshot {
+    /// Read a status.json; `None` when it is missing or unreadable.
+    pub fn load(path: &Path) -> Option<Self> {
+        let raw = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&raw).ok()
+    }
+}
+
+/// Rolling iteration timings for one loop, used to estimate time remaining.
+#[derive(Debug, Clone, Default)]
+pub struct IterationStats {
+    recent_secs: VecDeque<u64>,
+    iterations: u32,
+    completions: u32,
+}
+
+impl IterationStats {
+    /// Number of recent iterations averaged.
+    const WINDOW: usize = 5;
+
+    /// Record one finished iteration and whether it completed its task.
+    pub fn record(&mut self, secs: u64, completed_task: bool) {
+        if self.recent_secs.len() >= Self::WINDOW {
+            self.recent_secs.pop_front();
+        }
+        self.recent_secs.push_back(secs);
+        self.iterations += 1;
+        if completed_task {
+            self.completions += 1;
+        }
+    }
+
+    /// Whether tasks are being finished steadily: at least three iterations
+    /// so far, half or more of them completing their task.
+    pub fn is_steady(&self) -> bool {
+        self.iterations >= 3 && self.completions * 2 >= self.iterations
+    }
+
+    /// Average duration of the last few iterations.
+    pub fn average_secs(&self) -> Option<u64> {
+        if self.recent_secs.is_empty() {
+            return None;
+        }
+        Some(self.recent_secs.iter().sum::<u64>() / self.recent_secs.len() as u64)
+    }
+
+    /// Estimated seconds to finish `remaining_tasks`, scaling the rolling
+    /// average by how many iterations each task has needed so far this run.
+    pub fn eta_secs(&self, remaining_tasks: u32) -> Option<u64> {
+        let avg = self.average_secs()?;
+        let per_task = if self.completions == 0 {
+            self.iterations.max(1) as f64
+        } else {
+            (self.iterations as f64 / self.completions as f64).max(1.0)
+        };
+        Some((avg as f64 * per_task * remaining_tasks as f64).round() as u64)
+    }
+}
+
 /// Thread-safe handle to a `LoopStatus` shared between the loop task and the TUI.
 pub type SharedLoopStatus = Arc<Mutex<LoopStatus>>;
 
+// ── Usage ─────────────────────────────────────────────────────────────────────
+
+/// Exact token usage for one iteration, from an agent that reports it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub iteration: u32,
+    pub task_id: String,
+    pub agent: String,
+    pub model: String,
+    pub recorded_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub tokens: TokenUsage,
+    pub cost_usd: Option<f64>,
+}
+
+/// Contents of `usage.json`: every recorded iteration plus running totals.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageLog {
+    pub iterations: Vec<UsageRecord>,
+    pub total: TokenUsage,
+    /// Sum over iterations with known pricing (`None` if there are none).
+    pub total_cost_usd: Option<f64>,
+}
+
+impl UsageLog {
+    /// Read a usage.json; a missing file is an empty log.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw).context("Failed to parse usage.json"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(UsageLog::default()),
+            Err(e) => Err(e).context("Failed to read usage.json"),
+        }
+    }
+
+    /// Run totals, e.g. `48200 tokens over 6 iteration(s), $0.4120`.
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{} tokens over {} iteration(s)",
+            self.total.total(),
+            self.iterations.len()
+        );
+        if let Some(cost) = self.total_cost_usd {
+            line.push_str(&format!(", ${cost:.4}"));
+        }
+        line
+    }
+}
+
+/// Contents of `session.json`: the agent session the next iteration on
+/// `task_id` continues (`--keep-session`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentSession {
+    pub task_id: String,
+    pub agent: String,
+    pub session_id: String,
+}
+
 // ── State manager ─────────────────────────────────────────────────────────────
 
+/// Number of rolling `tasks.json.bak.N` snapshots kept (1 is the newest).
+const TASK_BACKUPS: usize = 3;
+
 /// Manages all on-disk state inside `.ralph/` under the project root.
 pub struct StateManager {
     pub ralph_dir: PathBuf,
     pub logs_dir: PathBuf,
+    /// `tasks.json`, or `tasks.toml` / `tasks.json5` in those formats.
     pub tasks_file: PathBuf,
+    pub tasks_format: TaskFormat,
+    pub schema_file: PathBuf,
     pub progress_file: PathBuf,
     pub lock_file: PathBuf,
+    pub usage_file: PathBuf,
+    /// The running iteration's details, for the agent to read.
+    pub current_file: PathBuf,
+    pub session_file: PathBuf,
+    /// Live `LoopStatus` copy for tools outside the process.
+    pub status_file: PathBuf,
+    /// Zone of the timestamps written to progress.md (`--timestamps`).
+    pub timestamps: TimestampZone,
 }
 
 impl StateManager {
@@ -187,10 +523,18 @@ impl StateManager {
 
         fs::create_dir_all(&logs_dir).context("Failed to create .ralph/logs/ directory")?;
 
+        let (tasks_format, tasks_file) = TaskFormat::detect(&ralph_dir);
         Ok(Self {
-            tasks_file: ralph_dir.join("tasks.json"),
+            tasks_file,
+            tasks_format,
+            schema_file: ralph_dir.join("tasks.schema.json"),
             progress_file: ralph_dir.join("progress.md"),
             lock_file: ralph_dir.join("lock"),
+            usage_file: ralph_dir.join("usage.json"),
+            current_file: ralph_dir.join("current.json"),
+            session_file: ralph_dir.join("session.json"),
+            status_file: ralph_dir.join("status.json"),
+            timestamps: TimestampZone::Utc,
             logs_dir,
             ralph_dir,
         })
@@ -205,10 +549,18 @@ impl StateManager {
         fs::create_dir_all(&logs_dir)
             .with_context(|| format!("Failed to create .ralph-{}/logs/ directory", name))?;
 
+        let (tasks_format, tasks_file) = TaskFormat::detect(&ralph_dir);
         Ok(Self {
-            tasks_file: ralph_dir.join("tasks.json"),
+            tasks_file,
+            tasks_format,
+            schema_file: ralph_dir.join("tasks.schema.json"),
             progress_file: ralph_dir.join("progress.md"),
             lock_file: ralph_dir.join("lock"),
+            usage_file: ralph_dir.join("usage.json"),
+            current_file: ralph_dir.join("current.json"),
+            session_file: ralph_dir.join("session.json"),
+            status_file: ralph_dir.join("status.json"),
+            timestamps: TimestampZone::Utc,
             logs_dir,
             ralph_dir,
         })
@@ -221,16 +573,102 @@ impl StateManager {
             return Ok(None);
         }
 
+        let name = format!(
+            "{}/{}",
+            self.ralph_dir.file_name().unwrap_or_default().to_string_lossy(),
+            self.tasks_format.file_name()
+        );
         let content =
-            fs::read_to_string(&self.tasks_file).context("Failed to read .ralph/tasks.json")?;
+            fs::read_to_string(&self.tasks_file).with_context(|| format!("Failed to read {name}"))?;
 
-        let list: TaskList =
-            serde_json::from_str(&content).context("Failed to parse .ralph/tasks.json")?;
-        validate_task_list(&list).context("Invalid .ralph/tasks.json")?;
+        let list = self
+            .tasks_format
+            .parse(&content)
+            .with_context(|| format!("Failed to parse {name}"))?;
+        validate_task_list(&list).with_context(|| format!("Invalid {name}"))?;
 
         Ok(Some(list))
     }
 
+    /// Store the task list in `format` from now on, converting an existing
+    /// list saved in another one. Returns the file it was converted from.
+    pub fn use_format(&mut self, format: TaskFormat) -> Result<Option<PathBuf>> {
+        if format == self.tasks_format {
+            return Ok(None);
+        }
+        let existing = self.load_tasks()?;
+        let old_file = std::mem::replace(&mut self.tasks_file, self.ralph_dir.join(format.file_name()));
+        self.tasks_format = format;
+        let Some(list) = existing else {
+            return Ok(None);
+        };
+        self.save_tasks(&list)?;
+        fs::remove_file(&old_file)
+            .with_context(|| format!("Failed to remove {}", old_file.display()))?;
+        Ok(Some(old_file))
+    }
+
+    /// Write `tasks.schema.json` next to tasks.json.
+    pub fn write_schema(&self) -> Result<()> {
+        fs::write(&self.schema_file, TASKS_SCHEMA).context("Failed to write tasks.schema.json")
+    }
+
+    /// Path of the `n`th rolling snapshot (`tasks.json.bak.1` is the newest).
+    pub fn backup_path(&self, n: usize) -> PathBuf {
+        self.ralph_dir
+            .join(format!("{}.bak.{n}", self.tasks_format.file_name()))
+    }
+
+    /// Snapshot tasks.json to `tasks.json.bak.1`, shifting older snapshots up
+    /// and dropping the oldest. Called before an agent gets write access.
+    pub fn backup_tasks(&self) -> Result<()> {
+        if !self.tasks_file.exists() {
+            return Ok(());
+        }
+        for n in (1..TASK_BACKUPS).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(n + 1))
+                    .with_context(|| format!("Failed to rotate {}", from.display()))?;
+            }
+        }
+        fs::copy(&self.tasks_file, self.backup_path(1)).context("Failed to back up tasks.json")?;
+        Ok(())
+    }
+
+    /// Replace tasks.json with the newest snapshot that still loads.
+    /// Returns the snapshot number and its tasks, or `None` if none is usable.
+    pub fn restore_latest_backup(&self) -> Result<Option<(usize, TaskList)>> {
+        for n in 1..=TASK_BACKUPS {
+            let Ok(content) = fs::read_to_string(self.backup_path(n)) else {
+                continue;
+            };
+            let Ok(list) = self.tasks_format.parse(&content) else {
+                continue;
+            };
+            if validate_task_list(&list).is_ok() {
+                self.save_tasks(&list)?;
+                return Ok(Some((n, list)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check tasks.json after an agent had write access to it. If it no longer
+    /// loads (or was deleted), restore the newest good snapshot (or `fallback`
+    /// when none is usable) and return what was wrong.
+    pub fn restore_tasks_if_invalid(&self, fallback: &TaskList) -> Result<Option<String>> {
+        let problem = match self.load_tasks() {
+            Ok(Some(_)) => return Ok(None),
+            Ok(None) => "tasks.json was deleted".to_string(),
+            Err(e) => format!("{e:#}"),
+        };
+        if self.restore_latest_backup()?.is_none() {
+            self.save_tasks(fallback)?;
+        }
+        Ok(Some(problem))
+    }
+
     /// Read tasks.json if it exists.
     #[cfg(test)]
     pub fn read_tasks(&self) -> Result<Option<TaskList>> {
@@ -239,8 +677,11 @@ impl StateManager {
 
     /// Atomically write tasks.json (write to tmp → fsync → rename).
     pub fn save_tasks(&self, tasks: &TaskList) -> Result<()> {
-        let content =
-            serde_json::to_string_pretty(tasks).context("Failed to serialise task list")?;
+        let previous = fs::read_to_string(&self.tasks_file).ok();
+        let content = self
+            .tasks_format
+            .render(tasks, previous.as_deref())
+            .context("Failed to serialise task list")?;
 
         // Write to a temp file in the same directory so rename is atomic.
         let mut tmp = tempfile::NamedTempFile::new_in(&self.ralph_dir)
@@ -402,11 +843,74 @@ impl StateManager {
             .join(format!("iteration-{iteration}-{task_id}.log"))
     }
 
+    /// `current.json` for one of several tasks running at once (`--task-parallel`).
+    pub fn current_file_for(&self, task_id: &str) -> PathBuf {
+        self.ralph_dir.join(format!("current-{task_id}.json"))
+    }
+
+    /// Where `--failure-snapshots` bundles debug context for a failed iteration.
+    pub fn failure_dir(&self, iteration: u32) -> PathBuf {
+        self.ralph_dir
+            .join("failures")
+            .join(format!("iteration-{iteration}"))
+    }
+
+    /// The `ralph doctor` report saved when the circuit breaker trips.
+    pub fn doctor_report_file(&self) -> PathBuf {
+        self.ralph_dir.join("failures").join("doctor.txt")
+    }
+
+    /// The patch an agent proposed under `--plan`, saved for review.
+    pub fn plan_file(&self, iteration: u32, task_id: &str) -> PathBuf {
+        self.ralph_dir
+            .join("plans")
+            .join(format!("iteration-{iteration}-{task_id}.patch"))
+    }
+
+    /// The `--snapshots` copy of the workdir, refreshed before each iteration.
+    pub fn snapshot_dir(&self) -> PathBuf {
+        self.ralph_dir.join("snapshots").join("workdir")
+    }
+
+    // ── usage.json ────────────────────────────────────────────────────────────
+
+    /// Append `record` to usage.json and return the updated log.
+    pub fn record_usage(&self, record: UsageRecord) -> Result<UsageLog> {
+        let mut log = UsageLog::load(&self.usage_file)?;
+
+        log.total.add(&record.tokens);
+        if let Some(cost) = record.cost_usd {
+            *log.total_cost_usd.get_or_insert(0.0) += cost;
+        }
+        log.iterations.push(record);
+
+        let json = serde_json::to_string_pretty(&log).context("Failed to serialise usage")?;
+        fs::write(&self.usage_file, json).context("Failed to write usage.json")?;
+        Ok(log)
+    }
+
+    // ── session.json ──────────────────────────────────────────────────────────
+
+    /// The saved agent session, if any (an unreadable file counts as none).
+    pub fn load_session(&self) -> Option<AgentSession> {
+        let raw = fs::read_to_string(&self.session_file).ok()?;
+        serde_json::from_str(&raw).ok()
+    }
+
+    pub fn save_session(&self, session: &AgentSession) -> Result<()> {
+        let json = serde_json::to_string_pretty(session).context("Failed to serialise session")?;
+        fs::write(&self.session_file, json).context("Failed to write session.json")
+    }
+
+    pub fn clear_session(&self) {
+        let _ = fs::remove_file(&self.session_file);
+    }
+
     // ── progress.md ───────────────────────────────────────────────────────────
 
     /// Append a timestamped entry to progress.md.
     pub fn append_progress(&self, entry: &str) -> Result<()> {
-        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
+        let timestamp = self.timestamps.format(Utc::now());
         let line = format!("\n## {timestamp}\n\n{entry}\n");
 
         let mut file = fs::OpenOptions::new()
@@ -422,7 +926,7 @@ impl StateManager {
     }
 }
 
-fn validate_task_list(task_list: &TaskList) -> Result<()> {
+pub(crate) fn validate_task_list(task_list: &TaskList) -> Result<()> {
     let mut seen_ids = HashSet::new();
     for task in &task_list.tasks {
         if !seen_ids.insert(task.id.as_str()) {
@@ -493,20 +997,15 @@ mod tests {
                     title: "First".to_string(),
                     description: "first task".to_string(),
                     priority: 2,
-                    status: TaskStatus::Pending,
-                    depends_on: vec![],
-                    completed_at: None,
                     notes: Some("note-1".to_string()),
+                    ..Default::default()
                 },
                 Task {
                     id: "T2".to_string(),
                     title: "Second".to_string(),
                     description: "second task".to_string(),
                     priority: 1,
-                    status: TaskStatus::Pending,
-                    depends_on: vec![],
-                    completed_at: None,
-                    notes: None,
+                    ..Default::default()
                 },
             ],
         }
@@ -616,20 +1115,15 @@ mod tests {
                     title: "blocked".to_string(),
                     description: "blocked task".to_string(),
                     priority: 1,
-                    status: TaskStatus::Pending,
                     depends_on: vec!["B".to_string()],
-                    completed_at: None,
-                    notes: None,
+                    ..Default::default()
                 },
                 Task {
                     id: "B".to_string(),
                     title: "dependency".to_string(),
                     description: "dependency task".to_string(),
                     priority: 2,
-                    status: TaskStatus::Pending,
-                    depends_on: vec![],
-                    completed_at: None,
-                    notes: None,
+                    ..Default::default()
                 },
             ],
         };
@@ -660,6 +1154,32 @@ mod tests {
         assert!(task.completed_at.is_some());
     }
 
+    #[test]
+    fn iteration_stats_estimates_time_remaining() {
+        let mut stats = IterationStats::default();
+        assert_eq!(stats.eta_secs(3), None);
+
+        // Two iterations per completed task at ~60s each → 120s per task.
+        stats.record(50, false);
+        stats.record(70, true);
+        assert_eq!(stats.average_secs(), Some(60));
+        assert_eq!(stats.eta_secs(3), Some(360));
+
+        // The window drops old samples.
+        for _ in 0..IterationStats::WINDOW {
+            stats.record(10, true);
+        }
+        assert_eq!(stats.average_secs(), Some(10));
+    }
+
+    #[test]
+    fn loop_status_eta_str_shows_placeholder_until_known() {
+        let mut status = LoopStatus::new("a".into(), "a.md".into(), "codex".into());
+        assert_eq!(status.eta_str(), "—");
+        status.eta_secs = Some(125);
+        assert_eq!(status.eta_str(), "~2m5s");
+    }
+
     #[test]
     fn append_progress_appends_without_overwriting() {
         let dir = tempdir().expect("create tempdir");
@@ -708,6 +1228,31 @@ mod tests {
         assert_eq!(loaded.tasks[0].id, "T1");
     }
 
+    #[test]
+    fn switching_format_converts_the_task_list() {
+        let dir = tempdir().expect("create tempdir");
+        let mut state = StateManager::new(dir.path()).expect("create state manager");
+        fs::write(
+            &state.tasks_file,
+            r#"{"version": 1, "prd_path": "PRD.md", "created_at": "2026-02-17T11:25:50Z",
+                "updated_at": "2026-02-17T11:25:50Z", "tasks": [{"id": "T1", "title": "Parse",
+                "description": "Parse the PRD", "priority": 1, "status": "complete"}]}"#,
+        )
+        .expect("write tasks file");
+
+        let old = state.use_format(TaskFormat::Toml).expect("convert");
+        assert_eq!(old, Some(dir.path().join(".ralph/tasks.json")));
+        assert!(!dir.path().join(".ralph/tasks.json").exists());
+
+        // Readers that know nothing of the setting find the TOML list.
+        let reader = StateManager::new(dir.path()).expect("create state manager");
+        assert_eq!(reader.tasks_format, TaskFormat::Toml);
+        let loaded = reader.load_tasks().expect("load").expect("tasks exist");
+        assert_eq!(loaded.tasks[0].status, TaskStatus::Complete);
+        reader.backup_tasks().expect("back up");
+        assert!(dir.path().join(".ralph/tasks.toml.bak.1").exists());
+    }
+
     #[test]
     fn missing_required_fields_produce_clear_error() {
         let dir = tempdir().expect("create tempdir");
@@ -831,4 +1376,122 @@ mod tests {
         let msg = format!("{:#}", err);
         assert!(msg.to_ascii_lowercase().contains("circular"));
     }
+
+    #[test]
+    fn schema_is_published_and_invalid_tasks_are_restored() {
+        let dir = tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        state.write_schema().expect("write schema");
+        let schema: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&state.schema_file).expect("read schema"))
+                .expect("schema is valid JSON");
+        let statuses = &schema["properties"]["tasks"]["items"]["properties"]["status"]["enum"];
+        assert!(statuses
+            .as_array()
+            .expect("status enum")
+            .contains(&serde_json::json!("needs_review")));
+
+        let snapshot = sample_task_list();
+        state.save_tasks(&snapshot).expect("save tasks");
+        assert_eq!(
+            state.restore_tasks_if_invalid(&snapshot).expect("check"),
+            None
+        );
+
+        fs::write(&state.tasks_file, "{\"tasks\": [").expect("corrupt tasks");
+        let problem = state
+            .restore_tasks_if_invalid(&snapshot)
+            .expect("restore")
+            .expect("problem reported");
+        assert!(problem.contains("Failed to parse"));
+        let restored = state.load_tasks().expect("load").expect("tasks");
+        assert_eq!(restored.tasks.len(), snapshot.tasks.len());
+    }
+
+    #[test]
+    fn backups_rotate_and_restore_skips_unusable_snapshots() {
+        let dir = tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let mut list = sample_task_list();
+
+        for title in ["first", "second", "third", "fourth"] {
+            list.tasks[0].title = title.to_string();
+            state.save_tasks(&list).expect("save tasks");
+            state.backup_tasks().expect("backup");
+        }
+        assert!(!state.backup_path(TASK_BACKUPS + 1).exists());
+        let oldest = fs::read_to_string(state.backup_path(TASK_BACKUPS)).expect("read oldest");
+        assert!(oldest.contains("\"second\""));
+
+        // The newest snapshot is corrupt too; the next one is used.
+        fs::write(state.backup_path(1), "not json").expect("corrupt backup");
+        fs::write(&state.tasks_file, "not json").expect("corrupt tasks");
+        let (n, restored) = state
+            .restore_latest_backup()
+            .expect("restore")
+            .expect("usable backup");
+        assert_eq!(n, 2);
+        assert_eq!(restored.tasks[0].title, "third");
+        let loaded = state.load_tasks().expect("load").expect("tasks");
+        assert_eq!(loaded.tasks[0].title, "third");
+    }
+
+    #[test]
+    fn record_usage_appends_iterations_and_keeps_totals() {
+        let dir = tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let record = |iteration, cost_usd| UsageRecord {
+            iteration,
+            task_id: "T1".to_string(),
+            agent: "api".to_string(),
+            model: "claude-sonnet-4-20250514".to_string(),
+            recorded_at: Utc::now(),
+            tokens: TokenUsage {
+                input_tokens: 1_000,
+                output_tokens: 200,
+                ..TokenUsage::default()
+            },
+            cost_usd,
+        };
+
+        state.record_usage(record(1, Some(0.006))).expect("record");
+        let log = state.record_usage(record(2, None)).expect("record");
+        assert_eq!(log.iterations.len(), 2);
+        assert_eq!(log.total.input_tokens, 2_000);
+        assert_eq!(log.total.output_tokens, 400);
+        assert_eq!(log.total_cost_usd, Some(0.006));
+
+        let raw = fs::read_to_string(&state.usage_file).expect("read usage.json");
+        let reloaded: UsageLog = serde_json::from_str(&raw).expect("parse usage.json");
+        assert_eq!(reloaded, log);
+        assert!(raw.contains("\"input_tokens\": 1000"));
+        assert_eq!(UsageLog::load(&state.usage_file).expect("load"), log);
+        assert_eq!(log.summary(), "2400 tokens over 2 iteration(s), $0.0060");
+    }
+
+    #[test]
+    fn agent_process_groups_are_tracked_in_the_lock() {
+        let dir = tempdir().expect("tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        track_agent_group(&state.lock_file, 42, true).expect("missing lock is fine");
+        assert!(!state.lock_file.exists());
+
+        state
+            .write_lock(&LockFile {
+                pid: 1,
+                current_task: "T1".to_string(),
+                progress: "0/1 done".to_string(),
+                started_at: Utc::now(),
+                prd_path: "prd.md".to_string(),
+                agent: "claude".to_string(),
+                eta_secs: None,
+                agent_pgids: Vec::new(),
+            })
+            .expect("write lock");
+        track_agent_group(&state.lock_file, 42, true).expect("track");
+        track_agent_group(&state.lock_file, 43, true).expect("track");
+        track_agent_group(&state.lock_file, 42, false).expect("untrack");
+        let lock = state.read_lock().expect("read").expect("lock");
+        assert_eq!(lock.agent_pgids, vec![43]);
+    }
 }
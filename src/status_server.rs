@@ -0,0 +1,137 @@
+//! `--status-port`: a read-only HTTP endpoint serving the live `LoopStatus` of
+//! `ralph run` or every `ralph watch` loop as JSON, for dashboard widgets.
+//!
+//! `GET /status` (or `/`) is the only route. The server binds to localhost
+//! and never changes anything; put a reverse proxy in front to share it.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::state::SharedLoopStatus;
+
+/// Recent log lines included per loop.
+const RECENT_EVENTS: usize = 50;
+
+/// Bind `127.0.0.1:<port>` and serve `statuses` in the background until the
+/// process exits. Binding errors are returned so a taken port fails fast.
+pub async fn spawn(port: u16, statuses: Vec<SharedLoopStatus>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Cannot listen on --status-port {port}"))?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let statuses = statuses.clone();
+            tokio::spawn(async move {
+                let _ = respond(stream, &statuses).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, statuses: &[SharedLoopStatus]) -> std::io::Result<()> {
+    // Only the request line matters; headers and bodies are ignored.
+    let mut buf = [0u8; 2048];
+    let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .unwrap_or(Ok(0))?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+
+    let (code, body) = match (method, path) {
+        ("GET", "/" | "/status") => ("200 OK", snapshot(statuses)),
+        ("GET", _) => ("404 Not Found", json!({ "error": "not found" })),
+        _ => (
+            "405 Method Not Allowed",
+            json!({ "error": "read-only: only GET is supported" }),
+        ),
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {code}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Every loop's status, with its most recent log lines.
+fn snapshot(statuses: &[SharedLoopStatus]) -> Value {
+    let loops: Vec<_> = statuses
+        .iter()
+        .filter_map(|status| Some(status.lock().ok()?.snapshot(RECENT_EVENTS)))
+        .collect();
+    json!({ "loops": loops })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{LogKind, LoopState, LoopStatus};
+    use std::sync::{Arc, Mutex};
+
+    /// Send `raw` to a one-shot server for `statuses` and return the response.
+    async fn exchange(statuses: Vec<SharedLoopStatus>, raw: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            respond(stream, &statuses).await.expect("respond");
+        });
+        let mut client = TcpStream::connect(addr).await.expect("connect");
+        client.write_all(raw.as_bytes()).await.expect("write");
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.expect("read");
+        server.await.expect("server");
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_loop_status_with_recent_events() {
+        let mut status = LoopStatus::new(
+            "auth".to_string(),
+            "auth.md".to_string(),
+            "codex".to_string(),
+        );
+        status.state = LoopState::Running;
+        status.tasks_total = 3;
+        for i in 0..60 {
+            status.push_log(LogKind::Status, format!("event {i}"));
+        }
+
+        let response = exchange(
+            vec![Arc::new(Mutex::new(status))],
+            "GET /status HTTP/1.1\r\nHost: x\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        let body = response.split("\r\n\r\n").nth(1).expect("body");
+        let body: Value = serde_json::from_str(body).expect("json body");
+        let first = &body["loops"][0];
+        assert_eq!(first["name"], "auth");
+        assert_eq!(first["state"], "running");
+        assert_eq!(first["tasks_total"], 3);
+        let events = first["recent_events"].as_array().expect("events");
+        assert_eq!(events.len(), RECENT_EVENTS);
+        assert_eq!(events[0]["kind"], "status");
+        assert_eq!(events[RECENT_EVENTS - 1]["text"], "event 59");
+    }
+
+    #[tokio::test]
+    async fn only_get_status_is_served() {
+        let post = exchange(vec![], "POST /status HTTP/1.1\r\n\r\n").await;
+        assert!(post.starts_with("HTTP/1.1 405"), "{post}");
+        let other = exchange(vec![], "GET /tasks HTTP/1.1\r\n\r\n").await;
+        assert!(other.starts_with("HTTP/1.1 404"), "{other}");
+    }
+}
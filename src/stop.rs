@@ -1,30 +1,76 @@
-//! `ralph stop [<name>|--all]` — gracefully stop running loops via SIGTERM.
+//! `ralph stop [<name>|--all]` — gracefully stop running loops via SIGTERM,
+//! then wait for them to exit and confirm their locks are gone.
 
 use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use tokio::time::{sleep, Duration, Instant};
 
 use crate::cli::StopArgs;
 use crate::state::LockFile;
 
+/// How long a SIGKILLed process gets to disappear before we give up on it.
+const KILL_WAIT: Duration = Duration::from_secs(5);
+
 pub async fn stop_loops(args: StopArgs) -> Result<()> {
-    if args.all && args.workdir.is_none() {
+    let wait = Wait {
+        timeout: Duration::from_secs(args.timeout),
+        force: args.force,
+    };
+
+    let stopping = if args.all && args.workdir.is_none() {
         // Stop all loops system-wide via global registry
-        return stop_all_global().await;
+        stop_all_global()?
+    } else {
+        let workdir = resolve_workdir(args.workdir.as_deref())?;
+        if args.all {
+            stop_all(&workdir).await?
+        } else {
+            stop_named(&workdir, args.name.as_deref())?
+        }
+    };
+
+    let outcomes = confirm_stopped(&stopping, &wait).await;
+    let still_running = outcomes
+        .iter()
+        .filter(|o| **o == Outcome::StillRunning)
+        .count();
+    if still_running > 0 {
+        anyhow::bail!(
+            "{still_running} loop(s) still running after {}s{}",
+            args.timeout,
+            if args.force {
+                ""
+            } else {
+                " (use --force to SIGKILL)"
+            }
+        );
     }
+    Ok(())
+}
 
-    let workdir = resolve_workdir(args.workdir.as_deref())?;
+/// `--timeout` / `--force` for confirming a stop.
+struct Wait {
+    timeout: Duration,
+    force: bool,
+}
 
-    if args.all {
-        stop_all(&workdir).await
-    } else {
-        stop_named(&workdir, args.name.as_deref()).await
-    }
+/// Final state of one signalled loop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    /// Exited after SIGTERM.
+    Stopped,
+    /// Ignored SIGTERM and was killed with SIGKILL (`--force`).
+    Killed,
+    /// Still alive when we gave up waiting.
+    StillRunning,
 }
 
 // ── Implementations ───────────────────────────────────────────────────────────
 
-/// Stop the loop identified by `name`, or the default `.ralph/` loop if name is None.
-async fn stop_named(workdir: &Path, name: Option<&str>) -> Result<()> {
+/// Signal the loop identified by `name`, or the default `.ralph/` loop if name
+/// is None. Returns the lock of each loop that was signalled.
+fn stop_named(workdir: &Path, name: Option<&str>) -> Result<Vec<(PathBuf, LockFile)>> {
     let lock_path = match name {
         Some(n) => workdir.join(format!(".ralph-{}", n)).join("lock"),
         None => workdir.join(".ralph").join("lock"),
@@ -34,48 +80,188 @@ async fn stop_named(workdir: &Path, name: Option<&str>) -> Result<()> {
         let label = name.unwrap_or("default");
         println!("💤  No lock file found for '{}' (already stopped?)", label);
         println!("    Checked: {}", lock_path.display());
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     let lock = read_lock(&lock_path)?;
-    send_sigterm_to_lock(&lock, &lock_path)?;
-    Ok(())
+    let sent = send_sigterm_to_lock(&lock, &lock_path)?;
+    Ok(if sent {
+        vec![(lock_path, lock)]
+    } else {
+        Vec::new()
+    })
 }
 
 /// Stop all loops system-wide using the global registry.
-async fn stop_all_global() -> Result<()> {
+fn stop_all_global() -> Result<Vec<(PathBuf, LockFile)>> {
     let locks = crate::state::StateManager::find_all_global_locks();
     if locks.is_empty() {
         println!("💤  No running ralph loops found system-wide");
-        return Ok(());
+        return Ok(Vec::new());
     }
     println!("🛑  Stopping {} loop(s) system-wide…", locks.len());
-    for (lock_path, lock) in &locks {
-        let _ = send_sigterm_to_lock(lock, lock_path);
-    }
-    Ok(())
+    Ok(signal_each(locks))
 }
 
 /// Find all `.ralph*/lock` files in workdir and stop every running loop.
-async fn stop_all(workdir: &Path) -> Result<()> {
+async fn stop_all(workdir: &Path) -> Result<Vec<(PathBuf, LockFile)>> {
     let lock_files = find_all_lock_files(workdir).await?;
 
     if lock_files.is_empty() {
         println!("💤  No running ralph loops found in {}", workdir.display());
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     println!("🛑  Stopping {} loop(s)…", lock_files.len());
-    for lock_path in &lock_files {
-        match read_lock(lock_path) {
-            Ok(lock) => {
-                let _ = send_sigterm_to_lock(&lock, lock_path);
-            }
+    let mut locks = Vec::new();
+    for lock_path in lock_files {
+        match read_lock(&lock_path) {
+            Ok(lock) => locks.push((lock_path, lock)),
             Err(e) => {
                 eprintln!("    ⚠️  Could not read {}: {e}", lock_path.display());
             }
         }
     }
+    Ok(signal_each(locks))
+}
+
+/// SIGTERM each loop, once per process (`ralph watch` loops share one), and
+/// keep the ones that were running.
+fn signal_each(locks: Vec<(PathBuf, LockFile)>) -> Vec<(PathBuf, LockFile)> {
+    let mut signalled = HashSet::new();
+    locks
+        .into_iter()
+        .filter(|(lock_path, lock)| {
+            if signalled.contains(&lock.pid) {
+                return true;
+            }
+            let sent = send_sigterm_to_lock(lock, lock_path).unwrap_or_else(|e| {
+                eprintln!("    ⚠️  {e:#}");
+                false
+            });
+            if sent {
+                signalled.insert(lock.pid);
+            }
+            sent
+        })
+        .collect()
+}
+
+/// Wait for every signalled loop to exit, escalating to SIGKILL with
+/// `--force`, then report each loop's final state. A loop that exited without
+/// removing its lock has the lock cleaned up.
+async fn confirm_stopped(locks: &[(PathBuf, LockFile)], wait: &Wait) -> Vec<Outcome> {
+    let mut seen = HashSet::new();
+    let pids: Vec<u32> = locks
+        .iter()
+        .map(|(_, lock)| lock.pid)
+        .filter(|pid| seen.insert(*pid))
+        .collect();
+    if !pids.is_empty() {
+        println!(
+            "⏳  Waiting up to {}s for {} process(es) to exit…",
+            wait.timeout.as_secs(),
+            pids.len()
+        );
+    }
+
+    let mut by_pid = HashMap::new();
+    for pid in pids {
+        let mut outcome = if wait_for_exit(pid, wait.timeout).await {
+            Outcome::Stopped
+        } else {
+            Outcome::StillRunning
+        };
+        if outcome == Outcome::StillRunning && wait.force {
+            println!("    💥  PID {pid} ignored SIGTERM — sending SIGKILL");
+            if send_sigkill(pid).is_ok() && wait_for_exit(pid, KILL_WAIT).await {
+                outcome = Outcome::Killed;
+            }
+            // Its agents lead their own process groups, so they outlive it.
+            // The lock is read again: it may have started one since.
+            for (lock_path, lock) in locks.iter().filter(|(_, lock)| lock.pid == pid) {
+                let latest = read_lock(lock_path).unwrap_or_else(|_| lock.clone());
+                for &pgid in &latest.agent_pgids {
+                    if send_sigkill_to_group(pgid).is_ok() {
+                        println!("    💥  Killed agent process group {pgid}");
+                    }
+                }
+            }
+        }
+        by_pid.insert(pid, outcome);
+    }
+
+    locks
+        .iter()
+        .map(|(lock_path, lock)| {
+            let outcome = by_pid[&lock.pid];
+            let label = &lock.prd_path;
+            match outcome {
+                Outcome::StillRunning => println!(
+                    "    ⚠️  {label}: PID {} still running after {}s",
+                    lock.pid,
+                    wait.timeout.as_secs()
+                ),
+                Outcome::Stopped | Outcome::Killed => {
+                    let how = if outcome == Outcome::Killed {
+                        "killed"
+                    } else {
+                        "stopped"
+                    };
+                    if lock_path.exists() && read_lock(lock_path).is_ok_and(|l| l.pid == lock.pid) {
+                        let _ = std::fs::remove_file(lock_path);
+                        println!(
+                            "    ✅  {label}: {how} (PID {}); removed the lock it left behind",
+                            lock.pid
+                        );
+                    } else {
+                        println!("    ✅  {label}: {how} (PID {}), lock released", lock.pid);
+                    }
+                }
+            }
+            outcome
+        })
+        .collect()
+}
+
+/// Poll until `pid` is gone; `false` if it is still alive after `timeout`.
+async fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if !is_pid_alive(pid) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+}
+
+fn send_sigkill(pid: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+        kill(Pid::from_raw(pid as i32), Signal::SIGKILL)
+            .with_context(|| format!("Failed to send SIGKILL to PID {}", pid))?;
+    }
+    // taskkill /F already killed it on other platforms.
+    #[cfg(not(unix))]
+    let _ = pid;
+    Ok(())
+}
+
+fn send_sigkill_to_group(pgid: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+        killpg(Pid::from_raw(pgid as i32), Signal::SIGKILL)
+            .with_context(|| format!("Failed to send SIGKILL to process group {pgid}"))?;
+    }
+    #[cfg(not(unix))]
+    let _ = pgid;
     Ok(())
 }
 
@@ -95,8 +281,9 @@ fn read_lock(lock_path: &Path) -> Result<LockFile> {
         .with_context(|| format!("Cannot parse lock file: {}", lock_path.display()))
 }
 
-/// Send SIGTERM to the PID in the lock file, reporting the result.
-fn send_sigterm_to_lock(lock: &LockFile, lock_path: &Path) -> Result<()> {
+/// Send SIGTERM to the PID in the lock file, reporting the result. Returns
+/// `false` for a stale lock, which is removed instead.
+fn send_sigterm_to_lock(lock: &LockFile, lock_path: &Path) -> Result<bool> {
     let pid = lock.pid;
     let task = &lock.current_task;
     let prd = &lock.prd_path;
@@ -110,7 +297,7 @@ fn send_sigterm_to_lock(lock: &LockFile, lock_path: &Path) -> Result<()> {
         );
         // Clean up stale lock
         let _ = std::fs::remove_file(lock_path);
-        return Ok(());
+        return Ok(false);
     }
 
     println!(
@@ -142,11 +329,11 @@ fn send_sigterm_to_lock(lock: &LockFile, lock_path: &Path) -> Result<()> {
         }
     }
 
-    Ok(())
+    Ok(true)
 }
 
 /// Return `true` if the process with the given PID is still running.
-fn is_pid_alive(pid: u32) -> bool {
+pub(crate) fn is_pid_alive(pid: u32) -> bool {
     #[cfg(unix)]
     {
         use nix::sys::signal::{kill, Signal};
@@ -212,6 +399,8 @@ mod tests {
             started_at: Utc::now(),
             prd_path: "tests/PRD.md".to_string(),
             agent: "codex".to_string(),
+            eta_secs: None,
+            agent_pgids: Vec::new(),
         }
     }
 
@@ -229,8 +418,9 @@ mod tests {
             "lock file should exist before cleanup"
         );
 
-        send_sigterm_to_lock(&lock, &state.lock_file).expect("handle stale lock");
+        let sent = send_sigterm_to_lock(&lock, &state.lock_file).expect("handle stale lock");
 
+        assert!(!sent, "no signal for a dead PID");
         assert!(
             !state.lock_file.exists(),
             "stale lock should be removed when PID is dead"
@@ -267,4 +457,66 @@ mod tests {
         assert!(locks.contains(&alpha_state.lock_file));
         assert!(locks.contains(&beta_state.lock_file));
     }
+
+    /// A live process whose exit is reaped in the background, so its PID
+    /// disappears once it dies.
+    fn spawn_reaped(script: &str) -> u32 {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", script])
+            .spawn()
+            .expect("spawn process");
+        let pid = child.id();
+        std::thread::spawn(move || child.wait());
+        pid
+    }
+
+    #[tokio::test]
+    async fn stop_waits_for_exit_and_cleans_up_the_lock() {
+        let dir = tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let pid = spawn_reaped("exec sleep 30");
+        state.write_lock(&sample_lock(pid)).expect("write lock");
+
+        let signalled = stop_named(dir.path(), None).expect("signal loop");
+        let wait = Wait {
+            timeout: Duration::from_secs(5),
+            force: false,
+        };
+        let outcomes = confirm_stopped(&signalled, &wait).await;
+
+        assert_eq!(outcomes, [Outcome::Stopped]);
+        assert!(!is_pid_alive(pid));
+        assert!(!state.lock_file.exists(), "leftover lock is removed");
+    }
+
+    #[tokio::test]
+    async fn stop_escalates_to_sigkill_only_with_force() {
+        let dir = tempdir().expect("create tempdir");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let ready = dir.path().join("ready");
+        let pid = spawn_reaped(&format!(
+            "trap '' TERM; touch '{}'; exec sleep 30",
+            ready.display()
+        ));
+        while !ready.exists() {
+            sleep(Duration::from_millis(10)).await;
+        }
+        state.write_lock(&sample_lock(pid)).expect("write lock");
+        let signalled = stop_named(dir.path(), None).expect("signal loop");
+
+        let mut wait = Wait {
+            timeout: Duration::from_millis(500),
+            force: false,
+        };
+        assert_eq!(
+            confirm_stopped(&signalled, &wait).await,
+            [Outcome::StillRunning]
+        );
+        assert!(state.lock_file.exists(), "a live loop keeps its lock");
+
+        wait.force = true;
+        assert_eq!(confirm_stopped(&signalled, &wait).await, [Outcome::Killed]);
+        assert!(!is_pid_alive(pid));
+        assert!(!state.lock_file.exists());
+    }
 }
@@ -0,0 +1,147 @@
+//! systemd service integration for `ralph daemon` and `ralph watch`:
+//! `Type=notify` readiness, `WatchdogSec=` pings and status lines, sent over
+//! `$NOTIFY_SOCKET` (the `sd_notify` protocol). Outside systemd every call is
+//! a no-op.
+
+use std::io;
+use std::time::Duration;
+
+/// Tell systemd start-up is finished (`Type=notify`).
+pub fn ready() {
+    notify("READY=1");
+}
+
+/// Tell systemd a clean shutdown has begun.
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// One-line status shown by `systemctl status`.
+pub fn status(line: &str) {
+    notify(&format!("STATUS={}", line.replace('\n', " ")));
+}
+
+/// Keep-alive ping; a service that stops sending these is restarted.
+pub fn watchdog() {
+    notify("WATCHDOG=1");
+}
+
+/// How often to ping the watchdog: half of `WatchdogSec=`, or `None` when the
+/// watchdog is off or meant for another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    interval_from(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// Resolves when the next watchdog ping is due; never without a watchdog.
+pub async fn watchdog_due(interval: Option<Duration>) {
+    match interval {
+        Some(every) => tokio::time::sleep(every).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Ctrl+C or SIGTERM (how systemd stops a service). SIGTERM is registered
+/// up front so one arriving between polls is not lost.
+pub struct Shutdown {
+    #[cfg(unix)]
+    term: Option<tokio::signal::unix::Signal>,
+}
+
+impl Shutdown {
+    pub fn listen() -> Self {
+        Shutdown {
+            #[cfg(unix)]
+            term: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok(),
+        }
+    }
+
+    /// Resolves once either signal arrives.
+    pub async fn requested(&mut self) {
+        #[cfg(unix)]
+        if let Some(term) = self.term.as_mut() {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn interval_from(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|p| p.parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    // A lost notification is at worst a restart; never fail the run over it.
+    let _ = send(&socket.to_string_lossy(), state);
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let sock = UnixDatagram::unbound()?;
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+        let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+        sock.send_to_addr(state.as_bytes(), &addr)?;
+        return Ok(());
+    }
+    sock.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval_is_half_the_timeout_for_this_process() {
+        assert_eq!(
+            interval_from(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            interval_from(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(interval_from(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(interval_from(Some("0"), None, 42), None);
+        assert_eq!(interval_from(None, None, 42), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn send_delivers_the_state_to_the_notify_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let path = dir.path().join("notify.sock");
+        let listener = UnixDatagram::bind(&path).expect("bind notify socket");
+
+        send(&path.to_string_lossy(), "READY=1").expect("send");
+        let mut buf = [0u8; 64];
+        let n = listener.recv(&mut buf).expect("recv");
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
@@ -3,10 +3,21 @@
 //! Templates are plain markdown files. The first non-empty line starting with `#`
 //! is treated as the title; a `> description` blockquote on the next line(s) is
 //! the short description shown in `ralph template list`.
+//!
+//! Names may be namespaced (`team/code-review`), which maps to a subdirectory.
+//! `ralph template export` packs templates into a tarball and `import` unpacks
+//! one, a directory, or a git repository into the store, so a team can share
+//! one set (`[templates] source` in ralph.toml). Every write goes through a
+//! temp file and a rename, so concurrent saves and imports never leave a
+//! half-written template behind.
 
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::TemplatesConfig;
 
 /// Directory where templates are stored.
 fn templates_dir() -> Result<PathBuf> {
@@ -68,16 +79,86 @@ fn extract_meta(content: &str) -> (Option<String>, Option<String>) {
     (title, description)
 }
 
+/// Where template `name` lives under `dir`; `team/code-review` is
+/// `team/code-review.md`.
+fn template_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let valid_segment = |s: &str| {
+        !s.is_empty()
+            && s != "."
+            && s != ".."
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    if !name.split('/').all(valid_segment) {
+        anyhow::bail!(
+            "Invalid template name '{name}': use letters, digits, '-', '_' and '.', \
+             with '/' after a namespace (e.g. team/code-review)"
+        );
+    }
+    Ok(dir.join(format!("{name}.md")))
+}
+
+/// Write `content` to `dest` through a temp file in the same directory, so
+/// readers see the old template or the new one, never part of either.
+fn write_atomic(dest: &Path, content: &str) -> Result<()> {
+    let parent = dest.parent().context("Template path has no parent")?;
+    fs::create_dir_all(parent).with_context(|| format!("Cannot create {}", parent.display()))?;
+    let mut tmp = tempfile::NamedTempFile::new_in(parent)
+        .with_context(|| format!("Cannot create a temp file in {}", parent.display()))?;
+    tmp.write_all(content.as_bytes())?;
+    tmp.persist(dest)
+        .map_err(|e| anyhow::anyhow!("Cannot write template {}: {}", dest.display(), e))?;
+    Ok(())
+}
+
+/// Every template under `dir`, as (name, path), sorted by name.
+fn template_names(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut found = Vec::new();
+    collect_markdown(dir, dir, &mut found);
+    found.sort();
+    found
+}
+
+/// Markdown files under `dir`, named by their path relative to `root`
+/// without `.md`. Hidden entries (`.git`, temp files) are skipped.
+fn collect_markdown(root: &Path, dir: &Path, found: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_markdown(root, &path, found);
+        } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            let Ok(rel) = path
+                .with_extension("")
+                .strip_prefix(root)
+                .map(Path::to_path_buf)
+            else {
+                continue;
+            };
+            let name = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            found.push((name, path));
+        }
+    }
+}
+
 /// Save a PRD file as a named template.
 pub fn save(name: &str, source: &PathBuf) -> Result<()> {
     let dir = templates_dir()?;
-    let dest = dir.join(format!("{name}.md"));
+    let dest = template_path(&dir, name)?;
 
     let content = fs::read_to_string(source)
         .with_context(|| format!("Cannot read source PRD: {}", source.display()))?;
 
-    fs::write(&dest, &content)
-        .with_context(|| format!("Cannot write template: {}", dest.display()))?;
+    write_atomic(&dest, &content)?;
 
     let (title, _) = extract_meta(&content);
     let display_title = title.as_deref().unwrap_or(name);
@@ -86,23 +167,15 @@ pub fn save(name: &str, source: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-/// List all saved templates.
-pub fn list(verbose: bool) -> Result<()> {
+/// List saved templates, optionally only those in `namespace`.
+pub fn list(namespace: Option<&str>, verbose: bool) -> Result<()> {
     let dir = templates_dir()?;
     let mut entries: Vec<TemplateMeta> = Vec::new();
 
-    for entry in fs::read_dir(&dir).context("Cannot read templates directory")? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+    for (name, path) in template_names(&dir) {
+        if namespace.is_some_and(|ns| !in_namespace(&name, ns)) {
             continue;
         }
-        let name = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("?")
-            .to_string();
-
         let content = fs::read_to_string(&path).unwrap_or_default();
         let (title, description) = extract_meta(&content);
 
@@ -114,9 +187,11 @@ pub fn list(verbose: bool) -> Result<()> {
         });
     }
 
-    entries.sort_by(|a, b| a.name.cmp(&b.name));
-
     if entries.is_empty() {
+        if let Some(ns) = namespace {
+            println!("📭  No templates in '{ns}'.");
+            return Ok(());
+        }
         println!("📭  No templates saved yet.");
         println!("    Save one with: ralph template save <name> <prd.md>");
         return Ok(());
@@ -149,16 +224,12 @@ pub fn list(verbose: bool) -> Result<()> {
 /// Get the path to a saved template by name.
 pub fn get(name: &str) -> Result<PathBuf> {
     let dir = templates_dir()?;
-    let path = dir.join(format!("{name}.md"));
+    let path = template_path(&dir, name)?;
     if !path.exists() {
         // Try to suggest similar names
-        let available: Vec<String> = fs::read_dir(&dir)?
-            .filter_map(|e| e.ok())
-            .filter_map(|e| {
-                e.path()
-                    .file_stem()
-                    .and_then(|s| s.to_str().map(String::from))
-            })
+        let available: Vec<String> = template_names(&dir)
+            .into_iter()
+            .map(|(name, _)| name)
             .collect();
 
         if available.is_empty() {
@@ -176,11 +247,19 @@ pub fn get(name: &str) -> Result<PathBuf> {
 /// Remove a saved template.
 pub fn remove(name: &str) -> Result<()> {
     let dir = templates_dir()?;
-    let path = dir.join(format!("{name}.md"));
+    let path = template_path(&dir, name)?;
     if !path.exists() {
         anyhow::bail!("Template '{name}' not found");
     }
     fs::remove_file(&path).context("Cannot remove template")?;
+    // Drop namespace directories the removal left empty.
+    let mut parent = path.parent();
+    while let Some(p) = parent.filter(|p| *p != dir) {
+        if fs::remove_dir(p).is_err() {
+            break;
+        }
+        parent = p.parent();
+    }
     println!("🗑️  Removed template '{name}'");
     Ok(())
 }
@@ -193,9 +272,197 @@ pub fn show(name: &str) -> Result<()> {
     Ok(())
 }
 
+fn in_namespace(name: &str, namespace: &str) -> bool {
+    let namespace = namespace.trim_end_matches('/');
+    name.strip_prefix(namespace)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Pack templates into a gzipped tarball at `out`: the ones named (a name
+/// or a whole namespace), or all of them.
+pub fn export(out: &Path, names: &[String]) -> Result<()> {
+    let dir = templates_dir()?;
+    let selected = select(&dir, names)?;
+    if selected.is_empty() {
+        anyhow::bail!("No templates to export. Save one with: ralph template save <name> <prd.md>");
+    }
+    let files: Vec<String> = selected.iter().map(|name| format!("{name}.md")).collect();
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(out)
+        .arg("-C")
+        .arg(&dir)
+        .args(&files)
+        .status()
+        .context("Failed to run tar")?;
+    if !status.success() {
+        anyhow::bail!("tar exited with {status}");
+    }
+    println!(
+        "📦  Exported {} template(s) to {}",
+        selected.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// The templates in `dir` that `names` pick out, each a template or a
+/// namespace; all of them when `names` is empty.
+fn select(dir: &Path, names: &[String]) -> Result<Vec<String>> {
+    let all: Vec<String> = template_names(dir).into_iter().map(|(n, _)| n).collect();
+    if names.is_empty() {
+        return Ok(all);
+    }
+    let mut selected = Vec::new();
+    for wanted in names {
+        let matched: Vec<&String> = all
+            .iter()
+            .filter(|name| *name == wanted || in_namespace(name, wanted))
+            .collect();
+        if matched.is_empty() {
+            anyhow::bail!("No template or namespace named '{wanted}'");
+        }
+        selected.extend(matched.into_iter().cloned());
+    }
+    selected.sort();
+    selected.dedup();
+    Ok(selected)
+}
+
+/// What an import added, replaced, and left alone.
+#[derive(Debug, Default)]
+struct ImportReport {
+    added: Vec<String>,
+    replaced: Vec<String>,
+    kept: Vec<String>,
+}
+
+/// Import templates from a tarball, a directory, or a git URL; without a
+/// source, from `[templates] source`. `namespace` (or `[templates]
+/// namespace`) is put in front of every name.
+pub fn import(
+    source: Option<&str>,
+    namespace: Option<&str>,
+    force: bool,
+    config: Option<&TemplatesConfig>,
+) -> Result<()> {
+    let source = source
+        .map(str::to_string)
+        .or_else(|| config.and_then(|c| c.source.clone()))
+        .context("No source given and no [templates] source in ralph.toml")?;
+    let namespace = namespace
+        .map(str::to_string)
+        .or_else(|| config.and_then(|c| c.namespace.clone()));
+
+    let staging = tempfile::tempdir().context("Cannot create a staging directory")?;
+    let root = fetch(&source, staging.path())?;
+    let dir = templates_dir()?;
+    let report = import_dir(&root, &dir, namespace.as_deref(), force)?;
+
+    for name in &report.added {
+        println!("  ➕ {name}");
+    }
+    for name in &report.replaced {
+        println!("  🔄 {name}");
+    }
+    for name in &report.kept {
+        println!("  ⏭️  {name} (exists; --force replaces it)");
+    }
+    println!(
+        "✅  Imported {} template(s) from {source}",
+        report.added.len() + report.replaced.len()
+    );
+    Ok(())
+}
+
+/// Put `source` somewhere readable and return the directory to import from.
+fn fetch(source: &str, staging: &Path) -> Result<PathBuf> {
+    let (program, args): (&str, Vec<&std::ffi::OsStr>) =
+        if source.contains("://") || source.starts_with("git@") {
+            // `--` keeps a source starting with `-` from being read as an option.
+            let args = ["clone", "--quiet", "--depth", "1", "--", source]
+                .into_iter()
+                .map(std::ffi::OsStr::new)
+                .chain([staging.as_os_str()])
+                .collect();
+            ("git", args)
+        } else if Path::new(source).is_dir() {
+            return Ok(PathBuf::from(source));
+        } else if Path::new(source).is_file() {
+            let args = ["-xf", source, "-C"]
+                .into_iter()
+                .map(std::ffi::OsStr::new)
+                .chain([staging.as_os_str()])
+                .collect();
+            ("tar", args)
+        } else {
+            anyhow::bail!("Template source not found: {source}");
+        };
+    let status = Command::new(program)
+        .args(&args)
+        .status()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !status.success() {
+        anyhow::bail!("{program} could not fetch {source} ({status})");
+    }
+    Ok(staging.to_path_buf())
+}
+
+/// Files in a source that document it rather than being templates.
+const NOT_TEMPLATES: &[&str] = &["readme", "changelog", "contributing", "license"];
+
+/// Copy the templates under `root` (or its `templates/` directory, if it has
+/// one) into `store`.
+fn import_dir(
+    root: &Path,
+    store: &Path,
+    namespace: Option<&str>,
+    force: bool,
+) -> Result<ImportReport> {
+    let nested = root.join("templates");
+    let root = if nested.is_dir() {
+        nested
+    } else {
+        root.to_path_buf()
+    };
+    let mut report = ImportReport::default();
+    for (name, path) in template_names(&root) {
+        let stem = name.rsplit('/').next().unwrap_or(&name);
+        if NOT_TEMPLATES.contains(&stem.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        let name = match namespace {
+            Some(ns) => format!("{}/{name}", ns.trim_end_matches('/')),
+            None => name,
+        };
+        let dest = match template_path(store, &name) {
+            Ok(dest) => dest,
+            Err(e) => {
+                eprintln!("⚠️   Skipped {}: {e}", path.display());
+                continue;
+            }
+        };
+        let existed = dest.exists();
+        if existed && !force {
+            report.kept.push(name);
+            continue;
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        write_atomic(&dest, &content)?;
+        if existed {
+            report.replaced.push(name);
+        } else {
+            report.added.push(name);
+        }
+    }
+    Ok(report)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::tempdir;
 
     #[test]
     fn extract_meta_parses_title_and_description() {
@@ -223,4 +490,54 @@ mod tests {
         assert!(title.is_none());
         assert!(desc.is_none());
     }
+
+    #[test]
+    fn namespaced_names_map_to_subdirectories() {
+        let dir = Path::new("/store");
+        assert_eq!(
+            template_path(dir, "team/code-review").unwrap(),
+            Path::new("/store/team/code-review.md")
+        );
+        assert!(template_path(dir, "../escape").is_err());
+        assert!(template_path(dir, "team//x").is_err());
+        assert!(template_path(dir, "/abs").is_err());
+        assert!(in_namespace("team/a/b", "team"));
+        assert!(!in_namespace("teamwork", "team"));
+    }
+
+    #[test]
+    fn import_namespaces_templates_and_keeps_existing_ones() {
+        let source = tempdir().expect("create source");
+        let store = tempdir().expect("create store");
+        let templates = source.path().join("templates");
+        fs::create_dir_all(templates.join("backend")).expect("create dirs");
+        fs::write(templates.join("review.md"), "# Review\n").expect("write");
+        fs::write(templates.join("backend/api.md"), "# API\n").expect("write");
+        fs::write(templates.join("README.md"), "# About\n").expect("write");
+        fs::write(source.path().join("ignored.md"), "# Outside\n").expect("write");
+
+        let first = import_dir(source.path(), store.path(), Some("team"), false).expect("import");
+        assert_eq!(first.added, ["team/backend/api", "team/review"]);
+        let names: Vec<String> = template_names(store.path())
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(names, ["team/backend/api", "team/review"]);
+
+        fs::write(templates.join("review.md"), "# Review v2\n").expect("rewrite");
+        let again = import_dir(source.path(), store.path(), Some("team"), false).expect("import");
+        assert_eq!(again.kept, ["team/backend/api", "team/review"]);
+        let forced = import_dir(source.path(), store.path(), Some("team"), true).expect("import");
+        assert_eq!(forced.replaced.len(), 2);
+        assert_eq!(
+            fs::read_to_string(store.path().join("team/review.md")).unwrap(),
+            "# Review v2\n"
+        );
+
+        assert_eq!(
+            select(store.path(), &["team/backend".to_string()]).unwrap(),
+            ["team/backend/api"]
+        );
+        assert!(select(store.path(), &["other".to_string()]).is_err());
+    }
 }
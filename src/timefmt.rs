@@ -0,0 +1,101 @@
+//! Duration and timestamp formatting shared by `ralph status`, the TUI,
+//! notifications, and `progress.md`.
+
+use chrono::{DateTime, Local, Utc};
+
+/// `--timestamps` choices for the timestamps Ralph writes to `progress.md`.
+pub const TIMESTAMP_ZONES: &[&str] = &["utc", "local"];
+
+/// Short form for tables and chat messages: `1h5m`, `3m20s`, `42s`.
+pub fn compact_duration(secs: u64) -> String {
+    let (h, m, s) = split(secs);
+    if h > 0 {
+        format!("{h}h{m}m")
+    } else if m > 0 {
+        format!("{m}m{s}s")
+    } else {
+        format!("{s}s")
+    }
+}
+
+/// Spelled-out form for terminal reports: `1h 5m 3s`, `3m 20s`, `42s`.
+pub fn verbose_duration(secs: u64) -> String {
+    let (h, m, s) = split(secs);
+    if h > 0 {
+        format!("{h}h {m}m {s}s")
+    } else if m > 0 {
+        format!("{m}m {s}s")
+    } else {
+        format!("{s}s")
+    }
+}
+
+fn split(secs: u64) -> (u64, u64, u64) {
+    (secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+/// Time zone timestamps are rendered in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimestampZone {
+    #[default]
+    Utc,
+    /// The machine's zone, with its offset so entries stay unambiguous
+    /// across DST changes and machines.
+    Local,
+}
+
+impl TimestampZone {
+    /// The zone named by one of `TIMESTAMP_ZONES`; anything else is UTC.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "local" => TimestampZone::Local,
+            _ => TimestampZone::Utc,
+        }
+    }
+
+    /// `2026-10-15 02:30:00 UTC`, or `2026-10-14 22:30:00 -04:00` in local time.
+    pub fn format(self, at: DateTime<Utc>) -> String {
+        match self {
+            TimestampZone::Utc => at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            TimestampZone::Local => at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S %:z")
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn durations_in_both_styles() {
+        assert_eq!(compact_duration(42), "42s");
+        assert_eq!(compact_duration(200), "3m20s");
+        assert_eq!(compact_duration(3903), "1h5m");
+        assert_eq!(verbose_duration(42), "42s");
+        assert_eq!(verbose_duration(200), "3m 20s");
+        assert_eq!(verbose_duration(3903), "1h 5m 3s");
+    }
+
+    #[test]
+    fn timestamps_render_in_utc_or_local_time() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 15, 2, 30, 0).unwrap();
+        assert_eq!(
+            TimestampZone::from_name("utc").format(at),
+            "2026-10-15 02:30:00 UTC"
+        );
+        let local = TimestampZone::from_name("local").format(at);
+        let expected = at.with_timezone(&Local);
+        assert_eq!(
+            local,
+            format!(
+                "{} {}",
+                expected.format("%Y-%m-%d %H:%M:%S"),
+                expected.offset()
+            )
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Log-pane filters for the TUI: stderr-only, a user-typed pattern, and hiding
+//! per-agent "thinking" chatter.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::state::{LogKind, LogLine};
+
+/// Built-in chatter patterns, keyed by agent name. Extended (not replaced) by
+/// `[tui.chatter]` in ralph.toml.
+const DEFAULT_CHATTER: &[(&str, &[&str])] = &[
+    ("codex", &[r"^(\[[^\]]+\]\s*)?thinking\b", r"^tokens used:"]),
+    ("claude", &[r"^(Thinking|∴)\b"]),
+    ("gemini", &[r"^(Thinking|\(thinking\))"]),
+    ("opencode", &[r"^(thinking|\.\.\.)"]),
+];
+
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    pub stderr_only: bool,
+    pub hide_chatter: bool,
+    pattern: Option<Regex>,
+    chatter: HashMap<String, Vec<Regex>>,
+}
+
+impl LogFilter {
+    /// Build a filter from the built-in chatter patterns plus `extra` ones.
+    /// Invalid user patterns are skipped with a warning.
+    pub fn new(extra: &HashMap<String, Vec<String>>) -> Self {
+        let mut chatter: HashMap<String, Vec<Regex>> = HashMap::new();
+        for (agent, patterns) in DEFAULT_CHATTER {
+            let compiled = patterns
+                .iter()
+                .map(|p| Regex::new(p).expect("built-in chatter pattern"));
+            chatter
+                .entry(agent.to_string())
+                .or_default()
+                .extend(compiled);
+        }
+        for (agent, patterns) in extra {
+            for p in patterns {
+                match Regex::new(p) {
+                    Ok(re) => chatter.entry(agent.clone()).or_default().push(re),
+                    Err(e) => eprintln!("⚠️   Ignoring chatter pattern for {agent}: {e}"),
+                }
+            }
+        }
+
+        Self {
+            chatter,
+            ..Self::default()
+        }
+    }
+
+    /// Set (or, with an empty string, clear) the match pattern.
+    pub fn set_pattern(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.pattern = if pattern.is_empty() {
+            None
+        } else {
+            Some(Regex::new(pattern)?)
+        };
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stderr_only || self.hide_chatter || self.pattern.is_some()
+    }
+
+    /// Whether `line` from a loop running `agent` should be shown.
+    pub fn keep(&self, agent: &str, line: &LogLine) -> bool {
+        if self.stderr_only && line.kind != LogKind::Stderr {
+            return false;
+        }
+        // Ralph's own status lines are never chatter.
+        if self.hide_chatter && line.kind != LogKind::Status {
+            if let Some(patterns) = self.chatter.get(agent) {
+                let body = line.text.trim_start();
+                if patterns.iter().any(|re| re.is_match(body)) {
+                    return false;
+                }
+            }
+        }
+        match &self.pattern {
+            Some(re) => re.is_match(&line.text),
+            None => true,
+        }
+    }
+
+    /// Short summary for the log pane title, e.g. `stderr, /panic/, -chatter`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.stderr_only {
+            parts.push("stderr".to_string());
+        }
+        if let Some(re) = &self.pattern {
+            parts.push(format!("/{}/", re.as_str()));
+        }
+        if self.hide_chatter {
+            parts.push("-chatter".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn out(text: &str) -> LogLine {
+        LogLine {
+            kind: LogKind::Stdout,
+            text: text.to_string(),
+        }
+    }
+
+    fn err(text: &str) -> LogLine {
+        LogLine {
+            kind: LogKind::Stderr,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn stderr_only_and_pattern_combine() {
+        let mut filter = LogFilter::new(&HashMap::new());
+        assert!(filter.keep("codex", &out("plain stdout")));
+
+        filter.stderr_only = true;
+        assert!(!filter.keep("codex", &out("plain stdout")));
+        assert!(filter.keep("codex", &err("error: boom")));
+
+        filter.set_pattern("(?i)panic").expect("valid pattern");
+        assert!(!filter.keep("codex", &err("error: boom")));
+        assert!(filter.keep("codex", &err("thread main PANICKED")));
+        assert_eq!(filter.describe(), "stderr, /(?i)panic/");
+
+        filter.set_pattern("").expect("clear pattern");
+        assert!(filter.set_pattern("(").is_err());
+    }
+
+    #[test]
+    fn hide_chatter_uses_builtin_and_configured_patterns() {
+        let extra = HashMap::from([("claude".to_string(), vec![r"^Planning".to_string()])]);
+        let mut filter = LogFilter::new(&extra);
+        filter.hide_chatter = true;
+
+        assert!(!filter.keep("codex", &out("thinking")));
+        assert!(!filter.keep("codex", &err("[2025-01-01T00:00:00] thinking")));
+        assert!(filter.keep("codex", &out("exec cargo test")));
+        assert!(!filter.keep("claude", &out("Planning the change")));
+        // Patterns are per agent, and never hide Ralph's own status lines.
+        assert!(filter.keep("gemini", &out("Planning the change")));
+        let status = LogLine {
+            kind: LogKind::Status,
+            text: "thinking about fallback".to_string(),
+        };
+        assert!(filter.keep("codex", &status));
+    }
+}
@@ -13,6 +13,9 @@
 //! ╚═══════════════════════════════════════════════════════════╝
 //! ```
 
+mod filter;
+
+use std::collections::HashMap;
 use std::io::{self, Stdout};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -24,7 +27,7 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
-    backend::CrosstermBackend,
+    backend::{CrosstermBackend, TestBackend},
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
@@ -32,7 +35,8 @@ use ratatui::{
     Frame, Terminal,
 };
 
-use crate::state::{LoopState, SharedLoopStatus};
+use crate::state::{BreakerChoice, BreakerPrompt, LogKind, LogLine, LoopState, SharedLoopStatus};
+use filter::LogFilter;
 
 // ── TUI state ─────────────────────────────────────────────────────────────────
 
@@ -42,10 +46,16 @@ struct TuiApp {
     log_scroll: u16,
     /// Cached row of the table for keyboard navigation.
     table_state: TableState,
+    /// Active log-pane filters.
+    filter: LogFilter,
+    /// Pattern being typed after `/`; `Some` while the prompt is open.
+    pattern_input: Option<String>,
+    /// Error from the last pattern, shown in the log title until the next edit.
+    pattern_error: Option<String>,
 }
 
 impl TuiApp {
-    fn new(loops: Vec<SharedLoopStatus>) -> Self {
+    fn new(loops: Vec<SharedLoopStatus>, filter: LogFilter) -> Self {
         let mut table_state = TableState::default();
         if !loops.is_empty() {
             table_state.select(Some(0));
@@ -55,9 +65,25 @@ impl TuiApp {
             selected: 0,
             log_scroll: 0,
             table_state,
+            filter,
+            pattern_input: None,
+            pattern_error: None,
         }
     }
 
+    /// Apply the typed pattern and close the prompt.
+    fn commit_pattern(&mut self) {
+        let Some(input) = self.pattern_input.take() else {
+            return;
+        };
+        self.pattern_error = self
+            .filter
+            .set_pattern(&input)
+            .err()
+            .map(|e| e.to_string().lines().last().unwrap_or_default().to_string());
+        self.scroll_to_bottom();
+    }
+
     fn select_next(&mut self) {
         if self.loops.is_empty() {
             return;
@@ -91,6 +117,34 @@ impl TuiApp {
         // Large value — ratatui will clamp to actual content height
         self.log_scroll = u16::MAX;
     }
+
+    /// The loop whose circuit-breaker prompt the keys answer: the selected
+    /// one if it is asking, else the first that is.
+    fn asking(&self) -> Option<(usize, BreakerPrompt)> {
+        let prompt = |i: usize| {
+            let s = self.loops.get(i)?.lock().ok()?;
+            s.breaker_prompt.clone().filter(|p| p.answer.is_none())
+        };
+        std::iter::once(self.selected)
+            .chain(0..self.loops.len())
+            .find_map(|i| prompt(i).map(|p| (i, p)))
+    }
+
+    /// Answer the pending prompt; `SwitchAgent` only when there is an agent
+    /// to switch to.
+    fn answer_breaker(&mut self, choice: BreakerChoice) {
+        let Some((i, prompt)) = self.asking() else {
+            return;
+        };
+        if choice == BreakerChoice::SwitchAgent && prompt.switch_to.is_none() {
+            return;
+        }
+        if let Ok(mut s) = self.loops[i].lock() {
+            if let Some(p) = s.breaker_prompt.as_mut() {
+                p.answer = Some(choice);
+            }
+        }
+    }
 }
 
 // ── Public entry point ────────────────────────────────────────────────────────
@@ -99,13 +153,19 @@ impl TuiApp {
 /// This should be called from a dedicated `std::thread::spawn`.
 ///
 /// Returns when the user presses `q`/`Q`/`Ctrl-C` or `cancel_flag` becomes true.
-pub fn run_tui(loops: Vec<SharedLoopStatus>, cancel_flag: Arc<AtomicBool>) -> anyhow::Result<()> {
+/// `chatter` holds extra per-agent regexes hidden by the `t` filter.
+pub fn run_tui(
+    loops: Vec<SharedLoopStatus>,
+    cancel_flag: Arc<AtomicBool>,
+    chatter: &HashMap<String, Vec<String>>,
+) -> anyhow::Result<()> {
     if loops.is_empty() {
         return Ok(());
     }
 
+    let filter = LogFilter::new(chatter);
     let mut terminal = setup_terminal()?;
-    let mut app = TuiApp::new(loops);
+    let mut app = TuiApp::new(loops, filter);
     // Start scrolled to bottom so users see latest logs immediately
     app.scroll_to_bottom();
 
@@ -138,6 +198,20 @@ fn run_loop(
         // Poll for keyboard events with a short timeout so we keep redrawing
         if event::poll(tick_rate)? {
             if let Event::Key(key) = event::read()? {
+                // While the `/` prompt is open, keys edit the pattern.
+                if let Some(input) = app.pattern_input.as_mut() {
+                    match key.code {
+                        KeyCode::Enter => app.commit_pattern(),
+                        KeyCode::Esc => app.pattern_input = None,
+                        KeyCode::Backspace => {
+                            input.pop();
+                        }
+                        KeyCode::Char(c) => input.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 match (key.code, key.modifiers) {
                     // Quit
                     (KeyCode::Char('q'), _)
@@ -146,6 +220,10 @@ fn run_loop(
                         cancel_flag.store(true, Ordering::Relaxed);
                         break;
                     }
+                    // Answer a circuit-breaker prompt
+                    (KeyCode::Char('c'), _) => app.answer_breaker(BreakerChoice::Continue),
+                    (KeyCode::Char('a'), _) => app.answer_breaker(BreakerChoice::SwitchAgent),
+                    (KeyCode::Char('s'), _) => app.answer_breaker(BreakerChoice::Stop),
                     // Navigate loops
                     (KeyCode::Tab, _) | (KeyCode::Right, _) => app.select_next(),
                     (KeyCode::BackTab, _) | (KeyCode::Left, _) => app.select_prev(),
@@ -160,6 +238,26 @@ fn run_loop(
                     (KeyCode::Char('k'), _) | (KeyCode::PageUp, _) => app.scroll_up(),
                     (KeyCode::Char('G'), _) | (KeyCode::End, _) => app.scroll_to_bottom(),
                     (KeyCode::Char('g'), _) | (KeyCode::Home, _) => app.log_scroll = 0,
+                    // Filter logs
+                    (KeyCode::Char('/'), _) => {
+                        app.pattern_error = None;
+                        app.pattern_input = Some(String::new());
+                    }
+                    (KeyCode::Char('e'), _) => {
+                        app.filter.stderr_only = !app.filter.stderr_only;
+                        app.scroll_to_bottom();
+                    }
+                    (KeyCode::Char('t'), _) => {
+                        app.filter.hide_chatter = !app.filter.hide_chatter;
+                        app.scroll_to_bottom();
+                    }
+                    (KeyCode::Esc, _) => {
+                        app.filter.stderr_only = false;
+                        app.filter.hide_chatter = false;
+                        let _ = app.filter.set_pattern("");
+                        app.pattern_error = None;
+                        app.scroll_to_bottom();
+                    }
                     _ => {}
                 }
             }
@@ -202,14 +300,17 @@ fn render(frame: &mut Frame, app: &mut TuiApp) {
         ])
         .split(area);
 
-    render_title(frame, chunks[0], active_count, app.loops.len());
+    match app.asking() {
+        Some((i, prompt)) => render_breaker_prompt(frame, chunks[0], app, i, &prompt),
+        None => render_title(frame, chunks[0], active_count, app.loops.len()),
+    }
     render_table(frame, chunks[1], app);
     render_logs(frame, chunks[2], app);
 }
 
 fn render_title(frame: &mut Frame, area: ratatui::layout::Rect, active: usize, total: usize) {
     let title = format!(
-        " Ralph — {}/{} loops active  [Tab] switch  [↑↓jk] scroll  [q] quit ",
+        " Ralph — {}/{} loops active  [Tab] switch  [↑↓jk] scroll  [/] filter  [e] stderr  [t] chatter  [Esc] clear  [q] quit ",
         active, total
     );
     let block = Block::default()
@@ -223,8 +324,38 @@ fn render_title(frame: &mut Frame, area: ratatui::layout::Rect, active: usize, t
     frame.render_widget(paragraph, area);
 }
 
+/// Replaces the title bar while a loop waits to trip the circuit breaker.
+fn render_breaker_prompt(
+    frame: &mut Frame,
+    area: ratatui::layout::Rect,
+    app: &TuiApp,
+    index: usize,
+    prompt: &BreakerPrompt,
+) {
+    let name = app.loops[index]
+        .lock()
+        .map(|s| s.name.clone())
+        .unwrap_or_default();
+    let switch = match &prompt.switch_to {
+        Some(agent) => format!("[a] switch to {agent}  "),
+        None => String::new(),
+    };
+    let text = format!(
+        " ⚠ {name}: {} consecutive failures — [c] continue  {switch}[s] stop  (stops in {}s) ",
+        prompt.failures,
+        prompt.secs_left()
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red));
+    let paragraph = Paragraph::new(text)
+        .block(block)
+        .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD));
+    frame.render_widget(paragraph, area);
+}
+
 fn render_table(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp) {
-    let header_cells = ["Name", "Agent", "PRD", "Progress", "Status", "Time"]
+    let header_cells = ["Name", "Agent", "PRD", "Progress", "Status", "Time", "ETA"]
         .iter()
         .map(|h| {
             Cell::from(*h).style(
@@ -248,9 +379,13 @@ fn render_table(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp
             let agent_cell = Cell::from(s.agent.clone());
             let prd_cell = Cell::from(s.prd_path.clone());
             let progress_cell = Cell::from(make_progress_bar(s.tasks_done, s.tasks_total, 12));
-            let (status_text, status_color) = state_display(&s.state);
+            let (status_text, status_color) = match &s.breaker_prompt {
+                Some(prompt) => (format!("confirm? {}s", prompt.secs_left()), Color::Red),
+                None => state_display(&s.state),
+            };
             let status_cell = Cell::from(status_text).style(Style::default().fg(status_color));
             let time_cell = Cell::from(s.elapsed_str());
+            let eta_cell = Cell::from(s.eta_str());
 
             Row::new(vec![
                 name_cell,
@@ -259,6 +394,7 @@ fn render_table(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp
                 progress_cell,
                 status_cell,
                 time_cell,
+                eta_cell,
             ])
             .height(1)
         })
@@ -277,6 +413,7 @@ fn render_table(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp
             Constraint::Length(18), // progress bar
             Constraint::Length(12), // status
             Constraint::Length(8),  // time
+            Constraint::Length(8),  // eta
         ],
     )
     .header(header)
@@ -301,7 +438,8 @@ fn render_logs(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp)
                 let lines: Vec<Line> = s
                     .recent_logs
                     .iter()
-                    .map(|l| Line::from(Span::raw(strip_ansi(l))))
+                    .filter(|l| app.filter.keep(&s.agent, l))
+                    .map(render_log_line)
                     .collect();
                 (name, lines)
             }
@@ -318,11 +456,18 @@ fn render_logs(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp)
         app.log_scroll = max_scroll;
     }
 
-    let title = format!(
+    let mut title = format!(
         " [{loop_name}] Logs  (line {}/{})",
         app.log_scroll + view_height,
         content_height
     );
+    if let Some(input) = &app.pattern_input {
+        title.push_str(&format!("  filter: /{input}▏ "));
+    } else if let Some(err) = &app.pattern_error {
+        title.push_str(&format!("  bad pattern: {err} "));
+    } else if app.filter.is_active() {
+        title.push_str(&format!("  filter: {} ", app.filter.describe()));
+    }
     let paragraph = Paragraph::new(log_lines)
         .block(
             Block::default()
@@ -335,6 +480,60 @@ fn render_logs(frame: &mut Frame, area: ratatui::layout::Rect, app: &mut TuiApp)
     frame.render_widget(paragraph, area);
 }
 
+// ── Failure snapshots ─────────────────────────────────────────────────────────
+
+/// Width of the loops table in a failure snapshot.
+const SNAPSHOT_WIDTH: u16 = 120;
+/// Log lines of the failing loop kept in a failure snapshot.
+const SNAPSHOT_LOG_LINES: usize = 50;
+
+/// Plain-text rendering of the dashboard for post-mortems once the TUI is
+/// gone: the loops table followed by the last log lines of loop `failing`.
+pub fn failure_snapshot(loops: &[SharedLoopStatus], failing: usize) -> String {
+    let mut app = TuiApp::new(loops.to_vec(), LogFilter::new(&HashMap::new()));
+    app.selected = failing;
+    app.table_state.select(Some(failing));
+
+    let mut out = format!(
+        "Ralph watch — failure snapshot ({})\n\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    let height = loops.len() as u16 + 3;
+    if let Ok(mut terminal) = Terminal::new(TestBackend::new(SNAPSHOT_WIDTH, height)) {
+        if terminal
+            .draw(|f| render_table(f, f.area(), &mut app))
+            .is_ok()
+        {
+            let buffer = terminal.backend().buffer();
+            for y in 0..height {
+                let row: String = (0..SNAPSHOT_WIDTH)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect();
+                out.push_str(row.trim_end());
+                out.push('\n');
+            }
+        }
+    }
+
+    if let Some(Ok(s)) = loops.get(failing).map(|ls| ls.lock()) {
+        let skip = s.recent_logs.len().saturating_sub(SNAPSHOT_LOG_LINES);
+        out.push_str(&format!(
+            "\n[{}] {} — last {} log lines\n",
+            s.name,
+            s.state,
+            s.recent_logs.len() - skip
+        ));
+        for line in s.recent_logs.iter().skip(skip) {
+            let text = crate::output::strip_ansi(&line.text);
+            match line.kind {
+                LogKind::Stderr => out.push_str(&format!("[stderr] {text}\n")),
+                _ => out.push_str(&format!("{text}\n")),
+            }
+        }
+    }
+    out
+}
+
 // ── Terminal setup/restore ────────────────────────────────────────────────────
 
 fn setup_terminal() -> anyhow::Result<Terminal<CrosstermBackend<Stdout>>> {
@@ -371,46 +570,117 @@ fn make_progress_bar(done: u32, total: u32, bar_width: usize) -> String {
     )
 }
 
+/// Style a log line by stream: stderr in dim red, Ralph status lines in cyan.
+fn render_log_line(line: &LogLine) -> Line<'static> {
+    let text = crate::output::strip_ansi(&line.text);
+    match line.kind {
+        LogKind::Stdout => Line::from(Span::raw(text)),
+        LogKind::Stderr => Line::from(Span::styled(
+            text,
+            Style::default().fg(Color::Red).add_modifier(Modifier::DIM),
+        )),
+        LogKind::Status => Line::from(Span::styled(text, Style::default().fg(Color::Cyan))),
+    }
+}
+
 fn state_display(state: &LoopState) -> (String, Color) {
     match state {
+        LoopState::Queued(position) => (format!("queued #{position}"), Color::DarkGray),
         LoopState::Starting => ("starting".to_string(), Color::DarkGray),
         LoopState::Parsing => ("parsing…".to_string(), Color::Yellow),
         LoopState::Running => ("running".to_string(), Color::Green),
+        LoopState::RateLimited { until } => {
+            let left = (*until - chrono::Utc::now()).num_seconds().max(0) as u64;
+            (
+                format!("rate limited {}", crate::timefmt::compact_duration(left)),
+                Color::Magenta,
+            )
+        }
         LoopState::Complete => ("complete".to_string(), Color::Cyan),
         LoopState::Failed(_) => ("failed".to_string(), Color::Red),
         LoopState::Stopped => ("stopped".to_string(), Color::Gray),
     }
 }
 
-/// Strip ANSI escape sequences from a string for clean terminal rendering.
-fn strip_ansi(s: &str) -> String {
-    let mut out = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
-    while let Some(c) = chars.next() {
-        if c == '\x1b' {
-            // ESC [ ... final_byte  (CSI sequence)
-            if chars.peek() == Some(&'[') {
-                chars.next();
-                for c2 in chars.by_ref() {
-                    if c2.is_ascii_alphabetic() {
-                        break;
-                    }
-                }
-            } else if chars.peek() == Some(&']') {
-                // OSC sequence: ESC ] ... ST (ESC \ or BEL)
-                chars.next();
-                let mut prev = '\0';
-                for c2 in chars.by_ref() {
-                    if c2 == '\x07' || (prev == '\x1b' && c2 == '\\') {
-                        break;
-                    }
-                    prev = c2;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::LoopStatus;
+
+    #[test]
+    fn breaker_keys_answer_the_loop_that_is_asking() {
+        let loops: Vec<SharedLoopStatus> = ["auth", "billing"]
+            .iter()
+            .map(|name| {
+                Arc::new(std::sync::Mutex::new(LoopStatus::new(
+                    name.to_string(),
+                    format!("{name}.md"),
+                    "codex".to_string(),
+                )))
+            })
+            .collect();
+        let mut app = TuiApp::new(loops.clone(), LogFilter::new(&HashMap::new()));
+        // Nothing asked yet: the keys do nothing.
+        app.answer_breaker(BreakerChoice::Stop);
+        assert!(app.asking().is_none());
+
+        loops[1].lock().unwrap().breaker_prompt = Some(BreakerPrompt {
+            failures: 3,
+            deadline: std::time::Instant::now() + Duration::from_secs(60),
+            switch_to: None,
+            answer: None,
+        });
+        assert_eq!(app.asking().map(|(i, _)| i), Some(1));
+        // No agent to switch to.
+        app.answer_breaker(BreakerChoice::SwitchAgent);
+        assert_eq!(app.asking().map(|(i, _)| i), Some(1));
+
+        app.answer_breaker(BreakerChoice::Continue);
+        let answer = loops[1]
+            .lock()
+            .unwrap()
+            .breaker_prompt
+            .as_ref()
+            .and_then(|p| p.answer);
+        assert_eq!(answer, Some(BreakerChoice::Continue));
+        assert!(app.asking().is_none());
+    }
+
+    #[test]
+    fn failure_snapshot_has_the_table_and_the_failing_loops_last_logs() {
+        let loops: Vec<SharedLoopStatus> = ["auth", "billing"]
+            .iter()
+            .map(|name| {
+                let mut s = LoopStatus::new(
+                    name.to_string(),
+                    format!("{name}.md"),
+                    "codex".to_string(),
+                );
+                s.tasks_total = 4;
+                for i in 0..60 {
+                    s.push_log(LogKind::Stdout, format!("{name} line {i}"));
                 }
-            }
-            // else: skip lone ESC
-        } else {
-            out.push(c);
+                Arc::new(std::sync::Mutex::new(s))
+            })
+            .collect();
+        {
+            let mut billing = loops[1].lock().unwrap();
+            billing.state = LoopState::Failed("circuit breaker".to_string());
+            billing.push_log(LogKind::Stderr, "\x1b[31mboom\x1b[0m".to_string());
         }
+
+        let snapshot = failure_snapshot(&loops, 1);
+        let table_row = snapshot
+            .lines()
+            .find(|l| l.contains("billing.md"))
+            .expect("billing row");
+        assert!(table_row.contains("failed"), "{table_row}");
+        assert!(snapshot.contains("auth.md"));
+
+        assert!(snapshot.contains("[billing] failed: circuit breaker — last 50 log lines"));
+        assert!(snapshot.contains("billing line 59\n[stderr] boom\n"));
+        assert!(snapshot.contains("billing line 11\n"));
+        assert!(!snapshot.contains("billing line 10\n"));
+        assert!(!snapshot.contains("auth line"));
     }
-    out
 }
@@ -0,0 +1,263 @@
+//! `ralph verify` — re-run the verify command against the tree as it is now
+//! and re-open completed tasks that no longer pass, so tasks.json matches
+//! the code after manual edits or merges.
+//!
+//! A task's own `verify` command checks it alone, as does a global command
+//! with `{task_id}`; otherwise the global suite runs once for the remaining
+//! tasks and a failure can't be pinned on one.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::Path;
+
+use crate::cli::VerifyArgs;
+use crate::error::RalphError;
+use crate::orchestrator::run_verify_cmd;
+use crate::shell::quote;
+use crate::state::{StateManager, TaskStatus};
+
+/// Placeholder for a per-task check, e.g. `cargo test {task_id}`.
+const TASK_PLACEHOLDER: &str = "{task_id}";
+
+pub async fn verify_tasks(args: VerifyArgs) -> Result<()> {
+    let workdir = args
+        .workdir
+        .as_deref()
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+        .context("Cannot resolve workdir — does it exist?")?;
+
+    let state = match args.name.as_deref() {
+        Some(name) => StateManager::new_named(&workdir, name)?,
+        None => StateManager::new(&workdir)?,
+    };
+    let mut task_list = state
+        .load_tasks()?
+        .context("No tasks.json found — nothing to verify")?;
+
+    if args.verify_cmd.is_none() && task_list.tasks.iter().all(|t| t.verify.is_none()) {
+        return Err(RalphError::Config(anyhow::anyhow!(
+            "No verify command — pass --verify-cmd, set verify_cmd under [completion], or give tasks a `verify` command"
+        ))
+        .into());
+    }
+
+    // A task's own `verify` command wins over the global one.
+    let complete: Vec<(String, String, Option<String>)> = task_list
+        .tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Complete)
+        .map(|t| {
+            let check = t.verify.clone().or_else(|| {
+                args.verify_cmd
+                    .as_deref()
+                    .filter(|cmd| cmd.contains(TASK_PLACEHOLDER))
+                    .map(|cmd| cmd.replace(TASK_PLACEHOLDER, &quote(&t.id)))
+            });
+            (t.id.clone(), t.title.clone(), check)
+        })
+        .collect();
+
+    // The suite covers the tasks without a check of their own, all at once.
+    let suite = args
+        .verify_cmd
+        .as_deref()
+        .filter(|cmd| !cmd.contains(TASK_PLACEHOLDER))
+        .filter(|_| complete.is_empty() || complete.iter().any(|(_, _, c)| c.is_none()));
+    let mut suite_failed = None;
+    if let Some(cmd) = suite {
+        println!("🔍  {cmd}");
+        if run_verify_cmd(cmd, &workdir, args.timeout).await? {
+            println!("✅  Passed");
+        } else {
+            suite_failed = Some(cmd);
+        }
+    }
+
+    let checked: Vec<_> = complete
+        .iter()
+        .filter_map(|(id, title, check)| Some((id, title, check.as_deref()?)))
+        .collect();
+    if checked.is_empty() && suite.is_none() {
+        println!("No completed tasks to verify.");
+        return Ok(());
+    }
+
+    let mut failing = Vec::new();
+    for &(id, title, task_cmd) in &checked {
+        if run_verify_cmd(task_cmd, &workdir, args.timeout).await? {
+            println!("✅  {id} — {title}");
+        } else {
+            println!("❌  {id} — {title}: `{task_cmd}` failed");
+            failing.push(id.clone());
+        }
+    }
+
+    if failing.is_empty() {
+        if let Some(cmd) = suite_failed {
+            anyhow::bail!(
+                "`{cmd}` failed. Add {TASK_PLACEHOLDER} to the command, or give tasks a `verify` command, to check each completed task on its own and re-open the ones that fail."
+            );
+        }
+        if !checked.is_empty() {
+            println!("\nAll {} checked tasks still pass.", checked.len());
+        }
+        return Ok(());
+    }
+    if args.dry_run {
+        println!("\n--dry-run: would re-open {}", failing.join(", "));
+    } else {
+        for task in task_list.tasks.iter_mut() {
+            if failing.contains(&task.id) {
+                task.status = TaskStatus::Pending;
+                task.completed_at = None;
+            }
+        }
+        task_list.updated_at = Utc::now();
+        state.save_tasks(&task_list)?;
+        state.append_progress(&format!(
+            "**Verify** — {} re-opened: their checks no longer pass",
+            failing.join(", ")
+        ))?;
+        println!(
+            "\n↩️   Re-opened {} — the next `ralph run` picks them up",
+            failing.join(", ")
+        );
+    }
+    anyhow::bail!(
+        "{} of {} checked tasks failed verification",
+        failing.len(),
+        checked.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Task, TaskList};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn seed(workdir: &Path) {
+        let state = StateManager::new(workdir).expect("create state manager");
+        let now = Utc::now();
+        let task = |id: &str, status| Task {
+            id: id.to_string(),
+            title: format!("{id} title"),
+            description: "body".to_string(),
+            priority: 1,
+            status,
+            completed_at: Some(now),
+            ..Default::default()
+        };
+        state
+            .save_tasks(&TaskList {
+                version: 1,
+                prd_path: "prd.md".to_string(),
+                created_at: now,
+                updated_at: now,
+                tasks: vec![
+                    task("T1", TaskStatus::Complete),
+                    task("T2", TaskStatus::Complete),
+                    task("T3", TaskStatus::Pending),
+                ],
+            })
+            .expect("save tasks");
+    }
+
+    fn args(workdir: &Path, cmd: &str, dry_run: bool) -> VerifyArgs {
+        VerifyArgs {
+            verify_cmd: Some(cmd.to_string()),
+            name: None,
+            workdir: Some(workdir.to_path_buf()),
+            timeout: 10,
+            dry_run,
+        }
+    }
+
+    fn statuses(workdir: &Path) -> Vec<TaskStatus> {
+        StateManager::new(workdir)
+            .expect("create state manager")
+            .load_tasks()
+            .expect("load tasks")
+            .expect("tasks exist")
+            .tasks
+            .into_iter()
+            .map(|t| t.status)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn failing_tasks_are_reopened() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path());
+        // T2's check fails; T3 is pending and isn't checked.
+        let cmd = "echo {task_id} >> checked.txt; test {task_id} != T2";
+
+        let err = verify_tasks(args(dir.path(), cmd, true))
+            .await
+            .expect_err("T2 fails");
+        assert!(err.to_string().contains("1 of 2"), "{err}");
+        assert_eq!(statuses(dir.path())[1], TaskStatus::Complete, "dry run");
+
+        verify_tasks(args(dir.path(), cmd, false))
+            .await
+            .expect_err("T2 fails");
+        assert_eq!(
+            statuses(dir.path()),
+            [
+                TaskStatus::Complete,
+                TaskStatus::Pending,
+                TaskStatus::Pending
+            ]
+        );
+        let checked = fs::read_to_string(dir.path().join("checked.txt")).expect("read");
+        assert_eq!(checked, "T1\nT2\nT1\nT2\n");
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let progress = fs::read_to_string(&state.progress_file).expect("read progress");
+        assert!(progress.contains("**Verify** — T2 re-opened"), "{progress}");
+    }
+
+    #[tokio::test]
+    async fn whole_suite_failure_leaves_tasks_alone() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path());
+        verify_tasks(args(dir.path(), "true", false))
+            .await
+            .expect("suite passes");
+        let err = verify_tasks(args(dir.path(), "false", false))
+            .await
+            .expect_err("suite fails");
+        assert!(err.to_string().contains("{task_id}"), "{err}");
+        assert_eq!(statuses(dir.path())[0], TaskStatus::Complete);
+    }
+
+    #[tokio::test]
+    async fn tasks_own_verify_commands_are_used_without_a_global_one() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path());
+        let state = StateManager::new(dir.path()).expect("create state manager");
+        let mut task_list = state.load_tasks().expect("load").expect("tasks exist");
+        task_list.tasks[0].verify = Some("true".to_string());
+        task_list.tasks[1].verify = Some("false".to_string());
+        state.save_tasks(&task_list).expect("save tasks");
+
+        let mut no_global = args(dir.path(), "", false);
+        no_global.verify_cmd = None;
+        let err = verify_tasks(no_global).await.expect_err("T2 fails");
+        assert!(err.to_string().contains("1 of 2"), "{err}");
+        assert_eq!(
+            statuses(dir.path())[..2],
+            [TaskStatus::Complete, TaskStatus::Pending]
+        );
+
+        // T1 has its own check; the suite covers the rest.
+        task_list.tasks[1].verify = None;
+        state.save_tasks(&task_list).expect("save tasks");
+        let err = verify_tasks(args(dir.path(), "false", false))
+            .await
+            .expect_err("suite fails");
+        assert!(err.to_string().contains("`false` failed"), "{err}");
+        assert_eq!(statuses(dir.path())[0], TaskStatus::Complete);
+    }
+}
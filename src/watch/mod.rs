@@ -1,14 +1,27 @@
 //! `ralph watch` — run multiple PRDs in parallel, each in its own orchestrator loop.
 
+mod tmux;
+
 use anyhow::{Context, Result};
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 
+use crate::agents::{AgentSlots, Sampling};
 use crate::cli::{RunArgs, WatchArgs};
-use crate::state::{LoopState, LoopStatus, SharedLoopStatus};
+use crate::error::{tag_of, RalphError};
+use crate::hooks::{send_hook, HookConfig, HookEvent, WatchLoop, WatchLoopSummary};
+use crate::notify::{send_notify, NotifyConfig};
+use crate::state::{
+    LockFile, LogKind, LoopState, LoopStatus, SharedLoopStatus, TaskFormat, TaskStatus,
+};
+use crate::systemd;
+
+/// `--existing` choices for a loop whose state holds an unfinished run.
+pub const EXISTING_RUN_ACTIONS: &[&str] = &["ask", "resume", "archive", "abort"];
 
 // ── Public entry point ────────────────────────────────────────────────────────
 
@@ -36,9 +49,30 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
         })
         .collect::<Result<Vec<_>>>()?;
 
+    // The tmux session reruns this command (minus --tmux) in its own window.
+    if args.tmux {
+        return tmux::launch(&workdir, &make_unique_slugs(&prds));
+    }
+
+    // Loops run without stdout, so warn about ignored sampling flags up front.
+    Sampling::new(args.reasoning_effort.as_deref(), args.temperature).warn_unsupported(&args.agent);
+
     // Derive unique slugs (deduplicate if two PRDs have the same stem)
     let slugs = make_unique_slugs(&prds);
 
+    // A slug reuses .ralph-<slug>/ from any earlier session on the same PRD.
+    let keep = resolve_existing_runs(&workdir, &slugs, &args.existing)?;
+    let (prds, slugs): (Vec<PathBuf>, Vec<String>) = prds
+        .into_iter()
+        .zip(slugs)
+        .zip(keep)
+        .filter_map(|(pair, keep)| keep.then_some(pair))
+        .unzip();
+    if prds.is_empty() {
+        println!("Nothing to run — every loop was aborted.");
+        return Ok(());
+    }
+
     println!(
         "🚀  Ralph Watch — {} PRDs, parallel={}",
         prds.len(),
@@ -48,6 +82,27 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
         println!("    • {} → .ralph-{}/", prd.display(), slug);
     }
 
+    // Each loop sends its own events; these two cover the session as a whole.
+    let hook = args
+        .hook_url
+        .as_ref()
+        .map(|url| HookConfig::new(url.clone(), args.hook_token.clone()));
+    // The loops warn about a missing gateway token themselves.
+    let notify = args
+        .notify
+        .as_deref()
+        .and_then(|flag| NotifyConfig::from_env(flag, "watch", args.notify_token.as_deref()));
+    let session_started = std::time::Instant::now();
+    let loops = prds
+        .iter()
+        .zip(slugs.iter())
+        .map(|(prd, slug)| WatchLoop {
+            name: slug.clone(),
+            prd: prd.to_string_lossy().to_string(),
+        })
+        .collect();
+    send_session_event(&hook, &notify, HookEvent::WatchStarted { loops, parallel }).await;
+
     // Create shared LoopStatus for each loop
     let statuses: Vec<SharedLoopStatus> = prds
         .iter()
@@ -61,6 +116,11 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
         })
         .collect();
 
+    if let Some(port) = args.status_port {
+        crate::status_server::spawn(port, statuses.clone()).await?;
+        println!("    Status: http://127.0.0.1:{port}/status");
+    }
+
     // Shared cancellation flag — set to true on SIGINT/SIGTERM or when TUI quits
     let cancel_flag = Arc::new(AtomicBool::new(false));
 
@@ -70,6 +130,7 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
         tokio::spawn(async move {
             let _ = tokio::signal::ctrl_c().await;
             eprintln!("\n🛑  Interrupt received — stopping all loops…");
+            systemd::stopping();
             cf.store(true, Ordering::Relaxed);
         });
     }
@@ -82,6 +143,7 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
             if let Ok(mut stream) = signal(SignalKind::terminate()) {
                 stream.recv().await;
                 eprintln!("\n🛑  SIGTERM received — stopping all loops…");
+                systemd::stopping();
                 cf.store(true, Ordering::Relaxed);
             }
         });
@@ -91,8 +153,9 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
     let tui_handle = if !args.no_tui && is_tty() {
         let statuses_clone = statuses.clone();
         let cf = cancel_flag.clone();
+        let chatter = args.chatter_patterns.clone();
         Some(std::thread::spawn(move || {
-            crate::tui::run_tui(statuses_clone, cf)
+            crate::tui::run_tui(statuses_clone, cf, &chatter)
         }))
     } else {
         if !args.no_tui {
@@ -102,38 +165,127 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
     };
 
     // ── Spawn orchestrator loops ──────────────────────────────────────────────
+    // Every loop is spawned up front and waits for its slot; a dispatcher hands
+    // out semaphore permits in PRD order, so queued loops show their position
+    // in the TUI instead of sitting at "starting" with no logs.
     let semaphore = Arc::new(Semaphore::new(parallel));
+    // Sessions per agent are capped across loops, whichever agent each falls back to.
+    let agent_slots = AgentSlots::new(&args.agent_limits);
     let mut join_set = JoinSet::new();
+    let mut slots = Vec::with_capacity(prds.len());
+    queue_loops(&statuses, parallel);
 
-    for (prd, (slug, status)) in prds.iter().zip(slugs.iter().zip(statuses.iter())) {
-        // Acquire a semaphore permit before spawning (blocks if at capacity)
-        let permit = semaphore.clone().acquire_owned().await?;
-
-        let run_args = build_run_args(&args, prd, slug, &workdir, status.clone(), &cancel_flag);
+    for (index, (prd, (slug, status))) in prds
+        .iter()
+        .zip(slugs.iter().zip(statuses.iter()))
+        .enumerate()
+    {
+        let mut run_args = build_run_args(
+            &args,
+            prd,
+            slug,
+            &workdir,
+            status.clone(),
+            &cancel_flag,
+            &agent_slots,
+        );
+        // Only the TUI can answer before the circuit breaker trips.
+        if tui_handle.is_some() && args.breaker_timeout > 0 {
+            run_args.breaker_timeout = Some(args.breaker_timeout);
+        }
         let status_clone = status.clone();
+        let all_statuses = statuses.clone();
+        let state_dir = workdir.join(format!(".ralph-{slug}"));
+        let cf = cancel_flag.clone();
+        let (slot_tx, slot_rx) = tokio::sync::oneshot::channel();
+        slots.push(slot_tx);
 
         join_set.spawn(async move {
+            // The dispatcher drops the sender instead of granting a slot once
+            // the session is cancelled.
+            let Ok(permit) = slot_rx.await else {
+                if let Ok(mut s) = status_clone.lock() {
+                    s.state = LoopState::Stopped;
+                    s.push_log(LogKind::Status, "🛑 Stopped before starting".to_string());
+                }
+                return Ok(());
+            };
+            if cf.load(Ordering::Relaxed) {
+                if let Ok(mut s) = status_clone.lock() {
+                    s.state = LoopState::Stopped;
+                }
+                return Ok(());
+            }
+
             let result = crate::orchestrator::run(run_args).await;
             drop(permit); // Release slot back to semaphore
+            if let Ok(mut s) = status_clone.lock() {
+                s.finished_at = Some(std::time::Instant::now());
+            }
 
+            let failed = result
+                .as_ref()
+                .is_err_and(|e| !matches!(tag_of(e), Some(RalphError::Cancelled)));
+            // Cancellation, the circuit breaker, budgets, and rejected logins
+            // already set the loop state.
+            let result = result.or_else(|e| match tag_of(&e) {
+                Some(
+                    RalphError::Cancelled
+                    | RalphError::CircuitBreaker { .. }
+                    | RalphError::BudgetExceeded(_)
+                    | RalphError::AuthFailed(_),
+                ) => Ok(()),
+                _ => Err(e),
+            });
             if let Err(ref e) = result {
                 if let Ok(mut s) = status_clone.lock() {
                     s.state = LoopState::Failed(e.to_string());
-                    s.push_log(format!("❌ Loop failed: {e}"));
+                    s.push_log(LogKind::Status, format!("❌ Loop failed: {e}"));
                 }
             }
+            if failed {
+                save_failure_snapshot(&all_statuses, index, &state_dir);
+            }
 
             result
         });
     }
 
+    {
+        let statuses = statuses.clone();
+        let cf = cancel_flag.clone();
+        tokio::spawn(async move {
+            for (index, slot) in slots.into_iter().enumerate() {
+                let permit = tokio::select! {
+                    permit = semaphore.clone().acquire_owned() => permit,
+                    _ = cancelled(&cf) => break,
+                };
+                let Ok(permit) = permit else { break };
+                start_loop(&statuses, index);
+                // A loop that already finished (a panic) just returns its slot.
+                let _ = slot.send(permit);
+            }
+        });
+    }
+
     // ── Wait for all loops to finish ──────────────────────────────────────────
+    // Under systemd the watchdog is pinged from here, so a wedged supervisor
+    // gets the service restarted.
+    systemd::ready();
+    let watchdog = systemd::watchdog_interval();
     let mut errors: Vec<String> = Vec::new();
-    while let Some(outcome) = join_set.join_next().await {
-        match outcome {
-            Ok(Ok(())) => {}
-            Ok(Err(e)) => errors.push(e.to_string()),
-            Err(e) => errors.push(format!("task panic: {e}")),
+    loop {
+        tokio::select! {
+            outcome = join_set.join_next() => match outcome {
+                None => break,
+                Some(Ok(Ok(()))) => {}
+                Some(Ok(Err(e))) => errors.push(e.to_string()),
+                Some(Err(e)) => errors.push(format!("task panic: {e}")),
+            },
+            _ = systemd::watchdog_due(watchdog) => {
+                systemd::watchdog();
+                systemd::status(&service_status(&statuses));
+            }
         }
     }
 
@@ -171,11 +323,93 @@ pub async fn watch(args: WatchArgs) -> Result<()> {
         }
     }
 
+    let event = HookEvent::WatchComplete {
+        loops: loop_summaries(&statuses),
+        total_duration_secs: session_started.elapsed().as_secs(),
+    };
+    send_session_event(&hook, &notify, event).await;
+
     Ok(())
 }
 
 // ── Helpers ───────────────────────────────────────────────────────────────────
 
+/// Send a session-wide event to `--hook-url` and `--notify`, when set.
+async fn send_session_event(
+    hook: &Option<HookConfig>,
+    notify: &Option<NotifyConfig>,
+    event: HookEvent,
+) {
+    if let Some(hook) = hook {
+        send_hook(hook, &event, None).await;
+    }
+    if let Some(notify) = notify {
+        send_notify(notify, &event, None).await;
+    }
+}
+
+/// `systemctl status` line, e.g. `2/3 loops active, 5/12 tasks done`.
+fn service_status(statuses: &[SharedLoopStatus]) -> String {
+    let (mut active, mut done, mut total) = (0, 0, 0);
+    for status in statuses {
+        if let Ok(s) = status.lock() {
+            if !matches!(
+                s.state,
+                LoopState::Complete | LoopState::Failed(_) | LoopState::Stopped
+            ) {
+                active += 1;
+            }
+            done += s.tasks_done;
+            total += s.tasks_total;
+        }
+    }
+    format!(
+        "{active}/{} loops active, {done}/{total} tasks done",
+        statuses.len()
+    )
+}
+
+/// Write the dashboard as it stood when loop `index` failed to
+/// `<state_dir>/failure-snapshot.txt`, for post-mortems after the TUI is gone.
+fn save_failure_snapshot(statuses: &[SharedLoopStatus], index: usize, state_dir: &Path) {
+    let path = state_dir.join("failure-snapshot.txt");
+    let snapshot = crate::tui::failure_snapshot(statuses, index);
+    // A loop can fail before it has created its state directory.
+    let written = std::fs::create_dir_all(state_dir).and_then(|()| std::fs::write(&path, snapshot));
+    let msg = match written {
+        Ok(()) => format!("📸 Dashboard saved to {}", path.display()),
+        Err(e) => format!("⚠️ Could not save dashboard snapshot: {e}"),
+    };
+    if let Ok(mut s) = statuses[index].lock() {
+        s.push_log(LogKind::Status, msg);
+    }
+}
+
+/// How each loop ended, for the `watch_complete` hook.
+fn loop_summaries(statuses: &[SharedLoopStatus]) -> Vec<WatchLoopSummary> {
+    statuses
+        .iter()
+        .filter_map(|status| {
+            let s = status.lock().ok()?;
+            let (outcome, error) = match &s.state {
+                LoopState::Complete => ("complete", None),
+                LoopState::Failed(e) => ("failed", Some(e.clone())),
+                LoopState::Stopped => ("stopped", None),
+                _ => ("incomplete", None),
+            };
+            Some(WatchLoopSummary {
+                name: s.name.clone(),
+                prd: s.prd_path.clone(),
+                outcome: outcome.to_string(),
+                error,
+                tasks_done: s.tasks_done,
+                tasks_total: s.tasks_total,
+                duration_secs: s.elapsed_secs(),
+            })
+        })
+        .collect()
+}
+
 fn resolve_workdir(workdir: Option<&Path>) -> Result<PathBuf> {
     workdir
         .unwrap_or_else(|| Path::new("."))
@@ -229,6 +463,7 @@ fn build_run_args(
     workdir: &Path,
     loop_status: SharedLoopStatus,
     cancel_flag: &Arc<AtomicBool>,
+    agent_slots: &AgentSlots,
 ) -> RunArgs {
     RunArgs {
         prd: Some(prd.to_path_buf()),
@@ -236,30 +471,442 @@ fn build_run_args(
         agent: watch_args.agent.clone(),
         model: watch_args.model.clone(),
         max_iterations: watch_args.max_iterations,
+        auto_extend: watch_args.auto_extend.clone(),
+        max_prompt_tokens: watch_args.max_prompt_tokens,
+        max_files_changed: watch_args.max_files_changed,
+        max_lines_changed: watch_args.max_lines_changed,
         timeout: watch_args.timeout,
         stall_timeout: watch_args.stall_timeout,
+        tasks: None,
         parse_timeout: 120,
+        parse_retries: 2,
         max_failures: watch_args.max_failures,
+        max_attempts: watch_args.max_attempts,
+        only: vec![],
+        from: None,
+        skip: vec![],
+        interactive: false,
+        // The loops share the workdir: rolling one back would undo the
+        // others' edits.
+        no_rollback: true,
+        plan: false,
+        auto_approve: false,
+        // Each watch loop already runs alongside the others.
+        task_parallel: 1,
         workdir: Some(workdir.to_path_buf()),
+        project: watch_args.project.clone(),
+        allow_path: watch_args.allow_path.clone(),
+        fresh: false,
+        reparse: watch_args.reparse,
+        failure_snapshots: watch_args.failure_snapshots,
+        // Loops share one workdir, so restoring one loop's snapshot would
+        // undo the others' work.
+        snapshots: false,
+        annotate_prd: watch_args.annotate_prd,
+        timestamps: watch_args.timestamps.clone(),
+        tasks_format: watch_args.tasks_format.clone(),
+        // A loop's cleanup would touch files the other loops are still editing.
+        cleanup_pass: false,
+        record: None,
+        replay: None,
+        timeout_warn: watch_args.timeout_warn,
+        kill_grace: watch_args.kill_grace,
+        stop_grace: watch_args.stop_grace,
+        reasoning_effort: watch_args.reasoning_effort.clone(),
+        temperature: watch_args.temperature,
+        parse_reasoning_effort: watch_args.parse_reasoning_effort.clone(),
+        parse_temperature: watch_args.parse_temperature,
         // Git branching is disabled for parallel watch mode (avoids concurrent conflicts).
         // Users who need branching should use `ralph run` per PRD.
         branch: None,
         no_branch: true,
+        on_dirty: "abort".to_string(),
+        sandbox: watch_args.sandbox.clone(),
+        sandbox_allow: watch_args.sandbox_allow.clone(),
+        base: None,
+        rebase: false,
+        max_tokens: watch_args.max_tokens,
+        max_cost_usd: watch_args.max_cost_usd,
         // Never print verbose output in watch mode — logs go to files + TUI buffer
         verbose: false,
         dry_run: false,
         hook_url: watch_args.hook_url.clone(),
         hook_token: watch_args.hook_token.clone(),
+        hook_log_lines: watch_args.hook_log_lines,
         notify: watch_args.notify.clone(),
-        api_url: None,
+        notify_token: watch_args.notify_token.clone(),
+        api_url: watch_args.api_url.clone(),
         api_key: None,
+        api_key_ref: watch_args.api_key_ref.clone(),
+        api_protocol: watch_args.api_protocol.clone(),
+        completion: watch_args.completion.clone(),
+        completion_token: watch_args.completion_token.clone(),
+        min_confidence: watch_args.min_confidence,
+        verify_cmd: watch_args.verify_cmd.clone(),
+        // Served once for all loops by `watch` itself.
+        status_port: None,
         state_name: Some(slug.to_string()),
         loop_status: Some(loop_status),
         cancel_flag: Some(cancel_flag.clone()),
+        // Set by the caller once it knows whether the TUI is up.
+        breaker_timeout: None,
+        agent_slots: Some(agent_slots.clone()),
+        agent_commands: watch_args.agent_commands.clone(),
+        agent_mcp: watch_args.agent_mcp.clone(),
+        output: watch_args.output.clone(),
+        // Loops sharing a repo would all write the same changelog.
+        report: Default::default(),
+        keep_session: watch_args.keep_session,
+        structured_output: watch_args.structured_output,
+        // Formatters could rewrite files other loops are still editing.
+        format_cmd: Vec::new(),
+        formatters: Vec::new(),
+        chaos: None,
+        chaos_config: Default::default(),
+        scheduler: Default::default(),
+        fallback: watch_args.fallback.clone(),
+        repo_context: watch_args.repo_context.clone(),
+    }
+}
+
+/// An unfinished task list left in a loop's state directory by an earlier session.
+#[derive(Debug)]
+struct UnfinishedRun {
+    state_dir: PathBuf,
+    done: usize,
+    total: usize,
+    /// PID of a ralph process still running this loop.
+    live_pid: Option<u32>,
+}
+
+fn unfinished_run(workdir: &Path, slug: &str) -> Option<UnfinishedRun> {
+    let state_dir = workdir.join(format!(".ralph-{slug}"));
+    let (format, tasks_file) = TaskFormat::detect(&state_dir);
+    let raw = std::fs::read_to_string(tasks_file).ok()?;
+    // An unreadable list is the loop's own problem (it restores a backup).
+    let list = format.parse(&raw).ok()?;
+    let done = list
+        .tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Complete)
+        .count();
+    if done == list.tasks.len() {
+        return None;
+    }
+    let live_pid = std::fs::read_to_string(state_dir.join("lock"))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<LockFile>(&raw).ok())
+        .map(|lock| lock.pid)
+        .filter(|&pid| crate::stop::is_pid_alive(pid));
+    Some(UnfinishedRun {
+        state_dir,
+        done,
+        total: list.tasks.len(),
+        live_pid,
+    })
+}
+
+/// Decide, per slug, whether its loop runs (`false` = aborted). Loops whose
+/// state is in use by a live process are always aborted.
+fn resolve_existing_runs(workdir: &Path, slugs: &[String], action: &str) -> Result<Vec<bool>> {
+    let interactive = std::io::stdin().is_terminal();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut keep = Vec::with_capacity(slugs.len());
+
+    for slug in slugs {
+        let Some(run) = unfinished_run(workdir, slug) else {
+            keep.push(true);
+            continue;
+        };
+        let label = format!(
+            ".ralph-{slug}/ has an unfinished run ({}/{} tasks done)",
+            run.done, run.total
+        );
+        if let Some(pid) = run.live_pid {
+            println!("⛔  {label} still running as PID {pid} — skipping this loop");
+            keep.push(false);
+            continue;
+        }
+
+        let choice = match action {
+            "ask" if interactive => loop {
+                print!("❓  {label}. [R]esume, [a]rchive, or a[b]ort? ");
+                std::io::stdout().flush()?;
+                let answer = lines.next().transpose()?.unwrap_or_default();
+                match answer.trim().to_lowercase().as_str() {
+                    "" | "r" | "resume" => break "resume",
+                    "a" | "archive" => break "archive",
+                    "b" | "abort" => break "abort",
+                    _ => continue,
+                }
+            },
+            "ask" => {
+                println!("⚠️   {label} — resuming (not a terminal; see --existing)");
+                "resume"
+            }
+            other => other,
+        };
+
+        match choice {
+            "archive" => {
+                let archived = archive_run(workdir, slug, &run.state_dir)?;
+                println!(
+                    "📦  Archived .ralph-{slug}/ → {}",
+                    archived
+                        .strip_prefix(workdir)
+                        .unwrap_or(&archived)
+                        .display()
+                );
+                keep.push(true);
+            }
+            "abort" => {
+                println!("🛑  {label} — not running this loop");
+                keep.push(false);
+            }
+            _ => {
+                if action != "ask" {
+                    println!("↩️   {label} — resuming");
+                }
+                keep.push(true);
+            }
+        }
+    }
+    Ok(keep)
+}
+
+/// Move a loop's state directory to `.ralph-archive/<slug>-<timestamp>/`.
+fn archive_run(workdir: &Path, slug: &str, state_dir: &Path) -> Result<PathBuf> {
+    let archive_dir = workdir.join(".ralph-archive");
+    std::fs::create_dir_all(&archive_dir).context("Failed to create .ralph-archive/")?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let target = archive_dir.join(format!("{slug}-{stamp}"));
+    std::fs::rename(state_dir, &target)
+        .with_context(|| format!("Failed to archive {}", state_dir.display()))?;
+    Ok(target)
+}
+
+/// Mark every loop past the first `parallel` as queued, with its position.
+fn queue_loops(statuses: &[SharedLoopStatus], parallel: usize) {
+    for status in statuses.iter().skip(parallel) {
+        if let Ok(mut s) = status.lock() {
+            s.state = LoopState::Queued(0);
+        }
+    }
+    renumber_queue(statuses);
+}
+
+/// Move loop `index` out of the queue, resetting its clock so elapsed time
+/// excludes the wait, and shift everyone behind it up a place.
+fn start_loop(statuses: &[SharedLoopStatus], index: usize) {
+    if let Ok(mut s) = statuses[index].lock() {
+        if matches!(s.state, LoopState::Queued(_)) {
+            s.state = LoopState::Starting;
+            s.started_at = std::time::Instant::now();
+            s.push_log(LogKind::Status, "▶ Slot free — starting".to_string());
+        }
+    }
+    renumber_queue(statuses);
+}
+
+fn renumber_queue(statuses: &[SharedLoopStatus]) {
+    let mut position = 0;
+    for status in statuses {
+        let Ok(mut s) = status.lock() else { continue };
+        if let LoopState::Queued(current) = s.state {
+            position += 1;
+            if current != position {
+                s.state = LoopState::Queued(position);
+                s.push_log(
+                    LogKind::Status,
+                    format!("⏳ Queued — waiting for a free slot (#{position})"),
+                );
+            }
+        }
+    }
+}
+
+/// Resolves once the session is cancelled (SIGINT, SIGTERM, or TUI quit).
+async fn cancelled(flag: &AtomicBool) {
+    while !flag.load(Ordering::Relaxed) {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
     }
 }
 
 fn is_tty() -> bool {
-    use std::io::IsTerminal;
     std::io::stdout().is_terminal()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{StateManager, Task, TaskList};
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn seed(workdir: &Path, slug: &str, statuses: &[TaskStatus]) {
+        let state = StateManager::new_named(workdir, slug).expect("create state");
+        let tasks = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| Task {
+                id: format!("T{}", i + 1),
+                title: format!("Task {}", i + 1),
+                priority: i as u32 + 1,
+                status: status.clone(),
+                ..Default::default()
+            })
+            .collect();
+        state
+            .save_tasks(&TaskList {
+                version: 1,
+                prd_path: format!("{slug}.md"),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                tasks,
+            })
+            .expect("save tasks");
+    }
+
+    #[test]
+    fn only_incomplete_task_lists_count_as_unfinished_runs() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path(), "done", &[TaskStatus::Complete]);
+        seed(
+            dir.path(),
+            "half",
+            &[TaskStatus::Complete, TaskStatus::Pending],
+        );
+
+        assert!(unfinished_run(dir.path(), "missing").is_none());
+        assert!(unfinished_run(dir.path(), "done").is_none());
+        let run = unfinished_run(dir.path(), "half").expect("unfinished");
+        assert_eq!((run.done, run.total), (1, 2));
+        assert_eq!(run.live_pid, None);
+    }
+
+    #[test]
+    fn existing_runs_are_archived_or_aborted_per_flag() {
+        let dir = tempdir().expect("create tempdir");
+        seed(dir.path(), "api", &[TaskStatus::Pending]);
+        let slugs = vec!["api".to_string(), "fresh".to_string()];
+
+        let keep = resolve_existing_runs(dir.path(), &slugs, "abort").expect("abort");
+        assert_eq!(keep, vec![false, true]);
+        assert!(dir.path().join(".ralph-api/tasks.json").exists());
+
+        let keep = resolve_existing_runs(dir.path(), &slugs, "archive").expect("archive");
+        assert_eq!(keep, vec![true, true]);
+        assert!(!dir.path().join(".ralph-api").exists());
+        let archived: Vec<_> = std::fs::read_dir(dir.path().join(".ralph-archive"))
+            .expect("read archive")
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].starts_with("api-"));
+    }
+
+    #[test]
+    fn queued_loops_move_up_as_slots_free() {
+        let statuses: Vec<SharedLoopStatus> = (0..4)
+            .map(|i| {
+                Arc::new(std::sync::Mutex::new(LoopStatus::new(
+                    format!("prd{i}"),
+                    format!("prd{i}.md"),
+                    "codex".to_string(),
+                )))
+            })
+            .collect();
+        let states = || -> Vec<LoopState> {
+            statuses
+                .iter()
+                .map(|s| s.lock().expect("lock").state.clone())
+                .collect()
+        };
+
+        queue_loops(&statuses, 2);
+        assert_eq!(
+            states(),
+            [
+                LoopState::Starting,
+                LoopState::Starting,
+                LoopState::Queued(1),
+                LoopState::Queued(2)
+            ]
+        );
+
+        start_loop(&statuses, 2);
+        assert_eq!(states()[2..], [LoopState::Starting, LoopState::Queued(1)]);
+        let last = statuses[3].lock().expect("lock");
+        let logs: Vec<&str> = last.recent_logs.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(
+            logs,
+            [
+                "⏳ Queued — waiting for a free slot (#2)",
+                "⏳ Queued — waiting for a free slot (#1)"
+            ]
+        );
+    }
+
+    #[test]
+    fn service_status_counts_active_loops_and_tasks() {
+        let statuses: Vec<SharedLoopStatus> = [LoopState::Running, LoopState::Complete]
+            .into_iter()
+            .enumerate()
+            .map(|(i, state)| {
+                let mut s = LoopStatus::new(
+                    format!("prd{i}"),
+                    format!("prd{i}.md"),
+                    "codex".to_string(),
+                );
+                s.state = state;
+                s.tasks_done = 2 + i as u32;
+                s.tasks_total = 4;
+                Arc::new(std::sync::Mutex::new(s))
+            })
+            .collect();
+
+        assert_eq!(
+            service_status(&statuses),
+            "1/2 loops active, 5/8 tasks done"
+        );
+    }
+
+    #[test]
+    fn loop_summaries_report_each_outcome() {
+        let statuses: Vec<SharedLoopStatus> = [
+            LoopState::Complete,
+            LoopState::Failed("agent unavailable".to_string()),
+            LoopState::Stopped,
+            LoopState::Running,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, state)| {
+            let mut s = LoopStatus::new(
+                format!("prd{i}"),
+                format!("prd{i}.md"),
+                "codex".to_string(),
+            );
+            s.state = state;
+            s.finished_at = Some(s.started_at + std::time::Duration::from_secs(90));
+            Arc::new(std::sync::Mutex::new(s))
+        })
+        .collect();
+
+        let summaries = loop_summaries(&statuses);
+        let outcomes: Vec<&str> = summaries.iter().map(|l| l.outcome.as_str()).collect();
+        assert_eq!(outcomes, ["complete", "failed", "stopped", "incomplete"]);
+        assert_eq!(summaries[1].error.as_deref(), Some("agent unavailable"));
+        assert_eq!(summaries[0].duration_secs, 90);
+
+        let event = HookEvent::WatchComplete {
+            loops: summaries,
+            total_duration_secs: 120,
+        };
+        let json = serde_json::to_value(&event).expect("serialize");
+        assert_eq!(json["event"], "watch_complete");
+        assert_eq!(json["loops"][2]["name"], "prd2");
+    }
+}
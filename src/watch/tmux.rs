@@ -0,0 +1,201 @@
+//! `ralph watch --tmux` — the same session inside tmux instead of the ratatui
+//! dashboard: a `control` window runs `ralph watch --no-tui` and every loop
+//! gets a window following its logs (`ralph logs <slug> --follow`).
+//!
+//! Because the loops run inside tmux, detaching leaves them going; reattach
+//! with `tmux attach -t <session>`.
+
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+use crate::shell::quote;
+
+/// Create the tmux session for `slugs` and attach to it.
+pub fn launch(workdir: &Path, slugs: &[String]) -> Result<()> {
+    let available = Command::new("tmux")
+        .arg("-V")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    if !available {
+        anyhow::bail!("--tmux needs tmux on PATH");
+    }
+
+    let exe = std::env::current_exe().context("Cannot locate the ralph executable")?;
+    let cwd = std::env::current_dir().context("Cannot resolve the current directory")?;
+    let session = free_session_name();
+    let control = control_command(&exe, std::env::args().skip(1));
+
+    for args in session_commands(&session, &cwd, workdir, &control, &exe, slugs) {
+        let status = Command::new("tmux")
+            .args(&args)
+            .status()
+            .context("Failed to run tmux")?;
+        if !status.success() {
+            anyhow::bail!("tmux {} failed ({status})", args.join(" "));
+        }
+    }
+
+    println!(
+        "🪟  tmux session '{session}' — {} loop window(s) + control",
+        slugs.len()
+    );
+    if !std::io::stdout().is_terminal() {
+        println!("    Attach with: tmux attach -t {session}");
+        return Ok(());
+    }
+    // Inside tmux, attaching would nest sessions; switch the client instead.
+    let verb = if std::env::var_os("TMUX").is_some() {
+        "switch-client"
+    } else {
+        "attach-session"
+    };
+    Command::new("tmux")
+        .args([verb, "-t", &session])
+        .status()
+        .context("Failed to attach to tmux")?;
+    Ok(())
+}
+
+/// `ralph-watch`, or `ralph-watch-N` when earlier sessions are still around.
+fn free_session_name() -> String {
+    let taken = |name: &str| {
+        Command::new("tmux")
+            .args(["has-session", "-t", &format!("={name}")])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    };
+    (1..)
+        .map(|n| match n {
+            1 => "ralph-watch".to_string(),
+            n => format!("ralph-watch-{n}"),
+        })
+        .find(|name| !taken(name))
+        .expect("unbounded range")
+}
+
+/// This invocation, minus `--tmux` and with the dashboard off.
+fn control_command(exe: &Path, args: impl Iterator<Item = String>) -> String {
+    let mut args: Vec<String> = args.filter(|a| a != "--tmux").collect();
+    if !args.iter().any(|a| a == "--no-tui") {
+        args.push("--no-tui".to_string());
+    }
+    std::iter::once(exe.display().to_string())
+        .chain(args)
+        .map(|a| quote(&a))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// tmux invocations that build the session. The control window starts in
+/// `cwd` so relative PRD paths still resolve; log windows wait for their
+/// loop's state directory, since queued loops have not created it yet.
+fn session_commands(
+    session: &str,
+    cwd: &Path,
+    workdir: &Path,
+    control: &str,
+    exe: &Path,
+    slugs: &[String],
+) -> Vec<Vec<String>> {
+    let cwd = cwd.display().to_string();
+    let workdir_str = workdir.display().to_string();
+    let mut commands = vec![
+        vec![
+            "new-session".to_string(),
+            "-d".to_string(),
+            "-s".to_string(),
+            session.to_string(),
+            "-n".to_string(),
+            "control".to_string(),
+            "-c".to_string(),
+            cwd,
+            control.to_string(),
+        ],
+        // Keep the final summary on screen after `ralph watch` exits.
+        vec![
+            "set-option".to_string(),
+            "-w".to_string(),
+            "-t".to_string(),
+            format!("{session}:control"),
+            "remain-on-exit".to_string(),
+            "on".to_string(),
+        ],
+    ];
+    for slug in slugs {
+        let logs_dir = workdir.join(format!(".ralph-{slug}")).join("logs");
+        let follow = format!(
+            "until [ -d {dir} ]; do sleep 1; done; exec {exe} logs {slug} --follow --workdir {workdir}",
+            dir = quote(&logs_dir.display().to_string()),
+            exe = quote(&exe.display().to_string()),
+            slug = quote(slug),
+            workdir = quote(&workdir_str),
+        );
+        commands.push(vec![
+            "new-window".to_string(),
+            "-d".to_string(),
+            "-t".to_string(),
+            format!("{session}:"),
+            "-n".to_string(),
+            slug.clone(),
+            "-c".to_string(),
+            workdir_str.clone(),
+            follow,
+        ]);
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_window_reruns_watch_without_tmux_or_tui() {
+        let args = ["watch", "auth.md", "my prd.md", "--tmux", "--parallel", "2"]
+            .map(String::from)
+            .into_iter();
+        assert_eq!(
+            control_command(Path::new("/usr/bin/ralph"), args),
+            "/usr/bin/ralph watch auth.md 'my prd.md' --parallel 2 --no-tui"
+        );
+
+        let args = ["watch", "a.md", "--no-tui", "--tmux"]
+            .map(String::from)
+            .into_iter();
+        assert_eq!(
+            control_command(Path::new("/usr/bin/ralph"), args),
+            "/usr/bin/ralph watch a.md --no-tui"
+        );
+    }
+
+    #[test]
+    fn session_has_a_control_window_and_one_window_per_loop() {
+        let commands = session_commands(
+            "ralph-watch",
+            Path::new("/home/me"),
+            Path::new("/srv/app"),
+            "ralph watch a.md --no-tui",
+            Path::new("/usr/bin/ralph"),
+            &["auth".to_string(), "api".to_string()],
+        );
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[0][..2], ["new-session", "-d"]);
+        assert_eq!(commands[0].last().unwrap(), "ralph watch a.md --no-tui");
+
+        let auth = &commands[2];
+        assert_eq!(
+            auth[..6],
+            ["new-window", "-d", "-t", "ralph-watch:", "-n", "auth"]
+        );
+        assert_eq!(
+            auth.last().unwrap(),
+            "until [ -d /srv/app/.ralph-auth/logs ]; do sleep 1; done; \
+             exec /usr/bin/ralph logs auth --follow --workdir /srv/app"
+        );
+        assert_eq!(commands[3][5], "api");
+    }
+}
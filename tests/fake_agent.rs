@@ -0,0 +1,174 @@
+//! End-to-end runs of the `ralph` binary against `ralph-fake-agent`.
+//!
+//! Run with `cargo test --features fake-agent`.
+#![cfg(feature = "fake-agent")]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::{tempdir, TempDir};
+
+const TASKS: &str = r#"[[step]]
+stdout = '[{"id": "T1", "title": "Create hello file", "description": "Write hello.txt", "priority": 1, "status": "pending", "depends_on": []}]'
+"#;
+
+/// A project with a PRD and every agent CLI on PATH playing `script`, so
+/// fallbacks never reach a real agent.
+struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    fn new(script: &str) -> Self {
+        let dir = tempdir().expect("create tempdir");
+        for sub in ["bin", "home", "project"] {
+            std::fs::create_dir_all(dir.path().join(sub)).expect("create sandbox dir");
+        }
+        for agent in ["codex", "claude", "gemini", "opencode"] {
+            std::os::unix::fs::symlink(
+                env!("CARGO_BIN_EXE_ralph-fake-agent"),
+                dir.path().join("bin").join(agent),
+            )
+            .expect("link fake agent");
+        }
+        std::fs::write(dir.path().join("script.toml"), script).expect("write script");
+        std::fs::write(
+            dir.path().join("project/prd.md"),
+            "# Hello\n\nWrite hello.txt\n",
+        )
+        .expect("write prd");
+        Self { dir }
+    }
+
+    fn project(&self) -> PathBuf {
+        self.dir.path().join("project")
+    }
+
+    fn ralph(&self, args: &[&str]) -> Output {
+        let path = format!(
+            "{}:{}",
+            self.dir.path().join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        Command::new(env!("CARGO_BIN_EXE_ralph"))
+            .args(args)
+            .current_dir(self.project())
+            .env("PATH", path)
+            .env("HOME", self.dir.path().join("home"))
+            .env("RALPH_FAKE_SCRIPT", self.dir.path().join("script.toml"))
+            .env("RALPH_FAKE_PROMPT_LOG", self.dir.path().join("prompts.log"))
+            .output()
+            .expect("run ralph")
+    }
+
+    fn prompts(&self) -> String {
+        std::fs::read_to_string(self.dir.path().join("prompts.log")).unwrap_or_default()
+    }
+}
+
+fn tasks_json(project: &Path) -> serde_json::Value {
+    let raw = std::fs::read_to_string(project.join(".ralph/tasks.json")).expect("read tasks.json");
+    serde_json::from_str(&raw).expect("parse tasks.json")
+}
+
+#[test]
+fn run_parses_and_completes_with_the_scripted_agent() {
+    let sandbox = Sandbox::new(&format!(
+        "{TASKS}\n[[step]]\nstdout = \"done\\n<promise>COMPLETE</promise>\\n\"\nfiles = {{ \"hello.txt\" = \"hello\\n\" }}\n"
+    ));
+
+    let output = sandbox.ralph(&["run", "prd.md", "--no-branch", "--max-iterations", "3"]);
+    assert!(
+        output.status.success(),
+        "ralph failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let project = sandbox.project();
+    assert_eq!(
+        std::fs::read_to_string(project.join("hello.txt")).expect("read hello.txt"),
+        "hello\n"
+    );
+    assert_eq!(tasks_json(&project)["tasks"][0]["status"], "complete");
+    let prompts = sandbox.prompts();
+    assert!(prompts.contains("=== step 1 ==="));
+    assert!(prompts.contains("=== step 2 ===") && prompts.contains("Create hello file"));
+}
+
+#[test]
+fn failing_agent_fails_the_task_and_keeps_its_output() {
+    let sandbox = Sandbox::new(&format!(
+        "{TASKS}\n[[step]]\nstderr = \"boom\\n\"\nexit_code = 1\n"
+    ));
+
+    sandbox.ralph(&["run", "prd.md", "--no-branch", "--max-iterations", "3"]);
+
+    let project = sandbox.project();
+    assert_eq!(tasks_json(&project)["tasks"][0]["status"], "failed");
+    let log = std::fs::read_to_string(project.join(".ralph/logs/iteration-1-T1.log"))
+        .expect("read iteration log");
+    assert!(log.contains("EXIT CODE: Some(1)"));
+    assert!(log.contains("=== STDERR ===\nboom"));
+}
+
+#[test]
+fn replay_reproduces_a_recorded_run_without_the_agent() {
+    let sandbox = Sandbox::new(&format!(
+        "{TASKS}\n[[step]]\nstdout = \"not yet\\n\"\n\n[[step]]\nstdout = \"<promise>COMPLETE</promise>\\n\"\n"
+    ));
+    let project = sandbox.project();
+    let fixture = sandbox.dir.path().join("fixture");
+    let fixture_arg = fixture.to_string_lossy().to_string();
+
+    let output = sandbox.ralph(&["run", "prd.md", "--no-branch", "--record", &fixture_arg]);
+    assert!(
+        output.status.success(),
+        "record run failed: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(fixture.join("001-T1.stdout").exists());
+    assert!(
+        fixture.join("002-T1.prompt.md").exists(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let recorded_prompts = sandbox.prompts();
+
+    std::fs::remove_dir_all(project.join(".ralph")).expect("reset state");
+    let output = sandbox.ralph(&["run", "prd.md", "--no-branch", "--replay", &fixture_arg]);
+    assert!(
+        output.status.success(),
+        "replay failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(sandbox.prompts(), recorded_prompts, "replay ran the agent");
+    assert_eq!(tasks_json(&project)["tasks"][0]["status"], "complete");
+    let progress = std::fs::read_to_string(project.join(".ralph/progress.md")).expect("progress");
+    assert!(!progress.contains("Replay diverged"), "{progress}");
+}
+
+#[test]
+fn task_checklist_skips_the_parse_agent() {
+    let sandbox = Sandbox::new("[[step]]\nstdout = \"<promise>COMPLETE</promise>\\n\"\n");
+    let project = sandbox.project();
+    std::fs::write(
+        project.join("tasks.md"),
+        "- [x] Scaffold\n- [ ] Write hello.txt (deps: T1)\n",
+    )
+    .expect("write checklist");
+
+    let output = sandbox.ralph(&["run", "--tasks", "tasks.md", "--no-branch"]);
+    assert!(
+        output.status.success(),
+        "ralph failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let tasks = tasks_json(&project);
+    assert_eq!(tasks["tasks"][1]["depends_on"][0], "T1");
+    assert_eq!(tasks["tasks"][1]["status"], "complete");
+    // The only agent call was the implementation iteration.
+    let prompts = sandbox.prompts();
+    assert!(prompts.contains("Write hello.txt"));
+    assert!(!prompts.contains("=== step 2 ==="), "{prompts}");
+}